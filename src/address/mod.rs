@@ -0,0 +1,225 @@
+use crate::arith::va_to_rva;
+use crate::port_exe::PortExe;
+use crate::ImageLayout;
+use std::fmt;
+use std::ops::Range;
+
+/// Address relative to the image base
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RelativeVirtualAddress(u32);
+
+impl RelativeVirtualAddress {
+    pub const fn new(rva: u32) -> Self {
+        Self(rva)
+    }
+
+    pub const fn value(self) -> u32 {
+        self.0
+    }
+}
+
+impl From<u32> for RelativeVirtualAddress {
+    fn from(rva: u32) -> Self {
+        Self(rva)
+    }
+}
+
+impl fmt::Display for RelativeVirtualAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&format!("{:#x}", self.0))
+    }
+}
+
+/// Address in the address space of a process the image is loaded into
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VirtualAddress(u64);
+
+impl VirtualAddress {
+    pub const fn new(va: u64) -> Self {
+        Self(va)
+    }
+
+    pub const fn value(self) -> u64 {
+        self.0
+    }
+}
+
+impl From<u64> for VirtualAddress {
+    fn from(va: u64) -> Self {
+        Self(va)
+    }
+}
+
+impl fmt::Display for VirtualAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&format!("{:#x}", self.0))
+    }
+}
+
+/// Where an RVA lands, see [`AddressResolver::locate`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AddressLocation {
+    /// Within the headers, which are mapped at RVA 0
+    Headers { offset: usize },
+    /// Within the raw data of the section at this index of the section table
+    Section { section: usize, offset: usize },
+    /// Within the section at this index, past its raw data, such as `.bss`, which the
+    /// loader fills with zeros
+    Virtual { section: usize },
+    /// Outside the headers and every section
+    Unmapped,
+}
+
+impl AddressLocation {
+    /// File offset of the address, if it has file data
+    pub fn offset(self) -> Option<usize> {
+        match self {
+            Self::Headers { offset } | Self::Section { offset, .. } => Some(offset),
+            Self::Virtual { .. } | Self::Unmapped => None,
+        }
+    }
+}
+
+/// Section as the resolver sees it
+#[derive(Debug, Clone)]
+struct Mapping {
+    /// RVAs the section occupies
+    virtual_range: Range<u32>,
+    /// Offset of the section's data in the buffer
+    offset: usize,
+    /// Bytes of data the buffer holds for the section, at most its virtual size
+    length: u32,
+}
+
+/// Translates between RVAs, file offsets and virtual addresses of one image, using
+/// the section table it was built from.
+///
+/// Sections map `VirtualSize` bytes, or `SizeOfRawData` when that is zero, at
+/// `VirtualAddress`; the first `SizeOfRawData` of them come from the file and the rest
+/// are zeros. Raw data past the virtual size is never mapped. The headers are mapped at
+/// RVA 0 up to `SizeOfHeaders`. For a mapped image every RVA is its own offset.
+#[derive(Debug, Clone)]
+pub struct AddressResolver {
+    image_base: u64,
+    size_of_headers: u32,
+    data_length: usize,
+    layout: ImageLayout,
+    sections: Vec<Mapping>,
+}
+
+impl AddressResolver {
+    pub fn new(pe: &PortExe) -> Self {
+        let data_length = pe.data().len();
+        let sections = pe
+            .section_headers()
+            .iter()
+            .map(|section| {
+                let virtual_address = section.virtual_address().into_value();
+                let size_of_raw_data = section.size_of_raw_data().into_value();
+                let virtual_size = match section.virtual_size().into_value() {
+                    0 => size_of_raw_data,
+                    virtual_size => virtual_size,
+                };
+                let end = virtual_address.saturating_add(virtual_size);
+                match pe.layout() {
+                    ImageLayout::File => Mapping {
+                        virtual_range: virtual_address..end,
+                        offset: section.pointer_to_raw_data().into_value() as usize,
+                        length: size_of_raw_data.min(virtual_size),
+                    },
+                    ImageLayout::Mapped => Mapping {
+                        virtual_range: virtual_address..end,
+                        offset: virtual_address as usize,
+                        length: virtual_size,
+                    },
+                }
+            })
+            .collect();
+        let header = pe.optional_header();
+        Self {
+            image_base: header.map_or(0, |header| header.image_base()),
+            size_of_headers: header.map_or(0, |header| header.size_of_headers()),
+            data_length,
+            layout: pe.layout(),
+            sections,
+        }
+    }
+
+    pub fn image_base(&self) -> u64 {
+        self.image_base
+    }
+
+    /// Headers, section and file offset `rva` falls in. Data cut off by the end of the
+    /// file counts as virtual.
+    pub fn locate(&self, rva: RelativeVirtualAddress) -> AddressLocation {
+        let rva = rva.value();
+        for (index, section) in self.sections.iter().enumerate() {
+            if !section.virtual_range.contains(&rva) {
+                continue;
+            }
+            let delta = rva - section.virtual_range.start;
+            let offset = section.offset.checked_add(delta as usize);
+            return match offset {
+                Some(offset) if delta < section.length && offset < self.data_length => {
+                    AddressLocation::Section {
+                        section: index,
+                        offset,
+                    }
+                }
+                _ => AddressLocation::Virtual { section: index },
+            };
+        }
+        if rva < self.size_of_headers && (rva as usize) < self.data_length {
+            AddressLocation::Headers {
+                offset: rva as usize,
+            }
+        } else {
+            AddressLocation::Unmapped
+        }
+    }
+
+    /// File offset of the byte at `rva`, if it has one
+    pub fn rva_to_offset(&self, rva: RelativeVirtualAddress) -> Option<usize> {
+        self.locate(rva).offset()
+    }
+
+    /// RVA the byte at file offset `offset` is mapped at, if the loader maps it
+    pub fn offset_to_rva(&self, offset: usize) -> Option<RelativeVirtualAddress> {
+        if offset >= self.data_length {
+            return None;
+        }
+        if self.layout.is_mapped() {
+            return u32::try_from(offset).ok().map(RelativeVirtualAddress);
+        }
+        let rva = self.sections.iter().find_map(|section| {
+            let delta = offset.checked_sub(section.offset)?;
+            (delta < section.length as usize)
+                .then(|| section.virtual_range.start.checked_add(delta as u32))
+                .flatten()
+        });
+        match rva {
+            Some(rva) => Some(RelativeVirtualAddress(rva)),
+            None => (offset < self.size_of_headers as usize)
+                .then(|| RelativeVirtualAddress(offset as u32)),
+        }
+    }
+
+    /// Virtual address of `rva` when the image is loaded at its preferred base
+    pub fn rva_to_va(&self, rva: RelativeVirtualAddress) -> VirtualAddress {
+        VirtualAddress(self.image_base.wrapping_add(rva.value() as u64))
+    }
+
+    /// RVA of `va` when the image is loaded at its preferred base, if it is within the
+    /// 32-bit range an image can span
+    pub fn va_to_rva(&self, va: VirtualAddress) -> Option<RelativeVirtualAddress> {
+        va_to_rva(va.value(), self.image_base).map(RelativeVirtualAddress)
+    }
+}
+
+impl PortExe {
+    /// Resolver for the addresses of this image, see [`AddressResolver`].
+    pub fn address_resolver(&self) -> AddressResolver {
+        AddressResolver::new(self)
+    }
+}
@@ -0,0 +1,342 @@
+use crate::digest::{md5, to_hex};
+use crate::endian::le_u32;
+use crate::messages::Message;
+use crate::pe_string::PeString;
+use crate::port_exe::PortExe;
+use crate::section_header::SectionHeaderWrapper;
+use std::fmt;
+
+/// Shannon entropy of `data` in bits per byte, from 0.0 to 8.0
+pub fn entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+
+    let mut counts = [0u64; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .fold(0.0, |sum, term| sum + term)
+}
+
+/// Ratio of compressed to raw size of `data` at the fastest deflate level.
+///
+/// Values close to (or above) 1.0 mean the data is already compressed or encrypted.
+/// Returns `None` for empty input.
+#[cfg(feature = "compression")]
+pub fn compression_ratio(data: &[u8]) -> Option<f64> {
+    if data.is_empty() {
+        return None;
+    }
+    let compressed = miniz_oxide::deflate::compress_to_vec(data, 1);
+    Some(compressed.len() as f64 / data.len() as f64)
+}
+
+/// Compression ratio is only computed with the `compression` feature enabled.
+#[cfg(not(feature = "compression"))]
+pub fn compression_ratio(_data: &[u8]) -> Option<f64> {
+    None
+}
+
+#[derive(Debug)]
+pub struct SectionCompression {
+    name: PeString,
+    raw_size: usize,
+    entropy: f64,
+    compression_ratio: Option<f64>,
+}
+
+impl SectionCompression {
+    pub fn name(&self) -> &PeString {
+        &self.name
+    }
+
+    /// Number of raw bytes the figures were computed over
+    pub fn raw_size(&self) -> usize {
+        self.raw_size
+    }
+
+    pub fn entropy(&self) -> f64 {
+        self.entropy
+    }
+
+    /// Compressed to raw size ratio, `None` for empty sections or without the `compression` feature
+    pub fn compression_ratio(&self) -> Option<f64> {
+        self.compression_ratio
+    }
+}
+
+/// Computes entropy and compression ratio of every section's raw data.
+pub fn section_compression(pe: &PortExe) -> Vec<SectionCompression> {
+    pe.section_headers()
+        .iter()
+        .map(|section| {
+            let data = pe.section_data(section);
+            SectionCompression {
+                name: section.name().into_value(),
+                raw_size: data.len(),
+                entropy: entropy(data),
+                compression_ratio: compression_ratio(data),
+            }
+        })
+        .collect()
+}
+
+/// Column of a section table report.
+///
+/// Each value is computed only when [`PortExe::section_column`] is asked for it, so
+/// reports that leave out [`SectionColumn::reads_data`] columns never touch section data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SectionColumn {
+    /// Name, with long object file names resolved through the string table
+    Name,
+    VirtualAddress,
+    VirtualSize,
+    /// `PointerToRawData`
+    RawAddress,
+    /// `SizeOfRawData`
+    RawSize,
+    /// Memory protection as `rwx`, with `-` for each missing permission
+    Flags,
+    /// Raw `Characteristics` value
+    Characteristics,
+    /// Shannon entropy of the raw data
+    Entropy,
+    /// MD5 of the raw data
+    Md5,
+}
+
+/// Columns of a section table report that only read the section headers
+pub const DEFAULT_SECTION_COLUMNS: [SectionColumn; 6] = [
+    SectionColumn::Name,
+    SectionColumn::VirtualAddress,
+    SectionColumn::VirtualSize,
+    SectionColumn::RawAddress,
+    SectionColumn::RawSize,
+    SectionColumn::Flags,
+];
+
+impl SectionColumn {
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::Name => "Name",
+            Self::VirtualAddress => "VirtAddr",
+            Self::VirtualSize => "VirtSize",
+            Self::RawAddress => "RawAddr",
+            Self::RawSize => "RawSize",
+            Self::Flags => "Flags",
+            Self::Characteristics => "Characteristics",
+            Self::Entropy => "Entropy",
+            Self::Md5 => "MD5",
+        }
+    }
+
+    /// Whether the column is computed from the section data rather than its header
+    pub fn reads_data(self) -> bool {
+        matches!(self, Self::Entropy | Self::Md5)
+    }
+}
+
+impl PortExe {
+    /// Value of `column` for `section`, formatted for a report.
+    pub fn section_column(&self, section: &SectionHeaderWrapper, column: SectionColumn) -> String {
+        match column {
+            SectionColumn::Name => self.section_name(section).to_string(),
+            SectionColumn::VirtualAddress => {
+                format!("{:#010x}", section.virtual_address().into_value())
+            }
+            SectionColumn::VirtualSize => format!("{:#x}", section.virtual_size().into_value()),
+            SectionColumn::RawAddress => {
+                format!("{:#010x}", section.pointer_to_raw_data().into_value())
+            }
+            SectionColumn::RawSize => format!("{:#x}", section.size_of_raw_data().into_value()),
+            SectionColumn::Flags => {
+                let characteristics = section.characteristics();
+                let flags = characteristics.value();
+                [
+                    (flags.mem_read(), 'r'),
+                    (flags.mem_write(), 'w'),
+                    (flags.mem_execute(), 'x'),
+                ]
+                .iter()
+                .map(|&(set, flag)| if set { flag } else { '-' })
+                .collect()
+            }
+            SectionColumn::Characteristics => {
+                format!("{:#010x}", le_u32(section.characteristics().raw_bytes()))
+            }
+            SectionColumn::Entropy => format!("{:.2}", entropy(self.section_data(section))),
+            SectionColumn::Md5 => to_hex(&md5(self.section_data(section))),
+        }
+    }
+}
+
+/// Factor by which the virtual size of a section holding the entry point must exceed
+/// its raw size to be reported, as packers reserve room to unpack code into
+pub const INFLATED_SECTION_RATIO: u32 = 8;
+
+/// Way the entry point departs from where linkers put it, see
+/// [`PortExe::entry_point_anomalies`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EntryPointAnomaly {
+    /// `AddressOfEntryPoint` is zero in an executable, so execution starts at the DOS
+    /// header
+    Zero,
+    /// The entry point is in the headers, below the first section
+    InHeaders { rva: u32, size_of_headers: u32 },
+    /// The entry point is at or past `SizeOfImage`
+    BeyondImage { rva: u32, size_of_image: u32 },
+    /// The entry point is inside the image but in no section
+    OutsideSections { rva: u32 },
+    /// The section holding the entry point is neither executable nor marked as code
+    NonExecutable { rva: u32, section: PeString },
+    /// The section holding the entry point maps far more memory than the file holds
+    InflatedSection {
+        section: PeString,
+        virtual_size: u32,
+        raw_size: u32,
+    },
+    /// The entry point is past the raw data of its section, in memory the loader fills
+    /// with zeros
+    InZeroFill {
+        rva: u32,
+        section: PeString,
+        raw_size: u32,
+    },
+}
+
+impl EntryPointAnomaly {
+    /// Catalog message describing the anomaly
+    pub fn message(&self) -> Message {
+        let hex = |value: &u32| format!("{:#x}", value);
+        let name = |section: &PeString| section.to_string_lossy().into_owned();
+        match self {
+            Self::Zero => Message::new("entry-point-zero", Vec::new()),
+            Self::InHeaders {
+                rva,
+                size_of_headers,
+            } => Message::new(
+                "entry-point-in-headers",
+                vec![hex(rva), hex(size_of_headers)],
+            ),
+            Self::BeyondImage { rva, size_of_image } => Message::new(
+                "entry-point-beyond-image",
+                vec![hex(rva), hex(size_of_image)],
+            ),
+            Self::OutsideSections { rva } => {
+                Message::new("entry-point-outside-sections", vec![hex(rva)])
+            }
+            Self::NonExecutable { rva, section } => {
+                Message::new("entry-point-not-executable", vec![hex(rva), name(section)])
+            }
+            Self::InflatedSection {
+                section,
+                virtual_size,
+                raw_size,
+            } => Message::new(
+                "entry-point-inflated-section",
+                vec![name(section), hex(virtual_size), hex(raw_size)],
+            ),
+            Self::InZeroFill {
+                rva,
+                section,
+                raw_size,
+            } => Message::new(
+                "entry-point-in-zero-fill",
+                vec![hex(rva), name(section), hex(raw_size)],
+            ),
+        }
+    }
+}
+
+impl fmt::Display for EntryPointAnomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&self.message().english())
+    }
+}
+
+impl PortExe {
+    /// Ways the entry point departs from where linkers put it, as packers and loader
+    /// tricks leave them: at RVA 0 of an executable, in the headers, past
+    /// `SizeOfImage`, between sections, in a section that is not executable or whose
+    /// virtual size dwarfs its raw size, or past the file data of its section.
+    ///
+    /// DLLs without an entry point and object files have none.
+    pub fn entry_point_anomalies(&self) -> Vec<EntryPointAnomaly> {
+        let header = match self.optional_header() {
+            Some(header) => header,
+            None => return Vec::new(),
+        };
+        let rva = header.address_of_entry_point();
+        if rva == 0 {
+            let dll = self
+                .file_header()
+                .characteristics()
+                .value()
+                .dynamic_link_library();
+            return if dll {
+                Vec::new()
+            } else {
+                vec![EntryPointAnomaly::Zero]
+            };
+        }
+        let size_of_image = header.size_of_image();
+        if rva >= size_of_image {
+            return vec![EntryPointAnomaly::BeyondImage { rva, size_of_image }];
+        }
+        let first_section = self
+            .section_headers()
+            .iter()
+            .map(|section| section.virtual_address().into_value())
+            .min()
+            .unwrap_or(size_of_image);
+        let section = match self.section_at_rva(rva) {
+            Some(section) => section,
+            None if rva < first_section => {
+                return vec![EntryPointAnomaly::InHeaders {
+                    rva,
+                    size_of_headers: header.size_of_headers(),
+                }]
+            }
+            None => return vec![EntryPointAnomaly::OutsideSections { rva }],
+        };
+        let name = self.section_name(section);
+        let characteristics = section.characteristics();
+        let flags = characteristics.value();
+        let virtual_size = section.virtual_size().into_value();
+        let raw_size = section.size_of_raw_data().into_value();
+        let mut anomalies = Vec::new();
+        if !flags.mem_execute() && !flags.cnt_code() {
+            anomalies.push(EntryPointAnomaly::NonExecutable {
+                rva,
+                section: name.clone(),
+            });
+        }
+        if virtual_size / INFLATED_SECTION_RATIO > raw_size {
+            anomalies.push(EntryPointAnomaly::InflatedSection {
+                section: name.clone(),
+                virtual_size,
+                raw_size,
+            });
+        }
+        if rva - section.virtual_address().into_value() >= raw_size {
+            anomalies.push(EntryPointAnomaly::InZeroFill {
+                rva,
+                section: name,
+                raw_size,
+            });
+        }
+        anomalies
+    }
+}
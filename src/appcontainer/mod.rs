@@ -0,0 +1,178 @@
+use crate::module_key;
+use crate::port_exe::PortExe;
+use crate::search::{ImportMatch, ImportQuery, MatchMode};
+use std::fmt;
+use std::io;
+
+/// API set contracts of the Windows core, the import surface store apps link against
+const CORE_API_SET_PREFIX: &str = "api-ms-win-core-";
+/// API set contracts of WinRT activation and strings
+const WINRT_API_SET_PREFIX: &str = "api-ms-win-core-winrt-";
+/// Functions that activate Windows Runtime classes
+const WINRT_ACTIVATION: [&str; 3] = [
+    "RoInitialize",
+    "RoGetActivationFactory",
+    "RoActivateInstance",
+];
+/// Stem of the Visual C++ runtimes built for the app platform, such as `vcruntime140_app`
+const APP_CRT_SUFFIX: &str = "_app";
+const APP_CRT_PREFIXES: [&str; 3] = ["vcruntime", "msvcp", "vccorlib"];
+
+/// Sign that an image was built for the UWP or MSIX app platform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum StoreAppTrait {
+    /// `IMAGE_DLLCHARACTERISTICS_APPCONTAINER`, which store apps must set
+    AppContainer,
+    /// Every DLL is an `api-ms-win-core-*` contract, as the umbrella libraries link
+    CoreApiSetsOnly,
+    /// Imports that activate Windows Runtime classes
+    WinRtActivation,
+    /// The `_app` flavour of the Visual C++ runtime
+    AppCrt,
+}
+
+impl fmt::Display for StoreAppTrait {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::AppContainer => "AppContainer",
+            Self::CoreApiSetsOnly => "core API sets only",
+            Self::WinRtActivation => "WinRT activation",
+            Self::AppCrt => "app CRT",
+        })
+    }
+}
+
+/// Import surface store apps may use, one [`ImportQuery`] per permitted DLL or function
+#[derive(Debug, Clone, Default)]
+pub struct ApiAllowlist {
+    queries: Vec<ImportQuery>,
+}
+
+impl ApiAllowlist {
+    pub fn new(queries: Vec<ImportQuery>) -> Self {
+        Self { queries }
+    }
+
+    /// Parses one `dll`, `dll!name` or `dll!#ordinal` per line, ignoring blank lines and
+    /// lines starting with `#`. Names compare ignoring ASCII case.
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let queries = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| ImportQuery::parse(line, MatchMode::IgnoreCase))
+            .collect::<io::Result<_>>()?;
+        Ok(Self { queries })
+    }
+
+    pub fn queries(&self) -> &[ImportQuery] {
+        &self.queries
+    }
+}
+
+/// Result of [`PortExe::store_app_report`]
+#[derive(Debug, Clone, Default)]
+pub struct StoreAppReport {
+    traits: Vec<StoreAppTrait>,
+    api_sets: Vec<String>,
+    disallowed: Vec<ImportMatch>,
+}
+
+impl StoreAppReport {
+    pub fn traits(&self) -> &[StoreAppTrait] {
+        &self.traits
+    }
+
+    /// Returns `true` if the image carries any sign of targeting the app platform.
+    pub fn targets_app_platform(&self) -> bool {
+        !self.traits.is_empty()
+    }
+
+    /// Imported API set contracts, as the import directory spells them
+    pub fn api_sets(&self) -> &[String] {
+        &self.api_sets
+    }
+
+    /// Imports no query of the allowlist accepts, in import table order
+    pub fn disallowed(&self) -> &[ImportMatch] {
+        &self.disallowed
+    }
+}
+
+impl PortExe {
+    /// Reports the traits of an image built for the UWP or MSIX app platform and, given
+    /// an allowlist, the imports outside the API surface approved for store apps.
+    pub fn store_app_report(&self, allowlist: Option<&ApiAllowlist>) -> io::Result<StoreAppReport> {
+        let mut report = StoreAppReport::default();
+        if self
+            .optional_header()
+            .map_or(false, |header| header.dll_characteristics().appcontainer())
+        {
+            report.traits.push(StoreAppTrait::AppContainer);
+        }
+
+        let descriptors = self.all_imports()?;
+        let is_api_set = |dll: &str| {
+            let dll = dll.to_ascii_lowercase();
+            dll.starts_with("api-ms-win-") || dll.starts_with("ext-ms-")
+        };
+        report.api_sets = descriptors
+            .iter()
+            .map(|descriptor| descriptor.dll_name())
+            .filter(|dll| is_api_set(dll))
+            .map(str::to_string)
+            .collect();
+        if !descriptors.is_empty()
+            && descriptors.iter().all(|descriptor| {
+                descriptor
+                    .dll_name()
+                    .to_ascii_lowercase()
+                    .starts_with(CORE_API_SET_PREFIX)
+            })
+        {
+            report.traits.push(StoreAppTrait::CoreApiSetsOnly);
+        }
+        let activates_winrt = descriptors.iter().any(|descriptor| {
+            descriptor
+                .dll_name()
+                .to_ascii_lowercase()
+                .starts_with(WINRT_API_SET_PREFIX)
+                || descriptor.imports().iter().any(|import| {
+                    import.name().map_or(false, |name| {
+                        WINRT_ACTIVATION.iter().any(|function| name == *function)
+                    })
+                })
+        });
+        if activates_winrt {
+            report.traits.push(StoreAppTrait::WinRtActivation);
+        }
+        let app_crt = descriptors.iter().any(|descriptor| {
+            let key = module_key(descriptor.dll_name());
+            key.ends_with(APP_CRT_SUFFIX)
+                && APP_CRT_PREFIXES
+                    .iter()
+                    .any(|prefix| key.starts_with(prefix))
+        });
+        if app_crt {
+            report.traits.push(StoreAppTrait::AppCrt);
+        }
+
+        if let Some(allowlist) = allowlist {
+            for descriptor in &descriptors {
+                for import in descriptor.imports() {
+                    let allowed = allowlist
+                        .queries
+                        .iter()
+                        .any(|query| query.matches(descriptor.dll_name(), import));
+                    if !allowed {
+                        report
+                            .disallowed
+                            .push(ImportMatch::new(descriptor.dll_name(), import));
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
+}
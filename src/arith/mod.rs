@@ -0,0 +1,179 @@
+use std::ops::Range;
+
+/// Rounds `value` up to the next multiple of `alignment`.
+///
+/// An alignment of zero leaves the value unchanged. Returns `None` if the result does not fit.
+pub fn align_up(value: u64, alignment: u64) -> Option<u64> {
+    if alignment == 0 {
+        return Some(value);
+    }
+    match value % alignment {
+        0 => Some(value),
+        remainder => value.checked_add(alignment - remainder),
+    }
+}
+
+/// Rounds `value` down to a multiple of `alignment`; an alignment of zero leaves it unchanged.
+// Counterpart of `align_up` no parser needs yet, kept for its property tests
+#[allow(dead_code)]
+pub fn align_down(value: u64, alignment: u64) -> u64 {
+    if alignment == 0 {
+        value
+    } else {
+        value - value % alignment
+    }
+}
+
+/// `value` is a multiple of `alignment`; everything is aligned to zero.
+pub fn is_aligned(value: u64, alignment: u64) -> bool {
+    alignment == 0 || value % alignment == 0
+}
+
+/// `offset..offset + size`, if the range ends within `limit`
+pub fn checked_range(offset: usize, size: usize, limit: usize) -> Option<Range<usize>> {
+    let end = offset.checked_add(size)?;
+    (end <= limit).then(|| offset..end)
+}
+
+/// Range of `count` entries of `entry_size` bytes starting at `offset`, if it ends within `limit`
+pub fn table_range(
+    offset: usize,
+    count: usize,
+    entry_size: usize,
+    limit: usize,
+) -> Option<Range<usize>> {
+    checked_range(offset, count.checked_mul(entry_size)?, limit)
+}
+
+/// `size` bytes of `data` starting at `offset`, if all of them are present
+pub fn slice_at(data: &[u8], offset: usize, size: usize) -> Option<&[u8]> {
+    data.get(checked_range(offset, size, data.len())?)
+}
+
+/// `count` entries of `entry_size` bytes of `data` starting at `offset`, if all are present
+pub fn table_at(data: &[u8], offset: usize, count: usize, entry_size: usize) -> Option<&[u8]> {
+    data.get(table_range(offset, count, entry_size, data.len())?)
+}
+
+/// `rva + delta`, if it stays within the 32-bit address space of an image
+pub fn rva_add(rva: u32, delta: u32) -> Option<u32> {
+    rva.checked_add(delta)
+}
+
+/// Relative address of the virtual address `va` in an image loaded at `image_base`
+pub fn va_to_rva(va: u64, image_base: u64) -> Option<u32> {
+    let rva = va.checked_sub(image_base)?;
+    (rva <= u32::MAX as u64).then(|| rva as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn align_up_is_smallest_aligned_value_not_below(value: u64, shift in 0u32..32) {
+            let alignment = 1u64 << shift;
+            match align_up(value, alignment) {
+                Some(aligned) => {
+                    prop_assert!(aligned >= value);
+                    prop_assert!(is_aligned(aligned, alignment));
+                    prop_assert!(aligned - value < alignment);
+                }
+                None => prop_assert!(value > u64::MAX - (alignment - 1)),
+            }
+        }
+
+        #[test]
+        fn align_up_handles_any_alignment(value in 0u64..1 << 48, alignment in 1u64..1 << 16) {
+            let aligned = align_up(value, alignment).unwrap();
+            prop_assert_eq!(aligned % alignment, 0);
+            prop_assert!(aligned >= value && aligned - value < alignment);
+        }
+
+        #[test]
+        fn align_down_is_largest_aligned_value_not_above(value: u64, alignment: u64) {
+            let aligned = align_down(value, alignment);
+            prop_assert!(aligned <= value);
+            prop_assert!(is_aligned(aligned, alignment));
+            if alignment != 0 {
+                prop_assert!(value - aligned < alignment);
+            }
+        }
+
+        #[test]
+        fn checked_range_stays_within_limit(offset: usize, size: usize, limit: usize) {
+            match checked_range(offset, size, limit) {
+                Some(range) => {
+                    prop_assert_eq!(range.start, offset);
+                    prop_assert_eq!(range.end - range.start, size);
+                    prop_assert!(range.end <= limit);
+                }
+                None => prop_assert!(offset.checked_add(size).map_or(true, |end| end > limit)),
+            }
+        }
+
+        #[test]
+        fn table_range_matches_multiplication(
+            offset: usize,
+            count: usize,
+            entry_size in 0usize..64,
+            limit: usize,
+        ) {
+            let expected = count
+                .checked_mul(entry_size)
+                .and_then(|size| checked_range(offset, size, limit));
+            prop_assert_eq!(table_range(offset, count, entry_size, limit), expected);
+        }
+
+        #[test]
+        fn slice_at_never_panics(
+            data in proptest::collection::vec(any::<u8>(), 0..64),
+            offset: usize,
+            size: usize,
+        ) {
+            match slice_at(&data, offset, size) {
+                Some(slice) => {
+                    prop_assert_eq!(slice.len(), size);
+                    prop_assert_eq!(slice, &data[offset..offset + size]);
+                }
+                None => prop_assert!(offset.saturating_add(size) > data.len() || offset.checked_add(size).is_none()),
+            }
+        }
+
+        #[test]
+        fn table_at_never_panics(
+            data in proptest::collection::vec(any::<u8>(), 0..64),
+            offset: usize,
+            count: usize,
+            entry_size: usize,
+        ) {
+            if let Some(table) = table_at(&data, offset, count, entry_size) {
+                prop_assert_eq!(table.len(), count * entry_size);
+            }
+        }
+
+        #[test]
+        fn rva_add_matches_wide_addition(rva: u32, delta: u32) {
+            let wide = rva as u64 + delta as u64;
+            prop_assert_eq!(rva_add(rva, delta).map(u64::from), (wide <= u32::MAX as u64).then(|| wide));
+        }
+
+        #[test]
+        fn va_to_rva_round_trips(image_base: u64, rva: u32) {
+            if let Some(va) = image_base.checked_add(rva as u64) {
+                prop_assert_eq!(va_to_rva(va, image_base), Some(rva));
+            }
+        }
+
+        #[test]
+        fn va_to_rva_rejects_addresses_outside_the_image(va: u64, image_base: u64) {
+            if let Some(rva) = va_to_rva(va, image_base) {
+                prop_assert_eq!(image_base + rva as u64, va);
+            } else {
+                prop_assert!(va < image_base || va - image_base > u32::MAX as u64);
+            }
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use crate::port_exe::PortExe;
+use crate::resource::{ResourceId, RT_MANIFEST};
+use std::fmt;
+use std::io;
+use std::vec;
+
+/// Kind of string or table entry yielded by [`PortExe::artifacts`], with what
+/// identifies it besides its value
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ArtifactKind {
+    /// Section name, with long object file names resolved through the string table
+    SectionName {
+        index: usize,
+    },
+    /// DLL name recorded in the export directory
+    ModuleName,
+    ExportName {
+        ordinal: u32,
+    },
+    /// `DLL.Function` target of a forwarded export
+    Forwarder {
+        ordinal: u32,
+    },
+    ImportDll,
+    ImportName {
+        dll: String,
+    },
+    /// String of an `RT_STRING` table
+    ResourceString {
+        id: u16,
+        language: u16,
+    },
+    /// Entry of the `StringFileInfo` of the version resource, such as `CompanyName`
+    VersionString {
+        key: String,
+    },
+    /// Text of an `RT_MANIFEST` resource
+    ManifestXml,
+    /// PDB file name of the CodeView debug entry
+    PdbPath,
+}
+
+impl fmt::Display for ArtifactKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::SectionName { .. } => "section",
+            Self::ModuleName => "module",
+            Self::ExportName { .. } => "export",
+            Self::Forwarder { .. } => "forwarder",
+            Self::ImportDll => "import-dll",
+            Self::ImportName { .. } => "import",
+            Self::ResourceString { .. } => "string",
+            Self::VersionString { .. } => "version",
+            Self::ManifestXml => "manifest",
+            Self::PdbPath => "pdb",
+        })
+    }
+}
+
+/// String extracted from an image, with where it is stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    kind: ArtifactKind,
+    value: String,
+    rva: Option<u32>,
+    offset: Option<usize>,
+}
+
+impl Artifact {
+    pub fn kind(&self) -> &ArtifactKind {
+        &self.kind
+    }
+
+    /// The string, decoded lossily
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Address of the string, absent for section names and strings outside the image.
+    ///
+    /// Version strings have no address of their own and report that of the version
+    /// resource.
+    pub fn rva(&self) -> Option<u32> {
+        self.rva
+    }
+
+    /// File offset of the string, absent if it is not backed by file data
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+}
+
+/// Extractor of one kind of artifacts
+type Source = fn(&PortExe) -> io::Result<Vec<Artifact>>;
+
+/// Extractors in the order [`PortExe::artifacts`] runs them
+const SOURCES: [Source; 7] = [
+    section_names,
+    exports,
+    imports,
+    resource_strings,
+    version_strings,
+    manifests,
+    pdb_path,
+];
+
+/// Iterator returned by [`PortExe::artifacts`]
+pub struct Artifacts<'a> {
+    pe: &'a PortExe,
+    source: usize,
+    pending: vec::IntoIter<Artifact>,
+}
+
+impl Iterator for Artifacts<'_> {
+    type Item = io::Result<Artifact>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(artifact) = self.pending.next() {
+                return Some(Ok(artifact));
+            }
+            let source = SOURCES.get(self.source)?;
+            self.source += 1;
+            match source(self.pe) {
+                Ok(artifacts) => self.pending = artifacts.into_iter(),
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+}
+
+impl PortExe {
+    /// Every string the other analyses extract, as one stream for indexing and search.
+    ///
+    /// Artifacts come in a fixed order: section names, the export DLL name with exports
+    /// and forwarders in export address table order, import DLLs each followed by their
+    /// functions, `RT_STRING` strings, version strings, manifests and the PDB path. A
+    /// structure that fails to parse yields its error in place of its artifacts, and
+    /// iteration goes on with the next one.
+    pub fn artifacts(&self) -> Artifacts<'_> {
+        Artifacts {
+            pe: self,
+            source: 0,
+            pending: Vec::new().into_iter(),
+        }
+    }
+
+    fn artifact(&self, kind: ArtifactKind, value: String, rva: u32) -> Artifact {
+        Artifact {
+            kind,
+            value,
+            rva: Some(rva),
+            offset: self.rva_to_offset(rva),
+        }
+    }
+}
+
+fn section_names(pe: &PortExe) -> io::Result<Vec<Artifact>> {
+    Ok(pe
+        .section_headers()
+        .iter()
+        .enumerate()
+        .map(|(index, section)| Artifact {
+            kind: ArtifactKind::SectionName { index },
+            value: pe.section_name(section).to_string(),
+            rva: None,
+            offset: Some(section.name().offset() as usize),
+        })
+        .collect())
+}
+
+fn exports(pe: &PortExe) -> io::Result<Vec<Artifact>> {
+    let directory = match pe.export_directory()? {
+        Some(directory) => directory,
+        None => return Ok(Vec::new()),
+    };
+    let mut artifacts = vec![pe.artifact(
+        ArtifactKind::ModuleName,
+        directory.name().to_string(),
+        directory.name_rva(),
+    )];
+    for export in pe.exports()? {
+        let ordinal = export.ordinal();
+        if let (Some(name), Some(rva)) = (export.name(), export.name_rva()) {
+            artifacts.push(pe.artifact(
+                ArtifactKind::ExportName { ordinal },
+                name.to_string(),
+                rva,
+            ));
+        }
+        if let Some(forwarder) = export.forwarder() {
+            artifacts.push(pe.artifact(
+                ArtifactKind::Forwarder { ordinal },
+                forwarder.to_string(),
+                export.rva(),
+            ));
+        }
+    }
+    Ok(artifacts)
+}
+
+fn imports(pe: &PortExe) -> io::Result<Vec<Artifact>> {
+    let mut artifacts = Vec::new();
+    for descriptor in pe.all_imports()? {
+        let dll = descriptor.dll_name().to_string();
+        artifacts.push(pe.artifact(ArtifactKind::ImportDll, dll.clone(), descriptor.name_rva()));
+        for import in descriptor.imports() {
+            if let (Some(name), Some(rva)) = (import.name(), import.hint_name_rva()) {
+                artifacts.push(pe.artifact(
+                    ArtifactKind::ImportName { dll: dll.clone() },
+                    name.to_string(),
+                    rva.wrapping_add(2),
+                ));
+            }
+        }
+    }
+    Ok(artifacts)
+}
+
+fn resource_strings(pe: &PortExe) -> io::Result<Vec<Artifact>> {
+    Ok(pe
+        .string_resources()?
+        .into_iter()
+        .map(|string| {
+            let kind = ArtifactKind::ResourceString {
+                id: string.id(),
+                language: string.language(),
+            };
+            pe.artifact(kind, string.value().to_string(), string.rva())
+        })
+        .collect())
+}
+
+fn version_strings(pe: &PortExe) -> io::Result<Vec<Artifact>> {
+    let (info, rva) = match pe.version_info_at()? {
+        Some(found) => found,
+        None => return Ok(Vec::new()),
+    };
+    Ok(info
+        .strings()
+        .iter()
+        .map(|string| {
+            let kind = ArtifactKind::VersionString {
+                key: string.key().to_string(),
+            };
+            pe.artifact(kind, string.value().to_string(), rva)
+        })
+        .collect())
+}
+
+fn manifests(pe: &PortExe) -> io::Result<Vec<Artifact>> {
+    let mut artifacts = Vec::new();
+    for entry in pe.resources()? {
+        if *entry.type_id() != ResourceId::Id(RT_MANIFEST) {
+            continue;
+        }
+        if let Some(data) = pe.resource_data(&entry) {
+            let text = String::from_utf8_lossy(data);
+            let text = text.trim_start_matches('\u{feff}').to_string();
+            artifacts.push(pe.artifact(ArtifactKind::ManifestXml, text, entry.data_rva()));
+        }
+    }
+    Ok(artifacts)
+}
+
+fn pdb_path(pe: &PortExe) -> io::Result<Vec<Artifact>> {
+    let (entry, start, name) = match pe.codeview_pdb_path()? {
+        Some(found) => found,
+        None => return Ok(Vec::new()),
+    };
+    let rva = entry.address_of_raw_data();
+    Ok(vec![Artifact {
+        kind: ArtifactKind::PdbPath,
+        value: name.to_string(),
+        rva: (rva != 0).then(|| rva.wrapping_add(start as u32)),
+        offset: Some(entry.pointer_to_raw_data() as usize + start),
+    }])
+}
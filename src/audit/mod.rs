@@ -0,0 +1,388 @@
+use crate::checksec::Protection;
+use crate::port_exe::PortExe;
+use crate::rich::RichEntry;
+use crate::version::format_version;
+use std::fmt;
+use std::io;
+
+const SEPARATORS: &[char] = &['\\', '/'];
+/// Directories user profiles live in, on Windows and on the Unix hosts of cross builds
+const PROFILE_DIRECTORIES: [&str; 3] = ["Users", "Documents and Settings", "home"];
+
+/// Properties of one binary that should agree across a product release
+#[derive(Debug, Clone)]
+pub struct ReleaseProfile {
+    name: String,
+    properties: Vec<(&'static str, String)>,
+    pdb_path: Option<String>,
+}
+
+impl ReleaseProfile {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Compared properties and their values, in report order
+    pub fn properties(&self) -> &[(&'static str, String)] {
+        &self.properties
+    }
+
+    /// PDB path of the CodeView debug entry, which names the build machine's directories
+    pub fn pdb_path(&self) -> Option<&str> {
+        self.pdb_path.as_deref()
+    }
+}
+
+/// Value of a property and the files that have it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    value: String,
+    files: Vec<String>,
+}
+
+impl Variant {
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+}
+
+/// Property whose value differs between files of a release
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inconsistency {
+    property: &'static str,
+    variants: Vec<Variant>,
+}
+
+impl Inconsistency {
+    pub fn property(&self) -> &'static str {
+        self.property
+    }
+
+    /// Values found, the most common first
+    pub fn variants(&self) -> &[Variant] {
+        &self.variants
+    }
+}
+
+/// Way a PDB path leaks build machine details or departs from the rest of a release
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PdbPathIssue {
+    /// The path lies in the profile directory of `user`
+    UserDirectory { user: String },
+    /// The path is under `root` rather than `expected`, the build root most of the
+    /// release shares
+    BuildRoot { root: String, expected: String },
+}
+
+impl fmt::Display for PdbPathIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UserDirectory { user } => write!(f, "in the profile of user {}", user),
+            Self::BuildRoot { root, expected } => {
+                write!(f, "built under {} instead of {}", root, expected)
+            }
+        }
+    }
+}
+
+/// PDB path of a release binary that fails the path audit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PdbPathFinding {
+    file: String,
+    path: String,
+    issue: PdbPathIssue,
+}
+
+impl PdbPathFinding {
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn issue(&self) -> &PdbPathIssue {
+        &self.issue
+    }
+}
+
+/// Consolidated result of [`audit_release`]
+#[derive(Debug, Clone, Default)]
+pub struct ReleaseAudit {
+    files: Vec<String>,
+    consistent: Vec<&'static str>,
+    inconsistencies: Vec<Inconsistency>,
+    pdb_paths: Vec<PdbPathFinding>,
+}
+
+impl ReleaseAudit {
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    /// Properties every file agrees on
+    pub fn consistent(&self) -> &[&'static str] {
+        &self.consistent
+    }
+
+    pub fn inconsistencies(&self) -> &[Inconsistency] {
+        &self.inconsistencies
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.inconsistencies.is_empty()
+    }
+
+    /// PDB paths leaking user directories or built under a stray root, see
+    /// [`audit_pdb_paths`]
+    pub fn pdb_paths(&self) -> &[PdbPathFinding] {
+        &self.pdb_paths
+    }
+}
+
+impl PortExe {
+    /// Collects the toolchain, mitigation, version and signer properties
+    /// [`audit_release`] compares.
+    ///
+    /// Mitigations that do not apply to the image, such as SafeSEH on x64, are left out
+    /// so that mixed-architecture releases are not reported for them.
+    pub fn release_profile(&self, name: &str) -> io::Result<ReleaseProfile> {
+        let mut properties = Vec::new();
+        if let Some(header) = self.optional_header() {
+            let (major, minor) = header.linker_version();
+            properties.push(("Linker version", format!("{}.{}", major, minor)));
+        }
+        let rich = self.rich_header();
+        let tool = |entry: Option<&RichEntry>| {
+            entry.map_or_else(
+                || "(no Rich header)".to_string(),
+                |entry| format!("product {:#x} build {}", entry.product_id(), entry.build()),
+            )
+        };
+        properties.push((
+            "Linker build",
+            tool(rich.as_ref().and_then(|rich| rich.linker())),
+        ));
+        properties.push((
+            "Compiler build",
+            tool(rich.as_ref().and_then(|rich| rich.primary_tool())),
+        ));
+
+        let checksec = self.checksec()?;
+        for (property, protection) in [
+            ("ASLR", checksec.aslr()),
+            ("DEP", checksec.dep()),
+            ("CFG", checksec.cfg()),
+            ("SafeSEH", checksec.safe_seh()),
+            ("GS", checksec.gs()),
+            ("CET", checksec.cet()),
+            ("EHCONT", checksec.eh_continuation()),
+            ("XFG", checksec.xfg()),
+            ("HighEntropyVA", checksec.high_entropy_va()),
+        ] {
+            if protection != Protection::NotApplicable {
+                properties.push((property, protection.to_string()));
+            }
+        }
+
+        // A malformed version resource is reported as missing rather than failing the audit
+        let version = self.version_info().ok().flatten();
+        let fixed = |version: Option<[u16; 4]>| {
+            version.map_or_else(|| "(none)".to_string(), format_version)
+        };
+        properties.push((
+            "FileVersion",
+            fixed(version.as_ref().and_then(|info| info.file_version())),
+        ));
+        properties.push((
+            "ProductVersion",
+            fixed(version.as_ref().and_then(|info| info.product_version())),
+        ));
+        for key in ["ProductName", "CompanyName"] {
+            let value = version
+                .as_ref()
+                .and_then(|info| info.string(key))
+                .unwrap_or("(none)");
+            properties.push((key, value.to_string()));
+        }
+
+        let signer = match self.signer() {
+            Ok(Some(signer)) => format!(
+                "{} ({})",
+                signer.common_name().unwrap_or_else(|| signer.subject()),
+                signer.serial_number_hex()
+            ),
+            Ok(None) if self.certificates()?.is_empty() => "(unsigned)".to_string(),
+            Ok(None) | Err(_) => "(undecodable signature)".to_string(),
+        };
+        properties.push(("Signer", signer));
+
+        let pdb_path = self
+            .pdb_path()
+            .ok()
+            .flatten()
+            .map(|path| path.to_string_lossy().into_owned());
+        Ok(ReleaseProfile {
+            name: name.to_string(),
+            properties,
+            pdb_path,
+        })
+    }
+}
+
+/// Compares the profiles of a release's binaries property by property, and audits
+/// their PDB paths.
+pub fn audit_release(profiles: &[ReleaseProfile]) -> ReleaseAudit {
+    let mut audit = ReleaseAudit {
+        files: profiles
+            .iter()
+            .map(|profile| profile.name.clone())
+            .collect(),
+        ..ReleaseAudit::default()
+    };
+    let mut properties: Vec<&'static str> = Vec::new();
+    for profile in profiles {
+        for (property, _) in &profile.properties {
+            if !properties.contains(property) {
+                properties.push(property);
+            }
+        }
+    }
+
+    for property in properties {
+        let mut variants: Vec<Variant> = Vec::new();
+        for profile in profiles {
+            let value = match profile
+                .properties
+                .iter()
+                .find(|(name, _)| *name == property)
+            {
+                Some((_, value)) => value,
+                None => continue,
+            };
+            match variants.iter_mut().find(|variant| variant.value == *value) {
+                Some(variant) => variant.files.push(profile.name.clone()),
+                None => variants.push(Variant {
+                    value: value.clone(),
+                    files: vec![profile.name.clone()],
+                }),
+            }
+        }
+        if variants.len() > 1 {
+            // Stable, so ties keep the order the files were given in
+            variants.sort_by_key(|variant| std::cmp::Reverse(variant.files.len()));
+            audit
+                .inconsistencies
+                .push(Inconsistency { property, variants });
+        } else {
+            audit.consistent.push(property);
+        }
+    }
+
+    let pdb_paths: Vec<(&str, &str)> = profiles
+        .iter()
+        .filter_map(|profile| Some((&profile.name[..], profile.pdb_path.as_deref()?)))
+        .collect();
+    audit.pdb_paths = audit_pdb_paths(&pdb_paths);
+    audit
+}
+
+/// Checks the PDB paths of a release, given as (file, path) pairs, for the "no path
+/// leakage" requirement: paths in a user profile directory such as `C:\Users\<name>`
+/// or `/home/<name>` are reported, and so are the rest of the absolute paths whose build
+/// root, the drive and first directory, differs from the one most of them share.
+pub fn audit_pdb_paths(paths: &[(&str, &str)]) -> Vec<PdbPathFinding> {
+    let finding = |file: &str, path: &str, issue| PdbPathFinding {
+        file: file.to_string(),
+        path: path.to_string(),
+        issue,
+    };
+    let mut findings = Vec::new();
+    // Build roots and how many paths share each, compared case-insensitively
+    let mut roots: Vec<(String, &str, usize)> = Vec::new();
+    for &(file, path) in paths {
+        if let Some(user) = profile_user(path) {
+            let issue = PdbPathIssue::UserDirectory {
+                user: user.to_string(),
+            };
+            findings.push(finding(file, path, issue));
+        } else if let Some(root) = build_root(path) {
+            let key = root_key(root);
+            match roots.iter_mut().find(|(seen, _, _)| *seen == key) {
+                Some((_, _, count)) => *count += 1,
+                None => roots.push((key, root, 1)),
+            }
+        }
+    }
+
+    // The first root seen wins a tie, as max_by_key would pick the last
+    let expected = roots.iter().fold(
+        None,
+        |best: Option<&(String, &str, usize)>, root| match best {
+            Some(best) if best.2 >= root.2 => Some(best),
+            _ => Some(root),
+        },
+    );
+    if let Some((expected_key, expected, _)) = expected {
+        for &(file, path) in paths {
+            let root = match build_root(path) {
+                Some(root) if profile_user(path).is_none() => root,
+                _ => continue,
+            };
+            if root_key(root) != *expected_key {
+                let issue = PdbPathIssue::BuildRoot {
+                    root: root.to_string(),
+                    expected: expected.to_string(),
+                };
+                findings.push(finding(file, path, issue));
+            }
+        }
+    }
+    findings
+}
+
+/// Name of the user whose profile directory `path` lies in
+fn profile_user(path: &str) -> Option<&str> {
+    // A colon second means an ASCII drive letter, so slicing after it is safe
+    let rest = match path.as_bytes() {
+        [_, b':', ..] => &path[2..],
+        _ => path,
+    };
+    let (directory, rest) = rest.strip_prefix(SEPARATORS)?.split_once(SEPARATORS)?;
+    if !PROFILE_DIRECTORIES
+        .iter()
+        .any(|profiles| directory.eq_ignore_ascii_case(profiles))
+    {
+        return None;
+    }
+    let (user, _) = rest.split_once(SEPARATORS)?;
+    (!user.is_empty() && !user.eq_ignore_ascii_case("Public")).then(|| user)
+}
+
+/// Drive, UNC share or Unix root of an absolute `path` with its first directory, such
+/// as `D:\build` for `D:\build\out\app.pdb`
+fn build_root(path: &str) -> Option<&str> {
+    let start = if let Some(unc) = path.strip_prefix("\\\\") {
+        // \\server\share counts as the drive
+        let (server, share) = unc.split_once(SEPARATORS)?;
+        server.len() + 2 + share.find(SEPARATORS)? + 2
+    } else if matches!(path.as_bytes(), [_, b':', b'\\' | b'/', ..]) {
+        3
+    } else if path.starts_with(SEPARATORS) {
+        1
+    } else {
+        return None;
+    };
+    let end = start + path.get(start..)?.find(SEPARATORS)?;
+    Some(&path[..end])
+}
+
+fn root_key(root: &str) -> String {
+    root.to_ascii_lowercase().replace('/', "\\")
+}
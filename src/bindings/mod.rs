@@ -0,0 +1,225 @@
+use crate::demangle::{demangle, Decoration};
+use crate::export::{Export, ExportKind};
+use crate::file_header::Machine;
+use crate::pe_string::PeString;
+use crate::port_exe::PortExe;
+use std::fmt::Write;
+use std::io;
+
+const RUST_KEYWORDS: [&str; 38] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while",
+];
+
+/// Output language of [`PortExe::export_bindings`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BindingLanguage {
+    /// C header with an include guard and `extern "C"` wrapper
+    C,
+    /// Rust `extern` blocks grouped by calling convention
+    Rust,
+}
+
+impl PortExe {
+    /// Generates declarations for the exports of a DLL.
+    ///
+    /// Export tables carry no type information, so every function is declared with an
+    /// unknown return type and, unless the decoration records the size of the arguments,
+    /// unknown parameters. Exports that cannot be declared (C++ symbols, data, ordinal-only
+    /// exports and forwarders) are listed in comments so nothing is silently dropped.
+    pub fn export_bindings(&self, language: BindingLanguage) -> io::Result<String> {
+        let dll = self
+            .export_directory()?
+            .map(|directory| directory.name().to_string())
+            .unwrap_or_default();
+        let exports = self.exports()?;
+        let x86 = self.file_header().machine().into_value() == Machine::Intel386;
+        Ok(match language {
+            BindingLanguage::C => c_header(&dll, &exports, x86),
+            BindingLanguage::Rust => rust_extern(&dll, &exports, x86),
+        })
+    }
+}
+
+fn c_header(dll: &str, exports: &[Export], x86: bool) -> String {
+    let guard: String = dll
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let guard = format!("PEXP_{}_H", guard);
+
+    let mut out = String::new();
+    let _ = writeln!(out, "/* Exports of {}; signatures are not known */", dll);
+    let _ = writeln!(out, "#ifndef {}\n#define {}\n", guard, guard);
+    let _ = writeln!(out, "#include <stdint.h>\n");
+    let _ = writeln!(out, "#ifdef __cplusplus\nextern \"C\" {{\n#endif\n");
+
+    for export in exports {
+        let name = match undeclarable(export) {
+            Some(comment) => {
+                let _ = writeln!(out, "/* {} */", comment);
+                continue;
+            }
+            None => export.name().and_then(PeString::as_str).unwrap_or_default(),
+        };
+        let demangled = demangle(name);
+        let (convention, parameters) = match demangled.decoration() {
+            Decoration::Stdcall { argument_bytes } if x86 => {
+                ("__stdcall ", c_parameters(argument_bytes))
+            }
+            Decoration::Fastcall { argument_bytes } if x86 => {
+                ("__fastcall ", c_parameters(argument_bytes))
+            }
+            Decoration::Vectorcall { .. } => ("__vectorcall ", String::new()),
+            _ => ("", String::new()),
+        };
+        let _ = writeln!(
+            out,
+            "void {}{}({}); /* ordinal {} */",
+            convention,
+            demangled.name(),
+            parameters,
+            export.ordinal()
+        );
+    }
+
+    let _ = writeln!(out, "\n#ifdef __cplusplus\n}}\n#endif\n");
+    let _ = writeln!(out, "#endif /* {} */", guard);
+    out
+}
+
+fn c_parameters(argument_bytes: u32) -> String {
+    match argument_bytes / 4 {
+        0 => "void".to_string(),
+        count => (0..count)
+            .map(|index| format!("uint32_t arg{}", index))
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
+}
+
+fn rust_extern(dll: &str, exports: &[Export], x86: bool) -> String {
+    let library = dll
+        .rsplit_once('.')
+        .map_or(dll, |(stem, _)| stem)
+        .to_string();
+
+    let mut blocks: Vec<(&str, Vec<String>)> = Vec::new();
+    let mut skipped = Vec::new();
+    for export in exports {
+        let name = match undeclarable(export) {
+            Some(comment) => {
+                skipped.push(comment);
+                continue;
+            }
+            None => export.name().and_then(PeString::as_str).unwrap_or_default(),
+        };
+        let demangled = demangle(name);
+        let (abi, parameters) = match demangled.decoration() {
+            Decoration::Stdcall { argument_bytes } if x86 => {
+                ("stdcall", rust_parameters(argument_bytes))
+            }
+            Decoration::Fastcall { argument_bytes } if x86 => {
+                ("fastcall", rust_parameters(argument_bytes))
+            }
+            Decoration::Vectorcall { .. } => {
+                skipped.push(format!(
+                    "{} (ordinal {}): vectorcall is not stable in Rust",
+                    name,
+                    export.ordinal()
+                ));
+                continue;
+            }
+            _ => ("C", String::new()),
+        };
+
+        let identifier = demangled.name();
+        let mut declaration = format!("    /// Ordinal {}\n", export.ordinal());
+        if RUST_KEYWORDS.contains(&identifier) {
+            let _ = writeln!(declaration, "    #[link_name = \"{}\"]", identifier);
+            let _ = writeln!(declaration, "    pub fn {}_({});", identifier, parameters);
+        } else {
+            let _ = writeln!(declaration, "    pub fn {}({});", identifier, parameters);
+        }
+        match blocks.iter_mut().find(|(block_abi, _)| *block_abi == abi) {
+            Some((_, declarations)) => declarations.push(declaration),
+            None => blocks.push((abi, vec![declaration])),
+        }
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(out, "// Exports of {}; signatures are not known", dll);
+    for comment in skipped {
+        let _ = writeln!(out, "// {}", comment);
+    }
+    for (abi, declarations) in blocks {
+        let _ = writeln!(out, "\n#[link(name = \"{}\")]", library);
+        let _ = writeln!(out, "extern \"{}\" {{", abi);
+        out.push_str(&declarations.join("\n"));
+        let _ = writeln!(out, "}}");
+    }
+    out
+}
+
+fn rust_parameters(argument_bytes: u32) -> String {
+    (0..argument_bytes / 4)
+        .map(|index| format!("arg{}: u32", index))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Reason an export cannot be turned into a declaration
+fn undeclarable(export: &Export) -> Option<String> {
+    let ordinal = export.ordinal();
+    if let Some(forwarder) = export.forwarder() {
+        return Some(format!(
+            "{} (ordinal {}) is forwarded to {}",
+            export
+                .name()
+                .map_or_else(|| "unnamed export".to_string(), ToString::to_string),
+            ordinal,
+            forwarder
+        ));
+    }
+    let name = match export.name() {
+        Some(name) => name,
+        None => return Some(format!("ordinal {} is exported without a name", ordinal)),
+    };
+    let name = match name.as_str() {
+        Some(name) => name,
+        None => return Some(format!("{} (ordinal {}) is not valid UTF-8", name, ordinal)),
+    };
+    if export.kind() == ExportKind::Data {
+        return Some(format!(
+            "{} (ordinal {}) is data, not a function",
+            name, ordinal
+        ));
+    }
+    let demangled = demangle(name);
+    if demangled.is_cpp() {
+        return Some(format!(
+            "{} (ordinal {}) is the C++ symbol {}",
+            name,
+            ordinal,
+            demangled.name()
+        ));
+    }
+    let identifier = demangled.name();
+    let valid = identifier
+        .chars()
+        .next()
+        .map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+        && identifier
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    (!valid).then(|| format!("{} (ordinal {}) is not a valid identifier", name, ordinal))
+}
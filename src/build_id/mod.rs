@@ -0,0 +1,211 @@
+use crate::clr::TABLE_MODULE;
+use crate::debug::{
+    CodeViewFormat, CodeViewRecord, IMAGE_DEBUG_TYPE_CODEVIEW, IMAGE_DEBUG_TYPE_REPRO,
+};
+use crate::endian::le_u32;
+use crate::port_exe::PortExe;
+use crate::read_u32;
+use crate::section_header::IMAGE_SCN_CNT_CODE;
+use std::fmt;
+use std::io;
+
+/// Marker the Go linker writes at the start of the text section, before the quoted ID
+const GO_BUILD_ID_PREFIX: &[u8] = b"\xff Go build ID: \"";
+/// Bytes of a code section searched for the Go build ID
+const GO_BUILD_ID_SEARCH: usize = 0x1000;
+/// Column of the module table holding the MVID
+const MODULE_MVID: usize = 2;
+
+/// Toolchain record a [`BuildId`] comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum BuildIdKind {
+    /// GUID and age of an `RSDS` CodeView record, which key the PDB on symbol servers
+    CodeView,
+    /// Module version ID of a managed image, a GUID the compiler makes for every build
+    Mvid,
+    /// ID the Go linker embeds in the text section
+    Go,
+    /// Hash payload of an `IMAGE_DEBUG_TYPE_REPRO` entry
+    Repro,
+}
+
+impl BuildIdKind {
+    /// Prefix of the canonical form
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Self::CodeView => "pdb",
+            Self::Mvid => "mvid",
+            Self::Go => "go",
+            Self::Repro => "repro",
+        }
+    }
+}
+
+impl fmt::Display for BuildIdKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::CodeView => "CodeView",
+            Self::Mvid => "MVID",
+            Self::Go => "Go build ID",
+            Self::Repro => "repro hash",
+        })
+    }
+}
+
+/// Identifier a toolchain gives one build of an image, see [`PortExe::build_ids`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BuildId {
+    kind: BuildIdKind,
+    canonical: String,
+    offset: Option<usize>,
+}
+
+impl BuildId {
+    pub fn kind(&self) -> BuildIdKind {
+        self.kind
+    }
+
+    /// `kind:value`, with the value in a form that does not depend on how the record
+    /// stores it: the GUID and age of a CodeView record as a symbol server key (upper
+    /// case GUID without dashes, then the age in hex), an MVID in registry form, a Go
+    /// build ID as written and a repro hash in lower case hex.
+    pub fn canonical(&self) -> &str {
+        &self.canonical
+    }
+
+    /// Part of the canonical form after the kind
+    pub fn value(&self) -> &str {
+        &self.canonical[self.kind.prefix().len() + 1..]
+    }
+
+    /// File offset of the identifier, if it is stored as is
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    fn new(kind: BuildIdKind, value: &str, offset: Option<usize>) -> Self {
+        Self {
+            kind,
+            canonical: format!("{}:{}", kind.prefix(), value),
+            offset,
+        }
+    }
+}
+
+impl fmt::Display for BuildId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&self.canonical)
+    }
+}
+
+/// `name/key/name`, the path of a file on a symbol server
+pub fn symbol_store_path(name: &str, key: &str) -> String {
+    format!("{}/{}/{}", name, key, name)
+}
+
+impl PortExe {
+    /// Every build identifier of the image, ordered by [`BuildIdKind`].
+    pub fn build_ids(&self) -> io::Result<Vec<BuildId>> {
+        let mut ids = Vec::new();
+        for entry in self.debug_directory()? {
+            let data = match self.debug_data(&entry) {
+                Some(data) => data,
+                None => continue,
+            };
+            let offset = entry.pointer_to_raw_data() as usize;
+            match entry.debug_type() {
+                IMAGE_DEBUG_TYPE_CODEVIEW => {
+                    if let Some(record) = CodeViewRecord::parse(data)
+                        .filter(|record| record.format() == CodeViewFormat::Pdb70)
+                    {
+                        ids.push(BuildId::new(
+                            BuildIdKind::CodeView,
+                            &record.symbol_key(),
+                            Some(offset + 4),
+                        ));
+                    }
+                }
+                IMAGE_DEBUG_TYPE_REPRO => {
+                    // Recent linkers prefix the hash with its length; an empty entry
+                    // means the hash replaced the time stamps instead
+                    let (hash, start) = match read_u32(data, 0) {
+                        Some(length) if length as usize == data.len() - 4 => (&data[4..], 4),
+                        _ => (data, 0),
+                    };
+                    if !hash.is_empty() {
+                        let hex: String = hash.iter().map(|b| format!("{:02x}", b)).collect();
+                        ids.push(BuildId::new(BuildIdKind::Repro, &hex, Some(offset + start)));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(metadata) = self.metadata()? {
+            if let Some(mvid) = metadata
+                .column(TABLE_MODULE, 1, MODULE_MVID)
+                .and_then(|index| metadata.guid(index))
+            {
+                ids.push(BuildId::new(BuildIdKind::Mvid, &mvid.to_string(), None));
+            }
+        }
+        if let Some((offset, id)) = self.go_build_id() {
+            ids.push(BuildId::new(BuildIdKind::Go, &id, Some(offset)));
+        }
+        ids.sort_by_key(|id| id.kind);
+        Ok(ids)
+    }
+
+    /// Identifier an artifact store should key the image by: the first of
+    /// [`PortExe::build_ids`], so the PDB key when there is one.
+    pub fn build_id(&self) -> io::Result<Option<BuildId>> {
+        Ok(self.build_ids()?.into_iter().next())
+    }
+
+    /// Key symbol servers store the image itself under: `TimeDateStamp` as eight hex
+    /// digits followed by `SizeOfImage` in hex, as SymStore writes it. Object files
+    /// have none.
+    pub fn image_symbol_key(&self) -> Option<String> {
+        let header = self.optional_header()?;
+        Some(format!(
+            "{:08X}{:x}",
+            self.file_header().time_date_stamp().into_value(),
+            header.size_of_image()
+        ))
+    }
+
+    /// Path of the image on a symbol server when it is named `name`, see
+    /// [`symbol_store_path`]
+    pub fn image_symbol_path(&self, name: &str) -> Option<String> {
+        Some(symbol_store_path(name, &self.image_symbol_key()?))
+    }
+
+    /// Path of the PDB on a symbol server, keyed by the GUID or signature and the age
+    /// of the first CodeView record and named after the file name of its PDB path
+    pub fn pdb_symbol_path(&self) -> io::Result<Option<String>> {
+        Ok(self
+            .codeview_record()?
+            .and_then(|record| record.symbol_path()))
+    }
+
+    /// File offset and text of the Go build ID at the start of a code section
+    fn go_build_id(&self) -> Option<(usize, String)> {
+        self.section_headers()
+            .iter()
+            .filter(|section| {
+                le_u32(section.characteristics().raw_bytes()) & IMAGE_SCN_CNT_CODE != 0
+            })
+            .find_map(|section| {
+                let data = self.section_data(section);
+                let data = &data[..data.len().min(GO_BUILD_ID_SEARCH)];
+                let start = data
+                    .windows(GO_BUILD_ID_PREFIX.len())
+                    .position(|window| window == GO_BUILD_ID_PREFIX)?
+                    + GO_BUILD_ID_PREFIX.len();
+                let length = data[start..].iter().position(|&b| b == b'"')?;
+                let id = std::str::from_utf8(&data[start..start + length]).ok()?;
+                let offset = section.pointer_to_raw_data().into_value() as usize + start;
+                (!id.is_empty()).then(|| (offset, id.to_string()))
+            })
+    }
+}
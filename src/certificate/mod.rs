@@ -0,0 +1,470 @@
+use crate::arith::slice_at;
+use crate::endian::{utf16_units, ByteOrder};
+use crate::optional_header::IMAGE_DIRECTORY_ENTRY_SECURITY;
+use crate::port_exe::PortExe;
+use crate::{read_u16, read_u32};
+use std::fmt;
+use std::fmt::Write;
+use std::io;
+
+pub const WIN_CERT_REVISION_1_0: u16 = 0x0100;
+pub const WIN_CERT_REVISION_2_0: u16 = 0x0200;
+pub const WIN_CERT_TYPE_X509: u16 = 0x0001;
+/// Authenticode signature: a PKCS#7 `SignedData` structure
+pub const WIN_CERT_TYPE_PKCS_SIGNED_DATA: u16 = 0x0002;
+pub const WIN_CERT_TYPE_TS_STACK_SIGNED: u16 = 0x0004;
+
+/// Size of `dwLength`, `wRevision` and `wCertificateType`
+const WIN_CERTIFICATE_HEADER_SIZE: usize = 8;
+/// Attribute certificate entries have to be followed by padding to this alignment
+const WIN_CERTIFICATE_ALIGNMENT: usize = 8;
+const MAX_CERTIFICATES: usize = 64;
+
+const DER_BOOLEAN: u8 = 0x01;
+const DER_INTEGER: u8 = 0x02;
+const DER_OCTET_STRING: u8 = 0x04;
+const DER_OID: u8 = 0x06;
+const DER_SEQUENCE: u8 = 0x30;
+const DER_SET: u8 = 0x31;
+const DER_CONTEXT_0: u8 = 0xA0;
+const DER_CONTEXT_3: u8 = 0xA3;
+/// `id-ce-extKeyUsage`, 2.5.29.37
+const OID_EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1D, 0x25];
+
+/// `WIN_CERTIFICATE` entry of the attribute certificate table
+#[derive(Debug, Clone)]
+pub struct WinCertificate {
+    offset: usize,
+    revision: u16,
+    certificate_type: u16,
+    data: Vec<u8>,
+}
+
+impl WinCertificate {
+    /// File offset of the entry
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn revision(&self) -> u16 {
+        self.revision
+    }
+
+    /// One of the `WIN_CERT_TYPE_*` constants
+    pub fn certificate_type(&self) -> u16 {
+        self.certificate_type
+    }
+
+    /// `bCertificate`, without the entry header
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Signer of an Authenticode signature, `None` for other certificate types or if the
+    /// PKCS#7 structure cannot be decoded.
+    pub fn signer(&self) -> Option<Signer> {
+        if self.certificate_type != WIN_CERT_TYPE_PKCS_SIGNED_DATA {
+            return None;
+        }
+        signer(&self.data)
+    }
+}
+
+/// Deviation of the attribute certificate table from covering exactly the overlay
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SignatureLayoutIssue {
+    /// The table does not start on an 8-byte boundary
+    UnalignedTable { offset: usize },
+    /// Overlay data between the image and the table. It is hashed, so it is signed,
+    /// but the signature no longer sits alone at the end of the file.
+    DataBeforeTable { offset: usize, size: usize },
+    /// Data after the table, which the Authenticode hash does not cover
+    DataAfterTable { offset: usize, size: usize },
+    /// Bytes inside an entry after its PKCS#7 structure, which the hash does not cover
+    /// either and which verifiers enforcing the padding check reject
+    DataAfterSignedData { offset: usize, size: usize },
+}
+
+impl fmt::Display for SignatureLayoutIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnalignedTable { offset } => write!(
+                f,
+                "certificate table at {:#x} is not aligned to 8 bytes",
+                offset
+            ),
+            Self::DataBeforeTable { offset, size } => write!(
+                f,
+                "{} bytes of overlay at {:#x} precede the certificate table",
+                size, offset
+            ),
+            Self::DataAfterTable { offset, size } => write!(
+                f,
+                "data appended after signature: {} bytes at {:#x}",
+                size, offset
+            ),
+            Self::DataAfterSignedData { offset, size } => write!(
+                f,
+                "data appended inside the signature entry: {} bytes at {:#x}",
+                size, offset
+            ),
+        }
+    }
+}
+
+/// Certificate an Authenticode signature was made with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signer {
+    subject: String,
+    common_name: Option<String>,
+    issuer: String,
+    serial_number: Vec<u8>,
+    extended_key_usages: Vec<String>,
+}
+
+impl Signer {
+    /// Subject distinguished name, such as `CN=Example Corp, O=Example Corp, C=US`
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    /// Big-endian serial number, unique among the certificates of an issuer
+    pub fn serial_number(&self) -> &[u8] {
+        &self.serial_number
+    }
+
+    /// Serial number as colon-separated hex bytes
+    pub fn serial_number_hex(&self) -> String {
+        let mut text = String::new();
+        for (index, byte) in self.serial_number.iter().enumerate() {
+            if index != 0 {
+                text.push(':');
+            }
+            let _ = write!(text, "{:02x}", byte);
+        }
+        text
+    }
+
+    /// Value of the subject's common name
+    pub fn common_name(&self) -> Option<&str> {
+        self.common_name.as_deref()
+    }
+
+    /// Dotted OIDs of the certificate's extended key usage extension, such as
+    /// `1.3.6.1.5.5.7.3.3` for code signing; empty if it has none
+    pub fn extended_key_usages(&self) -> &[String] {
+        &self.extended_key_usages
+    }
+}
+
+impl PortExe {
+    /// Entries of the attribute certificate table, which the security directory locates
+    /// by file offset rather than RVA.
+    pub fn certificates(&self) -> io::Result<Vec<WinCertificate>> {
+        let directory = match self
+            .optional_header()
+            .and_then(|h| h.data_directory(IMAGE_DIRECTORY_ENTRY_SECURITY))
+        {
+            Some(directory) => directory,
+            None => return Ok(Vec::new()),
+        };
+        let offset = directory.virtual_address().into_value() as usize;
+        let size = directory.size().into_value() as usize;
+        if offset == 0 || size == 0 {
+            return Ok(Vec::new());
+        }
+        let table = slice_at(self.data(), offset, size)
+            .ok_or_else(|| invalid_data("certificate table lies outside the file"))?;
+
+        let mut certificates = Vec::new();
+        let mut position = 0;
+        while position + WIN_CERTIFICATE_HEADER_SIZE <= table.len() {
+            if certificates.len() == MAX_CERTIFICATES {
+                return Err(invalid_data("too many attribute certificates"));
+            }
+            let length = read_u32(table, position).unwrap_or(0) as usize;
+            let data = length
+                .checked_sub(WIN_CERTIFICATE_HEADER_SIZE)
+                .and_then(|data| slice_at(table, position + WIN_CERTIFICATE_HEADER_SIZE, data))
+                .ok_or_else(|| invalid_data("attribute certificate length is out of range"))?;
+            certificates.push(WinCertificate {
+                offset: offset + position,
+                revision: read_u16(table, position + 4).unwrap_or(0),
+                certificate_type: read_u16(table, position + 6).unwrap_or(0),
+                data: data.to_vec(),
+            });
+            let padded =
+                (length + WIN_CERTIFICATE_ALIGNMENT - 1) & !(WIN_CERTIFICATE_ALIGNMENT - 1);
+            position += padded;
+        }
+        Ok(certificates)
+    }
+
+    /// Signer of the first Authenticode signature
+    pub fn signer(&self) -> io::Result<Option<Signer>> {
+        Ok(self.certificates()?.iter().find_map(WinCertificate::signer))
+    }
+
+    /// Checks that the attribute certificate table covers exactly the overlay: that it
+    /// starts where the image ends and that nothing follows it, either after the table
+    /// or inside an Authenticode entry after its PKCS#7 structure. Data appended after a
+    /// signature keeps it valid for lenient verifiers and is a known way to patch signed
+    /// files.
+    ///
+    /// Returns no issues for unsigned files.
+    pub fn signature_layout(&self) -> io::Result<Vec<SignatureLayoutIssue>> {
+        let certificates = self.certificates()?;
+        let directory = match self
+            .optional_header()
+            .and_then(|h| h.data_directory(IMAGE_DIRECTORY_ENTRY_SECURITY))
+        {
+            Some(directory) if !certificates.is_empty() => directory,
+            _ => return Ok(Vec::new()),
+        };
+        let offset = directory.virtual_address().into_value() as usize;
+        let end = offset + directory.size().into_value() as usize;
+        let mut issues = Vec::new();
+        if offset % WIN_CERTIFICATE_ALIGNMENT != 0 {
+            issues.push(SignatureLayoutIssue::UnalignedTable { offset });
+        }
+        let image_end = self.overlay_offset();
+        if offset > image_end {
+            issues.push(SignatureLayoutIssue::DataBeforeTable {
+                offset: image_end,
+                size: offset - image_end,
+            });
+        }
+        for certificate in &certificates {
+            if certificate.certificate_type != WIN_CERT_TYPE_PKCS_SIGNED_DATA {
+                continue;
+            }
+            let signed_data = match tlv(&certificate.data) {
+                Some((_, _, rest)) => certificate.data.len() - rest.len(),
+                None => continue,
+            };
+            let trailing = &certificate.data[signed_data..];
+            if trailing.len() >= WIN_CERTIFICATE_ALIGNMENT || trailing.iter().any(|&b| b != 0) {
+                issues.push(SignatureLayoutIssue::DataAfterSignedData {
+                    offset: certificate.offset + WIN_CERTIFICATE_HEADER_SIZE + signed_data,
+                    size: trailing.len(),
+                });
+            }
+        }
+        if self.data().len() > end {
+            issues.push(SignatureLayoutIssue::DataAfterTable {
+                offset: end,
+                size: self.data().len() - end,
+            });
+        }
+        Ok(issues)
+    }
+}
+
+/// Finds the certificate named by the first `SignerInfo` of a PKCS#7 `SignedData`.
+fn signer(data: &[u8]) -> Option<Signer> {
+    // ContentInfo ::= SEQUENCE { contentType OID, content [0] EXPLICIT SignedData }
+    let (_, content_info, _) = der(data, DER_SEQUENCE)?;
+    let (_, _, content) = der(content_info, DER_OID)?;
+    let (_, explicit, _) = der(content, DER_CONTEXT_0)?;
+    // SignedData ::= SEQUENCE { version, digestAlgorithms, contentInfo,
+    //     certificates [0] IMPLICIT OPTIONAL, crls [1] IMPLICIT OPTIONAL, signerInfos SET }
+    let (_, signed_data, _) = der(explicit, DER_SEQUENCE)?;
+    let (_, _, rest) = der(signed_data, DER_INTEGER)?;
+    let (_, _, rest) = der(rest, DER_SET)?;
+    let (_, _, mut rest) = der(rest, DER_SEQUENCE)?;
+    let mut certificates: &[u8] = &[];
+    let mut signer_infos = None;
+    while let Some((tag, value, next)) = tlv(rest) {
+        match tag {
+            DER_CONTEXT_0 => certificates = value,
+            DER_SET => signer_infos = Some(value),
+            _ => {}
+        }
+        rest = next;
+    }
+
+    // SignerInfo ::= SEQUENCE { version, issuerAndSerialNumber SEQUENCE { issuer, serial }, ... }
+    let (_, signer_info, _) = der(signer_infos?, DER_SEQUENCE)?;
+    let (_, _, rest) = der(signer_info, DER_INTEGER)?;
+    let (_, issuer_and_serial, _) = der(rest, DER_SEQUENCE)?;
+    let (_, issuer, rest) = der(issuer_and_serial, DER_SEQUENCE)?;
+    let (_, serial, _) = der(rest, DER_INTEGER)?;
+
+    let mut rest = certificates;
+    for _ in 0..MAX_CERTIFICATES {
+        let (_, certificate, next) = der(rest, DER_SEQUENCE)?;
+        rest = next;
+        if let Some(signer) = certificate_signer(certificate, issuer, serial) {
+            return Some(signer);
+        }
+    }
+    None
+}
+
+/// Subject of `certificate` if it is the one `issuer` issued with `serial`
+fn certificate_signer(certificate: &[u8], issuer: &[u8], serial: &[u8]) -> Option<Signer> {
+    // TBSCertificate ::= SEQUENCE { version [0] EXPLICIT OPTIONAL, serialNumber,
+    //     signature, issuer, validity, subject, ... }
+    let (_, tbs, _) = der(certificate, DER_SEQUENCE)?;
+    let tbs = match tlv(tbs)? {
+        (DER_CONTEXT_0, _, rest) => rest,
+        _ => tbs,
+    };
+    let (_, certificate_serial, rest) = der(tbs, DER_INTEGER)?;
+    let (_, _, rest) = der(rest, DER_SEQUENCE)?;
+    let (_, certificate_issuer, rest) = der(rest, DER_SEQUENCE)?;
+    if certificate_serial != serial || certificate_issuer != issuer {
+        return None;
+    }
+    let (_, _, rest) = der(rest, DER_SEQUENCE)?;
+    let (_, subject, rest) = der(rest, DER_SEQUENCE)?;
+    let (subject, common_name) = distinguished_name(subject);
+    Some(Signer {
+        subject,
+        common_name,
+        issuer: distinguished_name(issuer).0,
+        serial_number: serial.to_vec(),
+        extended_key_usages: extended_key_usages(rest),
+    })
+}
+
+/// Reads the extended key usage OIDs from what follows the subject of a TBSCertificate:
+/// `subjectPublicKeyInfo`, optional unique IDs and `extensions [3] EXPLICIT`.
+fn extended_key_usages(mut rest: &[u8]) -> Vec<String> {
+    let mut extensions: &[u8] = &[];
+    while let Some((tag, value, next)) = tlv(rest) {
+        if tag == DER_CONTEXT_3 {
+            extensions = der(value, DER_SEQUENCE).map_or(&[], |(_, value, _)| value);
+        }
+        rest = next;
+    }
+    // Extension ::= SEQUENCE { extnID OID, critical BOOLEAN DEFAULT FALSE, extnValue OCTET STRING }
+    while let Some((_, extension, next)) = der(extensions, DER_SEQUENCE) {
+        extensions = next;
+        let (_, oid, mut value) = match der(extension, DER_OID) {
+            Some(parsed) => parsed,
+            None => continue,
+        };
+        if oid != OID_EXTENDED_KEY_USAGE {
+            continue;
+        }
+        if let Some((DER_BOOLEAN, _, next)) = tlv(value) {
+            value = next;
+        }
+        // ExtKeyUsageSyntax ::= SEQUENCE OF KeyPurposeId
+        let mut usages = der(value, DER_OCTET_STRING)
+            .and_then(|(_, value, _)| der(value, DER_SEQUENCE))
+            .map_or(&[][..], |(_, usages, _)| usages);
+        let mut oids = Vec::new();
+        while let Some((_, oid, next)) = der(usages, DER_OID) {
+            oids.push(oid_string(oid));
+            usages = next;
+        }
+        return oids;
+    }
+    Vec::new()
+}
+
+/// Renders a DER-encoded object identifier in dotted form.
+fn oid_string(oid: &[u8]) -> String {
+    let mut arcs = Vec::new();
+    let mut arc = 0u64;
+    for &byte in oid {
+        arc = (arc << 7) | (byte & 0x7F) as u64;
+        if byte & 0x80 == 0 {
+            if arcs.is_empty() {
+                let first = (arc / 40).min(2);
+                arcs.push(first);
+                arcs.push(arc - first * 40);
+            } else {
+                arcs.push(arc);
+            }
+            arc = 0;
+        }
+    }
+    arcs.iter()
+        .map(|arc| arc.to_string())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Renders the attributes of an X.501 `Name` that have a well-known short name, and
+/// returns its common name separately.
+fn distinguished_name(name: &[u8]) -> (String, Option<String>) {
+    let mut parts = Vec::new();
+    let mut common_name = None;
+    let mut rest = name;
+    while let Some((_, set, next)) = der(rest, DER_SET) {
+        rest = next;
+        let mut attributes = set;
+        while let Some((_, attribute, next)) = der(attributes, DER_SEQUENCE) {
+            attributes = next;
+            let (_, oid, value) = match der(attribute, DER_OID) {
+                Some(parsed) => parsed,
+                None => continue,
+            };
+            let label = match oid {
+                [0x55, 0x04, 0x03] => "CN",
+                [0x55, 0x04, 0x06] => "C",
+                [0x55, 0x04, 0x07] => "L",
+                [0x55, 0x04, 0x08] => "ST",
+                [0x55, 0x04, 0x0A] => "O",
+                [0x55, 0x04, 0x0B] => "OU",
+                _ => continue,
+            };
+            if let Some((tag, value, _)) = tlv(value) {
+                let value = directory_string(tag, value);
+                if label == "CN" && common_name.is_none() {
+                    common_name = Some(value.clone());
+                }
+                parts.push(format!("{}={}", label, value));
+            }
+        }
+    }
+    (parts.join(", "), common_name)
+}
+
+/// Decodes the string types X.501 names use.
+fn directory_string(tag: u8, value: &[u8]) -> String {
+    match tag {
+        // BMPString
+        0x1E => String::from_utf16_lossy(&utf16_units(value, ByteOrder::Big)),
+        _ => String::from_utf8_lossy(value).into_owned(),
+    }
+}
+
+/// Reads a DER element with tag `expected`, returning its tag, contents and what follows.
+fn der(data: &[u8], expected: u8) -> Option<(u8, &[u8], &[u8])> {
+    tlv(data).filter(|(tag, _, _)| *tag == expected)
+}
+
+/// Reads a DER element with a single-byte tag and a definite length.
+pub(crate) fn tlv(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *data.first()?;
+    let first = *data.get(1)?;
+    let (length, header) = if first & 0x80 == 0 {
+        (first as usize, 2)
+    } else {
+        let count = (first & 0x7F) as usize;
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let bytes = data.get(2..2 + count)?;
+        let length = bytes
+            .iter()
+            .fold(0usize, |length, &byte| (length << 8) | byte as usize);
+        (length, 2 + count)
+    };
+    let end = header.checked_add(length)?;
+    let value = data.get(header..end)?;
+    Some((tag, value, &data[end..]))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
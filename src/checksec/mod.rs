@@ -0,0 +1,208 @@
+use crate::arith::checked_range;
+use crate::debug::IMAGE_DLLCHARACTERISTICS_EX_CET_COMPAT;
+use crate::file_header::Machine;
+use crate::load_config::{
+    IMAGE_GUARD_CF_INSTRUMENTED, IMAGE_GUARD_EH_CONTINUATION_TABLE_PRESENT, IMAGE_GUARD_XFG_ENABLED,
+};
+use crate::optional_header::IMAGE_DIRECTORY_ENTRY_SECURITY;
+use crate::port_exe::PortExe;
+use std::fmt;
+use std::io;
+
+/// State of a single exploit mitigation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protection {
+    Enabled,
+    Disabled,
+    /// The mitigation does not exist for this kind of image
+    NotApplicable,
+}
+
+impl Protection {
+    fn from_bool(enabled: bool) -> Self {
+        if enabled {
+            Self::Enabled
+        } else {
+            Self::Disabled
+        }
+    }
+}
+
+impl fmt::Display for Protection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Enabled => "Yes",
+            Self::Disabled => "No",
+            Self::NotApplicable => "N/A",
+        })
+    }
+}
+
+/// Exploit mitigations advertised by an image, in the spirit of Linux `checksec`
+#[derive(Debug, Clone)]
+pub struct Checksec {
+    aslr: Protection,
+    dep: Protection,
+    cfg: Protection,
+    safe_seh: Protection,
+    gs: Protection,
+    cet: Protection,
+    eh_continuation: Protection,
+    xfg: Protection,
+    signed: Protection,
+    high_entropy_va: Protection,
+}
+
+impl Checksec {
+    /// Dynamic base with relocations that have not been stripped
+    pub fn aslr(&self) -> Protection {
+        self.aslr
+    }
+
+    pub fn dep(&self) -> Protection {
+        self.dep
+    }
+
+    /// Control Flow Guard requested and instrumented
+    pub fn cfg(&self) -> Protection {
+        self.cfg
+    }
+
+    /// Safe exception handler table or `NO_SEH`, x86 images only
+    pub fn safe_seh(&self) -> Protection {
+        self.safe_seh
+    }
+
+    /// /GS security cookie registered in the load config
+    pub fn gs(&self) -> Protection {
+        self.gs
+    }
+
+    /// CET shadow stack compatibility
+    pub fn cet(&self) -> Protection {
+        self.cet
+    }
+
+    /// `/guard:ehcont` table of the valid targets of exception handling continuations,
+    /// x64 and ARM64 images only
+    pub fn eh_continuation(&self) -> Protection {
+        self.eh_continuation
+    }
+
+    /// eXtended Flow Guard, which checks the type hash of indirect call targets on top
+    /// of CFG, x64 images only
+    pub fn xfg(&self) -> Protection {
+        self.xfg
+    }
+
+    /// Presence of an Authenticode certificate table; the signature is not verified
+    pub fn signed(&self) -> Protection {
+        self.signed
+    }
+
+    /// High entropy ASLR, 64-bit images only
+    pub fn high_entropy_va(&self) -> Protection {
+        self.high_entropy_va
+    }
+}
+
+impl PortExe {
+    /// Summarises the exploit mitigations of the image.
+    ///
+    /// Object files have no optional header and report every mitigation as not applicable.
+    pub fn checksec(&self) -> io::Result<Checksec> {
+        let header = match self.optional_header() {
+            Some(header) => header,
+            None => {
+                return Ok(Checksec {
+                    aslr: Protection::NotApplicable,
+                    dep: Protection::NotApplicable,
+                    cfg: Protection::NotApplicable,
+                    safe_seh: Protection::NotApplicable,
+                    gs: Protection::NotApplicable,
+                    cet: Protection::NotApplicable,
+                    eh_continuation: Protection::NotApplicable,
+                    xfg: Protection::NotApplicable,
+                    signed: Protection::NotApplicable,
+                    high_entropy_va: Protection::NotApplicable,
+                })
+            }
+        };
+        let characteristics = self.file_header().characteristics().into_value();
+        let dll_characteristics = header.dll_characteristics();
+        let load_config = self.load_config()?;
+        let guard_flags = load_config
+            .as_ref()
+            .and_then(|config| config.guard_flags())
+            .unwrap_or(0);
+        let dll_characteristics_ex = self.dll_characteristics_ex()?.unwrap_or(0);
+
+        let cfg = dll_characteristics.guard_cf() && guard_flags & IMAGE_GUARD_CF_INSTRUMENTED != 0;
+        let aslr = dll_characteristics.dynamic_base() && !characteristics.relocs_stripped();
+        let safe_seh = match self.file_header().machine().into_value() {
+            Machine::Intel386 => Protection::from_bool(
+                dll_characteristics.no_seh()
+                    || load_config
+                        .as_ref()
+                        .and_then(|config| config.se_handler_table())
+                        .map_or(false, |table| table != 0),
+            ),
+            _ => Protection::NotApplicable,
+        };
+        let machine = self.file_header().machine().into_value();
+        let eh_continuation = match machine {
+            Machine::X64 | Machine::ARM64LittleEndian | Machine::Arm64Ec | Machine::Arm64X => {
+                Protection::from_bool(
+                    guard_flags & IMAGE_GUARD_EH_CONTINUATION_TABLE_PRESENT != 0
+                        && load_config
+                            .as_ref()
+                            .and_then(|config| config.guard_eh_continuation_table())
+                            .map_or(false, |table| table != 0),
+                )
+            }
+            _ => Protection::NotApplicable,
+        };
+        let xfg = match machine {
+            Machine::X64 => Protection::from_bool(
+                cfg && guard_flags & IMAGE_GUARD_XFG_ENABLED != 0
+                    && load_config
+                        .as_ref()
+                        .and_then(|config| config.guard_xfg_check_function_pointer())
+                        .map_or(false, |pointer| pointer != 0),
+            ),
+            _ => Protection::NotApplicable,
+        };
+        let high_entropy_va = if header.image_type().is_x64() {
+            Protection::from_bool(aslr && dll_characteristics.high_entropy_va())
+        } else {
+            Protection::NotApplicable
+        };
+        let signed = header
+            .data_directory(IMAGE_DIRECTORY_ENTRY_SECURITY)
+            .map_or(false, |directory| {
+                let offset = directory.virtual_address().into_value() as usize;
+                let size = directory.size().into_value() as usize;
+                offset != 0 && size != 0 && checked_range(offset, size, self.data().len()).is_some()
+            });
+
+        Ok(Checksec {
+            aslr: Protection::from_bool(aslr),
+            dep: Protection::from_bool(dll_characteristics.nx_compat()),
+            cfg: Protection::from_bool(cfg),
+            safe_seh,
+            gs: Protection::from_bool(
+                load_config
+                    .as_ref()
+                    .and_then(|config| config.security_cookie())
+                    .map_or(false, |cookie| cookie != 0),
+            ),
+            cet: Protection::from_bool(
+                dll_characteristics_ex & IMAGE_DLLCHARACTERISTICS_EX_CET_COMPAT != 0,
+            ),
+            eh_continuation,
+            xfg,
+            signed: Protection::from_bool(signed),
+            high_entropy_va,
+        })
+    }
+}
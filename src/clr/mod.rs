@@ -0,0 +1,1014 @@
+use crate::arith::{checked_range, slice_at, table_at, table_range, va_to_rva};
+use crate::endian::Guid;
+use crate::export::Export;
+use crate::optional_header::IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR;
+use crate::port_exe::PortExe;
+use crate::ImageType;
+use crate::{read_u16, read_u32};
+use std::io;
+
+const METADATA_SIGNATURE: u32 = 0x424A5342;
+const RESOURCES_FILE_MAGIC: u32 = 0xBEEFCACE;
+
+pub const COMIMAGE_FLAGS_ILONLY: u32 = 0x00000001;
+pub const COMIMAGE_FLAGS_32BITREQUIRED: u32 = 0x00000002;
+pub const COMIMAGE_FLAGS_IL_LIBRARY: u32 = 0x00000004;
+pub const COMIMAGE_FLAGS_STRONGNAMESIGNED: u32 = 0x00000008;
+pub const COMIMAGE_FLAGS_NATIVE_ENTRYPOINT: u32 = 0x00000010;
+pub const COMIMAGE_FLAGS_TRACKDEBUGDATA: u32 = 0x00010000;
+pub const COMIMAGE_FLAGS_32BITPREFERRED: u32 = 0x00020000;
+
+pub const COR_VTABLE_32BIT: u16 = 0x01;
+pub const COR_VTABLE_64BIT: u16 = 0x02;
+pub const COR_VTABLE_FROM_UNMANAGED: u16 = 0x04;
+pub const COR_VTABLE_FROM_UNMANAGED_RETAIN_APPDOMAIN: u16 = 0x08;
+pub const COR_VTABLE_CALL_MOST_DERIVED: u16 = 0x10;
+
+pub const TABLE_MODULE: usize = 0x00;
+pub const TABLE_TYPE_REF: usize = 0x01;
+pub const TABLE_TYPE_DEF: usize = 0x02;
+pub const TABLE_FIELD: usize = 0x04;
+pub const TABLE_METHOD_DEF: usize = 0x06;
+pub const TABLE_PARAM: usize = 0x08;
+pub const TABLE_MEMBER_REF: usize = 0x0A;
+pub const TABLE_EVENT: usize = 0x14;
+pub const TABLE_PROPERTY: usize = 0x17;
+pub const TABLE_MODULE_REF: usize = 0x1A;
+pub const TABLE_TYPE_SPEC: usize = 0x1B;
+pub const TABLE_ASSEMBLY: usize = 0x20;
+pub const TABLE_ASSEMBLY_REF: usize = 0x23;
+pub const TABLE_FILE: usize = 0x26;
+pub const TABLE_EXPORTED_TYPE: usize = 0x27;
+pub const TABLE_MANIFEST_RESOURCE: usize = 0x28;
+pub const TABLE_GENERIC_PARAM: usize = 0x2A;
+const TABLE_COUNT: usize = 0x2D;
+
+/// Names of the metadata tables, by table number
+const TABLE_NAMES: [&str; TABLE_COUNT] = [
+    "Module",
+    "TypeRef",
+    "TypeDef",
+    "FieldPtr",
+    "Field",
+    "MethodPtr",
+    "MethodDef",
+    "ParamPtr",
+    "Param",
+    "InterfaceImpl",
+    "MemberRef",
+    "Constant",
+    "CustomAttribute",
+    "FieldMarshal",
+    "DeclSecurity",
+    "ClassLayout",
+    "FieldLayout",
+    "StandAloneSig",
+    "EventMap",
+    "EventPtr",
+    "Event",
+    "PropertyMap",
+    "PropertyPtr",
+    "Property",
+    "MethodSemantics",
+    "MethodImpl",
+    "ModuleRef",
+    "TypeSpec",
+    "ImplMap",
+    "FieldRVA",
+    "EncLog",
+    "EncMap",
+    "Assembly",
+    "AssemblyProcessor",
+    "AssemblyOS",
+    "AssemblyRef",
+    "AssemblyRefProcessor",
+    "AssemblyRefOS",
+    "File",
+    "ExportedType",
+    "ManifestResource",
+    "NestedClass",
+    "GenericParam",
+    "MethodSpec",
+    "GenericParamConstraint",
+];
+
+/// Names of the `COMIMAGE_FLAGS_*` bits
+const FLAG_NAMES: [(u32, &str); 7] = [
+    (COMIMAGE_FLAGS_ILONLY, "ILONLY"),
+    (COMIMAGE_FLAGS_32BITREQUIRED, "32BITREQUIRED"),
+    (COMIMAGE_FLAGS_IL_LIBRARY, "IL_LIBRARY"),
+    (COMIMAGE_FLAGS_STRONGNAMESIGNED, "STRONGNAMESIGNED"),
+    (COMIMAGE_FLAGS_NATIVE_ENTRYPOINT, "NATIVE_ENTRYPOINT"),
+    (COMIMAGE_FLAGS_TRACKDEBUGDATA, "TRACKDEBUGDATA"),
+    (COMIMAGE_FLAGS_32BITPREFERRED, "32BITPREFERRED"),
+];
+
+/// Name of metadata `table`, such as `TypeDef` for 0x02
+pub fn table_name(table: usize) -> Option<&'static str> {
+    TABLE_NAMES.get(table).copied()
+}
+
+/// Data directory entry (RVA and size) inside the CLR header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClrDirectory {
+    virtual_address: u32,
+    size: u32,
+}
+
+impl ClrDirectory {
+    pub fn virtual_address(&self) -> u32 {
+        self.virtual_address
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    fn read(data: &[u8], offset: usize) -> Option<Self> {
+        Some(Self {
+            virtual_address: read_u32(data, offset)?,
+            size: read_u32(data, offset + 4)?,
+        })
+    }
+}
+
+/// `IMAGE_COR20_HEADER`
+#[derive(Debug, Clone)]
+pub struct ClrHeader {
+    cb: u32,
+    major_runtime_version: u16,
+    minor_runtime_version: u16,
+    metadata: ClrDirectory,
+    flags: u32,
+    entry_point_token: u32,
+    resources: ClrDirectory,
+    strong_name_signature: ClrDirectory,
+    code_manager_table: ClrDirectory,
+    vtable_fixups: ClrDirectory,
+    export_address_table_jumps: ClrDirectory,
+    managed_native_header: ClrDirectory,
+}
+
+impl ClrHeader {
+    fn read(data: &[u8]) -> Option<Self> {
+        Some(Self {
+            cb: read_u32(data, 0)?,
+            major_runtime_version: read_u16(data, 4)?,
+            minor_runtime_version: read_u16(data, 6)?,
+            metadata: ClrDirectory::read(data, 8)?,
+            flags: read_u32(data, 16)?,
+            entry_point_token: read_u32(data, 20)?,
+            resources: ClrDirectory::read(data, 24)?,
+            strong_name_signature: ClrDirectory::read(data, 32)?,
+            code_manager_table: ClrDirectory::read(data, 40)?,
+            vtable_fixups: ClrDirectory::read(data, 48)?,
+            export_address_table_jumps: ClrDirectory::read(data, 56)?,
+            managed_native_header: ClrDirectory::read(data, 64)?,
+        })
+    }
+
+    /// Size of the header in bytes
+    pub fn cb(&self) -> u32 {
+        self.cb
+    }
+
+    pub fn major_runtime_version(&self) -> u16 {
+        self.major_runtime_version
+    }
+
+    pub fn minor_runtime_version(&self) -> u16 {
+        self.minor_runtime_version
+    }
+
+    pub fn metadata(&self) -> ClrDirectory {
+        self.metadata
+    }
+
+    /// `COMIMAGE_FLAGS_*` bits
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// Managed entry point token, or native entry point RVA with `COMIMAGE_FLAGS_NATIVE_ENTRYPOINT`
+    pub fn entry_point_token(&self) -> u32 {
+        self.entry_point_token
+    }
+
+    pub fn resources(&self) -> ClrDirectory {
+        self.resources
+    }
+
+    pub fn strong_name_signature(&self) -> ClrDirectory {
+        self.strong_name_signature
+    }
+
+    pub fn code_manager_table(&self) -> ClrDirectory {
+        self.code_manager_table
+    }
+
+    pub fn vtable_fixups(&self) -> ClrDirectory {
+        self.vtable_fixups
+    }
+
+    pub fn export_address_table_jumps(&self) -> ClrDirectory {
+        self.export_address_table_jumps
+    }
+
+    pub fn managed_native_header(&self) -> ClrDirectory {
+        self.managed_native_header
+    }
+
+    /// Assembly contains only IL code
+    pub fn il_only(&self) -> bool {
+        self.flags & COMIMAGE_FLAGS_ILONLY != 0
+    }
+
+    pub fn strong_name_signed(&self) -> bool {
+        self.flags & COMIMAGE_FLAGS_STRONGNAMESIGNED != 0
+    }
+
+    pub fn native_entry_point(&self) -> bool {
+        self.flags & COMIMAGE_FLAGS_NATIVE_ENTRYPOINT != 0
+    }
+
+    /// Names of the set `COMIMAGE_FLAGS_*` bits, such as `ILONLY`
+    pub fn flag_names(&self) -> Vec<&'static str> {
+        FLAG_NAMES
+            .iter()
+            .filter(|(flag, _)| self.flags & flag != 0)
+            .map(|(_, name)| *name)
+            .collect()
+    }
+
+    /// Metadata table and 1-based row of the managed entry point, `None` if there is
+    /// none or it is native code
+    pub fn entry_point_row(&self) -> Option<(usize, u32)> {
+        let row = self.entry_point_token & 0x00FF_FFFF;
+        (!self.native_entry_point() && row != 0)
+            .then(|| ((self.entry_point_token >> 24) as usize, row))
+    }
+}
+
+/// Stream header from the metadata root
+#[derive(Debug, Clone)]
+pub struct MetadataStream {
+    offset: u32,
+    size: u32,
+    name: String,
+}
+
+impl MetadataStream {
+    /// Offset from the start of the metadata root
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[derive(Clone, Copy)]
+enum CodedIndex {
+    TypeDefOrRef,
+    HasConstant,
+    HasCustomAttribute,
+    HasFieldMarshal,
+    HasDeclSecurity,
+    MemberRefParent,
+    HasSemantics,
+    MethodDefOrRef,
+    MemberForwarded,
+    Implementation,
+    CustomAttributeType,
+    ResolutionScope,
+    TypeOrMethodDef,
+}
+
+impl CodedIndex {
+    /// Tables the tag selects, `None` for unused tag values
+    fn tables(self) -> &'static [Option<usize>] {
+        match self {
+            Self::TypeDefOrRef => &[Some(0x02), Some(0x01), Some(0x1B)],
+            Self::HasConstant => &[Some(0x04), Some(0x08), Some(0x17)],
+            Self::HasCustomAttribute => &[
+                Some(0x06),
+                Some(0x04),
+                Some(0x01),
+                Some(0x02),
+                Some(0x08),
+                Some(0x09),
+                Some(0x0A),
+                Some(0x00),
+                Some(0x0E),
+                Some(0x17),
+                Some(0x14),
+                Some(0x11),
+                Some(0x1A),
+                Some(0x1B),
+                Some(0x20),
+                Some(0x23),
+                Some(0x26),
+                Some(0x27),
+                Some(0x28),
+                Some(0x2A),
+                Some(0x2C),
+                Some(0x2B),
+            ],
+            Self::HasFieldMarshal => &[Some(0x04), Some(0x08)],
+            Self::HasDeclSecurity => &[Some(0x02), Some(0x06), Some(0x20)],
+            Self::MemberRefParent => &[Some(0x02), Some(0x01), Some(0x1A), Some(0x06), Some(0x1B)],
+            Self::HasSemantics => &[Some(0x14), Some(0x17)],
+            Self::MethodDefOrRef => &[Some(0x06), Some(0x0A)],
+            Self::MemberForwarded => &[Some(0x04), Some(0x06)],
+            Self::Implementation => &[Some(0x26), Some(0x23), Some(0x27)],
+            Self::CustomAttributeType => &[None, None, Some(0x06), Some(0x0A), None],
+            Self::ResolutionScope => &[Some(0x00), Some(0x1A), Some(0x23), Some(0x01)],
+            Self::TypeOrMethodDef => &[Some(0x02), Some(0x06)],
+        }
+    }
+
+    fn tag_bits(self) -> u32 {
+        let count = self.tables().len() as u32;
+        32 - (count - 1).leading_zeros()
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Column {
+    Fixed(usize),
+    String,
+    Guid,
+    Blob,
+    Table(usize),
+    Coded(CodedIndex),
+}
+
+fn table_schema(table: usize) -> &'static [Column] {
+    use CodedIndex::*;
+    use Column::{Blob, Coded, Fixed, Guid, String, Table};
+    match table {
+        0x00 => &[Fixed(2), String, Guid, Guid, Guid],
+        0x01 => &[Coded(ResolutionScope), String, String],
+        0x02 => &[
+            Fixed(4),
+            String,
+            String,
+            Coded(TypeDefOrRef),
+            Table(0x04),
+            Table(0x06),
+        ],
+        0x03 => &[Table(0x04)],
+        0x04 => &[Fixed(2), String, Blob],
+        0x05 => &[Table(0x06)],
+        0x06 => &[Fixed(4), Fixed(2), Fixed(2), String, Blob, Table(0x08)],
+        0x07 => &[Table(0x08)],
+        0x08 => &[Fixed(2), Fixed(2), String],
+        0x09 => &[Table(0x02), Coded(TypeDefOrRef)],
+        0x0A => &[Coded(MemberRefParent), String, Blob],
+        0x0B => &[Fixed(1), Fixed(1), Coded(HasConstant), Blob],
+        0x0C => &[Coded(HasCustomAttribute), Coded(CustomAttributeType), Blob],
+        0x0D => &[Coded(HasFieldMarshal), Blob],
+        0x0E => &[Fixed(2), Coded(HasDeclSecurity), Blob],
+        0x0F => &[Fixed(2), Fixed(4), Table(0x02)],
+        0x10 => &[Fixed(4), Table(0x04)],
+        0x11 => &[Blob],
+        0x12 => &[Table(0x02), Table(0x14)],
+        0x13 => &[Table(0x14)],
+        0x14 => &[Fixed(2), String, Coded(TypeDefOrRef)],
+        0x15 => &[Table(0x02), Table(0x17)],
+        0x16 => &[Table(0x17)],
+        0x17 => &[Fixed(2), String, Blob],
+        0x18 => &[Fixed(2), Table(0x06), Coded(HasSemantics)],
+        0x19 => &[Table(0x02), Coded(MethodDefOrRef), Coded(MethodDefOrRef)],
+        0x1A => &[String],
+        0x1B => &[Blob],
+        0x1C => &[Fixed(2), Coded(MemberForwarded), String, Table(0x1A)],
+        0x1D => &[Fixed(4), Table(0x04)],
+        0x1E => &[Fixed(4), Fixed(4)],
+        0x1F => &[Fixed(4)],
+        0x20 => &[
+            Fixed(4),
+            Fixed(2),
+            Fixed(2),
+            Fixed(2),
+            Fixed(2),
+            Fixed(4),
+            Blob,
+            String,
+            String,
+        ],
+        0x21 => &[Fixed(4)],
+        0x22 => &[Fixed(4), Fixed(4), Fixed(4)],
+        0x23 => &[
+            Fixed(2),
+            Fixed(2),
+            Fixed(2),
+            Fixed(2),
+            Fixed(4),
+            Blob,
+            String,
+            String,
+            Blob,
+        ],
+        0x24 => &[Fixed(4), Table(0x23)],
+        0x25 => &[Fixed(4), Fixed(4), Fixed(4), Table(0x23)],
+        0x26 => &[Fixed(4), String, Blob],
+        0x27 => &[Fixed(4), Fixed(4), String, String, Coded(Implementation)],
+        0x28 => &[Fixed(4), Fixed(4), String, Coded(Implementation)],
+        0x29 => &[Table(0x02), Table(0x02)],
+        0x2A => &[Fixed(2), Fixed(2), Coded(TypeOrMethodDef), String],
+        0x2B => &[Coded(MethodDefOrRef), Blob],
+        0x2C => &[Table(0x2A), Coded(TypeDefOrRef)],
+        _ => &[],
+    }
+}
+
+/// Parsed metadata root with access to the heaps and the `#~` table stream
+#[derive(Debug)]
+pub struct Metadata<'a> {
+    version: String,
+    streams: Vec<MetadataStream>,
+    strings: &'a [u8],
+    blob: &'a [u8],
+    guid: &'a [u8],
+    tables: &'a [u8],
+    table_stream_version: (u8, u8),
+    heap_sizes: u8,
+    row_counts: [u32; TABLE_COUNT],
+    table_offsets: [usize; TABLE_COUNT],
+}
+
+impl<'a> Metadata<'a> {
+    fn parse(root: &'a [u8]) -> io::Result<Self> {
+        let truncated = || invalid_data("truncated metadata root");
+        if read_u32(root, 0) != Some(METADATA_SIGNATURE) {
+            return Err(invalid_data("bad metadata signature"));
+        }
+        let version_length = read_u32(root, 12).ok_or_else(truncated)? as usize;
+        let version = slice_at(root, 16, version_length).ok_or_else(truncated)?;
+        let version_end = version
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(version.len());
+        let version = String::from_utf8_lossy(&version[..version_end]).into_owned();
+
+        let mut position = checked_range(16, version_length, root.len())
+            .ok_or_else(truncated)?
+            .end
+            + 2;
+        let number_of_streams = read_u16(root, position).ok_or_else(truncated)?;
+        position += 2;
+        let mut streams = Vec::with_capacity(number_of_streams as usize);
+        for _ in 0..number_of_streams {
+            let offset = read_u32(root, position).ok_or_else(truncated)?;
+            let size = read_u32(root, position + 4).ok_or_else(truncated)?;
+            let name = root.get(position + 8..).ok_or_else(truncated)?;
+            let name_length = name.iter().position(|&b| b == 0).ok_or_else(truncated)?;
+            let name = String::from_utf8_lossy(&name[..name_length]).into_owned();
+            position += 8 + (name_length + 4) / 4 * 4;
+            streams.push(MetadataStream { offset, size, name });
+        }
+
+        let stream = |names: &[&str]| -> &'a [u8] {
+            streams
+                .iter()
+                .find(|s| names.contains(&s.name.as_str()))
+                .and_then(|s| slice_at(root, s.offset as usize, s.size as usize))
+                .unwrap_or(&[])
+        };
+        let strings = stream(&["#Strings"]);
+        let blob = stream(&["#Blob"]);
+        let guid = stream(&["#GUID"]);
+        let tables = stream(&["#~", "#-"]);
+
+        let mut metadata = Self {
+            version,
+            streams,
+            strings,
+            blob,
+            guid,
+            tables,
+            table_stream_version: (0, 0),
+            heap_sizes: 0,
+            row_counts: [0; TABLE_COUNT],
+            table_offsets: [0; TABLE_COUNT],
+        };
+        if !tables.is_empty() {
+            metadata.parse_table_header()?;
+        }
+        Ok(metadata)
+    }
+
+    fn parse_table_header(&mut self) -> io::Result<()> {
+        let truncated = || invalid_data("truncated metadata table stream");
+        self.table_stream_version = (
+            *self.tables.get(4).ok_or_else(truncated)?,
+            *self.tables.get(5).ok_or_else(truncated)?,
+        );
+        self.heap_sizes = *self.tables.get(6).ok_or_else(truncated)?;
+        let valid_lo = read_u32(self.tables, 8).ok_or_else(truncated)? as u64;
+        let valid_hi = read_u32(self.tables, 12).ok_or_else(truncated)? as u64;
+        let valid = valid_lo | (valid_hi << 32);
+
+        let mut position = 24;
+        for table in 0..64 {
+            if valid & (1 << table) == 0 {
+                continue;
+            }
+            let rows = read_u32(self.tables, position).ok_or_else(truncated)?;
+            position += 4;
+            if table < TABLE_COUNT {
+                self.row_counts[table] = rows;
+            } else {
+                return Err(invalid_data("unknown metadata table present"));
+            }
+        }
+        if self.heap_sizes & 0x40 != 0 {
+            position += 4;
+        }
+
+        for table in 0..TABLE_COUNT {
+            self.table_offsets[table] = position;
+            position = table_range(
+                position,
+                self.row_counts[table] as usize,
+                self.row_size(table),
+                usize::MAX,
+            )
+            .ok_or_else(truncated)?
+            .end;
+        }
+        if position > self.tables.len() {
+            return Err(truncated());
+        }
+        Ok(())
+    }
+
+    /// Runtime version string, e.g. `v4.0.30319`
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn streams(&self) -> &[MetadataStream] {
+        &self.streams
+    }
+
+    /// Major and minor version of the `#~` table stream, `(0, 0)` without one
+    pub fn table_stream_version(&self) -> (u8, u8) {
+        self.table_stream_version
+    }
+
+    /// `HeapSizes` bits of the `#~` stream: 0x01, 0x02 and 0x04 for 4-byte `#Strings`,
+    /// `#GUID` and `#Blob` indexes
+    pub fn heap_sizes(&self) -> u8 {
+        self.heap_sizes
+    }
+
+    /// Tables with rows, and their row counts, in table order
+    pub fn tables(&self) -> impl Iterator<Item = (usize, u32)> + '_ {
+        self.row_counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &rows)| rows != 0)
+            .map(|(table, &rows)| (table, rows))
+    }
+
+    /// Number of rows in metadata `table`
+    pub fn row_count(&self, table: usize) -> u32 {
+        self.row_counts.get(table).copied().unwrap_or(0)
+    }
+
+    /// Zero-terminated string from the `#Strings` heap
+    pub fn string(&self, index: u32) -> Option<String> {
+        let data = self.strings.get(index as usize..)?;
+        let end = data.iter().position(|&b| b == 0)?;
+        Some(String::from_utf8_lossy(&data[..end]).into_owned())
+    }
+
+    /// Length-prefixed entry from the `#Blob` heap
+    pub fn blob(&self, index: u32) -> Option<&'a [u8]> {
+        let data = self.blob.get(index as usize..)?;
+        let (length, header) = match *data.first()? {
+            b if b & 0x80 == 0 => ((b & 0x7F) as usize, 1),
+            b if b & 0xC0 == 0x80 => ((((b & 0x3F) as usize) << 8) | *data.get(1)? as usize, 2),
+            b => (
+                (((b & 0x1F) as usize) << 24)
+                    | ((*data.get(1)? as usize) << 16)
+                    | ((*data.get(2)? as usize) << 8)
+                    | *data.get(3)? as usize,
+                4,
+            ),
+        };
+        slice_at(data, header, length)
+    }
+
+    /// Entry of the `#GUID` heap (indices are 1-based)
+    pub fn guid(&self, index: u32) -> Option<Guid> {
+        let bytes = table_at(self.guid, (index as usize).checked_sub(1)? * 16, 1, 16)?;
+        let mut guid = [0u8; 16];
+        guid.copy_from_slice(bytes);
+        Some(Guid::from_bytes(guid))
+    }
+
+    /// Raw value of `column` in the 1-based `row` of `table`; indexes and coded indexes are not decoded.
+    pub fn column(&self, table: usize, row: u32, column: usize) -> Option<u32> {
+        if row == 0 || row > self.row_count(table) {
+            return None;
+        }
+        let schema = table_schema(table);
+        let offset = schema[..column]
+            .iter()
+            .map(|&c| self.column_size(c))
+            .sum::<usize>();
+        let start = table_range(
+            self.table_offsets[table],
+            row as usize - 1,
+            self.row_size(table),
+            usize::MAX,
+        )?
+        .end
+        .checked_add(offset)?;
+        match self.column_size(*schema.get(column)?) {
+            1 => self.tables.get(start).map(|&b| b as u32),
+            2 => read_u16(self.tables, start).map(u32::from),
+            _ => read_u32(self.tables, start),
+        }
+    }
+
+    /// Name of the `MethodDef` a token such as the managed entry point refers to
+    pub fn method_name(&self, token: u32) -> Option<String> {
+        if (token >> 24) as usize != TABLE_METHOD_DEF {
+            return None;
+        }
+        let name = self.column(TABLE_METHOD_DEF, token & 0x00FFFFFF, 3)?;
+        self.string(name)
+    }
+
+    fn row_size(&self, table: usize) -> usize {
+        table_schema(table)
+            .iter()
+            .map(|&column| self.column_size(column))
+            .sum()
+    }
+
+    fn column_size(&self, column: Column) -> usize {
+        match column {
+            Column::Fixed(size) => size,
+            Column::String => self.heap_index_size(0x01),
+            Column::Guid => self.heap_index_size(0x02),
+            Column::Blob => self.heap_index_size(0x04),
+            Column::Table(table) => {
+                if self.row_count(table) < 0x10000 {
+                    2
+                } else {
+                    4
+                }
+            }
+            Column::Coded(coded) => {
+                let max_rows = coded
+                    .tables()
+                    .iter()
+                    .flatten()
+                    .map(|&table| self.row_count(table))
+                    .max()
+                    .unwrap_or(0);
+                if max_rows < (1 << (16 - coded.tag_bits())) {
+                    2
+                } else {
+                    4
+                }
+            }
+        }
+    }
+
+    fn heap_index_size(&self, flag: u8) -> usize {
+        if self.heap_sizes & flag != 0 {
+            4
+        } else {
+            2
+        }
+    }
+
+    /// Splits an `Implementation` coded index into table and 1-based row; `None` for a null index.
+    fn decode_implementation(&self, value: u32) -> Option<(usize, u32)> {
+        let coded = CodedIndex::Implementation;
+        let bits = coded.tag_bits();
+        let row = value >> bits;
+        if row == 0 {
+            return None;
+        }
+        let table = (*coded.tables().get((value & ((1 << bits) - 1)) as usize)?)?;
+        Some((table, row))
+    }
+}
+
+/// Where the data of a manifest resource lives
+#[derive(Debug)]
+pub enum ManagedResourceData<'a> {
+    /// Embedded in the assembly's resources directory
+    Embedded(&'a [u8]),
+    /// Stored in a separate file of a multi-file assembly
+    File(String),
+    /// Provided by another assembly
+    AssemblyRef(String),
+}
+
+/// Entry of the `ManifestResource` metadata table
+#[derive(Debug)]
+pub struct ManagedResource<'a> {
+    name: String,
+    flags: u32,
+    data: ManagedResourceData<'a>,
+}
+
+impl<'a> ManagedResource<'a> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// `ManifestResourceAttributes`: 1 = public, 2 = private
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    pub fn data(&self) -> &ManagedResourceData<'a> {
+        &self.data
+    }
+
+    /// Whether the embedded data is a `System.Resources` `.resources` file
+    pub fn is_resources_file(&self) -> bool {
+        match self.data {
+            ManagedResourceData::Embedded(data) => read_u32(data, 0) == Some(RESOURCES_FILE_MAGIC),
+            _ => false,
+        }
+    }
+}
+
+impl PortExe {
+    /// CLR runtime header of a managed image
+    pub fn clr_header(&self) -> Option<ClrHeader> {
+        let directory = self
+            .optional_header()?
+            .data_directory(IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR)?;
+        let rva = directory.virtual_address().into_value();
+        if rva == 0 {
+            return None;
+        }
+        ClrHeader::read(self.data_at_rva(rva, 72)?)
+    }
+
+    /// Metadata root of a managed image
+    pub fn metadata(&self) -> io::Result<Option<Metadata<'_>>> {
+        let clr_header = match self.clr_header() {
+            Some(clr_header) => clr_header,
+            None => return Ok(None),
+        };
+        let metadata = clr_header.metadata();
+        let root = self
+            .data_at_rva(metadata.virtual_address(), metadata.size())
+            .ok_or_else(|| invalid_data("metadata is not backed by file data"))?;
+        Metadata::parse(root).map(Some)
+    }
+
+    /// Lists the assembly's manifest resources with embedded data resolved.
+    pub fn managed_resources(&self) -> io::Result<Vec<ManagedResource<'_>>> {
+        let (clr_header, metadata) = match (self.clr_header(), self.metadata()?) {
+            (Some(clr_header), Some(metadata)) => (clr_header, metadata),
+            _ => return Ok(Vec::new()),
+        };
+        let resources = clr_header.resources();
+        let resources = self
+            .data_at_rva(resources.virtual_address(), resources.size())
+            .unwrap_or(&[]);
+
+        let mut managed_resources = Vec::new();
+        for row in 1..=metadata.row_count(TABLE_MANIFEST_RESOURCE) {
+            let column = |index| {
+                metadata
+                    .column(TABLE_MANIFEST_RESOURCE, row, index)
+                    .ok_or_else(|| invalid_data("truncated ManifestResource row"))
+            };
+            let offset = column(0)? as usize;
+            let flags = column(1)?;
+            let name = metadata.string(column(2)?).unwrap_or_default();
+            let data = match metadata.decode_implementation(column(3)?) {
+                None => {
+                    let length = read_u32(resources, offset)
+                        .ok_or_else(|| invalid_data("manifest resource outside resources"))?;
+                    let start = offset + 4;
+                    let data = resources
+                        .get(start..start.saturating_add(length as usize))
+                        .ok_or_else(|| invalid_data("manifest resource outside resources"))?;
+                    ManagedResourceData::Embedded(data)
+                }
+                Some((TABLE_FILE, file)) => ManagedResourceData::File(
+                    metadata
+                        .column(TABLE_FILE, file, 1)
+                        .and_then(|index| metadata.string(index))
+                        .unwrap_or_default(),
+                ),
+                Some((_, assembly_ref)) => ManagedResourceData::AssemblyRef(
+                    metadata
+                        .column(TABLE_ASSEMBLY_REF, assembly_ref, 6)
+                        .and_then(|index| metadata.string(index))
+                        .unwrap_or_default(),
+                ),
+            };
+            managed_resources.push(ManagedResource { name, flags, data });
+        }
+        Ok(managed_resources)
+    }
+}
+
+/// `IMAGE_COR_VTABLEFIXUP` entry with its slots resolved
+#[derive(Debug, Clone)]
+pub struct VTableFixup {
+    rva: u32,
+    count: u16,
+    kind: u16,
+    slots: Vec<VTableSlot>,
+}
+
+impl VTableFixup {
+    /// RVA of the first slot
+    pub fn rva(&self) -> u32 {
+        self.rva
+    }
+
+    pub fn count(&self) -> u16 {
+        self.count
+    }
+
+    /// `COR_VTABLE_*` flags
+    pub fn kind(&self) -> u16 {
+        self.kind
+    }
+
+    /// Slot is called from unmanaged code through a thunk
+    pub fn from_unmanaged(&self) -> bool {
+        self.kind & COR_VTABLE_FROM_UNMANAGED != 0
+    }
+
+    pub fn slots(&self) -> &[VTableSlot] {
+        &self.slots
+    }
+}
+
+/// Slot of a vtable fixup, holding the method token the runtime patches into a thunk
+#[derive(Debug, Clone)]
+pub struct VTableSlot {
+    rva: u32,
+    token: u32,
+    method_name: Option<String>,
+}
+
+impl VTableSlot {
+    pub fn rva(&self) -> u32 {
+        self.rva
+    }
+
+    pub fn token(&self) -> u32 {
+        self.token
+    }
+
+    /// Name of the `MethodDef` the token refers to
+    pub fn method_name(&self) -> Option<&str> {
+        self.method_name.as_deref()
+    }
+}
+
+/// Native export of a managed image and the vtable slot its stub jumps through
+#[derive(Debug, Clone)]
+pub struct UnmanagedExport {
+    export: Export,
+    slot: Option<VTableSlot>,
+}
+
+impl UnmanagedExport {
+    pub fn export(&self) -> &Export {
+        &self.export
+    }
+
+    /// Slot referenced by the export stub's indirect jump, if it could be decoded
+    pub fn slot(&self) -> Option<&VTableSlot> {
+        self.slot.as_ref()
+    }
+}
+
+impl PortExe {
+    /// Managed image that also carries native code (C++/CLI, IJW).
+    ///
+    /// The CLR header must clear `COMIMAGE_FLAGS_ILONLY` and the image must start in
+    /// native code: either the header sets `COMIMAGE_FLAGS_NATIVE_ENTRYPOINT`, or
+    /// `AddressOfEntryPoint` leads somewhere other than the `jmp [_CorExeMain]` stub
+    /// through which IL-only images enter the runtime.
+    pub fn is_mixed_mode(&self) -> bool {
+        let clr_header = match self.clr_header() {
+            Some(clr_header) => clr_header,
+            None => return false,
+        };
+        if clr_header.il_only() {
+            return false;
+        }
+        clr_header.native_entry_point() || self.enters_native_code()
+    }
+
+    /// `AddressOfEntryPoint` is set and does not point at a `jmp [mem]` stub
+    fn enters_native_code(&self) -> bool {
+        let entry_point = match self.optional_header() {
+            Some(header) => header.address_of_entry_point(),
+            None => return false,
+        };
+        entry_point != 0
+            && self
+                .data_at_rva(entry_point, 2)
+                .map_or(true, |stub| stub != [0xFF, 0x25])
+    }
+
+    pub fn vtable_fixups(&self) -> io::Result<Vec<VTableFixup>> {
+        let clr_header = match self.clr_header() {
+            Some(clr_header) => clr_header,
+            None => return Ok(Vec::new()),
+        };
+        let directory = clr_header.vtable_fixups();
+        if directory.virtual_address() == 0 || directory.size() == 0 {
+            return Ok(Vec::new());
+        }
+        let data = self
+            .data_at_rva(directory.virtual_address(), directory.size())
+            .ok_or_else(|| invalid_data("vtable fixups are not backed by file data"))?;
+        let metadata = self.metadata()?;
+
+        let mut fixups = Vec::new();
+        for entry in data.chunks_exact(8) {
+            let rva = read_u32(entry, 0).unwrap_or(0);
+            let count = read_u16(entry, 4).unwrap_or(0);
+            let kind = read_u16(entry, 6).unwrap_or(0);
+            let slot_size = if kind & COR_VTABLE_64BIT != 0 { 8 } else { 4 };
+            let slots = (0..count as u32)
+                .filter_map(|index| {
+                    let slot_rva = rva.checked_add(index * slot_size)?;
+                    let token = read_u32(self.data_at_rva(slot_rva, 4)?, 0)?;
+                    let method_name = metadata.as_ref().and_then(|m| m.method_name(token));
+                    Some(VTableSlot {
+                        rva: slot_rva,
+                        token,
+                        method_name,
+                    })
+                })
+                .collect();
+            fixups.push(VTableFixup {
+                rva,
+                count,
+                kind,
+                slots,
+            });
+        }
+        Ok(fixups)
+    }
+
+    /// Lists native exports of a managed image, resolving `jmp [slot]` stubs to vtable slots.
+    pub fn unmanaged_exports(&self) -> io::Result<Vec<UnmanagedExport>> {
+        if self.clr_header().is_none() {
+            return Ok(Vec::new());
+        }
+        let slots = self
+            .vtable_fixups()?
+            .into_iter()
+            .flat_map(|fixup| fixup.slots)
+            .collect::<Vec<_>>();
+        let image_base = self.optional_header().map_or(0, |h| h.image_base());
+        let is_x64 = self
+            .optional_header()
+            .map_or(false, |h| h.image_type() == ImageType::X64);
+
+        Ok(self
+            .exports()?
+            .into_iter()
+            .map(|export| {
+                let slot_rva = self
+                    .data_at_rva(export.rva(), 6)
+                    .filter(|stub| stub[0] == 0xFF && stub[1] == 0x25)
+                    .and_then(|stub| {
+                        let operand = read_u32(stub, 2)?;
+                        if is_x64 {
+                            Some(export.rva().wrapping_add(6).wrapping_add(operand))
+                        } else {
+                            va_to_rva(operand as u64, image_base)
+                        }
+                    });
+                let slot = slot_rva.and_then(|rva| slots.iter().find(|s| s.rva == rva).cloned());
+                UnmanagedExport { export, slot }
+            })
+            .collect())
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
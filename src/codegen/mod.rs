@@ -0,0 +1,159 @@
+use crate::endian::{le_u16, le_u32};
+use crate::port_exe::PortExe;
+use std::fmt::Write;
+use std::io;
+
+/// Output language of [`PortExe::codegen`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CodegenLanguage {
+    /// A self-contained Rust module of type definitions and `static` tables
+    Rust,
+}
+
+/// Type definitions every generated Rust module starts with
+const RUST_TYPES: &str = "#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Section {
+    pub name: &'static [u8],
+    pub virtual_address: u32,
+    pub virtual_size: u32,
+    pub size_of_raw_data: u32,
+    pub characteristics: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Export {
+    pub ordinal: u32,
+    pub name: Option<&'static [u8]>,
+    pub rva: u32,
+    pub forwarder: Option<&'static str>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Import {
+    pub dll: &'static str,
+    pub name: Option<&'static [u8]>,
+    pub hint: u16,
+    pub ordinal: Option<u16>,
+    pub iat_rva: u32,
+}
+";
+
+impl PortExe {
+    /// Generates source code describing the headers, sections, exports and imports of
+    /// the image, so tools can compile knowledge of a reference binary into themselves.
+    ///
+    /// Names are emitted as byte strings, exactly as stored in the file.
+    pub fn codegen(&self, language: CodegenLanguage) -> io::Result<String> {
+        match language {
+            CodegenLanguage::Rust => self.rust_module(),
+        }
+    }
+
+    fn rust_module(&self) -> io::Result<String> {
+        let exports = self.exports()?;
+        let imports = self.all_imports()?;
+        let dll_name = self
+            .export_directory()?
+            .map(|directory| directory.name().to_string());
+
+        let mut out = String::new();
+        let _ = writeln!(out, "// Generated by pexp; do not edit.\n");
+        out.push_str(RUST_TYPES);
+        out.push('\n');
+        let _ = writeln!(
+            out,
+            "pub const MACHINE: u16 = {:#06x};",
+            le_u16(self.file_header().machine().raw_bytes())
+        );
+        let _ = writeln!(
+            out,
+            "pub const TIME_DATE_STAMP: u32 = {:#010x};",
+            self.file_header().time_date_stamp().into_value()
+        );
+        if let Some(header) = self.optional_header() {
+            let _ = writeln!(
+                out,
+                "pub const IMAGE_BASE: u64 = {:#x};",
+                header.image_base()
+            );
+            let _ = writeln!(
+                out,
+                "pub const ENTRY_POINT: u32 = {:#x};",
+                header.address_of_entry_point()
+            );
+            let _ = writeln!(
+                out,
+                "pub const SIZE_OF_IMAGE: u32 = {:#x};",
+                header.size_of_image()
+            );
+        }
+        if let Some(name) = dll_name {
+            let _ = writeln!(out, "pub const DLL_NAME: &str = {:?};", name);
+        }
+
+        out.push_str("\npub static SECTIONS: &[Section] = &[\n");
+        for section in self.section_headers() {
+            let _ = writeln!(
+                out,
+                "    Section {{ name: {}, virtual_address: {:#x}, virtual_size: {:#x}, \
+                 size_of_raw_data: {:#x}, characteristics: {:#010x} }},",
+                byte_string(self.section_name(section).as_bytes()),
+                section.virtual_address().into_value(),
+                section.virtual_size().into_value(),
+                section.size_of_raw_data().into_value(),
+                le_u32(section.characteristics().raw_bytes()),
+            );
+        }
+        out.push_str("];\n\npub static EXPORTS: &[Export] = &[\n");
+        for export in &exports {
+            let _ = writeln!(
+                out,
+                "    Export {{ ordinal: {}, name: {}, rva: {:#x}, forwarder: {} }},",
+                export.ordinal(),
+                optional(export.name().map(|name| byte_slice(name.as_bytes()))),
+                export.rva(),
+                optional(
+                    export
+                        .forwarder()
+                        .map(|forwarder| format!("{:?}", forwarder))
+                ),
+            );
+        }
+        out.push_str("];\n\npub static IMPORTS: &[Import] = &[\n");
+        for descriptor in &imports {
+            for import in descriptor.imports() {
+                let _ = writeln!(
+                    out,
+                    "    Import {{ dll: {:?}, name: {}, hint: {}, ordinal: {}, iat_rva: {:#x} }},",
+                    descriptor.dll_name(),
+                    optional(import.name().map(|name| byte_slice(name.as_bytes()))),
+                    import.hint(),
+                    optional(import.ordinal().map(|ordinal| ordinal.to_string())),
+                    import.iat_rva(),
+                );
+            }
+        }
+        out.push_str("];\n");
+        Ok(out)
+    }
+}
+
+/// Rust byte string literal of `bytes`
+fn byte_string(bytes: &[u8]) -> String {
+    let escaped: String = bytes
+        .iter()
+        .flat_map(|&byte| std::ascii::escape_default(byte))
+        .map(char::from)
+        .collect();
+    format!("b\"{}\"", escaped)
+}
+
+/// Byte string cast to `&[u8]`, which `Some` does not coerce to on its own
+fn byte_slice(bytes: &[u8]) -> String {
+    format!("{} as &[u8]", byte_string(bytes))
+}
+
+fn optional(value: Option<String>) -> String {
+    value.map_or_else(|| "None".to_string(), |value| format!("Some({})", value))
+}
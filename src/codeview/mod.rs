@@ -0,0 +1,262 @@
+use crate::arith::{align_up, checked_range};
+use crate::port_exe::PortExe;
+use crate::section_header::SectionHeaderWrapper;
+use crate::{read_u16, read_u32};
+use std::io;
+
+/// Signature of C13 `.debug$S` and `.debug$T` sections
+pub const CV_SIGNATURE_C13: u32 = 4;
+
+pub const DEBUG_S_SYMBOLS: u32 = 0xF1;
+pub const DEBUG_S_LINES: u32 = 0xF2;
+pub const DEBUG_S_STRINGTABLE: u32 = 0xF3;
+pub const DEBUG_S_FILECHKSMS: u32 = 0xF4;
+/// Set on subsections the linker should skip
+pub const DEBUG_S_IGNORE: u32 = 0x80000000;
+
+pub const S_OBJNAME: u16 = 0x1101;
+pub const S_COMPILE2: u16 = 0x1116;
+pub const S_COMPILE3: u16 = 0x113C;
+
+/// Type record referring to the PDB that holds the types of a `/Zi` object
+pub const LF_TYPESERVER2: u16 = 0x1515;
+
+const DEBUG_SYMBOLS_SECTION: &str = ".debug$S";
+const DEBUG_TYPES_SECTION: &str = ".debug$T";
+const DEBUG_PRECOMPILED_TYPES_SECTION: &str = ".debug$P";
+
+/// Compiler identification from an `S_COMPILE2` or `S_COMPILE3` record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompilerInfo {
+    language: u8,
+    machine: u16,
+    frontend_version: [u16; 4],
+    backend_version: [u16; 4],
+    version: String,
+}
+
+impl CompilerInfo {
+    /// `CV_CFL_LANG` value: 0 = C, 1 = C++, 7 = MASM, ...
+    pub fn language(&self) -> u8 {
+        self.language
+    }
+
+    /// `CV_CPU_TYPE_e` value of the target
+    pub fn machine(&self) -> u16 {
+        self.machine
+    }
+
+    /// Major, minor, build and QFE version of the front end
+    pub fn frontend_version(&self) -> [u16; 4] {
+        self.frontend_version
+    }
+
+    /// Major, minor, build and QFE version of the back end
+    pub fn backend_version(&self) -> [u16; 4] {
+        self.backend_version
+    }
+
+    /// Compiler version string, e.g. `Microsoft (R) Optimizing Compiler`
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+}
+
+/// Provenance information from the CodeView sections of an object file
+#[derive(Debug, Clone, Default)]
+pub struct ObjectDebugInfo {
+    object_name: Option<String>,
+    compilers: Vec<CompilerInfo>,
+    source_files: Vec<String>,
+    type_records: usize,
+    type_server: Option<String>,
+    precompiled_types: bool,
+}
+
+impl ObjectDebugInfo {
+    /// Path of the object file recorded by `S_OBJNAME`
+    pub fn object_name(&self) -> Option<&str> {
+        self.object_name.as_deref()
+    }
+
+    pub fn compilers(&self) -> &[CompilerInfo] {
+        &self.compilers
+    }
+
+    /// Source files listed in the file checksum subsections
+    pub fn source_files(&self) -> &[String] {
+        &self.source_files
+    }
+
+    /// Number of type records in `.debug$T`
+    pub fn type_records(&self) -> usize {
+        self.type_records
+    }
+
+    /// PDB named by an `LF_TYPESERVER2` record, for objects compiled with `/Zi`
+    pub fn type_server(&self) -> Option<&str> {
+        self.type_server.as_deref()
+    }
+
+    /// Object carries a `.debug$P` precompiled header type stream
+    pub fn precompiled_types(&self) -> bool {
+        self.precompiled_types
+    }
+}
+
+impl PortExe {
+    /// Reads the `.debug$S` and `.debug$T` sections of an object file far enough to
+    /// identify the compiler, the source files and the kind of type information.
+    ///
+    /// Returns `None` when the file has no CodeView sections.
+    pub fn object_debug_info(&self) -> io::Result<Option<ObjectDebugInfo>> {
+        let mut info = ObjectDebugInfo::default();
+        let mut found = false;
+        for section in self.section_headers() {
+            let name = section.name().into_value();
+            match name.as_str().unwrap_or_default() {
+                DEBUG_SYMBOLS_SECTION => {
+                    found = true;
+                    self.read_symbol_section(section, &mut info)?;
+                }
+                DEBUG_TYPES_SECTION => {
+                    found = true;
+                    read_type_section(self.section_data(section), &mut info)?;
+                }
+                DEBUG_PRECOMPILED_TYPES_SECTION => {
+                    found = true;
+                    info.precompiled_types = true;
+                }
+                _ => {}
+            }
+        }
+        Ok(found.then(|| info))
+    }
+
+    fn read_symbol_section(
+        &self,
+        section: &SectionHeaderWrapper,
+        info: &mut ObjectDebugInfo,
+    ) -> io::Result<()> {
+        let data = self.section_data(section);
+        if read_u32(data, 0) != Some(CV_SIGNATURE_C13) {
+            return Err(invalid_data("unsupported .debug$S signature"));
+        }
+
+        let mut string_table: &[u8] = &[];
+        let mut file_checksums: Vec<&[u8]> = Vec::new();
+        for (kind, subsection) in subsections(&data[4..]) {
+            match kind & !DEBUG_S_IGNORE {
+                DEBUG_S_SYMBOLS => read_symbols(subsection, info),
+                DEBUG_S_STRINGTABLE => string_table = subsection,
+                DEBUG_S_FILECHKSMS => file_checksums.push(subsection),
+                _ => {}
+            }
+        }
+
+        for checksums in file_checksums {
+            let mut position = 0;
+            while position + 6 <= checksums.len() {
+                let name_offset = read_u32(checksums, position).unwrap_or(0) as usize;
+                let checksum_size = checksums[position + 4] as usize;
+                if let Some(name) = string_table.get(name_offset..).and_then(c_string) {
+                    if !info.source_files.contains(&name) {
+                        info.source_files.push(name);
+                    }
+                }
+                position = match align_up((position + 6 + checksum_size) as u64, 4) {
+                    Some(next) => next as usize,
+                    None => break,
+                };
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_type_section(data: &[u8], info: &mut ObjectDebugInfo) -> io::Result<()> {
+    if read_u32(data, 0) != Some(CV_SIGNATURE_C13) {
+        return Err(invalid_data("unsupported .debug$T signature"));
+    }
+    for (kind, record) in records(&data[4..]) {
+        info.type_records += 1;
+        if kind == LF_TYPESERVER2 {
+            // GUID (16 bytes) and age precede the PDB path
+            info.type_server = record.get(20..).and_then(c_string);
+        }
+    }
+    Ok(())
+}
+
+fn read_symbols(data: &[u8], info: &mut ObjectDebugInfo) {
+    for (kind, record) in records(data) {
+        match kind {
+            S_OBJNAME => info.object_name = record.get(4..).and_then(c_string),
+            S_COMPILE3 => {
+                let version = |index: usize| read_u16(record, 6 + index * 2).unwrap_or(0);
+                info.compilers.push(CompilerInfo {
+                    language: record.first().copied().unwrap_or(0),
+                    machine: read_u16(record, 4).unwrap_or(0),
+                    frontend_version: [version(0), version(1), version(2), version(3)],
+                    backend_version: [version(4), version(5), version(6), version(7)],
+                    version: record.get(22..).and_then(c_string).unwrap_or_default(),
+                });
+            }
+            S_COMPILE2 => {
+                let version = |index: usize| read_u16(record, 6 + index * 2).unwrap_or(0);
+                info.compilers.push(CompilerInfo {
+                    language: record.first().copied().unwrap_or(0),
+                    machine: read_u16(record, 4).unwrap_or(0),
+                    frontend_version: [version(0), version(1), version(2), 0],
+                    backend_version: [version(3), version(4), version(5), 0],
+                    version: record.get(18..).and_then(c_string).unwrap_or_default(),
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Splits a `.debug$S` body into `(kind, data)` subsections
+fn subsections(data: &[u8]) -> Vec<(u32, &[u8])> {
+    let mut subsections = Vec::new();
+    let mut position = 0;
+    while let (Some(kind), Some(length)) = (read_u32(data, position), read_u32(data, position + 4))
+    {
+        let range = match checked_range(position + 8, length as usize, data.len()) {
+            Some(range) => range,
+            None => break,
+        };
+        position = match align_up(range.end as u64, 4) {
+            Some(next) => next as usize,
+            None => break,
+        };
+        subsections.push((kind, &data[range]));
+    }
+    subsections
+}
+
+/// Splits a symbol or type stream into `(kind, data)` records with 16-bit length prefixes
+fn records(data: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut records = Vec::new();
+    let mut position = 0;
+    while let (Some(length), Some(kind)) = (read_u16(data, position), read_u16(data, position + 2))
+    {
+        let end = position + 2 + length as usize;
+        if length < 2 || end > data.len() {
+            break;
+        }
+        records.push((kind, &data[position + 4..end]));
+        position = end;
+    }
+    records
+}
+
+fn c_string(data: &[u8]) -> Option<String> {
+    let end = data.iter().position(|&b| b == 0)?;
+    Some(String::from_utf8_lossy(&data[..end]).into_owned())
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,164 @@
+use crate::pe_string::PeString;
+use crate::port_exe::PortExe;
+use crate::symbol::CoffSymbol;
+use std::io;
+
+/// Only one definition of the symbol may exist
+pub const IMAGE_COMDAT_SELECT_NODUPLICATES: u8 = 1;
+/// Any definition may be linked; the rest are discarded
+pub const IMAGE_COMDAT_SELECT_ANY: u8 = 2;
+/// Definitions must have the same size
+pub const IMAGE_COMDAT_SELECT_SAME_SIZE: u8 = 3;
+/// Definitions must have the same checksum
+pub const IMAGE_COMDAT_SELECT_EXACT_MATCH: u8 = 4;
+/// Section is linked if and only if the associated section is linked
+pub const IMAGE_COMDAT_SELECT_ASSOCIATIVE: u8 = 5;
+/// The largest definition is linked
+pub const IMAGE_COMDAT_SELECT_LARGEST: u8 = 6;
+/// The newest definition is linked
+pub const IMAGE_COMDAT_SELECT_NEWEST: u8 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComdatSelection {
+    NoDuplicates,
+    Any,
+    SameSize,
+    ExactMatch,
+    Associative,
+    Largest,
+    Newest,
+    Unknown(u8),
+}
+
+impl From<u8> for ComdatSelection {
+    fn from(value: u8) -> Self {
+        match value {
+            IMAGE_COMDAT_SELECT_NODUPLICATES => Self::NoDuplicates,
+            IMAGE_COMDAT_SELECT_ANY => Self::Any,
+            IMAGE_COMDAT_SELECT_SAME_SIZE => Self::SameSize,
+            IMAGE_COMDAT_SELECT_EXACT_MATCH => Self::ExactMatch,
+            IMAGE_COMDAT_SELECT_ASSOCIATIVE => Self::Associative,
+            IMAGE_COMDAT_SELECT_LARGEST => Self::Largest,
+            IMAGE_COMDAT_SELECT_NEWEST => Self::Newest,
+            value => Self::Unknown(value),
+        }
+    }
+}
+
+/// COMDAT section of an object file
+#[derive(Debug, Clone)]
+pub struct Comdat {
+    section_number: u16,
+    section_name: PeString,
+    selection: ComdatSelection,
+    symbol: Option<String>,
+    associated_section: Option<u16>,
+    associated_symbol: Option<String>,
+    length: u32,
+    checksum: u32,
+}
+
+impl Comdat {
+    /// One-based index of the section
+    pub fn section_number(&self) -> u16 {
+        self.section_number
+    }
+
+    /// Name of the section, read from the string table for `/offset` names
+    pub fn section_name(&self) -> &PeString {
+        &self.section_name
+    }
+
+    pub fn selection(&self) -> ComdatSelection {
+        self.selection
+    }
+
+    /// COMDAT symbol naming the section; associative sections usually have none
+    pub fn symbol(&self) -> Option<&str> {
+        self.symbol.as_deref()
+    }
+
+    /// One-based index of the section this one is associated with
+    pub fn associated_section(&self) -> Option<u16> {
+        self.associated_section
+    }
+
+    /// COMDAT symbol of the associated section, following chains of associative sections
+    pub fn associated_symbol(&self) -> Option<&str> {
+        self.associated_symbol.as_deref()
+    }
+
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    pub fn checksum(&self) -> u32 {
+        self.checksum
+    }
+}
+
+impl PortExe {
+    /// Lists the COMDAT sections of an object file with their selection kind and symbols.
+    pub fn comdats(&self) -> io::Result<Vec<Comdat>> {
+        let symbols = self.coff_symbols()?;
+        let mut comdats = Vec::new();
+        for (index, section) in self.section_headers().iter().enumerate() {
+            if !section.characteristics().value().lnk_comdat() {
+                continue;
+            }
+            let number = index as i16 + 1;
+            let definition = match symbols
+                .iter()
+                .filter(|symbol| symbol.section_number() == number)
+                .find_map(CoffSymbol::section_definition)
+            {
+                Some(definition) => definition,
+                None => continue,
+            };
+            let selection = ComdatSelection::from(definition.selection());
+            let associated_section =
+                (selection == ComdatSelection::Associative).then(|| definition.number());
+            comdats.push(Comdat {
+                section_number: number as u16,
+                section_name: self.section_name(section),
+                selection,
+                symbol: comdat_symbol(&symbols, number).map(str::to_string),
+                associated_section,
+                associated_symbol: None,
+                length: definition.length(),
+                checksum: definition.checksum(),
+            });
+        }
+
+        for index in 0..comdats.len() {
+            let mut target = comdats[index].associated_section;
+            // Associative sections may chain; bound the walk by the number of COMDATs.
+            for _ in 0..comdats.len() {
+                let leader = match comdats.iter().find(|c| Some(c.section_number) == target) {
+                    Some(leader) => leader,
+                    None => break,
+                };
+                if leader.associated_section.is_none() {
+                    comdats[index].associated_symbol = leader.symbol.clone();
+                    break;
+                }
+                target = leader.associated_section;
+            }
+            if comdats[index].associated_symbol.is_none() {
+                if let Some(number) = comdats[index].associated_section {
+                    comdats[index].associated_symbol =
+                        comdat_symbol(&symbols, number as i16).map(str::to_string);
+                }
+            }
+        }
+        Ok(comdats)
+    }
+}
+
+/// First symbol defined in the section after its section symbol
+fn comdat_symbol(symbols: &[CoffSymbol], number: i16) -> Option<&str> {
+    symbols
+        .iter()
+        .find(|symbol| symbol.section_number() == number && !symbol.is_section_definition())
+        .map(CoffSymbol::name)
+}
@@ -0,0 +1,322 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name of the configuration file, looked up in the working directory and its parents
+/// and in the user configuration directory
+pub const CONFIG_FILE_NAME: &str = "pexp.toml";
+
+/// When the CLI colors its output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color when writing to a terminal or when `CLICOLOR_FORCE` is set, unless
+    /// `NO_COLOR` is set
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether output should be colored; `terminal` tells [`ColorChoice::Auto`] whether
+    /// the output goes to a terminal.
+    pub fn enabled(self, terminal: bool) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::Auto => {
+                let forced = env::var_os("CLICOLOR_FORCE").map_or(false, |value| value != "0");
+                env::var_os("NO_COLOR").is_none() && (terminal || forced)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ColorChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Auto => "auto",
+            Self::Always => "always",
+            Self::Never => "never",
+        })
+    }
+}
+
+/// Persistent settings of the CLI, read from `pexp.toml` files.
+///
+/// The files hold top-level keys in a subset of TOML: strings, booleans and arrays of
+/// strings on one line, and `#` comments.
+///
+/// ```toml
+/// format = "x64dbg"                # default --format of annotate and iat-map
+/// analyses = ["security"]          # analyses scan runs without --analyses
+/// search-paths = ["../redist"]     # directories load-order adds to the PATH search
+/// policy = "store-apis.txt"        # allowlist appcontainer uses without --allowlist
+/// messages = "de.txt"              # catalog translating the findings scan reports
+/// color = "auto"                   # auto, always or never
+/// ```
+///
+/// Relative paths are relative to the file they are set in. Flags given on the command
+/// line take precedence over both files, and the project file over the user file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    format: Option<String>,
+    analyses: Option<Vec<String>>,
+    search_paths: Vec<PathBuf>,
+    policy: Option<PathBuf>,
+    messages: Option<PathBuf>,
+    color: Option<ColorChoice>,
+    sources: Vec<PathBuf>,
+}
+
+impl Config {
+    /// Default output format of the commands that print scripts
+    pub fn format(&self) -> Option<&str> {
+        self.format.as_deref()
+    }
+
+    /// Analyses `scan` runs by default, `None` if not set
+    pub fn analyses(&self) -> Option<&[String]> {
+        self.analyses.as_deref()
+    }
+
+    /// Directories searched for DLLs after those given on the command line, project
+    /// directories first
+    pub fn search_paths(&self) -> &[PathBuf] {
+        &self.search_paths
+    }
+
+    /// Policy file, such as the API allowlist of `appcontainer`
+    pub fn policy(&self) -> Option<&Path> {
+        self.policy.as_deref()
+    }
+
+    /// Message catalog findings are reported through, English if not set
+    pub fn messages(&self) -> Option<&Path> {
+        self.messages.as_deref()
+    }
+
+    pub fn color(&self) -> ColorChoice {
+        self.color.unwrap_or(ColorChoice::Auto)
+    }
+
+    /// Files the settings were read from, lowest precedence first
+    pub fn sources(&self) -> &[PathBuf] {
+        &self.sources
+    }
+
+    /// Parses the contents of a configuration file stored in `dir`.
+    pub fn parse(text: &str, dir: &Path) -> io::Result<Self> {
+        let mut config = Self::default();
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: &str| invalid_data(&format!("line {}: {}", index + 1, message));
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') {
+                return Err(error("tables are not supported"));
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected key = value"))?;
+            let key = key.trim().trim_matches('"');
+            let value = Value::parse(value.trim()).map_err(|message| error(&message))?;
+            match key {
+                "format" => config.format = Some(value.string(key).map_err(|m| error(&m))?),
+                "analyses" => config.analyses = Some(value.strings(key).map_err(|m| error(&m))?),
+                "search-paths" => {
+                    config.search_paths = value
+                        .strings(key)
+                        .map_err(|m| error(&m))?
+                        .iter()
+                        .map(|path| dir.join(path))
+                        .collect()
+                }
+                "policy" => {
+                    config.policy = Some(dir.join(value.string(key).map_err(|m| error(&m))?))
+                }
+                "messages" => {
+                    config.messages = Some(dir.join(value.string(key).map_err(|m| error(&m))?))
+                }
+                "color" => {
+                    config.color = Some(match value.string(key).map_err(|m| error(&m))?.as_str() {
+                        "auto" => ColorChoice::Auto,
+                        "always" => ColorChoice::Always,
+                        "never" => ColorChoice::Never,
+                        other => return Err(error(&format!("unknown color choice {}", other))),
+                    })
+                }
+                _ => return Err(error(&format!("unknown key {}", key))),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Reads the configuration file at `path`.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let mut config = Self::parse(&text, dir)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        config.sources.push(path.to_path_buf());
+        Ok(config)
+    }
+
+    /// Settings of `over` where it has them, these otherwise. Search paths of `over`
+    /// come before these.
+    pub fn merge(self, over: Self) -> Self {
+        let mut search_paths = over.search_paths;
+        search_paths.extend(self.search_paths);
+        let mut sources = self.sources;
+        sources.extend(over.sources);
+        Self {
+            format: over.format.or(self.format),
+            analyses: over.analyses.or(self.analyses),
+            search_paths,
+            policy: over.policy.or(self.policy),
+            messages: over.messages.or(self.messages),
+            color: over.color.or(self.color),
+            sources,
+        }
+    }
+
+    /// Merges the user configuration file with the closest project file in `dir` or
+    /// its parents. Missing files are skipped.
+    pub fn discover(dir: &Path) -> io::Result<Self> {
+        let mut config = Self::default();
+        for path in user_config_path()
+            .into_iter()
+            .chain(project_config_path(dir))
+        {
+            config = config.merge(Self::load(&path)?);
+        }
+        Ok(config)
+    }
+}
+
+/// `pexp.toml` in `dir` or the closest of its parents that has one
+pub fn project_config_path(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// `pexp.toml` in the user configuration directory: `%APPDATA%\pexp` on Windows,
+/// `$XDG_CONFIG_HOME/pexp` or `~/.config/pexp` elsewhere, if it exists
+pub fn user_config_path() -> Option<PathBuf> {
+    let dir = if cfg!(windows) {
+        PathBuf::from(env::var_os("APPDATA")?)
+    } else {
+        match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        }
+    };
+    Some(dir.join("pexp").join(CONFIG_FILE_NAME)).filter(|path| path.is_file())
+}
+
+/// Value of a key
+enum Value {
+    String(String),
+    /// No key takes one yet
+    Boolean,
+    Array(Vec<String>),
+}
+
+impl Value {
+    fn parse(text: &str) -> Result<Self, String> {
+        if text == "true" || text == "false" {
+            return Ok(Self::Boolean);
+        }
+        if let Some(items) = text.strip_prefix('[') {
+            let items = items
+                .strip_suffix(']')
+                .ok_or_else(|| "arrays must end on the line they start".to_string())?;
+            let mut strings = Vec::new();
+            let mut rest = items.trim();
+            while !rest.is_empty() {
+                let (string, tail) = parse_string(rest)?;
+                strings.push(string);
+                rest = tail.trim_start();
+                rest = match rest.strip_prefix(',') {
+                    Some(tail) => tail.trim_start(),
+                    None if rest.is_empty() => rest,
+                    None => return Err("expected , between array items".to_string()),
+                };
+            }
+            return Ok(Self::Array(strings));
+        }
+        match parse_string(text)? {
+            (string, "") => Ok(Self::String(string)),
+            _ => Err("unexpected text after the value".to_string()),
+        }
+    }
+
+    fn string(self, key: &str) -> Result<String, String> {
+        match self {
+            Self::String(string) => Ok(string),
+            _ => Err(format!("{} must be a string", key)),
+        }
+    }
+
+    fn strings(self, key: &str) -> Result<Vec<String>, String> {
+        match self {
+            Self::Array(strings) => Ok(strings),
+            Self::String(string) => Ok(vec![string]),
+            Self::Boolean => Err(format!("{} must be an array of strings", key)),
+        }
+    }
+}
+
+/// Basic (`"..."`) or literal (`'...'`) string at the start of `text`, and the text after it
+fn parse_string(text: &str) -> Result<(String, &str), String> {
+    if let Some(literal) = text.strip_prefix('\'') {
+        let end = literal
+            .find('\'')
+            .ok_or_else(|| "unterminated string".to_string())?;
+        return Ok((literal[..end].to_string(), &literal[end + 1..]));
+    }
+    let basic = text
+        .strip_prefix('"')
+        .ok_or_else(|| "expected a string, a boolean or an array".to_string())?;
+    let mut string = String::new();
+    let mut chars = basic.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Ok((string, &basic[index + 1..])),
+            '\\' => match chars.next() {
+                Some((_, '"')) => string.push('"'),
+                Some((_, '\\')) => string.push('\\'),
+                Some((_, 'n')) => string.push('\n'),
+                Some((_, 't')) => string.push('\t'),
+                _ => return Err("unsupported escape sequence".to_string()),
+            },
+            c => string.push(c),
+        }
+    }
+    Err("unterminated string".to_string())
+}
+
+/// `line` up to a `#` outside strings
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' => return &line[..index],
+            None => {}
+        }
+    }
+    line
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,691 @@
+use crate::arith::slice_at;
+use crate::decompress::{
+    find_zip_end, inflate_mszip, zip_member_data, zip_members, ZipMember, MAX_DECOMPRESSED_SIZE,
+};
+use crate::port_exe::PortExe;
+use crate::{read_u16, read_u32, read_u64};
+use std::io;
+
+/// Depth to which scanners should open containers found inside containers
+pub const MAX_CONTAINER_DEPTH: usize = 4;
+
+const ZIP_LOCAL_FILE_HEADER_SIGNATURE: [u8; 4] = [b'P', b'K', 3, 4];
+const CAB_SIGNATURE: [u8; 4] = [b'M', b'S', b'C', b'F'];
+/// Format version every cabinet since the first release declares, 1.3
+const CAB_VERSION: [u8; 2] = [3, 1];
+const CAB_FLAG_PREV_CABINET: u16 = 0x0001;
+const CAB_FLAG_NEXT_CABINET: u16 = 0x0002;
+const CAB_FLAG_RESERVE_PRESENT: u16 = 0x0004;
+const CAB_COMPRESS_MASK: u16 = 0x000F;
+const CAB_COMPRESS_NONE: u16 = 0;
+const CAB_COMPRESS_MSZIP: u16 = 1;
+/// Lowest `iFolder` value, marking files continued from or into another cabinet of a set
+const CAB_FOLDER_CONTINUED: u16 = 0xFFFD;
+/// `_A_NAME_IS_UTF`, set when a file name is UTF-8 rather than in the ANSI code page
+const CAB_ATTRIBUTE_NAME_IS_UTF: u16 = 0x80;
+
+const CFB_SIGNATURE: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+const CFB_HEADER_SIZE: usize = 512;
+const CFB_HEADER_DIFAT_ENTRIES: usize = 109;
+const CFB_DIRECTORY_ENTRY_SIZE: usize = 128;
+/// Sector numbers from here up mark free sectors and the ends of chains
+const CFB_MAX_SECTOR: u32 = 0xFFFF_FFFA;
+const CFB_NO_STREAM: u32 = 0xFFFF_FFFF;
+const CFB_STORAGE: u8 = 1;
+const CFB_STREAM: u8 = 2;
+const CFB_ROOT_STORAGE: u8 = 5;
+/// Characters MSI packs into stream names, two to a UTF-16 unit
+const MSI_NAME_ALPHABET: &[u8; 64] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz._";
+/// First unit of an MSI name encoding two characters, one character, and the table marker
+const MSI_NAME_PAIR: u16 = 0x3800;
+const MSI_NAME_SINGLE: u16 = 0x4800;
+const MSI_NAME_TABLE: u16 = 0x4840;
+
+/// File held by a [`DataSource`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainedFile {
+    name: String,
+    size: u64,
+    index: usize,
+}
+
+impl ContainedFile {
+    /// Path within the container, with `/` between directories
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Size of the extracted file, as the container records it
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// Collection of files that scanners can read images from without extracting them
+/// to disk first
+pub trait DataSource {
+    /// Files in the order the container stores them
+    fn files(&self) -> &[ContainedFile];
+
+    /// Extracts `file`, which must be one of [`DataSource::files`].
+    fn read(&mut self, file: &ContainedFile) -> io::Result<Vec<u8>>;
+}
+
+/// Opens `data` as a container, or returns `None` if it is not one.
+///
+/// Zip archives and cabinets are recognised at the start of the data and, for
+/// installers, after a self-extractor stub; compound files such as MSI packages only at
+/// the start. Data that starts with a container signature but fails to parse is an
+/// error; a stub whose apparent payload fails to parse is not a container.
+pub fn open_container(data: &[u8]) -> io::Result<Option<Box<dyn DataSource>>> {
+    if data.starts_with(&ZIP_LOCAL_FILE_HEADER_SIGNATURE) {
+        return Ok(Some(Box::new(ZipArchive::parse(data.to_vec())?)));
+    }
+    if data.starts_with(&CAB_SIGNATURE) {
+        return Ok(Some(Box::new(Cabinet::parse(data.to_vec())?)));
+    }
+    if data.starts_with(&CFB_SIGNATURE) {
+        return Ok(Some(Box::new(CompoundFile::parse(data.to_vec())?)));
+    }
+    if find_zip_end(data).is_some() {
+        if let Ok(archive) = ZipArchive::parse(data.to_vec()) {
+            return Ok(Some(Box::new(archive)));
+        }
+    }
+    let embedded = (0..data.len().saturating_sub(CAB_SIGNATURE.len()))
+        .filter(|&offset| data[offset..].starts_with(&CAB_SIGNATURE))
+        .filter_map(|offset| {
+            let size = read_u32(data, offset + 8)? as usize;
+            slice_at(data, offset, size)
+        })
+        .filter(|cabinet| cabinet.get(24..26) == Some(&CAB_VERSION[..]))
+        .find_map(|cabinet| Cabinet::parse(cabinet.to_vec()).ok());
+    Ok(embedded.map(|cabinet| Box::new(cabinet) as Box<dyn DataSource>))
+}
+
+/// Zip archive, possibly appended to a self-extractor stub
+#[derive(Debug)]
+pub struct ZipArchive {
+    data: Vec<u8>,
+    members: Vec<ZipMember>,
+    files: Vec<ContainedFile>,
+}
+
+impl ZipArchive {
+    /// Reads the central directory at the end of `data`.
+    pub fn parse(data: Vec<u8>) -> io::Result<Self> {
+        let members = zip_members(&data)?;
+        let files = members
+            .iter()
+            .enumerate()
+            .filter(|(_, member)| !member.name.ends_with('/'))
+            .map(|(index, member)| ContainedFile {
+                name: member.name.clone(),
+                size: member.uncompressed_size as u64,
+                index,
+            })
+            .collect();
+        Ok(Self {
+            data,
+            members,
+            files,
+        })
+    }
+}
+
+impl DataSource for ZipArchive {
+    fn files(&self) -> &[ContainedFile] {
+        &self.files
+    }
+
+    fn read(&mut self, file: &ContainedFile) -> io::Result<Vec<u8>> {
+        let member = self
+            .members
+            .get(file.index)
+            .ok_or_else(|| invalid_input("file is not in this archive"))?;
+        zip_member_data(&self.data, member)
+    }
+}
+
+/// Cabinet folder: a run of data blocks compressed as one stream
+#[derive(Debug, Clone)]
+struct CabFolder {
+    first_block: usize,
+    blocks: u16,
+    compression: u16,
+}
+
+/// Microsoft cabinet, as used by Windows Update, driver packages and installers.
+///
+/// Stored and MSZIP folders can be read; Quantum and LZX folders are listed but
+/// cannot be extracted. Files continued from or into another cabinet of a set are
+/// left out, since their data is not all in this one.
+#[derive(Debug)]
+pub struct Cabinet {
+    data: Vec<u8>,
+    data_reserve: usize,
+    folders: Vec<CabFolder>,
+    files: Vec<ContainedFile>,
+    /// Folder of each file and the offset of the file within it
+    locations: Vec<(u16, u32)>,
+    /// Most recently decompressed folder, as files of a folder are usually read in turn
+    cache: Option<(u16, Vec<u8>)>,
+}
+
+impl Cabinet {
+    /// Reads the cabinet header, folders and file list.
+    pub fn parse(data: Vec<u8>) -> io::Result<Self> {
+        let truncated = || invalid_data("truncated cabinet");
+        if !data.starts_with(&CAB_SIGNATURE) {
+            return Err(invalid_data("missing cabinet signature"));
+        }
+        let files_offset = read_u32(&data, 16).ok_or_else(truncated)? as usize;
+        let folder_count = read_u16(&data, 26).ok_or_else(truncated)?;
+        let file_count = read_u16(&data, 28).ok_or_else(truncated)?;
+        let flags = read_u16(&data, 30).ok_or_else(truncated)?;
+
+        let mut position = 36;
+        let mut folder_reserve = 0;
+        let mut data_reserve = 0;
+        if flags & CAB_FLAG_RESERVE_PRESENT != 0 {
+            let header_reserve = read_u16(&data, 36).ok_or_else(truncated)? as usize;
+            folder_reserve = *data.get(38).ok_or_else(truncated)? as usize;
+            data_reserve = *data.get(39).ok_or_else(truncated)? as usize;
+            position = 40 + header_reserve;
+        }
+        // Names of the previous and next cabinets of a set, and of their disks
+        let strings = [CAB_FLAG_PREV_CABINET, CAB_FLAG_NEXT_CABINET]
+            .iter()
+            .filter(|&&flag| flags & flag != 0)
+            .count()
+            * 2;
+        for _ in 0..strings {
+            position = find_zero(&data, position).ok_or_else(truncated)? + 1;
+        }
+
+        let mut folders = Vec::with_capacity(folder_count as usize);
+        for _ in 0..folder_count {
+            folders.push(CabFolder {
+                first_block: read_u32(&data, position).ok_or_else(truncated)? as usize,
+                blocks: read_u16(&data, position + 4).ok_or_else(truncated)?,
+                compression: read_u16(&data, position + 6).ok_or_else(truncated)?,
+            });
+            position += 8 + folder_reserve;
+        }
+
+        let mut files = Vec::with_capacity(file_count as usize);
+        let mut locations = Vec::with_capacity(file_count as usize);
+        let mut position = files_offset;
+        for _ in 0..file_count {
+            let size = read_u32(&data, position).ok_or_else(truncated)?;
+            let offset = read_u32(&data, position + 4).ok_or_else(truncated)?;
+            let folder = read_u16(&data, position + 8).ok_or_else(truncated)?;
+            let attributes = read_u16(&data, position + 14).ok_or_else(truncated)?;
+            let end = find_zero(&data, position + 16).ok_or_else(truncated)?;
+            let name = &data[position + 16..end];
+            position = end + 1;
+            if folder >= CAB_FOLDER_CONTINUED {
+                continue;
+            }
+            if folder as usize >= folders.len() {
+                return Err(invalid_data("cabinet file in a folder that does not exist"));
+            }
+            let name = if attributes & CAB_ATTRIBUTE_NAME_IS_UTF != 0 {
+                String::from_utf8_lossy(name).into_owned()
+            } else {
+                // The ANSI code page is unknown; Latin-1 keeps every byte visible
+                name.iter().map(|&byte| char::from(byte)).collect()
+            };
+            files.push(ContainedFile {
+                name: name.replace('\\', "/"),
+                size: size as u64,
+                index: locations.len(),
+            });
+            locations.push((folder, offset));
+        }
+        Ok(Self {
+            data,
+            data_reserve,
+            folders,
+            files,
+            locations,
+            cache: None,
+        })
+    }
+
+    /// Decompresses folder `index` as a whole.
+    fn folder_data(&self, index: u16) -> io::Result<Vec<u8>> {
+        let truncated = || invalid_data("truncated cabinet data block");
+        let folder = &self.folders[index as usize];
+        let mut blocks = Vec::with_capacity(folder.blocks as usize);
+        let mut size = 0usize;
+        let mut position = folder.first_block;
+        for _ in 0..folder.blocks {
+            let stored = read_u16(&self.data, position + 4).ok_or_else(truncated)? as usize;
+            let uncompressed = read_u16(&self.data, position + 6).ok_or_else(truncated)? as usize;
+            let start = position + 8 + self.data_reserve;
+            blocks.push(slice_at(&self.data, start, stored).ok_or_else(truncated)?);
+            size += uncompressed;
+            position = start + stored;
+        }
+        if size > MAX_DECOMPRESSED_SIZE {
+            return Err(invalid_data("cabinet folder too large"));
+        }
+        match folder.compression & CAB_COMPRESS_MASK {
+            CAB_COMPRESS_NONE => Ok(blocks.concat()),
+            CAB_COMPRESS_MSZIP => inflate_mszip(&blocks, size),
+            compression => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unsupported cabinet compression type {}", compression),
+            )),
+        }
+    }
+}
+
+impl DataSource for Cabinet {
+    fn files(&self) -> &[ContainedFile] {
+        &self.files
+    }
+
+    fn read(&mut self, file: &ContainedFile) -> io::Result<Vec<u8>> {
+        let (folder, offset) = *self
+            .locations
+            .get(file.index)
+            .ok_or_else(|| invalid_input("file is not in this cabinet"))?;
+        if self.cache.as_ref().map(|(cached, _)| *cached) != Some(folder) {
+            self.cache = Some((folder, self.folder_data(folder)?));
+        }
+        let data = self.cache.as_ref().map_or(&[][..], |(_, data)| &data[..]);
+        slice_at(data, offset as usize, file.size as usize)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| invalid_data("cabinet file extends past its folder"))
+    }
+}
+
+/// Directory entry of a compound file that holds data
+#[derive(Debug, Clone)]
+struct CfbStream {
+    start: u32,
+    size: u64,
+}
+
+/// Compound file, the structured storage format of MSI packages and legacy Office
+/// documents.
+///
+/// Every stream is a file, named by its path through the storages above it. MSI
+/// packages compress stream names into a private alphabet; names are decoded, so an
+/// embedded cabinet appears under the name the `Media` table gives it and custom
+/// action DLLs as `Binary.<name>`, with the table streams prefixed by `!`.
+#[derive(Debug)]
+pub struct CompoundFile {
+    data: Vec<u8>,
+    sector_shift: u32,
+    fat: Vec<u32>,
+    mini_fat: Vec<u32>,
+    mini_stream: Vec<u8>,
+    mini_stream_cutoff: u64,
+    streams: Vec<CfbStream>,
+    files: Vec<ContainedFile>,
+}
+
+impl CompoundFile {
+    /// Reads the header, allocation tables and directory.
+    pub fn parse(data: Vec<u8>) -> io::Result<Self> {
+        let truncated = || invalid_data("truncated compound file header");
+        if !data.starts_with(&CFB_SIGNATURE) {
+            return Err(invalid_data("missing compound file signature"));
+        }
+        if data.len() < CFB_HEADER_SIZE {
+            return Err(truncated());
+        }
+        let major_version = read_u16(&data, 0x1A).ok_or_else(truncated)?;
+        let sector_shift = read_u16(&data, 0x1E).ok_or_else(truncated)? as u32;
+        if sector_shift != 9 && sector_shift != 12 {
+            return Err(invalid_data("unsupported compound file sector size"));
+        }
+        let mini_sector_shift = read_u16(&data, 0x20).ok_or_else(truncated)? as u32;
+        if mini_sector_shift != 6 {
+            return Err(invalid_data("unsupported compound file mini sector size"));
+        }
+        let first_directory_sector = read_u32(&data, 0x30).ok_or_else(truncated)?;
+        let mini_stream_cutoff = read_u32(&data, 0x38).ok_or_else(truncated)? as u64;
+        let first_mini_fat_sector = read_u32(&data, 0x3C).ok_or_else(truncated)?;
+        let mut difat_sector = read_u32(&data, 0x44).ok_or_else(truncated)?;
+
+        let mut file = Self {
+            data,
+            sector_shift,
+            fat: Vec::new(),
+            mini_fat: Vec::new(),
+            mini_stream: Vec::new(),
+            mini_stream_cutoff,
+            streams: Vec::new(),
+            files: Vec::new(),
+        };
+        let sector_size = 1usize << sector_shift;
+        let sector_count = file.data.len() / sector_size;
+
+        // The FAT sectors are listed by the header, then by a chain of DIFAT sectors
+        let mut fat_sectors: Vec<u32> = (0..CFB_HEADER_DIFAT_ENTRIES)
+            .filter_map(|index| read_u32(&file.data, 0x4C + index * 4))
+            .collect();
+        let mut difat_sectors = 0;
+        while difat_sector <= CFB_MAX_SECTOR {
+            difat_sectors += 1;
+            if difat_sectors > sector_count {
+                return Err(invalid_data("compound file DIFAT chain loops"));
+            }
+            let sector = file
+                .sector(difat_sector)
+                .ok_or_else(|| invalid_data("compound file DIFAT sector outside the file"))?;
+            let entries = sector_size / 4 - 1;
+            fat_sectors.extend((0..entries).filter_map(|index| read_u32(sector, index * 4)));
+            difat_sector = read_u32(sector, entries * 4).unwrap_or(CFB_NO_STREAM);
+        }
+        let mut fat = Vec::new();
+        for sector in fat_sectors
+            .into_iter()
+            .filter(|&sector| sector <= CFB_MAX_SECTOR)
+        {
+            let sector = file
+                .sector(sector)
+                .ok_or_else(|| invalid_data("compound file FAT sector outside the file"))?;
+            fat.extend((0..sector_size / 4).filter_map(|index| read_u32(sector, index * 4)));
+        }
+        file.fat = fat;
+
+        let mini_fat = file.chain(first_mini_fat_sector, None)?;
+        file.mini_fat = (0..mini_fat.len() / 4)
+            .filter_map(|index| read_u32(&mini_fat, index * 4))
+            .collect();
+
+        let directory = file.chain(first_directory_sector, None)?;
+        let entry = |index: u32| {
+            slice_at(
+                &directory,
+                index as usize * CFB_DIRECTORY_ENTRY_SIZE,
+                CFB_DIRECTORY_ENTRY_SIZE,
+            )
+        };
+        let root = entry(0).ok_or_else(|| invalid_data("compound file has no root entry"))?;
+        if root[0x42] != CFB_ROOT_STORAGE {
+            return Err(invalid_data("bad compound file root entry"));
+        }
+        let root_start = read_u32(root, 0x74).unwrap_or(CFB_NO_STREAM);
+        let root_size = stream_size(root, major_version);
+        file.mini_stream = file.chain(root_start, Some(root_size))?;
+
+        // In-order walk of each storage's tree of children, the order names sort in
+        let mut visited = vec![false; directory.len() / CFB_DIRECTORY_ENTRY_SIZE];
+        let mut pending = vec![(read_u32(root, 0x4C).unwrap_or(CFB_NO_STREAM), String::new())];
+        let mut stack: Vec<(u32, String)> = Vec::new();
+        while let Some((index, prefix)) = pending.pop() {
+            let mut current = Some((index, prefix));
+            let mut storages = Vec::new();
+            while current.is_some() || !stack.is_empty() {
+                while let Some((index, prefix)) = current.take() {
+                    let seen = match visited.get_mut(index as usize) {
+                        Some(seen) => seen,
+                        None => break,
+                    };
+                    if *seen {
+                        return Err(invalid_data("compound file directory loops"));
+                    }
+                    *seen = true;
+                    let left = entry(index).and_then(|entry| read_u32(entry, 0x44));
+                    stack.push((index, prefix.clone()));
+                    current = left.map(|left| (left, prefix));
+                }
+                let (index, prefix) = match stack.pop() {
+                    Some(top) => top,
+                    None => break,
+                };
+                let entry = entry(index).ok_or_else(truncated)?;
+                let name_length = (read_u16(entry, 0x40).unwrap_or(0) as usize / 2).min(32);
+                let units: Vec<u16> = (0..name_length)
+                    .filter_map(|unit| read_u16(entry, unit * 2))
+                    .take_while(|&unit| unit != 0)
+                    .collect();
+                let name = format!("{}{}", prefix, decode_msi_name(&units));
+                match entry[0x42] {
+                    CFB_STREAM => {
+                        let size = stream_size(entry, major_version);
+                        file.files.push(ContainedFile {
+                            name,
+                            size,
+                            index: file.streams.len(),
+                        });
+                        file.streams.push(CfbStream {
+                            start: read_u32(entry, 0x74).unwrap_or(CFB_NO_STREAM),
+                            size,
+                        });
+                    }
+                    CFB_STORAGE => {
+                        let child = read_u32(entry, 0x4C).unwrap_or(CFB_NO_STREAM);
+                        storages.push((child, format!("{}/", name)));
+                    }
+                    _ => {}
+                }
+                let right = read_u32(entry, 0x48).unwrap_or(CFB_NO_STREAM);
+                current = Some((right, prefix));
+            }
+            // Storages are expanded after their siblings, in reverse so the first pops first
+            pending.extend(storages.into_iter().rev());
+        }
+        Ok(file)
+    }
+
+    fn sector(&self, sector: u32) -> Option<&[u8]> {
+        let size = 1usize << self.sector_shift;
+        let offset = (sector as usize).checked_add(1)?.checked_mul(size)?;
+        // The last sector may be cut short when the file was not padded
+        slice_at(&self.data, offset, size)
+            .or_else(|| self.data.get(offset..).filter(|rest| !rest.is_empty()))
+    }
+
+    /// Concatenates the sectors of the FAT chain starting at `start`, truncated to
+    /// `size` if given.
+    fn chain(&self, start: u32, size: Option<u64>) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut sector = start;
+        let mut steps = 0;
+        while sector <= CFB_MAX_SECTOR {
+            steps += 1;
+            if steps > self.fat.len() {
+                return Err(invalid_data("compound file sector chain loops"));
+            }
+            data.extend_from_slice(
+                self.sector(sector)
+                    .ok_or_else(|| invalid_data("compound file sector outside the file"))?,
+            );
+            if size.map_or(false, |size| data.len() as u64 >= size) {
+                break;
+            }
+            sector = self
+                .fat
+                .get(sector as usize)
+                .copied()
+                .unwrap_or(CFB_NO_STREAM);
+        }
+        if let Some(size) = size {
+            if (data.len() as u64) < size {
+                return Err(invalid_data("compound file stream is truncated"));
+            }
+            data.truncate(size as usize);
+        }
+        Ok(data)
+    }
+
+    /// Concatenates the mini sectors of the mini FAT chain starting at `start`.
+    fn mini_chain(&self, start: u32, size: u64) -> io::Result<Vec<u8>> {
+        const MINI_SECTOR_SIZE: usize = 64;
+        let mut data = Vec::new();
+        let mut sector = start;
+        while (data.len() as u64) < size {
+            if sector > CFB_MAX_SECTOR || data.len() / MINI_SECTOR_SIZE > self.mini_fat.len() {
+                return Err(invalid_data("compound file mini stream is truncated"));
+            }
+            data.extend_from_slice(
+                slice_at(
+                    &self.mini_stream,
+                    sector as usize * MINI_SECTOR_SIZE,
+                    MINI_SECTOR_SIZE,
+                )
+                .ok_or_else(|| invalid_data("compound file mini sector outside the mini stream"))?,
+            );
+            sector = self
+                .mini_fat
+                .get(sector as usize)
+                .copied()
+                .unwrap_or(CFB_NO_STREAM);
+        }
+        data.truncate(size as usize);
+        Ok(data)
+    }
+}
+
+impl DataSource for CompoundFile {
+    fn files(&self) -> &[ContainedFile] {
+        &self.files
+    }
+
+    fn read(&mut self, file: &ContainedFile) -> io::Result<Vec<u8>> {
+        let stream = self
+            .streams
+            .get(file.index)
+            .ok_or_else(|| invalid_input("file is not in this compound file"))?;
+        if stream.size as usize > MAX_DECOMPRESSED_SIZE {
+            return Err(invalid_data("compound file stream too large"));
+        }
+        if stream.size < self.mini_stream_cutoff {
+            self.mini_chain(stream.start, stream.size)
+        } else {
+            self.chain(stream.start, Some(stream.size))
+        }
+    }
+}
+
+/// Size of the stream of a directory entry. Version 3 files only define the low 32
+/// bits, and some writers leave garbage in the rest.
+fn stream_size(entry: &[u8], major_version: u16) -> u64 {
+    match major_version {
+        3 => read_u32(entry, 0x78).unwrap_or(0) as u64,
+        _ => read_u64(entry, 0x78).unwrap_or(0),
+    }
+}
+
+/// Decodes a stream name MSI compressed into its private alphabet; other names pass
+/// through unchanged.
+fn decode_msi_name(units: &[u16]) -> String {
+    let alphabet = |index: u16| char::from(MSI_NAME_ALPHABET[(index & 0x3F) as usize]);
+    let mut name = String::new();
+    for &unit in units {
+        match unit {
+            MSI_NAME_PAIR..=0x47FF => {
+                name.push(alphabet(unit - MSI_NAME_PAIR));
+                name.push(alphabet((unit - MSI_NAME_PAIR) >> 6));
+            }
+            MSI_NAME_SINGLE..=0x483F => name.push(alphabet(unit - MSI_NAME_SINGLE)),
+            MSI_NAME_TABLE => name.push('!'),
+            unit => name.push(char::from_u32(unit as u32).unwrap_or(char::REPLACEMENT_CHARACTER)),
+        }
+    }
+    name
+}
+
+/// Image found by [`contained_images`]
+#[derive(Debug)]
+pub struct ContainedImage {
+    path: String,
+    image: io::Result<PortExe>,
+}
+
+impl ContainedImage {
+    /// Path of the file within the outermost container, through any nested ones
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The parsed image, or why the file could not be read or parsed
+    pub fn image(&self) -> Result<&PortExe, &io::Error> {
+        self.image.as_ref()
+    }
+
+    pub fn into_image(self) -> io::Result<PortExe> {
+        self.image
+    }
+}
+
+/// Parses every PE file in `source` and in the containers it holds, down to
+/// [`MAX_CONTAINER_DEPTH`] levels, in the order the containers store them.
+///
+/// Files that start with `MZ` are reported whether or not they parse, together with
+/// files and nested containers that could not be extracted.
+pub fn contained_images(source: &mut dyn DataSource) -> Vec<ContainedImage> {
+    let mut images = Vec::new();
+    collect_images(source, "", 1, &mut images);
+    images
+}
+
+fn collect_images(
+    source: &mut dyn DataSource,
+    prefix: &str,
+    depth: usize,
+    images: &mut Vec<ContainedImage>,
+) {
+    for file in source.files().to_vec() {
+        let path = format!("{}{}", prefix, file.name());
+        let data = match source.read(&file) {
+            Ok(data) => data,
+            Err(e) => {
+                images.push(ContainedImage {
+                    path,
+                    image: Err(e),
+                });
+                continue;
+            }
+        };
+        let nested = match open_container(&data) {
+            Ok(nested) => nested,
+            Err(e) => {
+                images.push(ContainedImage {
+                    path: path.clone(),
+                    image: Err(e),
+                });
+                None
+            }
+        };
+        if data.starts_with(b"MZ") {
+            images.push(ContainedImage {
+                path: path.clone(),
+                image: PortExe::from_bytes(data),
+            });
+        }
+        if let Some(mut nested) = nested {
+            if depth >= MAX_CONTAINER_DEPTH {
+                images.push(ContainedImage {
+                    path,
+                    image: Err(invalid_data("containers nested too deeply")),
+                });
+            } else {
+                collect_images(nested.as_mut(), &format!("{}/", path), depth + 1, images);
+            }
+        }
+    }
+}
+
+fn find_zero(data: &[u8], from: usize) -> Option<usize> {
+    data.get(from..)?
+        .iter()
+        .position(|&b| b == 0)
+        .map(|position| from + position)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
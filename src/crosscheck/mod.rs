@@ -0,0 +1,176 @@
+use crate::arith::va_to_rva;
+use crate::load_config::{IMAGE_GUARD_CF_FUNCTION_TABLE_PRESENT, IMAGE_GUARD_CF_INSTRUMENTED};
+use crate::port_exe::PortExe;
+use crate::{read_u32, read_u64};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::io;
+
+/// Inconsistency between the exception directory, the base relocations and the
+/// Guard CF function table, as left by compiler or linker bugs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CrossCheckFinding {
+    /// `.pdata` entry starting outside the executable sections
+    FunctionOutsideCode { begin: u32 },
+    /// `.pdata` entry that ends before it begins or runs past its section
+    InvalidRange { begin: u32, end: u32 },
+    /// `.pdata` entry starting before the end of the previous one; the loader
+    /// binary-searches the table, so entries must be sorted and disjoint
+    Overlap { previous: u32, begin: u32 },
+    /// Function whose address is stored in the relocated slot at `reference`, but which
+    /// the Guard CF function table does not list, so indirect calls to it fail
+    MissingFromCfg { function: u32, reference: u32 },
+}
+
+impl fmt::Display for CrossCheckFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FunctionOutsideCode { begin } => {
+                write!(f, "function at {:#x} is outside executable sections", begin)
+            }
+            Self::InvalidRange { begin, end } => {
+                write!(f, "function range {:#x}-{:#x} is invalid", begin, end)
+            }
+            Self::Overlap { previous, begin } => write!(
+                f,
+                "function at {:#x} overlaps or precedes the one at {:#x}",
+                begin, previous
+            ),
+            Self::MissingFromCfg {
+                function,
+                reference,
+            } => write!(
+                f,
+                "function at {:#x} is address-taken at {:#x} but missing from the CFG table",
+                function, reference
+            ),
+        }
+    }
+}
+
+/// Result of [`PortExe::cross_check`]
+#[derive(Debug, Clone, Default)]
+pub struct CrossCheck {
+    functions: usize,
+    address_taken: usize,
+    cfg_targets: Option<usize>,
+    findings: Vec<CrossCheckFinding>,
+}
+
+impl CrossCheck {
+    /// Entries of the exception directory
+    pub fn functions(&self) -> usize {
+        self.functions
+    }
+
+    /// Functions whose start address is stored in a relocated slot
+    pub fn address_taken(&self) -> usize {
+        self.address_taken
+    }
+
+    /// Entries of the Guard CF function table, `None` for images without one
+    pub fn cfg_targets(&self) -> Option<usize> {
+        self.cfg_targets
+    }
+
+    pub fn findings(&self) -> &[CrossCheckFinding] {
+        &self.findings
+    }
+}
+
+impl PortExe {
+    /// Correlates the function ranges of the exception directory with the base
+    /// relocations and, for CFG images, the Guard CF function table.
+    ///
+    /// A function counts as address-taken when a relocated pointer holds its start
+    /// address; every such function must be a valid indirect call target.
+    pub fn cross_check(&self) -> io::Result<CrossCheck> {
+        let mut check = CrossCheck::default();
+        let header = match self.optional_header() {
+            Some(header) => header,
+            None => return Ok(check),
+        };
+        let image_base = header.image_base();
+        let functions = self.runtime_functions()?;
+        check.functions = functions.len();
+
+        let mut previous: Option<(u32, u32)> = None;
+        for function in &functions {
+            let begin = function.begin_address();
+            let section = self.section_at_rva(begin);
+            let code = section.filter(|section| section.characteristics().value().mem_execute());
+            match code {
+                None => check
+                    .findings
+                    .push(CrossCheckFinding::FunctionOutsideCode { begin }),
+                Some(section) => {
+                    let section_end = section
+                        .virtual_address()
+                        .into_value()
+                        .saturating_add(section.virtual_size().into_value());
+                    if let Some(end) = function.end_address() {
+                        if end <= begin || end > section_end {
+                            check
+                                .findings
+                                .push(CrossCheckFinding::InvalidRange { begin, end });
+                        }
+                    }
+                }
+            }
+            if let Some((previous_begin, previous_end)) = previous {
+                if begin <= previous_begin || begin < previous_end {
+                    check.findings.push(CrossCheckFinding::Overlap {
+                        previous: previous_begin,
+                        begin,
+                    });
+                }
+            }
+            previous = Some((begin, function.end_address().unwrap_or(begin)));
+        }
+
+        // Function start -> first relocated slot pointing at it
+        let starts: HashSet<u32> = functions.iter().map(|f| f.begin_address()).collect();
+        let mut address_taken = BTreeMap::new();
+        for relocation in self.base_relocations()? {
+            let target = match relocation.size() {
+                Some(8) => self
+                    .data_at_rva(relocation.rva(), 8)
+                    .and_then(|data| read_u64(data, 0)),
+                Some(_) => self
+                    .data_at_rva(relocation.rva(), 4)
+                    .and_then(|data| read_u32(data, 0))
+                    .map(u64::from),
+                None => None,
+            };
+            if let Some(target) = target.and_then(|target| va_to_rva(target, image_base)) {
+                if starts.contains(&target) {
+                    address_taken
+                        .entry(target)
+                        .or_insert_with(|| relocation.rva());
+                }
+            }
+        }
+        check.address_taken = address_taken.len();
+
+        // Code compiled with /guard:cf but linked without it has no table to check
+        let required = IMAGE_GUARD_CF_INSTRUMENTED | IMAGE_GUARD_CF_FUNCTION_TABLE_PRESENT;
+        let enforced = self
+            .load_config()?
+            .and_then(|config| config.guard_flags())
+            .map_or(false, |flags| flags & required == required);
+        if enforced {
+            let targets: HashSet<u32> = self.guard_cf_functions()?.into_iter().collect();
+            check.cfg_targets = Some(targets.len());
+            for (&function, &reference) in &address_taken {
+                if !targets.contains(&function) {
+                    check.findings.push(CrossCheckFinding::MissingFromCfg {
+                        function,
+                        reference,
+                    });
+                }
+            }
+        }
+        Ok(check)
+    }
+}
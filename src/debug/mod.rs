@@ -0,0 +1,351 @@
+use crate::arith::slice_at;
+use crate::build_id::symbol_store_path;
+use crate::endian::Guid;
+use crate::optional_header::IMAGE_DIRECTORY_ENTRY_DEBUG;
+use crate::pe_string::PeString;
+use crate::port_exe::PortExe;
+use crate::{read_u16, read_u32};
+use std::fmt;
+use std::io;
+
+pub const IMAGE_DEBUG_TYPE_UNKNOWN: u32 = 0;
+pub const IMAGE_DEBUG_TYPE_COFF: u32 = 1;
+pub const IMAGE_DEBUG_TYPE_CODEVIEW: u32 = 2;
+pub const IMAGE_DEBUG_TYPE_FPO: u32 = 3;
+pub const IMAGE_DEBUG_TYPE_MISC: u32 = 4;
+pub const IMAGE_DEBUG_TYPE_EXCEPTION: u32 = 5;
+pub const IMAGE_DEBUG_TYPE_FIXUP: u32 = 6;
+pub const IMAGE_DEBUG_TYPE_OMAP_TO_SRC: u32 = 7;
+pub const IMAGE_DEBUG_TYPE_OMAP_FROM_SRC: u32 = 8;
+pub const IMAGE_DEBUG_TYPE_BORLAND: u32 = 9;
+pub const IMAGE_DEBUG_TYPE_RESERVED10: u32 = 10;
+pub const IMAGE_DEBUG_TYPE_CLSID: u32 = 11;
+pub const IMAGE_DEBUG_TYPE_VC_FEATURE: u32 = 12;
+pub const IMAGE_DEBUG_TYPE_POGO: u32 = 13;
+pub const IMAGE_DEBUG_TYPE_ILTCG: u32 = 14;
+pub const IMAGE_DEBUG_TYPE_MPX: u32 = 15;
+pub const IMAGE_DEBUG_TYPE_REPRO: u32 = 16;
+pub const IMAGE_DEBUG_TYPE_EX_DLLCHARACTERISTICS: u32 = 20;
+
+/// Image is CET shadow stack compatible
+pub const IMAGE_DLLCHARACTERISTICS_EX_CET_COMPAT: u32 = 0x0001;
+/// Only CET-compatible images may be loaded in strict mode
+pub const IMAGE_DLLCHARACTERISTICS_EX_CET_COMPAT_STRICT_MODE: u32 = 0x0002;
+/// Context IP validation is relaxed for dynamic code
+pub const IMAGE_DLLCHARACTERISTICS_EX_CET_SET_CONTEXT_IP_VALIDATION_RELAXED_MODE: u32 = 0x0004;
+/// Dynamic APIs are only allowed out of process
+pub const IMAGE_DLLCHARACTERISTICS_EX_CET_DYNAMIC_APIS_ALLOW_IN_PROC_ONLY: u32 = 0x0008;
+
+/// `RSDS`, signature of the CodeView record pointing at a PDB 7.0 file, which GNU ld
+/// also writes for `--build-id`
+pub const CODEVIEW_PDB70_SIGNATURE: u32 = 0x5344_5352;
+/// `NB10`, signature of the CodeView record pointing at a PDB 2.0 file
+pub const CODEVIEW_PDB20_SIGNATURE: u32 = 0x3031_424E;
+
+pub(crate) const IMAGE_SIZEOF_DEBUG_DIRECTORY: usize = 28;
+
+/// `IMAGE_DEBUG_DIRECTORY` entry
+#[derive(Debug, Clone)]
+pub struct DebugDirectoryEntry {
+    characteristics: u32,
+    time_date_stamp: u32,
+    major_version: u16,
+    minor_version: u16,
+    debug_type: u32,
+    size_of_data: u32,
+    address_of_raw_data: u32,
+    pointer_to_raw_data: u32,
+}
+
+impl DebugDirectoryEntry {
+    /// Decodes an entry from the first `IMAGE_SIZEOF_DEBUG_DIRECTORY` bytes of `entry`.
+    pub(crate) fn parse(entry: &[u8]) -> Self {
+        let field = |offset| read_u32(entry, offset).unwrap_or(0);
+        DebugDirectoryEntry {
+            characteristics: field(0),
+            time_date_stamp: field(4),
+            major_version: read_u16(entry, 8).unwrap_or(0),
+            minor_version: read_u16(entry, 10).unwrap_or(0),
+            debug_type: field(12),
+            size_of_data: field(16),
+            address_of_raw_data: field(20),
+            pointer_to_raw_data: field(24),
+        }
+    }
+
+    pub(crate) fn to_bytes(&self) -> [u8; IMAGE_SIZEOF_DEBUG_DIRECTORY] {
+        let mut bytes = [0u8; IMAGE_SIZEOF_DEBUG_DIRECTORY];
+        bytes[0..4].copy_from_slice(&self.characteristics.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.time_date_stamp.to_le_bytes());
+        bytes[8..10].copy_from_slice(&self.major_version.to_le_bytes());
+        bytes[10..12].copy_from_slice(&self.minor_version.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.debug_type.to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.size_of_data.to_le_bytes());
+        bytes[20..24].copy_from_slice(&self.address_of_raw_data.to_le_bytes());
+        bytes[24..28].copy_from_slice(&self.pointer_to_raw_data.to_le_bytes());
+        bytes
+    }
+    pub fn characteristics(&self) -> u32 {
+        self.characteristics
+    }
+
+    pub fn time_date_stamp(&self) -> u32 {
+        self.time_date_stamp
+    }
+
+    pub fn major_version(&self) -> u16 {
+        self.major_version
+    }
+
+    pub fn minor_version(&self) -> u16 {
+        self.minor_version
+    }
+
+    /// `IMAGE_DEBUG_TYPE_*` value
+    pub fn debug_type(&self) -> u32 {
+        self.debug_type
+    }
+
+    pub fn size_of_data(&self) -> u32 {
+        self.size_of_data
+    }
+
+    /// RVA of the debug data when it is mapped, otherwise zero
+    pub fn address_of_raw_data(&self) -> u32 {
+        self.address_of_raw_data
+    }
+
+    pub fn pointer_to_raw_data(&self) -> u32 {
+        self.pointer_to_raw_data
+    }
+}
+
+/// Layout of a CodeView debug record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CodeViewFormat {
+    /// `RSDS` record of a PDB 7.0 file, keyed by a GUID
+    Pdb70,
+    /// `NB10` record of a PDB 2.0 file, keyed by a time stamp
+    Pdb20,
+}
+
+impl fmt::Display for CodeViewFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Pdb70 => "RSDS",
+            Self::Pdb20 => "NB10",
+        })
+    }
+}
+
+/// CodeView debug record naming the PDB file of an image
+#[derive(Debug, Clone)]
+pub struct CodeViewRecord {
+    format: CodeViewFormat,
+    guid: Option<Guid>,
+    signature: u32,
+    age: u32,
+    pdb_path: PeString,
+}
+
+impl CodeViewRecord {
+    /// Decodes an `RSDS` or `NB10` record, `None` for other signatures or records too
+    /// short for their header.
+    pub fn parse(record: &[u8]) -> Option<Self> {
+        let (format, guid, signature, age, start) = match read_u32(record, 0)? {
+            CODEVIEW_PDB70_SIGNATURE => {
+                let mut guid = [0u8; 16];
+                guid.copy_from_slice(record.get(4..20)?);
+                (
+                    CodeViewFormat::Pdb70,
+                    Some(Guid::from_bytes(guid)),
+                    0,
+                    read_u32(record, 20)?,
+                    24,
+                )
+            }
+            // The offset at 4 is always zero for a separate PDB file
+            CODEVIEW_PDB20_SIGNATURE => (
+                CodeViewFormat::Pdb20,
+                None,
+                read_u32(record, 8)?,
+                read_u32(record, 12)?,
+                16,
+            ),
+            _ => return None,
+        };
+        let name = &record[start..];
+        let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        Some(Self {
+            format,
+            guid,
+            signature,
+            age,
+            pdb_path: PeString::from_bytes(&name[..end]),
+        })
+    }
+
+    pub fn format(&self) -> CodeViewFormat {
+        self.format
+    }
+
+    /// GUID of the PDB, for [`CodeViewFormat::Pdb70`] records
+    pub fn guid(&self) -> Option<Guid> {
+        self.guid
+    }
+
+    /// Time stamp of the PDB, for [`CodeViewFormat::Pdb20`] records
+    pub fn signature(&self) -> u32 {
+        self.signature
+    }
+
+    /// Number of times the PDB was updated since its GUID or signature was assigned
+    pub fn age(&self) -> u32 {
+        self.age
+    }
+
+    /// PDB file name as the linker wrote it, usually an absolute path on the build
+    /// machine
+    pub fn pdb_path(&self) -> &PeString {
+        &self.pdb_path
+    }
+
+    /// Last component of [`CodeViewRecord::pdb_path`], which names the PDB on symbol
+    /// servers
+    pub fn pdb_name(&self) -> String {
+        let path = self.pdb_path.to_string_lossy();
+        path.rsplit(['\\', '/'])
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    /// Symbol server key of the PDB: the GUID without dashes followed by the age for
+    /// `RSDS`, the signature followed by the age for `NB10`
+    pub fn symbol_key(&self) -> String {
+        match self.guid {
+            Some(guid) => format!("{}{:X}", guid.to_string().replace('-', ""), self.age),
+            None => format!("{:08X}{:X}", self.signature, self.age),
+        }
+    }
+
+    /// Path of the PDB on a symbol server, see [`symbol_store_path`], `None` if the
+    /// record names no file
+    pub fn symbol_path(&self) -> Option<String> {
+        let name = self.pdb_name();
+        (!name.is_empty()).then(|| symbol_store_path(&name, &self.symbol_key()))
+    }
+
+    /// URL of the PDB on the symbol server at `server`, such as
+    /// `https://msdl.microsoft.com/download/symbols`
+    pub fn symbol_server_url(&self, server: &str) -> Option<String> {
+        Some(format!(
+            "{}/{}",
+            server.trim_end_matches('/'),
+            self.symbol_path()?
+        ))
+    }
+}
+
+impl PortExe {
+    pub fn debug_directory(&self) -> io::Result<Vec<DebugDirectoryEntry>> {
+        let directory = match self
+            .optional_header()
+            .and_then(|h| h.data_directory(IMAGE_DIRECTORY_ENTRY_DEBUG))
+        {
+            Some(directory) => directory,
+            None => return Ok(Vec::new()),
+        };
+        let rva = directory.virtual_address().into_value();
+        let size = directory.size().into_value();
+        if rva == 0 || size == 0 {
+            return Ok(Vec::new());
+        }
+        let data = self
+            .data_at_rva(rva, size)
+            .ok_or_else(|| invalid_data("debug directory is not backed by file data"))?;
+
+        Ok(data
+            .chunks_exact(IMAGE_SIZEOF_DEBUG_DIRECTORY)
+            .map(DebugDirectoryEntry::parse)
+            .collect())
+    }
+
+    /// Data referenced by a debug directory entry, read through its file pointer.
+    pub fn debug_data(&self, entry: &DebugDirectoryEntry) -> Option<&[u8]> {
+        slice_at(
+            self.data(),
+            entry.pointer_to_raw_data as usize,
+            entry.size_of_data as usize,
+        )
+    }
+
+    /// `IMAGE_DLLCHARACTERISTICS_EX_*` flags from the extended DLL characteristics debug entry
+    pub fn dll_characteristics_ex(&self) -> io::Result<Option<u32>> {
+        Ok(self
+            .debug_directory()?
+            .iter()
+            .find(|entry| entry.debug_type == IMAGE_DEBUG_TYPE_EX_DLLCHARACTERISTICS)
+            .and_then(|entry| self.debug_data(entry))
+            .and_then(|data| read_u32(data, 0)))
+    }
+
+    /// Decoded records of the CodeView debug entries, skipping entries whose data is
+    /// outside the file or has an unknown signature.
+    pub fn codeview_records(&self) -> io::Result<Vec<CodeViewRecord>> {
+        Ok(self
+            .debug_directory()?
+            .iter()
+            .filter(|entry| entry.debug_type == IMAGE_DEBUG_TYPE_CODEVIEW)
+            .filter_map(|entry| self.debug_data(entry))
+            .filter_map(CodeViewRecord::parse)
+            .collect())
+    }
+
+    /// First decodable CodeView record, see [`PortExe::codeview_records`]
+    pub fn codeview_record(&self) -> io::Result<Option<CodeViewRecord>> {
+        Ok(self.codeview_records()?.into_iter().next())
+    }
+
+    /// PDB file name the first CodeView debug entry records, as the linker wrote it,
+    /// usually an absolute path on the build machine.
+    pub fn pdb_path(&self) -> io::Result<Option<PeString>> {
+        Ok(self.codeview_pdb_path()?.map(|(_, _, name)| name))
+    }
+
+    /// First CodeView debug entry naming a PDB file, with the offset of the name in the
+    /// entry's data and the name itself.
+    pub(crate) fn codeview_pdb_path(
+        &self,
+    ) -> io::Result<Option<(DebugDirectoryEntry, usize, PeString)>> {
+        let entry = match self
+            .debug_directory()?
+            .into_iter()
+            .find(|entry| entry.debug_type == IMAGE_DEBUG_TYPE_CODEVIEW)
+        {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let record = match self.debug_data(&entry) {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        let start = match read_u32(record, 0) {
+            Some(CODEVIEW_PDB70_SIGNATURE) => 24,
+            Some(CODEVIEW_PDB20_SIGNATURE) => 16,
+            _ => return Ok(None),
+        };
+        let name = match record.get(start..) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        let name = PeString::from_bytes(&name[..end]);
+        Ok(Some((entry, start, name)))
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
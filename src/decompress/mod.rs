@@ -0,0 +1,390 @@
+use crate::arith::slice_at;
+use crate::endian::be_u16;
+use crate::{read_u16, read_u32};
+use std::io;
+
+/// Upper bound on the size of a single decompressed payload
+pub const MAX_DECOMPRESSED_SIZE: usize = 256 * 1024 * 1024;
+
+const ZIP_LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x04034B50;
+const ZIP_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x02014B50;
+const ZIP_END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054B50;
+const ZIP_METHOD_STORED: u16 = 0;
+const ZIP_METHOD_DEFLATED: u16 = 8;
+const ZIP_FLAG_ENCRYPTED: u16 = 0x0001;
+
+const GZIP_FHCRC: u8 = 0x02;
+const GZIP_FEXTRA: u8 = 0x04;
+const GZIP_FNAME: u8 = 0x08;
+const GZIP_FCOMMENT: u8 = 0x10;
+
+const LZNT1_CHUNK_SIZE: usize = 0x1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CompressionFormat {
+    Zlib,
+    Gzip,
+    /// Windows `COMPRESSION_FORMAT_LZNT1` as produced by `RtlCompressBuffer`
+    Lznt1,
+    Zip,
+}
+
+/// One decompressed stream, or one member of an archive
+#[derive(Debug, Clone)]
+pub struct Payload {
+    name: Option<String>,
+    data: Vec<u8>,
+}
+
+impl Payload {
+    /// File name stored in the gzip header or zip entry
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Identifies the compression format of `data` from its leading bytes.
+///
+/// LZNT1 has no magic number, so it is only reported when the whole buffer decompresses cleanly.
+pub fn detect_compression(data: &[u8]) -> Option<CompressionFormat> {
+    if read_u32(data, 0) == Some(ZIP_LOCAL_FILE_HEADER_SIGNATURE) {
+        return Some(CompressionFormat::Zip);
+    }
+    if data.len() >= 10 && data[0] == 0x1F && data[1] == 0x8B && data[2] == 8 {
+        return Some(CompressionFormat::Gzip);
+    }
+    if data.len() >= 2 {
+        let cmf = data[0];
+        let flg = data[1];
+        let header = be_u16([cmf, flg]);
+        if cmf & 0x0F == 8 && cmf >> 4 <= 7 && flg & 0x20 == 0 && header % 31 == 0 {
+            return Some(CompressionFormat::Zlib);
+        }
+    }
+    if looks_like_lznt1(data) && decompress_lznt1(data).is_ok() {
+        return Some(CompressionFormat::Lznt1);
+    }
+    None
+}
+
+/// Decompresses `data` according to [`detect_compression`].
+///
+/// Deflate-based formats (zlib, gzip and deflated zip members) need the `compression` feature.
+pub fn decompress(data: &[u8]) -> io::Result<Vec<Payload>> {
+    match detect_compression(data) {
+        Some(CompressionFormat::Zlib) => Ok(vec![Payload {
+            name: None,
+            data: inflate_zlib(data)?,
+        }]),
+        Some(CompressionFormat::Gzip) => decompress_gzip(data).map(|payload| vec![payload]),
+        Some(CompressionFormat::Lznt1) => Ok(vec![Payload {
+            name: None,
+            data: decompress_lznt1(data)?,
+        }]),
+        Some(CompressionFormat::Zip) => decompress_zip(data),
+        None => Err(invalid_data("no supported compression format detected")),
+    }
+}
+
+/// Decompresses an LZNT1 buffer.
+pub fn decompress_lznt1(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    let mut position = 0;
+
+    while position + 2 <= data.len() {
+        let header = read_u16(data, position).unwrap_or(0);
+        if header == 0 {
+            break;
+        }
+        if header & 0x7000 != 0x3000 {
+            return Err(invalid_data("bad LZNT1 chunk signature"));
+        }
+        let chunk_size = (header & 0x0FFF) as usize + 1;
+        let chunk = slice_at(data, position + 2, chunk_size)
+            .ok_or_else(|| invalid_data("truncated LZNT1 chunk"))?;
+        position += 2 + chunk_size;
+
+        if header & 0x8000 == 0 {
+            output.extend_from_slice(chunk);
+            continue;
+        }
+
+        let chunk_start = output.len();
+        let mut index = 0;
+        while index < chunk.len() {
+            let flags = chunk[index];
+            index += 1;
+            for bit in 0..8 {
+                if index >= chunk.len() {
+                    break;
+                }
+                if flags & (1 << bit) == 0 {
+                    output.push(chunk[index]);
+                    index += 1;
+                    continue;
+                }
+
+                let token = read_u16(chunk, index)
+                    .ok_or_else(|| invalid_data("truncated LZNT1 back-reference"))?;
+                index += 2;
+
+                let produced = output.len() - chunk_start;
+                if produced == 0 {
+                    return Err(invalid_data("LZNT1 back-reference at chunk start"));
+                }
+                let mut length_bits = 12;
+                let mut p = produced - 1;
+                while p >= 0x10 {
+                    length_bits -= 1;
+                    p >>= 1;
+                }
+                let length = (token & ((1 << length_bits) - 1)) as usize + 3;
+                let displacement = (token >> length_bits) as usize + 1;
+                if displacement > produced || produced + length > LZNT1_CHUNK_SIZE {
+                    return Err(invalid_data("LZNT1 back-reference out of range"));
+                }
+                let start = output.len() - displacement;
+                for offset in 0..length {
+                    let byte = output[start + offset];
+                    output.push(byte);
+                }
+            }
+        }
+    }
+    Ok(output)
+}
+
+fn looks_like_lznt1(data: &[u8]) -> bool {
+    match read_u16(data, 0) {
+        Some(header) => header & 0xF000 == 0xB000 && (header & 0x0FFF) as usize + 3 <= data.len(),
+        None => false,
+    }
+}
+
+fn decompress_gzip(data: &[u8]) -> io::Result<Payload> {
+    let truncated = || invalid_data("truncated gzip header");
+    let flags = *data.get(3).ok_or_else(truncated)?;
+    let mut position = 10;
+
+    if flags & GZIP_FEXTRA != 0 {
+        let extra_length = read_u16(data, position).ok_or_else(truncated)? as usize;
+        position += 2 + extra_length;
+    }
+    let mut name = None;
+    if flags & GZIP_FNAME != 0 {
+        let end = find_zero(data, position).ok_or_else(truncated)?;
+        name = Some(String::from_utf8_lossy(&data[position..end]).into_owned());
+        position = end + 1;
+    }
+    if flags & GZIP_FCOMMENT != 0 {
+        position = find_zero(data, position).ok_or_else(truncated)? + 1;
+    }
+    if flags & GZIP_FHCRC != 0 {
+        position += 2;
+    }
+
+    let stream = data.get(position..).ok_or_else(truncated)?;
+    Ok(Payload {
+        name,
+        data: inflate_raw(stream)?,
+    })
+}
+
+fn decompress_zip(data: &[u8]) -> io::Result<Vec<Payload>> {
+    zip_members(data)?
+        .into_iter()
+        .map(|member| {
+            Ok(Payload {
+                data: zip_member_data(data, &member)?,
+                name: Some(member.name),
+            })
+        })
+        .collect()
+}
+
+/// Entry of a zip central directory
+#[derive(Debug, Clone)]
+pub(crate) struct ZipMember {
+    pub(crate) name: String,
+    pub(crate) uncompressed_size: u32,
+    flags: u16,
+    method: u16,
+    compressed_size: u32,
+    local_header: usize,
+}
+
+/// Lists the members of the zip archive ending `data`, in central directory order.
+///
+/// The archive may follow other data, as in self-extracting executables: offsets are
+/// taken relative to where the central directory actually sits.
+pub(crate) fn zip_members(data: &[u8]) -> io::Result<Vec<ZipMember>> {
+    let truncated = || invalid_data("truncated zip archive");
+    let end_of_central_directory =
+        find_zip_end(data).ok_or_else(|| invalid_data("zip end of central directory not found"))?;
+
+    let count = read_u16(data, end_of_central_directory + 10).ok_or_else(truncated)?;
+    let size = read_u32(data, end_of_central_directory + 12).ok_or_else(truncated)? as usize;
+    let offset = read_u32(data, end_of_central_directory + 16).ok_or_else(truncated)? as usize;
+    let prefix = end_of_central_directory
+        .checked_sub(size)
+        .and_then(|start| start.checked_sub(offset))
+        .ok_or_else(|| invalid_data("zip central directory outside the archive"))?;
+    let mut position = prefix + offset;
+
+    let mut members = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        if read_u32(data, position) != Some(ZIP_CENTRAL_DIRECTORY_SIGNATURE) {
+            return Err(invalid_data("bad zip central directory entry"));
+        }
+        let flags = read_u16(data, position + 8).ok_or_else(truncated)?;
+        let method = read_u16(data, position + 10).ok_or_else(truncated)?;
+        let compressed_size = read_u32(data, position + 20).ok_or_else(truncated)?;
+        let uncompressed_size = read_u32(data, position + 24).ok_or_else(truncated)?;
+        let name_length = read_u16(data, position + 28).ok_or_else(truncated)? as usize;
+        let extra_length = read_u16(data, position + 30).ok_or_else(truncated)? as usize;
+        let comment_length = read_u16(data, position + 32).ok_or_else(truncated)? as usize;
+        let local_header = read_u32(data, position + 42).ok_or_else(truncated)? as usize;
+        let name = slice_at(data, position + 46, name_length).ok_or_else(truncated)?;
+        members.push(ZipMember {
+            name: String::from_utf8_lossy(name).into_owned(),
+            uncompressed_size,
+            flags,
+            method,
+            compressed_size,
+            local_header: prefix + local_header,
+        });
+        position += 46 + name_length + extra_length + comment_length;
+    }
+    Ok(members)
+}
+
+/// Offset of the zip end of central directory record in `data`, if there is one
+pub(crate) fn find_zip_end(data: &[u8]) -> Option<usize> {
+    let search_start = data.len().saturating_sub(0xFFFF + 22);
+    (search_start..data.len().saturating_sub(21))
+        .rev()
+        .find(|&offset| read_u32(data, offset) == Some(ZIP_END_OF_CENTRAL_DIRECTORY_SIGNATURE))
+}
+
+/// Reads and decompresses one member listed by [`zip_members`].
+pub(crate) fn zip_member_data(data: &[u8], member: &ZipMember) -> io::Result<Vec<u8>> {
+    let truncated = || invalid_data("truncated zip archive");
+    if member.flags & ZIP_FLAG_ENCRYPTED != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("zip member {} is encrypted", member.name),
+        ));
+    }
+    let local_header = member.local_header;
+    if read_u32(data, local_header) != Some(ZIP_LOCAL_FILE_HEADER_SIGNATURE) {
+        return Err(invalid_data("bad zip local file header"));
+    }
+    let local_name_length = read_u16(data, local_header + 26).ok_or_else(truncated)? as usize;
+    let local_extra_length = read_u16(data, local_header + 28).ok_or_else(truncated)? as usize;
+    let start = local_header + 30 + local_name_length + local_extra_length;
+    let stored = slice_at(data, start, member.compressed_size as usize).ok_or_else(truncated)?;
+
+    let data = match member.method {
+        ZIP_METHOD_STORED => stored.to_vec(),
+        ZIP_METHOD_DEFLATED => inflate_raw(stored)?,
+        method => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unsupported zip compression method {}", method),
+            ))
+        }
+    };
+    if data.len() != member.uncompressed_size as usize {
+        return Err(invalid_data(&format!(
+            "zip member {} does not match its recorded size",
+            member.name
+        )));
+    }
+    Ok(data)
+}
+
+/// Inflates the blocks of a cabinet folder compressed with MSZIP.
+///
+/// Each block is a complete deflate stream after a `CK` signature, but back-references
+/// may reach into the output of earlier blocks, so the blocks share one window.
+#[cfg(feature = "containers")]
+pub(crate) fn inflate_mszip(blocks: &[&[u8]], size: usize) -> io::Result<Vec<u8>> {
+    use miniz_oxide::inflate::core::inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
+    use miniz_oxide::inflate::core::{decompress as inflate, DecompressorOxide};
+    use miniz_oxide::inflate::TINFLStatus;
+
+    if size > MAX_DECOMPRESSED_SIZE {
+        return Err(invalid_data("MSZIP folder too large"));
+    }
+    let mut output = vec![0u8; size];
+    let mut position = 0;
+    for block in blocks {
+        let stream = block
+            .strip_prefix(b"CK")
+            .ok_or_else(|| invalid_data("bad MSZIP block signature"))?;
+        let mut state = DecompressorOxide::new();
+        let (status, _, written) = inflate(
+            &mut state,
+            stream,
+            &mut output,
+            position,
+            TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF,
+        );
+        if status != TINFLStatus::Done {
+            return Err(invalid_data(&format!("MSZIP block error: {:?}", status)));
+        }
+        position += written;
+    }
+    output.truncate(position);
+    Ok(output)
+}
+
+#[cfg(feature = "compression")]
+fn inflate_raw(data: &[u8]) -> io::Result<Vec<u8>> {
+    miniz_oxide::inflate::decompress_to_vec_with_limit(data, MAX_DECOMPRESSED_SIZE)
+        .map_err(|e| invalid_data(&format!("deflate stream error: {:?}", e)))
+}
+
+#[cfg(feature = "compression")]
+pub(crate) fn inflate_zlib(data: &[u8]) -> io::Result<Vec<u8>> {
+    miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(data, MAX_DECOMPRESSED_SIZE)
+        .map_err(|e| invalid_data(&format!("zlib stream error: {:?}", e)))
+}
+
+#[cfg(not(feature = "compression"))]
+fn inflate_raw(_data: &[u8]) -> io::Result<Vec<u8>> {
+    Err(deflate_unsupported())
+}
+
+#[cfg(not(feature = "compression"))]
+pub(crate) fn inflate_zlib(_data: &[u8]) -> io::Result<Vec<u8>> {
+    Err(deflate_unsupported())
+}
+
+#[cfg(not(feature = "compression"))]
+fn deflate_unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "deflate support requires the `compression` feature",
+    )
+}
+
+fn find_zero(data: &[u8], from: usize) -> Option<usize> {
+    data.get(from..)?
+        .iter()
+        .position(|&b| b == 0)
+        .map(|position| from + position)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
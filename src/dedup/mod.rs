@@ -0,0 +1,300 @@
+use crate::arith::align_up;
+use crate::optional_header::IMAGE_DIRECTORY_ENTRY_RESOURCE;
+use crate::port_exe::PortExe;
+use crate::resource::{ResourceEntry, ResourceLimits};
+use crate::strip::zero;
+use crate::write_at;
+use std::collections::HashMap;
+use std::io;
+use std::ops::Range;
+
+/// Alignment resource compilers give resource data, kept when compacting it
+const RESOURCE_DATA_ALIGNMENT: u64 = 8;
+/// Trailing zeros below this size are ordinary alignment or bitmap padding
+const MIN_REPORTED_PADDING: u32 = 256;
+
+/// Resources whose data is identical but stored more than once
+#[derive(Debug, Clone)]
+pub struct DuplicateResources {
+    size: u32,
+    copies: usize,
+    entries: Vec<ResourceEntry>,
+}
+
+impl DuplicateResources {
+    /// Size of the data of each resource
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Number of separate copies of the data in the image
+    pub fn copies(&self) -> usize {
+        self.copies
+    }
+
+    /// Resources with the data, the first holding the copy that would be kept
+    pub fn entries(&self) -> &[ResourceEntry] {
+        &self.entries
+    }
+
+    /// Bytes sharing a single copy would save
+    pub fn wasted(&self) -> u64 {
+        self.size as u64 * (self.copies as u64 - 1)
+    }
+}
+
+/// Resource whose data is mostly trailing zeros
+#[derive(Debug, Clone)]
+pub struct PaddedResource {
+    entry: ResourceEntry,
+    padding: u32,
+}
+
+impl PaddedResource {
+    pub fn entry(&self) -> &ResourceEntry {
+        &self.entry
+    }
+
+    /// Number of zero bytes the data ends with
+    pub fn padding(&self) -> u32 {
+        self.padding
+    }
+}
+
+/// Result of [`PortExe::resource_waste`]
+#[derive(Debug, Clone, Default)]
+pub struct ResourceWaste {
+    duplicates: Vec<DuplicateResources>,
+    padded: Vec<PaddedResource>,
+}
+
+impl ResourceWaste {
+    /// Groups of identical resources, in resource data order
+    pub fn duplicates(&self) -> &[DuplicateResources] {
+        &self.duplicates
+    }
+
+    pub fn padded(&self) -> &[PaddedResource] {
+        &self.padded
+    }
+
+    /// Bytes [`PortExe::dedup_resources`] would free
+    pub fn duplicate_bytes(&self) -> u64 {
+        self.duplicates.iter().map(DuplicateResources::wasted).sum()
+    }
+
+    pub fn padding_bytes(&self) -> u64 {
+        self.padded.iter().map(|padded| padded.padding as u64).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.duplicates.is_empty() && self.padded.is_empty()
+    }
+}
+
+impl PortExe {
+    /// Finds resource data stored more than once, such as icons repeated for every
+    /// language, and resources that are at least half trailing zeros.
+    ///
+    /// Resources whose entries already point at the same data count as one copy.
+    pub fn resource_waste(&self) -> io::Result<ResourceWaste> {
+        let mut waste = ResourceWaste::default();
+        let mut entries = self.resources()?;
+        entries.sort_by_key(|entry| entry.data_rva());
+        let mut groups: Vec<DuplicateResources> = Vec::new();
+        let mut by_data: HashMap<&[u8], usize> = HashMap::new();
+        for entry in entries {
+            let data = match self.resource_data(&entry) {
+                Some(data) if !data.is_empty() => data,
+                _ => continue,
+            };
+            match by_data.get(data) {
+                Some(&group) => {
+                    let group = &mut groups[group];
+                    if group
+                        .entries
+                        .iter()
+                        .all(|other| other.data_rva() != entry.data_rva())
+                    {
+                        group.copies += 1;
+                    }
+                    group.entries.push(entry);
+                }
+                None => {
+                    let padding = data.iter().rev().take_while(|&&byte| byte == 0).count() as u32;
+                    if padding >= MIN_REPORTED_PADDING && padding as usize * 2 >= data.len() {
+                        waste.padded.push(PaddedResource {
+                            entry: entry.clone(),
+                            padding,
+                        });
+                    }
+                    by_data.insert(data, groups.len());
+                    groups.push(DuplicateResources {
+                        size: entry.size(),
+                        copies: 1,
+                        entries: vec![entry],
+                    });
+                }
+            }
+        }
+        waste.duplicates = groups
+            .into_iter()
+            .filter(|group| group.copies > 1)
+            .collect();
+        Ok(waste)
+    }
+
+    /// Points every resource at the first copy of its data and drops the other copies,
+    /// returning the number of bytes freed.
+    ///
+    /// When all resource data follows the directory within one section it is packed
+    /// after the directory, and the section shrinks if it ends the file. Otherwise the
+    /// dropped copies are only zeroed. Signed files are refused.
+    pub fn dedup_resources(&mut self) -> io::Result<u64> {
+        if self.layout().is_mapped() {
+            return Err(invalid_input("mapped images cannot be edited"));
+        }
+        let header = self
+            .optional_header()
+            .ok_or_else(|| invalid_input("object files have no resource directory"))?;
+        let waste = self.resource_waste()?;
+        if waste.duplicates.is_empty() {
+            return Ok(0);
+        }
+        self.refuse_signed()?;
+
+        // Copy each duplicate's entries should use, by the RVA they use now
+        let mut canonical: HashMap<u32, u32> = HashMap::new();
+        for group in &waste.duplicates {
+            let kept = group.entries[0].data_rva();
+            for entry in &group.entries[1..] {
+                if entry.data_rva() != kept {
+                    canonical.insert(entry.data_rva(), kept);
+                }
+            }
+        }
+        let tree = self.resource_directory(&ResourceLimits::default())?;
+        let metadata_end = tree.metadata_end();
+        let entries = tree.into_entries();
+        let mut blobs: Vec<(u32, u32)> = entries
+            .iter()
+            .filter(|entry| !canonical.contains_key(&entry.data_rva()))
+            .map(|entry| (entry.data_rva(), entry.size()))
+            .collect();
+        blobs.sort_unstable();
+        blobs.dedup();
+
+        let mut data = self.to_bytes();
+        let mut moved: HashMap<u32, u32> = HashMap::new();
+        let directory = header
+            .data_directory(IMAGE_DIRECTORY_ENTRY_RESOURCE)
+            .ok_or_else(|| invalid_data("image has no resource directory"))?;
+        let directory_rva = directory.virtual_address().into_value();
+        let section = self
+            .section_headers()
+            .iter()
+            .find(|section| {
+                let start = section.virtual_address().into_value();
+                let size = section
+                    .virtual_size()
+                    .into_value()
+                    .max(section.size_of_raw_data().into_value());
+                (start..start.saturating_add(size)).contains(&directory_rva)
+            })
+            .ok_or_else(|| invalid_data("resource directory lies outside every section"))?;
+        let section_start = section.pointer_to_raw_data().into_value() as usize;
+        let section_rva = section.virtual_address().into_value();
+        let raw = section_start
+            ..section_start.saturating_add(section.size_of_raw_data().into_value() as usize);
+        let offset_of = |rva: u32| section_start + (rva - section_rva) as usize;
+        let compactable = blobs
+            .windows(2)
+            .all(|pair| pair[0].0 + pair[0].1 <= pair[1].0)
+            && blobs.iter().all(|&(rva, size)| {
+                rva >= section_rva
+                    && offset_of(rva) >= metadata_end
+                    && offset_of(rva) + size as usize <= raw.end.min(data.len())
+            });
+
+        let freed = waste.duplicate_bytes();
+        if compactable {
+            let mut cursor = (metadata_end - section_start) as u32 + section_rva;
+            for &(rva, size) in &blobs {
+                let target = (align_up(cursor as u64, RESOURCE_DATA_ALIGNMENT)
+                    .ok_or_else(|| invalid_data("resource data RVA overflows"))?
+                    as u32)
+                    .min(rva);
+                let source = offset_of(rva);
+                data.copy_within(source..source + size as usize, offset_of(target));
+                moved.insert(rva, target);
+                cursor = target + size;
+            }
+            let used_end = offset_of(cursor);
+            let old_end = raw.end.min(data.len());
+            zero(&mut data, &(used_end..old_end));
+            write_at(
+                &mut data,
+                directory.size().offset(),
+                &(cursor - directory_rva).to_le_bytes(),
+            );
+            // Nothing follows the section, so its unused raw data can be cut off
+            if raw.end == data.len() {
+                let size = align_up(
+                    (used_end - section_start) as u64,
+                    header.file_alignment() as u64,
+                )
+                .ok_or_else(|| invalid_data("section size overflows"))?;
+                write_at(
+                    &mut data,
+                    section.size_of_raw_data().offset(),
+                    &(size as u32).to_le_bytes(),
+                );
+                data.truncate(section_start + size as usize);
+            }
+        } else {
+            let kept: Vec<Range<usize>> = blobs
+                .iter()
+                .filter_map(|&(rva, size)| {
+                    self.rva_to_offset(rva)
+                        .map(|offset| offset..offset + size as usize)
+                })
+                .collect();
+            for entry in &entries {
+                if !canonical.contains_key(&entry.data_rva()) {
+                    continue;
+                }
+                // Identical data may overlap data that stays, such as runs of zeros
+                if let Some(offset) = self.rva_to_offset(entry.data_rva()) {
+                    let copy = offset..offset + entry.size() as usize;
+                    if kept
+                        .iter()
+                        .all(|kept| kept.end <= copy.start || copy.end <= kept.start)
+                    {
+                        zero(&mut data, &copy);
+                    }
+                }
+            }
+        }
+
+        for entry in &entries {
+            let rva = canonical
+                .get(&entry.data_rva())
+                .copied()
+                .unwrap_or_else(|| entry.data_rva());
+            let rva = moved.get(&rva).copied().unwrap_or(rva);
+            write_at(&mut data, entry.entry_offset() as u64, &rva.to_le_bytes());
+        }
+
+        *self = PortExe::from_bytes(data)?;
+        self.refresh_checksum();
+        Ok(freed)
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
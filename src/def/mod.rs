@@ -0,0 +1,333 @@
+use crate::export::{ExportKind, ExportSpec, ExportTarget};
+use crate::port_exe::PortExe;
+use std::fmt;
+use std::io;
+
+/// Entry of the `EXPORTS` section of a module-definition file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefExport {
+    name: String,
+    internal_name: Option<String>,
+    ordinal: Option<u16>,
+    noname: bool,
+    private: bool,
+    data: bool,
+}
+
+impl DefExport {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            internal_name: None,
+            ordinal: None,
+            noname: false,
+            private: false,
+            data: false,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Symbol the export resolves to, or `module.function` for a forwarder
+    pub fn internal_name(&self) -> Option<&str> {
+        self.internal_name.as_deref()
+    }
+
+    pub fn ordinal(&self) -> Option<u16> {
+        self.ordinal
+    }
+
+    /// Exported by ordinal only
+    pub fn noname(&self) -> bool {
+        self.noname
+    }
+
+    /// Left out of the import library
+    pub fn private(&self) -> bool {
+        self.private
+    }
+
+    pub fn data(&self) -> bool {
+        self.data
+    }
+
+    /// Target of a forwarded export (`module.function`)
+    pub fn forwarder(&self) -> Option<&str> {
+        self.internal_name
+            .as_deref()
+            .filter(|internal_name| internal_name.contains('.'))
+    }
+}
+
+/// Contents of a module-definition (`.def`) file relevant to exports
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleDefinition {
+    library: Option<String>,
+    base: Option<u64>,
+    exports: Vec<DefExport>,
+}
+
+impl ModuleDefinition {
+    /// Name given by the `LIBRARY` or `NAME` statement
+    pub fn library(&self) -> Option<&str> {
+        self.library.as_deref()
+    }
+
+    /// `BASE=` address of the `LIBRARY` or `NAME` statement
+    pub fn base(&self) -> Option<u64> {
+        self.base
+    }
+
+    pub fn exports(&self) -> &[DefExport] {
+        &self.exports
+    }
+
+    /// Parses the `LIBRARY`/`NAME` and `EXPORTS` statements of a module-definition file.
+    ///
+    /// Other statements (`DESCRIPTION`, `HEAPSIZE`, `SECTIONS`, `STACKSIZE`, `STUB`,
+    /// `VERSION`) are accepted and ignored.
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut definition = Self::default();
+        let mut in_exports = false;
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default();
+            let tokens = tokenize(line);
+            let first = match tokens.first() {
+                Some(first) => first.as_str(),
+                None => continue,
+            };
+            let error = |message: &str| invalid_data(&format!("line {}: {}", index + 1, message));
+
+            match first.to_ascii_uppercase().as_str() {
+                "LIBRARY" | "NAME" => {
+                    in_exports = false;
+                    for token in &tokens[1..] {
+                        if let Some(base) = strip_prefix_ignore_case(token, "BASE=") {
+                            definition.base =
+                                Some(parse_number(base).ok_or_else(|| error("bad BASE value"))?);
+                        } else if definition.library.is_none() {
+                            definition.library = Some(token.clone());
+                        }
+                    }
+                    continue;
+                }
+                "EXPORTS" => {
+                    in_exports = true;
+                    if tokens.len() == 1 {
+                        continue;
+                    }
+                }
+                "DESCRIPTION" | "HEAPSIZE" | "SECTIONS" | "STACKSIZE" | "STUB" | "VERSION" => {
+                    in_exports = false;
+                    continue;
+                }
+                _ if !in_exports => continue,
+                _ => {}
+            }
+
+            let entry = if first.eq_ignore_ascii_case("EXPORTS") {
+                &tokens[1..]
+            } else {
+                &tokens[..]
+            };
+            definition.exports.push(parse_export(entry).map_err(error)?);
+        }
+        Ok(definition)
+    }
+
+    /// Builds the export specification for [`crate::export::build_export_table`], resolving
+    /// internal symbol names to RVAs with `resolve`.
+    pub fn export_specs<F>(&self, mut resolve: F) -> io::Result<Vec<ExportSpec>>
+    where
+        F: FnMut(&str) -> Option<u32>,
+    {
+        self.exports
+            .iter()
+            .map(|export| {
+                let target = match export.forwarder() {
+                    Some(forwarder) => ExportTarget::Forwarder(forwarder.to_string()),
+                    None => {
+                        let symbol = export.internal_name.as_deref().unwrap_or(&export.name);
+                        ExportTarget::Rva(resolve(symbol).ok_or_else(|| {
+                            invalid_data(&format!("unresolved export symbol {}", symbol))
+                        })?)
+                    }
+                };
+                Ok(ExportSpec::new(
+                    (!export.noname).then(|| export.name.clone()),
+                    export.ordinal.map(u32::from),
+                    target,
+                ))
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for ModuleDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(library) = &self.library {
+            write!(f, "LIBRARY {}", quote(library))?;
+            if let Some(base) = self.base {
+                write!(f, " BASE={:#x}", base)?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "EXPORTS")?;
+        for export in &self.exports {
+            write!(f, "    {}", quote(&export.name))?;
+            if let Some(internal_name) = &export.internal_name {
+                write!(f, "={}", quote(internal_name))?;
+            }
+            if let Some(ordinal) = export.ordinal {
+                write!(f, " @{}", ordinal)?;
+                if export.noname {
+                    write!(f, " NONAME")?;
+                }
+            }
+            if export.private {
+                write!(f, " PRIVATE")?;
+            }
+            if export.data {
+                write!(f, " DATA")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl PortExe {
+    /// Describes the export table as a module-definition file.
+    ///
+    /// Exports without a name are written as `OrdinalN @N NONAME`, since the format requires a
+    /// name for every entry, and exports in data sections are marked `DATA`.
+    pub fn module_definition(&self) -> io::Result<ModuleDefinition> {
+        let library = self
+            .export_directory()?
+            .map(|directory| directory.name().to_string())
+            .filter(|name| !name.is_empty());
+        let exports = self
+            .exports()?
+            .into_iter()
+            .map(|export| {
+                let ordinal = export.ordinal() as u16;
+                DefExport {
+                    name: export
+                        .name()
+                        .map_or_else(|| format!("Ordinal{}", ordinal), ToString::to_string),
+                    internal_name: export.forwarder().map(str::to_string),
+                    ordinal: Some(ordinal),
+                    noname: export.name().is_none(),
+                    private: false,
+                    data: export.kind() == ExportKind::Data,
+                }
+            })
+            .collect();
+        Ok(ModuleDefinition {
+            library,
+            base: None,
+            exports,
+        })
+    }
+}
+
+fn parse_export(tokens: &[String]) -> Result<DefExport, &'static str> {
+    let (first, rest) = tokens.split_first().ok_or("empty export entry")?;
+    let mut export = match first.split_once('=') {
+        Some((name, internal_name)) if !name.is_empty() && !internal_name.is_empty() => {
+            let mut export = DefExport::new(name.to_string());
+            export.internal_name = Some(internal_name.to_string());
+            export
+        }
+        Some(_) => return Err("bad export alias"),
+        None => DefExport::new(first.clone()),
+    };
+
+    for token in rest {
+        if let Some(ordinal) = token.strip_prefix('@') {
+            let ordinal = ordinal.parse::<u16>().map_err(|_| "bad export ordinal")?;
+            if ordinal == 0 {
+                return Err("export ordinals start at 1");
+            }
+            export.ordinal = Some(ordinal);
+        } else if token.eq_ignore_ascii_case("NONAME") {
+            export.noname = true;
+        } else if token.eq_ignore_ascii_case("PRIVATE") {
+            export.private = true;
+        } else if token.eq_ignore_ascii_case("DATA") || token.eq_ignore_ascii_case("CONSTANT") {
+            export.data = true;
+        } else {
+            return Err("unexpected token in export entry");
+        }
+    }
+    if export.noname && export.ordinal.is_none() {
+        return Err("NONAME export without an ordinal");
+    }
+    Ok(export)
+}
+
+/// Splits a line on whitespace, keeping quoted strings together and joining `=` aliases
+/// written with spaces around the equals sign.
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut pending = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                pending = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if pending {
+                    tokens.push(std::mem::take(&mut current));
+                    pending = false;
+                }
+            }
+            c => {
+                current.push(c);
+                pending = true;
+            }
+        }
+    }
+    if pending {
+        tokens.push(current);
+    }
+
+    let mut joined: Vec<String> = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        match joined.last_mut() {
+            Some(last) if last.ends_with('=') || token.starts_with('=') => last.push_str(&token),
+            _ => joined.push(token),
+        }
+    }
+    joined
+}
+
+fn quote(name: &str) -> String {
+    if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == ';' || c == '=') {
+        format!("\"{}\"", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn parse_number(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = text.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &text[prefix.len()..])
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
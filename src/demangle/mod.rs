@@ -0,0 +1,227 @@
+/// Decoration scheme recognised in a symbol name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decoration {
+    /// Plain C name, or a `__cdecl` name without its leading underscore
+    None,
+    /// `_name@N`
+    Stdcall { argument_bytes: u32 },
+    /// `@name@N`
+    Fastcall { argument_bytes: u32 },
+    /// `name@@N`
+    Vectorcall { argument_bytes: u32 },
+    /// MSVC C++ name starting with `?`
+    Msvc,
+    /// Itanium C++ ABI name starting with `_Z`, as produced by MinGW
+    Itanium,
+}
+
+/// Symbol name with its decoration removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DemangledName {
+    name: String,
+    decoration: Decoration,
+}
+
+impl DemangledName {
+    /// Undecorated name; the qualified name without signature for C++ symbols
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn decoration(&self) -> Decoration {
+        self.decoration
+    }
+
+    /// The name is a C++ symbol that cannot be declared from C
+    pub fn is_cpp(&self) -> bool {
+        matches!(self.decoration, Decoration::Msvc | Decoration::Itanium)
+    }
+}
+
+/// Removes C calling convention decorations and extracts the qualified name of
+/// C++ symbols.
+///
+/// This is a best-effort decoder: C++ names using templates or other constructs
+/// it does not understand are returned unchanged with [`Decoration::None`].
+pub fn demangle(symbol: &str) -> DemangledName {
+    let demangled = if symbol.starts_with('?') {
+        demangle_msvc(symbol).map(|name| (name, Decoration::Msvc))
+    } else if let Some(encoding) = symbol
+        .strip_prefix("__Z")
+        .or_else(|| symbol.strip_prefix("_Z"))
+    {
+        demangle_itanium(encoding).map(|name| (name, Decoration::Itanium))
+    } else {
+        demangle_c(symbol)
+    };
+    let (name, decoration) = demangled.unwrap_or_else(|| (symbol.to_string(), Decoration::None));
+    DemangledName { name, decoration }
+}
+
+fn demangle_c(symbol: &str) -> Option<(String, Decoration)> {
+    let at = symbol.rfind('@')?;
+    let argument_bytes = symbol[at + 1..].parse::<u32>().ok()?;
+    let body = &symbol[..at];
+    if let Some(name) = body.strip_suffix('@') {
+        Some((name.to_string(), Decoration::Vectorcall { argument_bytes }))
+    } else if let Some(name) = body.strip_prefix('@') {
+        Some((name.to_string(), Decoration::Fastcall { argument_bytes }))
+    } else {
+        let name = body.strip_prefix('_').unwrap_or(body);
+        (!name.is_empty() && !name.contains('@'))
+            .then(|| (name.to_string(), Decoration::Stdcall { argument_bytes }))
+    }
+}
+
+fn demangle_msvc(symbol: &str) -> Option<String> {
+    let mut rest = symbol.strip_prefix('?')?;
+    let mut special = None;
+    if let Some(after) = rest.strip_prefix('?') {
+        let (operator, after) = msvc_operator(after)?;
+        special = Some(operator);
+        rest = after;
+    }
+
+    let mut names: Vec<String> = Vec::new();
+    loop {
+        if rest.starts_with('@') {
+            break;
+        }
+        let first = rest.chars().next()?;
+        if first.is_ascii_digit() {
+            let index = first.to_digit(10)? as usize;
+            names.push(names.get(index)?.clone());
+            rest = &rest[1..];
+        } else if first == '?' {
+            return None;
+        } else {
+            let end = rest.find('@')?;
+            names.push(rest[..end].to_string());
+            rest = &rest[end + 1..];
+        }
+    }
+
+    let mut scopes: Vec<String> = names.into_iter().rev().collect();
+    let name = match special {
+        Some(MsvcOperator::Constructor) => scopes.last()?.clone(),
+        Some(MsvcOperator::Destructor) => format!("~{}", scopes.last()?),
+        Some(MsvcOperator::Named(operator)) => operator.to_string(),
+        None => scopes.pop()?,
+    };
+    scopes.push(name);
+    Some(scopes.join("::"))
+}
+
+enum MsvcOperator {
+    Constructor,
+    Destructor,
+    Named(&'static str),
+}
+
+fn msvc_operator(rest: &str) -> Option<(MsvcOperator, &str)> {
+    let mut chars = rest.chars();
+    let code = chars.next()?;
+    let (operator, length) = match code {
+        '0' => (MsvcOperator::Constructor, 1),
+        '1' => (MsvcOperator::Destructor, 1),
+        '_' => {
+            let name = match chars.next()? {
+                '0' => "operator/=",
+                '1' => "operator%=",
+                '2' => "operator>>=",
+                '3' => "operator<<=",
+                '4' => "operator&=",
+                '5' => "operator|=",
+                '6' => "operator^=",
+                '7' => "`vftable'",
+                '8' => "`vbtable'",
+                'E' => "`vector deleting destructor'",
+                'G' => "`scalar deleting destructor'",
+                'U' => "operator new[]",
+                'V' => "operator delete[]",
+                _ => return None,
+            };
+            (MsvcOperator::Named(name), 2)
+        }
+        _ => {
+            let name = match code {
+                '2' => "operator new",
+                '3' => "operator delete",
+                '4' => "operator=",
+                '5' => "operator>>",
+                '6' => "operator<<",
+                '7' => "operator!",
+                '8' => "operator==",
+                '9' => "operator!=",
+                'A' => "operator[]",
+                'B' => "operator cast",
+                'C' => "operator->",
+                'D' => "operator*",
+                'E' => "operator++",
+                'F' => "operator--",
+                'G' => "operator-",
+                'H' => "operator+",
+                'I' => "operator&",
+                'J' => "operator->*",
+                'K' => "operator/",
+                'L' => "operator%",
+                'M' => "operator<",
+                'N' => "operator<=",
+                'O' => "operator>",
+                'P' => "operator>=",
+                'Q' => "operator,",
+                'R' => "operator()",
+                'S' => "operator~",
+                'T' => "operator^",
+                'U' => "operator|",
+                'V' => "operator&&",
+                'W' => "operator||",
+                'X' => "operator*=",
+                'Y' => "operator+=",
+                'Z' => "operator-=",
+                _ => return None,
+            };
+            (MsvcOperator::Named(name), 1)
+        }
+    };
+    Some((operator, &rest[length..]))
+}
+
+fn demangle_itanium(encoding: &str) -> Option<String> {
+    let mut rest = encoding;
+    let nested = match rest.strip_prefix('N') {
+        Some(after) => {
+            rest = after.trim_start_matches(['r', 'V', 'K']);
+            true
+        }
+        None => false,
+    };
+
+    let mut names: Vec<String> = Vec::new();
+    loop {
+        if let Some(after) = rest.strip_prefix("St") {
+            names.push("std".to_string());
+            rest = after;
+        } else if rest.starts_with('C') || rest.starts_with('D') {
+            let class = names.last()?.clone();
+            names.push(if rest.starts_with('C') {
+                class
+            } else {
+                format!("~{}", class)
+            });
+            rest = rest.get(2..)?;
+        } else {
+            let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            if digits == 0 {
+                return None;
+            }
+            let length = rest[..digits].parse::<usize>().ok()?;
+            names.push(rest.get(digits..digits + length)?.to_string());
+            rest = &rest[digits + length..];
+        }
+        if !nested || rest.starts_with('E') {
+            break;
+        }
+    }
+    Some(names.join("::"))
+}
@@ -0,0 +1,261 @@
+use crate::endian::le_u32;
+use crate::port_exe::PortExe;
+use crate::symbol::{CoffSymbol, IMAGE_SYM_CLASS_FILE};
+use std::collections::BTreeMap;
+use std::io;
+
+/// Size and relocation count of one section of an object file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionSummary {
+    size: u32,
+    relocations: u32,
+    characteristics: u32,
+}
+
+impl SectionSummary {
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn relocations(&self) -> u32 {
+        self.relocations
+    }
+
+    pub fn characteristics(&self) -> u32 {
+        self.characteristics
+    }
+}
+
+/// Section present in at least one of the compared objects
+#[derive(Debug, Clone)]
+pub struct SectionDelta {
+    key: String,
+    before: Option<SectionSummary>,
+    after: Option<SectionSummary>,
+}
+
+impl SectionDelta {
+    /// Section name, followed by the COMDAT symbol for COMDAT sections
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn before(&self) -> Option<&SectionSummary> {
+        self.before.as_ref()
+    }
+
+    pub fn after(&self) -> Option<&SectionSummary> {
+        self.after.as_ref()
+    }
+
+    pub fn size_delta(&self) -> i64 {
+        self.after.as_ref().map_or(0, |s| s.size as i64)
+            - self.before.as_ref().map_or(0, |s| s.size as i64)
+    }
+
+    pub fn relocation_delta(&self) -> i64 {
+        self.after.as_ref().map_or(0, |s| s.relocations as i64)
+            - self.before.as_ref().map_or(0, |s| s.relocations as i64)
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.before != self.after
+    }
+}
+
+/// Defined symbol with the section it lives in and its extent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolSummary {
+    section: String,
+    size: u32,
+    storage_class: u8,
+}
+
+impl SymbolSummary {
+    /// Key of the containing section, as in [`SectionDelta::key`]
+    pub fn section(&self) -> &str {
+        &self.section
+    }
+
+    /// Distance to the next symbol or to the end of the section
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn storage_class(&self) -> u8 {
+        self.storage_class
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SymbolDelta {
+    name: String,
+    before: Option<SymbolSummary>,
+    after: Option<SymbolSummary>,
+}
+
+impl SymbolDelta {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn before(&self) -> Option<&SymbolSummary> {
+        self.before.as_ref()
+    }
+
+    pub fn after(&self) -> Option<&SymbolSummary> {
+        self.after.as_ref()
+    }
+
+    pub fn size_delta(&self) -> i64 {
+        self.after.as_ref().map_or(0, |s| s.size as i64)
+            - self.before.as_ref().map_or(0, |s| s.size as i64)
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.before != self.after
+    }
+}
+
+/// Section and symbol level comparison of two object files
+#[derive(Debug, Clone)]
+pub struct ObjectDiff {
+    sections: Vec<SectionDelta>,
+    symbols: Vec<SymbolDelta>,
+}
+
+impl ObjectDiff {
+    /// Every section of either object, sorted by key
+    pub fn sections(&self) -> &[SectionDelta] {
+        &self.sections
+    }
+
+    /// Every defined symbol of either object, sorted by name
+    pub fn symbols(&self) -> &[SymbolDelta] {
+        &self.symbols
+    }
+}
+
+/// Compares two object files section by section and symbol by symbol.
+///
+/// Sections are matched by name; COMDAT sections, which usually share a name such as
+/// `.text$mn`, are matched by their COMDAT symbol instead, and remaining duplicates by
+/// their order in the section table.
+pub fn diff_objects(before: &PortExe, after: &PortExe) -> io::Result<ObjectDiff> {
+    let (before_sections, before_symbols) = summarize(before)?;
+    let (after_sections, after_symbols) = summarize(after)?;
+    Ok(ObjectDiff {
+        sections: merge(before_sections, after_sections)
+            .into_iter()
+            .map(|(key, (before, after))| SectionDelta { key, before, after })
+            .collect(),
+        symbols: merge(before_symbols, after_symbols)
+            .into_iter()
+            .map(|(name, (before, after))| SymbolDelta {
+                name,
+                before,
+                after,
+            })
+            .collect(),
+    })
+}
+
+type Summaries = (
+    BTreeMap<String, SectionSummary>,
+    BTreeMap<String, SymbolSummary>,
+);
+
+fn summarize(pe: &PortExe) -> io::Result<Summaries> {
+    let symbols = pe.coff_symbols()?;
+    let keys = section_keys(pe, &symbols);
+
+    let mut sections = BTreeMap::new();
+    for (section, key) in pe.section_headers().iter().zip(&keys) {
+        let characteristics = section.characteristics();
+        let raw = characteristics.raw_bytes();
+        sections.insert(
+            key.clone(),
+            SectionSummary {
+                size: section.size_of_raw_data().into_value(),
+                relocations: pe.relocation_count(section),
+                characteristics: le_u32([raw[0], raw[1], raw[2], raw[3]]),
+            },
+        );
+    }
+
+    let mut defined: Vec<&CoffSymbol> = symbols
+        .iter()
+        .filter(|symbol| {
+            symbol.is_defined()
+                && symbol.storage_class() != IMAGE_SYM_CLASS_FILE
+                && !symbol.is_section_definition()
+        })
+        .collect();
+    defined.sort_by_key(|symbol| (symbol.section_number(), symbol.value()));
+
+    let mut summaries = BTreeMap::new();
+    for (position, symbol) in defined.iter().enumerate() {
+        let section_index = symbol.section_number() as usize - 1;
+        let section_size = pe
+            .section_headers()
+            .get(section_index)
+            .map_or(0, |section| section.size_of_raw_data().into_value());
+        let end = defined
+            .get(position + 1)
+            .filter(|next| next.section_number() == symbol.section_number())
+            .map_or(section_size, |next| next.value());
+        summaries
+            .entry(symbol.name().to_string())
+            .or_insert(SymbolSummary {
+                section: keys.get(section_index).cloned().unwrap_or_default(),
+                size: end.saturating_sub(symbol.value()),
+                storage_class: symbol.storage_class(),
+            });
+    }
+    Ok((sections, summaries))
+}
+
+/// Stable identity of every section used to pair sections between objects
+fn section_keys(pe: &PortExe, symbols: &[CoffSymbol]) -> Vec<String> {
+    let mut keys = Vec::new();
+    let mut seen: BTreeMap<String, usize> = BTreeMap::new();
+    for (index, section) in pe.section_headers().iter().enumerate() {
+        let name = pe.section_name(section);
+        let number = index as i16 + 1;
+        let comdat_symbol = section
+            .characteristics()
+            .value()
+            .lnk_comdat()
+            .then(|| {
+                symbols.iter().find(|symbol| {
+                    symbol.section_number() == number && !symbol.is_section_definition()
+                })
+            })
+            .flatten();
+        let key = match comdat_symbol {
+            Some(symbol) => format!("{} ({})", name, symbol.name()),
+            None => name.to_string(),
+        };
+        let occurrence = seen.entry(key.clone()).or_insert(0);
+        *occurrence += 1;
+        keys.push(if *occurrence == 1 {
+            key
+        } else {
+            format!("{} #{}", key, occurrence)
+        });
+    }
+    keys
+}
+
+type Pairs<T> = BTreeMap<String, (Option<T>, Option<T>)>;
+
+fn merge<T>(before: BTreeMap<String, T>, after: BTreeMap<String, T>) -> Pairs<T> {
+    let mut pairs: Pairs<T> = BTreeMap::new();
+    for (key, value) in before {
+        pairs.entry(key).or_insert((None, None)).0 = Some(value);
+    }
+    for (key, value) in after {
+        pairs.entry(key).or_insert((None, None)).1 = Some(value);
+    }
+    pairs
+}
@@ -0,0 +1,234 @@
+use crate::decompress::decompress;
+use crate::pe_string::PeString;
+use crate::port_exe::PortExe;
+use crate::{read_u16, read_u32, read_u64};
+use std::collections::BTreeSet;
+
+/// Upper bound on the compilation unit headers read from `.debug_info`
+pub const MAX_COMPILATION_UNITS: usize = 0x10000;
+/// Header of `.zdebug_*` sections: `ZLIB` and the big-endian uncompressed size
+const ZDEBUG_MAGIC: &[u8; 4] = b"ZLIB";
+const ZDEBUG_HEADER_SIZE: usize = 12;
+/// `unit_length` escape announcing the 64-bit DWARF format
+const DWARF64_ESCAPE: u32 = 0xFFFF_FFFF;
+/// Lowest `unit_length` value reserved by the standard
+const DWARF_RESERVED_LENGTH: u32 = 0xFFFF_FFF0;
+
+/// Section holding DWARF debug information
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DwarfSection {
+    name: PeString,
+    size: u32,
+}
+
+impl DwarfSection {
+    /// Full name, resolved through the string table
+    pub fn name(&self) -> &PeString {
+        &self.name
+    }
+
+    /// Virtual size of the section
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Returns `true` for `.zdebug_*` sections, compressed with zlib.
+    pub fn is_compressed(&self) -> bool {
+        self.name.as_bytes().starts_with(b".zdebug_")
+    }
+}
+
+/// Header of a unit in `.debug_info`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnitHeader {
+    offset: u64,
+    length: u64,
+    dwarf64: bool,
+    version: u16,
+    unit_type: Option<u8>,
+    address_size: u8,
+    abbrev_offset: u64,
+}
+
+impl UnitHeader {
+    /// Offset of the header in `.debug_info`
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// `unit_length`: size of the unit after the length field
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// Returns `true` for the 64-bit DWARF format.
+    pub fn is_dwarf64(&self) -> bool {
+        self.dwarf64
+    }
+
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// `DW_UT_*` unit type, only recorded from DWARF 5 on
+    pub fn unit_type(&self) -> Option<u8> {
+        self.unit_type
+    }
+
+    pub fn address_size(&self) -> u8 {
+        self.address_size
+    }
+
+    /// Offset of the unit's abbreviations in `.debug_abbrev`
+    pub fn abbrev_offset(&self) -> u64 {
+        self.abbrev_offset
+    }
+}
+
+/// DWARF sections of an image and the unit headers of `.debug_info`
+#[derive(Debug, Clone, Default)]
+pub struct DwarfSummary {
+    sections: Vec<DwarfSection>,
+    units: Vec<UnitHeader>,
+    truncated: bool,
+}
+
+impl DwarfSummary {
+    pub fn sections(&self) -> &[DwarfSection] {
+        &self.sections
+    }
+
+    /// Unit headers in section order
+    pub fn units(&self) -> &[UnitHeader] {
+        &self.units
+    }
+
+    /// DWARF versions used by the units
+    pub fn versions(&self) -> BTreeSet<u16> {
+        self.units.iter().map(|unit| unit.version).collect()
+    }
+
+    /// Returns `true` if `.debug_info` could not be decompressed or the walk stopped at
+    /// a truncated header or an unknown version, so [`DwarfSummary::units`] may be
+    /// incomplete.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+impl PortExe {
+    /// Sections named `.debug_*` or `.zdebug_*`, in section table order
+    pub fn dwarf_sections(&self) -> Vec<DwarfSection> {
+        self.section_headers()
+            .iter()
+            .filter_map(|section| {
+                let name = self.section_name(section);
+                let dwarf = name.as_bytes().starts_with(b".debug_")
+                    || name.as_bytes().starts_with(b".zdebug_");
+                dwarf.then(|| DwarfSection {
+                    size: match section.virtual_size().into_value() {
+                        0 => section.size_of_raw_data().into_value(),
+                        size => size,
+                    },
+                    name,
+                })
+            })
+            .collect()
+    }
+
+    /// Lists the DWARF sections and reads the unit headers of `.debug_info`.
+    ///
+    /// Returns `None` when the image has no DWARF sections. A compressed `.zdebug_info`
+    /// needs the `compression` feature; without it the summary is marked truncated.
+    pub fn dwarf_summary(&self) -> Option<DwarfSummary> {
+        let sections = self.dwarf_sections();
+        if sections.is_empty() {
+            return None;
+        }
+        let mut summary = DwarfSummary {
+            sections,
+            ..DwarfSummary::default()
+        };
+        let info = self.section_headers().iter().find_map(|section| {
+            let name = self.section_name(section);
+            (name == ".debug_info" || name == ".zdebug_info").then(|| {
+                let data = self.section_data(section);
+                match section.virtual_size().into_value() {
+                    0 => data,
+                    size => &data[..data.len().min(size as usize)],
+                }
+            })
+        });
+        let info = match info {
+            Some(info) if info.starts_with(ZDEBUG_MAGIC) => {
+                match decompress(&info[ZDEBUG_HEADER_SIZE.min(info.len())..]) {
+                    Ok(mut payloads) if !payloads.is_empty() => payloads.remove(0).into_data(),
+                    _ => {
+                        summary.truncated = true;
+                        return Some(summary);
+                    }
+                }
+            }
+            Some(info) => info.to_vec(),
+            None => return Some(summary),
+        };
+
+        let mut offset = 0;
+        while offset < info.len() && summary.units.len() < MAX_COMPILATION_UNITS {
+            // Section alignment padding
+            if info[offset..].iter().all(|&b| b == 0) {
+                break;
+            }
+            match unit_header(&info, offset) {
+                Some((unit, next)) => {
+                    summary.units.push(unit);
+                    offset = next;
+                }
+                None => {
+                    summary.truncated = true;
+                    break;
+                }
+            }
+        }
+        Some(summary)
+    }
+}
+
+/// Reads the unit header at `offset` and returns it with the offset of the next unit.
+fn unit_header(info: &[u8], offset: usize) -> Option<(UnitHeader, usize)> {
+    let (length, dwarf64, mut cursor) = match read_u32(info, offset)? {
+        DWARF64_ESCAPE => (read_u64(info, offset + 4)?, true, offset + 12),
+        length if length >= DWARF_RESERVED_LENGTH => return None,
+        length => (u64::from(length), false, offset + 4),
+    };
+    let next = cursor.checked_add(usize::try_from(length).ok()?)?;
+    let version = read_u16(info, cursor)?;
+    cursor += 2;
+    let read_offset = |at: usize| {
+        if dwarf64 {
+            read_u64(info, at)
+        } else {
+            read_u32(info, at).map(u64::from)
+        }
+    };
+    let offset_size = if dwarf64 { 8 } else { 4 };
+    let (unit_type, address_size, abbrev_offset) = match version {
+        2..=4 => (None, *info.get(cursor + offset_size)?, read_offset(cursor)?),
+        5 => (
+            Some(*info.get(cursor)?),
+            *info.get(cursor + 1)?,
+            read_offset(cursor + 2)?,
+        ),
+        _ => return None,
+    };
+    let unit = UnitHeader {
+        offset: offset as u64,
+        length,
+        dwarf64,
+        version,
+        unit_type,
+        address_size,
+        abbrev_offset,
+    };
+    Some((unit, next))
+}
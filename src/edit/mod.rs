@@ -0,0 +1,673 @@
+use crate::arith::align_up;
+use crate::debug::IMAGE_SIZEOF_DEBUG_DIRECTORY;
+use crate::endian::{le_u16, le_u32};
+use crate::optional_header::{
+    OptionalHeaderWrapper, IMAGE_DIRECTORY_ENTRY_BASERELOC, IMAGE_DIRECTORY_ENTRY_BOUND_IMPORT,
+    IMAGE_DIRECTORY_ENTRY_DEBUG, IMAGE_DIRECTORY_ENTRY_EXCEPTION, IMAGE_DIRECTORY_ENTRY_EXPORT,
+    IMAGE_DIRECTORY_ENTRY_IMPORT, IMAGE_DIRECTORY_ENTRY_RESOURCE, IMAGE_DIRECTORY_ENTRY_SECURITY,
+    IMAGE_DLLCHARACTERISTICS_DYNAMIC_BASE, IMAGE_DLLCHARACTERISTICS_HIGH_ENTROPY_VA,
+};
+use crate::pe_string::PeString;
+use crate::port_exe::PortExe;
+use crate::section_header::{
+    IMAGE_SCN_CNT_CODE, IMAGE_SCN_CNT_UNINITIALIZED_DATA, IMAGE_SCN_MEM_EXECUTE,
+    IMAGE_SCN_MEM_WRITE, IMAGE_SIZEOF_SECTION_HEADER,
+};
+use crate::symbol::IMAGE_SIZEOF_SYMBOL;
+use crate::{read_u32, write_at};
+use std::io;
+
+/// Smallest `FileAlignment` the PE format allows
+pub const MIN_FILE_ALIGNMENT: u32 = 0x200;
+/// Largest `FileAlignment` the PE format allows
+pub const MAX_FILE_ALIGNMENT: u32 = 0x10000;
+
+/// Whether editor operations may leave a section both writable and executable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WxPolicy {
+    Allow,
+    /// Refuse edits that would produce a writable and executable section
+    Refuse,
+}
+
+/// Permissions [`PortExe::harden_sections`] removes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HardenPolicy {
+    /// Clear `EXECUTE` from sections that do not contain code
+    pub execute_requires_code: bool,
+    /// Clear `WRITE` from executable sections
+    pub no_writable_code: bool,
+    /// Sections whose `WRITE` bit is cleared by name
+    pub read_only: Vec<String>,
+}
+
+impl Default for HardenPolicy {
+    fn default() -> Self {
+        Self {
+            execute_requires_code: true,
+            no_writable_code: true,
+            read_only: [".rdata", ".pdata", ".rsrc", ".reloc", ".edata"]
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Characteristics of a section before and after an edit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionChange {
+    index: usize,
+    name: PeString,
+    before: u32,
+    after: u32,
+}
+
+impl SectionChange {
+    /// Position of the section in the section table
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn name(&self) -> &PeString {
+        &self.name
+    }
+
+    pub fn before(&self) -> u32 {
+        self.before
+    }
+
+    pub fn after(&self) -> u32 {
+        self.after
+    }
+}
+
+/// How [`PortExe::rename_sections`] stores names longer than the 8 bytes of a section
+/// header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LongNames {
+    /// Reject names longer than 8 bytes
+    Refuse,
+    /// Append them to the COFF string table and store `/offset` in the header, as GNU
+    /// linkers do. The string table must end the file, or be created at its end.
+    StringTable,
+}
+
+/// Name of a section before and after [`PortExe::rename_sections`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionRename {
+    index: usize,
+    before: PeString,
+    after: String,
+}
+
+impl SectionRename {
+    /// Position of the section in the section table
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Full name before the rename, with long names resolved
+    pub fn before(&self) -> &PeString {
+        &self.before
+    }
+
+    pub fn after(&self) -> &str {
+        &self.after
+    }
+}
+
+impl PortExe {
+    /// Replaces the characteristics of section `index`.
+    ///
+    /// With [`WxPolicy::Refuse`], characteristics that are both writable and executable
+    /// are rejected and the section is left unchanged.
+    pub fn set_section_characteristics(
+        &mut self,
+        index: usize,
+        characteristics: u32,
+        policy: WxPolicy,
+    ) -> io::Result<SectionChange> {
+        if self.layout().is_mapped() {
+            return Err(invalid_input("mapped images cannot be edited"));
+        }
+        let wx = IMAGE_SCN_MEM_WRITE | IMAGE_SCN_MEM_EXECUTE;
+        if policy == WxPolicy::Refuse && characteristics & wx == wx {
+            return Err(invalid_input(
+                "refusing to make a section writable and executable",
+            ));
+        }
+        let section = self
+            .section_headers_mut()
+            .get_mut(index)
+            .ok_or_else(|| invalid_input("section index out of range"))?;
+        let change = SectionChange {
+            index,
+            name: section.name().into_value(),
+            before: le_u32(section.characteristics().raw_bytes()),
+            after: characteristics,
+        };
+        section.set_characteristics(characteristics);
+        self.commit_headers();
+        Ok(change)
+    }
+
+    /// Clears the `WRITE` and `EXECUTE` bits `policy` deems unnecessary and returns
+    /// the sections that changed.
+    ///
+    /// Every change is checked before any is applied: if one would leave a section
+    /// writable and executable, or the file is signed, the image is left untouched.
+    /// A nonzero checksum is recomputed afterwards; a zero one is left for the loader to ignore.
+    pub fn harden_sections(&mut self, policy: &HardenPolicy) -> io::Result<Vec<SectionChange>> {
+        if self.layout().is_mapped() {
+            return Err(invalid_input("mapped images cannot be edited"));
+        }
+        self.refuse_signed()?;
+        let wx = IMAGE_SCN_MEM_WRITE | IMAGE_SCN_MEM_EXECUTE;
+        let mut planned = Vec::new();
+        for (index, section) in self.section_headers().iter().enumerate() {
+            let name = section.name().into_value();
+            let before = le_u32(section.characteristics().raw_bytes());
+            let mut after = before;
+            if policy.execute_requires_code && after & IMAGE_SCN_CNT_CODE == 0 {
+                after &= !IMAGE_SCN_MEM_EXECUTE;
+            }
+            if policy.no_writable_code && after & IMAGE_SCN_MEM_EXECUTE != 0 {
+                after &= !IMAGE_SCN_MEM_WRITE;
+            }
+            if policy
+                .read_only
+                .iter()
+                .any(|read_only| name == read_only.as_str())
+            {
+                after &= !IMAGE_SCN_MEM_WRITE;
+            }
+            if after == before {
+                continue;
+            }
+            if after & wx == wx {
+                return Err(invalid_input(
+                    "refusing to leave a section writable and executable",
+                ));
+            }
+            planned.push((index, after));
+        }
+
+        let changes = planned
+            .into_iter()
+            .map(|(index, after)| self.set_section_characteristics(index, after, WxPolicy::Refuse))
+            .collect::<io::Result<Vec<_>>>()?;
+        if !changes.is_empty() {
+            self.refresh_checksum();
+        }
+        Ok(changes)
+    }
+
+    /// Sets `DYNAMIC_BASE`, and `HIGH_ENTROPY_VA` for PE32+ images, so the loader
+    /// randomises the image base. Returns `false` if both were already set.
+    ///
+    /// Refuses images whose relocations were stripped or that have no base relocation
+    /// directory, as the loader could not fix them up after moving them.
+    pub fn enable_aslr(&mut self) -> io::Result<bool> {
+        if self.layout().is_mapped() {
+            return Err(invalid_input("mapped images cannot be edited"));
+        }
+        let header = self
+            .optional_header()
+            .ok_or_else(|| invalid_input("object files have no DllCharacteristics"))?;
+        if self
+            .file_header()
+            .characteristics()
+            .into_value()
+            .relocs_stripped()
+        {
+            return Err(invalid_input(
+                "relocations are stripped (IMAGE_FILE_RELOCS_STRIPPED), relink with /DYNAMICBASE",
+            ));
+        }
+        let has_relocations = header
+            .data_directory(IMAGE_DIRECTORY_ENTRY_BASERELOC)
+            .map_or(false, |directory| {
+                directory.virtual_address().into_value() != 0 && directory.size().into_value() != 0
+            });
+        if !has_relocations {
+            return Err(invalid_input(
+                "image has no base relocations to apply when it is moved",
+            ));
+        }
+
+        let before = match header {
+            OptionalHeaderWrapper::X32(h) => h.dll_characteristics().into_value(),
+            OptionalHeaderWrapper::X64(h) => h.dll_characteristics().into_value(),
+        };
+        let mut after = before | IMAGE_DLLCHARACTERISTICS_DYNAMIC_BASE;
+        if header.image_type().is_x64() {
+            after |= IMAGE_DLLCHARACTERISTICS_HIGH_ENTROPY_VA;
+        }
+        if after == before {
+            return Ok(false);
+        }
+        if let Some(header) = self.optional_header_mut() {
+            header.set_dll_characteristics(after);
+        }
+        self.commit_headers();
+        self.refresh_checksum();
+        Ok(true)
+    }
+
+    /// Re-lays out the raw data of every section for a new `FileAlignment`, moving the
+    /// overlay, the COFF symbol table and debug payloads along with it.
+    ///
+    /// Returns `false` if the image already used `alignment`. Signed files are refused,
+    /// as moving their data invalidates the signature.
+    pub fn set_file_alignment(&mut self, alignment: u32) -> io::Result<bool> {
+        if self.layout().is_mapped() {
+            return Err(invalid_input("mapped images cannot be edited"));
+        }
+        let header = self
+            .optional_header()
+            .ok_or_else(|| invalid_input("object files have no FileAlignment"))?;
+        if !alignment.is_power_of_two()
+            || !(MIN_FILE_ALIGNMENT..=MAX_FILE_ALIGNMENT).contains(&alignment)
+        {
+            return Err(invalid_input(
+                "FileAlignment must be a power of two between 0x200 and 0x10000",
+            ));
+        }
+        if alignment > header.section_alignment() {
+            return Err(invalid_input(
+                "FileAlignment cannot exceed SectionAlignment",
+            ));
+        }
+        if header.file_alignment() == alignment {
+            return Ok(false);
+        }
+        self.refuse_signed()?;
+
+        let align = |value: usize| {
+            align_up(value as u64, alignment as u64)
+                .map(|value| value as usize)
+                .ok_or_else(|| invalid_input("file grows beyond the addressable range"))
+        };
+        let old = self.to_bytes();
+        let old_headers = (header.size_of_headers() as usize).min(old.len());
+        // Headers keep the section table and bound imports, the only data linkers put there
+        let table_end = self
+            .section_headers()
+            .last()
+            .map_or(old_headers, |section| {
+                section.name().offset() as usize + IMAGE_SIZEOF_SECTION_HEADER as usize
+            });
+        let bound_end = header
+            .data_directory(IMAGE_DIRECTORY_ENTRY_BOUND_IMPORT)
+            .map_or(0, |directory| {
+                directory.virtual_address().into_value() as usize
+                    + directory.size().into_value() as usize
+            });
+        let used_headers = table_end.max(bound_end).min(old_headers);
+        let mut order: Vec<usize> = (0..self.section_headers().len())
+            .filter(|&index| {
+                let section = &self.section_headers()[index];
+                section.pointer_to_raw_data().into_value() != 0
+                    && section.size_of_raw_data().into_value() != 0
+            })
+            .collect();
+        order.sort_by_key(|&index| {
+            self.section_headers()[index]
+                .pointer_to_raw_data()
+                .into_value()
+        });
+
+        // Old file range, new offset and new size of each section's raw data
+        let mut moves = Vec::new();
+        let mut data = old[..used_headers].to_vec();
+        data.resize(align(used_headers)?, 0);
+        let mut old_end = old_headers;
+        for &index in &order {
+            let section = &self.section_headers()[index];
+            let start = section.pointer_to_raw_data().into_value() as usize;
+            let size = section.size_of_raw_data().into_value() as usize;
+            if start < old_end {
+                return Err(invalid_input(
+                    "sections overlap in the file and cannot be moved apart",
+                ));
+            }
+            let end = start.saturating_add(size);
+            old_end = end;
+            // Raw data past VirtualSize is never mapped and the loader zero-fills up to it,
+            // so neither it nor trailing zeros need to be carried over
+            let virtual_size = section.virtual_size().into_value() as usize;
+            let mut raw = old
+                .get(start..start.saturating_add(size).min(old.len()))
+                .unwrap_or(&[]);
+            if virtual_size != 0 {
+                raw = &raw[..raw.len().min(virtual_size)];
+                let used = raw
+                    .iter()
+                    .rposition(|&byte| byte != 0)
+                    .map_or(1, |last| last + 1);
+                raw = &raw[..used.min(raw.len())];
+            }
+            let kept = if virtual_size == 0 {
+                size
+            } else {
+                raw.len().max(1)
+            };
+            let new_start = data.len();
+            data.extend_from_slice(raw);
+            let new_size = align(kept)?;
+            data.resize(new_start + new_size, 0);
+            moves.push((index, start..end, new_start, new_size));
+        }
+        let overlay_start = data.len();
+        data.extend_from_slice(old.get(old_end..).unwrap_or(&[]));
+        let relocate = |offset: usize| -> usize {
+            if offset >= old_end {
+                return overlay_start + (offset - old_end);
+            }
+            moves
+                .iter()
+                .find(|(_, range, _, _)| range.contains(&offset))
+                .map_or(offset, |(_, range, new_start, _)| {
+                    new_start + (offset - range.start)
+                })
+        };
+
+        for (index, _, new_start, new_size) in &moves {
+            let section = &self.section_headers()[*index];
+            let size = *new_size as u32;
+            write_at(
+                &mut data,
+                section.pointer_to_raw_data().offset(),
+                &(*new_start as u32).to_le_bytes(),
+            );
+            write_at(
+                &mut data,
+                section.size_of_raw_data().offset(),
+                &size.to_le_bytes(),
+            );
+        }
+        let (file_alignment, size_of_headers) = match header {
+            OptionalHeaderWrapper::X32(h) => (h.file_alignment(), h.size_of_headers()),
+            OptionalHeaderWrapper::X64(h) => (h.file_alignment(), h.size_of_headers()),
+        };
+        write_at(&mut data, file_alignment.offset(), &alignment.to_le_bytes());
+        let headers = align(used_headers)? as u32;
+        write_at(&mut data, size_of_headers.offset(), &headers.to_le_bytes());
+
+        let symbols = self.file_header().pointer_to_symbol_table();
+        if *symbols.value() != 0 {
+            let moved = relocate(*symbols.value() as usize) as u32;
+            write_at(&mut data, symbols.offset(), &moved.to_le_bytes());
+        }
+        let debug = header
+            .data_directory(IMAGE_DIRECTORY_ENTRY_DEBUG)
+            .and_then(|directory| self.rva_to_offset(directory.virtual_address().into_value()));
+        if let Some(table) = debug {
+            let table = relocate(table);
+            for (index, entry) in self.debug_directory()?.iter().enumerate() {
+                if entry.pointer_to_raw_data() != 0 {
+                    let field = table + index * IMAGE_SIZEOF_DEBUG_DIRECTORY + 24;
+                    let moved = relocate(entry.pointer_to_raw_data() as usize) as u32;
+                    write_at(&mut data, field as u64, &moved.to_le_bytes());
+                }
+            }
+        }
+
+        *self = PortExe::from_bytes(data)?;
+        self.refresh_checksum();
+        Ok(true)
+    }
+
+    /// Appends zeros so the file is `size` bytes long, as an overlay after the last
+    /// section.
+    pub fn pad_to(&mut self, size: usize) -> io::Result<()> {
+        if self.layout().is_mapped() {
+            return Err(invalid_input("mapped images cannot be edited"));
+        }
+        let length = self.data().len();
+        if size < length {
+            return Err(invalid_input("file is already larger than the target size"));
+        }
+        if size == length {
+            return Ok(());
+        }
+        self.refuse_signed()?;
+        let mut data = self.to_bytes();
+        data.resize(size, 0);
+        *self = PortExe::from_bytes(data)?;
+        self.refresh_checksum();
+        Ok(())
+    }
+
+    /// Gives each section index in `names` the name next to it, and returns the sections
+    /// whose name changed.
+    ///
+    /// Names must be non-empty, must not contain NUL bytes and must not start with `/`,
+    /// which marks string table references. Names longer than 8 bytes are stored as
+    /// `long_names` says. Nothing is changed if any name is rejected.
+    pub fn rename_sections(
+        &mut self,
+        names: &[(usize, String)],
+        long_names: LongNames,
+    ) -> io::Result<Vec<SectionRename>> {
+        if self.layout().is_mapped() {
+            return Err(invalid_input("mapped images cannot be edited"));
+        }
+        let mut renames = Vec::new();
+        for (index, name) in names {
+            let section = self
+                .section_headers()
+                .get(*index)
+                .ok_or_else(|| invalid_input("section index out of range"))?;
+            if name.is_empty() || name.contains('\0') || name.starts_with('/') {
+                return Err(invalid_input(&format!("invalid section name {:?}", name)));
+            }
+            if name.len() > 8 && long_names == LongNames::Refuse {
+                return Err(invalid_input(&format!(
+                    "section name {} is longer than 8 bytes",
+                    name
+                )));
+            }
+            let before = self.section_name(section);
+            if before != name.as_str() {
+                renames.push(SectionRename {
+                    index: *index,
+                    before,
+                    after: name.clone(),
+                });
+            }
+        }
+        if renames.is_empty() {
+            return Ok(renames);
+        }
+
+        let mut data = self.to_bytes();
+        let long = renames.iter().any(|rename| rename.after.len() > 8);
+        let mut strings = if long {
+            Some(self.appendable_string_table(&mut data)?)
+        } else {
+            None
+        };
+        for rename in &renames {
+            let mut field = [0u8; 8];
+            if rename.after.len() > 8 {
+                let start = strings.expect("string table was prepared");
+                let size = read_u32(&data, start).unwrap_or(4);
+                let reference = format!("/{}", size);
+                if reference.len() > 8 {
+                    return Err(invalid_input("string table is too large"));
+                }
+                data.extend_from_slice(rename.after.as_bytes());
+                data.push(0);
+                let size = size + rename.after.len() as u32 + 1;
+                data[start..start + 4].copy_from_slice(&size.to_le_bytes());
+                strings = Some(start);
+                field[..reference.len()].copy_from_slice(reference.as_bytes());
+            } else {
+                field[..rename.after.len()].copy_from_slice(rename.after.as_bytes());
+            }
+            let offset = self.section_headers()[rename.index].name().offset() as usize;
+            data[offset..offset + 8].copy_from_slice(&field);
+        }
+        *self = PortExe::from_bytes(data)?;
+        self.refresh_checksum();
+        Ok(renames)
+    }
+
+    /// Offset in `data` of the size field of a string table that ends the file, after
+    /// creating an empty one past the end of a file without a symbol table
+    fn appendable_string_table(&self, data: &mut Vec<u8>) -> io::Result<usize> {
+        self.refuse_signed()?;
+        let file_header = self.file_header();
+        if let Some(table) = self.string_table() {
+            let start = file_header.pointer_to_symbol_table().into_value() as usize
+                + file_header.number_of_symbols().into_value() as usize * IMAGE_SIZEOF_SYMBOL;
+            if start + table.len() != data.len() {
+                return Err(invalid_input(
+                    "the string table does not end the file, long names cannot be added",
+                ));
+            }
+            return Ok(start);
+        }
+        if file_header.pointer_to_symbol_table().into_value() != 0
+            || file_header.number_of_symbols().into_value() != 0
+        {
+            return Err(invalid_input("the symbol table has no string table"));
+        }
+        if self.overlay_offset() != data.len() {
+            return Err(invalid_input(
+                "file has an overlay, a string table cannot be added",
+            ));
+        }
+        let start = data.len();
+        let pointer = file_header.pointer_to_symbol_table().offset() as usize;
+        data[pointer..pointer + 4].copy_from_slice(&(start as u32).to_le_bytes());
+        data.extend_from_slice(&4u32.to_le_bytes());
+        Ok(start)
+    }
+
+    /// Conventional names for sections whose name differs from the one a linker would
+    /// give them, such as those packers leave behind.
+    ///
+    /// Sections starting with the export, import, resource, exception or base
+    /// relocation directory are named after it; others by their characteristics as
+    /// `.bss` when they have no file data, or `.text`, `.data` or `.rdata`. Repeated names get a number, as in `.text1`.
+    pub fn normalized_section_names(&self) -> Vec<(usize, String)> {
+        let directories = [
+            (IMAGE_DIRECTORY_ENTRY_EXPORT, ".edata"),
+            (IMAGE_DIRECTORY_ENTRY_IMPORT, ".idata"),
+            (IMAGE_DIRECTORY_ENTRY_RESOURCE, ".rsrc"),
+            (IMAGE_DIRECTORY_ENTRY_EXCEPTION, ".pdata"),
+            (IMAGE_DIRECTORY_ENTRY_BASERELOC, ".reloc"),
+        ];
+        let header = self.optional_header();
+        let mut used: Vec<String> = Vec::new();
+        let mut names = Vec::new();
+        for (index, section) in self.section_headers().iter().enumerate() {
+            let start = section.virtual_address().into_value();
+            let characteristics = le_u32(section.characteristics().raw_bytes());
+            let directory = directories.iter().find(|(entry, _)| {
+                header
+                    .and_then(|header| header.data_directory(*entry))
+                    .map_or(false, |directory| {
+                        directory.size().into_value() != 0
+                            && directory.virtual_address().into_value() == start
+                    })
+            });
+            let base = match directory {
+                Some((_, name)) => *name,
+                None if characteristics & IMAGE_SCN_CNT_UNINITIALIZED_DATA != 0
+                    && section.size_of_raw_data().into_value() == 0 =>
+                {
+                    ".bss"
+                }
+                None if characteristics & (IMAGE_SCN_CNT_CODE | IMAGE_SCN_MEM_EXECUTE) != 0 => {
+                    ".text"
+                }
+                None if characteristics & IMAGE_SCN_MEM_WRITE != 0 => ".data",
+                None => ".rdata",
+            };
+            let count = used.iter().filter(|name| name.as_str() == base).count();
+            used.push(base.to_string());
+            let name = match count {
+                0 => base.to_string(),
+                count => format!("{}{}", base, count),
+            };
+            if self.section_name(section) != name.as_str() {
+                names.push((index, name));
+            }
+        }
+        names
+    }
+
+    /// Random 8-byte names for every section, the same for the same `seed`, as
+    /// used to check that detections do not hinge on section names
+    pub fn random_section_names(&self, seed: u64) -> Vec<(usize, String)> {
+        let mut state = seed;
+        (0..self.section_headers().len())
+            .map(|index| {
+                let name: String = std::iter::once('.')
+                    .chain((0..7).map(|_| {
+                        state = state
+                            .wrapping_mul(6_364_136_223_846_793_005)
+                            .wrapping_add(1_442_695_040_888_963_407);
+                        (b'a' + ((state >> 33) % 26) as u8) as char
+                    }))
+                    .collect();
+                (index, name)
+            })
+            .collect()
+    }
+
+    pub(crate) fn refuse_signed(&self) -> io::Result<()> {
+        let signed = self
+            .optional_header()
+            .and_then(|header| header.data_directory(IMAGE_DIRECTORY_ENTRY_SECURITY))
+            .map_or(false, |directory| directory.size().into_value() != 0);
+        if signed {
+            return Err(invalid_input(
+                "file is signed, editing it would invalidate the signature",
+            ));
+        }
+        Ok(())
+    }
+
+    /// PE checksum of the file contents, as computed by `CheckSumMappedFile`
+    pub fn compute_checksum(&self) -> Option<u32> {
+        let checksum_offset = match self.optional_header()? {
+            OptionalHeaderWrapper::X32(h) => h.checksum().offset(),
+            OptionalHeaderWrapper::X64(h) => h.checksum().offset(),
+        } as usize;
+        let data = self.data();
+        let mut sum: u32 = 0;
+        for (index, word) in data.chunks(2).enumerate() {
+            if index * 2 == checksum_offset || index * 2 == checksum_offset + 2 {
+                continue;
+            }
+            let word = le_u16([word[0], word.get(1).copied().unwrap_or(0)]);
+            sum += word as u32;
+            sum = (sum & 0xFFFF) + (sum >> 16);
+        }
+        sum = (sum & 0xFFFF) + (sum >> 16);
+        Some(sum.wrapping_add(data.len() as u32))
+    }
+
+    /// Recomputes a nonzero checksum over the current contents.
+    pub(crate) fn refresh_checksum(&mut self) {
+        let current = self.optional_header().map_or(0, |header| header.checksum());
+        if current == 0 {
+            return;
+        }
+        if let Some(checksum) = self.compute_checksum() {
+            if let Some(header) = self.optional_header_mut() {
+                header.set_checksum(checksum);
+            }
+            self.commit_headers();
+        }
+    }
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
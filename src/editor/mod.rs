@@ -0,0 +1,642 @@
+use crate::optional_header::{
+    IMAGE_DIRECTORY_ENTRY_IMPORT, IMAGE_DIRECTORY_ENTRY_SECURITY, IMAGE_NUMBEROF_DIRECTORY_ENTRIES,
+};
+use crate::port_exe::PortExe;
+use crate::validation::{Finding, ValidationMode};
+use std::io;
+use std::ops::Range;
+
+/// Size of an `IMAGE_IMPORT_DESCRIPTOR`
+const IMPORT_DESCRIPTOR_SIZE: usize = 20;
+
+/// Bytes replaced by an [`Operation`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    offset: usize,
+    before: Vec<u8>,
+    after: Vec<u8>,
+}
+
+impl Change {
+    /// File offset of the replaced bytes, after the changes before it in the same
+    /// operation are applied
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn before(&self) -> &[u8] {
+        &self.before
+    }
+
+    /// Replacement bytes, of a different length than [`Change::before`] only for
+    /// operations that grow or shrink the file
+    pub fn after(&self) -> &[u8] {
+        &self.after
+    }
+
+    /// File range the change replaces
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.before.len()
+    }
+
+    fn inverse(&self) -> Self {
+        Self {
+            offset: self.offset,
+            before: self.after.clone(),
+            after: self.before.clone(),
+        }
+    }
+}
+
+/// Entry of the journal of a [`PeEditor`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operation {
+    name: String,
+    changes: Vec<Change>,
+}
+
+impl Operation {
+    /// `write` for [`PeEditor::write`], the name given to [`PeEditor::apply`] otherwise
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Changes in the order they are applied
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+}
+
+/// What an edit of a [`PeEditor`] changed and which structures it refreshed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Refresh {
+    regions: Vec<Range<usize>>,
+    headers: bool,
+    imports: bool,
+    applied: bool,
+}
+
+impl Refresh {
+    /// File ranges the edit replaces, in the order it replaces them
+    pub fn regions(&self) -> &[Range<usize>] {
+        &self.regions
+    }
+
+    /// Whether the edit touches the headers, which are parsed and validated again
+    pub fn reparsed_headers(&self) -> bool {
+        self.headers
+    }
+
+    /// Whether the import checks run again, after an edit of the headers or of the
+    /// descriptors, lookup tables or DLL names of the import directory
+    pub fn revalidated_imports(&self) -> bool {
+        self.imports
+    }
+
+    /// Whether the edit was carried out, `false` in dry-run mode
+    pub fn applied(&self) -> bool {
+        self.applied
+    }
+}
+
+/// Byte-level editor over a [`PortExe`] that keeps the parsed headers and the
+/// [`PortExe::validate`] findings current.
+///
+/// Only the headers are parsed eagerly; every other structure is read on demand from
+/// the file contents. After each write the editor works out which of the two the
+/// changed bytes belong to and refreshes only those, so edits to section data of a
+/// large binary cost no more than the write itself.
+///
+/// Every edit is journaled as an [`Operation`] that can be undone and redone. In
+/// dry-run mode edits report the ranges they would replace and leave the file alone.
+#[derive(Debug)]
+pub struct PeEditor {
+    pe: PortExe,
+    mode: ValidationMode,
+    dry_run: bool,
+    findings: Vec<Finding>,
+    /// Number of leading `findings` produced by the header checks
+    header_findings: usize,
+    /// File ranges the import checks read
+    import_extents: Vec<Range<usize>>,
+    /// Ranges changed since the editor was created, sorted and merged
+    dirty: Vec<Range<usize>>,
+    journal: Vec<Operation>,
+    undone: Vec<Operation>,
+}
+
+impl PeEditor {
+    /// Starts editing `pe`, validating it once in `mode`.
+    pub fn new(pe: PortExe, mode: ValidationMode) -> Self {
+        let mut editor = Self {
+            pe,
+            mode,
+            dry_run: false,
+            findings: Vec::new(),
+            header_findings: 0,
+            import_extents: Vec::new(),
+            dirty: Vec::new(),
+            journal: Vec::new(),
+            undone: Vec::new(),
+        };
+        editor.revalidate_headers();
+        editor
+    }
+
+    pub fn pe(&self) -> &PortExe {
+        &self.pe
+    }
+
+    pub fn into_inner(self) -> PortExe {
+        self.pe
+    }
+
+    /// Findings of [`PortExe::validate`] for the current contents, in the same order
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// File ranges changed since the editor was created, sorted and with adjacent
+    /// ranges merged. Undoing an edit does not clear its range.
+    pub fn dirty_regions(&self) -> &[Range<usize>] {
+        &self.dirty
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Switches dry-run mode, in which edits, undo and redo only report what they
+    /// would change.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    /// Operations carried out, oldest first
+    pub fn journal(&self) -> &[Operation] {
+        &self.journal
+    }
+
+    /// Operations undone and not redone yet, the next to redo last
+    pub fn undone(&self) -> &[Operation] {
+        &self.undone
+    }
+
+    /// Overwrites the file contents at `offset` with `bytes` and refreshes the structures
+    /// they belong to.
+    ///
+    /// Writes past the end of the file are refused. A write that leaves the headers
+    /// unparseable is undone and its error returned.
+    pub fn write(&mut self, offset: usize, bytes: &[u8]) -> io::Result<Refresh> {
+        let changes = self.write_changes(offset, bytes)?;
+        self.record("write", changes)
+    }
+
+    /// Runs one of the [`PortExe`] edits, such as [`PortExe::enable_aslr`], and
+    /// journals the bytes it changed under `name`.
+    ///
+    /// In dry-run mode the edit runs against the file and is reverted right away, so its
+    /// result and the returned ranges are those of the real edit.
+    pub fn apply<T, F>(&mut self, name: &str, edit: F) -> io::Result<(T, Refresh)>
+    where
+        F: FnOnce(&mut PortExe) -> io::Result<T>,
+    {
+        let (value, changes) = self.edit_changes(edit)?;
+        Ok((value, self.record(name, changes)?))
+    }
+
+    /// Runs `edits` as one operation that is kept only if the result passes a
+    /// consistency gate, and rolled back otherwise.
+    ///
+    /// Before the gate a nonzero checksum is recomputed, as the [`PortExe`] edits do.
+    /// The gate then requires that no two sections overlap in memory or in the file,
+    /// that a nonzero checksum matches the contents, and that every data directory lies
+    /// within the headers or a single section. A failed gate or an error from `edits`
+    /// undoes every change the transaction made and returns the error.
+    ///
+    /// The transaction is journaled as a single operation named `transaction`. In
+    /// dry-run mode it is always rolled back, after `edits` and the gate have run.
+    pub fn transaction<T, F>(&mut self, edits: F) -> io::Result<T>
+    where
+        F: FnOnce(&mut Transaction<'_>) -> io::Result<T>,
+    {
+        let dry_run = std::mem::replace(&mut self.dry_run, false);
+        let dirty = self.dirty.clone();
+        let mut transaction = Transaction {
+            editor: self,
+            changes: Vec::new(),
+        };
+        let result = edits(&mut transaction).and_then(|value| {
+            transaction.apply(|pe| {
+                pe.refresh_checksum();
+                Ok(())
+            })?;
+            match gate(transaction.pe()) {
+                problems if problems.is_empty() => Ok(value),
+                problems => Err(invalid_data(format!(
+                    "transaction rolled back: {}",
+                    problems.join("; ")
+                ))),
+            }
+        });
+        let changes = transaction.changes;
+        self.dry_run = dry_run;
+
+        if result.is_ok() && !dry_run {
+            if !changes.is_empty() {
+                self.journal.push(Operation {
+                    name: "transaction".to_string(),
+                    changes,
+                });
+                self.undone.clear();
+            }
+            return result;
+        }
+        let inverse: Vec<Change> = changes.iter().rev().map(Change::inverse).collect();
+        let applied = std::mem::replace(&mut self.dry_run, false);
+        let rollback = self.perform(&inverse);
+        self.dry_run = applied;
+        rollback?;
+        self.dirty = dirty;
+        result
+    }
+
+    /// Reverts the most recent operation of the journal, `None` if it is empty.
+    pub fn undo(&mut self) -> io::Result<Option<Refresh>> {
+        let inverse: Vec<Change> = match self.journal.last() {
+            Some(operation) => operation
+                .changes
+                .iter()
+                .rev()
+                .map(Change::inverse)
+                .collect(),
+            None => return Ok(None),
+        };
+        let refresh = self.perform(&inverse)?;
+        if refresh.applied {
+            let operation = self.journal.pop().expect("journal is not empty");
+            self.undone.push(operation);
+        }
+        Ok(Some(refresh))
+    }
+
+    /// Carries out the most recently undone operation again, `None` if there is none.
+    pub fn redo(&mut self) -> io::Result<Option<Refresh>> {
+        let changes = match self.undone.last() {
+            Some(operation) => operation.changes.clone(),
+            None => return Ok(None),
+        };
+        let refresh = self.perform(&changes)?;
+        if refresh.applied {
+            let operation = self.undone.pop().expect("an operation was undone");
+            self.journal.push(operation);
+        }
+        Ok(Some(refresh))
+    }
+
+    /// Changes that write `bytes` at `offset`
+    fn write_changes(&self, offset: usize, bytes: &[u8]) -> io::Result<Vec<Change>> {
+        let end = offset
+            .checked_add(bytes.len())
+            .filter(|&end| end <= self.pe.data().len())
+            .ok_or_else(|| invalid_input("write past the end of the file"))?;
+        let before = &self.pe.data()[offset..end];
+        if before == bytes {
+            return Ok(Vec::new());
+        }
+        Ok(vec![Change {
+            offset,
+            before: before.to_vec(),
+            after: bytes.to_vec(),
+        }])
+    }
+
+    /// Runs `edit` and reverts it, returning its result and the changes it made
+    fn edit_changes<T, F>(&mut self, edit: F) -> io::Result<(T, Vec<Change>)>
+    where
+        F: FnOnce(&mut PortExe) -> io::Result<T>,
+    {
+        let before = self.pe.to_bytes();
+        let result = edit(&mut self.pe);
+        let after = self.pe.to_bytes();
+        *self.pe.data_mut() = before;
+        self.pe.reparse_headers()?;
+        let value = result?;
+        Ok((value, diff(self.pe.data(), &after)))
+    }
+
+    /// Performs `changes` and journals them as a new operation, dropping the operations
+    /// that could be redone.
+    fn record(&mut self, name: &str, changes: Vec<Change>) -> io::Result<Refresh> {
+        let refresh = self.perform(&changes)?;
+        if refresh.applied && !changes.is_empty() {
+            self.journal.push(Operation {
+                name: name.to_string(),
+                changes,
+            });
+            self.undone.clear();
+        }
+        Ok(refresh)
+    }
+
+    /// Applies `changes` in order and refreshes what they touch; in dry-run mode only
+    /// works out what they would touch.
+    fn perform(&mut self, changes: &[Change]) -> io::Result<Refresh> {
+        let headers_end = self.pe.headers_end();
+        let headers = changes.iter().any(|change| change.offset < headers_end);
+        let imports = headers
+            || changes.iter().any(|change| {
+                let range = change.range();
+                self.import_extents
+                    .iter()
+                    .any(|extent| extent.start < range.end && range.start < extent.end)
+            });
+        let regions = changes.iter().map(Change::range).collect();
+        if self.dry_run {
+            return Ok(Refresh {
+                regions,
+                headers,
+                imports,
+                applied: false,
+            });
+        }
+
+        for change in changes {
+            splice(self.pe.data_mut(), change);
+        }
+        if headers {
+            if let Err(error) = self.pe.reparse_headers() {
+                for change in changes.iter().rev() {
+                    splice(self.pe.data_mut(), &change.inverse());
+                }
+                return Err(error);
+            }
+            self.revalidate_headers();
+        } else if imports {
+            self.revalidate_imports();
+        }
+        for change in changes {
+            self.mark_dirty(change.offset..change.offset + change.after.len());
+        }
+        Ok(Refresh {
+            regions,
+            headers,
+            imports,
+            applied: true,
+        })
+    }
+
+    fn revalidate_headers(&mut self) {
+        self.findings = self.pe.validate_headers(self.mode);
+        self.header_findings = self.findings.len();
+        self.revalidate_imports();
+    }
+
+    fn revalidate_imports(&mut self) {
+        self.findings.truncate(self.header_findings);
+        self.findings.extend(self.pe.validate_imports(self.mode));
+        self.import_extents = import_extents(&self.pe);
+    }
+
+    fn mark_dirty(&mut self, region: Range<usize>) {
+        if region.is_empty() {
+            return;
+        }
+        let mut merged = region;
+        self.dirty.retain(|dirty| {
+            if dirty.start <= merged.end && merged.start <= dirty.end {
+                merged = merged.start.min(dirty.start)..merged.end.max(dirty.end);
+                false
+            } else {
+                true
+            }
+        });
+        let index = self
+            .dirty
+            .iter()
+            .position(|dirty| dirty.start > merged.start)
+            .unwrap_or(self.dirty.len());
+        self.dirty.insert(index, merged);
+    }
+}
+
+/// Edits of a [`PeEditor::transaction`], carried out right away and journaled together
+pub struct Transaction<'a> {
+    editor: &'a mut PeEditor,
+    changes: Vec<Change>,
+}
+
+impl Transaction<'_> {
+    /// The file as edited so far
+    pub fn pe(&self) -> &PortExe {
+        &self.editor.pe
+    }
+
+    /// Findings of [`PortExe::validate`] for the file as edited so far
+    pub fn findings(&self) -> &[Finding] {
+        &self.editor.findings
+    }
+
+    /// Same as [`PeEditor::write`], as part of the transaction
+    pub fn write(&mut self, offset: usize, bytes: &[u8]) -> io::Result<Refresh> {
+        let changes = self.editor.write_changes(offset, bytes)?;
+        self.perform(changes)
+    }
+
+    /// Same as [`PeEditor::apply`], as part of the transaction
+    pub fn apply<T, F>(&mut self, edit: F) -> io::Result<(T, Refresh)>
+    where
+        F: FnOnce(&mut PortExe) -> io::Result<T>,
+    {
+        let (value, changes) = self.editor.edit_changes(edit)?;
+        Ok((value, self.perform(changes)?))
+    }
+
+    fn perform(&mut self, changes: Vec<Change>) -> io::Result<Refresh> {
+        let refresh = self.editor.perform(&changes)?;
+        self.changes.extend(changes);
+        Ok(refresh)
+    }
+}
+
+/// Problems that keep a [`PeEditor::transaction`] from committing
+fn gate(pe: &PortExe) -> Vec<String> {
+    let header = match pe.optional_header() {
+        Some(header) => header,
+        None => return Vec::new(),
+    };
+    let mut problems = Vec::new();
+
+    let sections = pe.section_headers();
+    let mut virtual_extents: Vec<(u32, u32, usize)> = sections
+        .iter()
+        .enumerate()
+        .map(|(index, section)| {
+            let start = section.virtual_address().into_value();
+            let size = match section.virtual_size().into_value() {
+                0 => section.size_of_raw_data().into_value(),
+                size => size,
+            };
+            (start, start.saturating_add(size), index)
+        })
+        .collect();
+    let mut file_extents: Vec<(u32, u32, usize)> = sections
+        .iter()
+        .enumerate()
+        .filter(|(_, section)| section.size_of_raw_data().into_value() != 0)
+        .map(|(index, section)| {
+            let start = section.pointer_to_raw_data().into_value();
+            let size = section.size_of_raw_data().into_value();
+            (start, start.saturating_add(size), index)
+        })
+        .collect();
+    for (extents, space) in [
+        (&mut virtual_extents, "memory"),
+        (&mut file_extents, "the file"),
+    ] {
+        extents.sort_unstable();
+        for pair in extents.windows(2) {
+            if pair[1].0 < pair[0].1 {
+                problems.push(format!(
+                    "sections {} and {} overlap in {}",
+                    pe.section_name(&sections[pair[0].2]),
+                    pe.section_name(&sections[pair[1].2]),
+                    space
+                ));
+            }
+        }
+    }
+
+    let checksum = header.checksum();
+    if checksum != 0 && pe.compute_checksum() != Some(checksum) {
+        problems.push(format!(
+            "checksum {:#x} does not match the contents",
+            checksum
+        ));
+    }
+
+    let size_of_headers = header.size_of_headers();
+    for directory in header
+        .data_directories()
+        .iter()
+        .take(IMAGE_NUMBEROF_DIRECTORY_ENTRIES as usize)
+    {
+        let (rva, size) = (
+            directory.virtual_address().into_value(),
+            directory.size().into_value(),
+        );
+        if rva == 0 || size == 0 {
+            continue;
+        }
+        let end = rva as u64 + size as u64;
+        let consistent = if directory.index() == IMAGE_DIRECTORY_ENTRY_SECURITY {
+            // A file offset rather than an RVA
+            end <= pe.data().len() as u64
+        } else {
+            end <= size_of_headers as u64
+                || virtual_extents
+                    .iter()
+                    .any(|&(start, stop, _)| rva >= start && end <= stop as u64)
+        };
+        if !consistent {
+            problems.push(format!(
+                "data directory {} at {:#x}+{:#x} is not within the headers or a section",
+                directory.index(),
+                rva,
+                size
+            ));
+        }
+    }
+    problems
+}
+
+/// Replaces the bytes `change` covers with its replacement.
+fn splice(data: &mut Vec<u8>, change: &Change) {
+    data.splice(change.range(), change.after.iter().copied());
+}
+
+/// Runs of differing bytes between `before` and `after`, with whatever one has past the
+/// end of the other folded into the last change
+fn diff(before: &[u8], after: &[u8]) -> Vec<Change> {
+    let common = before.len().min(after.len());
+    let mut changes: Vec<Change> = Vec::new();
+    let mut index = 0;
+    while index < common {
+        if before[index] == after[index] {
+            index += 1;
+            continue;
+        }
+        let start = index;
+        while index < common && before[index] != after[index] {
+            index += 1;
+        }
+        changes.push(Change {
+            offset: start,
+            before: before[start..index].to_vec(),
+            after: after[start..index].to_vec(),
+        });
+    }
+    if before.len() != after.len() {
+        match changes.last_mut() {
+            Some(last) if last.offset + last.before.len() == common => {
+                last.before.extend_from_slice(&before[common..]);
+                last.after.extend_from_slice(&after[common..]);
+            }
+            _ => changes.push(Change {
+                offset: common,
+                before: before[common..].to_vec(),
+                after: after[common..].to_vec(),
+            }),
+        }
+    }
+    changes
+}
+
+/// File ranges of the import descriptors, DLL names and lookup tables, the parts of the
+/// import directory the import checks depend on
+fn import_extents(pe: &PortExe) -> Vec<Range<usize>> {
+    let header = match pe.optional_header() {
+        Some(header) => header,
+        None => return Vec::new(),
+    };
+    let directory_rva = header
+        .data_directory(IMAGE_DIRECTORY_ENTRY_IMPORT)
+        .map_or(0, |directory| directory.virtual_address().into_value());
+    // Malformed imports are left to the import parser to report, as in the checks
+    let descriptors = pe.imports().unwrap_or_default();
+    let thunk_size = if header.image_type().is_x64() { 8 } else { 4 };
+    let extent = |rva: u32, size: usize| {
+        pe.rva_to_offset(rva)
+            .map(|offset| offset..offset.saturating_add(size))
+    };
+
+    let mut extents: Vec<Range<usize>> = extent(
+        directory_rva,
+        (descriptors.len() + 1) * IMPORT_DESCRIPTOR_SIZE,
+    )
+    .into_iter()
+    .collect();
+    for descriptor in &descriptors {
+        extents.extend(extent(
+            descriptor.name_rva(),
+            descriptor.dll_name().len() + 1,
+        ));
+        let lookup = match descriptor.original_first_thunk() {
+            0 => descriptor.first_thunk(),
+            rva => rva,
+        };
+        extents.extend(extent(
+            lookup,
+            (descriptor.imports().len() + 1) * thunk_size,
+        ));
+    }
+    extents
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
@@ -0,0 +1,182 @@
+use std::fmt;
+
+/// Byte order of a multi-byte value.
+///
+/// PE and COFF structures are little-endian whatever the host; the few big-endian
+/// values the crate reads come from embedded formats such as zlib streams and ASN.1
+/// strings. Every conversion goes through this module and names its byte order, so
+/// results do not depend on the byte order of the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    Little,
+    Big,
+}
+
+pub(crate) fn le_u16(bytes: [u8; 2]) -> u16 {
+    u16::from_le_bytes(bytes)
+}
+
+pub(crate) fn le_u32(bytes: [u8; 4]) -> u32 {
+    u32::from_le_bytes(bytes)
+}
+
+pub(crate) fn le_u64(bytes: [u8; 8]) -> u64 {
+    u64::from_le_bytes(bytes)
+}
+
+pub(crate) fn be_u16(bytes: [u8; 2]) -> u16 {
+    u16::from_be_bytes(bytes)
+}
+
+pub(crate) fn be_u32(bytes: [u8; 4]) -> u32 {
+    u32::from_be_bytes(bytes)
+}
+
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset.checked_add(2)?)?;
+    Some(le_u16([bytes[0], bytes[1]]))
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(le_u32([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub(crate) fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset.checked_add(8)?)?;
+    let mut value = [0u8; 8];
+    value.copy_from_slice(bytes);
+    Some(le_u64(value))
+}
+
+pub(crate) fn read_be_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(be_u32([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// UTF-16 code units of `data` in `order`; a trailing odd byte is dropped.
+pub(crate) fn utf16_units(data: &[u8], order: ByteOrder) -> Vec<u16> {
+    data.chunks_exact(2)
+        .map(|unit| match order {
+            ByteOrder::Little => le_u16([unit[0], unit[1]]),
+            ByteOrder::Big => be_u16([unit[0], unit[1]]),
+        })
+        .collect()
+}
+
+/// GUID as stored in CodeView records and the CLR `#GUID` heap.
+///
+/// The 16 bytes are a structure, not a number: `Data1`, `Data2` and `Data3` are
+/// little-endian integers and `Data4` is a byte array kept in stored order, which the
+/// registry form prints as if it were big-endian.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Guid {
+    bytes: [u8; 16],
+}
+
+impl Guid {
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self { bytes }
+    }
+
+    /// Bytes as stored in the file
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.bytes
+    }
+
+    pub fn data1(&self) -> u32 {
+        le_u32([self.bytes[0], self.bytes[1], self.bytes[2], self.bytes[3]])
+    }
+
+    pub fn data2(&self) -> u16 {
+        le_u16([self.bytes[4], self.bytes[5]])
+    }
+
+    pub fn data3(&self) -> u16 {
+        le_u16([self.bytes[6], self.bytes[7]])
+    }
+
+    pub fn data4(&self) -> [u8; 8] {
+        let mut data4 = [0u8; 8];
+        data4.copy_from_slice(&self.bytes[8..]);
+        data4
+    }
+}
+
+/// Registry form, such as `6B29FC40-CA47-1067-B31D-00DD010662DA`
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let data4 = self.data4();
+        write!(
+            f,
+            "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-",
+            self.data1(),
+            self.data2(),
+            self.data3(),
+            data4[0],
+            data4[1]
+        )?;
+        for byte in &data4[2..] {
+            write!(f, "{:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fixture bytes with a distinct value in every position, so a swapped or
+    // native-endian read cannot produce the expected value by accident.
+    const BYTES: [u8; 8] = [0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF];
+
+    #[test]
+    fn little_endian_reads_do_not_depend_on_the_host() {
+        assert_eq!(read_u16(&BYTES, 0), Some(0x2301));
+        assert_eq!(read_u32(&BYTES, 0), Some(0x6745_2301));
+        assert_eq!(read_u64(&BYTES, 0), Some(0xEFCD_AB89_6745_2301));
+        assert_eq!(read_u32(&BYTES, 3), Some(0xCDAB_8967));
+        assert_eq!(le_u16([0x4C, 0x01]), 0x014C);
+    }
+
+    #[test]
+    fn big_endian_reads_do_not_depend_on_the_host() {
+        assert_eq!(be_u16([0x78, 0x9C]), 0x789C);
+        assert_eq!(read_be_u32(&BYTES, 0), Some(0x0123_4567));
+        assert_eq!(read_be_u32(&BYTES, 5), None);
+        assert_eq!(
+            utf16_units(&[0x00, 0x41, 0x04, 0x10], ByteOrder::Big),
+            [0x0041, 0x0410]
+        );
+        assert_eq!(
+            utf16_units(&[0x41, 0x00, 0x10, 0x04, 0xFF], ByteOrder::Little),
+            [0x0041, 0x0410]
+        );
+    }
+
+    #[test]
+    fn reads_past_the_end_fail() {
+        assert_eq!(read_u16(&BYTES, 7), None);
+        assert_eq!(read_u32(&BYTES, 5), None);
+        assert_eq!(read_u64(&BYTES, 1), None);
+        assert_eq!(read_u64(&BYTES, usize::MAX), None);
+    }
+
+    #[test]
+    fn guid_keeps_data4_in_stored_order() {
+        // {6B29FC40-CA47-1067-B31D-00DD010662DA} as laid out in a CodeView record
+        let guid = Guid::from_bytes([
+            0x40, 0xFC, 0x29, 0x6B, 0x47, 0xCA, 0x67, 0x10, 0xB3, 0x1D, 0x00, 0xDD, 0x01, 0x06,
+            0x62, 0xDA,
+        ]);
+        assert_eq!(guid.data1(), 0x6B29_FC40);
+        assert_eq!(guid.data2(), 0xCA47);
+        assert_eq!(guid.data3(), 0x1067);
+        assert_eq!(
+            guid.data4(),
+            [0xB3, 0x1D, 0x00, 0xDD, 0x01, 0x06, 0x62, 0xDA]
+        );
+        assert_eq!(guid.to_string(), "6B29FC40-CA47-1067-B31D-00DD010662DA");
+    }
+}
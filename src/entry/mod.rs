@@ -0,0 +1,195 @@
+use crate::file_header::Machine;
+use crate::port_exe::PortExe;
+use iced_x86::{Decoder, DecoderOptions, FlowControl};
+use std::fmt;
+use std::io;
+
+/// Upper bound on the number of wrappers followed from the entry point
+pub const MAX_ENTRY_HOPS: usize = 8;
+/// Longest wrapper, in instructions, still taken as trivial
+pub const MAX_WRAPPER_INSTRUCTIONS: usize = 32;
+
+/// Shape of a wrapper followed by [`PortExe::entry_trace`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EntryHopKind {
+    /// The code starts with a direct jump
+    Jump,
+    /// One call to an initializer at `init`, then a jump, as in the MSVC
+    /// `mainCRTStartup` calling `__security_init_cookie` before `__scrt_common_main`
+    InitThenJump { init: u32 },
+    /// One call followed by a return
+    CallWrapper,
+}
+
+impl fmt::Display for EntryHopKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Jump => write!(f, "jump"),
+            Self::InitThenJump { init } => write!(f, "call {:#x}, then jump", init),
+            Self::CallWrapper => write!(f, "call wrapper"),
+        }
+    }
+}
+
+/// Wrapper left on the way from the entry point, by RVA
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryHop {
+    from: u32,
+    to: u32,
+    kind: EntryHopKind,
+}
+
+impl EntryHop {
+    /// Start of the wrapper
+    pub fn from(&self) -> u32 {
+        self.from
+    }
+
+    /// Code the wrapper hands over to
+    pub fn to(&self) -> u32 {
+        self.to
+    }
+
+    pub fn kind(&self) -> EntryHopKind {
+        self.kind
+    }
+}
+
+/// Result of [`PortExe::entry_trace`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryTrace {
+    entry_point: u32,
+    hops: Vec<EntryHop>,
+}
+
+impl EntryTrace {
+    /// `AddressOfEntryPoint`
+    pub fn entry_point(&self) -> u32 {
+        self.entry_point
+    }
+
+    /// Wrappers followed, in order
+    pub fn hops(&self) -> &[EntryHop] {
+        &self.hops
+    }
+
+    /// RVA of the first code that is not a trivial wrapper: the probable user `main`,
+    /// `WinMain` or `DllMain` behind a compiler's startup stubs, or the CRT main
+    /// function it dispatches from.
+    pub fn start(&self) -> u32 {
+        self.hops.last().map_or(self.entry_point, |hop| hop.to)
+    }
+}
+
+impl PortExe {
+    /// Follows trivial wrappers from the entry point of an x86 or x64 image: direct
+    /// jumps, an initializer call followed by a jump and single-call stubs, as long as
+    /// they stay in executable sections.
+    ///
+    /// The result is a heuristic meant for triage; it stops at the first code that does
+    /// anything more, at an indirect branch and after [`MAX_ENTRY_HOPS`] wrappers.
+    pub fn entry_trace(&self) -> io::Result<EntryTrace> {
+        let header = self
+            .optional_header()
+            .ok_or_else(|| invalid_input("object files have no entry point"))?;
+        let bitness = match self.file_header().machine().into_value() {
+            Machine::Intel386 => 32,
+            Machine::X64 => 64,
+            machine => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("cannot decode {:?} code", machine),
+                ))
+            }
+        };
+        let entry_point = header.address_of_entry_point();
+        let mut hops: Vec<EntryHop> = Vec::new();
+        let mut rva = entry_point;
+        while hops.len() < MAX_ENTRY_HOPS {
+            let hop = match self.wrapper_at(rva, bitness, header.image_base()) {
+                Some(hop) => hop,
+                None => break,
+            };
+            if hop.to == entry_point || hops.iter().any(|seen| seen.to == hop.to) {
+                break;
+            }
+            rva = hop.to;
+            hops.push(hop);
+        }
+        Ok(EntryTrace { entry_point, hops })
+    }
+
+    /// Decodes the code at `rva` and returns where it hands over to if it is a
+    /// trivial wrapper.
+    fn wrapper_at(&self, rva: u32, bitness: u32, image_base: u64) -> Option<EntryHop> {
+        let code = self.executable_code(rva)?;
+        let ip = image_base.wrapping_add(rva as u64);
+        let target = |address: u64| {
+            let rva = u32::try_from(address.checked_sub(image_base)?).ok()?;
+            self.executable_code(rva).map(|_| rva)
+        };
+        let mut decoder = Decoder::with_ip(bitness, code, ip, DecoderOptions::NONE);
+        let mut calls = Vec::new();
+        for index in 0..MAX_WRAPPER_INSTRUCTIONS {
+            if !decoder.can_decode() {
+                return None;
+            }
+            let instruction = decoder.decode();
+            if instruction.is_invalid() {
+                return None;
+            }
+            match instruction.flow_control() {
+                FlowControl::Next | FlowControl::ConditionalBranch => {}
+                FlowControl::Call => calls.push(target(instruction.near_branch_target())?),
+                FlowControl::UnconditionalBranch => {
+                    let to = target(instruction.near_branch_target())?;
+                    let kind = match calls[..] {
+                        [] if index == 0 => EntryHopKind::Jump,
+                        [init] => EntryHopKind::InitThenJump { init },
+                        _ => return None,
+                    };
+                    return Some(EntryHop {
+                        from: rva,
+                        to,
+                        kind,
+                    });
+                }
+                FlowControl::Return => {
+                    return match calls[..] {
+                        [to] => Some(EntryHop {
+                            from: rva,
+                            to,
+                            kind: EntryHopKind::CallWrapper,
+                        }),
+                        _ => None,
+                    }
+                }
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    /// File data from `rva` to the end of the raw data of the executable section
+    /// holding it.
+    fn executable_code(&self, rva: u32) -> Option<&[u8]> {
+        let section = self.section_at_rva(rva)?;
+        let characteristics = section.characteristics();
+        let flags = characteristics.value();
+        if !flags.mem_execute() && !flags.cnt_code() {
+            return None;
+        }
+        let offset = self.rva_to_offset(rva)?;
+        let delta = rva - section.virtual_address().into_value();
+        let available = section.size_of_raw_data().into_value().checked_sub(delta)?;
+        let end = offset
+            .saturating_add(available as usize)
+            .min(self.data().len());
+        Some(&self.data()[offset..end])
+    }
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
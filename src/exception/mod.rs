@@ -0,0 +1,679 @@
+use crate::arith::rva_add;
+use crate::file_header::Machine;
+use crate::optional_header::IMAGE_DIRECTORY_ENTRY_EXCEPTION;
+use crate::port_exe::PortExe;
+use crate::{read_u16, read_u32};
+use std::fmt;
+use std::io;
+
+/// `UNW_FLAG_EHANDLER`: the function has an exception handler
+pub const UNW_FLAG_EHANDLER: u8 = 0x1;
+/// `UNW_FLAG_UHANDLER`: the function has a termination handler
+pub const UNW_FLAG_UHANDLER: u8 = 0x2;
+/// `UNW_FLAG_CHAININFO`: the unwind info continues that of another function
+pub const UNW_FLAG_CHAININFO: u8 = 0x4;
+
+/// Names of the x64 integer registers by the number unwind codes use
+const X64_REGISTERS: [&str; 16] = [
+    "rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15",
+];
+
+/// Entry of the exception directory (`.pdata`)
+#[derive(Debug, Clone)]
+pub enum RuntimeFunction {
+    Arm(ArmRuntimeFunction),
+    Arm64(Arm64RuntimeFunction),
+    X64(X64RuntimeFunction),
+}
+
+impl RuntimeFunction {
+    /// RVA of the first instruction of the function
+    pub fn begin_address(&self) -> u32 {
+        match self {
+            Self::Arm(function) => function.begin_address(),
+            Self::Arm64(function) => function.begin_address(),
+            Self::X64(function) => function.begin_address(),
+        }
+    }
+
+    /// RVA just past the function, when its length is known
+    pub fn end_address(&self) -> Option<u32> {
+        match self {
+            Self::Arm(function) => function
+                .function_length()
+                .and_then(|length| function.begin_address().checked_add(length)),
+            Self::Arm64(function) => function
+                .function_length()
+                .and_then(|length| function.begin_address().checked_add(length)),
+            Self::X64(function) => Some(function.end_address()),
+        }
+    }
+}
+
+/// x64 `RUNTIME_FUNCTION`
+#[derive(Debug, Clone)]
+pub struct X64RuntimeFunction {
+    begin_address: u32,
+    end_address: u32,
+    unwind_info_rva: u32,
+}
+
+impl X64RuntimeFunction {
+    pub fn begin_address(&self) -> u32 {
+        self.begin_address
+    }
+
+    /// RVA just past the last instruction of the function
+    pub fn end_address(&self) -> u32 {
+        self.end_address
+    }
+
+    /// RVA of the `UNWIND_INFO`
+    pub fn unwind_info_rva(&self) -> u32 {
+        self.unwind_info_rva
+    }
+}
+
+/// ARM (Thumb-2) `.pdata` entry
+#[derive(Debug, Clone)]
+pub struct ArmRuntimeFunction {
+    begin_address: u32,
+    unwind: ArmUnwind,
+}
+
+impl ArmRuntimeFunction {
+    /// Function start with the Thumb bit cleared
+    pub fn begin_address(&self) -> u32 {
+        self.begin_address
+    }
+
+    pub fn unwind(&self) -> &ArmUnwind {
+        &self.unwind
+    }
+
+    /// Function length in bytes, when known from packed or `.xdata` unwind information
+    pub fn function_length(&self) -> Option<u32> {
+        match &self.unwind {
+            ArmUnwind::Packed(packed) => Some(packed.function_length()),
+            ArmUnwind::Unpacked { unwind_data, .. } => {
+                unwind_data.as_ref().map(|data| data.function_length())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ArmUnwind {
+    /// Unwind information packed into the `.pdata` entry itself
+    Packed(ArmPackedUnwind),
+    /// Unwind information stored in `.xdata`
+    Unpacked {
+        unwind_data_rva: u32,
+        unwind_data: Option<ArmUnwindData>,
+    },
+}
+
+/// Packed ARM unwind data (`Flag` 1 or 2)
+#[derive(Debug, Clone)]
+pub struct ArmPackedUnwind {
+    raw: u32,
+}
+
+impl ArmPackedUnwind {
+    /// Function fragment without a prologue (`Flag` = 2)
+    pub fn fragment(&self) -> bool {
+        self.raw & 0x3 == 2
+    }
+
+    pub fn function_length(&self) -> u32 {
+        ((self.raw >> 2) & 0x7FF) * 2
+    }
+
+    /// Return type: 0 = pop {pc}, 1 = 16-bit branch, 2 = 32-bit branch, 3 = no epilogue
+    pub fn ret(&self) -> u8 {
+        ((self.raw >> 13) & 0x3) as u8
+    }
+
+    /// Function homes the integer parameter registers r0-r3
+    pub fn homes_parameters(&self) -> bool {
+        (self.raw >> 15) & 0x1 != 0
+    }
+
+    /// Index of the last saved non-volatile register (r4 + `reg`)
+    pub fn reg(&self) -> u8 {
+        ((self.raw >> 16) & 0x7) as u8
+    }
+
+    /// Saved registers are floating point (d8 + `reg`) instead of integer
+    pub fn r(&self) -> bool {
+        (self.raw >> 19) & 0x1 != 0
+    }
+
+    /// Function saves and restores LR
+    pub fn saves_link_register(&self) -> bool {
+        (self.raw >> 20) & 0x1 != 0
+    }
+
+    /// Function sets up r11 as a frame pointer
+    pub fn chains_frame(&self) -> bool {
+        (self.raw >> 21) & 0x1 != 0
+    }
+
+    /// Stack allocation in 4-byte units, including the folded encodings above 0x3F3
+    pub fn stack_adjust(&self) -> u16 {
+        ((self.raw >> 22) & 0x3FF) as u16
+    }
+}
+
+/// ARM64 `.pdata` entry
+#[derive(Debug, Clone)]
+pub struct Arm64RuntimeFunction {
+    begin_address: u32,
+    unwind: Arm64Unwind,
+}
+
+impl Arm64RuntimeFunction {
+    pub fn begin_address(&self) -> u32 {
+        self.begin_address
+    }
+
+    pub fn unwind(&self) -> &Arm64Unwind {
+        &self.unwind
+    }
+
+    /// Function length in bytes, when known from packed or `.xdata` unwind information
+    pub fn function_length(&self) -> Option<u32> {
+        match &self.unwind {
+            Arm64Unwind::Packed(packed) => Some(packed.function_length()),
+            Arm64Unwind::Unpacked { unwind_data, .. } => {
+                unwind_data.as_ref().map(|data| data.function_length())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Arm64Unwind {
+    /// Unwind information packed into the `.pdata` entry itself
+    Packed(Arm64PackedUnwind),
+    /// Unwind information stored in `.xdata`
+    Unpacked {
+        unwind_data_rva: u32,
+        unwind_data: Option<ArmUnwindData>,
+    },
+}
+
+/// Packed ARM64 unwind data (`Flag` 1 or 2)
+#[derive(Debug, Clone)]
+pub struct Arm64PackedUnwind {
+    raw: u32,
+}
+
+impl Arm64PackedUnwind {
+    /// Code without a prologue or epilogue, such as a separated function part (`Flag` = 2)
+    pub fn fragment(&self) -> bool {
+        self.raw & 0x3 == 2
+    }
+
+    pub fn function_length(&self) -> u32 {
+        ((self.raw >> 2) & 0x7FF) * 4
+    }
+
+    /// Number of saved floating point registers, d8 to d8 + `reg_f`, when non-zero
+    pub fn reg_f(&self) -> u8 {
+        ((self.raw >> 13) & 0x7) as u8
+    }
+
+    /// Number of saved integer registers starting at x19
+    pub fn reg_i(&self) -> u8 {
+        ((self.raw >> 16) & 0xF) as u8
+    }
+
+    /// Function homes the integer parameter registers x0-x7
+    pub fn homes_parameters(&self) -> bool {
+        (self.raw >> 20) & 0x1 != 0
+    }
+
+    /// 0 = LR not saved, 1 = LR saved with the integer registers, 2 = PAC-signed LR
+    /// saved, 3 = frame chained with x29 and LR
+    pub fn cr(&self) -> u8 {
+        ((self.raw >> 21) & 0x3) as u8
+    }
+
+    /// Stack allocated by the function, in bytes
+    pub fn frame_size(&self) -> u32 {
+        ((self.raw >> 23) & 0x1FF) * 16
+    }
+}
+
+/// Header of an ARM or ARM64 `.xdata` record
+#[derive(Debug, Clone)]
+pub struct ArmUnwindData {
+    function_length: u32,
+    version: u8,
+    has_exception_data: bool,
+    epilogue_in_header: bool,
+    fragment: bool,
+    epilogue_count: u16,
+    code_words: u8,
+    exception_handler_rva: Option<u32>,
+}
+
+impl ArmUnwindData {
+    fn read(pe: &PortExe, rva: u32) -> Option<Self> {
+        Self::read_for(pe, rva, false)
+    }
+
+    /// Reads an ARM64 record, which has no `F` bit and one more bit of code words.
+    fn read_arm64(pe: &PortExe, rva: u32) -> Option<Self> {
+        Self::read_for(pe, rva, true)
+    }
+
+    fn read_for(pe: &PortExe, rva: u32, arm64: bool) -> Option<Self> {
+        let header = read_u32(pe.data_at_rva(rva, 4)?, 0)?;
+        let (mut epilogue_count, mut code_words) = if arm64 {
+            (
+                ((header >> 22) & 0x1F) as u16,
+                ((header >> 27) & 0x1F) as u8,
+            )
+        } else {
+            (((header >> 23) & 0x1F) as u16, ((header >> 28) & 0xF) as u8)
+        };
+        let mut size = 4;
+        if epilogue_count == 0 && code_words == 0 {
+            let extension = read_u32(pe.data_at_rva(rva_add(rva, 4)?, 4)?, 0)?;
+            epilogue_count = (extension & 0xFFFF) as u16;
+            code_words = ((extension >> 16) & 0xFF) as u8;
+            size += 4;
+        }
+        let epilogue_in_header = (header >> 21) & 0x1 != 0;
+        let has_exception_data = (header >> 20) & 0x1 != 0;
+        if !epilogue_in_header {
+            size += epilogue_count as u32 * 4;
+        }
+        size += code_words as u32 * 4;
+        let exception_handler_rva = if has_exception_data {
+            rva_add(rva, size)
+                .and_then(|handler| pe.data_at_rva(handler, 4))
+                .and_then(|data| read_u32(data, 0))
+        } else {
+            None
+        };
+
+        Some(Self {
+            function_length: (header & 0x3FFFF) * if arm64 { 4 } else { 2 },
+            version: ((header >> 18) & 0x3) as u8,
+            has_exception_data,
+            epilogue_in_header,
+            fragment: !arm64 && (header >> 22) & 0x1 != 0,
+            epilogue_count,
+            code_words,
+            exception_handler_rva,
+        })
+    }
+
+    pub fn function_length(&self) -> u32 {
+        self.function_length
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// An exception handler RVA follows the unwind codes
+    pub fn has_exception_data(&self) -> bool {
+        self.has_exception_data
+    }
+
+    /// The single epilogue is described in the header instead of an epilogue scope list
+    pub fn epilogue_in_header(&self) -> bool {
+        self.epilogue_in_header
+    }
+
+    /// Function fragment without a prologue; always `false` on ARM64, which has no `F` bit
+    pub fn fragment(&self) -> bool {
+        self.fragment
+    }
+
+    /// Number of epilogue scopes, or the starting unwind code index when `epilogue_in_header` is set
+    pub fn epilogue_count(&self) -> u16 {
+        self.epilogue_count
+    }
+
+    /// Number of 32-bit words holding unwind codes
+    pub fn code_words(&self) -> u8 {
+        self.code_words
+    }
+
+    pub fn exception_handler_rva(&self) -> Option<u32> {
+        self.exception_handler_rva
+    }
+}
+
+/// Operation of an x64 unwind code, undoing one prologue instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UnwindOperation {
+    /// `UWOP_PUSH_NONVOL`: push of a non-volatile integer register
+    PushNonVolatile { register: u8 },
+    /// `UWOP_ALLOC_SMALL` or `UWOP_ALLOC_LARGE`: stack allocation of `size` bytes
+    Alloc { size: u32 },
+    /// `UWOP_SET_FPREG`: frame register set to RSP plus the frame offset
+    SetFramePointer,
+    /// `UWOP_SAVE_NONVOL` or `UWOP_SAVE_NONVOL_FAR`: integer register stored at
+    /// `offset` from RSP
+    SaveNonVolatile { register: u8, offset: u32 },
+    /// `UWOP_SAVE_XMM128` or `UWOP_SAVE_XMM128_FAR`: XMM register stored at `offset`
+    /// from RSP
+    SaveXmm128 { register: u8, offset: u32 },
+    /// `UWOP_PUSH_MACHFRAME`: hardware interrupt or exception frame, with an error
+    /// code if `error_code` is set
+    PushMachineFrame { error_code: bool },
+    /// `UWOP_EPILOG` of version 2 unwind info, describing the size or location of an
+    /// epilogue with its prologue offset field and `info`
+    Epilog { info: u8 },
+    /// Operation not defined by the format
+    Unknown { op: u8, info: u8 },
+}
+
+impl fmt::Display for UnwindOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let register = |number: &u8| X64_REGISTERS[*number as usize & 0xF];
+        f.pad(&match self {
+            Self::PushNonVolatile { register: number } => format!("push {}", register(number)),
+            Self::Alloc { size } => format!("alloc {:#x}", size),
+            Self::SetFramePointer => "set frame pointer".to_string(),
+            Self::SaveNonVolatile {
+                register: number,
+                offset,
+            } => format!("save {} at rsp+{:#x}", register(number), offset),
+            Self::SaveXmm128 { register, offset } => {
+                format!("save xmm{} at rsp+{:#x}", register, offset)
+            }
+            Self::PushMachineFrame { error_code: true } => {
+                "push machine frame with error code".to_string()
+            }
+            Self::PushMachineFrame { error_code: false } => "push machine frame".to_string(),
+            Self::Epilog { info } => format!("epilog {:#x}", info),
+            Self::Unknown { op, info } => format!("unknown op {} info {}", op, info),
+        })
+    }
+}
+
+/// x64 unwind code with the prologue offset it applies from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnwindCode {
+    prolog_offset: u8,
+    operation: UnwindOperation,
+}
+
+impl UnwindCode {
+    /// Offset from the start of the prologue of the end of the instruction undone
+    pub fn prolog_offset(&self) -> u8 {
+        self.prolog_offset
+    }
+
+    pub fn operation(&self) -> UnwindOperation {
+        self.operation
+    }
+}
+
+/// x64 `UNWIND_INFO`
+#[derive(Debug, Clone)]
+pub struct UnwindInfo {
+    version: u8,
+    flags: u8,
+    size_of_prolog: u8,
+    frame_register: u8,
+    frame_offset: u8,
+    codes: Vec<UnwindCode>,
+    handler_rva: Option<u32>,
+    chained: Option<X64RuntimeFunction>,
+}
+
+impl UnwindInfo {
+    fn read(pe: &PortExe, rva: u32) -> io::Result<Self> {
+        let truncated = || invalid_data("unwind info is not backed by file data");
+        let header = pe.data_at_rva(rva, 4).ok_or_else(truncated)?;
+        let version = header[0] & 0x7;
+        let flags = header[0] >> 3;
+        let count = header[2] as u32;
+        if version != 1 && version != 2 {
+            return Err(invalid_data(&format!(
+                "unsupported unwind info version {}",
+                version
+            )));
+        }
+        let slots_rva = rva_add(rva, 4).ok_or_else(truncated)?;
+        let slots = pe.data_at_rva(slots_rva, count * 2).ok_or_else(truncated)?;
+        let slot = |index: usize| read_u16(slots, index * 2);
+
+        let mut codes = Vec::new();
+        let mut index = 0;
+        while index < count as usize {
+            let code = slot(index).ok_or_else(truncated)?;
+            let prolog_offset = (code & 0xFF) as u8;
+            let op = ((code >> 8) & 0xF) as u8;
+            let info = (code >> 12) as u8;
+            let next = |n: usize| slot(index + n).map(u32::from).ok_or_else(truncated);
+            let far = || Ok::<u32, io::Error>(next(1)? | next(2)? << 16);
+            let (operation, used) = match op {
+                0 => (UnwindOperation::PushNonVolatile { register: info }, 1),
+                1 if info == 0 => (UnwindOperation::Alloc { size: next(1)? * 8 }, 2),
+                1 => (UnwindOperation::Alloc { size: far()? }, 3),
+                2 => (
+                    UnwindOperation::Alloc {
+                        size: info as u32 * 8 + 8,
+                    },
+                    1,
+                ),
+                3 => (UnwindOperation::SetFramePointer, 1),
+                4 => (
+                    UnwindOperation::SaveNonVolatile {
+                        register: info,
+                        offset: next(1)? * 8,
+                    },
+                    2,
+                ),
+                5 => (
+                    UnwindOperation::SaveNonVolatile {
+                        register: info,
+                        offset: far()?,
+                    },
+                    3,
+                ),
+                6 => (UnwindOperation::Epilog { info }, 2),
+                8 => (
+                    UnwindOperation::SaveXmm128 {
+                        register: info,
+                        offset: next(1)? * 16,
+                    },
+                    2,
+                ),
+                9 => (
+                    UnwindOperation::SaveXmm128 {
+                        register: info,
+                        offset: far()?,
+                    },
+                    3,
+                ),
+                10 => (
+                    UnwindOperation::PushMachineFrame {
+                        error_code: info != 0,
+                    },
+                    1,
+                ),
+                // UWOP_SPARE_CODE takes three slots like the far forms
+                op => (
+                    UnwindOperation::Unknown { op, info },
+                    if op == 7 { 3 } else { 1 },
+                ),
+            };
+            codes.push(UnwindCode {
+                prolog_offset,
+                operation,
+            });
+            index += used;
+        }
+
+        // The codes array is padded to an even number of slots
+        let trailer = rva_add(slots_rva, (count + count % 2) * 2).ok_or_else(truncated)?;
+        let mut handler_rva = None;
+        let mut chained = None;
+        if flags & UNW_FLAG_CHAININFO != 0 {
+            let entry = pe.data_at_rva(trailer, 12).ok_or_else(truncated)?;
+            let field = |offset| read_u32(entry, offset).unwrap_or(0);
+            chained = Some(X64RuntimeFunction {
+                begin_address: field(0),
+                end_address: field(4),
+                unwind_info_rva: field(8),
+            });
+        } else if flags & (UNW_FLAG_EHANDLER | UNW_FLAG_UHANDLER) != 0 {
+            let data = pe.data_at_rva(trailer, 4).ok_or_else(truncated)?;
+            handler_rva = read_u32(data, 0);
+        }
+
+        Ok(Self {
+            version,
+            flags,
+            size_of_prolog: header[1],
+            frame_register: header[3] & 0xF,
+            frame_offset: header[3] >> 4,
+            codes,
+            handler_rva,
+            chained,
+        })
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// `UNW_FLAG_*` bits
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    pub fn size_of_prolog(&self) -> u8 {
+        self.size_of_prolog
+    }
+
+    /// Name of the frame pointer register, `None` if the function has none
+    pub fn frame_register(&self) -> Option<&'static str> {
+        (self.frame_register != 0).then(|| X64_REGISTERS[self.frame_register as usize])
+    }
+
+    /// Offset from RSP the frame register is set to, in bytes
+    pub fn frame_offset(&self) -> u32 {
+        self.frame_offset as u32 * 16
+    }
+
+    /// Unwind codes, from the end of the prologue backwards
+    pub fn codes(&self) -> &[UnwindCode] {
+        &self.codes
+    }
+
+    /// RVA of the exception or termination handler
+    pub fn handler_rva(&self) -> Option<u32> {
+        self.handler_rva
+    }
+
+    /// Function whose unwind info continues this one, with `UNW_FLAG_CHAININFO`
+    pub fn chained(&self) -> Option<&X64RuntimeFunction> {
+        self.chained.as_ref()
+    }
+}
+
+impl PortExe {
+    /// Decodes the x64 `UNWIND_INFO` of `function`.
+    pub fn unwind_info(&self, function: &X64RuntimeFunction) -> io::Result<UnwindInfo> {
+        UnwindInfo::read(self, function.unwind_info_rva())
+    }
+
+    /// Decodes the exception directory according to the image's machine type.
+    pub fn runtime_functions(&self) -> io::Result<Vec<RuntimeFunction>> {
+        let directory = match self
+            .optional_header()
+            .and_then(|h| h.data_directory(IMAGE_DIRECTORY_ENTRY_EXCEPTION))
+        {
+            Some(directory) => directory,
+            None => return Ok(Vec::new()),
+        };
+        let rva = directory.virtual_address().into_value();
+        let size = directory.size().into_value();
+        if rva == 0 || size == 0 {
+            return Ok(Vec::new());
+        }
+        let data = self
+            .data_at_rva(rva, size)
+            .ok_or_else(|| invalid_data("exception directory is not backed by file data"))?;
+
+        match self.file_header().machine().into_value() {
+            Machine::ARMThumb2 => Ok(data
+                .chunks_exact(8)
+                .map(|entry| RuntimeFunction::Arm(self.arm_runtime_function(entry)))
+                .collect()),
+            Machine::ARM64LittleEndian | Machine::Arm64Ec | Machine::Arm64X => Ok(data
+                .chunks_exact(8)
+                .map(|entry| RuntimeFunction::Arm64(self.arm64_runtime_function(entry)))
+                .collect()),
+            Machine::X64 => Ok(data
+                .chunks_exact(12)
+                .map(|entry| {
+                    let field = |offset| read_u32(entry, offset).unwrap_or(0);
+                    RuntimeFunction::X64(X64RuntimeFunction {
+                        begin_address: field(0),
+                        end_address: field(4),
+                        unwind_info_rva: field(8),
+                    })
+                })
+                .collect()),
+            machine => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "exception directory format of {:?} is not supported",
+                    machine
+                ),
+            )),
+        }
+    }
+
+    fn arm_runtime_function(&self, entry: &[u8]) -> ArmRuntimeFunction {
+        let begin_address = read_u32(entry, 0).unwrap_or(0) & !1;
+        let unwind_word = read_u32(entry, 4).unwrap_or(0);
+        let unwind = if unwind_word & 0x3 == 0 {
+            ArmUnwind::Unpacked {
+                unwind_data_rva: unwind_word,
+                unwind_data: ArmUnwindData::read(self, unwind_word),
+            }
+        } else {
+            ArmUnwind::Packed(ArmPackedUnwind { raw: unwind_word })
+        };
+        ArmRuntimeFunction {
+            begin_address,
+            unwind,
+        }
+    }
+
+    fn arm64_runtime_function(&self, entry: &[u8]) -> Arm64RuntimeFunction {
+        let begin_address = read_u32(entry, 0).unwrap_or(0);
+        let unwind_word = read_u32(entry, 4).unwrap_or(0);
+        let unwind = if unwind_word & 0x3 == 0 {
+            Arm64Unwind::Unpacked {
+                unwind_data_rva: unwind_word,
+                unwind_data: ArmUnwindData::read_arm64(self, unwind_word),
+            }
+        } else {
+            Arm64Unwind::Packed(Arm64PackedUnwind { raw: unwind_word })
+        };
+        Arm64RuntimeFunction {
+            begin_address,
+            unwind,
+        }
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,435 @@
+use crate::arith::rva_add;
+use crate::optional_header::IMAGE_DIRECTORY_ENTRY_EXPORT;
+use crate::pe_string::PeString;
+use crate::port_exe::PortExe;
+use crate::{read_u16, read_u32};
+use std::fmt;
+use std::io;
+
+/// Upper bound on the number of export table entries walked
+pub const MAX_EXPORTS: u32 = 0x10000;
+
+/// `IMAGE_EXPORT_DIRECTORY`
+#[derive(Debug, Clone)]
+pub struct ExportDirectory {
+    characteristics: u32,
+    time_date_stamp: u32,
+    major_version: u16,
+    minor_version: u16,
+    name_rva: u32,
+    name: String,
+    ordinal_base: u32,
+    number_of_functions: u32,
+    number_of_names: u32,
+    address_of_functions: u32,
+    address_of_names: u32,
+    address_of_name_ordinals: u32,
+}
+
+impl ExportDirectory {
+    pub fn characteristics(&self) -> u32 {
+        self.characteristics
+    }
+
+    pub fn time_date_stamp(&self) -> u32 {
+        self.time_date_stamp
+    }
+
+    pub fn major_version(&self) -> u16 {
+        self.major_version
+    }
+
+    pub fn minor_version(&self) -> u16 {
+        self.minor_version
+    }
+
+    pub fn name_rva(&self) -> u32 {
+        self.name_rva
+    }
+
+    /// DLL name recorded by the linker
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn ordinal_base(&self) -> u32 {
+        self.ordinal_base
+    }
+
+    pub fn number_of_functions(&self) -> u32 {
+        self.number_of_functions
+    }
+
+    pub fn number_of_names(&self) -> u32 {
+        self.number_of_names
+    }
+
+    pub fn address_of_functions(&self) -> u32 {
+        self.address_of_functions
+    }
+
+    pub fn address_of_names(&self) -> u32 {
+        self.address_of_names
+    }
+
+    pub fn address_of_name_ordinals(&self) -> u32 {
+        self.address_of_name_ordinals
+    }
+}
+
+/// What an export resolves to, by the section its RVA lands in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExportKind {
+    /// In a section that is executable or holds code
+    Code,
+    /// In a section of data. Importers must take the address of such an export rather
+    /// than call it, and relinking them by ordinal is a common cause of breakage, so
+    /// import libraries mark them `DATA`.
+    Data,
+    /// Forwarded to another DLL
+    Forwarder,
+    /// Outside every section
+    Unmapped,
+}
+
+impl fmt::Display for ExportKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Code => "code",
+            Self::Data => "data",
+            Self::Forwarder => "forwarder",
+            Self::Unmapped => "unmapped",
+        })
+    }
+}
+
+/// Entry of the export address table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Export {
+    ordinal: u32,
+    name: Option<PeString>,
+    name_rva: Option<u32>,
+    rva: u32,
+    forwarder: Option<String>,
+    kind: ExportKind,
+}
+
+impl Export {
+    /// Biased ordinal, as used by importers
+    pub fn ordinal(&self) -> u32 {
+        self.ordinal
+    }
+
+    pub fn name(&self) -> Option<&PeString> {
+        self.name.as_ref()
+    }
+
+    /// Address of the name string, absent for exports by ordinal only
+    pub fn name_rva(&self) -> Option<u32> {
+        self.name_rva
+    }
+
+    /// Address of the exported code or data; for forwarders, the address of the forwarder string
+    pub fn rva(&self) -> u32 {
+        self.rva
+    }
+
+    /// `DLL.Function` or `DLL.#Ordinal` target of a forwarded export
+    pub fn forwarder(&self) -> Option<&str> {
+        self.forwarder.as_deref()
+    }
+
+    pub fn kind(&self) -> ExportKind {
+        self.kind
+    }
+}
+
+/// Address an export resolves to in a generated export table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExportTarget {
+    Rva(u32),
+    /// `DLL.Function` or `DLL.#Ordinal`
+    Forwarder(String),
+}
+
+/// Input of [`build_export_table`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportSpec {
+    name: Option<String>,
+    ordinal: Option<u32>,
+    target: ExportTarget,
+}
+
+impl ExportSpec {
+    /// `name` of `None` exports by ordinal only; an `ordinal` of `None` is assigned automatically.
+    pub fn new(name: Option<String>, ordinal: Option<u32>, target: ExportTarget) -> Self {
+        Self {
+            name,
+            ordinal,
+            target,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn ordinal(&self) -> Option<u32> {
+        self.ordinal
+    }
+
+    pub fn target(&self) -> &ExportTarget {
+        &self.target
+    }
+}
+
+/// Serialises an export directory for `dll_name` to be placed at `rva`.
+///
+/// Exports without an explicit ordinal take the lowest free ordinals, the ordinal base is the
+/// lowest ordinal in use and the name pointer table is sorted as the loader's binary search
+/// requires. Forwarder strings are stored inside the returned block so they fall within the
+/// export directory range.
+pub fn build_export_table(dll_name: &str, specs: &[ExportSpec], rva: u32) -> io::Result<Vec<u8>> {
+    let mut ordinals: Vec<Option<u32>> = specs.iter().map(|spec| spec.ordinal).collect();
+    let mut used: Vec<u32> = ordinals.iter().flatten().copied().collect();
+    used.sort_unstable();
+    if used.windows(2).any(|pair| pair[0] == pair[1]) {
+        return Err(invalid_data("duplicate export ordinal"));
+    }
+    if used.first() == Some(&0) {
+        return Err(invalid_data("export ordinals start at 1"));
+    }
+    let mut next = 1;
+    for ordinal in ordinals.iter_mut().filter(|ordinal| ordinal.is_none()) {
+        while used.binary_search(&next).is_ok() {
+            next += 1;
+        }
+        *ordinal = Some(next);
+        next += 1;
+    }
+    let ordinals: Vec<u32> = ordinals.into_iter().flatten().collect();
+    let base = ordinals.iter().copied().min().unwrap_or(1);
+    let number_of_functions = ordinals.iter().map(|&o| o - base + 1).max().unwrap_or(0);
+    if number_of_functions > MAX_EXPORTS {
+        return Err(invalid_data("export ordinal range is too large"));
+    }
+
+    let mut names: Vec<(&str, u32)> = specs
+        .iter()
+        .zip(&ordinals)
+        .filter_map(|(spec, &ordinal)| spec.name.as_deref().map(|name| (name, ordinal - base)))
+        .collect();
+    names.sort_unstable_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+    if names.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+        return Err(invalid_data("duplicate export name"));
+    }
+
+    let functions_offset = 40;
+    let names_offset = functions_offset + number_of_functions as usize * 4;
+    let name_ordinals_offset = names_offset + names.len() * 4;
+    let strings_offset = name_ordinals_offset + names.len() * 2;
+    let strings_size = specs
+        .iter()
+        .filter_map(|spec| match &spec.target {
+            ExportTarget::Forwarder(forwarder) => Some(forwarder.len() + 1),
+            ExportTarget::Rva(_) => None,
+        })
+        .chain(names.iter().map(|(name, _)| name.len() + 1))
+        .sum::<usize>()
+        + dll_name.len()
+        + 1;
+    // Every RVA below lies inside the table, so a table that fits keeps them in range.
+    u32::try_from(strings_offset + strings_size)
+        .ok()
+        .and_then(|size| rva_add(rva, size))
+        .ok_or_else(|| invalid_data("export table does not fit in the address space"))?;
+
+    let mut strings = Vec::new();
+    let mut add_string = |string: &str| {
+        let string_rva = rva + (strings_offset + strings.len()) as u32;
+        strings.extend_from_slice(string.as_bytes());
+        strings.push(0);
+        string_rva
+    };
+    let dll_name_rva = add_string(dll_name);
+    let name_rvas: Vec<u32> = names.iter().map(|(name, _)| add_string(name)).collect();
+    let mut functions = vec![0u32; number_of_functions as usize];
+    for (spec, &ordinal) in specs.iter().zip(&ordinals) {
+        functions[(ordinal - base) as usize] = match &spec.target {
+            ExportTarget::Rva(target) => *target,
+            ExportTarget::Forwarder(forwarder) => add_string(forwarder),
+        };
+    }
+
+    let mut table = Vec::with_capacity(strings_offset + strings.len());
+    // Characteristics, TimeDateStamp, MajorVersion and MinorVersion
+    table.extend_from_slice(&[0; 12]);
+    for field in [
+        dll_name_rva,
+        base,
+        number_of_functions,
+        names.len() as u32,
+        rva + functions_offset as u32,
+        rva + names_offset as u32,
+        rva + name_ordinals_offset as u32,
+    ] {
+        table.extend_from_slice(&field.to_le_bytes());
+    }
+    for function in functions {
+        table.extend_from_slice(&function.to_le_bytes());
+    }
+    for name_rva in name_rvas {
+        table.extend_from_slice(&name_rva.to_le_bytes());
+    }
+    for (_, index) in &names {
+        table.extend_from_slice(&(*index as u16).to_le_bytes());
+    }
+    table.extend_from_slice(&strings);
+    Ok(table)
+}
+
+impl PortExe {
+    pub fn export_directory(&self) -> io::Result<Option<ExportDirectory>> {
+        let (rva, _) = match self.export_directory_range() {
+            Some(range) => range,
+            None => return Ok(None),
+        };
+        let data = self
+            .data_at_rva(rva, 40)
+            .ok_or_else(|| invalid_data("export directory is not backed by file data"))?;
+        let field = |offset| read_u32(data, offset).unwrap_or(0);
+        let name_rva = field(12);
+        Ok(Some(ExportDirectory {
+            characteristics: field(0),
+            time_date_stamp: field(4),
+            major_version: read_u16(data, 8).unwrap_or(0),
+            minor_version: read_u16(data, 10).unwrap_or(0),
+            name_rva,
+            name: self.string_at_rva(name_rva).unwrap_or_default(),
+            ordinal_base: field(16),
+            number_of_functions: field(20),
+            number_of_names: field(24),
+            address_of_functions: field(28),
+            address_of_names: field(32),
+            address_of_name_ordinals: field(36),
+        }))
+    }
+
+    /// Lists exports in export address table order, skipping unused slots.
+    pub fn exports(&self) -> io::Result<Vec<Export>> {
+        let directory = match self.export_directory()? {
+            Some(directory) => directory,
+            None => return Ok(Vec::new()),
+        };
+        let (directory_rva, directory_size) = self.export_directory_range().unwrap_or((0, 0));
+
+        let number_of_functions = directory.number_of_functions.min(MAX_EXPORTS);
+        let number_of_names = directory.number_of_names.min(MAX_EXPORTS);
+        let functions = self
+            .data_at_rva(directory.address_of_functions, number_of_functions * 4)
+            .ok_or_else(|| invalid_data("export address table is not backed by file data"))?;
+        let names = self
+            .data_at_rva(directory.address_of_names, number_of_names * 4)
+            .unwrap_or(&[]);
+        let name_ordinals = self
+            .data_at_rva(directory.address_of_name_ordinals, number_of_names * 2)
+            .unwrap_or(&[]);
+
+        let mut function_names = vec![None; number_of_functions as usize];
+        for index in 0..number_of_names as usize {
+            let (name_rva, ordinal) = match (
+                read_u32(names, index * 4),
+                read_u16(name_ordinals, index * 2),
+            ) {
+                (Some(name_rva), Some(ordinal)) => (name_rva, ordinal as usize),
+                _ => break,
+            };
+            if let Some(slot) = function_names.get_mut(ordinal) {
+                if slot.is_none() {
+                    *slot = self.name_at_rva(name_rva).map(|name| (name_rva, name));
+                }
+            }
+        }
+
+        let mut exports = Vec::new();
+        for (index, named) in function_names.into_iter().enumerate() {
+            let rva = read_u32(functions, index * 4).unwrap_or(0);
+            if rva == 0 {
+                continue;
+            }
+            let forwarder = if rva >= directory_rva && rva - directory_rva < directory_size {
+                self.string_at_rva(rva)
+            } else {
+                None
+            };
+            let kind = match (&forwarder, self.section_at_rva(rva)) {
+                (Some(_), _) => ExportKind::Forwarder,
+                (None, None) => ExportKind::Unmapped,
+                (None, Some(section)) => {
+                    let characteristics = section.characteristics();
+                    let flags = characteristics.value();
+                    if flags.mem_execute() || flags.cnt_code() {
+                        ExportKind::Code
+                    } else {
+                        ExportKind::Data
+                    }
+                }
+            };
+            exports.push(Export {
+                ordinal: directory.ordinal_base.wrapping_add(index as u32),
+                name_rva: named.as_ref().map(|&(name_rva, _)| name_rva),
+                name: named.map(|(_, name)| name),
+                rva,
+                forwarder,
+                kind,
+            });
+        }
+        Ok(exports)
+    }
+
+    /// Lists the export name pointer table in table order, the order import hints index.
+    ///
+    /// Names that cannot be read are kept as empty strings so positions stay intact.
+    pub fn export_names(&self) -> io::Result<Vec<PeString>> {
+        let directory = match self.export_directory()? {
+            Some(directory) => directory,
+            None => return Ok(Vec::new()),
+        };
+        let number_of_names = directory.number_of_names.min(MAX_EXPORTS);
+        let names = self
+            .data_at_rva(directory.address_of_names, number_of_names * 4)
+            .ok_or_else(|| invalid_data("export name pointer table is not backed by file data"))?;
+        Ok((0..number_of_names as usize)
+            .filter_map(|index| read_u32(names, index * 4))
+            .map(|rva| self.name_at_rva(rva).unwrap_or_default())
+            .collect())
+    }
+
+    /// Zero-terminated byte string at `rva`, decoded lossily
+    pub fn string_at_rva(&self, rva: u32) -> Option<String> {
+        self.name_at_rva(rva)
+            .map(|name| name.to_string_lossy().into_owned())
+    }
+
+    /// Zero-terminated byte string at `rva`, as stored
+    pub fn name_at_rva(&self, rva: u32) -> Option<PeString> {
+        let offset = self.rva_to_offset(rva)?;
+        let data = &self.data()[offset..];
+        let end = data.iter().position(|&b| b == 0)?;
+        Some(PeString::from_bytes(&data[..end]))
+    }
+
+    fn export_directory_range(&self) -> Option<(u32, u32)> {
+        let directory = self
+            .optional_header()?
+            .data_directory(IMAGE_DIRECTORY_ENTRY_EXPORT)?;
+        let rva = directory.virtual_address().into_value();
+        let size = directory.size().into_value();
+        (rva != 0 && size != 0).then(|| (rva, size))
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
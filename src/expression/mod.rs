@@ -0,0 +1,331 @@
+use crate::address::{AddressResolver, RelativeVirtualAddress, VirtualAddress};
+use crate::port_exe::PortExe;
+use std::fmt;
+use std::io;
+
+/// Value of an [`AddressExpression`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AddressValue {
+    /// File offset, written `offset:n` or `off:n`
+    Offset(u64),
+    /// Address relative to the image base, written `rva:n`
+    Rva(RelativeVirtualAddress),
+    /// Virtual address in a process the image is loaded into, written `va:n`
+    Va(VirtualAddress),
+    /// Plain number, such as a size or a difference of addresses; used as a file offset
+    Number(u64),
+}
+
+impl AddressValue {
+    /// File offset of the value, `None` if it has no file data
+    pub fn offset(self, resolver: &AddressResolver) -> Option<usize> {
+        match self {
+            Self::Offset(offset) | Self::Number(offset) => usize::try_from(offset).ok(),
+            Self::Rva(rva) => resolver.rva_to_offset(rva),
+            Self::Va(va) => resolver.rva_to_offset(resolver.va_to_rva(va)?),
+        }
+    }
+
+    /// RVA of the value, `None` if it is outside the image
+    pub fn rva(self, resolver: &AddressResolver) -> Option<RelativeVirtualAddress> {
+        match self {
+            Self::Offset(offset) | Self::Number(offset) => {
+                resolver.offset_to_rva(usize::try_from(offset).ok()?)
+            }
+            Self::Rva(rva) => Some(rva),
+            Self::Va(va) => resolver.va_to_rva(va),
+        }
+    }
+
+    /// Kind and value as a raw number
+    fn parts(self) -> (Kind, u64) {
+        match self {
+            Self::Offset(value) => (Kind::Offset, value),
+            Self::Rva(rva) => (Kind::Rva, rva.value() as u64),
+            Self::Va(va) => (Kind::Va, va.value()),
+            Self::Number(value) => (Kind::Number, value),
+        }
+    }
+
+    fn from_parts(kind: Kind, value: u64) -> Option<Self> {
+        Some(match kind {
+            Kind::Offset => Self::Offset(value),
+            Kind::Rva => Self::Rva(RelativeVirtualAddress::new(u32::try_from(value).ok()?)),
+            Kind::Va => Self::Va(VirtualAddress::new(value)),
+            Kind::Number => Self::Number(value),
+        })
+    }
+}
+
+impl fmt::Display for AddressValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (kind, value) = self.parts();
+        match kind {
+            Kind::Number => f.pad(&format!("{:#x}", value)),
+            kind => f.pad(&format!("{}:{:#x}", kind.prefix(), value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Offset,
+    Rva,
+    Va,
+    Number,
+}
+
+impl Kind {
+    fn prefix(self) -> &'static str {
+        match self {
+            Self::Offset => "offset",
+            Self::Rva => "rva",
+            Self::Va => "va",
+            Self::Number => "",
+        }
+    }
+}
+
+/// Part of a section a `section(name)` term refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SectionField {
+    /// RVA of the first byte
+    Start,
+    /// RVA one past the last byte of the virtual size
+    End,
+    /// Virtual size
+    Size,
+    /// File offset of the raw data
+    Raw,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Term {
+    Number(u64),
+    Typed(Kind, u64),
+    Entry,
+    Base,
+    Section(String, SectionField),
+    Export(String),
+}
+
+/// Address given as a sum of terms over the parsed image, such as `entry+0x20`,
+/// `section(.text).start+10` or `rva:0x1234`.
+///
+/// Terms are numbers, `offset:n`, `rva:n` and `va:n`, `entry` for the entry point RVA,
+/// `base` for the image base VA, `export(name)` for the RVA of an export and
+/// `section(name)` followed by `.start`, `.end`, `.size` or `.raw` for the RVA range,
+/// virtual size or file offset of a section; `section(name)` alone is its start.
+/// Numbers may be added to and subtracted from addresses, and addresses of the same
+/// kind subtracted from each other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressExpression {
+    /// Terms with whether they are subtracted
+    terms: Vec<(bool, Term)>,
+}
+
+impl AddressExpression {
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut terms = Vec::new();
+        let mut rest = text.trim();
+        let mut negative = false;
+        loop {
+            let (term, tail) = parse_term(rest)?;
+            terms.push((negative, term));
+            rest = tail.trim_start();
+            negative = match rest.chars().next() {
+                None => break,
+                Some('+') => false,
+                Some('-') => true,
+                Some(_) => return Err(invalid_input(&format!("unexpected {:?}", rest))),
+            };
+            rest = rest[1..].trim_start();
+        }
+        Ok(Self { terms })
+    }
+
+    /// Value of the expression for `pe`.
+    pub fn evaluate(&self, pe: &PortExe) -> io::Result<AddressValue> {
+        let mut total: Option<AddressValue> = None;
+        for (negative, term) in &self.terms {
+            let value = term_value(pe, term)?;
+            let (kind, value) = value.parts();
+            total = Some(match total {
+                None if *negative => match kind {
+                    Kind::Number => AddressValue::Number(0u64.wrapping_sub(value)),
+                    _ => return Err(invalid_input("an address cannot be negated")),
+                },
+                None => AddressValue::from_parts(kind, value)
+                    .ok_or_else(|| invalid_input("address is out of range"))?,
+                Some(total) => {
+                    let (total_kind, total_value) = total.parts();
+                    let (kind, value) = match (total_kind, kind, negative) {
+                        (total_kind, Kind::Number, false) => {
+                            (total_kind, total_value.wrapping_add(value))
+                        }
+                        (total_kind, Kind::Number, true) => {
+                            (total_kind, total_value.wrapping_sub(value))
+                        }
+                        (Kind::Number, kind, false) => (kind, total_value.wrapping_add(value)),
+                        (total_kind, kind, true) if total_kind == kind => {
+                            (Kind::Number, total_value.wrapping_sub(value))
+                        }
+                        _ => {
+                            return Err(invalid_input(&format!(
+                                "cannot combine {} and {} addresses",
+                                kind_name(total_kind),
+                                kind_name(kind)
+                            )))
+                        }
+                    };
+                    AddressValue::from_parts(kind, value)
+                        .ok_or_else(|| invalid_input("address is out of range"))?
+                }
+            });
+        }
+        total.ok_or_else(|| invalid_input("empty expression"))
+    }
+}
+
+impl PortExe {
+    /// Parses and evaluates an [`AddressExpression`].
+    pub fn evaluate_address(&self, expression: &str) -> io::Result<AddressValue> {
+        AddressExpression::parse(expression)?.evaluate(self)
+    }
+}
+
+fn kind_name(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Offset => "file offset",
+        Kind::Rva => "RVA",
+        Kind::Va => "VA",
+        Kind::Number => "plain",
+    }
+}
+
+fn term_value(pe: &PortExe, term: &Term) -> io::Result<AddressValue> {
+    let header = pe.optional_header();
+    let value = match term {
+        Term::Number(value) => AddressValue::Number(*value),
+        Term::Typed(kind, value) => AddressValue::from_parts(*kind, *value)
+            .ok_or_else(|| invalid_input("RVA is out of range"))?,
+        Term::Entry => AddressValue::Rva(RelativeVirtualAddress::new(
+            header
+                .ok_or_else(|| invalid_input("object files have no entry point"))?
+                .address_of_entry_point(),
+        )),
+        Term::Base => AddressValue::Va(VirtualAddress::new(
+            header
+                .ok_or_else(|| invalid_input("object files have no image base"))?
+                .image_base(),
+        )),
+        Term::Section(name, field) => {
+            let section = pe
+                .section_headers()
+                .iter()
+                .find(|section| pe.section_name(section).to_string_lossy() == name.as_str())
+                .ok_or_else(|| invalid_input(&format!("no section {}", name)))?;
+            let start = section.virtual_address().into_value();
+            let size = match section.virtual_size().into_value() {
+                0 => section.size_of_raw_data().into_value(),
+                size => size,
+            };
+            match field {
+                SectionField::Start => AddressValue::Rva(RelativeVirtualAddress::new(start)),
+                SectionField::End => {
+                    AddressValue::Rva(RelativeVirtualAddress::new(start.wrapping_add(size)))
+                }
+                SectionField::Size => AddressValue::Number(size as u64),
+                SectionField::Raw => {
+                    AddressValue::Offset(section.pointer_to_raw_data().into_value() as u64)
+                }
+            }
+        }
+        Term::Export(name) => {
+            let export = pe
+                .exports()?
+                .into_iter()
+                .find(|export| {
+                    export
+                        .name()
+                        .map_or(false, |export| export.to_string_lossy() == name.as_str())
+                })
+                .ok_or_else(|| invalid_input(&format!("no export {}", name)))?;
+            AddressValue::Rva(RelativeVirtualAddress::new(export.rva()))
+        }
+    };
+    Ok(value)
+}
+
+/// Term at the start of `text`, and the text after it
+fn parse_term(text: &str) -> io::Result<(Term, &str)> {
+    if let Some(rest) = text.strip_prefix("section(") {
+        let (name, rest) = parenthesized(rest)?;
+        let (field, rest) = match rest.strip_prefix('.') {
+            Some(rest) => {
+                let (word, rest) = split_word(rest);
+                let field = match word {
+                    "start" => SectionField::Start,
+                    "end" => SectionField::End,
+                    "size" => SectionField::Size,
+                    "raw" => SectionField::Raw,
+                    _ => return Err(invalid_input(&format!("unknown section field {}", word))),
+                };
+                (field, rest)
+            }
+            None => (SectionField::Start, rest),
+        };
+        return Ok((Term::Section(name.to_string(), field), rest));
+    }
+    if let Some(rest) = text.strip_prefix("export(") {
+        let (name, rest) = parenthesized(rest)?;
+        return Ok((Term::Export(name.to_string()), rest));
+    }
+    let (word, rest) = split_word(text);
+    let term = match word.split_once(':') {
+        Some((prefix, number)) => {
+            let kind = match prefix {
+                "offset" | "off" => Kind::Offset,
+                "rva" => Kind::Rva,
+                "va" => Kind::Va,
+                _ => return Err(invalid_input(&format!("unknown address kind {}", prefix))),
+            };
+            Term::Typed(kind, parse_number(number)?)
+        }
+        None => match word {
+            "entry" => Term::Entry,
+            "base" => Term::Base,
+            "" => return Err(invalid_input("expected a term")),
+            _ => Term::Number(parse_number(word)?),
+        },
+    };
+    Ok((term, rest))
+}
+
+/// Text up to the closing parenthesis, and the text after it
+fn parenthesized(text: &str) -> io::Result<(&str, &str)> {
+    let end = text.find(')').ok_or_else(|| invalid_input("missing )"))?;
+    Ok((text[..end].trim(), &text[end + 1..]))
+}
+
+/// Leading letters, digits, underscores and colons of `text`, and the text after them
+fn split_word(text: &str) -> (&str, &str) {
+    let end = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == ':'))
+        .unwrap_or(text.len());
+    text.split_at(end)
+}
+
+/// Decimal or `0x`-prefixed hexadecimal number
+fn parse_number(text: &str) -> io::Result<u64> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+    .ok_or_else(|| invalid_input(&format!("invalid number {}", text)))
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
@@ -1,11 +1,18 @@
-use crate::StructField;
-use chrono::{DateTime, Utc};
+use crate::endian::{le_u16, le_u32};
+use crate::port_exe::IMAGE_SIZEOF_FILE_HEADER;
+use crate::{read_block, write_at, StructField};
+use std::io;
 use std::io::Read;
 use std::io::Seek;
 use std::io::SeekFrom;
 
-pub fn read_file_header<R: Read + Seek>(reader: &mut R, offset: u64) -> FileHeaderWrapper {
-    let _ = reader.seek(SeekFrom::Start(offset));
+pub(crate) fn read_file_header<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+) -> io::Result<FileHeaderWrapper> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let block = read_block(reader, IMAGE_SIZEOF_FILE_HEADER as usize)?;
+    let mut fields = &block[..];
 
     let mut machine = [0u8; 2];
     let mut number_of_sections = [0u8; 2];
@@ -15,13 +22,13 @@ pub fn read_file_header<R: Read + Seek>(reader: &mut R, offset: u64) -> FileHead
     let mut size_of_optional_header = [0u8; 2];
     let mut characteristics = [0u8; 2];
 
-    let _ = reader.read_exact(&mut machine);
-    let _ = reader.read_exact(&mut number_of_sections);
-    let _ = reader.read_exact(&mut time_date_stamp);
-    let _ = reader.read_exact(&mut pointer_to_symbol_table);
-    let _ = reader.read_exact(&mut number_of_symbols);
-    let _ = reader.read_exact(&mut size_of_optional_header);
-    let _ = reader.read_exact(&mut characteristics);
+    fields.read_exact(&mut machine)?;
+    fields.read_exact(&mut number_of_sections)?;
+    fields.read_exact(&mut time_date_stamp)?;
+    fields.read_exact(&mut pointer_to_symbol_table)?;
+    fields.read_exact(&mut number_of_symbols)?;
+    fields.read_exact(&mut size_of_optional_header)?;
+    fields.read_exact(&mut characteristics)?;
 
     let file_header_raw = FileHeaderRaw {
         machine,
@@ -38,7 +45,7 @@ pub fn read_file_header<R: Read + Seek>(reader: &mut R, offset: u64) -> FileHead
         file_header_raw,
     };
 
-    FileHeaderWrapper { file_header }
+    Ok(FileHeaderWrapper { file_header })
 }
 
 #[derive(Debug)]
@@ -52,6 +59,21 @@ struct FileHeaderRaw {
     characteristics: [u8; 2],
 }
 
+impl FileHeaderRaw {
+    fn to_bytes(&self) -> Vec<u8> {
+        [
+            &self.machine[..],
+            &self.number_of_sections,
+            &self.time_date_stamp,
+            &self.pointer_to_symbol_table,
+            &self.number_of_symbols,
+            &self.size_of_optional_header,
+            &self.characteristics,
+        ]
+        .concat()
+    }
+}
+
 #[derive(Debug)]
 struct FileHeader {
     offset: u64,
@@ -60,31 +82,31 @@ struct FileHeader {
 
 impl FileHeader {
     fn machine(&self) -> u16 {
-        u16::from_le_bytes(self.file_header_raw.machine)
+        le_u16(self.file_header_raw.machine)
     }
 
     fn number_of_sections(&self) -> u16 {
-        u16::from_le_bytes(self.file_header_raw.number_of_sections)
+        le_u16(self.file_header_raw.number_of_sections)
     }
 
     fn time_date_stamp(&self) -> u32 {
-        u32::from_le_bytes(self.file_header_raw.time_date_stamp)
+        le_u32(self.file_header_raw.time_date_stamp)
     }
 
     fn pointer_to_symbol_table(&self) -> u32 {
-        u32::from_le_bytes(self.file_header_raw.pointer_to_symbol_table)
+        le_u32(self.file_header_raw.pointer_to_symbol_table)
     }
 
     fn number_of_symbols(&self) -> u32 {
-        u32::from_le_bytes(self.file_header_raw.number_of_symbols)
+        le_u32(self.file_header_raw.number_of_symbols)
     }
 
     fn size_of_optional_header(&self) -> u16 {
-        u16::from_le_bytes(self.file_header_raw.size_of_optional_header)
+        le_u16(self.file_header_raw.size_of_optional_header)
     }
 
     fn characteristics(&self) -> u16 {
-        u16::from_le_bytes(self.file_header_raw.characteristics)
+        le_u16(self.file_header_raw.characteristics)
     }
 }
 
@@ -94,6 +116,15 @@ pub struct FileHeaderWrapper {
 }
 
 impl FileHeaderWrapper {
+    /// Encodes the header back into `data` at the offset it was read from.
+    pub(crate) fn write(&self, data: &mut [u8]) {
+        write_at(
+            data,
+            self.file_header.offset,
+            &self.file_header.file_header_raw.to_bytes(),
+        );
+    }
+
     pub fn machine(&self) -> StructField<Machine, 2> {
         let offset = self.file_header.offset;
         let name = String::from("Machine");
@@ -120,12 +151,12 @@ impl FileHeaderWrapper {
         }
     }
 
-    pub fn time_date_stamp(&self) -> StructField<DateTime<Utc>, 4> {
+    /// Seconds since the Unix epoch, or a build hash for reproducible builds
+    pub fn time_date_stamp(&self) -> StructField<u32, 4> {
         let offset = self.file_header.offset + 4;
         let name = String::from("Time date stamp");
         let raw_bytes = self.file_header.file_header_raw.time_date_stamp;
-        let value = DateTime::from_timestamp(self.file_header.time_date_stamp() as i64, 0)
-            .expect("invalid timestamp");
+        let value = self.file_header.time_date_stamp();
         StructField {
             offset,
             name,
@@ -134,6 +165,12 @@ impl FileHeaderWrapper {
         }
     }
 
+    /// Time date stamp decoded as a UTC date and time
+    #[cfg(feature = "chrono")]
+    pub fn timestamp(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        chrono::DateTime::from_timestamp(self.file_header.time_date_stamp() as i64, 0)
+    }
+
     pub fn pointer_to_symbol_table(&self) -> StructField<u32, 4> {
         let offset = self.file_header.offset + 8;
         let name = String::from("Pointer to symbol table");
@@ -187,6 +224,8 @@ impl FileHeaderWrapper {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum Machine {
     Unknown,
     AlphaAXP,
@@ -195,6 +234,8 @@ pub enum Machine {
     X64,
     ARMLittleEndian,
     ARM64LittleEndian,
+    Arm64Ec,
+    Arm64X,
     ARMThumb2,
     EFIByteCode,
     Intel386,
@@ -207,6 +248,7 @@ pub enum Machine {
     MIPSFPU16,
     PowerPCLE,
     PowerPCFPU,
+    PowerPCBE,
     MIPSLE,
     RISCV32,
     RISCV64,
@@ -228,6 +270,8 @@ impl From<u16> for Machine {
             IMAGE_FILE_MACHINE_AMD64 => Self::X64,
             IMAGE_FILE_MACHINE_ARM => Self::ARMLittleEndian,
             IMAGE_FILE_MACHINE_ARM64 => Self::ARM64LittleEndian,
+            IMAGE_FILE_MACHINE_ARM64EC => Self::Arm64Ec,
+            IMAGE_FILE_MACHINE_ARM64X => Self::Arm64X,
             IMAGE_FILE_MACHINE_ARMNT => Self::ARMThumb2,
             IMAGE_FILE_MACHINE_EBC => Self::EFIByteCode,
             IMAGE_FILE_MACHINE_I386 => Self::Intel386,
@@ -240,6 +284,7 @@ impl From<u16> for Machine {
             IMAGE_FILE_MACHINE_MIPSFPU16 => Self::MIPSFPU16,
             IMAGE_FILE_MACHINE_POWERPC => Self::PowerPCLE,
             IMAGE_FILE_MACHINE_POWERPCFP => Self::PowerPCFPU,
+            IMAGE_FILE_MACHINE_POWERPCBE => Self::PowerPCBE,
             IMAGE_FILE_MACHINE_R4000 => Self::MIPSLE,
             IMAGE_FILE_MACHINE_RISCV32 => Self::RISCV32,
             IMAGE_FILE_MACHINE_RISCV64 => Self::RISCV64,
@@ -256,71 +301,79 @@ impl From<u16> for Machine {
 }
 
 /// Alpha AXP, 32-bit address space
-const IMAGE_FILE_MACHINE_ALPHA: u16 = 0x0184;
+pub const IMAGE_FILE_MACHINE_ALPHA: u16 = 0x0184;
 /// Alpha 64, 64-bit address space
-const IMAGE_FILE_MACHINE_ALPHA64: u16 = 0x0284;
+pub const IMAGE_FILE_MACHINE_ALPHA64: u16 = 0x0284;
 /// Matsushita AM33
-const IMAGE_FILE_MACHINE_AM33: u16 = 0x01D3;
+pub const IMAGE_FILE_MACHINE_AM33: u16 = 0x01D3;
 /// x64
-const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
+pub const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
 /// ARM little endian
-const IMAGE_FILE_MACHINE_ARM: u16 = 0x01C0;
+pub const IMAGE_FILE_MACHINE_ARM: u16 = 0x01C0;
 /// ARM64 little endian
-const IMAGE_FILE_MACHINE_ARM64: u16 = 0xaa64;
+pub const IMAGE_FILE_MACHINE_ARM64: u16 = 0xaa64;
+/// ARM64EC, ARM64 code that interoperates with x64 code in the same process
+pub const IMAGE_FILE_MACHINE_ARM64EC: u16 = 0xA641;
+/// ARM64X, holding both ARM64 and ARM64EC code
+pub const IMAGE_FILE_MACHINE_ARM64X: u16 = 0xA64E;
 /// ARM Thumb-2 little endian
-const IMAGE_FILE_MACHINE_ARMNT: u16 = 0x01C4;
+pub const IMAGE_FILE_MACHINE_ARMNT: u16 = 0x01C4;
 /// AXP 64 (Same as Alpha 64)
-const IMAGE_FILE_MACHINE_AXP64: u16 = 0x0284;
+pub const IMAGE_FILE_MACHINE_AXP64: u16 = 0x0284;
 /// EFI byte code
-const IMAGE_FILE_MACHINE_EBC: u16 = 0x0EBC;
+pub const IMAGE_FILE_MACHINE_EBC: u16 = 0x0EBC;
 /// Intel 386 or later processors and compatible processors
-const IMAGE_FILE_MACHINE_I386: u16 = 0x014C;
+pub const IMAGE_FILE_MACHINE_I386: u16 = 0x014C;
 /// Intel Itanium processor family
-const IMAGE_FILE_MACHINE_IA64: u16 = 0x0200;
+pub const IMAGE_FILE_MACHINE_IA64: u16 = 0x0200;
 /// LoongArch 32-bit processor family
-const IMAGE_FILE_MACHINE_LOONGARCH32: u16 = 0x6232;
+pub const IMAGE_FILE_MACHINE_LOONGARCH32: u16 = 0x6232;
 /// LoongArch 64-bit processor family
-const IMAGE_FILE_MACHINE_LOONGARCH64: u16 = 0x6264;
+pub const IMAGE_FILE_MACHINE_LOONGARCH64: u16 = 0x6264;
 /// Mitsubishi M32R little endian
-const IMAGE_FILE_MACHINE_M32R: u16 = 0x9041;
+pub const IMAGE_FILE_MACHINE_M32R: u16 = 0x9041;
 /// MIPS16
-const IMAGE_FILE_MACHINE_MIPS16: u16 = 0x0266;
+pub const IMAGE_FILE_MACHINE_MIPS16: u16 = 0x0266;
 /// MIPS with FPU
-const IMAGE_FILE_MACHINE_MIPSFPU: u16 = 0x0366;
+pub const IMAGE_FILE_MACHINE_MIPSFPU: u16 = 0x0366;
 /// MIPS16 with FPU
-const IMAGE_FILE_MACHINE_MIPSFPU16: u16 = 0x0466;
+pub const IMAGE_FILE_MACHINE_MIPSFPU16: u16 = 0x0466;
 /// Power PC little endian
-const IMAGE_FILE_MACHINE_POWERPC: u16 = 0x01F0;
+pub const IMAGE_FILE_MACHINE_POWERPC: u16 = 0x01F0;
 /// Power PC with floating point support
-const IMAGE_FILE_MACHINE_POWERPCFP: u16 = 0x01F1;
+pub const IMAGE_FILE_MACHINE_POWERPCFP: u16 = 0x01F1;
+/// Power PC big endian (Xbox 360)
+pub const IMAGE_FILE_MACHINE_POWERPCBE: u16 = 0x01F2;
 /// MIPS little endian
-const IMAGE_FILE_MACHINE_R4000: u16 = 0x0166;
+pub const IMAGE_FILE_MACHINE_R4000: u16 = 0x0166;
 /// RISC-V 32-bit address space
-const IMAGE_FILE_MACHINE_RISCV32: u16 = 0x5032;
+pub const IMAGE_FILE_MACHINE_RISCV32: u16 = 0x5032;
 /// RISC-V 64-bit address space
-const IMAGE_FILE_MACHINE_RISCV64: u16 = 0x5064;
+pub const IMAGE_FILE_MACHINE_RISCV64: u16 = 0x5064;
 /// RISC-V 128-bit address space
-const IMAGE_FILE_MACHINE_RISCV128: u16 = 0x5128;
+pub const IMAGE_FILE_MACHINE_RISCV128: u16 = 0x5128;
 /// Hitachi SH3
-const IMAGE_FILE_MACHINE_SH3: u16 = 0x01A2;
+pub const IMAGE_FILE_MACHINE_SH3: u16 = 0x01A2;
 /// Hitachi SH3 DSP
-const IMAGE_FILE_MACHINE_SH3DSP: u16 = 0x01A3;
+pub const IMAGE_FILE_MACHINE_SH3DSP: u16 = 0x01A3;
 /// Hitachi SH4
-const IMAGE_FILE_MACHINE_SH4: u16 = 0x01A6;
+pub const IMAGE_FILE_MACHINE_SH4: u16 = 0x01A6;
 /// Hitachi SH5
-const IMAGE_FILE_MACHINE_SH5: u16 = 0x01A8;
+pub const IMAGE_FILE_MACHINE_SH5: u16 = 0x01A8;
 /// Thumb
-const IMAGE_FILE_MACHINE_THUMB: u16 = 0x01C2;
+pub const IMAGE_FILE_MACHINE_THUMB: u16 = 0x01C2;
 /// MIPS little-endian WCE v2
-const IMAGE_FILE_MACHINE_WCEMIPSV2: u16 = 0x0169;
+pub const IMAGE_FILE_MACHINE_WCEMIPSV2: u16 = 0x0169;
 
-pub const MACHINE_LIST: [u16; 29] = [
+pub const MACHINE_LIST: [u16; 32] = [
     IMAGE_FILE_MACHINE_ALPHA,
     IMAGE_FILE_MACHINE_ALPHA64,
     IMAGE_FILE_MACHINE_AM33,
     IMAGE_FILE_MACHINE_AMD64,
     IMAGE_FILE_MACHINE_ARM,
     IMAGE_FILE_MACHINE_ARM64,
+    IMAGE_FILE_MACHINE_ARM64EC,
+    IMAGE_FILE_MACHINE_ARM64X,
     IMAGE_FILE_MACHINE_ARMNT,
     IMAGE_FILE_MACHINE_AXP64,
     IMAGE_FILE_MACHINE_EBC,
@@ -334,6 +387,7 @@ pub const MACHINE_LIST: [u16; 29] = [
     IMAGE_FILE_MACHINE_MIPSFPU16,
     IMAGE_FILE_MACHINE_POWERPC,
     IMAGE_FILE_MACHINE_POWERPCFP,
+    IMAGE_FILE_MACHINE_POWERPCBE,
     IMAGE_FILE_MACHINE_R4000,
     IMAGE_FILE_MACHINE_RISCV32,
     IMAGE_FILE_MACHINE_RISCV64,
@@ -346,6 +400,7 @@ pub const MACHINE_LIST: [u16; 29] = [
     IMAGE_FILE_MACHINE_WCEMIPSV2,
 ];
 
+#[derive(Debug)]
 pub struct Characteristics {
     relocs_stripped: bool,
     executable_image: bool,
@@ -367,22 +422,22 @@ pub struct Characteristics {
 
 impl From<u16> for Characteristics {
     fn from(value: u16) -> Self {
-        let relocs_stripped = (value % 2) != 0;
-        let executable_image = ((value >> 1) % 2) != 0;
-        let line_nums_stripped = ((value >> 2) % 2) != 0;
-        let local_syms_stripped = ((value >> 3) % 2) != 0;
-        let agressive_ws_trim = ((value >> 4) % 2) != 0;
-        let large_address_aware = ((value >> 5) % 2) != 0;
-        let reserved = ((value >> 6) % 2) != 0;
-        let bytes_reserved_lo = ((value >> 7) % 2) != 0;
-        let x32_machine = ((value >> 7) % 2) != 0;
-        let debug_stripped = ((value >> 8) % 2) != 0;
-        let removable_run_from_swap = ((value >> 9) % 2) != 0;
-        let net_run_from_swap = ((value >> 10) % 2) != 0;
-        let system = ((value >> 11) % 2) != 0;
-        let dynamic_link_library = ((value >> 12) % 2) != 0;
-        let uniprocessor_system_only = ((value >> 13) % 2) != 0;
-        let bytes_reserved_hi = ((value >> 14) % 2) != 0;
+        let relocs_stripped = value & IMAGE_FILE_RELOCS_STRIPPED != 0;
+        let executable_image = value & IMAGE_FILE_EXECUTABLE_IMAGE != 0;
+        let line_nums_stripped = value & IMAGE_FILE_LINE_NUMS_STRIPPED != 0;
+        let local_syms_stripped = value & IMAGE_FILE_LOCAL_SYMS_STRIPPED != 0;
+        let agressive_ws_trim = value & IMAGE_FILE_AGGRESSIVE_WS_TRIM != 0;
+        let large_address_aware = value & IMAGE_FILE_LARGE_ADDRESS_AWARE != 0;
+        let reserved = value & IMAGE_FILE_RESERVED != 0;
+        let bytes_reserved_lo = value & IMAGE_FILE_BYTES_REVERSED_LO != 0;
+        let x32_machine = value & IMAGE_FILE_32BIT_MACHINE != 0;
+        let debug_stripped = value & IMAGE_FILE_DEBUG_STRIPPED != 0;
+        let removable_run_from_swap = value & IMAGE_FILE_REMOVABLE_RUN_FROM_SWAP != 0;
+        let net_run_from_swap = value & IMAGE_FILE_NET_RUN_FROM_SWAP != 0;
+        let system = value & IMAGE_FILE_SYSTEM != 0;
+        let dynamic_link_library = value & IMAGE_FILE_DLL != 0;
+        let uniprocessor_system_only = value & IMAGE_FILE_UP_SYSTEM_ONLY != 0;
+        let bytes_reserved_hi = value & IMAGE_FILE_BYTES_REVERSED_HI != 0;
 
         Self {
             relocs_stripped,
@@ -405,6 +460,88 @@ impl From<u16> for Characteristics {
     }
 }
 
+impl Characteristics {
+    /// Image does not contain base relocations
+    pub fn relocs_stripped(&self) -> bool {
+        self.relocs_stripped
+    }
+
+    /// Image is valid and can be run
+    pub fn executable_image(&self) -> bool {
+        self.executable_image
+    }
+
+    /// COFF line numbers have been removed (deprecated)
+    pub fn line_nums_stripped(&self) -> bool {
+        self.line_nums_stripped
+    }
+
+    /// COFF symbol table entries for local symbols have been removed (deprecated)
+    pub fn local_syms_stripped(&self) -> bool {
+        self.local_syms_stripped
+    }
+
+    /// Aggressively trim working set (obsolete)
+    pub fn agressive_ws_trim(&self) -> bool {
+        self.agressive_ws_trim
+    }
+
+    /// Application can handle > 2 GB addresses
+    pub fn large_address_aware(&self) -> bool {
+        self.large_address_aware
+    }
+
+    /// Reserved for future use
+    pub fn reserved(&self) -> bool {
+        self.reserved
+    }
+
+    /// Little endian (deprecated)
+    pub fn bytes_reserved_lo(&self) -> bool {
+        self.bytes_reserved_lo
+    }
+
+    /// Machine is based on a 32-bit-word architecture
+    pub fn x32_machine(&self) -> bool {
+        self.x32_machine
+    }
+
+    /// Debugging information is removed from the image file
+    pub fn debug_stripped(&self) -> bool {
+        self.debug_stripped
+    }
+
+    /// If the image is on removable media, fully load it and copy it to the swap file
+    pub fn removable_run_from_swap(&self) -> bool {
+        self.removable_run_from_swap
+    }
+
+    /// If the image is on network media, fully load it and copy it to the swap file
+    pub fn net_run_from_swap(&self) -> bool {
+        self.net_run_from_swap
+    }
+
+    /// Image file is a system file, not a user program
+    pub fn system(&self) -> bool {
+        self.system
+    }
+
+    /// Image file is a dynamic-link library
+    pub fn dynamic_link_library(&self) -> bool {
+        self.dynamic_link_library
+    }
+
+    /// File should be run only on a uniprocessor machine
+    pub fn uniprocessor_system_only(&self) -> bool {
+        self.uniprocessor_system_only
+    }
+
+    /// Big endian (deprecated)
+    pub fn bytes_reserved_hi(&self) -> bool {
+        self.bytes_reserved_hi
+    }
+}
+
 pub const IMAGE_FILE_RELOCS_STRIPPED: u16 = 0x0001;
 pub const IMAGE_FILE_EXECUTABLE_IMAGE: u16 = 0x0002;
 pub const IMAGE_FILE_LINE_NUMS_STRIPPED: u16 = 0x0004;
@@ -0,0 +1,170 @@
+use crate::endian::{le_u32, le_u64};
+use crate::pe_string::PeString;
+use crate::port_exe::PortExe;
+use crate::ImageType;
+use std::fmt;
+use std::io;
+
+/// Number of prologue bytes compared by [`PortExe::inline_hooks`] by default
+pub const DEFAULT_PROLOGUE_LENGTH: usize = 16;
+
+/// Shape of the instruction found at a modified export entry point
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookKind {
+    /// `jmp rel32` or `jmp rel8`
+    Jump,
+    /// `call rel32`
+    Call,
+    /// `jmp [mem]`, through an absolute or RIP-relative pointer
+    IndirectJump,
+    /// `push imm32; ret`
+    PushRet,
+    /// `mov rax, imm64; jmp rax`
+    MovJump,
+    /// Bytes differ without a recognised detour instruction at the entry point
+    Patched,
+}
+
+impl fmt::Display for HookKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Jump => "jmp",
+            Self::Call => "call",
+            Self::IndirectJump => "jmp [mem]",
+            Self::PushRet => "push/ret",
+            Self::MovJump => "mov/jmp",
+            Self::Patched => "patched",
+        })
+    }
+}
+
+/// Export whose first bytes differ between a dumped module and its file on disk
+#[derive(Debug, Clone)]
+pub struct InlineHook {
+    ordinal: u32,
+    name: Option<PeString>,
+    rva: u32,
+    kind: HookKind,
+    target: Option<u64>,
+    original: Vec<u8>,
+    current: Vec<u8>,
+}
+
+impl InlineHook {
+    pub fn ordinal(&self) -> u32 {
+        self.ordinal
+    }
+
+    pub fn name(&self) -> Option<&PeString> {
+        self.name.as_ref()
+    }
+
+    pub fn rva(&self) -> u32 {
+        self.rva
+    }
+
+    pub fn kind(&self) -> HookKind {
+        self.kind
+    }
+
+    /// Virtual address the detour transfers control to, when encoded in the instruction
+    pub fn target(&self) -> Option<u64> {
+        self.target
+    }
+
+    /// Prologue bytes of the file on disk
+    pub fn original(&self) -> &[u8] {
+        &self.original
+    }
+
+    /// Prologue bytes of the dumped module
+    pub fn current(&self) -> &[u8] {
+        &self.current
+    }
+}
+
+impl PortExe {
+    /// Compares the first `length` bytes of every exported function of this module,
+    /// typically a memory dump, with those of `original`, its file on disk.
+    ///
+    /// Exports outside executable sections and forwarders are skipped. The dump is
+    /// expected to be loaded at its preferred base; relocated operands in a prologue
+    /// are reported as [`HookKind::Patched`].
+    pub fn inline_hooks(&self, original: &PortExe, length: usize) -> io::Result<Vec<InlineHook>> {
+        let image_base = self
+            .optional_header()
+            .map_or(0, |header| header.image_base());
+        let x64 = self
+            .optional_header()
+            .map_or(false, |header| header.image_type() == ImageType::X64);
+
+        let mut hooks = Vec::new();
+        for export in original.exports()? {
+            if export.forwarder().is_some() {
+                continue;
+            }
+            let executable = original
+                .section_at_rva(export.rva())
+                .map_or(false, |section| {
+                    section.characteristics().value().mem_execute()
+                });
+            if !executable {
+                continue;
+            }
+            let (expected, current) = match (
+                prologue(original, export.rva(), length),
+                prologue(self, export.rva(), length),
+            ) {
+                (Some(expected), Some(current)) => (expected, current),
+                _ => continue,
+            };
+            let compared = expected.len().min(current.len());
+            if expected[..compared] == current[..compared] {
+                continue;
+            }
+            let address = image_base.wrapping_add(export.rva() as u64);
+            let (kind, target) = classify(current, address, x64);
+            hooks.push(InlineHook {
+                ordinal: export.ordinal(),
+                name: export.name().cloned(),
+                rva: export.rva(),
+                kind,
+                target,
+                original: expected[..compared].to_vec(),
+                current: current[..compared].to_vec(),
+            });
+        }
+        Ok(hooks)
+    }
+}
+
+/// Up to `length` bytes at `rva`, cut short at the end of the backing data
+fn prologue(pe: &PortExe, rva: u32, length: usize) -> Option<&[u8]> {
+    let offset = pe.rva_to_offset(rva)?;
+    let data = &pe.data()[offset..];
+    Some(&data[..length.min(data.len())])
+}
+
+/// Recognises the usual detour instructions at `code`, located at virtual address `address`.
+fn classify(code: &[u8], address: u64, x64: bool) -> (HookKind, Option<u64>) {
+    let relative = |size: u64, displacement: i64| {
+        Some(address.wrapping_add(size).wrapping_add(displacement as u64))
+    };
+    match *code {
+        [0xE9, a, b, c, d, ..] => (
+            HookKind::Jump,
+            relative(5, le_u32([a, b, c, d]) as i32 as i64),
+        ),
+        [0xEB, displacement, ..] => (HookKind::Jump, relative(2, displacement as i8 as i64)),
+        [0xE8, a, b, c, d, ..] => (
+            HookKind::Call,
+            relative(5, le_u32([a, b, c, d]) as i32 as i64),
+        ),
+        [0xFF, 0x25, _, _, _, _, ..] => (HookKind::IndirectJump, None),
+        [0x68, a, b, c, d, 0xC3, ..] => (HookKind::PushRet, Some(le_u32([a, b, c, d]) as u64)),
+        [0x48, 0xB8, a, b, c, d, e, f, g, h, 0xFF, 0xE0, ..] if x64 => {
+            (HookKind::MovJump, Some(le_u64([a, b, c, d, e, f, g, h])))
+        }
+        _ => (HookKind::Patched, None),
+    }
+}
@@ -0,0 +1,375 @@
+use crate::arith::rva_add;
+use crate::import::{IMAGE_ORDINAL_FLAG32, IMAGE_ORDINAL_FLAG64};
+use crate::labels::Label;
+use crate::loader::{SymbolRef, VirtualLoader};
+use crate::optional_header::IMAGE_DIRECTORY_ENTRY_IAT;
+use crate::port_exe::PortExe;
+use crate::{module_key, read_u32, read_u64};
+use std::collections::HashMap;
+use std::io;
+
+/// Upper bound on the number of slots scanned in an import address table
+pub const MAX_IAT_SLOTS: u32 = 0x10000;
+
+/// Export a pointer in the import address table was resolved to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotTarget {
+    module: String,
+    symbol: SymbolRef,
+}
+
+impl SlotTarget {
+    /// Name of the module the import is attributed to
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    pub fn symbol(&self) -> &SymbolRef {
+        &self.symbol
+    }
+}
+
+/// Pointer-sized slot of an import address table in a dumped image
+#[derive(Debug, Clone)]
+pub struct IatSlot {
+    rva: u32,
+    value: u64,
+    target: Option<SlotTarget>,
+}
+
+impl IatSlot {
+    pub fn rva(&self) -> u32 {
+        self.rva
+    }
+
+    /// Address stored in the slot by the loader
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    /// Export the address belongs to, `None` for terminators and unknown addresses
+    pub fn target(&self) -> Option<&SlotTarget> {
+        self.target.as_ref()
+    }
+}
+
+/// Import address table slot of an image and the import the loader fills it with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IatEntry {
+    rva: u32,
+    target: SlotTarget,
+}
+
+impl IatEntry {
+    pub fn rva(&self) -> u32 {
+        self.rva
+    }
+
+    /// DLL named by the import descriptor and the imported function
+    pub fn target(&self) -> &SlotTarget {
+        &self.target
+    }
+
+    /// `DLL!Function` or `DLL!#Ordinal` label for the slot
+    pub fn label(&self) -> Label {
+        Label::new(
+            self.rva,
+            &format!("{}!{}", self.target.module, self.target.symbol),
+        )
+    }
+}
+
+/// Thunk array of one DLL recovered from an import address table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RebuiltDescriptor {
+    dll_name: String,
+    first_thunk: u32,
+    symbols: Vec<SymbolRef>,
+}
+
+impl RebuiltDescriptor {
+    pub fn dll_name(&self) -> &str {
+        &self.dll_name
+    }
+
+    /// RVA of the first slot, kept as the `FirstThunk` of the rebuilt descriptor
+    pub fn first_thunk(&self) -> u32 {
+        self.first_thunk
+    }
+
+    /// Imports of consecutive slots starting at [`first_thunk`](Self::first_thunk)
+    pub fn symbols(&self) -> &[SymbolRef] {
+        &self.symbols
+    }
+}
+
+impl PortExe {
+    /// Range of the import address table given by its data directory
+    pub fn iat_range(&self) -> Option<(u32, u32)> {
+        let directory = self
+            .optional_header()?
+            .data_directory(IMAGE_DIRECTORY_ENTRY_IAT)?;
+        let rva = directory.virtual_address().into_value();
+        let size = directory.size().into_value();
+        (rva != 0 && size != 0).then(|| (rva, size))
+    }
+
+    /// Maps every import address table slot named by the import directory to its DLL
+    /// and function, sorted by RVA, for debuggers to label.
+    pub fn iat_map(&self) -> io::Result<Vec<IatEntry>> {
+        let mut entries: Vec<IatEntry> = self
+            .imports()?
+            .iter()
+            .flat_map(|descriptor| {
+                descriptor.imports().iter().filter_map(move |import| {
+                    let symbol = match (import.name(), import.ordinal()) {
+                        (Some(name), _) => SymbolRef::Name(name.clone()),
+                        (None, Some(ordinal)) => SymbolRef::Ordinal(ordinal as u32),
+                        (None, None) => return None,
+                    };
+                    Some(IatEntry {
+                        rva: import.iat_rva(),
+                        target: SlotTarget {
+                            module: descriptor.dll_name().to_string(),
+                            symbol,
+                        },
+                    })
+                })
+            })
+            .collect();
+        entries.sort_by_key(|entry| entry.rva);
+        Ok(entries)
+    }
+
+    /// Reads the pointers in `size` bytes at `rva` and resolves each of them against
+    /// the exports of the modules in `loader`, laid out at their bases at dump time.
+    ///
+    /// An address exported by several modules, typically through a forwarder, is
+    /// attributed to the module its neighbours come from, so that every thunk array
+    /// names a single DLL as the loader requires.
+    pub fn scan_iat(
+        &self,
+        loader: &VirtualLoader,
+        rva: u32,
+        size: u32,
+    ) -> io::Result<Vec<IatSlot>> {
+        let pe64 = self
+            .optional_header()
+            .ok_or_else(|| invalid_data("object files have no import address table"))?
+            .image_type()
+            .is_x64();
+        let thunk_size = if pe64 { 8 } else { 4 };
+        let count = (size / thunk_size).min(MAX_IAT_SLOTS);
+        let data = self
+            .data_at_rva(rva, count * thunk_size)
+            .ok_or_else(|| invalid_data("import address table is not backed by file data"))?;
+        let values: Vec<u64> = (0..count as usize)
+            .map(|index| {
+                let offset = index * thunk_size as usize;
+                if pe64 {
+                    read_u64(data, offset).unwrap_or(0)
+                } else {
+                    read_u32(data, offset).map_or(0, u64::from)
+                }
+            })
+            .collect();
+
+        let candidates = export_addresses(loader);
+        let lookup = |value: &u64| candidates.get(value).map(Vec::as_slice).unwrap_or(&[]);
+        let mut slots = Vec::with_capacity(values.len());
+        let mut current: Option<String> = None;
+        for (index, value) in values.iter().enumerate() {
+            let options = lookup(value);
+            let chosen = current
+                .as_deref()
+                .and_then(|module| options.iter().find(|target| target.module == module))
+                .or_else(|| {
+                    // Prefer the module that also explains most of the following slots.
+                    let run: Vec<&[SlotTarget]> = values[index + 1..]
+                        .iter()
+                        .take_while(|&&value| value != 0)
+                        .map(lookup)
+                        .collect();
+                    options.iter().rev().max_by_key(|target| {
+                        run.iter()
+                            .filter(|next| next.iter().any(|other| other.module == target.module))
+                            .count()
+                    })
+                })
+                .cloned();
+            current = chosen.as_ref().map(|target| target.module.clone());
+            slots.push(IatSlot {
+                rva: rva.wrapping_add(index as u32 * thunk_size),
+                value: *value,
+                target: chosen,
+            });
+        }
+        Ok(slots)
+    }
+
+    /// Groups the resolved slots of an import address table into thunk arrays per DLL,
+    /// the way Scylla rebuilds the imports of a dumped image.
+    ///
+    /// Arrays end at null slots, at unresolved addresses and where the module changes.
+    pub fn reconstruct_imports(
+        &self,
+        loader: &VirtualLoader,
+        rva: u32,
+        size: u32,
+    ) -> io::Result<Vec<RebuiltDescriptor>> {
+        let mut descriptors: Vec<RebuiltDescriptor> = Vec::new();
+        let mut open = false;
+        for slot in self.scan_iat(loader, rva, size)? {
+            let target = match slot.target {
+                Some(target) => target,
+                None => {
+                    open = false;
+                    continue;
+                }
+            };
+            match descriptors.last_mut() {
+                Some(descriptor) if open && descriptor.dll_name == target.module => {
+                    descriptor.symbols.push(target.symbol)
+                }
+                _ => {
+                    descriptors.push(RebuiltDescriptor {
+                        dll_name: target.module,
+                        first_thunk: slot.rva,
+                        symbols: vec![target.symbol],
+                    });
+                    open = true;
+                }
+            }
+        }
+        Ok(descriptors)
+    }
+}
+
+/// Serialises an import directory for `descriptors` to be placed at `rva`.
+///
+/// The descriptors keep the `FirstThunk` of the existing import address table, and the
+/// import lookup tables, hint/name entries and DLL names all follow them in the returned
+/// block. Hints are zero, which only costs the loader a binary search.
+pub fn build_import_table(
+    descriptors: &[RebuiltDescriptor],
+    rva: u32,
+    pe64: bool,
+) -> io::Result<Vec<u8>> {
+    let (thunk_size, ordinal_flag) = if pe64 {
+        (8, IMAGE_ORDINAL_FLAG64)
+    } else {
+        (4, IMAGE_ORDINAL_FLAG32)
+    };
+    let lookup_offset = (descriptors.len() + 1) * 20;
+    let strings_offset = lookup_offset
+        + descriptors
+            .iter()
+            .map(|descriptor| (descriptor.symbols.len() + 1) * thunk_size)
+            .sum::<usize>();
+    let strings_size = descriptors
+        .iter()
+        .flat_map(|descriptor| &descriptor.symbols)
+        .map(|symbol| match symbol {
+            SymbolRef::Name(name) => (name.len() + 4) & !1,
+            SymbolRef::Ordinal(_) => 0,
+        })
+        .chain(
+            descriptors
+                .iter()
+                .map(|descriptor| descriptor.dll_name.len() + 1),
+        )
+        .sum::<usize>();
+    // Every RVA below lies inside the table, so a table that fits keeps them in range.
+    u32::try_from(strings_offset + strings_size)
+        .ok()
+        .and_then(|size| rva_add(rva, size))
+        .ok_or_else(|| invalid_data("import table does not fit in the address space"))?;
+
+    let mut table = vec![0u8; lookup_offset];
+    let mut strings = Vec::new();
+    let mut lookup_tables = Vec::new();
+    for (index, descriptor) in descriptors.iter().enumerate() {
+        let lookup_rva = rva + (lookup_offset + lookup_tables.len()) as u32;
+        for symbol in &descriptor.symbols {
+            let thunk = match symbol {
+                SymbolRef::Ordinal(ordinal) => {
+                    let ordinal = u16::try_from(*ordinal)
+                        .map_err(|_| invalid_data("import ordinal does not fit in 16 bits"))?;
+                    ordinal_flag | ordinal as u64
+                }
+                SymbolRef::Name(name) => {
+                    let hint_name_rva = rva + (strings_offset + strings.len()) as u32;
+                    strings.extend_from_slice(&[0, 0]);
+                    strings.extend_from_slice(name.as_bytes());
+                    strings.push(0);
+                    if strings.len() % 2 != 0 {
+                        strings.push(0);
+                    }
+                    hint_name_rva as u64
+                }
+            };
+            lookup_tables.extend_from_slice(&thunk.to_le_bytes()[..thunk_size]);
+        }
+        lookup_tables.extend_from_slice(&vec![0; thunk_size]);
+
+        let name_rva = rva + (strings_offset + strings.len()) as u32;
+        strings.extend_from_slice(descriptor.dll_name.as_bytes());
+        strings.push(0);
+
+        let entry = &mut table[index * 20..index * 20 + 20];
+        entry[0..4].copy_from_slice(&lookup_rva.to_le_bytes());
+        entry[12..16].copy_from_slice(&name_rva.to_le_bytes());
+        entry[16..20].copy_from_slice(&descriptor.first_thunk.to_le_bytes());
+    }
+    table.extend_from_slice(&lookup_tables);
+    table.extend_from_slice(&strings);
+    Ok(table)
+}
+
+/// Exports of every loaded module by virtual address, including those reached through
+/// forwarders, which are listed after the module that implements them.
+fn export_addresses(loader: &VirtualLoader) -> HashMap<u64, Vec<SlotTarget>> {
+    let mut addresses: HashMap<u64, Vec<SlotTarget>> = HashMap::new();
+    let mut forwarded = Vec::new();
+    for module in loader.modules() {
+        for export in module.exports() {
+            let symbol = match export.name() {
+                Some(name) => SymbolRef::Name(name.clone()),
+                None => SymbolRef::Ordinal(export.ordinal()),
+            };
+            let target = SlotTarget {
+                module: module.name().to_string(),
+                symbol,
+            };
+            if export.forwarder().is_some() {
+                if let Ok(resolution) = loader.resolve(module.name(), &target.symbol) {
+                    forwarded.push((resolution.address(), target));
+                }
+            } else {
+                addresses
+                    .entry(module.base().wrapping_add(export.rva() as u64))
+                    .or_default()
+                    .push(target);
+            }
+        }
+    }
+    for (address, target) in forwarded {
+        addresses.entry(address).or_default().push(target);
+    }
+    for targets in addresses.values_mut() {
+        // Named exports first, and no module twice for the same address.
+        targets.sort_by_key(|target| matches!(target.symbol, SymbolRef::Ordinal(_)));
+        let mut seen = Vec::new();
+        targets.retain(|target| {
+            let key = module_key(&target.module);
+            let new = !seen.contains(&key);
+            seen.push(key);
+            new
+        });
+    }
+    addresses
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
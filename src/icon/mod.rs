@@ -0,0 +1,527 @@
+use crate::port_exe::PortExe;
+use crate::resource::{ResourceEntry, ResourceId, RT_GROUP_ICON, RT_ICON};
+use crate::{read_u16, read_u32};
+use std::fmt;
+use std::io;
+
+/// `GRPICONDIR`: reserved, type and count
+const GROUP_HEADER_SIZE: usize = 6;
+/// `GRPICONDIRENTRY`
+const GROUP_ENTRY_SIZE: usize = 14;
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1A\n";
+/// Largest width or height decoded; icons stop at 256 pixels
+const MAX_DIMENSION: u32 = 1024;
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+
+/// Largest [`IconHash::distance`] at which two icons are taken to show the same picture
+pub const DEFAULT_ICON_DISTANCE: u32 = 10;
+
+/// Storage format of an icon image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IconFormat {
+    /// Device-independent bitmap with an AND mask
+    Bitmap,
+    Png,
+}
+
+/// Image of an icon group chosen to represent the file
+#[derive(Debug, Clone)]
+pub struct Icon {
+    group: ResourceId,
+    id: u16,
+    width: u32,
+    height: u32,
+    bit_count: u16,
+    format: IconFormat,
+    data: Vec<u8>,
+}
+
+impl Icon {
+    /// Name of the `RT_GROUP_ICON` resource the image belongs to
+    pub fn group(&self) -> &ResourceId {
+        &self.group
+    }
+
+    /// Name of the `RT_ICON` resource holding the image
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    /// Width as the group records it, 256 for the byte value 0
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn bit_count(&self) -> u16 {
+        self.bit_count
+    }
+
+    pub fn format(&self) -> IconFormat {
+        self.format
+    }
+
+    /// Contents of the `RT_ICON` resource
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Perceptual hashes of the image composited over white.
+    ///
+    /// PNG images need the `icon-png` feature; without it they are an
+    /// [`io::ErrorKind::Unsupported`] error.
+    pub fn hash(&self) -> io::Result<IconHash> {
+        let image = match self.format {
+            IconFormat::Bitmap => decode_bitmap(&self.data)?,
+            IconFormat::Png => decode_png(&self.data)?,
+        };
+        Ok(IconHash {
+            ahash: average_hash(&image),
+            dhash: difference_hash(&image),
+        })
+    }
+}
+
+/// Average and difference hashes of an icon.
+///
+/// Icons that look alike have hashes a small [`hamming_distance`] apart, whatever
+/// their size, bit depth or format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IconHash {
+    ahash: u64,
+    dhash: u64,
+}
+
+impl IconHash {
+    pub fn new(ahash: u64, dhash: u64) -> Self {
+        Self { ahash, dhash }
+    }
+
+    /// Parses the `ahash:dhash` hexadecimal form [`IconHash`] displays as.
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let invalid = || invalid_input(&format!("invalid icon hash: {}", text));
+        let (ahash, dhash) = text.trim().split_once(':').ok_or_else(invalid)?;
+        Ok(Self {
+            ahash: u64::from_str_radix(ahash, 16).map_err(|_| invalid())?,
+            dhash: u64::from_str_radix(dhash, 16).map_err(|_| invalid())?,
+        })
+    }
+
+    /// Bits of an 8x8 thumbnail set where the pixel is brighter than the mean
+    pub fn ahash(&self) -> u64 {
+        self.ahash
+    }
+
+    /// Bits of a 9x8 thumbnail set where a pixel is brighter than its right neighbour
+    pub fn dhash(&self) -> u64 {
+        self.dhash
+    }
+
+    /// Largest of the distances between the two average and difference hashes
+    pub fn distance(&self, other: &IconHash) -> u32 {
+        hamming_distance(self.ahash, other.ahash).max(hamming_distance(self.dhash, other.dhash))
+    }
+}
+
+impl fmt::Display for IconHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}:{:016x}", self.ahash, self.dhash)
+    }
+}
+
+/// Number of bits that differ between two hashes
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+impl PortExe {
+    /// Largest, deepest image of the icon group Explorer shows for the file: the first
+    /// group in directory order, where named groups precede numbered ones.
+    pub fn primary_icon(&self) -> io::Result<Option<Icon>> {
+        let resources = self.resources()?;
+        let groups: Vec<&ResourceEntry> = resources
+            .iter()
+            .filter(|entry| entry.type_id() == &ResourceId::Id(RT_GROUP_ICON))
+            .collect();
+        let group = match groups
+            .iter()
+            .find(|entry| matches!(entry.name_id(), ResourceId::Name(_)))
+            .or_else(|| groups.first())
+        {
+            Some(group) => group,
+            None => return Ok(None),
+        };
+        let directory = self
+            .resource_data(group)
+            .ok_or_else(|| invalid_data("icon group lies outside the file"))?;
+        let count = read_u16(directory, 4).ok_or_else(|| invalid_data("truncated icon group"))?;
+        let best = (0..count as usize)
+            .map_while(|index| {
+                let entry = directory.get(
+                    GROUP_HEADER_SIZE + index * GROUP_ENTRY_SIZE
+                        ..GROUP_HEADER_SIZE + (index + 1) * GROUP_ENTRY_SIZE,
+                )?;
+                let dimension = |byte: u8| if byte == 0 { 256 } else { byte as u32 };
+                Some((
+                    dimension(entry[0]),
+                    dimension(entry[1]),
+                    read_u16(entry, 6)?,
+                    read_u16(entry, 12)?,
+                ))
+            })
+            .max_by_key(|&(width, height, bit_count, _)| (width * height, bit_count));
+        let (width, height, bit_count, id) = match best {
+            Some(best) => best,
+            None => return Err(invalid_data("icon group lists no images")),
+        };
+        let image = resources
+            .iter()
+            .find(|entry| {
+                entry.type_id() == &ResourceId::Id(RT_ICON)
+                    && entry.name_id() == &ResourceId::Id(id)
+            })
+            .ok_or_else(|| invalid_data("icon group names a missing icon"))?;
+        let data = self
+            .resource_data(image)
+            .ok_or_else(|| invalid_data("icon lies outside the file"))?;
+        let format = if data.starts_with(PNG_SIGNATURE) {
+            IconFormat::Png
+        } else {
+            IconFormat::Bitmap
+        };
+        Ok(Some(Icon {
+            group: group.name_id().clone(),
+            id,
+            width,
+            height,
+            bit_count,
+            format,
+            data: data.to_vec(),
+        }))
+    }
+
+    /// Perceptual hash of [`PortExe::primary_icon`], `None` if the file has no icon.
+    pub fn icon_hash(&self) -> io::Result<Option<IconHash>> {
+        self.primary_icon()?.map(|icon| icon.hash()).transpose()
+    }
+}
+
+/// Grayscale image, one byte per pixel in rows from the top
+struct GrayImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+/// Luma of a pixel composited over white
+fn gray(red: u8, green: u8, blue: u8, alpha: u8) -> u8 {
+    let luma = (299 * red as u32 + 587 * green as u32 + 114 * blue as u32) / 1000;
+    ((luma * alpha as u32 + 255 * (255 - alpha as u32)) / 255) as u8
+}
+
+/// Decodes the `BITMAPINFOHEADER`, colour table, XOR image and AND mask of an icon.
+fn decode_bitmap(data: &[u8]) -> io::Result<GrayImage> {
+    let truncated = || invalid_data("truncated icon bitmap");
+    let header_size = read_u32(data, 0).ok_or_else(truncated)? as usize;
+    let width = read_u32(data, 4).ok_or_else(truncated)? as i32;
+    // The height covers the XOR image and the AND mask
+    let height = read_u32(data, 8).ok_or_else(truncated)? as i32 / 2;
+    let bit_count = read_u16(data, 14).ok_or_else(truncated)?;
+    let compression = read_u32(data, 16).ok_or_else(truncated)?;
+    let colors_used = read_u32(data, 32).ok_or_else(truncated)? as usize;
+    if header_size < 40 || width <= 0 || height <= 0 {
+        return Err(invalid_data("invalid icon bitmap header"));
+    }
+    let (width, height) = (width as u32, height as u32);
+    if width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(invalid_data("icon bitmap is too large"));
+    }
+    let supported = match bit_count {
+        1 | 4 | 8 | 24 => compression == BI_RGB,
+        32 => compression == BI_RGB || compression == BI_BITFIELDS,
+        _ => false,
+    };
+    if !supported {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "unsupported icon bitmap format: {} bits per pixel",
+                bit_count
+            ),
+        ));
+    }
+
+    let (width, height) = (width as usize, height as usize);
+    let palette_size = match bit_count {
+        1 | 4 | 8 if colors_used > 0 => colors_used.min(256),
+        1 | 4 | 8 => 1 << bit_count,
+        _ => 0,
+    };
+    let mut offset = header_size;
+    if compression == BI_BITFIELDS {
+        offset += 12;
+    }
+    let palette = data
+        .get(offset..offset + palette_size * 4)
+        .ok_or_else(truncated)?;
+    offset += palette_size * 4;
+    let stride = (width * bit_count as usize + 31) / 32 * 4;
+    let pixels = data
+        .get(offset..offset + stride * height)
+        .ok_or_else(truncated)?;
+    let mask_stride = (width + 31) / 32 * 4;
+    // Icons with an alpha channel may omit the mask
+    let mask = data.get(offset + stride * height..offset + (stride + mask_stride) * height);
+    let has_alpha = bit_count == 32
+        && pixels
+            .chunks(stride)
+            .any(|row| row[..width * 4].chunks(4).any(|pixel| pixel[3] != 0));
+
+    let mut gray_pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        // Rows are stored bottom-up
+        let row = &pixels[(height - 1 - y) * stride..][..stride];
+        let mask_row = mask.map(|mask| &mask[(height - 1 - y) * mask_stride..][..mask_stride]);
+        for x in 0..width {
+            let (blue, green, red, alpha) = match bit_count {
+                24 => (row[x * 3], row[x * 3 + 1], row[x * 3 + 2], 255),
+                32 => (row[x * 4], row[x * 4 + 1], row[x * 4 + 2], row[x * 4 + 3]),
+                _ => {
+                    let bits = bit_count as usize;
+                    let byte = row[x * bits / 8];
+                    let shift = 8 - bits - (x * bits % 8);
+                    let index = ((byte >> shift) as usize) & ((1 << bits) - 1);
+                    match palette.get(index * 4..index * 4 + 3) {
+                        Some(color) => (color[0], color[1], color[2], 255),
+                        None => (0, 0, 0, 255),
+                    }
+                }
+            };
+            let transparent = mask_row.map_or(false, |mask| mask[x / 8] & (0x80 >> (x % 8)) != 0);
+            let alpha = match (has_alpha, transparent) {
+                (true, _) => alpha,
+                (false, true) => 0,
+                (false, false) => 255,
+            };
+            gray_pixels.push(gray(red, green, blue, alpha));
+        }
+    }
+    Ok(GrayImage {
+        width,
+        height,
+        pixels: gray_pixels,
+    })
+}
+
+/// Decodes a non-interlaced PNG with a bit depth of 8, or a palette of any depth.
+#[cfg(feature = "icon-png")]
+fn decode_png(data: &[u8]) -> io::Result<GrayImage> {
+    use crate::endian::read_be_u32;
+
+    let truncated = || invalid_data("truncated PNG icon");
+    let mut offset = PNG_SIGNATURE.len();
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut transparency: &[u8] = &[];
+    let mut compressed = Vec::new();
+    loop {
+        let length = read_be_u32(data, offset).ok_or_else(truncated)? as usize;
+        let kind = data.get(offset + 4..offset + 8).ok_or_else(truncated)?;
+        let body = data
+            .get(offset + 8..(offset + 8).saturating_add(length))
+            .ok_or_else(truncated)?;
+        match kind {
+            b"IHDR" => header = Some(body),
+            b"PLTE" => palette = body,
+            b"tRNS" => transparency = body,
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        // Length, type, data and CRC
+        offset += 12 + length;
+    }
+    let header = header.ok_or_else(|| invalid_data("PNG icon has no IHDR chunk"))?;
+    if header.len() < 13 {
+        return Err(truncated());
+    }
+    let width = read_be_u32(header, 0).ok_or_else(truncated)?;
+    let height = read_be_u32(header, 4).ok_or_else(truncated)?;
+    let (depth, color_type, interlace) = (header[8], header[9], header[12]);
+    if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(invalid_data("invalid PNG icon dimensions"));
+    }
+    let channels = match (color_type, depth) {
+        (0, 8) => 1,
+        (2, 8) => 3,
+        (3, 1) | (3, 2) | (3, 4) | (3, 8) => 1,
+        (4, 8) => 2,
+        (6, 8) => 4,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "unsupported PNG icon format: color type {}, depth {}",
+                    color_type, depth
+                ),
+            ))
+        }
+    };
+    if interlace != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "interlaced PNG icons are not supported",
+        ));
+    }
+
+    let (width, height) = (width as usize, height as usize);
+    let raw = crate::decompress::inflate_zlib(&compressed)?;
+    let stride = (width * channels * depth as usize + 7) / 8;
+    let pixel_size = ((channels * depth as usize + 7) / 8).max(1);
+    let mut rows = vec![0u8; stride * height];
+    for y in 0..height {
+        let line = raw
+            .get(y * (stride + 1)..(y + 1) * (stride + 1))
+            .ok_or_else(truncated)?;
+        let (filter, line) = (line[0], &line[1..]);
+        let (previous, current) = rows.split_at_mut(y * stride);
+        let previous = if y == 0 {
+            None
+        } else {
+            Some(&previous[(y - 1) * stride..])
+        };
+        let current = &mut current[..stride];
+        for x in 0..stride {
+            let left = if x >= pixel_size {
+                current[x - pixel_size]
+            } else {
+                0
+            };
+            let up = previous.map_or(0, |row| row[x]);
+            let up_left = match previous {
+                Some(row) if x >= pixel_size => row[x - pixel_size],
+                _ => 0,
+            };
+            let predictor = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err(invalid_data("invalid PNG filter type")),
+            };
+            current[x] = line[x].wrapping_add(predictor);
+        }
+    }
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for row in rows.chunks(stride) {
+        for x in 0..width {
+            let pixel = match color_type {
+                0 => (row[x], row[x], row[x], 255),
+                2 => (row[x * 3], row[x * 3 + 1], row[x * 3 + 2], 255),
+                4 => (row[x * 2], row[x * 2], row[x * 2], row[x * 2 + 1]),
+                6 => (row[x * 4], row[x * 4 + 1], row[x * 4 + 2], row[x * 4 + 3]),
+                _ => {
+                    let bits = depth as usize;
+                    let shift = 8 - bits - (x * bits % 8);
+                    let index = ((row[x * bits / 8] >> shift) as usize) & ((1 << bits) - 1);
+                    let alpha = transparency.get(index).copied().unwrap_or(255);
+                    match palette.get(index * 3..index * 3 + 3) {
+                        Some(color) => (color[0], color[1], color[2], alpha),
+                        None => (0, 0, 0, alpha),
+                    }
+                }
+            };
+            pixels.push(gray(pixel.0, pixel.1, pixel.2, pixel.3));
+        }
+    }
+    Ok(GrayImage {
+        width,
+        height,
+        pixels,
+    })
+}
+
+#[cfg(not(feature = "icon-png"))]
+fn decode_png(_data: &[u8]) -> io::Result<GrayImage> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "PNG icons require the `icon-png` feature",
+    ))
+}
+
+#[cfg(feature = "icon-png")]
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let (to_left, to_up, to_up_left) = (
+        (estimate - left as i16).abs(),
+        (estimate - up as i16).abs(),
+        (estimate - up_left as i16).abs(),
+    );
+    if to_left <= to_up && to_left <= to_up_left {
+        left
+    } else if to_up <= to_up_left {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// Shrinks `image` to `width` by `height` pixels, averaging the pixels each covers.
+fn thumbnail(image: &GrayImage, width: usize, height: usize) -> Vec<u32> {
+    let span = |index: usize, target: usize, source: usize| {
+        let start = index * source / target;
+        let end = ((index + 1) * source / target).max(start + 1);
+        start..end
+    };
+    let mut cells = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let rows = span(y, height, image.height);
+        for x in 0..width {
+            let columns = span(x, width, image.width);
+            let mut sum = 0;
+            let mut count = 0;
+            for row in rows.clone() {
+                for column in columns.clone() {
+                    sum += image.pixels[row * image.width + column] as u32;
+                    count += 1;
+                }
+            }
+            cells.push(sum / count);
+        }
+    }
+    cells
+}
+
+fn average_hash(image: &GrayImage) -> u64 {
+    let cells = thumbnail(image, 8, 8);
+    let mean = cells.iter().sum::<u32>() / 64;
+    cells
+        .iter()
+        .fold(0, |hash, &cell| (hash << 1) | (cell > mean) as u64)
+}
+
+fn difference_hash(image: &GrayImage) -> u64 {
+    let cells = thumbnail(image, 9, 8);
+    let mut hash = 0;
+    for row in cells.chunks(9) {
+        for pair in row.windows(2) {
+            hash = (hash << 1) | (pair[0] > pair[1]) as u64;
+        }
+    }
+    hash
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,551 @@
+use crate::optional_header::{IMAGE_DIRECTORY_ENTRY_DELAY_IMPORT, IMAGE_DIRECTORY_ENTRY_IMPORT};
+use crate::pe_string::PeString;
+use crate::port_exe::PortExe;
+use crate::{read_u16, read_u32, read_u64};
+use std::fmt;
+use std::io;
+
+/// Upper bound on the number of import descriptors walked
+pub const MAX_IMPORT_DESCRIPTORS: u32 = 0x1000;
+/// Upper bound on the number of thunks walked per imported DLL
+pub const MAX_IMPORTS_PER_DLL: u32 = 0x10000;
+
+/// Upper bound on the number of thunks walked over all imported DLLs
+pub const MAX_IMPORTS: u32 = 0x40000;
+/// Upper bound on the length of DLL and function names
+pub const MAX_IMPORT_NAME_LENGTH: usize = 0x1000;
+
+/// Thunk flag of a PE32 import by ordinal
+pub const IMAGE_ORDINAL_FLAG32: u64 = 0x8000_0000;
+/// Thunk flag of a PE32+ import by ordinal
+pub const IMAGE_ORDINAL_FLAG64: u64 = 0x8000_0000_0000_0000;
+/// Bits of an import-by-name thunk holding the hint/name RVA
+const HINT_NAME_RVA_MASK: u32 = 0x7FFF_FFFF;
+
+/// Size of an `IMAGE_IMPORT_DESCRIPTOR`
+const IMAGE_SIZEOF_IMPORT_DESCRIPTOR: u32 = 20;
+/// Size of an `IMAGE_DELAYLOAD_DESCRIPTOR`
+const IMAGE_SIZEOF_DELAYLOAD_DESCRIPTOR: u32 = 32;
+/// Delay-load descriptor attribute set when its addresses are RVAs; Visual C++ 6 left
+/// it clear and stored virtual addresses instead
+pub const DLATTR_RVA: u32 = 0x1;
+
+/// Function imported by name or by ordinal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    name: Option<PeString>,
+    hint_name_rva: Option<u32>,
+    hint: u16,
+    ordinal: Option<u16>,
+    iat_rva: u32,
+}
+
+impl Import {
+    /// Imported name, absent for imports by ordinal
+    pub fn name(&self) -> Option<&PeString> {
+        self.name.as_ref()
+    }
+
+    /// Address of the `IMAGE_IMPORT_BY_NAME` entry, the hint followed by the name; absent
+    /// for imports by ordinal
+    pub fn hint_name_rva(&self) -> Option<u32> {
+        self.hint_name_rva
+    }
+
+    /// Index into the export name table of the DLL tried first by the loader
+    pub fn hint(&self) -> u16 {
+        self.hint
+    }
+
+    /// Ordinal of an import by ordinal
+    pub fn ordinal(&self) -> Option<u16> {
+        self.ordinal
+    }
+
+    /// Address of the import address table slot the loader fills in
+    pub fn iat_rva(&self) -> u32 {
+        self.iat_rva
+    }
+}
+
+/// `IMAGE_IMPORT_DESCRIPTOR` or `IMAGE_DELAYLOAD_DESCRIPTOR` with the functions imported
+/// from one DLL
+#[derive(Debug, Clone)]
+pub struct ImportDescriptor {
+    original_first_thunk: u32,
+    time_date_stamp: u32,
+    forwarder_chain: u32,
+    name_rva: u32,
+    dll_name: String,
+    first_thunk: u32,
+    imports: Vec<Import>,
+    delay_load: Option<DelayLoad>,
+}
+
+impl ImportDescriptor {
+    /// RVA of the import lookup table, zero for some old linkers; the import name table
+    /// of a delay-load descriptor
+    pub fn original_first_thunk(&self) -> u32 {
+        self.original_first_thunk
+    }
+
+    /// Zero unless the image is bound
+    pub fn time_date_stamp(&self) -> u32 {
+        self.time_date_stamp
+    }
+
+    pub fn forwarder_chain(&self) -> u32 {
+        self.forwarder_chain
+    }
+
+    pub fn name_rva(&self) -> u32 {
+        self.name_rva
+    }
+
+    pub fn dll_name(&self) -> &str {
+        &self.dll_name
+    }
+
+    /// RVA of the import address table
+    pub fn first_thunk(&self) -> u32 {
+        self.first_thunk
+    }
+
+    pub fn imports(&self) -> &[Import] {
+        &self.imports
+    }
+
+    /// Fields only a delay-load descriptor has, `None` for the import directory
+    pub fn delay_load(&self) -> Option<&DelayLoad> {
+        self.delay_load.as_ref()
+    }
+
+    /// Whether the DLL is loaded on the first call into it rather than with the image
+    pub fn is_delay_loaded(&self) -> bool {
+        self.delay_load.is_some()
+    }
+}
+
+/// Fields of an `IMAGE_DELAYLOAD_DESCRIPTOR` that an import descriptor has no
+/// counterpart for.
+///
+/// Addresses are given as RVAs even for descriptors that store virtual addresses, see
+/// [`DLATTR_RVA`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DelayLoad {
+    attributes: u32,
+    module_handle_rva: u32,
+    bound_iat_rva: u32,
+    unload_iat_rva: u32,
+}
+
+impl DelayLoad {
+    pub fn attributes(&self) -> u32 {
+        self.attributes
+    }
+
+    /// Whether the descriptor stores RVAs rather than virtual addresses
+    pub fn is_rva_based(&self) -> bool {
+        self.attributes & DLATTR_RVA != 0
+    }
+
+    /// RVA of the variable the delay-load helper stores the module handle in
+    pub fn module_handle_rva(&self) -> u32 {
+        self.module_handle_rva
+    }
+
+    /// RVA of the bound import address table, zero unless the image is bound
+    pub fn bound_iat_rva(&self) -> u32 {
+        self.bound_iat_rva
+    }
+
+    /// RVA of the copy of the import address table restored when the DLL is unloaded,
+    /// zero if it cannot be
+    pub fn unload_iat_rva(&self) -> u32 {
+        self.unload_iat_rva
+    }
+}
+
+/// Problem [`PortExe::import_table`] worked around.
+///
+/// The walk keeps whatever it could read before the problem, so hostile tables still
+/// yield their well-formed part.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ImportWarning {
+    /// Descriptor `index` is not backed by file data; the table ends before it
+    DescriptorOutsideImage { index: u32 },
+    /// [`MAX_IMPORT_DESCRIPTORS`] were read without reaching the null descriptor
+    TooManyDescriptors,
+    /// [`MAX_IMPORTS_PER_DLL`] thunks were read without reaching the null thunk
+    TooManyThunks { dll: String },
+    /// [`MAX_IMPORTS`] functions were read; the remaining descriptors were skipped
+    TooManyImports,
+    /// The thunk array runs out of file data before its null thunk
+    ThunksOutsideImage { dll: String },
+    /// PE32+ import by name with reserved bits set; the low 31 bits were used
+    InvalidThunk { dll: String, value: u64 },
+    /// Hint/name entry or DLL name not backed by file data
+    NameOutsideImage { rva: u32 },
+    /// Name without a terminator within [`MAX_IMPORT_NAME_LENGTH`] bytes or the file;
+    /// the name was truncated
+    UnterminatedName { rva: u32 },
+}
+
+impl fmt::Display for ImportWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DescriptorOutsideImage { index } => {
+                write!(f, "import descriptor {} is outside the image", index)
+            }
+            Self::TooManyDescriptors => f.write_str("too many import descriptors"),
+            Self::TooManyThunks { dll } => write!(f, "too many imports from {}", dll),
+            Self::TooManyImports => f.write_str("too many imports"),
+            Self::ThunksOutsideImage { dll } => {
+                write!(f, "thunks of {} run outside the image", dll)
+            }
+            Self::InvalidThunk { dll, value } => {
+                write!(f, "thunk {:#x} of {} has reserved bits set", value, dll)
+            }
+            Self::NameOutsideImage { rva } => {
+                write!(f, "name at {:#x} is outside the image", rva)
+            }
+            Self::UnterminatedName { rva } => {
+                write!(f, "name at {:#x} is not terminated", rva)
+            }
+        }
+    }
+}
+
+/// Result of [`PortExe::import_table`] and [`PortExe::delay_import_table`]
+#[derive(Debug, Clone, Default)]
+pub struct ImportTable {
+    descriptors: Vec<ImportDescriptor>,
+    warnings: Vec<ImportWarning>,
+}
+
+impl ImportTable {
+    pub fn descriptors(&self) -> &[ImportDescriptor] {
+        &self.descriptors
+    }
+
+    pub fn into_descriptors(self) -> Vec<ImportDescriptor> {
+        self.descriptors
+    }
+
+    pub fn warnings(&self) -> &[ImportWarning] {
+        &self.warnings
+    }
+}
+
+impl PortExe {
+    /// Lists the DLLs named by the import directory and the functions imported from each.
+    ///
+    /// Names are read from the import lookup table, or from the import address table
+    /// when the image has none. See [`PortExe::import_table`] for malformed tables.
+    pub fn imports(&self) -> io::Result<Vec<ImportDescriptor>> {
+        Ok(self.import_table()?.descriptors)
+    }
+
+    /// Lists the DLLs named by the delay-load import directory and the functions
+    /// imported from each. See [`PortExe::delay_import_table`] for malformed tables.
+    pub fn delay_imports(&self) -> io::Result<Vec<ImportDescriptor>> {
+        Ok(self.delay_import_table()?.descriptors)
+    }
+
+    /// Imports of the import directory followed by those of the delay-load import
+    /// directory, telling them apart by [`ImportDescriptor::is_delay_loaded`]
+    pub fn all_imports(&self) -> io::Result<Vec<ImportDescriptor>> {
+        let mut descriptors = self.imports()?;
+        descriptors.extend(self.delay_imports()?);
+        Ok(descriptors)
+    }
+
+    /// Walks the import directory, truncating where it stops making sense.
+    ///
+    /// Descriptors and thunks outside the file end the table or the DLL, names are
+    /// bounded by [`MAX_IMPORT_NAME_LENGTH`] and the counts by [`MAX_IMPORT_DESCRIPTORS`],
+    /// [`MAX_IMPORTS_PER_DLL`] and [`MAX_IMPORTS`]. Each of these is reported as an
+    /// [`ImportWarning`] instead of failing the whole table.
+    pub fn import_table(&self) -> io::Result<ImportTable> {
+        self.descriptor_table(IMAGE_DIRECTORY_ENTRY_IMPORT)
+    }
+
+    /// Walks the delay-load import directory with the limits of
+    /// [`PortExe::import_table`].
+    ///
+    /// Functions are named by the import name table; the import address table holds
+    /// pointers to the delay-load thunks until the first call. Descriptors without an
+    /// import name table list no functions.
+    pub fn delay_import_table(&self) -> io::Result<ImportTable> {
+        self.descriptor_table(IMAGE_DIRECTORY_ENTRY_DELAY_IMPORT)
+    }
+
+    fn descriptor_table(&self, directory_index: usize) -> io::Result<ImportTable> {
+        let mut table = ImportTable::default();
+        let header = match self.optional_header() {
+            Some(header) => header,
+            None => return Ok(table),
+        };
+        let directory = match header.data_directory(directory_index) {
+            Some(directory) => directory,
+            None => return Ok(table),
+        };
+        let rva = directory.virtual_address().into_value();
+        if rva == 0 || directory.size().into_value() == 0 {
+            return Ok(table);
+        }
+        let delay_load = directory_index == IMAGE_DIRECTORY_ENTRY_DELAY_IMPORT;
+        let descriptor_size = if delay_load {
+            IMAGE_SIZEOF_DELAYLOAD_DESCRIPTOR
+        } else {
+            IMAGE_SIZEOF_IMPORT_DESCRIPTOR
+        };
+        let pe64 = header.image_type().is_x64();
+
+        let mut budget = MAX_IMPORTS;
+        let mut terminated = false;
+        for index in 0..MAX_IMPORT_DESCRIPTORS {
+            let data = match rva
+                .checked_add(index * descriptor_size)
+                .and_then(|descriptor_rva| self.data_at_rva(descriptor_rva, descriptor_size))
+            {
+                Some(data) => data,
+                None => {
+                    table
+                        .warnings
+                        .push(ImportWarning::DescriptorOutsideImage { index });
+                    terminated = true;
+                    break;
+                }
+            };
+            if data.iter().all(|&b| b == 0) {
+                terminated = true;
+                break;
+            }
+            if budget == 0 {
+                // Already reported if the previous DLL ran out mid-table
+                if table.warnings.last() != Some(&ImportWarning::TooManyImports) {
+                    table.warnings.push(ImportWarning::TooManyImports);
+                }
+                terminated = true;
+                break;
+            }
+            let descriptor = if delay_load {
+                self.delay_import_descriptor(
+                    data,
+                    header.image_base(),
+                    pe64,
+                    &mut budget,
+                    &mut table.warnings,
+                )
+            } else {
+                self.import_descriptor(data, pe64, &mut budget, &mut table.warnings)
+            };
+            table.descriptors.push(descriptor);
+        }
+        if !terminated {
+            table.warnings.push(ImportWarning::TooManyDescriptors);
+        }
+        Ok(table)
+    }
+
+    fn import_descriptor(
+        &self,
+        data: &[u8],
+        pe64: bool,
+        budget: &mut u32,
+        warnings: &mut Vec<ImportWarning>,
+    ) -> ImportDescriptor {
+        let field = |offset| read_u32(data, offset).unwrap_or(0);
+        let original_first_thunk = field(0);
+        let name_rva = field(12);
+        let first_thunk = field(16);
+        let lookup_table = match original_first_thunk {
+            0 => first_thunk,
+            rva => rva,
+        };
+        let dll_name = self.import_dll_name(name_rva, warnings);
+        let imports = self.import_thunks(
+            &dll_name,
+            (lookup_table, first_thunk),
+            (pe64, 0),
+            budget,
+            warnings,
+        );
+        ImportDescriptor {
+            original_first_thunk,
+            time_date_stamp: field(4),
+            forwarder_chain: field(8),
+            name_rva,
+            dll_name,
+            first_thunk,
+            imports,
+            delay_load: None,
+        }
+    }
+
+    fn delay_import_descriptor(
+        &self,
+        data: &[u8],
+        image_base: u64,
+        pe64: bool,
+        budget: &mut u32,
+        warnings: &mut Vec<ImportWarning>,
+    ) -> ImportDescriptor {
+        let attributes = read_u32(data, 0).unwrap_or(0);
+        // Visual C++ 6 descriptors, and the names they point to, hold virtual addresses
+        let base = if attributes & DLATTR_RVA != 0 {
+            0
+        } else {
+            image_base
+        };
+        let field = |offset| match read_u32(data, offset).unwrap_or(0) {
+            0 => 0,
+            address => u64::from(address).wrapping_sub(base) as u32,
+        };
+        let name_rva = field(4);
+        let first_thunk = field(12);
+        let name_table = field(16);
+        let dll_name = self.import_dll_name(name_rva, warnings);
+        let imports = match name_table {
+            0 => Vec::new(),
+            _ => self.import_thunks(
+                &dll_name,
+                (name_table, first_thunk),
+                (pe64, base),
+                budget,
+                warnings,
+            ),
+        };
+        ImportDescriptor {
+            original_first_thunk: name_table,
+            time_date_stamp: read_u32(data, 28).unwrap_or(0),
+            forwarder_chain: 0,
+            name_rva,
+            dll_name,
+            first_thunk,
+            imports,
+            delay_load: Some(DelayLoad {
+                attributes,
+                module_handle_rva: field(8),
+                bound_iat_rva: field(20),
+                unload_iat_rva: field(24),
+            }),
+        }
+    }
+
+    fn import_dll_name(&self, rva: u32, warnings: &mut Vec<ImportWarning>) -> String {
+        self.import_name(rva, warnings)
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Walks a zero-terminated thunk array, stopping early where it leaves the file or
+    /// `budget` runs out. Import-by-name thunks are virtual addresses relative to `base`,
+    /// zero but for old delay-load descriptors.
+    fn import_thunks(
+        &self,
+        dll: &str,
+        (lookup_table, first_thunk): (u32, u32),
+        (pe64, base): (bool, u64),
+        budget: &mut u32,
+        warnings: &mut Vec<ImportWarning>,
+    ) -> Vec<Import> {
+        let (thunk_size, ordinal_flag) = if pe64 {
+            (8, IMAGE_ORDINAL_FLAG64)
+        } else {
+            (4, IMAGE_ORDINAL_FLAG32)
+        };
+        let mut imports = Vec::new();
+        for index in 0..MAX_IMPORTS_PER_DLL {
+            let thunk = lookup_table
+                .checked_add(index * thunk_size)
+                .and_then(|rva| self.data_at_rva(rva, thunk_size))
+                .and_then(|data| {
+                    if pe64 {
+                        read_u64(data, 0)
+                    } else {
+                        read_u32(data, 0).map(u64::from)
+                    }
+                });
+            let thunk = match thunk {
+                Some(0) => return imports,
+                Some(thunk) => thunk,
+                None => {
+                    warnings.push(ImportWarning::ThunksOutsideImage {
+                        dll: dll.to_string(),
+                    });
+                    return imports;
+                }
+            };
+            if *budget == 0 {
+                warnings.push(ImportWarning::TooManyImports);
+                return imports;
+            }
+            *budget -= 1;
+            let iat_rva = first_thunk.wrapping_add(index * thunk_size);
+            let import = if thunk & ordinal_flag != 0 {
+                Import {
+                    name: None,
+                    hint_name_rva: None,
+                    hint: 0,
+                    ordinal: Some(thunk as u16),
+                    iat_rva,
+                }
+            } else {
+                let thunk = thunk.wrapping_sub(base);
+                if thunk > u64::from(HINT_NAME_RVA_MASK) {
+                    warnings.push(ImportWarning::InvalidThunk {
+                        dll: dll.to_string(),
+                        value: thunk,
+                    });
+                }
+                let hint_name_rva = thunk as u32 & HINT_NAME_RVA_MASK;
+                let hint = self
+                    .data_at_rva(hint_name_rva, 2)
+                    .and_then(|data| read_u16(data, 0));
+                let name = match hint {
+                    Some(_) => self.import_name(hint_name_rva.wrapping_add(2), warnings),
+                    None => {
+                        warnings.push(ImportWarning::NameOutsideImage { rva: hint_name_rva });
+                        None
+                    }
+                };
+                Import {
+                    name,
+                    hint_name_rva: Some(hint_name_rva),
+                    hint: hint.unwrap_or(0),
+                    ordinal: None,
+                    iat_rva,
+                }
+            };
+            imports.push(import);
+        }
+        warnings.push(ImportWarning::TooManyThunks {
+            dll: dll.to_string(),
+        });
+        imports
+    }
+
+    /// Reads a zero-terminated name of at most [`MAX_IMPORT_NAME_LENGTH`] bytes.
+    fn import_name(&self, rva: u32, warnings: &mut Vec<ImportWarning>) -> Option<PeString> {
+        let offset = match self.rva_to_offset(rva) {
+            Some(offset) => offset,
+            None => {
+                warnings.push(ImportWarning::NameOutsideImage { rva });
+                return None;
+            }
+        };
+        let data = &self.data()[offset..];
+        let data = &data[..data.len().min(MAX_IMPORT_NAME_LENGTH)];
+        let end = match data.iter().position(|&b| b == 0) {
+            Some(end) => end,
+            None => {
+                warnings.push(ImportWarning::UnterminatedName { rva });
+                data.len()
+            }
+        };
+        Some(PeString::from_bytes(&data[..end]))
+    }
+}
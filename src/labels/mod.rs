@@ -0,0 +1,311 @@
+use crate::export::ExportKind;
+use crate::port_exe::PortExe;
+use std::fmt::Write;
+use std::io;
+
+/// What a [`Label`] names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelKind {
+    /// Data, such as an import address table slot or an exported variable
+    Data,
+    /// Start of a function, which disassemblers also create a function at
+    Function,
+}
+
+/// Name for an address of an image, applied by a debugger or disassembler script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    rva: u32,
+    name: String,
+    kind: LabelKind,
+}
+
+impl Label {
+    /// Label for data at `rva`
+    pub fn new(rva: u32, name: &str) -> Self {
+        Self {
+            rva,
+            name: name.to_string(),
+            kind: LabelKind::Data,
+        }
+    }
+
+    /// Label for a function starting at `rva`
+    pub fn function(rva: u32, name: &str) -> Self {
+        Self {
+            kind: LabelKind::Function,
+            ..Self::new(rva, name)
+        }
+    }
+
+    pub fn rva(&self) -> u32 {
+        self.rva
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> LabelKind {
+        self.kind
+    }
+}
+
+/// Section of an image, by the name pexp decoded for it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionRange {
+    name: String,
+    rva: u32,
+    size: u32,
+}
+
+impl SectionRange {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn rva(&self) -> u32 {
+        self.rva
+    }
+
+    /// Virtual size of the section
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+}
+
+/// Script dialect of [`label_script`] and [`Annotations::script`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LabelFormat {
+    /// x64dbg script of `lblset` commands at `module:$rva` addresses
+    X64dbg,
+    /// WinDbg commands defining an `aS /x` alias per label, relative to the module
+    Windbg,
+    /// Ghidra Python script naming memory blocks, functions and labels
+    Ghidra,
+    /// IDAPython script naming segments, functions and addresses
+    Ida,
+}
+
+/// Everything [`PortExe::annotations`] knows how to name in a reverse engineering database
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Annotations {
+    sections: Vec<SectionRange>,
+    labels: Vec<Label>,
+}
+
+impl Annotations {
+    pub fn sections(&self) -> &[SectionRange] {
+        &self.sections
+    }
+
+    /// Exports, then import address table slots, then TLS callbacks
+    pub fn labels(&self) -> &[Label] {
+        &self.labels
+    }
+
+    /// Renders the annotations for the image loaded as `module`, a file name such as
+    /// `app.exe`. Debugger formats leave the sections to the debugger.
+    pub fn script(&self, module: &str, format: LabelFormat) -> String {
+        match format {
+            LabelFormat::X64dbg | LabelFormat::Windbg => label_script(module, &self.labels, format),
+            LabelFormat::Ghidra => self.ghidra_script(module),
+            LabelFormat::Ida => self.ida_script(module),
+        }
+    }
+
+    fn ghidra_script(&self, module: &str) -> String {
+        let mut out = format!("# Annotations for {}\n", module);
+        out.push_str("from ghidra.program.model.symbol import SourceType\n\n");
+        out.push_str("base = currentProgram.getImageBase()\n");
+        self.python_tables(&mut out);
+        out.push_str(
+            "for rva, name in sections:
+    block = getMemoryBlock(base.add(rva))
+    if block is not None:
+        block.setName(name)
+for rva, name in functions:
+    function = getFunctionAt(base.add(rva))
+    if function is None:
+        createFunction(base.add(rva), name)
+    else:
+        function.setName(name, SourceType.IMPORTED)
+for rva, name in labels:
+    createLabel(base.add(rva), name, True, SourceType.IMPORTED)
+",
+        );
+        out
+    }
+
+    fn ida_script(&self, module: &str) -> String {
+        let mut out = format!("# Annotations for {}\n", module);
+        out.push_str("import ida_funcs\nimport ida_nalt\nimport ida_name\nimport ida_segment\n\n");
+        out.push_str("base = ida_nalt.get_imagebase()\n");
+        out.push_str("flags = ida_name.SN_NOCHECK | ida_name.SN_NOWARN\n");
+        self.python_tables(&mut out);
+        out.push_str(
+            "for rva, name in sections:
+    segment = ida_segment.getseg(base + rva)
+    if segment is not None:
+        ida_segment.set_segm_name(segment, name)
+for rva, name in functions:
+    ida_funcs.add_func(base + rva)
+    ida_name.set_name(base + rva, name, flags)
+for rva, name in labels:
+    ida_name.set_name(base + rva, name, flags)
+",
+        );
+        out
+    }
+
+    /// `sections`, `functions` and `labels` lists of `(rva, name)` tuples
+    fn python_tables(&self, out: &mut String) {
+        let sections = self
+            .sections
+            .iter()
+            .map(|section| (section.rva, &section.name[..]));
+        python_list(out, "sections", sections);
+        let functions = self
+            .labels
+            .iter()
+            .filter(|label| label.kind == LabelKind::Function)
+            .map(|label| (label.rva, &label.name[..]));
+        python_list(out, "functions", functions);
+        let labels = self
+            .labels
+            .iter()
+            .filter(|label| label.kind == LabelKind::Data)
+            .map(|label| (label.rva, &label.name[..]));
+        python_list(out, "labels", labels);
+    }
+}
+
+impl PortExe {
+    /// Collects section names, exports, import address table slots and TLS callbacks
+    /// for [`Annotations::script`].
+    ///
+    /// Exports of code are labelled as functions, forwarders are skipped and exports
+    /// without a name are called `ordinal_<n>`.
+    pub fn annotations(&self) -> io::Result<Annotations> {
+        let sections = self
+            .section_headers()
+            .iter()
+            .map(|section| SectionRange {
+                name: self.section_name(section).to_string_lossy().into_owned(),
+                rva: section.virtual_address().into_value(),
+                size: section.virtual_size().into_value(),
+            })
+            .collect();
+
+        let mut labels = Vec::new();
+        for export in self.exports()? {
+            if export.forwarder().is_some() {
+                continue;
+            }
+            let name = match export.name() {
+                Some(name) => name.to_string_lossy().into_owned(),
+                None => format!("ordinal_{}", export.ordinal()),
+            };
+            labels.push(if export.kind() == ExportKind::Code {
+                Label::function(export.rva(), &name)
+            } else {
+                Label::new(export.rva(), &name)
+            });
+        }
+        labels.extend(self.iat_map()?.iter().map(|entry| entry.label()));
+        let image_base = self
+            .optional_header()
+            .map_or(0, |header| header.image_base());
+        for (index, callback) in self.tls_callbacks().into_iter().enumerate() {
+            if let Some(rva) = callback
+                .checked_sub(image_base)
+                .and_then(|rva| u32::try_from(rva).ok())
+            {
+                labels.push(Label::function(rva, &format!("tls_callback_{}", index)));
+            }
+        }
+        Ok(Annotations { sections, labels })
+    }
+}
+
+/// Renders `labels` for the image loaded as `module`, a file name such as `app.exe`.
+///
+/// Addresses are relative to the module, so the script applies wherever ASLR loaded it.
+pub fn label_script(module: &str, labels: &[Label], format: LabelFormat) -> String {
+    let mut out = String::new();
+    match format {
+        LabelFormat::X64dbg => {
+            let _ = writeln!(out, "// Labels for {}", module);
+            for label in labels {
+                let _ = writeln!(
+                    out,
+                    "lblset {}:${:x}, \"{}\"",
+                    module,
+                    label.rva,
+                    label.name.replace('"', "")
+                );
+            }
+        }
+        LabelFormat::Windbg => {
+            // WinDbg names modules by their file name without the extension
+            let stem = module.rsplit_once('.').map_or(module, |(stem, _)| stem);
+            let _ = writeln!(out, "$$ Labels for {}", module);
+            for label in labels {
+                let _ = writeln!(
+                    out,
+                    "aS /x {} {}+0x{:x}",
+                    identifier(&label.name),
+                    stem,
+                    label.rva
+                );
+            }
+        }
+        LabelFormat::Ghidra | LabelFormat::Ida => {
+            let annotations = Annotations {
+                sections: Vec::new(),
+                labels: labels.to_vec(),
+            };
+            out = annotations.script(module, format);
+        }
+    }
+    out
+}
+
+fn python_list<'a>(out: &mut String, name: &str, entries: impl Iterator<Item = (u32, &'a str)>) {
+    let _ = writeln!(out, "{} = [", name);
+    for (rva, value) in entries {
+        let _ = writeln!(out, "    (0x{:x}, {}),", rva, python_string(value));
+    }
+    out.push_str("]\n");
+}
+
+/// `value` as a double-quoted Python string literal, a `u""` one if it is not ASCII so
+/// that the Python 2 interpreter of Ghidra decodes the escapes too
+fn python_string(value: &str) -> String {
+    let mut literal = String::from(if value.is_ascii() { "\"" } else { "u\"" });
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                literal.push('\\');
+                literal.push(c);
+            }
+            ' '..='~' => literal.push(c),
+            c if (c as u32) < 0x10000 => {
+                let _ = write!(literal, "\\u{:04x}", c as u32);
+            }
+            c => {
+                let _ = write!(literal, "\\U{:08x}", c as u32);
+            }
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// `name` with everything but ASCII letters, digits and underscores replaced by `_`
+fn identifier(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
@@ -1,7 +1,125 @@
+//! Parser and analysis toolkit for PE images and COFF object files.
+//!
+//! The types needed to parse a file and inspect its headers are re-exported at the
+//! crate root; the analyses live in their own modules as extension methods on
+//! [`PortExe`]. Enums describing values defined by the PE format are
+//! `#[non_exhaustive]`, as new machines, subsystems and directories keep appearing.
+//!
+//! No features are enabled by default, so the parser itself only depends on `std`:
+//!
+//! - `chrono`: `FileHeaderWrapper::timestamp` decodes the header timestamp
+//! - `compression`: deflate-based compression ratio probe in [`analysis`]
+//! - `containers`: ZIP and cabinet archives as `container::DataSource`s
+//! - `disassembler`: entry point wrapper unwrapping in `entry`
+//! - `icon-png`: PNG-format icons in the perceptual hashes of [`icon`]
+//! - `regex-search`: regular expression name matching in [`search`]
+//! - `testkit`: synthetic image builder and header field mutator in `testing`
+//!
+//! Everything returned as a list has a fixed order that depends only on the input, so
+//! output can be diffed and snapshotted across runs:
+//!
+//! - sections, relocation blocks and runtime functions in table order
+//! - import descriptors in directory order, their functions in thunk order
+//! - exports in export address table order
+//! - resources depth first, by numeric ID and then by name at each level
+//! - files the command line tool finds in a directory sorted by path, and the files
+//!   of a container in the order it stores them
+
 use std::fmt;
+use std::io;
+use std::io::Read;
 
+pub mod address;
+pub mod analysis;
+pub mod appcontainer;
+pub(crate) mod arith;
+pub mod artifacts;
+pub mod audit;
+pub mod bindings;
+pub mod build_id;
+pub mod certificate;
+pub mod checksec;
+pub mod clr;
+pub mod codegen;
+pub mod codeview;
+pub mod comdat;
+pub mod config;
+#[cfg(feature = "containers")]
+pub mod container;
+pub mod crosscheck;
+pub mod debug;
+pub mod decompress;
+pub mod dedup;
+pub mod def;
+pub mod demangle;
+pub mod diff;
+pub mod digest;
+pub mod dwarf;
+pub mod edit;
+pub mod editor;
+pub(crate) mod endian;
+#[cfg(feature = "disassembler")]
+pub mod entry;
+pub mod exception;
+pub mod export;
+pub mod expression;
 pub mod file_header;
+pub mod hook;
+pub mod iat;
+pub mod icon;
+pub mod import;
+pub mod labels;
+pub mod load_config;
+pub mod load_order;
+pub mod loader;
+pub mod localization;
+pub mod manifest;
+pub mod messages;
+pub mod min_os;
+pub mod mingw;
+pub mod minidump;
 pub mod optional_header;
+pub mod ordinals;
+pub mod overlay;
+pub mod patch;
+pub mod pe_string;
+pub mod plugin;
+pub mod port_exe;
+pub mod provenance;
+pub mod reloc;
+pub mod repro;
+pub mod resource;
+pub mod rich;
+pub mod search;
+pub mod section_header;
+pub mod signing;
+pub mod similarity;
+pub mod slack;
+pub mod split;
+pub mod strip;
+pub mod symbol;
+#[cfg(feature = "testkit")]
+pub mod testing;
+pub mod timeline;
+pub mod tls;
+pub mod truncation;
+pub mod validation;
+pub mod version;
+pub mod wow64;
+
+pub(crate) use endian::{read_u16, read_u32, read_u64};
+
+pub use checksec::{Checksec, Protection};
+pub use endian::Guid;
+pub use file_header::{Characteristics, FileHeaderWrapper, Machine};
+pub use optional_header::{
+    DataDirectoryType, DataDirectoryWrapper, DllCharacteristics, OptionalHeaderWrapper,
+    WindowsSubsystem,
+};
+pub use pe_string::PeString;
+pub use port_exe::PortExe;
+pub use section_header::{SectionCharacteristics, SectionHeaderWrapper};
+pub use validation::{Finding, Severity, ValidationMode};
 
 #[derive(Debug)]
 pub struct StructField<T, const N: usize> {
@@ -11,12 +129,45 @@ pub struct StructField<T, const N: usize> {
     value: T,
 }
 
+impl<T, const N: usize> StructField<T, N> {
+    /// Offset of the field from the start of the file
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Human-readable field name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Bytes of the field as stored in the file
+    pub fn raw_bytes(&self) -> [u8; N] {
+        self.raw_bytes
+    }
+
+    /// Decoded value of the field
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// Consumes the field and returns the decoded value
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+
 impl fmt::Display for StructField<u16, 2> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "{}\t{}\t{:?}\t{}", self.offset, self.name, self.raw_bytes, self.value)
+        writeln!(
+            f,
+            "{}\t{}\t{:?}\t{}",
+            self.offset, self.name, self.raw_bytes, self.value
+        )
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum PEType {
     Object,
     Image,
@@ -39,6 +190,34 @@ impl PEType {
     }
 }
 
+/// Arrangement of the sections in the buffer a [`PortExe`] was parsed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ImageLayout {
+    /// Sections at their raw data pointers, as stored on disk
+    File,
+    /// Sections at their virtual addresses, as mapped by the loader or dumped from memory
+    Mapped,
+}
+
+impl ImageLayout {
+    /// Returns `true` if the image layout is [`File`].
+    ///
+    /// [`File`]: ImageLayout::File
+    pub fn is_file(&self) -> bool {
+        matches!(self, Self::File)
+    }
+
+    /// Returns `true` if the image layout is [`Mapped`].
+    ///
+    /// [`Mapped`]: ImageLayout::Mapped
+    pub fn is_mapped(&self) -> bool {
+        matches!(self, Self::Mapped)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ImageType {
     X32,
     X64,
@@ -67,3 +246,92 @@ impl ImageType {
         matches!(self, Self::ROM)
     }
 }
+
+/// Reads `size` bytes with a single call, so that fixed-size fields can be sliced
+/// from the buffer instead of being read from `reader` one at a time.
+pub(crate) fn read_block<R: Read>(reader: &mut R, size: usize) -> io::Result<Vec<u8>> {
+    let mut block = vec![0u8; size];
+    reader.read_exact(&mut block)?;
+    Ok(block)
+}
+
+/// Case-insensitive key of a DLL name, with any `.dll` extension removed
+pub(crate) fn module_key(name: &str) -> String {
+    let name = name.to_ascii_lowercase();
+    match name.strip_suffix(".dll") {
+        Some(stem) => stem.to_string(),
+        None => name,
+    }
+}
+
+/// Copies `bytes` into `data` at `offset`, dropping whatever does not fit.
+pub(crate) fn write_at(data: &mut [u8], offset: u64, bytes: &[u8]) {
+    let start = (offset as usize).min(data.len());
+    let end = start.saturating_add(bytes.len()).min(data.len());
+    data[start..end].copy_from_slice(&bytes[..end - start]);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::file_header::read_file_header;
+    use crate::section_header::read_section_headers;
+    use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+    /// Reader that counts the calls made to it, like an unbuffered file would
+    struct CountingReader {
+        inner: Cursor<Vec<u8>>,
+        reads: usize,
+    }
+
+    impl Read for CountingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads += 1;
+            self.inner.read(buf)
+        }
+    }
+
+    impl Seek for CountingReader {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    fn reader(data: Vec<u8>) -> CountingReader {
+        CountingReader {
+            inner: Cursor::new(data),
+            reads: 0,
+        }
+    }
+
+    #[test]
+    fn headers_are_read_with_one_call_each() {
+        let mut data = vec![0u8; 8 + 20 + 3 * 40];
+        data[8..10].copy_from_slice(&0x8664u16.to_le_bytes());
+        data[10..12].copy_from_slice(&3u16.to_le_bytes());
+        for (index, name) in [b".text", b".data", b".bss\0"].iter().enumerate() {
+            let header = 28 + index * 40;
+            data[header..header + 5].copy_from_slice(*name);
+            data[header + 36..header + 40].copy_from_slice(&(index as u32 + 1).to_le_bytes());
+        }
+
+        let mut file = reader(data.clone());
+        let file_header = read_file_header(&mut file, 8).unwrap();
+        assert_eq!(file_header.number_of_sections().into_value(), 3);
+        assert_eq!(file.reads, 1);
+
+        let mut file = reader(data.clone());
+        let sections = read_section_headers(&mut file, 28, 3).unwrap();
+        assert_eq!(file.reads, 1);
+        assert_eq!(sections[1].name().into_value().to_string(), ".data");
+        let characteristics: Vec<_> = sections
+            .iter()
+            .map(|section| section.characteristics().raw_bytes()[0])
+            .collect();
+        assert_eq!(characteristics, [1, 2, 3]);
+
+        // A table cut short fails as a whole rather than yielding the headers that fit
+        let mut file = reader(data[..data.len() - 1].to_vec());
+        let error = read_section_headers(&mut file, 28, 3).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,282 @@
+use crate::arith::va_to_rva;
+use crate::optional_header::IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG;
+use crate::port_exe::PortExe;
+use crate::{read_u16, read_u32};
+use std::io;
+
+/// Module performs control flow integrity checks using system-supplied support
+pub const IMAGE_GUARD_CF_INSTRUMENTED: u32 = 0x00000100;
+/// Module performs control flow and write integrity checks
+pub const IMAGE_GUARD_CFW_INSTRUMENTED: u32 = 0x00000200;
+/// Module contains valid control flow target metadata
+pub const IMAGE_GUARD_CF_FUNCTION_TABLE_PRESENT: u32 = 0x00000400;
+/// Module does not make use of the /GS security cookie
+pub const IMAGE_GUARD_SECURITY_COOKIE_UNUSED: u32 = 0x00000800;
+/// Module supports read only delay load IAT
+pub const IMAGE_GUARD_PROTECT_DELAYLOAD_IAT: u32 = 0x00001000;
+/// Delayload import table in its own .didat section
+pub const IMAGE_GUARD_DELAYLOAD_IAT_IN_ITS_OWN_SECTION: u32 = 0x00002000;
+/// Module contains suppressed export information
+pub const IMAGE_GUARD_CF_EXPORT_SUPPRESSION_INFO_PRESENT: u32 = 0x00004000;
+/// Module enables suppression of exports
+pub const IMAGE_GUARD_CF_ENABLE_EXPORT_SUPPRESSION: u32 = 0x00008000;
+/// Module contains longjmp target information
+pub const IMAGE_GUARD_CF_LONGJUMP_TABLE_PRESENT: u32 = 0x00010000;
+/// Module contains return flow instrumentation and metadata
+pub const IMAGE_GUARD_RF_INSTRUMENTED: u32 = 0x00020000;
+/// Module requests that the OS enable return flow protection
+pub const IMAGE_GUARD_RF_ENABLE: u32 = 0x00040000;
+/// Module requests that the OS enable return flow protection in strict mode
+pub const IMAGE_GUARD_RF_STRICT: u32 = 0x00080000;
+/// Module was built with retpoline support
+pub const IMAGE_GUARD_RETPOLINE_PRESENT: u32 = 0x00100000;
+/// Module contains EH continuation target information
+pub const IMAGE_GUARD_EH_CONTINUATION_TABLE_PRESENT: u32 = 0x00400000;
+/// Module was built with eXtended Flow Guard
+pub const IMAGE_GUARD_XFG_ENABLED: u32 = 0x00800000;
+/// Module has CastGuard instrumentation present
+pub const IMAGE_GUARD_CASTGUARD_PRESENT: u32 = 0x01000000;
+/// Module has Guarded Memcpy instrumentation present
+pub const IMAGE_GUARD_MEMCPY_PRESENT: u32 = 0x02000000;
+/// Stride of Guard CF function table entries encoded in the top four bits
+pub const IMAGE_GUARD_CF_FUNCTION_TABLE_SIZE_MASK: u32 = 0xF0000000;
+pub const IMAGE_GUARD_CF_FUNCTION_TABLE_SIZE_SHIFT: u32 = 28;
+
+/// `IMAGE_LOAD_CONFIG_DIRECTORY32` / `IMAGE_LOAD_CONFIG_DIRECTORY64`
+///
+/// The structure has grown with every Windows release and is versioned by its
+/// `Size` field, so each getter returns `None` for fields the image does not contain.
+#[derive(Debug, Clone)]
+pub struct LoadConfig {
+    pe64: bool,
+    data: Vec<u8>,
+}
+
+impl LoadConfig {
+    /// Size of the structure as recorded in its first field
+    pub fn size(&self) -> u32 {
+        read_u32(&self.data, 0).unwrap_or(0)
+    }
+
+    pub fn time_date_stamp(&self) -> Option<u32> {
+        self.dword(4, 4)
+    }
+
+    pub fn major_version(&self) -> Option<u16> {
+        read_u16(&self.data, 8)
+    }
+
+    pub fn minor_version(&self) -> Option<u16> {
+        read_u16(&self.data, 10)
+    }
+
+    pub fn global_flags_clear(&self) -> Option<u32> {
+        self.dword(12, 12)
+    }
+
+    pub fn global_flags_set(&self) -> Option<u32> {
+        self.dword(16, 16)
+    }
+
+    pub fn dependent_load_flags(&self) -> Option<u16> {
+        read_u16(&self.data, if self.pe64 { 78 } else { 54 })
+    }
+
+    /// VA of the /GS security cookie
+    pub fn security_cookie(&self) -> Option<u64> {
+        self.pointer(60, 88)
+    }
+
+    /// VA of the sorted table of safe exception handler RVAs (x86 only)
+    pub fn se_handler_table(&self) -> Option<u64> {
+        self.pointer(64, 96)
+    }
+
+    pub fn se_handler_count(&self) -> Option<u64> {
+        self.pointer(68, 104)
+    }
+
+    pub fn guard_cf_check_function_pointer(&self) -> Option<u64> {
+        self.pointer(72, 112)
+    }
+
+    pub fn guard_cf_dispatch_function_pointer(&self) -> Option<u64> {
+        self.pointer(76, 120)
+    }
+
+    /// VA of the table of valid indirect call targets
+    pub fn guard_cf_function_table(&self) -> Option<u64> {
+        self.pointer(80, 128)
+    }
+
+    pub fn guard_cf_function_count(&self) -> Option<u64> {
+        self.pointer(84, 136)
+    }
+
+    /// `IMAGE_GUARD_*` flags
+    pub fn guard_flags(&self) -> Option<u32> {
+        self.dword(88, 144)
+    }
+
+    pub fn guard_address_taken_iat_entry_table(&self) -> Option<u64> {
+        self.pointer(104, 160)
+    }
+
+    pub fn guard_address_taken_iat_entry_count(&self) -> Option<u64> {
+        self.pointer(108, 168)
+    }
+
+    pub fn guard_long_jump_target_table(&self) -> Option<u64> {
+        self.pointer(112, 176)
+    }
+
+    pub fn guard_long_jump_target_count(&self) -> Option<u64> {
+        self.pointer(116, 184)
+    }
+
+    pub fn dynamic_value_reloc_table(&self) -> Option<u64> {
+        self.pointer(120, 192)
+    }
+
+    /// VA of the hybrid (CHPE / ARM64EC) metadata
+    pub fn chpe_metadata_pointer(&self) -> Option<u64> {
+        self.pointer(124, 200)
+    }
+
+    pub fn volatile_metadata_pointer(&self) -> Option<u64> {
+        self.pointer(160, 256)
+    }
+
+    /// VA of the table of valid exception handling continuation targets
+    pub fn guard_eh_continuation_table(&self) -> Option<u64> {
+        self.pointer(164, 264)
+    }
+
+    pub fn guard_eh_continuation_count(&self) -> Option<u64> {
+        self.pointer(168, 272)
+    }
+
+    pub fn guard_xfg_check_function_pointer(&self) -> Option<u64> {
+        self.pointer(172, 280)
+    }
+
+    pub fn guard_xfg_dispatch_function_pointer(&self) -> Option<u64> {
+        self.pointer(176, 288)
+    }
+
+    pub fn guard_xfg_table_dispatch_function_pointer(&self) -> Option<u64> {
+        self.pointer(180, 296)
+    }
+
+    fn dword(&self, offset32: usize, offset64: usize) -> Option<u32> {
+        read_u32(&self.data, if self.pe64 { offset64 } else { offset32 })
+    }
+
+    fn pointer(&self, offset32: usize, offset64: usize) -> Option<u64> {
+        if self.pe64 {
+            let low = read_u32(&self.data, offset64)?;
+            let high = read_u32(&self.data, offset64 + 4)?;
+            Some((high as u64) << 32 | low as u64)
+        } else {
+            read_u32(&self.data, offset32).map(u64::from)
+        }
+    }
+}
+
+impl PortExe {
+    /// Reads the load configuration directory, truncated to the size it declares.
+    pub fn load_config(&self) -> io::Result<Option<LoadConfig>> {
+        let header = match self.optional_header() {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let directory = match header.data_directory(IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG) {
+            Some(directory) => directory,
+            None => return Ok(None),
+        };
+        let rva = directory.virtual_address().into_value();
+        if rva == 0 || directory.size().into_value() == 0 {
+            return Ok(None);
+        }
+        let size = self
+            .data_at_rva(rva, 4)
+            .and_then(|data| read_u32(data, 0))
+            .ok_or_else(|| invalid_data("load config directory is not backed by file data"))?;
+        let data = self
+            .data_at_rva(rva, size)
+            .ok_or_else(|| invalid_data("truncated load config directory"))?;
+        Ok(Some(LoadConfig {
+            pe64: header.image_type().is_x64(),
+            data: data.to_vec(),
+        }))
+    }
+
+    /// RVAs of the valid indirect call targets listed in the Guard CF function table.
+    ///
+    /// Empty when the image has no load configuration or no table.
+    pub fn guard_cf_functions(&self) -> io::Result<Vec<u32>> {
+        let config = match self.load_config()? {
+            Some(config) => config,
+            None => return Ok(Vec::new()),
+        };
+        self.guard_table(
+            &config,
+            config.guard_cf_function_table(),
+            config.guard_cf_function_count(),
+            "Guard CF function",
+        )
+    }
+
+    /// RVAs of the valid exception handling continuation targets `/guard:ehcont` lists.
+    ///
+    /// Empty when the image has no load configuration or no table.
+    pub fn guard_eh_continuation_targets(&self) -> io::Result<Vec<u32>> {
+        let config = match self.load_config()? {
+            Some(config) => config,
+            None => return Ok(Vec::new()),
+        };
+        self.guard_table(
+            &config,
+            config.guard_eh_continuation_table(),
+            config.guard_eh_continuation_count(),
+            "EH continuation",
+        )
+    }
+
+    /// RVAs of a Guard table, whose entries carry as many extra bytes as the guard
+    /// flags say
+    fn guard_table(
+        &self,
+        config: &LoadConfig,
+        table: Option<u64>,
+        count: Option<u64>,
+        name: &str,
+    ) -> io::Result<Vec<u32>> {
+        let (table, count) = match (table, count) {
+            (Some(table), Some(count)) if table != 0 && count != 0 => (table, count),
+            _ => return Ok(Vec::new()),
+        };
+        let image_base = self.optional_header().map_or(0, |h| h.image_base());
+        let rva = va_to_rva(table, image_base)
+            .ok_or_else(|| invalid_data(&format!("{} table is outside the image", name)))?;
+        let flags = config.guard_flags().unwrap_or(0);
+        let stride = 4
+            + ((flags & IMAGE_GUARD_CF_FUNCTION_TABLE_SIZE_MASK)
+                >> IMAGE_GUARD_CF_FUNCTION_TABLE_SIZE_SHIFT);
+        let size = u32::try_from(count)
+            .ok()
+            .and_then(|count| count.checked_mul(stride))
+            .ok_or_else(|| invalid_data(&format!("{} count is out of range", name)))?;
+        let data = self
+            .data_at_rva(rva, size)
+            .ok_or_else(|| invalid_data(&format!("{} table is not backed by file data", name)))?;
+        Ok(data
+            .chunks_exact(stride as usize)
+            .filter_map(|entry| read_u32(entry, 0))
+            .collect())
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
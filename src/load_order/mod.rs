@@ -0,0 +1,363 @@
+use crate::loader::VirtualLoader;
+use crate::module_key;
+use crate::port_exe::PortExe;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Prefixes of API set contracts, which the loader maps through the API set schema
+/// instead of searching for a file
+const API_SET_PREFIXES: [&str; 2] = ["api-ms-win-", "ext-ms-"];
+
+/// Directories and lists the Windows loader consults when a module imports a DLL by name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchOrder {
+    /// Directory of the executable that started the process
+    pub application_dir: PathBuf,
+    /// `System32`, or `SysWOW64` for a 32-bit process on a 64-bit system
+    pub system_dir: Option<PathBuf>,
+    /// The Windows directory
+    pub windows_dir: Option<PathBuf>,
+    /// Working directory of the process
+    pub current_dir: Option<PathBuf>,
+    /// Directories of the `PATH` environment variable, in order
+    pub path: Vec<PathBuf>,
+    /// Names under `HKLM\...\Session Manager\KnownDLLs`, always mapped from the system directory
+    pub known_dlls: Vec<String>,
+    /// DLLs a side-by-side manifest redirects to a specific file
+    pub redirects: BTreeMap<String, PathBuf>,
+    /// `SafeDllSearchMode`, on by default, which moves the current directory after
+    /// the system and Windows directories
+    pub safe_dll_search_mode: bool,
+}
+
+impl SearchOrder {
+    pub fn new(application_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            application_dir: application_dir.into(),
+            system_dir: None,
+            windows_dir: None,
+            current_dir: None,
+            path: Vec::new(),
+            known_dlls: Vec::new(),
+            redirects: BTreeMap::new(),
+            safe_dll_search_mode: true,
+        }
+    }
+
+    /// Directories searched for DLLs that are neither redirected nor known, in order
+    pub fn directories(&self) -> Vec<(BindingSource, &Path)> {
+        let mut directories = vec![(
+            BindingSource::ApplicationDir,
+            self.application_dir.as_path(),
+        )];
+        let current = self
+            .current_dir
+            .as_deref()
+            .map(|dir| (BindingSource::CurrentDir, dir));
+        if !self.safe_dll_search_mode {
+            directories.extend(current);
+        }
+        directories.extend(
+            self.system_dir
+                .as_deref()
+                .map(|dir| (BindingSource::SystemDir, dir)),
+        );
+        directories.extend(
+            self.windows_dir
+                .as_deref()
+                .map(|dir| (BindingSource::WindowsDir, dir)),
+        );
+        if self.safe_dll_search_mode {
+            directories.extend(current);
+        }
+        directories.extend(
+            self.path
+                .iter()
+                .map(|dir| (BindingSource::Path, dir.as_path())),
+        );
+        directories
+    }
+
+    fn is_known_dll(&self, name: &str) -> bool {
+        let key = module_key(name);
+        self.known_dlls.iter().any(|known| module_key(known) == key)
+    }
+
+    fn redirect(&self, name: &str) -> Option<&PathBuf> {
+        let key = module_key(name);
+        self.redirects
+            .iter()
+            .find(|(redirected, _)| module_key(redirected) == key)
+            .map(|(_, path)| path)
+    }
+}
+
+/// Step of the search order that supplied a DLL
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BindingSource {
+    /// API set contract, resolved by the loader without touching the file system
+    ApiSet,
+    /// Side-by-side manifest redirection
+    Redirect,
+    KnownDll,
+    ApplicationDir,
+    SystemDir,
+    WindowsDir,
+    CurrentDir,
+    Path,
+}
+
+impl BindingSource {
+    /// Returns `true` for directories only administrators can normally write to.
+    pub fn is_protected(self) -> bool {
+        matches!(self, Self::SystemDir | Self::WindowsDir)
+    }
+}
+
+impl fmt::Display for BindingSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::ApiSet => "API set",
+            Self::Redirect => "SxS redirect",
+            Self::KnownDll => "KnownDLLs",
+            Self::ApplicationDir => "application directory",
+            Self::SystemDir => "system directory",
+            Self::WindowsDir => "Windows directory",
+            Self::CurrentDir => "current directory",
+            Self::Path => "PATH",
+        })
+    }
+}
+
+/// File an imported DLL name binds to under a [`SearchOrder`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DllBinding {
+    name: String,
+    importer: String,
+    source: Option<BindingSource>,
+    path: Option<PathBuf>,
+    planting_directories: Vec<PathBuf>,
+}
+
+impl DllBinding {
+    /// DLL name as imported, lowercased
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// First loaded module found to import the DLL
+    pub fn importer(&self) -> &str {
+        &self.importer
+    }
+
+    /// Step that supplied the DLL, `None` if the search found nothing
+    pub fn source(&self) -> Option<BindingSource> {
+        self.source
+    }
+
+    /// File the DLL binds to; `None` for API sets, missing DLLs and known DLLs
+    /// without a system directory to look in
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Unprotected directories searched before the one the DLL was found in, or all
+    /// of them for a missing DLL: a file planted there is loaded instead.
+    pub fn planting_directories(&self) -> &[PathBuf] {
+        &self.planting_directories
+    }
+
+    pub fn is_missing(&self) -> bool {
+        self.source.is_none()
+    }
+
+    /// Returns `true` if a DLL planted in a searched directory would be loaded.
+    pub fn is_hijackable(&self) -> bool {
+        !self.planting_directories.is_empty()
+    }
+}
+
+/// Problem met while loading a DLL the search bound to
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LoadOrderWarning {
+    /// The file could not be read or is not a loadable image
+    Unloadable { path: PathBuf, message: String },
+}
+
+impl fmt::Display for LoadOrderWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unloadable { path, message } => {
+                write!(f, "{} cannot be loaded: {}", path.display(), message)
+            }
+        }
+    }
+}
+
+/// Result of [`VirtualLoader::load_dependencies`]
+#[derive(Debug, Clone, Default)]
+pub struct LoadOrder {
+    bindings: Vec<DllBinding>,
+    warnings: Vec<LoadOrderWarning>,
+}
+
+impl LoadOrder {
+    /// Bindings in the order the DLLs were first needed
+    pub fn bindings(&self) -> &[DllBinding] {
+        &self.bindings
+    }
+
+    pub fn warnings(&self) -> &[LoadOrderWarning] {
+        &self.warnings
+    }
+}
+
+impl VirtualLoader {
+    /// Binds every missing dependency of the loaded modules to a file the way the
+    /// Windows loader searches for it, and loads the files found at their preferred
+    /// bases until the dependency graph is closed.
+    ///
+    /// Names already loaded are reused, as the loader does with its module list.
+    pub fn load_dependencies(&mut self, search: &SearchOrder) -> io::Result<LoadOrder> {
+        let mut order = LoadOrder::default();
+        let mut listings = DirectoryListings::default();
+        let mut attempted = BTreeSet::new();
+        loop {
+            let missing: Vec<String> = self
+                .missing_dependencies()
+                .into_iter()
+                .filter(|name| !attempted.contains(name))
+                .collect();
+            if missing.is_empty() {
+                return Ok(order);
+            }
+            for name in missing {
+                attempted.insert(name.clone());
+                let importer = self
+                    .modules()
+                    .iter()
+                    .find(|module| {
+                        module
+                            .dependencies()
+                            .iter()
+                            .any(|dll| dll.eq_ignore_ascii_case(&name))
+                    })
+                    .map_or_else(String::new, |module| module.name().to_string());
+                let binding = bind(&name, importer, search, &mut listings)?;
+                if let Some(path) = &binding.path {
+                    let loaded = File::open(path)
+                        .and_then(|mut file| PortExe::parse(&mut file))
+                        .and_then(|pe| self.load_at_preferred_base(&name, pe).map(|_| ()));
+                    if let Err(e) = loaded {
+                        order.warnings.push(LoadOrderWarning::Unloadable {
+                            path: path.clone(),
+                            message: e.to_string(),
+                        });
+                    }
+                }
+                order.bindings.push(binding);
+            }
+        }
+    }
+}
+
+/// Runs the search for one DLL name.
+fn bind(
+    name: &str,
+    importer: String,
+    search: &SearchOrder,
+    listings: &mut DirectoryListings,
+) -> io::Result<DllBinding> {
+    let mut binding = DllBinding {
+        name: name.to_string(),
+        importer,
+        source: None,
+        path: None,
+        planting_directories: Vec::new(),
+    };
+    let lowercase = name.to_ascii_lowercase();
+    if API_SET_PREFIXES
+        .iter()
+        .any(|prefix| lowercase.starts_with(prefix))
+    {
+        binding.source = Some(BindingSource::ApiSet);
+        return Ok(binding);
+    }
+    if let Some(path) = search.redirect(name) {
+        binding.source = Some(BindingSource::Redirect);
+        binding.path = Some(path.clone());
+        return Ok(binding);
+    }
+    let file_name = file_name(name);
+    if search.is_known_dll(name) {
+        binding.source = Some(BindingSource::KnownDll);
+        if let Some(system_dir) = &search.system_dir {
+            binding.path = listings.find(system_dir, &file_name)?;
+        }
+        return Ok(binding);
+    }
+    for (source, directory) in search.directories() {
+        if let Some(path) = listings.find(directory, &file_name)? {
+            binding.source = Some(source);
+            binding.path = Some(path);
+            return Ok(binding);
+        }
+        if !source.is_protected() && !binding.planting_directories.iter().any(|d| d == directory) {
+            binding.planting_directories.push(directory.to_path_buf());
+        }
+    }
+    Ok(binding)
+}
+
+/// File name the loader looks for: names without an extension get `.dll`, and a
+/// trailing dot means no extension at all.
+fn file_name(name: &str) -> String {
+    match name.strip_suffix('.') {
+        Some(stem) => stem.to_string(),
+        None if !name.contains('.') => format!("{}.dll", name),
+        None => name.to_string(),
+    }
+}
+
+/// Case-insensitive view of the searched directories, read once each
+#[derive(Debug, Default)]
+struct DirectoryListings {
+    listings: HashMap<PathBuf, HashMap<String, PathBuf>>,
+}
+
+impl DirectoryListings {
+    fn find(&mut self, directory: &Path, file_name: &str) -> io::Result<Option<PathBuf>> {
+        if !self.listings.contains_key(directory) {
+            let mut listing = HashMap::new();
+            match fs::read_dir(directory) {
+                Ok(entries) => {
+                    let mut files = Vec::new();
+                    for entry in entries {
+                        let entry = entry?;
+                        if !entry.file_type()?.is_dir() {
+                            files.push(entry.path());
+                        }
+                    }
+                    // Names that differ only in case resolve to the first in path order
+                    files.sort();
+                    for path in files {
+                        if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                            listing.entry(name.to_ascii_lowercase()).or_insert(path);
+                        }
+                    }
+                }
+                // A directory that does not exist is searched and skipped by the loader
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+            self.listings.insert(directory.to_path_buf(), listing);
+        }
+        Ok(self.listings[directory]
+            .get(&file_name.to_ascii_lowercase())
+            .cloned())
+    }
+}
@@ -0,0 +1,516 @@
+use crate::export::Export;
+use crate::import::Import;
+use crate::module_key;
+use crate::pe_string::PeString;
+use crate::port_exe::PortExe;
+use std::fmt;
+use std::io;
+
+/// Alignment of the bases picked for modules whose preferred base is taken
+pub const ALLOCATION_GRANULARITY: u64 = 0x10000;
+/// Upper bound on the number of forwarders followed while resolving an export
+pub const MAX_FORWARDER_DEPTH: usize = 32;
+
+/// Export looked up by name or by ordinal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolRef {
+    Name(PeString),
+    Ordinal(u32),
+}
+
+impl fmt::Display for SymbolRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymbolRef::Name(name) => write!(f, "{}", name),
+            SymbolRef::Ordinal(ordinal) => write!(f, "#{}", ordinal),
+        }
+    }
+}
+
+/// Module laid out in a [`VirtualLoader`]
+#[derive(Debug)]
+pub struct LoadedModule {
+    name: String,
+    base: u64,
+    size: u64,
+    pe: PortExe,
+    exports: Vec<Export>,
+    dependencies: Vec<String>,
+}
+
+impl LoadedModule {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Virtual address the module is laid out at
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// `SizeOfImage` of the module
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn pe(&self) -> &PortExe {
+        &self.pe
+    }
+
+    pub fn exports(&self) -> &[Export] {
+        &self.exports
+    }
+
+    /// Names of the DLLs in the import directory of the module
+    pub fn dependencies(&self) -> &[String] {
+        &self.dependencies
+    }
+}
+
+/// Final location of an export, after following any forwarders
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    module: String,
+    rva: u32,
+    address: u64,
+    forwarders: Vec<String>,
+}
+
+impl Resolution {
+    /// Name of the module implementing the export
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    pub fn rva(&self) -> u32 {
+        self.rva
+    }
+
+    /// Virtual address of the export at the base its module was laid out at
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    /// Forwarder strings followed on the way, in order
+    pub fn forwarders(&self) -> &[String] {
+        &self.forwarders
+    }
+}
+
+/// How following the forwarders of an export ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ForwarderOutcome {
+    /// The chain ends at an export implemented by a loaded module
+    Resolved(Resolution),
+    /// A forwarder leads back to an export already on the chain
+    Loop,
+    /// The chain is longer than the given number of forwarders
+    TooDeep(usize),
+    /// A forwarder names a module that is not loaded
+    ModuleNotLoaded(String),
+    /// A module on the chain does not export the symbol asked for
+    MissingExport { module: String, symbol: SymbolRef },
+    /// A forwarder string is neither `DLL.Function` nor `DLL.#Ordinal`
+    Malformed(String),
+}
+
+impl fmt::Display for ForwarderOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ForwarderOutcome::Resolved(resolution) => write!(
+                f,
+                "{}+{:#x} at {:#x}",
+                resolution.module, resolution.rva, resolution.address
+            ),
+            ForwarderOutcome::Loop => write!(f, "forwarder loop"),
+            ForwarderOutcome::TooDeep(depth) => {
+                write!(f, "forwarder chain deeper than {}", depth)
+            }
+            ForwarderOutcome::ModuleNotLoaded(module) => write!(f, "{} is not loaded", module),
+            ForwarderOutcome::MissingExport { module, symbol } => {
+                write!(f, "{} does not export {}", module, symbol)
+            }
+            ForwarderOutcome::Malformed(forwarder) => {
+                write!(f, "malformed forwarder {}", forwarder)
+            }
+        }
+    }
+}
+
+/// Forwarded export of a loaded module and where its forwarders lead
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForwarderChain {
+    module: String,
+    export: SymbolRef,
+    forwarders: Vec<String>,
+    outcome: ForwarderOutcome,
+}
+
+impl ForwarderChain {
+    /// Name of the module exporting the forwarder
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    /// Name of the export, or its ordinal if it has none
+    pub fn export(&self) -> &SymbolRef {
+        &self.export
+    }
+
+    /// Forwarder strings followed, in order
+    pub fn forwarders(&self) -> &[String] {
+        &self.forwarders
+    }
+
+    pub fn outcome(&self) -> &ForwarderOutcome {
+        &self.outcome
+    }
+
+    /// Whether the chain ends in a loop, runs too deep or breaks inside the loaded modules.
+    /// Chains leaving the module set are not counted.
+    pub fn is_broken(&self) -> bool {
+        !matches!(
+            self.outcome,
+            ForwarderOutcome::Resolved(_) | ForwarderOutcome::ModuleNotLoaded(_)
+        )
+    }
+}
+
+/// Import by name whose hint does not point at its name in the export name table of
+/// the loaded DLL, see [`VirtualLoader::stale_hints`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleHint {
+    importer: String,
+    dll: String,
+    function: PeString,
+    hint: u16,
+    position: usize,
+}
+
+impl StaleHint {
+    /// Name of the module holding the import
+    pub fn importer(&self) -> &str {
+        &self.importer
+    }
+
+    /// DLL name as written in the import directory
+    pub fn dll(&self) -> &str {
+        &self.dll
+    }
+
+    pub fn function(&self) -> &PeString {
+        &self.function
+    }
+
+    /// Index into the export name table recorded in the import
+    pub fn hint(&self) -> u16 {
+        self.hint
+    }
+
+    /// Index the name actually has in the export name table
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl fmt::Display for StaleHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} imports {}!{} with hint {}, but it is at {}",
+            self.importer, self.dll, self.function, self.hint, self.position
+        )
+    }
+}
+
+/// Static stand-in for the Windows loader: lays out modules at chosen bases and
+/// resolves imports and forwarder chains between them without running anything.
+#[derive(Debug, Default)]
+pub struct VirtualLoader {
+    modules: Vec<LoadedModule>,
+}
+
+impl VirtualLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn modules(&self) -> &[LoadedModule] {
+        &self.modules
+    }
+
+    /// Looks a module up by name, ignoring case and a `.dll` extension.
+    pub fn module(&self, name: &str) -> Option<&LoadedModule> {
+        let key = module_key(name);
+        self.modules
+            .iter()
+            .find(|module| module_key(&module.name) == key)
+    }
+
+    /// Lays `pe` out at `base` under `name`.
+    ///
+    /// Fails if the image overlaps a module already loaded or a module of the same name exists.
+    pub fn load(&mut self, name: &str, pe: PortExe, base: u64) -> io::Result<&LoadedModule> {
+        if self.module(name).is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} is already loaded", name),
+            ));
+        }
+        let size = pe
+            .optional_header()
+            .map(|header| header.size_of_image() as u64)
+            .ok_or_else(|| invalid_input("object files cannot be loaded"))?;
+        if let Some(module) = self.overlapping(base, size) {
+            return Err(invalid_input(&format!(
+                "{} would overlap {} at {:#x}",
+                name, module.name, module.base
+            )));
+        }
+        let exports = pe.exports()?;
+        let dependencies = pe
+            .imports()?
+            .iter()
+            .map(|descriptor| descriptor.dll_name().to_string())
+            .collect();
+        self.modules.push(LoadedModule {
+            name: name.to_string(),
+            base,
+            size,
+            pe,
+            exports,
+            dependencies,
+        });
+        Ok(&self.modules[self.modules.len() - 1])
+    }
+
+    /// Lays `pe` out at its preferred base, or at the first free aligned address
+    /// above the loaded modules if that range is taken, as the loader does under ASLR.
+    pub fn load_at_preferred_base(&mut self, name: &str, pe: PortExe) -> io::Result<&LoadedModule> {
+        let (preferred, size) = pe
+            .optional_header()
+            .map(|header| (header.image_base(), header.size_of_image() as u64))
+            .ok_or_else(|| invalid_input("object files cannot be loaded"))?;
+        let base = if self.overlapping(preferred, size).is_none() {
+            preferred
+        } else {
+            let end = self
+                .modules
+                .iter()
+                .map(|module| module.base.saturating_add(module.size))
+                .max()
+                .unwrap_or(0);
+            end.checked_add(ALLOCATION_GRANULARITY - 1)
+                .map(|end| end / ALLOCATION_GRANULARITY * ALLOCATION_GRANULARITY)
+                .ok_or_else(|| invalid_input("no free address range left"))?
+        };
+        self.load(name, pe, base)
+    }
+
+    /// Names of DLLs imported by a loaded module that are not loaded themselves,
+    /// sorted and without duplicates.
+    pub fn missing_dependencies(&self) -> Vec<String> {
+        let mut missing: Vec<String> = self
+            .modules
+            .iter()
+            .flat_map(|module| module.dependencies.iter())
+            .filter(|dll| self.module(dll).is_none())
+            .map(|dll| dll.to_ascii_lowercase())
+            .collect();
+        missing.sort();
+        missing.dedup();
+        missing
+    }
+
+    /// Module containing `address` and the RVA of the address within it
+    pub fn module_at(&self, address: u64) -> Option<(&LoadedModule, u32)> {
+        self.modules.iter().find_map(|module| {
+            let rva = address.checked_sub(module.base)?;
+            (rva < module.size).then(|| (module, rva as u32))
+        })
+    }
+
+    /// Resolves `symbol` exported by `dll`, following forwarders to the module implementing it.
+    pub fn resolve(&self, dll: &str, symbol: &SymbolRef) -> io::Result<Resolution> {
+        let (kind, outcome) = match self.follow(dll, symbol, MAX_FORWARDER_DEPTH).1 {
+            ForwarderOutcome::Resolved(resolution) => return Ok(resolution),
+            outcome @ ForwarderOutcome::ModuleNotLoaded(_)
+            | outcome @ ForwarderOutcome::MissingExport { .. } => {
+                (io::ErrorKind::NotFound, outcome)
+            }
+            outcome => (io::ErrorKind::InvalidData, outcome),
+        };
+        Err(io::Error::new(kind, outcome.to_string()))
+    }
+
+    /// Follows every forwarded export of the loaded modules, in load and export order,
+    /// giving up on chains of more than `max_depth` forwarders.
+    pub fn forwarder_chains(&self, max_depth: usize) -> Vec<ForwarderChain> {
+        self.modules
+            .iter()
+            .flat_map(|module| {
+                module
+                    .exports
+                    .iter()
+                    .filter(|export| export.forwarder().is_some())
+                    .map(move |export| {
+                        let export = match export.name() {
+                            Some(name) => SymbolRef::Name(name.clone()),
+                            None => SymbolRef::Ordinal(export.ordinal()),
+                        };
+                        let (forwarders, outcome) = self.follow(&module.name, &export, max_depth);
+                        ForwarderChain {
+                            module: module.name.clone(),
+                            export,
+                            forwarders,
+                            outcome,
+                        }
+                    })
+            })
+            .collect()
+    }
+
+    /// Resolves an entry of the import directory of a loaded module.
+    pub fn resolve_import(&self, dll: &str, import: &Import) -> io::Result<Resolution> {
+        let symbol = match (import.name(), import.ordinal()) {
+            (Some(name), _) => SymbolRef::Name(name.clone()),
+            (None, Some(ordinal)) => SymbolRef::Ordinal(ordinal as u32),
+            (None, None) => return Err(invalid_input("import has neither name nor ordinal")),
+        };
+        self.resolve(dll, &symbol)
+    }
+
+    /// Checks the hint of every import by name of the loaded modules against the export
+    /// name table of the loaded DLL it names, in load and import order.
+    ///
+    /// The linker takes hints from the import library, so a hint that misses while the
+    /// name is exported means the DLL was rebuilt since or the import table was edited.
+    /// Imports of DLLs that are not loaded and names the DLL does not export are skipped.
+    pub fn stale_hints(&self) -> io::Result<Vec<StaleHint>> {
+        let mut name_tables: Vec<Option<Vec<PeString>>> = vec![None; self.modules.len()];
+        let mut stale = Vec::new();
+        for module in &self.modules {
+            for descriptor in module.pe.imports()? {
+                let index = match self.modules.iter().position(|exporter| {
+                    module_key(&exporter.name) == module_key(descriptor.dll_name())
+                }) {
+                    Some(index) => index,
+                    None => continue,
+                };
+                if name_tables[index].is_none() {
+                    name_tables[index] = Some(self.modules[index].pe.export_names()?);
+                }
+                let names = name_tables[index].as_deref().unwrap_or_default();
+                for import in descriptor.imports() {
+                    let name = match import.name() {
+                        Some(name) => name,
+                        None => continue,
+                    };
+                    if names.get(import.hint() as usize) == Some(name) {
+                        continue;
+                    }
+                    if let Some(position) = names.iter().position(|export| export == name) {
+                        stale.push(StaleHint {
+                            importer: module.name.clone(),
+                            dll: descriptor.dll_name().to_string(),
+                            function: name.clone(),
+                            hint: import.hint(),
+                            position,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(stale)
+    }
+
+    /// Walks the forwarders from `symbol` of `dll`, stopping at the first export that is
+    /// implemented, revisited or missing, or after `max_depth` forwarders.
+    fn follow(
+        &self,
+        dll: &str,
+        symbol: &SymbolRef,
+        max_depth: usize,
+    ) -> (Vec<String>, ForwarderOutcome) {
+        let mut dll = dll.to_string();
+        let mut symbol = symbol.clone();
+        let mut forwarders = Vec::new();
+        let mut visited = Vec::new();
+        loop {
+            let (index, module) = match self
+                .modules
+                .iter()
+                .enumerate()
+                .find(|(_, module)| module_key(&module.name) == module_key(&dll))
+            {
+                Some(found) => found,
+                None => return (forwarders, ForwarderOutcome::ModuleNotLoaded(dll)),
+            };
+            let position = module.exports.iter().position(|export| match &symbol {
+                SymbolRef::Name(name) => export.name() == Some(name),
+                SymbolRef::Ordinal(ordinal) => export.ordinal() == *ordinal,
+            });
+            let export = match position {
+                Some(position) => &module.exports[position],
+                None => {
+                    let outcome = ForwarderOutcome::MissingExport {
+                        module: module.name.clone(),
+                        symbol,
+                    };
+                    return (forwarders, outcome);
+                }
+            };
+            let forwarder = match export.forwarder() {
+                Some(forwarder) => forwarder.to_string(),
+                None => {
+                    let resolution = Resolution {
+                        module: module.name.clone(),
+                        rva: export.rva(),
+                        address: module.base.wrapping_add(export.rva() as u64),
+                        forwarders: forwarders.clone(),
+                    };
+                    return (forwarders, ForwarderOutcome::Resolved(resolution));
+                }
+            };
+            if visited.contains(&(index, position)) {
+                return (forwarders, ForwarderOutcome::Loop);
+            }
+            visited.push((index, position));
+            if forwarders.len() == max_depth {
+                return (forwarders, ForwarderOutcome::TooDeep(max_depth));
+            }
+            let (target_dll, target_symbol) = match parse_forwarder(&forwarder) {
+                Some(target) => target,
+                None => return (forwarders, ForwarderOutcome::Malformed(forwarder)),
+            };
+            dll = target_dll;
+            symbol = target_symbol;
+            forwarders.push(forwarder);
+        }
+    }
+
+    fn overlapping(&self, base: u64, size: u64) -> Option<&LoadedModule> {
+        let end = base.saturating_add(size);
+        self.modules
+            .iter()
+            .find(|module| base < module.base.saturating_add(module.size) && module.base < end)
+    }
+}
+
+/// Splits `DLL.Function` or `DLL.#Ordinal` into the target module and export.
+fn parse_forwarder(forwarder: &str) -> Option<(String, SymbolRef)> {
+    let (dll, symbol) = forwarder.rsplit_once('.')?;
+    if dll.is_empty() || symbol.is_empty() {
+        return None;
+    }
+    let symbol = match symbol.strip_prefix('#') {
+        Some(ordinal) => SymbolRef::Ordinal(ordinal.parse().ok()?),
+        None => SymbolRef::Name(symbol.into()),
+    };
+    Some((dll.to_string(), symbol))
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
@@ -0,0 +1,227 @@
+use crate::port_exe::PortExe;
+use crate::resource::{ResourceId, RT_VERSION};
+use crate::version::VersionInfo;
+use std::collections::BTreeMap;
+use std::io;
+
+/// Localizable strings of one or more files, by language ID
+///
+/// Adding a file and its MUI satellites merges their resources, so strings that were
+/// moved into a satellite count for its language.
+#[derive(Debug, Clone, Default)]
+pub struct LocalizedResources {
+    strings: BTreeMap<u16, BTreeMap<u16, String>>,
+    version_strings: BTreeMap<u16, BTreeMap<String, String>>,
+}
+
+/// Localization gaps of one language compared with the reference language
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LanguageReport {
+    language: u16,
+    strings: usize,
+    missing: Vec<u16>,
+    untranslated: Vec<u16>,
+    extra: Vec<u16>,
+    missing_version_keys: Vec<String>,
+}
+
+impl LanguageReport {
+    pub fn language(&self) -> u16 {
+        self.language
+    }
+
+    /// Number of string table entries in the language
+    pub fn strings(&self) -> usize {
+        self.strings
+    }
+
+    /// String IDs the reference language has and this one lacks
+    pub fn missing(&self) -> &[u16] {
+        &self.missing
+    }
+
+    /// String IDs whose text is identical to the reference language
+    pub fn untranslated(&self) -> &[u16] {
+        &self.untranslated
+    }
+
+    /// String IDs only this language has
+    pub fn extra(&self) -> &[u16] {
+        &self.extra
+    }
+
+    /// Version information keys the reference language has and this one lacks
+    pub fn missing_version_keys(&self) -> &[String] {
+        &self.missing_version_keys
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+            && self.untranslated.is_empty()
+            && self.missing_version_keys.is_empty()
+    }
+}
+
+/// Result of [`LocalizedResources::audit`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalizationAudit {
+    reference: u16,
+    languages: Vec<LanguageReport>,
+}
+
+impl LocalizationAudit {
+    /// Language the others were compared with
+    pub fn reference(&self) -> u16 {
+        self.reference
+    }
+
+    /// Every other language, in ascending language ID order
+    pub fn languages(&self) -> &[LanguageReport] {
+        &self.languages
+    }
+}
+
+impl LocalizedResources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the string tables and version information strings of `pe`.
+    pub fn add(&mut self, pe: &PortExe) -> io::Result<()> {
+        for string in pe.string_resources()? {
+            self.strings
+                .entry(string.language())
+                .or_default()
+                .insert(string.id(), string.value().to_string());
+        }
+        for entry in pe.resources()? {
+            if *entry.type_id() != ResourceId::Id(RT_VERSION) {
+                continue;
+            }
+            let info = match pe.resource_data(&entry).map(VersionInfo::parse) {
+                Some(Ok(info)) => info,
+                _ => continue,
+            };
+            // String tables are keyed by language and code page, e.g. `040904b0`
+            for string in info.strings() {
+                let language = match string
+                    .table()
+                    .get(..4)
+                    .and_then(|language| u16::from_str_radix(language, 16).ok())
+                {
+                    Some(language) => language,
+                    None => continue,
+                };
+                self.version_strings
+                    .entry(language)
+                    .or_default()
+                    .insert(string.key().to_string(), string.value().to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Language IDs with string tables or version information, in ascending order
+    pub fn languages(&self) -> Vec<u16> {
+        let mut languages: Vec<u16> = self
+            .strings
+            .keys()
+            .chain(self.version_strings.keys())
+            .copied()
+            .collect();
+        languages.sort_unstable();
+        languages.dedup();
+        languages
+    }
+
+    /// Compares every language with `reference`, or with the language that has the most
+    /// strings if `None`.
+    ///
+    /// A string counts as untranslated when it is identical to the reference and contains
+    /// letters, so numbers and format strings such as `%d` are not reported. Version
+    /// information is only checked for missing keys, as values such as `CompanyName` are
+    /// commonly left untranslated.
+    pub fn audit(&self, reference: Option<u16>) -> Option<LocalizationAudit> {
+        let reference = match reference {
+            Some(reference) => reference,
+            None => *self
+                .languages()
+                .iter()
+                .max_by_key(|language| self.strings.get(language).map_or(0, BTreeMap::len))?,
+        };
+        let empty_strings = BTreeMap::new();
+        let empty_version = BTreeMap::new();
+        let reference_strings = self.strings.get(&reference).unwrap_or(&empty_strings);
+        let reference_version = self
+            .version_strings
+            .get(&reference)
+            .unwrap_or(&empty_version);
+
+        let languages = self
+            .languages()
+            .into_iter()
+            .filter(|&language| language != reference)
+            .map(|language| {
+                let strings = self.strings.get(&language).unwrap_or(&empty_strings);
+                let version = self
+                    .version_strings
+                    .get(&language)
+                    .unwrap_or(&empty_version);
+                LanguageReport {
+                    language,
+                    strings: strings.len(),
+                    missing: reference_strings
+                        .keys()
+                        .filter(|id| !strings.contains_key(id))
+                        .copied()
+                        .collect(),
+                    untranslated: reference_strings
+                        .iter()
+                        .filter(|(id, text)| strings.get(id) == Some(text) && has_words(text))
+                        .map(|(id, _)| *id)
+                        .collect(),
+                    extra: strings
+                        .keys()
+                        .filter(|id| !reference_strings.contains_key(id))
+                        .copied()
+                        .collect(),
+                    missing_version_keys: reference_version
+                        .keys()
+                        .filter(|key| !version.contains_key(*key))
+                        .cloned()
+                        .collect(),
+                }
+            })
+            .collect();
+        Some(LocalizationAudit {
+            reference,
+            languages,
+        })
+    }
+
+    /// Text of string `id` in `language`
+    pub fn string(&self, language: u16, id: u16) -> Option<&str> {
+        self.strings
+            .get(&language)
+            .and_then(|strings| strings.get(&id))
+            .map(String::as_str)
+    }
+}
+
+/// Whether `text` has letters outside `printf` format specifiers such as `%d` or `%1!s!`
+fn has_words(text: &str) -> bool {
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            // Flags, width, precision and size prefixes, then the conversion letter
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() && !matches!(c, 'h' | 'l' | 'I' | 'w' | 'z') {
+                    break;
+                }
+            }
+        } else if c.is_alphabetic() {
+            return true;
+        }
+    }
+    false
+}
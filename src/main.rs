@@ -1,28 +1,3736 @@
-use std::{
-    fs::File,
-    io::{Read, Seek, SeekFrom},
-};
+use pexp::analysis::{SectionColumn, DEFAULT_SECTION_COLUMNS};
+use pexp::appcontainer::ApiAllowlist;
+use pexp::artifacts::ArtifactKind;
+use pexp::audit::audit_release;
+use pexp::bindings::BindingLanguage;
+use pexp::clr::{table_name, ClrDirectory};
+use pexp::codegen::CodegenLanguage;
+use pexp::config::{ColorChoice, Config};
+#[cfg(feature = "containers")]
+use pexp::container::{contained_images, open_container};
+use pexp::diff::diff_objects;
+use pexp::edit::{HardenPolicy, LongNames};
+use pexp::exception::{Arm64Unwind, ArmUnwind, RuntimeFunction};
+use pexp::export::ExportKind;
+use pexp::expression::AddressExpression;
+use pexp::hook::DEFAULT_PROLOGUE_LENGTH;
+use pexp::icon::{IconHash, DEFAULT_ICON_DISTANCE};
+use pexp::labels::{label_script, Label, LabelFormat};
+use pexp::load_order::SearchOrder;
+use pexp::loader::{SymbolRef, VirtualLoader, MAX_FORWARDER_DEPTH};
+use pexp::localization::LocalizedResources;
+use pexp::manifest::SideBySide;
+use pexp::messages::Catalog;
+use pexp::min_os::ApiVersionMap;
+use pexp::minidump::Minidump;
+use pexp::ordinals::OrdinalMap;
+use pexp::overlay::OverlaySniffers;
+use pexp::patch::Patch;
+use pexp::plugin::AnalysisRegistry;
+use pexp::repro::compare_builds;
+use pexp::resource::ResourceId;
+use pexp::search::{ImportQuery, ImportSymbol, MatchMode, NameMatcher};
+use pexp::similarity::{cluster, DEFAULT_CLUSTER_THRESHOLD};
+use pexp::strip::StripOptions;
+use pexp::timeline::{format_timestamp, timeline as build_timeline};
+use pexp::validation::Severity;
+use pexp::PortExe;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{env, fs, fs::File, process};
+
+const USAGE: &str = "usage: pexp <command> [args]
+
+commands:
+    align [--file-alignment <n>] [--pad-to <size>] [-o <output>] <file>
+                                      re-lay out section data for a new FileAlignment
+                                      and pad the file with zeros
+    annotate [--format ghidra|ida|x64dbg|windbg] <file>
+                                      print a script naming the sections, exports, IAT
+                                      slots and TLS callbacks of a file in a disassembler
+                                      or debugger
+    appcontainer [--allowlist <file>] <file...>
+                                      report UWP/MSIX traits and imports outside an
+                                      allowlist of store app APIs
+    apply-patch <old> <patch> <out>   rebuild a file from its previous version and a patch
+                                      made by make-patch
+    artifacts <file>                  list the section, export, import, resource, version,
+                                      manifest and PDB strings of a file with their offsets
+    bindings [--lang c|rust] <dll>    generate declarations for the exports of a DLL
+    build-id [--server <url>] <file...>
+                                      print the CodeView, MVID, Go and repro build IDs of
+                                      each file in canonical form, preferred first, and
+                                      the symbol server paths of the file and its PDB,
+                                      or their URLs on a server
+    checksec <file...>                print the exploit mitigations of each file
+    clr <file>                        print the CLR header of a managed image: runtime
+                                      version, flags, entry point, strong name signature
+                                      and the metadata streams and tables
+    codegen [--lang rust] <file>      emit the headers, sections, exports and imports of a
+                                      file as source code to compile into other tools
+    config                            print the settings read from pexp.toml in the user
+                                      configuration directory and the closest project
+                                      directory, and the files they came from
+    crosscheck <file...>              check .pdata function ranges against relocated
+                                      function pointers and the CFG function table
+    cvinfo <obj>                      print the CodeView provenance records of an object file
+    comdat <obj>                      list the COMDAT sections of an object file
+    dedup [--rewrite [-o <output>]] <file>
+                                      report resources stored more than once or mostly
+                                      padding, and optionally share duplicates
+    dwarf <file>                      list the DWARF sections and the versions of the
+                                      compilation units in .debug_info
+    def <dll>                         print a module-definition file for the exports of a DLL
+    entry <file...>                   follow jumps and startup stubs from the entry point to
+                                      the probable main function, with the disassembler
+                                      feature
+    exports <dll>                     list the exports of a DLL as code, data or forwarders,
+                                      flagging data exports
+    imports [--ordinals <def|dll>]... <file>
+                                      list imports and delay-load imports, naming ordinals
+                                      from bundled and given maps
+    find-export [-i|--prefix|--regex] [--recursive] <name> <dir>
+                                      list the modules in a directory that export a name
+    find-import [-i|--prefix|--regex] [--recursive] <dll!name|dll!#n|dll> <dir>
+                                      list the files in a directory that import a
+                                      function or DLL
+    forwarders [--max-depth <n>] <file...>
+                                      lay out the files at their preferred bases and follow
+                                      every forwarded export, flagging loops, chains
+                                      deeper than n (32 by default) and broken links
+    harden [--dry-run] [--aslr] [-o <output>] <file...>
+                                      clear WRITE/EXECUTE bits sections do not need and
+                                      optionally enable ASLR; -o takes a single file
+    hexdump [--at <address>] [--length <n>] <file>
+                                      dump bytes of a file from an offset or an address
+                                      expression such as entry+0x20,
+                                      section(.text).end-16, export(name) or rva:0x1234
+    hooks <dump> <original>           report exports whose prologue differs in a memory dump
+    iat-map [--format ghidra|ida|x64dbg|windbg] <file>
+                                      map each import address table slot to its DLL and
+                                      function, or print debugger commands labelling them
+    icon-hash [--near <hash>]... <file...>
+                                      print the perceptual hash of each file's icon and
+                                      flag icons that look like one of the given hashes
+    l10n [--reference <langid>] <file> [<mui>...]
+                                      report strings and version keys missing or left
+                                      untranslated per language
+    load-order [--known-dlls <file>] [--system <dir>] [--windows <dir>] [--cwd <dir>]
+               [--path <dir>]... [--redirect <dll>=<file>]... [--winsxs <dir>]
+               [--unsafe-search] [--check-hints] <exe>
+                                      predict the file each DLL the executable needs binds
+                                      to, through its manifest's side-by-side assemblies,
+                                      and flag directories a planted DLL would win from;
+                                      --check-hints flags import hints that miss the
+                                      export name table of the DLL found
+    make-patch <old> <new> <patch>    write a patch turning one version of a file into the
+                                      next, diffing the headers, each section and the
+                                      overlay against their previous versions
+    messages [<catalog>]              print the English message catalog to translate, or
+                                      the keys a catalog has no template for
+    min-os [--apis <file>] <file...>  estimate the oldest Windows each file runs on from
+                                      its subsystem version and imports, listing the
+                                      imports that need a newer one than it declares
+    mingw <file>                      decode the import data pieces, initializer lists,
+                                      TLS callbacks, build ID and DWARF sections GNU
+                                      linkers produce
+    minidump <dump>                   rebuild the modules of a minidump from its captured
+                                      memory and check them against the module list
+    objdiff <before> <after>          compare the sections and symbols of two object files
+    ordinal-coverage <file...>        count the imports by name and by ordinal per DLL,
+                                      flagging system DLLs imported only by ordinal
+    overlay <file...>                 identify archives and installer data appended to
+                                      images
+    pdata [--unwind] <file>           list the functions of the exception directory of an
+                                      x64, ARM or ARM64 image with their unwind data, and
+                                      decode the x64 unwind codes
+    provenance <file...>              recognize Visual Basic 6, Delphi, AutoIt, PyInstaller,
+                                      NSIS and Inno Setup images and list their projects,
+                                      units, forms, archive entries and installer data
+    release-audit <dir>               check that the binaries under a directory share
+                                      toolchain, mitigations, version and signer, and
+                                      that their PDB paths name no user directories or
+                                      stray build roots
+    rename-sections [--dry-run] [--long-names] [-o <output>]
+        --normalize|--random <seed>|<old>=<new>... <file>
+                                      rename sections, by name or #index, to conventional
+                                      or random names; --long-names stores names
+                                      over 8 bytes in the COFF string table
+    repro <before> <after>            compare two builds ignoring timestamps, the checksum,
+                                      the Rich header, the PDB GUID and the signature, and
+                                      list the byte ranges that still differ
+    resolve <dll!name|dll!#n> <file...>
+                                      lay out the files at their preferred bases and
+                                      resolve an export through its forwarders
+    scan [--recursive] [--analyses <list>|all] [--color auto|always|never] <path...>
+                                      list the PE files in files and directories, and in
+                                      zip, cabinet and MSI packages with the containers
+                                      feature, with the findings of the entropy,
+                                      anomalies, security and entry-point analyses
+                                      chosen
+    sections [--columns <list>] <file>
+                                      print the section table with the chosen columns out
+                                      of name, vaddr, vsize, rawaddr, rawsize, flags,
+                                      characteristics, entropy and md5
+    signature <file...>               print the signer of each file and check that the
+                                      certificate table ends the file, with no data
+                                      appended after the signature
+    signing <file...>                 check ELAM certificate resources, /INTEGRITYCHECK
+                                      and signer EKUs against what ELAM drivers and
+                                      protected processes require, and the signer
+                                      against the company the version resource names
+    similarity [--threshold <d>] <dir>
+                                      group the binaries under a directory by imports,
+                                      Rich header, sections and exports
+    slack [--extract <dir>] <file>    report non-zero bytes in header padding, section
+                                      tails, gaps between sections and between
+                                      directories, and optionally write them to files
+    split [--move-codeview] [-o <output>] <file> [<sidecar>]
+                                      move debug payloads and COFF symbols into a
+                                      sidecar, <file>.debug by default
+    timeline <dir>                    order the binaries under a directory by build time,
+                                      with signing times and anomalies
+    tls [--extract <dir>] <file...>   print the size, zero fill and entropy of the TLS
+                                      template, the index address and the callbacks,
+                                      and optionally write the template to files
+    strip [--relocs] [-o <output>] <file...>
+                                      remove debug data, the Rich header and COFF symbols,
+                                      and optionally the relocations of an EXE; -o takes
+                                      a single file
+    wow64 <file...>                   report whether each file runs on x86, x64 and ARM64
+                                      Windows, natively, under WoW64 or emulated
+
+Commands that edit a file write the result to -o/--output <file> when given and
+otherwise replace the input, through a temporary file renamed over it once written.
+
+Defaults for format, analyses, search-paths, policy, messages and color are read from
+pexp.toml files; flags given on the command line override them.";
 
 fn main() {
-    let mut pe_reader =
-        File::open(".\\target\\debug\\pexp.exe").expect("The file must exists and could be opened");
-    let mut first_two_bytes = [0u8; 2];
-    let _ = pe_reader.read_exact(&mut first_two_bytes);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let status = match args.split_first() {
+        Some((command, rest)) if command == "align" => align(rest),
+        Some((command, rest)) if command == "annotate" => annotate(rest),
+        Some((command, rest)) if command == "appcontainer" => appcontainer(rest),
+        Some((command, rest)) if command == "apply-patch" => apply_patch(rest),
+        Some((command, rest)) if command == "artifacts" => artifacts(rest),
+        Some((command, rest)) if command == "bindings" => bindings(rest),
+        Some((command, rest)) if command == "build-id" => build_id(rest),
+        Some((command, rest)) if command == "checksec" => checksec(rest),
+        Some((command, rest)) if command == "clr" => clr(rest),
+        Some((command, rest)) if command == "codegen" => codegen(rest),
+        Some((command, rest)) if command == "comdat" => comdat(rest),
+        Some((command, rest)) if command == "config" => config(rest),
+        Some((command, rest)) if command == "crosscheck" => crosscheck(rest),
+        Some((command, rest)) if command == "cvinfo" => cvinfo(rest),
+        Some((command, rest)) if command == "dedup" => dedup(rest),
+        Some((command, rest)) if command == "def" => def(rest),
+        Some((command, rest)) if command == "dwarf" => dwarf(rest),
+        Some((command, rest)) if command == "entry" => entry(rest),
+        Some((command, rest)) if command == "exports" => exports(rest),
+        Some((command, rest)) if command == "find-export" => find_export(rest),
+        Some((command, rest)) if command == "find-import" => find_import(rest),
+        Some((command, rest)) if command == "forwarders" => forwarders(rest),
+        Some((command, rest)) if command == "harden" => harden(rest),
+        Some((command, rest)) if command == "hexdump" => hexdump(rest),
+        Some((command, rest)) if command == "hooks" => hooks(rest),
+        Some((command, rest)) if command == "iat-map" => iat_map(rest),
+        Some((command, rest)) if command == "icon-hash" => icon_hash(rest),
+        Some((command, rest)) if command == "imports" => imports(rest),
+        Some((command, rest)) if command == "l10n" => l10n(rest),
+        Some((command, rest)) if command == "load-order" => load_order(rest),
+        Some((command, rest)) if command == "make-patch" => make_patch(rest),
+        Some((command, rest)) if command == "messages" => messages(rest),
+        Some((command, rest)) if command == "min-os" => min_os(rest),
+        Some((command, rest)) if command == "mingw" => mingw(rest),
+        Some((command, rest)) if command == "minidump" => minidump(rest),
+        Some((command, rest)) if command == "objdiff" => objdiff(rest),
+        Some((command, rest)) if command == "ordinal-coverage" => ordinal_coverage(rest),
+        Some((command, rest)) if command == "overlay" => overlay(rest),
+        Some((command, rest)) if command == "pdata" => pdata(rest),
+        Some((command, rest)) if command == "provenance" => provenance(rest),
+        Some((command, rest)) if command == "release-audit" => release_audit(rest),
+        Some((command, rest)) if command == "rename-sections" => rename_sections(rest),
+        Some((command, rest)) if command == "repro" => repro(rest),
+        Some((command, rest)) if command == "resolve" => resolve(rest),
+        Some((command, rest)) if command == "scan" => scan(rest),
+        Some((command, rest)) if command == "sections" => sections(rest),
+        Some((command, rest)) if command == "signature" => signature(rest),
+        Some((command, rest)) if command == "signing" => signing(rest),
+        Some((command, rest)) if command == "similarity" => similarity(rest),
+        Some((command, rest)) if command == "slack" => slack(rest),
+        Some((command, rest)) if command == "split" => split(rest),
+        Some((command, rest)) if command == "strip" => strip(rest),
+        Some((command, rest)) if command == "timeline" => timeline(rest),
+        Some((command, rest)) if command == "tls" => tls(rest),
+        Some((command, rest)) if command == "wow64" => wow64(rest),
+        _ => {
+            eprintln!("{}", USAGE);
+            2
+        }
+    };
+    process::exit(status);
+}
+
+fn open(path: &str) -> Result<PortExe, String> {
+    let mut file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    PortExe::parse(&mut file).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Opens a module dumped from memory, with sections at their virtual addresses.
+fn open_mapped(path: &str) -> Result<PortExe, String> {
+    let data = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    PortExe::from_mapped_bytes(data).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Settings of the user and project `pexp.toml` files for the working directory
+fn load_config() -> Result<Config, String> {
+    let dir = env::current_dir().map_err(|e| e.to_string())?;
+    Config::discover(&dir).map_err(|e| e.to_string())
+}
+
+/// Parses a decimal or `0x`-prefixed hexadecimal number.
+fn parse_number(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Writes an edited image to `path` through a temporary file next to it, so an
+/// interrupted write never leaves a truncated file behind.
+fn write_image(path: &str, data: &[u8]) -> Result<(), String> {
+    let error = |e: std::io::Error| format!("{}: {}", path, e);
+    let mut temporary = Path::new(path).as_os_str().to_owned();
+    temporary.push(".pexp-tmp");
+    let temporary = PathBuf::from(temporary);
+    fs::write(&temporary, data).map_err(error)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        let _ = fs::set_permissions(&temporary, metadata.permissions());
+    }
+    fs::rename(&temporary, path).map_err(|e| {
+        let _ = fs::remove_file(&temporary);
+        error(e)
+    })
+}
+
+fn is_output_flag(flag: &str) -> bool {
+    flag == "-o" || flag == "--output"
+}
+
+fn align(args: &[String]) -> i32 {
+    const ALIGN_USAGE: &str =
+        "usage: pexp align [--file-alignment <n>] [--pad-to <size>] [-o <output>] <file>";
+    let mut alignment = None;
+    let mut pad_to = None;
+    let mut output = None;
+    let mut rest = args;
+    loop {
+        match rest {
+            [flag, value, tail @ ..] if is_output_flag(flag) => {
+                output = Some(value);
+                rest = tail;
+            }
+            [flag, value, tail @ ..] if flag == "--file-alignment" => {
+                match parse_number(value).and_then(|value| u32::try_from(value).ok()) {
+                    Some(value) => alignment = Some(value),
+                    None => {
+                        eprintln!("{}", ALIGN_USAGE);
+                        return 2;
+                    }
+                }
+                rest = tail;
+            }
+            [flag, value, tail @ ..] if flag == "--pad-to" => {
+                match parse_number(value).and_then(|value| usize::try_from(value).ok()) {
+                    Some(value) => pad_to = Some(value),
+                    None => {
+                        eprintln!("{}", ALIGN_USAGE);
+                        return 2;
+                    }
+                }
+                rest = tail;
+            }
+            _ => break,
+        }
+    }
+    let path = match rest {
+        [path] if alignment.is_some() || pad_to.is_some() => path,
+        _ => {
+            eprintln!("{}", ALIGN_USAGE);
+            return 2;
+        }
+    };
+    let result = open(path).and_then(|mut pe| {
+        let error = |e: std::io::Error| format!("{}: {}", path, e);
+        let before = pe.data().len();
+        if let Some(alignment) = alignment {
+            pe.set_file_alignment(alignment).map_err(error)?;
+        }
+        if let Some(size) = pad_to {
+            pe.pad_to(size).map_err(error)?;
+        }
+        write_image(output.unwrap_or(path), pe.data())?;
+        Ok((before, pe.data().len()))
+    });
+    match result {
+        Ok((before, after)) => {
+            println!("{}: {} -> {} bytes", output.unwrap_or(path), before, after);
+            0
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            1
+        }
+    }
+}
+
+fn checksec(paths: &[String]) -> i32 {
+    if paths.is_empty() {
+        eprintln!("usage: pexp checksec <file...>");
+        return 2;
+    }
+    let mut status = 0;
+    println!(
+        "{:<5} {:<5} {:<5} {:<7} {:<5} {:<5} {:<6} {:<5} {:<6} {:<13} File",
+        "ASLR", "DEP", "CFG", "SafeSEH", "GS", "CET", "EHCONT", "XFG", "Signed", "HighEntropyVA"
+    );
+    for path in paths {
+        let result =
+            open(path).and_then(|pe| pe.checksec().map_err(|e| format!("{}: {}", path, e)));
+        match result {
+            Ok(checksec) => println!(
+                "{:<5} {:<5} {:<5} {:<7} {:<5} {:<5} {:<6} {:<5} {:<6} {:<13} {}",
+                checksec.aslr(),
+                checksec.dep(),
+                checksec.cfg(),
+                checksec.safe_seh(),
+                checksec.gs(),
+                checksec.cet(),
+                checksec.eh_continuation(),
+                checksec.xfg(),
+                checksec.signed(),
+                checksec.high_entropy_va(),
+                path
+            ),
+            Err(message) => {
+                eprintln!("{}", message);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+fn build_id(args: &[String]) -> i32 {
+    let (server, paths) = match args {
+        [flag, server, paths @ ..] if flag == "--server" => (Some(server), paths),
+        paths => (None, paths),
+    };
+    if paths.is_empty() || paths[0].starts_with("--") {
+        eprintln!("usage: pexp build-id [--server <url>] <file...>");
+        return 2;
+    }
+    let mut status = 0;
+    for path in paths {
+        let result = open(path).and_then(|pe| {
+            let ids = pe.build_ids().map_err(|e| format!("{}: {}", path, e))?;
+            let pdb = pe
+                .pdb_symbol_path()
+                .map_err(|e| format!("{}: {}", path, e))?;
+            Ok((ids, pe.image_symbol_path(&file_name(path)), pdb))
+        });
+        match result {
+            Ok((ids, image, pdb)) => {
+                if ids.is_empty() {
+                    println!("{}: no build ID", path);
+                }
+                for id in ids {
+                    println!("{}: {}", path, id);
+                }
+                for symbol_path in image.iter().chain(&pdb) {
+                    match server {
+                        Some(server) => println!(
+                            "{}: symbol server url {}/{}",
+                            path,
+                            server.trim_end_matches('/'),
+                            symbol_path
+                        ),
+                        None => println!("{}: symbol server path {}", path, symbol_path),
+                    }
+                }
+            }
+            Err(message) => {
+                eprintln!("{}", message);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+fn annotate(args: &[String]) -> i32 {
+    let config = match load_config() {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+    let (format, path) = match args {
+        [flag, format, path] if flag == "--format" => (Some(format.as_str()), path),
+        [path] if !path.starts_with("--") => (config.format(), path),
+        _ => {
+            eprintln!("usage: pexp annotate [--format ghidra|ida|x64dbg|windbg] <file>");
+            return 2;
+        }
+    };
+    let format = match format {
+        Some(name) => match label_format(name) {
+            Some(format) => format,
+            None => {
+                eprintln!("unknown format {}", name);
+                return 2;
+            }
+        },
+        None => {
+            eprintln!("no --format given and none set in pexp.toml");
+            return 2;
+        }
+    };
+    match open(path).and_then(|pe| pe.annotations().map_err(|e| format!("{}: {}", path, e))) {
+        Ok(annotations) => {
+            print!("{}", annotations.script(&file_name(path), format));
+            0
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            1
+        }
+    }
+}
+
+fn appcontainer(args: &[String]) -> i32 {
+    let config = match load_config() {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+    let (list, paths) = match args {
+        [flag, list, paths @ ..] if flag == "--allowlist" && !paths.is_empty() => {
+            (Some(Path::new(list)), paths)
+        }
+        [first, ..] if !first.starts_with("--") => (config.policy(), args),
+        _ => {
+            eprintln!("usage: pexp appcontainer [--allowlist <file>] <file...>");
+            return 2;
+        }
+    };
+    let allowlist = match list.map(|list| {
+        fs::read_to_string(list)
+            .and_then(|text| ApiAllowlist::parse(&text))
+            .map_err(|e| format!("{}: {}", list.display(), e))
+    }) {
+        Some(Ok(allowlist)) => Some(allowlist),
+        Some(Err(message)) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+        None => None,
+    };
+    let mut status = 0;
+    for path in paths {
+        let report = match open(path).and_then(|pe| {
+            pe.store_app_report(allowlist.as_ref())
+                .map_err(|e| format!("{}: {}", path, e))
+        }) {
+            Ok(report) => report,
+            Err(message) => {
+                eprintln!("{}", message);
+                status = 1;
+                continue;
+            }
+        };
+        let traits: Vec<String> = report.traits().iter().map(ToString::to_string).collect();
+        match traits.is_empty() {
+            true => println!("{}: no app platform traits", path),
+            false => println!("{}: {}", path, traits.join(", ")),
+        }
+        if !report.api_sets().is_empty() {
+            println!("  {} API sets", report.api_sets().len());
+        }
+        for found in report.disallowed() {
+            let import = found.import();
+            match (import.name(), import.ordinal()) {
+                (Some(name), _) => println!("  not allowed: {}!{}", found.dll(), name),
+                (None, Some(ordinal)) => println!("  not allowed: {}!#{}", found.dll(), ordinal),
+                (None, None) => println!("  not allowed: {}", found.dll()),
+            }
+            status = 1;
+        }
+    }
+    status
+}
+
+fn artifacts(args: &[String]) -> i32 {
+    let path = match args {
+        [path] => path,
+        _ => {
+            eprintln!("usage: pexp artifacts <file>");
+            return 2;
+        }
+    };
+    let pe = match open(path) {
+        Ok(pe) => pe,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+    let mut status = 0;
+    println!("{:<10} {:<10} {:<10} Value", "Kind", "Offset", "RVA");
+    for artifact in pe.artifacts() {
+        let artifact = match artifact {
+            Ok(artifact) => artifact,
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                status = 1;
+                continue;
+            }
+        };
+        let offset = artifact
+            .offset()
+            .map_or_else(|| "-".to_string(), |offset| format!("{:#x}", offset));
+        let rva = artifact
+            .rva()
+            .map_or_else(|| "-".to_string(), |rva| format!("{:#x}", rva));
+        let value = match artifact.kind() {
+            ArtifactKind::ImportName { dll } => format!("{}!{}", dll, artifact.value()),
+            ArtifactKind::VersionString { key } => format!("{}={}", key, artifact.value()),
+            _ => artifact.value().to_string(),
+        };
+        println!(
+            "{:<10} {:<10} {:<10} {}",
+            artifact.kind(),
+            offset,
+            rva,
+            value.escape_debug()
+        );
+    }
+    status
+}
+
+fn bindings(args: &[String]) -> i32 {
+    let (language, path) = match args {
+        [path] => (BindingLanguage::C, path),
+        [flag, lang, path] if flag == "--lang" && lang == "c" => (BindingLanguage::C, path),
+        [flag, lang, path] if flag == "--lang" && lang == "rust" => (BindingLanguage::Rust, path),
+        _ => {
+            eprintln!("usage: pexp bindings [--lang c|rust] <dll>");
+            return 2;
+        }
+    };
+    match open(path).and_then(|pe| {
+        pe.export_bindings(language)
+            .map_err(|e| format!("{}: {}", path, e))
+    }) {
+        Ok(bindings) => {
+            print!("{}", bindings);
+            0
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            1
+        }
+    }
+}
+
+fn dedup(args: &[String]) -> i32 {
+    const DEDUP_USAGE: &str = "usage: pexp dedup [--rewrite [-o <output>]] <file>";
+    let mut rewrite = false;
+    let mut output = None;
+    let mut rest = args;
+    let path = loop {
+        match rest {
+            [flag, tail @ ..] if flag == "--rewrite" => {
+                rewrite = true;
+                rest = tail;
+            }
+            [flag, value, tail @ ..] if rewrite && is_output_flag(flag) => {
+                output = Some(value);
+                rest = tail;
+            }
+            [path] => break path,
+            _ => {
+                eprintln!("{}", DEDUP_USAGE);
+                return 2;
+            }
+        }
+    };
+    let mut pe = match open(path) {
+        Ok(pe) => pe,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    let waste = match pe.resource_waste() {
+        Ok(waste) => waste,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            return 1;
+        }
+    };
+    for group in waste.duplicates() {
+        println!(
+            "{} copies of {} bytes, {} wasted:",
+            group.copies(),
+            group.size(),
+            group.wasted()
+        );
+        for entry in group.entries() {
+            println!(
+                "    {}/{}/{} at {:#x}",
+                resource_id(entry.type_id()),
+                resource_id(entry.name_id()),
+                resource_id(entry.language()),
+                entry.data_rva()
+            );
+        }
+    }
+    for padded in waste.padded() {
+        let entry = padded.entry();
+        println!(
+            "{}/{}/{} at {:#x}: {} of {} bytes are trailing zeros",
+            resource_id(entry.type_id()),
+            resource_id(entry.name_id()),
+            resource_id(entry.language()),
+            entry.data_rva(),
+            padded.padding(),
+            entry.size()
+        );
+    }
+    println!(
+        "{} bytes duplicated, {} bytes of padding",
+        waste.duplicate_bytes(),
+        waste.padding_bytes()
+    );
+    if !rewrite || waste.duplicates().is_empty() {
+        return 0;
+    }
+    let before = pe.data().len();
+    let output = output.unwrap_or(path);
+    let result = pe
+        .dedup_resources()
+        .map_err(|e| format!("{}: {}", path, e))
+        .and_then(|freed| write_image(output, pe.data()).map(|_| freed));
+    match result {
+        Ok(freed) => {
+            println!(
+                "{}: freed {} bytes of resource data, {} -> {} bytes",
+                output,
+                freed,
+                before,
+                pe.data().len()
+            );
+            0
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            1
+        }
+    }
+}
+
+fn resource_id(id: &ResourceId) -> String {
+    match id {
+        ResourceId::Id(id) => id.to_string(),
+        ResourceId::Name(name) => format!("\"{}\"", name),
+    }
+}
+
+fn def(args: &[String]) -> i32 {
+    let path = match args {
+        [path] => path,
+        _ => {
+            eprintln!("usage: pexp def <dll>");
+            return 2;
+        }
+    };
+    match open(path).and_then(|pe| {
+        pe.module_definition()
+            .map_err(|e| format!("{}: {}", path, e))
+    }) {
+        Ok(definition) => {
+            print!("{}", definition);
+            0
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            1
+        }
+    }
+}
+
+fn forwarders(args: &[String]) -> i32 {
+    const FORWARDERS_USAGE: &str = "usage: pexp forwarders [--max-depth <n>] <file...>";
+    let (max_depth, paths) = match args {
+        [flag, value, paths @ ..] if flag == "--max-depth" => match value.parse() {
+            Ok(max_depth) => (max_depth, paths),
+            Err(_) => {
+                eprintln!("{}", FORWARDERS_USAGE);
+                return 2;
+            }
+        },
+        paths => (MAX_FORWARDER_DEPTH, paths),
+    };
+    if paths.is_empty() {
+        eprintln!("{}", FORWARDERS_USAGE);
+        return 2;
+    }
+    let loader = match load_modules(paths) {
+        Ok(loader) => loader,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+
+    let mut status = 0;
+    for chain in loader.forwarder_chains(max_depth) {
+        let mut links = vec![format!("{}!{}", chain.module(), chain.export())];
+        links.extend(chain.forwarders().iter().cloned());
+        println!("{} => {}", links.join(" -> "), chain.outcome());
+        if chain.is_broken() {
+            status = 1;
+        }
+    }
+    status
+}
+
+fn harden(args: &[String]) -> i32 {
+    const HARDEN_USAGE: &str =
+        "usage: pexp harden [--dry-run] [--aslr] <file...>|-o <output> <file>";
+    let mut dry_run = false;
+    let mut aslr = false;
+    let mut output = None;
+    let mut paths = args;
+    loop {
+        match paths {
+            [flag, rest @ ..] if flag == "--dry-run" => {
+                dry_run = true;
+                paths = rest;
+            }
+            [flag, rest @ ..] if flag == "--aslr" => {
+                aslr = true;
+                paths = rest;
+            }
+            [flag, value, rest @ ..] if is_output_flag(flag) => {
+                output = Some(value);
+                paths = rest;
+            }
+            _ => break,
+        }
+    }
+    if paths.is_empty() || (output.is_some() && paths.len() != 1) {
+        eprintln!("{}", HARDEN_USAGE);
+        return 2;
+    }
+    let policy = HardenPolicy::default();
+    let mut status = 0;
+    for path in paths {
+        let result = open(path).and_then(|mut pe| {
+            let error = |e: std::io::Error| format!("{}: {}", path, e);
+            let changes = pe.harden_sections(&policy).map_err(error)?;
+            let aslr_enabled = aslr && pe.enable_aslr().map_err(error)?;
+            if !dry_run && (aslr_enabled || !changes.is_empty() || output.is_some()) {
+                write_image(output.unwrap_or(path), pe.data())?;
+            }
+            Ok((changes, aslr_enabled))
+        });
+        match result {
+            Ok((changes, aslr_enabled)) => {
+                for change in changes {
+                    println!(
+                        "{}: {} {:#010x} -> {:#010x}",
+                        path,
+                        change.name(),
+                        change.before(),
+                        change.after()
+                    );
+                }
+                if aslr_enabled {
+                    println!("{}: ASLR enabled", path);
+                }
+            }
+            Err(message) => {
+                eprintln!("{}", message);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+fn hexdump(args: &[String]) -> i32 {
+    const HEXDUMP_USAGE: &str = "usage: pexp hexdump [--at <address>] [--length <n>] <file>";
+    let mut at = None;
+    let mut length = 0x100;
+    let mut rest = args;
+    let path = loop {
+        match rest {
+            [flag, value, tail @ ..] if flag == "--at" => {
+                match AddressExpression::parse(value) {
+                    Ok(expression) => at = Some(expression),
+                    Err(e) => {
+                        eprintln!("{}: {}", value, e);
+                        return 2;
+                    }
+                }
+                rest = tail;
+            }
+            [flag, value, tail @ ..] if flag == "--length" => {
+                match parse_number(value).and_then(|value| usize::try_from(value).ok()) {
+                    Some(value) => length = value,
+                    None => {
+                        eprintln!("{}", HEXDUMP_USAGE);
+                        return 2;
+                    }
+                }
+                rest = tail;
+            }
+            [path] if !path.starts_with("--") => break path,
+            _ => {
+                eprintln!("{}", HEXDUMP_USAGE);
+                return 2;
+            }
+        }
+    };
+    let pe = match open(path) {
+        Ok(pe) => pe,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+    let resolver = pe.address_resolver();
+    let start = match at {
+        Some(expression) => {
+            let value = match expression.evaluate(&pe) {
+                Ok(value) => value,
+                Err(e) => {
+                    eprintln!("{}: {}", path, e);
+                    return 1;
+                }
+            };
+            match value.offset(&resolver) {
+                Some(offset) => {
+                    let rva = value
+                        .rva(&resolver)
+                        .map_or_else(|| "no RVA".to_string(), |rva| format!("rva {}", rva));
+                    println!("{} is offset {:#x}, {}", value, offset, rva);
+                    offset
+                }
+                None => {
+                    eprintln!("{}: {} has no file data", path, value);
+                    return 1;
+                }
+            }
+        }
+        None => 0,
+    };
+    let data = pe.data();
+    let end = start.saturating_add(length).min(data.len());
+    for (index, line) in data[start.min(end)..end].chunks(16).enumerate() {
+        let hex: Vec<String> = line.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let text: String = line
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        println!(
+            "{:08x}  {:<47}  |{}|",
+            start + index * 16,
+            hex.join(" "),
+            text
+        );
+    }
+    0
+}
+
+fn hooks(args: &[String]) -> i32 {
+    let (dump, original) = match args {
+        [dump, original] => (dump, original),
+        _ => {
+            eprintln!("usage: pexp hooks <dump> <original>");
+            return 2;
+        }
+    };
+    let hooks = match open_mapped(dump).and_then(|dump_pe| {
+        let original_pe = open(original)?;
+        dump_pe
+            .inline_hooks(&original_pe, DEFAULT_PROLOGUE_LENGTH)
+            .map_err(|e| format!("{}: {}", original, e))
+    }) {
+        Ok(hooks) => hooks,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+
+    println!("{:<10} {:<9} {:<18} Export", "RVA", "Kind", "Target");
+    for hook in &hooks {
+        println!(
+            "{:#010x} {:<9} {:<18} {}",
+            hook.rva(),
+            hook.kind(),
+            hook.target()
+                .map_or("-".to_string(), |target| format!("{:#x}", target)),
+            hook.name()
+                .map_or_else(|| format!("#{}", hook.ordinal()), ToString::to_string)
+        );
+    }
+    0
+}
+
+fn iat_map(args: &[String]) -> i32 {
+    let config = match load_config() {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+    let (format, path) = match args {
+        [path] => match config.format() {
+            Some(name) if label_format(name).is_none() => {
+                eprintln!("unknown format {}", name);
+                return 2;
+            }
+            name => (name.and_then(label_format), path),
+        },
+        [flag, format, path] if flag == "--format" && label_format(format).is_some() => {
+            (label_format(format), path)
+        }
+        _ => {
+            eprintln!("usage: pexp iat-map [--format ghidra|ida|x64dbg|windbg] <file>");
+            return 2;
+        }
+    };
+    let entries =
+        match open(path).and_then(|pe| pe.iat_map().map_err(|e| format!("{}: {}", path, e))) {
+            Ok(entries) => entries,
+            Err(message) => {
+                eprintln!("{}", message);
+                return 1;
+            }
+        };
+    match format {
+        Some(format) => {
+            let labels: Vec<Label> = entries.iter().map(|entry| entry.label()).collect();
+            print!("{}", label_script(&file_name(path), &labels, format));
+        }
+        None => {
+            for entry in entries {
+                println!(
+                    "{:#010x} {}!{}",
+                    entry.rva(),
+                    entry.target().module(),
+                    entry.target().symbol()
+                );
+            }
+        }
+    }
+    0
+}
+
+fn icon_hash(args: &[String]) -> i32 {
+    let mut known = Vec::new();
+    let mut rest = args;
+    while let [flag, hash, tail @ ..] = rest {
+        if flag != "--near" {
+            break;
+        }
+        match IconHash::parse(hash) {
+            Ok(hash) => known.push(hash),
+            Err(e) => {
+                eprintln!("{}", e);
+                return 2;
+            }
+        }
+        rest = tail;
+    }
+    if rest.is_empty() {
+        eprintln!("usage: pexp icon-hash [--near <hash>]... <file...>");
+        return 2;
+    }
+    let mut status = 0;
+    for path in rest {
+        let hash = match open(path)
+            .and_then(|pe| pe.icon_hash().map_err(|e| format!("{}: {}", path, e)))
+        {
+            Ok(Some(hash)) => hash,
+            Ok(None) => {
+                println!("{:<33}  {}", "-", path);
+                continue;
+            }
+            Err(message) => {
+                eprintln!("{}", message);
+                status = 1;
+                continue;
+            }
+        };
+        println!("{}  {}", hash, path);
+        for other in &known {
+            let distance = hash.distance(other);
+            if distance <= DEFAULT_ICON_DISTANCE {
+                println!("  looks like {} (distance {})", other, distance);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+fn imports(args: &[String]) -> i32 {
+    let mut ordinals = OrdinalMap::bundled();
+    let mut rest = args;
+    while let [flag, map, tail @ ..] = rest {
+        if flag != "--ordinals" {
+            break;
+        }
+        if let Err(message) = load_ordinals(&mut ordinals, map) {
+            eprintln!("{}", message);
+            return 1;
+        }
+        rest = tail;
+    }
+    let path = match rest {
+        [path] => path,
+        _ => {
+            eprintln!("usage: pexp imports [--ordinals <def|dll>]... <file>");
+            return 2;
+        }
+    };
+    match open(path).and_then(|pe| pe.all_imports().map_err(|e| format!("{}: {}", path, e))) {
+        Ok(descriptors) => {
+            for descriptor in &descriptors {
+                let delay_load = if descriptor.is_delay_loaded() {
+                    " (delay-load)"
+                } else {
+                    ""
+                };
+                for import in descriptor.imports() {
+                    println!(
+                        "{:#010x} {}!{}{}",
+                        import.iat_rva(),
+                        descriptor.dll_name(),
+                        ordinals.import_name(descriptor.dll_name(), import),
+                        delay_load
+                    );
+                }
+            }
+            0
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            1
+        }
+    }
+}
+
+fn ordinal_coverage(paths: &[String]) -> i32 {
+    if paths.is_empty() {
+        eprintln!("usage: pexp ordinal-coverage <file...>");
+        return 2;
+    }
+    let mut status = 0;
+    for path in paths {
+        let coverage = match open(path).and_then(|pe| {
+            pe.ordinal_coverage()
+                .map_err(|e| format!("{}: {}", path, e))
+        }) {
+            Ok(coverage) => coverage,
+            Err(message) => {
+                eprintln!("{}", message);
+                status = 1;
+                continue;
+            }
+        };
+        println!("{}:", path);
+        println!(
+            "  {:>7} {:>10} {:>8}  DLL",
+            "By name", "By ordinal", "Ordinal"
+        );
+        for dll in &coverage {
+            println!(
+                "  {:>7} {:>10} {:>7.0}%  {}{}",
+                dll.by_name(),
+                dll.by_ordinal(),
+                dll.ordinal_fraction() * 100.0,
+                dll.dll(),
+                if dll.is_suspicious() {
+                    " (system DLL imported only by ordinal)"
+                } else {
+                    ""
+                }
+            );
+        }
+        if coverage.iter().any(|dll| dll.is_suspicious()) {
+            status = 1;
+        }
+    }
+    status
+}
+
+/// Adds the ordinals of a module-definition file, or the exports of a DLL, to `ordinals`.
+fn load_ordinals(ordinals: &mut OrdinalMap, path: &str) -> Result<(), String> {
+    let data = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    if data.starts_with(b"MZ") {
+        let pe = PortExe::from_bytes(data).map_err(|e| format!("{}: {}", path, e))?;
+        let exports = pe.exports().map_err(|e| format!("{}: {}", path, e))?;
+        let dll = match pe.export_directory() {
+            Ok(Some(directory)) if !directory.name().is_empty() => directory.name().to_string(),
+            _ => Path::new(path).file_name().map_or_else(
+                || path.to_string(),
+                |name| name.to_string_lossy().into_owned(),
+            ),
+        };
+        ordinals.add_exports(&dll, &exports);
+        Ok(())
+    } else {
+        ordinals
+            .add_definition_text(&String::from_utf8_lossy(&data))
+            .map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+fn l10n(args: &[String]) -> i32 {
+    const L10N_USAGE: &str = "usage: pexp l10n [--reference <langid>] <file> [<mui>...]";
+    let (reference, paths) = match args {
+        [flag, value, paths @ ..] if flag == "--reference" => {
+            match parse_number(value).and_then(|value| u16::try_from(value).ok()) {
+                Some(value) => (Some(value), paths),
+                None => {
+                    eprintln!("{}", L10N_USAGE);
+                    return 2;
+                }
+            }
+        }
+        paths => (None, paths),
+    };
+    if paths.is_empty() {
+        eprintln!("{}", L10N_USAGE);
+        return 2;
+    }
+    let mut resources = LocalizedResources::new();
+    for path in paths {
+        let result =
+            open(path).and_then(|pe| resources.add(&pe).map_err(|e| format!("{}: {}", path, e)));
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            return 1;
+        }
+    }
+    let audit = match resources.audit(reference) {
+        Some(audit) => audit,
+        None => {
+            println!("no string tables or version information");
+            return 0;
+        }
+    };
+    let reference = audit.reference();
+    println!("reference language {:#06x}", reference);
+    for language in audit.languages() {
+        println!(
+            "{:#06x}: {} strings, {} missing, {} untranslated, {} extra",
+            language.language(),
+            language.strings(),
+            language.missing().len(),
+            language.untranslated().len(),
+            language.extra().len()
+        );
+        for &id in language.missing() {
+            let text = resources.string(reference, id).unwrap_or_default();
+            println!("    missing {:>5}: {:?}", id, text);
+        }
+        for &id in language.untranslated() {
+            let text = resources.string(reference, id).unwrap_or_default();
+            println!("    untranslated {:>5}: {:?}", id, text);
+        }
+        for key in language.missing_version_keys() {
+            println!("    missing version key {}", key);
+        }
+    }
+    if audit
+        .languages()
+        .iter()
+        .all(|language| language.is_complete())
+    {
+        0
+    } else {
+        1
+    }
+}
+
+fn clr(args: &[String]) -> i32 {
+    let path = match args {
+        [path] => path,
+        _ => {
+            eprintln!("usage: pexp clr <file>");
+            return 2;
+        }
+    };
+    let pe = match open(path) {
+        Ok(pe) => pe,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+    let header = match pe.clr_header() {
+        Some(header) => header,
+        None => {
+            println!("{}: no CLR header", path);
+            return 0;
+        }
+    };
+    let metadata = pe.metadata();
+    let location = |directory: ClrDirectory| match directory.size() {
+        0 => "none".to_string(),
+        size => match pe.rva_to_offset(directory.virtual_address()) {
+            Some(offset) => format!(
+                "rva {:#x}, {} bytes at offset {:#x}",
+                directory.virtual_address(),
+                size,
+                offset
+            ),
+            None => format!(
+                "rva {:#x}, {} bytes not in the file",
+                directory.virtual_address(),
+                size
+            ),
+        },
+    };
+    println!(
+        "Runtime:      {}.{}",
+        header.major_runtime_version(),
+        header.minor_runtime_version()
+    );
+    println!(
+        "Flags:        {:#010x} {}",
+        header.flags(),
+        header.flag_names().join(" ")
+    );
+    if header.native_entry_point() {
+        println!("Entry point:  native rva {:#x}", header.entry_point_token());
+    } else if let Some((table, row)) = header.entry_point_row() {
+        let name = metadata
+            .as_ref()
+            .ok()
+            .and_then(Option::as_ref)
+            .and_then(|metadata| metadata.method_name(header.entry_point_token()));
+        println!(
+            "Entry point:  {:#010x} ({} row {}{})",
+            header.entry_point_token(),
+            table_name(table).unwrap_or("unknown table"),
+            row,
+            name.map_or_else(String::new, |name| format!(", {}", name))
+        );
+    }
+    println!("Metadata:     {}", location(header.metadata()));
+    println!("Strong name:  {}", location(header.strong_name_signature()));
+    println!("Resources:    {}", location(header.resources()));
+    if header.vtable_fixups().size() != 0 {
+        println!("VTableFixups: {}", location(header.vtable_fixups()));
+    }
+    let metadata = match metadata {
+        Ok(Some(metadata)) => metadata,
+        Ok(None) => return 0,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            return 1;
+        }
+    };
+    println!("Version:      {}", metadata.version());
+    for stream in metadata.streams() {
+        println!(
+            "Stream:       {} at {:#x}, {} bytes",
+            stream.name(),
+            stream.offset(),
+            stream.size()
+        );
+    }
+    let (major, minor) = metadata.table_stream_version();
+    if (major, minor) != (0, 0) {
+        println!(
+            "Tables:       version {}.{}, heap sizes {:#04x}",
+            major,
+            minor,
+            metadata.heap_sizes()
+        );
+    }
+    for (table, rows) in metadata.tables() {
+        println!(
+            "  {:<24}{}",
+            table_name(table).unwrap_or("unknown table"),
+            rows
+        );
+    }
+    0
+}
+
+fn dwarf(args: &[String]) -> i32 {
+    let path = match args {
+        [path] => path,
+        _ => {
+            eprintln!("usage: pexp dwarf <file>");
+            return 2;
+        }
+    };
+    let summary = match open(path).map(|pe| pe.dwarf_summary()) {
+        Ok(Some(summary)) => summary,
+        Ok(None) => {
+            println!("{}: no DWARF sections", path);
+            return 0;
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+    for section in summary.sections() {
+        println!(
+            "Section:      {} ({} bytes{})",
+            section.name(),
+            section.size(),
+            if section.is_compressed() {
+                ", compressed"
+            } else {
+                ""
+            }
+        );
+    }
+    let versions: Vec<String> = summary
+        .versions()
+        .iter()
+        .map(|version| version.to_string())
+        .collect();
+    println!(
+        "Units:        {}{}",
+        summary.units().len(),
+        if summary.is_truncated() {
+            " (incomplete)"
+        } else {
+            ""
+        }
+    );
+    if !versions.is_empty() {
+        println!("Versions:     {}", versions.join(", "));
+    }
+    if summary.units().iter().any(|unit| unit.is_dwarf64()) {
+        println!("Format:       64-bit DWARF");
+    }
+    0
+}
+
+#[cfg(feature = "disassembler")]
+fn entry(paths: &[String]) -> i32 {
+    if paths.is_empty() {
+        eprintln!("usage: pexp entry <file...>");
+        return 2;
+    }
+    let mut status = 0;
+    for path in paths {
+        let trace = match open(path)
+            .and_then(|pe| pe.entry_trace().map_err(|e| format!("{}: {}", path, e)))
+        {
+            Ok(trace) => trace,
+            Err(message) => {
+                eprintln!("{}", message);
+                status = 1;
+                continue;
+            }
+        };
+        println!("{}: entry point {:#x}", path, trace.entry_point());
+        for hop in trace.hops() {
+            println!("  {:#x} -> {:#x} ({})", hop.from(), hop.to(), hop.kind());
+        }
+        println!("  probable start {:#x}", trace.start());
+    }
+    status
+}
+
+#[cfg(not(feature = "disassembler"))]
+fn entry(_paths: &[String]) -> i32 {
+    eprintln!("pexp was built without the disassembler feature");
+    1
+}
+
+fn exports(args: &[String]) -> i32 {
+    let path = match args {
+        [path] => path,
+        _ => {
+            eprintln!("usage: pexp exports <dll>");
+            return 2;
+        }
+    };
+    let exports =
+        match open(path).and_then(|pe| pe.exports().map_err(|e| format!("{}: {}", path, e))) {
+            Ok(exports) => exports,
+            Err(message) => {
+                eprintln!("{}", message);
+                return 1;
+            }
+        };
+    println!("{:>7} {:<10} {:<9} Name", "Ordinal", "RVA", "Kind");
+    let mut data = Vec::new();
+    for export in &exports {
+        let name = export
+            .name()
+            .map_or_else(|| format!("#{}", export.ordinal()), ToString::to_string);
+        match export.forwarder() {
+            Some(forwarder) => println!(
+                "{:>7} {:<10} {:<9} {} -> {}",
+                export.ordinal(),
+                "-",
+                export.kind(),
+                name,
+                forwarder
+            ),
+            None => println!(
+                "{:>7} {:#010x} {:<9} {}",
+                export.ordinal(),
+                export.rva(),
+                export.kind(),
+                name
+            ),
+        }
+        if export.kind() == ExportKind::Data {
+            data.push(name);
+        }
+    }
+    if !data.is_empty() {
+        println!(
+            "data exports, which importers must not call and import libraries must mark DATA: {}",
+            data.join(", ")
+        );
+    }
+    0
+}
+
+fn load_order(args: &[String]) -> i32 {
+    const LOAD_ORDER_USAGE: &str = "usage: pexp load-order [--known-dlls <file>] [--system <dir>] \
+[--windows <dir>] [--cwd <dir>] [--path <dir>]... [--redirect <dll>=<file>]... [--winsxs <dir>] \
+[--unsafe-search] [--check-hints] <exe>";
+    let mut known_dlls = None;
+    let mut system_dir = None;
+    let mut windows_dir = None;
+    let mut current_dir = None;
+    let mut path_dirs = Vec::new();
+    let mut redirects = BTreeMap::new();
+    let mut winsxs = None;
+    let mut safe_dll_search_mode = true;
+    let mut check_hints = false;
+    let config = match load_config() {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+    let mut rest = args;
+    let exe = loop {
+        match rest {
+            [flag, value, tail @ ..] if flag == "--known-dlls" => {
+                known_dlls = Some(value);
+                rest = tail;
+            }
+            [flag, value, tail @ ..] if flag == "--system" => {
+                system_dir = Some(PathBuf::from(value));
+                rest = tail;
+            }
+            [flag, value, tail @ ..] if flag == "--windows" => {
+                windows_dir = Some(PathBuf::from(value));
+                rest = tail;
+            }
+            [flag, value, tail @ ..] if flag == "--cwd" => {
+                current_dir = Some(PathBuf::from(value));
+                rest = tail;
+            }
+            [flag, value, tail @ ..] if flag == "--path" => {
+                path_dirs.push(PathBuf::from(value));
+                rest = tail;
+            }
+            [flag, value, tail @ ..] if flag == "--redirect" => match value.split_once('=') {
+                Some((dll, file)) if !dll.is_empty() && !file.is_empty() => {
+                    redirects.insert(dll.to_string(), PathBuf::from(file));
+                    rest = tail;
+                }
+                _ => {
+                    eprintln!("{}", LOAD_ORDER_USAGE);
+                    return 2;
+                }
+            },
+            [flag, value, tail @ ..] if flag == "--winsxs" => {
+                winsxs = Some(PathBuf::from(value));
+                rest = tail;
+            }
+            [flag, tail @ ..] if flag == "--unsafe-search" => {
+                safe_dll_search_mode = false;
+                rest = tail;
+            }
+            [flag, tail @ ..] if flag == "--check-hints" => {
+                check_hints = true;
+                rest = tail;
+            }
+            [exe] if !exe.starts_with("--") => break exe,
+            _ => {
+                eprintln!("{}", LOAD_ORDER_USAGE);
+                return 2;
+            }
+        }
+    };
+
+    let application_dir = Path::new(exe)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let mut search = SearchOrder::new(application_dir);
+    search.system_dir = system_dir;
+    search.windows_dir = windows_dir;
+    search.current_dir = current_dir;
+    // Directories set in pexp.toml come after those given on the command line
+    path_dirs.extend(config.search_paths().iter().cloned());
+    search.path = path_dirs;
+    search.redirects = redirects;
+    search.safe_dll_search_mode = safe_dll_search_mode;
+    if let Some(list) = known_dlls {
+        match fs::read_to_string(list) {
+            // One name per line, as exported from the registry key
+            Ok(text) => {
+                search.known_dlls = text
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }
+            Err(e) => {
+                eprintln!("{}: {}", list, e);
+                return 1;
+            }
+        }
+    }
+
+    let name = Path::new(exe)
+        .file_name()
+        .map_or_else(|| exe.clone(), |name| name.to_string_lossy().into_owned());
+    let pe = match open(exe) {
+        Ok(pe) => pe,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+    let mut status = 0;
+    if let Some(winsxs) = &winsxs {
+        // Manifest redirects apply before the search path; --redirect overrides them
+        let side_by_side = pe.manifest().and_then(|manifest| match manifest {
+            Some(manifest) => SideBySide::resolve(&manifest, &search.application_dir, Some(winsxs)),
+            None => Ok(SideBySide::default()),
+        });
+        let side_by_side = match side_by_side {
+            Ok(side_by_side) => side_by_side,
+            Err(e) => {
+                eprintln!("{}: {}", exe, e);
+                return 1;
+            }
+        };
+        for assembly in side_by_side.assemblies() {
+            match assembly.directory() {
+                Some(directory) => println!(
+                    "assembly {} in {}",
+                    assembly.identity(),
+                    directory.display()
+                ),
+                None => {
+                    println!("assembly {} not found", assembly.identity());
+                    status = 1;
+                }
+            }
+        }
+        for (dll, path) in side_by_side.redirects() {
+            search
+                .redirects
+                .entry(dll.clone())
+                .or_insert_with(|| path.clone());
+        }
+    }
+    let mut loader = VirtualLoader::new();
+    let order = match loader
+        .load_at_preferred_base(&name, pe)
+        .map(|_| ())
+        .and_then(|_| loader.load_dependencies(&search))
+    {
+        Ok(order) => order,
+        Err(e) => {
+            eprintln!("{}: {}", exe, e);
+            return 1;
+        }
+    };
+
+    for binding in order.bindings() {
+        let source = binding
+            .source()
+            .map_or_else(|| "not found".to_string(), |source| source.to_string());
+        match binding.path() {
+            Some(path) => println!("{:<32} {:<22} {}", binding.name(), source, path.display()),
+            None => println!("{:<32} {}", binding.name(), source),
+        }
+        if binding.is_hijackable() {
+            let directories: Vec<String> = binding
+                .planting_directories()
+                .iter()
+                .map(|dir| dir.display().to_string())
+                .collect();
+            println!(
+                "  hijackable via {} (imported by {})",
+                directories.join(", "),
+                binding.importer()
+            );
+            status = 1;
+        } else if binding.is_missing() {
+            status = 1;
+        }
+    }
+    for warning in order.warnings() {
+        println!("warning: {}", warning);
+    }
+    if check_hints {
+        match loader.stale_hints() {
+            Ok(stale) => {
+                for hint in &stale {
+                    println!("stale hint: {}", hint);
+                }
+                if !stale.is_empty() {
+                    status = 1;
+                }
+            }
+            Err(e) => {
+                eprintln!("{}: {}", exe, e);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+fn minidump(args: &[String]) -> i32 {
+    let path = match args {
+        [path] => path,
+        _ => {
+            eprintln!("usage: pexp minidump <dump>");
+            return 2;
+        }
+    };
+    let dump = match fs::read(path).and_then(Minidump::parse) {
+        Ok(dump) => dump,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            return 1;
+        }
+    };
+    let mut status = 0;
     println!(
-        "First two bytes: {:X} {:X}\n",
-        first_two_bytes[0], first_two_bytes[1]
+        "{:<18} {:<10} {:<10} {:<17} Module",
+        "Base", "Size", "Captured", "Machine"
     );
-    if first_two_bytes == [b'M', b'Z'] {
-        let _ = pe_reader.seek(SeekFrom::Start(0x3C));
-        let mut pe_header_addr = [0u8; 4];
-        let _ = pe_reader.read_exact(&mut pe_header_addr);
-        let _ = pe_reader.seek(SeekFrom::Start(u32::from_le_bytes(pe_header_addr) as u64));
-        let mut image_signature = [0u8; 4];
-        let _ = pe_reader.read_exact(&mut image_signature);
+    for report in dump.module_reports() {
+        let module = report.module();
+        let machine = match report.image() {
+            Ok(pe) => format!("{:?}", pe.file_header().machine().into_value()),
+            Err(_) => "-".to_string(),
+        };
+        println!(
+            "{:#018x} {:<#10x} {:<#10x} {:<17} {}",
+            module.base(),
+            module.size(),
+            report.captured(),
+            machine,
+            module.name()
+        );
+        if let Err(e) = report.image() {
+            println!("  error: {}", e);
+            status = 1;
+        }
+        for warning in report.warnings() {
+            println!("  warning: {}", warning);
+            status = 1;
+        }
+    }
+    status
+}
+
+fn messages(args: &[String]) -> i32 {
+    match args {
+        [] => {
+            println!(
+                "# Message catalog for locale {}",
+                Catalog::english().locale()
+            );
+            for (key, template) in Catalog::english().iter() {
+                println!("{} = {}", key, template);
+            }
+            0
+        }
+        [path] => match Catalog::load(Path::new(path)) {
+            Ok(catalog) => {
+                let missing = catalog.missing();
+                for key in &missing {
+                    println!("{}: no template for {}", path, key);
+                }
+                !missing.is_empty() as i32
+            }
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                1
+            }
+        },
+        _ => {
+            eprintln!("usage: pexp messages [<catalog>]");
+            2
+        }
+    }
+}
+
+fn min_os(args: &[String]) -> i32 {
+    let mut map = ApiVersionMap::bundled();
+    let paths = match args {
+        [flag, list, paths @ ..] if flag == "--apis" && !paths.is_empty() => {
+            if let Err(error) = fs::read_to_string(list).and_then(|text| map.add_text(&text)) {
+                eprintln!("{}: {}", list, error);
+                return 1;
+            }
+            paths
+        }
+        [flag, ..] if flag == "--apis" => &[],
+        paths => paths,
+    };
+    if paths.is_empty() {
+        eprintln!("usage: pexp min-os [--apis <file>] <file...>");
+        return 2;
+    }
+    let mut status = 0;
+    for path in paths {
+        let minimum = match open(path)
+            .and_then(|pe| pe.minimum_os(&map).map_err(|e| format!("{}: {}", path, e)))
+        {
+            Ok(Some(minimum)) => minimum,
+            Ok(None) => {
+                println!("{}: object file", path);
+                continue;
+            }
+            Err(message) => {
+                eprintln!("{}", message);
+                status = 1;
+                continue;
+            }
+        };
+        println!(
+            "{}: needs {}; header declares subsystem {} and OS {}",
+            path,
+            minimum.minimum(),
+            minimum.subsystem_version(),
+            minimum.os_version()
+        );
+        for import in minimum.blocking() {
+            let function = import.function().unwrap_or("by ordinal");
+            println!(
+                "  {:<28} {}!{}",
+                import.version().to_string(),
+                import.dll(),
+                function
+            );
+        }
+        for import in minimum.delay_loaded() {
+            let function = import.function().unwrap_or("by ordinal");
+            println!(
+                "  {:<28} {}!{} (delay-loaded)",
+                import.version().to_string(),
+                import.dll(),
+                function
+            );
+        }
+        if minimum.is_understated() {
+            status = 1;
+        }
+    }
+    status
+}
+
+fn make_patch(args: &[String]) -> i32 {
+    let (old_path, new_path, patch_path) = match args {
+        [old, new, patch] => (old, new, patch),
+        _ => {
+            eprintln!("usage: pexp make-patch <old> <new> <patch>");
+            return 2;
+        }
+    };
+    let result = open(old_path).and_then(|old| {
+        let new = open(new_path)?;
+        let patch = Patch::between(&old, &new).to_bytes();
+        fs::write(patch_path, &patch).map_err(|e| format!("{}: {}", patch_path, e))?;
+        Ok((patch.len(), new.data().len()))
+    });
+    match result {
+        Ok((patch, new)) => {
+            println!("{}: {} bytes patching to {} bytes", patch_path, patch, new);
+            0
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            1
+        }
+    }
+}
+
+fn apply_patch(args: &[String]) -> i32 {
+    let (old_path, patch_path, out_path) = match args {
+        [old, patch, out] => (old, patch, out),
+        _ => {
+            eprintln!("usage: pexp apply-patch <old> <patch> <out>");
+            return 2;
+        }
+    };
+    let result = fs::read(old_path)
+        .map_err(|e| format!("{}: {}", old_path, e))
+        .and_then(|old| {
+            let patch = fs::read(patch_path)
+                .and_then(|data| Patch::parse(&data))
+                .map_err(|e| format!("{}: {}", patch_path, e))?;
+            patch
+                .apply(&old)
+                .map_err(|e| format!("{}: {}", old_path, e))
+        })
+        .and_then(|new| fs::write(out_path, new).map_err(|e| format!("{}: {}", out_path, e)));
+    match result {
+        Ok(()) => 0,
+        Err(message) => {
+            eprintln!("{}", message);
+            1
+        }
+    }
+}
+
+fn mingw(args: &[String]) -> i32 {
+    let path = match args {
+        [path] => path,
+        _ => {
+            eprintln!("usage: pexp mingw <file>");
+            return 2;
+        }
+    };
+    let sections = match open(path)
+        .and_then(|pe| pe.mingw_sections().map_err(|e| format!("{}: {}", path, e)))
+    {
+        Ok(sections) => sections,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+    if sections.is_empty() {
+        println!("{}: no GNU toolchain structures", path);
+        return 0;
+    }
+    for span in sections.idata() {
+        let rva = span.rva();
+        println!(
+            "Import data:  {:<9} {:#010x}-{:#010x} ({:?})",
+            span.piece().grouped_section(),
+            rva.start,
+            rva.end,
+            span.piece()
+        );
+    }
+    for list in sections.crt() {
+        let kind = list
+            .kind()
+            .map_or_else(|| "unnamed list".to_string(), |kind| format!("{:?}", kind));
+        let entries: Vec<String> = list
+            .entries()
+            .iter()
+            .map(|entry| format!("{:#x}", entry))
+            .collect();
+        println!(
+            "CRT:          {} at {:#x}: {}",
+            kind,
+            list.rva(),
+            entries.join(", ")
+        );
+    }
+    if !sections.tls_callbacks().is_empty() {
+        let callbacks: Vec<String> = sections
+            .tls_callbacks()
+            .iter()
+            .map(|callback| format!("{:#x}", callback))
+            .collect();
+        println!("TLS:          {}", callbacks.join(", "));
+    }
+    if let Some(build_id) = sections.build_id() {
+        let id: String = build_id.id().iter().map(|b| format!("{:02x}", b)).collect();
+        println!("Build ID:     {} (age {})", id, build_id.age());
+    }
+    for section in sections.dwarf() {
+        println!(
+            "DWARF:        {} ({} bytes{})",
+            section.name(),
+            section.size(),
+            if section.is_compressed() {
+                ", compressed"
+            } else {
+                ""
+            }
+        );
+    }
+    0
+}
+
+fn rename_sections(args: &[String]) -> i32 {
+    const RENAME_USAGE: &str = "usage: pexp rename-sections [--dry-run] [--long-names] \
+[-o <output>] --normalize|--random <seed>|<old>=<new>... <file>";
+    let mut dry_run = false;
+    let mut long_names = LongNames::Refuse;
+    let mut normalize = false;
+    let mut seed = None;
+    let mut renames = Vec::new();
+    let mut output = None;
+    let mut rest = args;
+    let path = loop {
+        match rest {
+            [flag, value, tail @ ..] if is_output_flag(flag) => {
+                output = Some(value);
+                rest = tail;
+            }
+            [flag, tail @ ..] if flag == "--dry-run" => {
+                dry_run = true;
+                rest = tail;
+            }
+            [flag, tail @ ..] if flag == "--long-names" => {
+                long_names = LongNames::StringTable;
+                rest = tail;
+            }
+            [flag, tail @ ..] if flag == "--normalize" => {
+                normalize = true;
+                rest = tail;
+            }
+            [flag, value, tail @ ..] if flag == "--random" => match parse_number(value) {
+                Some(value) => {
+                    seed = Some(value);
+                    rest = tail;
+                }
+                None => {
+                    eprintln!("{}", RENAME_USAGE);
+                    return 2;
+                }
+            },
+            [path] if !path.starts_with("--") => break path,
+            [rename, tail @ ..] => match rename.split_once('=') {
+                Some((old, new)) if !old.is_empty() => {
+                    renames.push((old.to_string(), new.to_string()));
+                    rest = tail;
+                }
+                _ => {
+                    eprintln!("{}", RENAME_USAGE);
+                    return 2;
+                }
+            },
+            [] => {
+                eprintln!("{}", RENAME_USAGE);
+                return 2;
+            }
+        }
+    };
+    if (normalize as usize + seed.is_some() as usize + !renames.is_empty() as usize) != 1 {
+        eprintln!("{}", RENAME_USAGE);
+        return 2;
+    }
+    let result = open(path).and_then(|mut pe| {
+        let names = if normalize {
+            pe.normalized_section_names()
+        } else if let Some(seed) = seed {
+            pe.random_section_names(seed)
+        } else {
+            let mut names = Vec::new();
+            for (old, new) in &renames {
+                let index = match old.strip_prefix('#') {
+                    Some(index) => index.parse::<usize>().ok(),
+                    None => pe
+                        .section_headers()
+                        .iter()
+                        .position(|section| pe.section_name(section) == old.as_str()),
+                }
+                .ok_or_else(|| format!("{}: no section {}", path, old))?;
+                names.push((index, new.clone()));
+            }
+            names
+        };
+        let changes = pe
+            .rename_sections(&names, long_names)
+            .map_err(|e| format!("{}: {}", path, e))?;
+        if !dry_run && (!changes.is_empty() || output.is_some()) {
+            write_image(output.unwrap_or(path), pe.data())?;
+        }
+        Ok(changes)
+    });
+    match result {
+        Ok(changes) => {
+            for change in changes {
+                println!(
+                    "{}: {} -> {}",
+                    path,
+                    change.before().to_string_lossy(),
+                    change.after()
+                );
+            }
+            0
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            1
+        }
+    }
+}
+
+fn repro(args: &[String]) -> i32 {
+    let (before, after) = match args {
+        [before, after] => (before, after),
+        _ => {
+            eprintln!("usage: pexp repro <before> <after>");
+            return 2;
+        }
+    };
+    let comparison = match open(before).and_then(|before_pe| {
+        let after_pe = open(after)?;
+        compare_builds(&before_pe, &after_pe).map_err(|e| format!("{}: {}", after, e))
+    }) {
+        Ok(comparison) => comparison,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+
+    let ignored: Vec<String> = comparison
+        .ignored()
+        .iter()
+        .map(|field| field.to_string())
+        .collect();
+    if !ignored.is_empty() {
+        println!("ignored: {}", ignored.join(", "));
+    }
+    if comparison.before_size() != comparison.after_size() {
+        println!(
+            "size: {:#x} -> {:#x}",
+            comparison.before_size(),
+            comparison.after_size()
+        );
+    }
+    for change in comparison.changes() {
+        let range = change.range();
+        println!(
+            "{:#010x}..{:#010x} {:>8} bytes  {}{}",
+            range.start,
+            range.end,
+            range.len(),
+            change.region(),
+            if change.is_zero_padding() {
+                " (null padding)"
+            } else {
+                ""
+            }
+        );
+    }
+    if comparison.is_reproducible() {
+        println!("reproducible");
+        0
+    } else {
+        if comparison.differs_only_in_padding() {
+            println!("builds differ only in null padding");
+        }
+        1
+    }
+}
+
+fn objdiff(args: &[String]) -> i32 {
+    let (before, after) = match args {
+        [before, after] => (before, after),
+        _ => {
+            eprintln!("usage: pexp objdiff <before> <after>");
+            return 2;
+        }
+    };
+    let diff = match open(before).and_then(|before_pe| {
+        let after_pe = open(after)?;
+        diff_objects(&before_pe, &after_pe).map_err(|e| format!("{}: {}", after, e))
+    }) {
+        Ok(diff) => diff,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+
+    println!(
+        "{:>10} {:>10} {:>8} {:>6}  Section",
+        "Before", "After", "Delta", "Relocs"
+    );
+    for section in diff.sections().iter().filter(|s| s.is_changed()) {
+        println!(
+            "{:>10} {:>10} {:>+8} {:>+6}  {}",
+            section
+                .before()
+                .map_or("-".to_string(), |s| s.size().to_string()),
+            section
+                .after()
+                .map_or("-".to_string(), |s| s.size().to_string()),
+            section.size_delta(),
+            section.relocation_delta(),
+            section.key()
+        );
+    }
+    println!();
+    println!("{:>10} {:>10} {:>8}  Symbol", "Before", "After", "Delta");
+    for symbol in diff.symbols().iter().filter(|s| s.is_changed()) {
+        println!(
+            "{:>10} {:>10} {:>+8}  {}",
+            symbol
+                .before()
+                .map_or("-".to_string(), |s| s.size().to_string()),
+            symbol
+                .after()
+                .map_or("-".to_string(), |s| s.size().to_string()),
+            symbol.size_delta(),
+            symbol.name()
+        );
+    }
+    0
+}
+
+fn codegen(args: &[String]) -> i32 {
+    let path = match args {
+        [path] => path,
+        [flag, lang, path] if flag == "--lang" && lang == "rust" => path,
+        _ => {
+            eprintln!("usage: pexp codegen [--lang rust] <file>");
+            return 2;
+        }
+    };
+    match open(path).and_then(|pe| {
+        pe.codegen(CodegenLanguage::Rust)
+            .map_err(|e| format!("{}: {}", path, e))
+    }) {
+        Ok(code) => {
+            print!("{}", code);
+            0
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            1
+        }
+    }
+}
+
+fn comdat(args: &[String]) -> i32 {
+    let path = match args {
+        [path] => path,
+        _ => {
+            eprintln!("usage: pexp comdat <obj>");
+            return 2;
+        }
+    };
+    let comdats =
+        match open(path).and_then(|pe| pe.comdats().map_err(|e| format!("{}: {}", path, e))) {
+            Ok(comdats) => comdats,
+            Err(message) => {
+                eprintln!("{}", message);
+                return 1;
+            }
+        };
+    println!(
+        "{:>7} {:<14} {:>8}  {:<10} Symbol",
+        "Section", "Selection", "Length", "Name"
+    );
+    for comdat in comdats {
+        let symbol = match (comdat.symbol(), comdat.associated_section()) {
+            (_, Some(section)) => format!(
+                "-> section {} ({})",
+                section,
+                comdat.associated_symbol().unwrap_or("?")
+            ),
+            (Some(symbol), None) => symbol.to_string(),
+            (None, None) => "-".to_string(),
+        };
+        println!(
+            "{:>7} {:<14} {:>8}  {:<10} {}",
+            comdat.section_number(),
+            format!("{:?}", comdat.selection()),
+            comdat.length(),
+            comdat.section_name(),
+            symbol
+        );
+    }
+    0
+}
+
+fn config(args: &[String]) -> i32 {
+    if !args.is_empty() {
+        eprintln!("usage: pexp config");
+        return 2;
+    }
+    let config = match load_config() {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+    if config.sources().is_empty() {
+        println!("no pexp.toml found, using the defaults");
+    }
+    for source in config.sources() {
+        println!("read {}", source.display());
+    }
+    println!("format       {}", config.format().unwrap_or("-"));
+    println!(
+        "analyses     {}",
+        config
+            .analyses()
+            .map_or("-".to_string(), |names| names.join(","))
+    );
+    for path in config.search_paths() {
+        println!("search-path  {}", path.display());
+    }
+    println!(
+        "policy       {}",
+        config
+            .policy()
+            .map_or("-".to_string(), |path| path.display().to_string())
+    );
+    println!(
+        "messages     {}",
+        config
+            .messages()
+            .map_or("-".to_string(), |path| path.display().to_string())
+    );
+    println!("color        {}", config.color());
+    0
+}
+
+fn crosscheck(args: &[String]) -> i32 {
+    if args.is_empty() {
+        eprintln!("usage: pexp crosscheck <file...>");
+        return 2;
+    }
+    let mut status = 0;
+    for path in args {
+        let check = match open(path)
+            .and_then(|pe| pe.cross_check().map_err(|e| format!("{}: {}", path, e)))
+        {
+            Ok(check) => check,
+            Err(message) => {
+                eprintln!("{}", message);
+                status = 1;
+                continue;
+            }
+        };
+        let cfg = check.cfg_targets().map_or_else(
+            || "no CFG".to_string(),
+            |count| format!("{} CFG targets", count),
+        );
+        println!(
+            "{}: {} functions, {} address-taken, {}",
+            path,
+            check.functions(),
+            check.address_taken(),
+            cfg
+        );
+        for finding in check.findings() {
+            println!("  {}", finding);
+            status = 1;
+        }
+    }
+    status
+}
+
+fn cvinfo(args: &[String]) -> i32 {
+    let path = match args {
+        [path] => path,
+        _ => {
+            eprintln!("usage: pexp cvinfo <obj>");
+            return 2;
+        }
+    };
+    let info = match open(path).and_then(|pe| {
+        pe.object_debug_info()
+            .map_err(|e| format!("{}: {}", path, e))
+    }) {
+        Ok(Some(info)) => info,
+        Ok(None) => {
+            println!("{}: no CodeView sections", path);
+            return 0;
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+    if let Some(object_name) = info.object_name() {
+        println!("Object:       {}", object_name);
+    }
+    for compiler in info.compilers() {
+        let [fe_major, fe_minor, fe_build, fe_qfe] = compiler.frontend_version();
+        let [be_major, be_minor, be_build, be_qfe] = compiler.backend_version();
+        println!(
+            "Compiler:     {} (front end {}.{}.{}.{}, back end {}.{}.{}.{}, language {})",
+            compiler.version(),
+            fe_major,
+            fe_minor,
+            fe_build,
+            fe_qfe,
+            be_major,
+            be_minor,
+            be_build,
+            be_qfe,
+            compiler.language()
+        );
+    }
+    for source_file in info.source_files() {
+        println!("Source:       {}", source_file);
+    }
+    match info.type_server() {
+        Some(pdb) => println!("Types:        in {}", pdb),
+        None if info.type_records() > 0 => {
+            println!("Types:        {} records", info.type_records())
+        }
+        None => println!("Types:        none"),
+    }
+    if info.precompiled_types() {
+        println!("Precompiled:  yes");
+    }
+    0
+}
+
+fn overlay(args: &[String]) -> i32 {
+    if args.is_empty() {
+        eprintln!("usage: pexp overlay <file...>");
+        return 2;
+    }
+    let sniffers = OverlaySniffers::default();
+    let mut status = 0;
+    for path in args {
+        let pe = match open(path) {
+            Ok(pe) => pe,
+            Err(message) => {
+                eprintln!("{}", message);
+                status = 1;
+                continue;
+            }
+        };
+        if pe.overlay().is_empty() {
+            println!("{}: no overlay", path);
+            continue;
+        }
+        println!(
+            "{}: {} bytes of overlay at {:#x}",
+            path,
+            pe.overlay().len(),
+            pe.overlay_offset()
+        );
+        for found in pe.sniff_overlay(&sniffers) {
+            println!("  {:#010x}  {}", found.offset(), found.format());
+        }
+    }
+    status
+}
+
+fn pdata(args: &[String]) -> i32 {
+    let (unwind, path) = match args {
+        [flag, path] if flag == "--unwind" => (true, path),
+        [path] => (false, path),
+        _ => {
+            eprintln!("usage: pexp pdata [--unwind] <file>");
+            return 2;
+        }
+    };
+    let result = open(path).and_then(|pe| {
+        let functions = pe
+            .runtime_functions()
+            .map_err(|e| format!("{}: {}", path, e))?;
+        Ok((pe, functions))
+    });
+    let (pe, functions) = match result {
+        Ok(result) => result,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+    let mut status = 0;
+    for function in &functions {
+        let range = match function.end_address() {
+            Some(end) => format!("{:#010x}-{:#010x}", function.begin_address(), end),
+            None => format!("{:#010x}-?", function.begin_address()),
+        };
+        let data = match function {
+            RuntimeFunction::X64(function) => {
+                format!("unwind {:#010x}", function.unwind_info_rva())
+            }
+            RuntimeFunction::Arm(function) => match function.unwind() {
+                ArmUnwind::Packed(_) => "packed".to_string(),
+                ArmUnwind::Unpacked {
+                    unwind_data_rva, ..
+                } => format!("xdata {:#010x}", unwind_data_rva),
+            },
+            RuntimeFunction::Arm64(function) => match function.unwind() {
+                Arm64Unwind::Packed(_) => "packed".to_string(),
+                Arm64Unwind::Unpacked {
+                    unwind_data_rva, ..
+                } => format!("xdata {:#010x}", unwind_data_rva),
+            },
+        };
+        println!("{} {}", range, data);
+        let function = match function {
+            RuntimeFunction::X64(function) if unwind => function,
+            _ => continue,
+        };
+        let info = match pe.unwind_info(function) {
+            Ok(info) => info,
+            Err(e) => {
+                eprintln!("{}: {:#x}: {}", path, function.begin_address(), e);
+                status = 1;
+                continue;
+            }
+        };
+        let mut summary = vec![format!("prolog {:#x}", info.size_of_prolog())];
+        if let Some(register) = info.frame_register() {
+            summary.push(format!("frame {}+{:#x}", register, info.frame_offset()));
+        }
+        if let Some(handler) = info.handler_rva() {
+            summary.push(format!("handler {:#x}", handler));
+        }
+        if let Some(chained) = info.chained() {
+            summary.push(format!("chained to {:#x}", chained.begin_address()));
+        }
+        println!("  {}", summary.join(", "));
+        for code in info.codes() {
+            println!("  {:#04x} {}", code.prolog_offset(), code.operation());
+        }
+    }
+    status
+}
+
+fn provenance(args: &[String]) -> i32 {
+    if args.is_empty() {
+        eprintln!("usage: pexp provenance <file...>");
+        return 2;
+    }
+    let mut status = 0;
+    for path in args {
+        let pe = match open(path) {
+            Ok(pe) => pe,
+            Err(message) => {
+                eprintln!("{}", message);
+                status = 1;
+                continue;
+            }
+        };
+        let provenance = pe.provenance();
+        if provenance.is_empty() {
+            println!("{}: no known runtime", path);
+        }
+        if let Some(project) = provenance.visual_basic() {
+            let code = match project.native_code() {
+                Some(true) => "native code",
+                Some(false) => "P-Code",
+                None => "unknown code",
+            };
+            println!(
+                "{}: Visual Basic project {} ({}, {}, {} forms)",
+                path,
+                project.project_name().unwrap_or("(unnamed)"),
+                project.runtime(),
+                code,
+                project.form_count()
+            );
+            if !project.objects().is_empty() {
+                println!("  objects: {}", project.objects().join(", "));
+            }
+        }
+        if let Some(delphi) = provenance.delphi() {
+            println!("{}: Delphi or C++Builder {:?}", path, delphi.markers());
+            if !delphi.required_packages().is_empty() {
+                println!("  packages: {}", delphi.required_packages().join(", "));
+            }
+            if !delphi.units().is_empty() {
+                println!("  units: {}", delphi.units().join(", "));
+            }
+            for form in delphi.forms() {
+                println!("  form {}: {}", form.name(), form.class_name());
+            }
+        }
+        if let Some(script) = provenance.autoit() {
+            println!(
+                "{}: AutoIt script {} ({} bytes at {:#x} in {})",
+                path,
+                script.format(),
+                script.size(),
+                script.offset(),
+                if script.resource() {
+                    "resource"
+                } else {
+                    "overlay"
+                }
+            );
+        }
+        if let Some(archive) = provenance.pyinstaller() {
+            let (major, minor) = archive.python_version();
+            println!(
+                "{}: PyInstaller archive for Python {}.{} ({} bytes at {:#x})",
+                path,
+                major,
+                minor,
+                archive.size(),
+                archive.offset()
+            );
+            for entry in archive.entries() {
+                println!(
+                    "  {} {:>10} {}",
+                    entry.kind(),
+                    entry.uncompressed_size(),
+                    entry.name()
+                );
+            }
+        }
+        if let Some(nsis) = provenance.nsis() {
+            println!(
+                "{}: NSIS {} data at {:#x} ({} bytes, {}{}, header {} bytes)",
+                path,
+                if nsis.uninstaller() {
+                    "uninstaller"
+                } else {
+                    "installer"
+                },
+                nsis.offset(),
+                nsis.data_size(),
+                if nsis.solid() { "solid " } else { "" },
+                nsis.compression(),
+                nsis.header_size()
+            );
+        }
+        if let Some(inno) = provenance.inno_setup() {
+            println!(
+                "{}: Inno Setup {} loader, setup data at {:#x}, files at {:#x}",
+                path,
+                inno.loader_version(),
+                inno.setup_offset(),
+                inno.data_offset()
+            );
+            if let Some(id) = inno.setup_id() {
+                println!("  {}", id);
+            }
+        }
+    }
+    status
+}
+
+fn release_audit(args: &[String]) -> i32 {
+    let root = match args {
+        [root] => Path::new(root),
+        _ => {
+            eprintln!("usage: pexp release-audit <dir>");
+            return 2;
+        }
+    };
+    let mut paths = Vec::new();
+    if let Err(e) = collect_files(root, true, &mut paths) {
+        eprintln!("{}: {}", root.display(), e);
+        return 1;
+    }
+    paths.sort();
+
+    let mut profiles = Vec::new();
+    for path in &paths {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                return 1;
+            }
+        };
+        // Other files of the release, such as documentation, are not audited
+        if !data.starts_with(b"MZ") {
+            continue;
+        }
+        let name = path
+            .strip_prefix(root)
+            .unwrap_or(path)
+            .display()
+            .to_string();
+        let profile = PortExe::from_bytes(data).and_then(|pe| match pe.optional_header() {
+            Some(_) => pe.release_profile(&name).map(Some),
+            None => Ok(None),
+        });
+        match profile {
+            Ok(Some(profile)) => profiles.push(profile),
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                return 1;
+            }
+        }
+    }
+
+    let audit = audit_release(&profiles);
+    println!("{} binaries audited", audit.files().len());
+    for inconsistency in audit.inconsistencies() {
+        println!("{} differs:", inconsistency.property());
+        for variant in inconsistency.variants() {
+            println!("    {}: {}", variant.value(), variant.files().join(", "));
+        }
+    }
+    if !audit.consistent().is_empty() {
+        println!("consistent: {}", audit.consistent().join(", "));
+    }
+    for finding in audit.pdb_paths() {
+        println!(
+            "{}: PDB path {} is {}",
+            finding.file(),
+            finding.path(),
+            finding.issue()
+        );
+    }
+    if audit.is_consistent() && audit.pdb_paths().is_empty() {
+        0
+    } else {
+        1
+    }
+}
+
+/// Appends the files under `directory`, recursively.
+fn collect_files(
+    directory: &Path,
+    recursive: bool,
+    paths: &mut Vec<std::path::PathBuf>,
+) -> std::io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if recursive {
+                collect_files(&entry.path(), recursive, paths)?;
+            }
+        } else if file_type.is_file() {
+            paths.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Calls `visit` for every PE file in a directory, in path order, or for `root` itself
+/// if it is a file. Files that are not PE files are skipped, and ones that fail to parse
+/// are reported and skipped.
+///
+/// With the `containers` feature, the PE files inside archives and installers are
+/// visited too, under the path of the container joined with their name in it.
+fn scan_images(root: &Path, recursive: bool, mut visit: impl FnMut(&Path, &PortExe)) -> i32 {
+    let mut paths = Vec::new();
+    if root.is_file() {
+        paths.push(root.to_path_buf());
+    } else if let Err(e) = collect_files(root, recursive, &mut paths) {
+        eprintln!("{}: {}", root.display(), e);
+        return 1;
+    }
+    paths.sort();
+    for path in &paths {
+        match fs::read(path) {
+            Ok(data) => scan_data(path, data, &mut visit),
+            Err(e) => eprintln!("{}: {}", path.display(), e),
+        }
+    }
+    0
+}
+
+/// Visits `data` if it is a PE file, then the PE files inside it if it is a container.
+fn scan_data(path: &Path, data: Vec<u8>, visit: &mut impl FnMut(&Path, &PortExe)) {
+    #[cfg(feature = "containers")]
+    let container = match open_container(&data) {
+        Ok(container) => container,
+        Err(e) => {
+            eprintln!("{}: {}", path.display(), e);
+            None
+        }
+    };
+    if data.starts_with(b"MZ") {
+        match PortExe::from_bytes(data) {
+            Ok(pe) => visit(path, &pe),
+            Err(e) => eprintln!("{}: {}", path.display(), e),
+        }
+    }
+    #[cfg(feature = "containers")]
+    if let Some(mut container) = container {
+        for contained in contained_images(container.as_mut()) {
+            let path = path.join(contained.path());
+            match contained.image() {
+                Ok(pe) => visit(&path, pe),
+                Err(e) => eprintln!("{}: {}", path.display(), e),
+            }
+        }
+    }
+}
+
+/// Parses the `--exact`, `--ignore-case`, `--prefix`, `--regex` and `--recursive` flags
+/// of the search commands, returning the remaining arguments.
+fn search_flags(args: &[String]) -> (MatchMode, bool, &[String]) {
+    let mut mode = MatchMode::Exact;
+    let mut recursive = false;
+    let mut rest = args;
+    while let Some((flag, tail)) = rest.split_first() {
+        match flag.as_str() {
+            "--exact" => mode = MatchMode::Exact,
+            "-i" | "--ignore-case" => mode = MatchMode::IgnoreCase,
+            "--prefix" => mode = MatchMode::Prefix,
+            "--regex" => mode = MatchMode::Regex,
+            "-r" | "--recursive" => recursive = true,
+            _ => break,
+        }
+        rest = tail;
+    }
+    (mode, recursive, rest)
+}
+
+fn find_export(args: &[String]) -> i32 {
+    let (mode, recursive, rest) = search_flags(args);
+    let (pattern, root) = match rest {
+        [pattern, root] => (pattern, Path::new(root)),
+        _ => {
+            eprintln!("usage: pexp find-export [-i|--prefix|--regex] [--recursive] <name> <dir>");
+            return 2;
+        }
+    };
+    let matcher = match NameMatcher::new(mode, pattern) {
+        Ok(matcher) => matcher,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    let mut found = 0;
+    let status = scan_images(root, recursive, |path, pe| {
+        let exports = match pe.find_exports(&matcher) {
+            Ok(exports) => exports,
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                return;
+            }
+        };
+        let name = path.strip_prefix(root).unwrap_or(path).display();
+        for export in exports {
+            found += 1;
+            let symbol = export.name().map(ToString::to_string).unwrap_or_default();
+            match export.forwarder() {
+                Some(forwarder) => println!(
+                    "{}: {} (ordinal {}) -> {}",
+                    name,
+                    symbol,
+                    export.ordinal(),
+                    forwarder
+                ),
+                None => println!("{}: {} (ordinal {})", name, symbol, export.ordinal()),
+            }
+        }
+    });
+    match (status, found) {
+        (0, 0) => 1,
+        (status, _) => status,
+    }
+}
+
+fn find_import(args: &[String]) -> i32 {
+    const FIND_IMPORT_USAGE: &str =
+        "usage: pexp find-import [-i|--prefix|--regex] [--recursive] <dll!name|dll!#n|dll> <dir>";
+    let (mode, recursive, rest) = search_flags(args);
+    let (query, root) = match rest {
+        [query, root] => (query, Path::new(root)),
+        _ => {
+            eprintln!("{}", FIND_IMPORT_USAGE);
+            return 2;
+        }
+    };
+    let query = match ImportQuery::parse(query, mode) {
+        Ok(query) => query,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    let mut found = 0;
+    let status = scan_images(root, recursive, |path, pe| {
+        let imports = match pe.find_imports(&query) {
+            Ok(imports) => imports,
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                return;
+            }
+        };
+        let name = path.strip_prefix(root).unwrap_or(path).display();
+        if let ImportSymbol::Any = query.symbol() {
+            // One line per DLL rather than per function
+            let mut counts: Vec<(&str, usize)> = Vec::new();
+            for found_import in &imports {
+                match counts
+                    .iter_mut()
+                    .find(|(dll, _)| *dll == found_import.dll())
+                {
+                    Some((_, count)) => *count += 1,
+                    None => counts.push((found_import.dll(), 1)),
+                }
+            }
+            for (dll, count) in counts {
+                found += 1;
+                println!("{}: {} ({} functions)", name, dll, count);
+            }
+            return;
+        }
+        for found_import in imports {
+            found += 1;
+            let import = found_import.import();
+            match (import.name(), import.ordinal()) {
+                (Some(symbol), _) => println!("{}: {}!{}", name, found_import.dll(), symbol),
+                (None, Some(ordinal)) => {
+                    println!("{}: {}!#{}", name, found_import.dll(), ordinal)
+                }
+                (None, None) => println!("{}: {}", name, found_import.dll()),
+            }
+        }
+    });
+    match (status, found) {
+        (0, 0) => 1,
+        (status, _) => status,
+    }
+}
+
+fn label_format(name: &str) -> Option<LabelFormat> {
+    match name {
+        "ghidra" => Some(LabelFormat::Ghidra),
+        "ida" => Some(LabelFormat::Ida),
+        "x64dbg" => Some(LabelFormat::X64dbg),
+        "windbg" => Some(LabelFormat::Windbg),
+        _ => None,
+    }
+}
+
+/// Last component of `path`, which debuggers name the module after
+fn file_name(path: &str) -> String {
+    Path::new(path).file_name().map_or_else(
+        || path.to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+/// Lays each file out at its preferred base under its file name.
+fn load_modules(paths: &[String]) -> Result<VirtualLoader, String> {
+    let mut loader = VirtualLoader::new();
+    for path in paths {
+        let name = Path::new(path)
+            .file_name()
+            .map_or_else(|| path.clone(), |name| name.to_string_lossy().into_owned());
+        let pe = open(path)?;
+        loader
+            .load_at_preferred_base(&name, pe)
+            .map_err(|e| format!("{}: {}", path, e))?;
+    }
+    Ok(loader)
+}
+
+fn resolve(args: &[String]) -> i32 {
+    let parsed = args.split_first().and_then(|(export, paths)| {
+        let (dll, symbol) = export.split_once('!')?;
+        let symbol = match symbol.strip_prefix('#') {
+            Some(ordinal) => SymbolRef::Ordinal(ordinal.parse().ok()?),
+            None => SymbolRef::Name(symbol.into()),
+        };
+        (!paths.is_empty()).then(|| (dll, symbol, paths))
+    });
+    let (dll, symbol, paths) = match parsed {
+        Some(parsed) => parsed,
+        None => {
+            eprintln!("usage: pexp resolve <dll!name|dll!#n> <file...>");
+            return 2;
+        }
+    };
+
+    let loader = match load_modules(paths) {
+        Ok(loader) => loader,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+    match loader.resolve(dll, &symbol) {
+        Ok(resolution) => {
+            for forwarder in resolution.forwarders() {
+                println!("forwarded to {}", forwarder);
+            }
+            println!(
+                "{}+{:#x} at {:#x}",
+                resolution.module(),
+                resolution.rva(),
+                resolution.address()
+            );
+            0
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            1
+        }
+    }
+}
+
+fn scan(args: &[String]) -> i32 {
+    const SCAN_USAGE: &str =
+        "usage: pexp scan [--recursive] [--analyses <list>|all] [--color auto|always|never] <path...>";
+    let config = match load_config() {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+    let mut recursive = false;
+    let mut analyses = None;
+    let mut color = config.color();
+    let mut args = args;
+    loop {
+        match args {
+            [flag, rest @ ..] if flag == "-r" || flag == "--recursive" => {
+                recursive = true;
+                args = rest;
+            }
+            [flag, list, rest @ ..] if flag == "--analyses" => {
+                analyses = Some(list.split(',').map(str::to_string).collect());
+                args = rest;
+            }
+            [flag, choice, rest @ ..] if flag == "--color" => {
+                color = match color_choice(choice) {
+                    Some(choice) => choice,
+                    None => {
+                        eprintln!("{}", SCAN_USAGE);
+                        return 2;
+                    }
+                };
+                args = rest;
+            }
+            _ => break,
+        }
+    }
+    let paths = args;
+    if paths.is_empty() {
+        eprintln!("{}", SCAN_USAGE);
+        return 2;
+    }
+    let analyses: Vec<String> = analyses
+        .or_else(|| config.analyses().map(<[String]>::to_vec))
+        .unwrap_or_default();
+    let registry = if analyses.iter().any(|name| name == "all") {
+        AnalysisRegistry::builtin()
+    } else {
+        let names: Vec<&str> = analyses.iter().map(String::as_str).collect();
+        match AnalysisRegistry::builtin().select(&names) {
+            Ok(registry) => registry,
+            Err(name) => {
+                eprintln!("unknown analysis {}", name);
+                return 2;
+            }
+        }
+    };
+    let color = color.enabled(stdout_is_terminal());
+    let catalog = match config.messages() {
+        Some(path) => match Catalog::load(path) {
+            Ok(catalog) => catalog,
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                return 1;
+            }
+        },
+        None => Catalog::english(),
+    };
+    println!("{:<17} {:<6} {:<24} File", "Machine", "Format", "Subsystem");
+    let mut status = 0;
+    let mut flagged = false;
+    for path in paths {
+        status |= scan_images(Path::new(path), recursive, |path, pe| {
+            let header = pe.optional_header();
+            let format = match header {
+                Some(header) if header.image_type().is_x64() => "PE32+",
+                Some(_) => "PE32",
+                None => "-",
+            };
+            let subsystem = header.map_or_else(
+                || "-".to_string(),
+                |header| format!("{:?}", header.subsystem()),
+            );
+            println!(
+                "{:<17} {:<6} {:<24} {}",
+                format!("{:?}", pe.file_header().machine().into_value()),
+                format,
+                subsystem,
+                path.display()
+            );
+            for report in registry.run(pe) {
+                match report.findings() {
+                    Ok(findings) => {
+                        for finding in findings {
+                            println!(
+                                "    {}: {}: {}",
+                                report.analysis(),
+                                paint_severity(finding.severity(), color),
+                                finding.localize(&catalog)
+                            );
+                            flagged |= finding.severity() >= Severity::Warning;
+                        }
+                    }
+                    Err(error) => {
+                        eprintln!("{}: {}: {}", path.display(), report.analysis(), error);
+                        flagged = true;
+                    }
+                }
+            }
+        });
+    }
+    status | flagged as i32
+}
+
+fn color_choice(name: &str) -> Option<ColorChoice> {
+    match name {
+        "auto" => Some(ColorChoice::Auto),
+        "always" => Some(ColorChoice::Always),
+        "never" => Some(ColorChoice::Never),
+        _ => None,
+    }
+}
+
+/// Standard output is a character device, which in practice means a terminal.
+///
+/// `IsTerminal` is newer than the minimum supported Rust version, so Unix terminals are
+/// recognized through `/dev/stdout`; elsewhere `auto` colors only with `CLICOLOR_FORCE`.
+#[cfg(unix)]
+fn stdout_is_terminal() -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    fs::metadata("/dev/stdout").map_or(false, |metadata| metadata.file_type().is_char_device())
+}
+
+#[cfg(not(unix))]
+fn stdout_is_terminal() -> bool {
+    false
+}
+
+/// Name of a severity, in red for errors and yellow for warnings if `color`
+fn paint_severity(severity: Severity, color: bool) -> String {
+    let code = match severity {
+        Severity::Error => "31",
+        Severity::Warning => "33",
+        _ => "",
+    };
+    if color && !code.is_empty() {
+        format!("\x1b[{}m{}\x1b[0m", code, severity)
+    } else {
+        severity.to_string()
+    }
+}
+
+fn sections(args: &[String]) -> i32 {
+    let (columns, path) = match args {
+        [path] => (DEFAULT_SECTION_COLUMNS.to_vec(), path),
+        [flag, list, path] if flag == "--columns" => {
+            let mut columns = Vec::new();
+            for name in list.split(',') {
+                match section_column(name) {
+                    Some(column) => columns.push(column),
+                    None => {
+                        eprintln!("unknown column {}", name);
+                        return 2;
+                    }
+                }
+            }
+            (columns, path)
+        }
+        _ => {
+            eprintln!("usage: pexp sections [--columns <list>] <file>");
+            return 2;
+        }
+    };
+    let pe = match open(path) {
+        Ok(pe) => pe,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+    let widths: Vec<usize> = columns.iter().map(|&column| column_width(column)).collect();
+    let row = |cells: Vec<String>| {
+        let cells: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", cells.join(" ").trim_end());
+    };
+    row(columns
+        .iter()
+        .map(|column| column.title().to_string())
+        .collect());
+    for section in pe.section_headers() {
+        row(columns
+            .iter()
+            .map(|&column| pe.section_column(section, column))
+            .collect());
+    }
+    0
+}
+
+fn section_column(name: &str) -> Option<SectionColumn> {
+    match name {
+        "name" => Some(SectionColumn::Name),
+        "vaddr" => Some(SectionColumn::VirtualAddress),
+        "vsize" => Some(SectionColumn::VirtualSize),
+        "rawaddr" => Some(SectionColumn::RawAddress),
+        "rawsize" => Some(SectionColumn::RawSize),
+        "flags" => Some(SectionColumn::Flags),
+        "characteristics" => Some(SectionColumn::Characteristics),
+        "entropy" => Some(SectionColumn::Entropy),
+        "md5" => Some(SectionColumn::Md5),
+        _ => None,
+    }
+}
+
+/// Width that fits the title and the values of `column`
+fn column_width(column: SectionColumn) -> usize {
+    let values = match column {
+        SectionColumn::Name => 8,
+        SectionColumn::Flags => 3,
+        SectionColumn::Entropy => 4,
+        SectionColumn::Md5 => 32,
+        _ => 10,
+    };
+    column.title().len().max(values)
+}
+
+fn signature(args: &[String]) -> i32 {
+    if args.is_empty() {
+        eprintln!("usage: pexp signature <file...>");
+        return 2;
+    }
+    let mut status = 0;
+    for path in args {
+        let result = open(path).and_then(|pe| {
+            let error = |e: std::io::Error| format!("{}: {}", path, e);
+            let signer = pe.signer().map_err(error)?;
+            let signed = !pe.certificates().map_err(error)?.is_empty();
+            let issues = pe.signature_layout().map_err(error)?;
+            Ok((signer, signed, issues))
+        });
+        match result {
+            Ok((signer, signed, issues)) => {
+                match (signer, signed) {
+                    (Some(signer), _) => println!(
+                        "{}: signed by {} ({})",
+                        path,
+                        signer.common_name().unwrap_or_else(|| signer.subject()),
+                        signer.serial_number_hex()
+                    ),
+                    (None, true) => println!("{}: undecodable signature", path),
+                    (None, false) => println!("{}: unsigned", path),
+                }
+                for issue in &issues {
+                    println!("  {}", issue);
+                    status = 1;
+                }
+            }
+            Err(message) => {
+                eprintln!("{}", message);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+fn signing(args: &[String]) -> i32 {
+    if args.is_empty() {
+        eprintln!("usage: pexp signing <file...>");
+        return 2;
+    }
+    let mut status = 0;
+    for path in args {
+        let requirements = match open(path).and_then(|pe| {
+            pe.signing_requirements()
+                .map_err(|e| format!("{}: {}", path, e))
+        }) {
+            Ok(requirements) => requirements,
+            Err(message) => {
+                eprintln!("{}", message);
+                status = 1;
+                continue;
+            }
+        };
+        let yes_no = |value| if value { "yes" } else { "no" };
+        println!(
+            "{}: signed {}, /INTEGRITYCHECK {}",
+            path,
+            yes_no(requirements.signed()),
+            yes_no(requirements.integrity_check())
+        );
+        if !requirements.signer_extended_key_usages().is_empty() {
+            println!(
+                "  signer EKUs: {}",
+                requirements.signer_extended_key_usages().join(", ")
+            );
+        }
+        for certificate in requirements.elam_certificates().unwrap_or(&[]) {
+            println!(
+                "  ELAM certificate {} (algorithm {:#06x}) EKUs: {}",
+                certificate.hash(),
+                certificate.algorithm(),
+                certificate.extended_key_usages().join(", ")
+            );
+        }
+        for issue in requirements.issues() {
+            println!("  warning: {}", issue);
+            status = 1;
+        }
+    }
+    status
+}
+
+fn similarity(args: &[String]) -> i32 {
+    const SIMILARITY_USAGE: &str = "usage: pexp similarity [--threshold <d>] <dir>";
+    let (threshold, root) = match args {
+        [flag, value, root] if flag == "--threshold" => match value.parse::<f64>() {
+            Ok(threshold) if (0.0..=1.0).contains(&threshold) => (threshold, root),
+            _ => {
+                eprintln!("{}", SIMILARITY_USAGE);
+                return 2;
+            }
+        },
+        [root] => (DEFAULT_CLUSTER_THRESHOLD, root),
+        _ => {
+            eprintln!("{}", SIMILARITY_USAGE);
+            return 2;
+        }
+    };
+    let root = Path::new(root);
+    let mut fingerprints = Vec::new();
+    let status = scan_images(root, true, |path, pe| {
+        let name = path
+            .strip_prefix(root)
+            .unwrap_or(path)
+            .display()
+            .to_string();
+        match pe.fingerprint(&name) {
+            Ok(fingerprint) => fingerprints.push(fingerprint),
+            Err(e) => eprintln!("{}: {}", path.display(), e),
+        }
+    });
+    if status != 0 {
+        return status;
+    }
+
+    let clusters = cluster(&fingerprints, threshold);
+    let mut unclustered = Vec::new();
+    for (index, cluster) in clusters
+        .iter()
+        .filter(|cluster| cluster.members().len() > 1)
+        .enumerate()
+    {
+        println!("cluster {} ({} files):", index + 1, cluster.members().len());
+        for member in cluster.members() {
+            let fingerprint = fingerprints.iter().find(|f| f.name() == member);
+            let imphash = fingerprint.and_then(|f| f.imphash());
+            println!("    {}  {}", imphash.as_deref().unwrap_or("-"), member);
+        }
+    }
+    for cluster in clusters
+        .iter()
+        .filter(|cluster| cluster.members().len() == 1)
+    {
+        unclustered.extend_from_slice(cluster.members());
+    }
+    if !unclustered.is_empty() {
+        println!("unclustered: {}", unclustered.join(", "));
+    }
+    0
+}
+
+fn slack(args: &[String]) -> i32 {
+    let (extract, path) = match args {
+        [flag, dir, path] if flag == "--extract" => (Some(dir), path),
+        [path] => (None, path),
+        _ => {
+            eprintln!("usage: pexp slack [--extract <dir>] <file>");
+            return 2;
+        }
+    };
+    let pe = match open(path) {
+        Ok(pe) => pe,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    let regions = match pe.slack_regions() {
+        Ok(regions) => regions,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            return 1;
+        }
+    };
+    if let Some(dir) = extract {
+        if let Err(e) = fs::create_dir_all(dir) {
+            eprintln!("{}: {}", dir, e);
+            return 1;
+        }
+    }
+    let mut status = 0;
+    for region in regions.iter().filter(|region| !region.is_zero()) {
+        let range = region.range();
+        println!(
+            "{:#010x} {:>8} bytes, {} non-zero, entropy {:.2}: {}",
+            range.start,
+            range.len(),
+            region.nonzero_bytes(),
+            region.entropy(),
+            region.kind()
+        );
+        for (offset, text) in region.strings() {
+            println!("    {:#010x} {:?}", offset, text);
+        }
+        if let Some(dir) = extract {
+            let file = Path::new(dir).join(format!("{:08x}.bin", range.start));
+            if let Err(e) = fs::write(&file, pe.slack_data(region)) {
+                eprintln!("{}: {}", file.display(), e);
+                return 1;
+            }
+        }
+        status = 1;
+    }
+    status
+}
+
+fn split(args: &[String]) -> i32 {
+    let (keep_codeview, args) = match args.split_first() {
+        Some((flag, rest)) if flag == "--move-codeview" => (false, rest),
+        _ => (true, args),
+    };
+    let (output, args) = match args {
+        [flag, value, rest @ ..] if is_output_flag(flag) => (Some(value), rest),
+        _ => (None, args),
+    };
+    let (path, sidecar) = match args {
+        [path] => (path, format!("{}.debug", path)),
+        [path, sidecar] => (path, sidecar.clone()),
+        _ => {
+            eprintln!("usage: pexp split [--move-codeview] [-o <output>] <file> [<sidecar>]");
+            return 2;
+        }
+    };
+    let name = Path::new(&sidecar).file_name().map_or_else(
+        || sidecar.clone(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    let result = open(path).and_then(|mut pe| {
+        let container = pe
+            .split_debug(&name, keep_codeview)
+            .map_err(|e| format!("{}: {}", path, e))?;
+        fs::write(&sidecar, container.to_bytes()).map_err(|e| format!("{}: {}", sidecar, e))?;
+        write_image(output.unwrap_or(path), pe.data())?;
+        Ok(container)
+    });
+    match result {
+        Ok(container) => {
+            println!(
+                "{}: moved {} debug entries and {} symbols to {}",
+                path,
+                container.payloads().len(),
+                container.number_of_symbols(),
+                sidecar
+            );
+            0
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            1
+        }
+    }
+}
+
+fn strip(args: &[String]) -> i32 {
+    let mut options = StripOptions::default();
+    let mut output = None;
+    let mut paths = args;
+    loop {
+        match paths {
+            [flag, rest @ ..] if flag == "--relocs" => {
+                options.relocations = true;
+                paths = rest;
+            }
+            [flag, value, rest @ ..] if is_output_flag(flag) => {
+                output = Some(value);
+                paths = rest;
+            }
+            _ => break,
+        }
+    }
+    if paths.is_empty() || (output.is_some() && paths.len() != 1) {
+        eprintln!("usage: pexp strip [--relocs] <file...>|-o <output> <file>");
+        return 2;
+    }
+    let mut status = 0;
+    for path in paths {
+        let result = open(path).and_then(|mut pe| {
+            let report = pe.strip(&options).map_err(|e| format!("{}: {}", path, e))?;
+            if !report.is_empty() || output.is_some() {
+                write_image(output.unwrap_or(path), pe.data())?;
+            }
+            Ok(report)
+        });
+        match result {
+            Ok(report) => {
+                let mut removed = Vec::new();
+                if report.debug_entries() != 0 {
+                    removed.push(format!("{} debug entries", report.debug_entries()));
+                }
+                if report.rich_header() {
+                    removed.push("Rich header".to_string());
+                }
+                if report.symbols() != 0 {
+                    removed.push(format!("{} symbols", report.symbols()));
+                }
+                if report.relocations() {
+                    removed.push("relocations".to_string());
+                }
+                if removed.is_empty() {
+                    println!("{}: nothing to strip", path);
+                } else {
+                    println!(
+                        "{}: removed {} ({} bytes saved)",
+                        path,
+                        removed.join(", "),
+                        report.bytes_removed()
+                    );
+                }
+                for reason in report.skipped() {
+                    println!("{}: kept {}", path, reason);
+                }
+            }
+            Err(message) => {
+                eprintln!("{}", message);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+fn timeline(args: &[String]) -> i32 {
+    let root = match args {
+        [root] => Path::new(root),
+        _ => {
+            eprintln!("usage: pexp timeline <dir>");
+            return 2;
+        }
+    };
+    let mut entries = Vec::new();
+    let status = scan_images(root, true, |path, pe| {
+        let name = path
+            .strip_prefix(root)
+            .unwrap_or(path)
+            .display()
+            .to_string();
+        match pe.timeline_entry(&name) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => eprintln!("{}: {}", path.display(), e),
+        }
+    });
+    if status != 0 {
+        return status;
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64);
+
+    let time = |time: Option<i64>| time.map_or_else(|| "-".to_string(), format_timestamp);
+    println!("{:<19}  {:<19}  file", "built (UTC)", "signed (UTC)");
+    for entry in build_timeline(entries, now) {
+        let flags: Vec<String> = entry.flags().iter().map(ToString::to_string).collect();
+        print!(
+            "{:<19}  {:<19}  {}",
+            time(entry.build_time()),
+            time(entry.signing_time()),
+            entry.name()
+        );
+        if flags.is_empty() {
+            println!();
+        } else {
+            println!("  [{}]", flags.join(", "));
+        }
+    }
+    0
+}
+
+fn tls(args: &[String]) -> i32 {
+    let (extract, paths) = match args {
+        [flag, dir, paths @ ..] if flag == "--extract" => (Some(dir), paths),
+        paths => (None, paths),
+    };
+    if paths.is_empty() {
+        eprintln!("usage: pexp tls [--extract <dir>] <file...>");
+        return 2;
+    }
+    if let Some(dir) = extract {
+        if let Err(e) = fs::create_dir_all(dir) {
+            eprintln!("{}: {}", dir, e);
+            return 1;
+        }
+    }
+    let mut status = 0;
+    for path in paths {
+        let pe = match open(path) {
+            Ok(pe) => pe,
+            Err(message) => {
+                eprintln!("{}", message);
+                status = 1;
+                continue;
+            }
+        };
+        let (directory, template) = match pe
+            .tls_directory()
+            .and_then(|directory| Ok(directory.zip(pe.tls_template()?)))
+            .map_err(|e| format!("{}: {}", path, e))
+        {
+            Ok(Some(tls)) => tls,
+            Ok(None) => {
+                println!("{}: no TLS directory", path);
+                continue;
+            }
+            Err(message) => {
+                eprintln!("{}", message);
+                status = 1;
+                continue;
+            }
+        };
+        let section = template.section().map_or_else(
+            || "no section".to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        println!(
+            "{}: template {:#x}-{:#x}, {} bytes in {} plus {} zero-filled, entropy {:.2}",
+            path,
+            template.start_address(),
+            template.end_address(),
+            template.size(),
+            section,
+            template.size_of_zero_fill(),
+            template.entropy()
+        );
+        if template.is_truncated() {
+            println!("  only {} bytes are in the file", template.data().len());
+        }
+        println!("  index at {:#x}", directory.address_of_index());
+        if let Some(alignment) = directory.alignment() {
+            println!("  aligned to {} bytes", alignment);
+        }
+        for callback in directory.callbacks(&pe) {
+            println!("  callback {:#x}", callback);
+        }
+        if let Some(dir) = extract.filter(|_| !template.data().is_empty()) {
+            let name = Path::new(path)
+                .file_name()
+                .map_or_else(|| path.clone(), |name| name.to_string_lossy().into_owned());
+            let file = Path::new(dir).join(format!("{}.tls", name));
+            if let Err(e) = fs::write(&file, template.data()) {
+                eprintln!("{}: {}", file.display(), e);
+                return 1;
+            }
+        }
+    }
+    status
+}
+
+fn wow64(paths: &[String]) -> i32 {
+    if paths.is_empty() {
+        eprintln!("usage: pexp wow64 <file...>");
+        return 2;
+    }
+    let mut status = 0;
+    for path in paths {
+        let compatibility = match open(path).and_then(|pe| {
+            pe.platform_compatibility()
+                .map_err(|e| format!("{}: {}", path, e))
+        }) {
+            Ok(Some(compatibility)) => compatibility,
+            Ok(None) => {
+                println!("{}: object file", path);
+                continue;
+            }
+            Err(message) => {
+                eprintln!("{}", message);
+                status = 1;
+                continue;
+            }
+        };
+        let hybrid = compatibility
+            .hybrid()
+            .map_or_else(String::new, |hybrid| format!(" ({})", hybrid));
+        let large_address_aware = if compatibility.large_address_aware() {
+            ", LARGE_ADDRESS_AWARE"
+        } else {
+            ""
+        };
         println!(
-            "Image signature: {:X} {:X} {:X} {:X}\n",
-            image_signature[0], image_signature[1], image_signature[2], image_signature[3]
+            "{}: {:?}{}{}",
+            path,
+            compatibility.machine(),
+            hybrid,
+            large_address_aware
         );
-        let _offset = pe_reader.stream_position().unwrap();
+        for platform in compatibility.platforms() {
+            let address_space =
+                platform
+                    .address_space()
+                    .map_or_else(String::new, |size| match size >> 30 {
+                        gib if gib >= 1024 => format!("{} TiB", gib >> 10),
+                        gib => format!("{} GiB", gib),
+                    });
+            let line = format!(
+                "  {:<6} {:<12} {:<8} {}",
+                platform.platform(),
+                platform.support(),
+                address_space,
+                platform.note().unwrap_or("")
+            );
+            println!("{}", line.trim_end());
+        }
     }
+    status
 }
@@ -0,0 +1,486 @@
+use crate::endian::{utf16_units, ByteOrder};
+use crate::module_key;
+use crate::port_exe::PortExe;
+use crate::resource::{ResourceEntry, ResourceId, RT_MANIFEST};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// `RT_MANIFEST` ID the loader reads for executables
+pub const CREATEPROCESS_MANIFEST_RESOURCE_ID: u16 = 1;
+/// `RT_MANIFEST` ID the loader reads for DLLs
+pub const ISOLATIONAWARE_MANIFEST_RESOURCE_ID: u16 = 2;
+/// Upper bound on the elements read from a manifest
+pub const MAX_MANIFEST_ELEMENTS: usize = 0x10000;
+
+/// `assemblyIdentity` element: the name, version and platform of a side-by-side assembly
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AssemblyIdentity {
+    name: String,
+    version: Option<String>,
+    processor_architecture: Option<String>,
+    public_key_token: Option<String>,
+    language: Option<String>,
+    kind: Option<String>,
+}
+
+impl AssemblyIdentity {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Four-part version such as `6.0.0.0`
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// `x86`, `amd64`, `arm64`, `msil` or `*` for the architecture of the process
+    pub fn processor_architecture(&self) -> Option<&str> {
+        self.processor_architecture.as_deref()
+    }
+
+    pub fn public_key_token(&self) -> Option<&str> {
+        self.public_key_token.as_deref()
+    }
+
+    /// Culture, `*` or absent for language-neutral assemblies
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    /// The `type` attribute, `win32` for native assemblies
+    pub fn kind(&self) -> Option<&str> {
+        self.kind.as_deref()
+    }
+}
+
+impl fmt::Display for AssemblyIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        for value in [
+            &self.version,
+            &self.processor_architecture,
+            &self.public_key_token,
+        ]
+        .iter()
+        .filter_map(|value| value.as_deref())
+        {
+            write!(f, " {}", value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Application or assembly manifest, reduced to what dependency resolution needs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    identity: Option<AssemblyIdentity>,
+    dependencies: Vec<AssemblyIdentity>,
+    files: Vec<String>,
+}
+
+impl Manifest {
+    /// Parses a manifest in UTF-8 or, with a byte order mark, UTF-16.
+    ///
+    /// The reader only follows elements and attributes; it does not validate the XML.
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        let text = decode(data);
+        let mut manifest = Manifest::default();
+        // Local names of the open elements
+        let mut open: Vec<String> = Vec::new();
+        let mut elements = 0;
+        let mut rest = text.as_str();
+        while let Some(start) = rest.find('<') {
+            rest = &rest[start + 1..];
+            if let Some(comment) = rest.strip_prefix("!--") {
+                rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+                continue;
+            }
+            let end = tag_end(rest).ok_or_else(|| invalid_data("unterminated manifest tag"))?;
+            let tag = &rest[..end];
+            rest = &rest[end + 1..];
+            if tag.starts_with('?') || tag.starts_with('!') {
+                continue;
+            }
+            if let Some(name) = tag.strip_prefix('/') {
+                let name = local_name(name.trim());
+                if let Some(position) = open.iter().rposition(|open| *open == name) {
+                    open.truncate(position);
+                }
+                continue;
+            }
+            elements += 1;
+            if elements > MAX_MANIFEST_ELEMENTS {
+                return Err(invalid_data("too many manifest elements"));
+            }
+            let self_closing = tag.ends_with('/');
+            let tag = tag.trim_end_matches('/');
+            let name_end = tag
+                .find(|c: char| c.is_ascii_whitespace())
+                .unwrap_or(tag.len());
+            let name = local_name(&tag[..name_end]).to_string();
+            let attributes = attributes(&tag[name_end..]);
+            let parent = open.last().map(String::as_str);
+            match (name.as_str(), parent) {
+                ("assemblyIdentity", Some("dependentAssembly")) => {
+                    manifest.dependencies.push(identity(&attributes))
+                }
+                ("assemblyIdentity", Some("assembly")) if manifest.identity.is_none() => {
+                    manifest.identity = Some(identity(&attributes))
+                }
+                ("file", Some("assembly")) => {
+                    if let Some(file) = attributes.get("name") {
+                        manifest.files.push(file.clone());
+                    }
+                }
+                _ => {}
+            }
+            if !self_closing {
+                open.push(name);
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Identity of the manifest itself, if it declares one
+    pub fn identity(&self) -> Option<&AssemblyIdentity> {
+        self.identity.as_ref()
+    }
+
+    /// Assemblies referenced by `dependentAssembly` elements
+    pub fn dependencies(&self) -> &[AssemblyIdentity] {
+        &self.dependencies
+    }
+
+    /// Files an assembly manifest declares as part of the assembly
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+}
+
+/// Where a dependent assembly was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssemblyLocation {
+    /// Subdirectory of the application directory named after the assembly
+    Private,
+    /// WinSxS directory of the exact version referenced
+    Shared,
+    /// WinSxS directory of a newer build of the same major and minor version, the
+    /// kind of redirect publisher policies make
+    Policy,
+}
+
+/// Directory a dependent assembly resolves to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblyBinding {
+    identity: AssemblyIdentity,
+    resolved: Option<(AssemblyLocation, PathBuf)>,
+}
+
+impl AssemblyBinding {
+    pub fn identity(&self) -> &AssemblyIdentity {
+        &self.identity
+    }
+
+    pub fn location(&self) -> Option<AssemblyLocation> {
+        self.resolved.as_ref().map(|(location, _)| *location)
+    }
+
+    pub fn directory(&self) -> Option<&Path> {
+        self.resolved
+            .as_ref()
+            .map(|(_, directory)| directory.as_path())
+    }
+
+    /// Returns `true` if neither the application directory nor WinSxS holds the assembly.
+    pub fn is_unresolved(&self) -> bool {
+        self.resolved.is_none()
+    }
+}
+
+/// Dependent assemblies of a manifest and the DLLs they redirect
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SideBySide {
+    assemblies: Vec<AssemblyBinding>,
+    redirects: BTreeMap<String, PathBuf>,
+}
+
+impl SideBySide {
+    /// Looks the dependent assemblies of `manifest` up as the activation context does:
+    /// private assemblies in `application_dir` first, then the assembly directories
+    /// under `winsxs`, when given.
+    pub fn resolve(
+        manifest: &Manifest,
+        application_dir: &Path,
+        winsxs: Option<&Path>,
+    ) -> io::Result<Self> {
+        let shared = match winsxs {
+            Some(winsxs) => store_directories(winsxs)?,
+            None => Vec::new(),
+        };
+        let mut side_by_side = SideBySide::default();
+        for identity in manifest.dependencies() {
+            let private = application_dir.join(&identity.name);
+            let resolved = if !identity.name.is_empty() && private.is_dir() {
+                Some((AssemblyLocation::Private, private))
+            } else {
+                find_shared(identity, &shared)
+            };
+            if let Some((_, directory)) = &resolved {
+                let mut files = Vec::new();
+                for entry in fs::read_dir(directory)? {
+                    let entry = entry?;
+                    if entry.file_type()?.is_file() {
+                        files.push(entry.path());
+                    }
+                }
+                files.sort();
+                for path in files {
+                    let name = match path.file_name() {
+                        Some(name) => name.to_string_lossy().into_owned(),
+                        None => continue,
+                    };
+                    if name.to_ascii_lowercase().ends_with(".dll")
+                        && !side_by_side
+                            .redirects
+                            .keys()
+                            .any(|dll| module_key(dll) == module_key(&name))
+                    {
+                        side_by_side.redirects.insert(name, path);
+                    }
+                }
+            }
+            side_by_side.assemblies.push(AssemblyBinding {
+                identity: identity.clone(),
+                resolved,
+            });
+        }
+        Ok(side_by_side)
+    }
+
+    pub fn assemblies(&self) -> &[AssemblyBinding] {
+        &self.assemblies
+    }
+
+    /// DLLs of the resolved assemblies, which take precedence over the search path;
+    /// the form [`SearchOrder::redirects`](crate::load_order::SearchOrder::redirects) takes
+    pub fn redirects(&self) -> &BTreeMap<String, PathBuf> {
+        &self.redirects
+    }
+
+    /// Assemblies referenced by the manifest that could not be found
+    pub fn unresolved(&self) -> impl Iterator<Item = &AssemblyIdentity> {
+        self.assemblies
+            .iter()
+            .filter(|assembly| assembly.is_unresolved())
+            .map(|assembly| &assembly.identity)
+    }
+}
+
+impl PortExe {
+    /// The first `RT_MANIFEST` resource with the ID the loader reads, falling back to
+    /// any manifest; `None` if the image has none.
+    pub fn manifest(&self) -> io::Result<Option<Manifest>> {
+        let manifests: Vec<_> = self
+            .resources()?
+            .into_iter()
+            .filter(|entry| *entry.type_id() == ResourceId::Id(RT_MANIFEST))
+            .collect();
+        let loader_id = |entry: &&ResourceEntry| {
+            matches!(
+                entry.name_id(),
+                ResourceId::Id(CREATEPROCESS_MANIFEST_RESOURCE_ID)
+                    | ResourceId::Id(ISOLATIONAWARE_MANIFEST_RESOURCE_ID)
+            )
+        };
+        let entry = match manifests
+            .iter()
+            .find(loader_id)
+            .or_else(|| manifests.first())
+        {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let data = self
+            .resource_data(entry)
+            .ok_or_else(|| invalid_data("manifest resource is not backed by file data"))?;
+        Manifest::parse(data).map(Some)
+    }
+}
+
+/// WinSxS component directory such as
+/// `x86_microsoft.windows.common-controls_6595b64144ccf1df_6.0.19041.1_none_a8625c18`
+struct StoreDirectory {
+    architecture: String,
+    name: String,
+    public_key_token: String,
+    version: Vec<u32>,
+    language: String,
+    path: PathBuf,
+}
+
+fn store_directories(winsxs: &Path) -> io::Result<Vec<StoreDirectory>> {
+    let mut directories = Vec::new();
+    for entry in fs::read_dir(winsxs)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_ascii_lowercase();
+        // The assembly name may itself contain underscores, so split from both ends
+        let mut parts = name.splitn(2, '_');
+        let architecture = parts.next().unwrap_or_default().to_string();
+        let mut tail = parts.next().unwrap_or_default().rsplitn(5, '_');
+        let _hash = tail.next();
+        let (language, version, token, name) =
+            match (tail.next(), tail.next(), tail.next(), tail.next()) {
+                (Some(language), Some(version), Some(token), Some(name)) => {
+                    (language, version, token, name)
+                }
+                _ => continue,
+            };
+        let version = match parse_version(version) {
+            Some(version) => version,
+            None => continue,
+        };
+        directories.push(StoreDirectory {
+            architecture,
+            name: name.to_string(),
+            public_key_token: token.to_string(),
+            version,
+            language: language.to_string(),
+            path: entry.path(),
+        });
+    }
+    // Sorted so ties between equally good matches resolve the same way on every run
+    directories.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(directories)
+}
+
+/// Picks the store directory of the referenced version, or else the newest build of the
+/// same major and minor version.
+fn find_shared(
+    identity: &AssemblyIdentity,
+    directories: &[StoreDirectory],
+) -> Option<(AssemblyLocation, PathBuf)> {
+    let wanted = identity.version.as_deref().and_then(parse_version);
+    let architecture = identity
+        .processor_architecture()
+        .filter(|&value| value != "*");
+    let language = identity.language().filter(|&value| value != "*");
+    directories
+        .iter()
+        .filter(|directory| {
+            directory.name.eq_ignore_ascii_case(&identity.name)
+                && architecture.map_or(true, |value| {
+                    value.eq_ignore_ascii_case(&directory.architecture)
+                })
+                && identity.public_key_token().map_or(true, |value| {
+                    value.eq_ignore_ascii_case(&directory.public_key_token)
+                })
+                && language
+                    .unwrap_or("none")
+                    .eq_ignore_ascii_case(&directory.language)
+        })
+        .filter_map(|directory| {
+            let location = match &wanted {
+                Some(wanted) if *wanted == directory.version => AssemblyLocation::Shared,
+                Some(wanted)
+                    if wanted.get(..2) == directory.version.get(..2)
+                        && directory.version > *wanted =>
+                {
+                    AssemblyLocation::Policy
+                }
+                Some(_) => return None,
+                None => AssemblyLocation::Shared,
+            };
+            Some((location, directory))
+        })
+        // The exact version wins, then the newest build
+        .max_by(|(a, first), (b, second)| {
+            (*a == AssemblyLocation::Shared, &first.version)
+                .cmp(&(*b == AssemblyLocation::Shared, &second.version))
+        })
+        .map(|(location, directory)| (location, directory.path.clone()))
+}
+
+fn parse_version(text: &str) -> Option<Vec<u32>> {
+    text.split('.').map(|part| part.parse().ok()).collect()
+}
+
+fn identity(attributes: &BTreeMap<String, String>) -> AssemblyIdentity {
+    let get = |key: &str| attributes.get(key).cloned();
+    AssemblyIdentity {
+        name: get("name").unwrap_or_default(),
+        version: get("version"),
+        processor_architecture: get("processorArchitecture"),
+        public_key_token: get("publicKeyToken"),
+        language: get("language"),
+        kind: get("type"),
+    }
+}
+
+/// Decodes UTF-16 with a byte order mark, and anything else as UTF-8.
+fn decode(data: &[u8]) -> String {
+    let utf16 = |order| String::from_utf16_lossy(&utf16_units(&data[2..], order));
+    match data {
+        [0xFF, 0xFE, ..] => utf16(ByteOrder::Little),
+        [0xFE, 0xFF, ..] => utf16(ByteOrder::Big),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        _ => String::from_utf8_lossy(data).into_owned(),
+    }
+}
+
+/// Offset of the `>` closing the tag `text` starts with, skipping quoted attribute values.
+fn tag_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '>') => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Element or attribute name without its namespace prefix, as in `asmv3:application`
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn attributes(text: &str) -> BTreeMap<String, String> {
+    let mut attributes = BTreeMap::new();
+    let mut rest = text;
+    while let Some(equals) = rest.find('=') {
+        let name = local_name(rest[..equals].trim()).to_string();
+        let value = rest[equals + 1..].trim_start();
+        let quote = match value.chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => quote,
+            _ => break,
+        };
+        let end = match value[1..].find(quote) {
+            Some(end) => end + 1,
+            None => break,
+        };
+        attributes.insert(name, unescape(&value[1..end]));
+        rest = &value[end + 1..];
+    }
+    attributes
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
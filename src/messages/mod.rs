@@ -0,0 +1,300 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Locale of the built-in templates
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Built-in English templates of the messages the crate reports, by key
+const ENGLISH: &[(&str, &str)] = &[
+    (
+        "ce-desktop-subsystem",
+        "{0} image uses the {1} subsystem instead of WindowsCEGraphicalUI",
+    ),
+    (
+        "ce-subsystem-version-unknown",
+        "subsystem version {0} matches no Windows CE release",
+    ),
+    (
+        "ce-subsystem-version-low",
+        "subsystem version {0} is below 3.10 but names a Windows CE release",
+    ),
+    (
+        "subsystem-version-low",
+        "subsystem version {0} is below 3.10 and is rejected by the loader",
+    ),
+    (
+        "magic-mismatch",
+        "{0} image has {1} magic, but the machine requires {2}",
+    ),
+    (
+        "pe64-low-image-base",
+        "ImageBase {0} of a PE32+ image is below 4 GiB, where 64-bit linkers do not place images by default",
+    ),
+    (
+        "pe64-not-large-address-aware",
+        "PE32+ image is not LARGE_ADDRESS_AWARE, which confines it to the low 2 GiB",
+    ),
+    (
+        "pe32-high-entropy-va",
+        "HIGH_ENTROPY_VA is set on a PE32 image, which has no 64-bit address space",
+    ),
+    (
+        "file-alignment-not-power-of-two",
+        "FileAlignment {0} is not a power of two",
+    ),
+    ("file-alignment-too-large", "FileAlignment {0} is above 64 KiB"),
+    ("file-alignment-too-small", "FileAlignment {0} is below 512 bytes"),
+    (
+        "section-alignment-below-file-alignment",
+        "SectionAlignment {0} is smaller than FileAlignment {1}",
+    ),
+    (
+        "section-alignment-mismatch",
+        "SectionAlignment {0} is below the page size but differs from FileAlignment {1}",
+    ),
+    (
+        "size-of-image-unaligned",
+        "SizeOfImage {0} is not a multiple of SectionAlignment",
+    ),
+    (
+        "size-of-headers-unaligned",
+        "SizeOfHeaders {0} is not a multiple of FileAlignment",
+    ),
+    (
+        "raw-data-unaligned",
+        "PointerToRawData {0} of {1} is not a multiple of FileAlignment",
+    ),
+    (
+        "architecture-directory-set",
+        "reserved Architecture directory is set to {0}+{1} on a {2} image",
+    ),
+    (
+        "global-ptr-size",
+        "GlobalPtr directory has a Size of {0} instead of zero",
+    ),
+    (
+        "too-many-directories",
+        "NumberOfRvaAndSizes is {0}; the loader ignores entries past the first {1}",
+    ),
+    (
+        "extra-directory-set",
+        "extra data directory {0} is set to {1}+{2}",
+    ),
+    (
+        "file-truncated",
+        "file is {0} bytes, {1} short of the {2} its headers describe",
+    ),
+    (
+        "structure-truncated",
+        "{0} at {1}..{2} is cut off after {3} of its {4} bytes",
+    ),
+    (
+        "truncated-size-of-image",
+        "section table implies SizeOfImage {0} instead of {1}",
+    ),
+    (
+        "truncated-size-of-headers",
+        "section table implies SizeOfHeaders {0} instead of {1}",
+    ),
+    (
+        "system-dll-by-ordinal",
+        "all {0} imports from system DLL {1} are by ordinal",
+    ),
+    ("entry-point-zero", "entry point is at RVA 0, the DOS header"),
+    (
+        "entry-point-in-headers",
+        "entry point {0} is in the headers, which span {1} bytes",
+    ),
+    (
+        "entry-point-beyond-image",
+        "entry point {0} is past SizeOfImage {1}",
+    ),
+    (
+        "entry-point-outside-sections",
+        "entry point {0} is in no section",
+    ),
+    (
+        "entry-point-not-executable",
+        "entry point {0} is in section {1}, which is not executable",
+    ),
+    (
+        "entry-point-inflated-section",
+        "entry point section {0} maps {1} bytes from {2} bytes of file data",
+    ),
+    (
+        "entry-point-in-zero-fill",
+        "entry point {0} is past the {2} bytes of file data of section {1}",
+    ),
+    (
+        "high-entropy-section",
+        "section {0} has entropy {1}, likely compressed or encrypted",
+    ),
+    ("mitigation-disabled", "{0} is not enabled"),
+    ("not-signed", "file is not signed"),
+];
+
+/// Message identified by a catalog key, with the values filling its `{0}`, `{1}`...
+/// placeholders
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    key: String,
+    args: Vec<String>,
+}
+
+impl Message {
+    pub fn new(key: impl Into<String>, args: Vec<String>) -> Self {
+        Self {
+            key: key.into(),
+            args,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Values of the placeholders, already formatted
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Message in the built-in English templates
+    pub fn english(&self) -> String {
+        Catalog::new(DEFAULT_LOCALE).format(self)
+    }
+}
+
+/// Templates of the messages in one locale.
+///
+/// Catalog files hold one `key = template` per line, with `#` starting comment lines.
+/// Placeholders `{0}`, `{1}`... stand for the arguments of the message and `{{` and `}}`
+/// for literal braces. Messages a catalog has no template for fall back to English.
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    locale: String,
+    templates: BTreeMap<String, String>,
+}
+
+impl Catalog {
+    /// Catalog for `locale` without templates
+    pub fn new(locale: impl Into<String>) -> Self {
+        Self {
+            locale: locale.into(),
+            templates: BTreeMap::new(),
+        }
+    }
+
+    /// Catalog of the built-in English templates
+    pub fn english() -> Self {
+        let mut catalog = Self::new(DEFAULT_LOCALE);
+        for (key, template) in ENGLISH {
+            catalog.insert(*key, *template);
+        }
+        catalog
+    }
+
+    /// Parses a catalog file for `locale`.
+    pub fn parse(locale: impl Into<String>, text: &str) -> io::Result<Self> {
+        let mut catalog = Self::new(locale);
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, template) = line.split_once('=').ok_or_else(|| {
+                invalid_data(&format!("line {}: expected key = template", index + 1))
+            })?;
+            catalog.insert(key.trim(), template.trim());
+        }
+        Ok(catalog)
+    }
+
+    /// Reads a catalog file, named after its locale such as `de.txt` or `pt-BR.txt`.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let locale = path
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        Self::parse(locale, &fs::read_to_string(path)?)
+    }
+
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    /// Adds a template, replacing any for the same key.
+    pub fn insert(&mut self, key: impl Into<String>, template: impl Into<String>) {
+        self.templates.insert(key.into(), template.into());
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.templates.get(key).map(String::as_str)
+    }
+
+    /// Templates by key, in key order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.templates
+            .iter()
+            .map(|(key, template)| (key.as_str(), template.as_str()))
+    }
+
+    /// Keys of the built-in messages this catalog has no template for
+    pub fn missing(&self) -> Vec<&'static str> {
+        ENGLISH
+            .iter()
+            .map(|(key, _)| *key)
+            .filter(|key| !self.templates.contains_key(*key))
+            .collect()
+    }
+
+    /// Text of `message` in this catalog, in English if it has no template for it, or
+    /// its key and arguments if no catalog has one.
+    pub fn format(&self, message: &Message) -> String {
+        let english = ENGLISH
+            .iter()
+            .find(|(key, _)| *key == message.key())
+            .map(|(_, template)| *template);
+        match self.get(message.key()).or(english) {
+            Some(template) => fill(template, message.args()),
+            None if message.args().is_empty() => message.key().to_string(),
+            None => format!("{}: {}", message.key(), message.args().join(", ")),
+        }
+    }
+}
+
+/// `template` with its placeholders replaced by `args`; placeholders without an
+/// argument are kept as they are
+fn fill(template: &str, args: &[String]) -> String {
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(index) = rest.find(['{', '}']) {
+        text.push_str(&rest[..index]);
+        rest = &rest[index..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            text.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+        let argument = rest.strip_prefix('{').and_then(|tail| {
+            let (number, tail) = tail.split_once('}')?;
+            Some((args.get(number.parse::<usize>().ok()?)?, tail))
+        });
+        match argument {
+            Some((argument, tail)) => {
+                text.push_str(argument);
+                rest = tail;
+            }
+            None => {
+                text.push_str(&rest[..1]);
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,320 @@
+use crate::import::ImportDescriptor;
+use crate::module_key;
+use crate::port_exe::PortExe;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+
+/// Minimum versions of DLLs and functions shipped with the crate, see `apis.txt`
+const BUNDLED: &str = include_str!("apis.txt");
+
+/// Prefixes of API set contract names, which the loader resolves only from Windows 7 on
+const API_SET_PREFIXES: [&str; 2] = ["api-ms-win-", "ext-ms-win-"];
+
+/// First release that resolves API set contract names
+const API_SET_VERSION: WindowsVersion = WindowsVersion::new(6, 1, 0);
+
+/// Windows releases by NT version, with Windows 10 feature updates by build number
+const RELEASES: [(WindowsVersion, &str); 14] = [
+    (WindowsVersion::new(4, 0, 0), "Windows NT 4.0"),
+    (WindowsVersion::new(5, 0, 0), "Windows 2000"),
+    (WindowsVersion::new(5, 1, 0), "Windows XP"),
+    (WindowsVersion::new(5, 2, 0), "Windows Server 2003"),
+    (WindowsVersion::new(6, 0, 0), "Windows Vista"),
+    (WindowsVersion::new(6, 1, 0), "Windows 7"),
+    (WindowsVersion::new(6, 2, 0), "Windows 8"),
+    (WindowsVersion::new(6, 3, 0), "Windows 8.1"),
+    (WindowsVersion::new(10, 0, 0), "Windows 10"),
+    (WindowsVersion::new(10, 0, 10240), "Windows 10 1507"),
+    (WindowsVersion::new(10, 0, 14393), "Windows 10 1607"),
+    (WindowsVersion::new(10, 0, 15063), "Windows 10 1703"),
+    (WindowsVersion::new(10, 0, 16299), "Windows 10 1709"),
+    (WindowsVersion::new(10, 0, 17763), "Windows 10 1809"),
+];
+
+/// Windows version as NT major, minor and build number; build 0 stands for any build
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WindowsVersion {
+    major: u16,
+    minor: u16,
+    build: u32,
+}
+
+impl WindowsVersion {
+    pub const fn new(major: u16, minor: u16, build: u32) -> Self {
+        Self {
+            major,
+            minor,
+            build,
+        }
+    }
+
+    pub fn major(&self) -> u16 {
+        self.major
+    }
+
+    pub fn minor(&self) -> u16 {
+        self.minor
+    }
+
+    pub fn build(&self) -> u32 {
+        self.build
+    }
+
+    /// Marketing name of the release, `None` for versions no release carries
+    pub fn name(&self) -> Option<&'static str> {
+        RELEASES
+            .iter()
+            .find(|(version, _)| version == self)
+            .map(|&(_, name)| name)
+    }
+
+    /// Parses `major.minor` or `major.minor.build`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parts = text.split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next()?.parse().ok()?;
+        let build = match parts.next() {
+            Some(build) => build.parse().ok()?,
+            None => 0,
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self::new(major, minor, build))
+    }
+}
+
+impl fmt::Display for WindowsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let number = if self.build == 0 {
+            format!("{}.{}", self.major, self.minor)
+        } else {
+            format!("{}.{}.{}", self.major, self.minor, self.build)
+        };
+        match self.name() {
+            Some(name) => write!(f, "{} ({})", name, number),
+            None => f.write_str(&number),
+        }
+    }
+}
+
+/// First Windows version that ships DLLs and functions
+#[derive(Debug, Clone, Default)]
+pub struct ApiVersionMap {
+    modules: HashMap<String, WindowsVersion>,
+    functions: HashMap<(String, String), WindowsVersion>,
+}
+
+impl ApiVersionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map preloaded with common APIs of the system DLLs added from Windows 2000 to
+    /// Windows 10
+    pub fn bundled() -> Self {
+        let mut map = Self::new();
+        map.add_text(BUNDLED)
+            .expect("bundled API version list is valid");
+        map
+    }
+
+    /// Adds lines of `dll function version`, where `function` is `*` for a DLL that
+    /// earlier releases lack entirely. Blank lines and `#` comments are skipped.
+    ///
+    /// Entries override those already known for the same DLL and function.
+    pub fn add_text(&mut self, text: &str) -> io::Result<()> {
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (dll, function, version) = match fields[..] {
+                [dll, function, version] => match WindowsVersion::parse(version) {
+                    Some(version) => (module_key(dll), function, version),
+                    None => {
+                        return Err(invalid_data(format!(
+                            "line {}: invalid version {}",
+                            number + 1,
+                            version
+                        )))
+                    }
+                },
+                _ => {
+                    return Err(invalid_data(format!(
+                        "line {}: expected a DLL, a function and a version",
+                        number + 1
+                    )))
+                }
+            };
+            if function == "*" {
+                self.modules.insert(dll, version);
+            } else {
+                self.functions.insert((dll, function.to_string()), version);
+            }
+        }
+        Ok(())
+    }
+
+    /// First version that ships `function` of `dll`, ignoring case and a `.dll`
+    /// extension in the DLL name; `function` is `None` for imports by ordinal.
+    ///
+    /// A function newer than its DLL takes the later of the two versions. API set
+    /// contracts need at least Windows 7.
+    pub fn requirement(&self, dll: &str, function: Option<&str>) -> Option<WindowsVersion> {
+        let dll = module_key(dll);
+        let module = self.modules.get(&dll).copied().or_else(|| {
+            API_SET_PREFIXES
+                .iter()
+                .any(|prefix| dll.starts_with(prefix))
+                .then(|| API_SET_VERSION)
+        });
+        let function = function.and_then(|function| {
+            self.functions
+                .get(&(dll.clone(), function.to_string()))
+                .copied()
+        });
+        module.max(function)
+    }
+}
+
+/// Import that needs a newer Windows than the header declares
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockingImport {
+    dll: String,
+    function: Option<String>,
+    version: WindowsVersion,
+}
+
+impl BlockingImport {
+    pub fn dll(&self) -> &str {
+        &self.dll
+    }
+
+    /// Imported name, `None` for imports by ordinal
+    pub fn function(&self) -> Option<&str> {
+        self.function.as_deref()
+    }
+
+    /// First version that ships the function
+    pub fn version(&self) -> WindowsVersion {
+        self.version
+    }
+}
+
+/// Oldest Windows an image can run on, see [`PortExe::minimum_os`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinimumOs {
+    os_version: WindowsVersion,
+    subsystem_version: WindowsVersion,
+    minimum: WindowsVersion,
+    blocking: Vec<BlockingImport>,
+    delay_loaded: Vec<BlockingImport>,
+}
+
+impl MinimumOs {
+    /// `MajorOperatingSystemVersion` and `MinorOperatingSystemVersion`, which the loader
+    /// ignores
+    pub fn os_version(&self) -> WindowsVersion {
+        self.os_version
+    }
+
+    /// `MajorSubsystemVersion` and `MinorSubsystemVersion`; the loader refuses images
+    /// that declare a newer subsystem than the running system
+    pub fn subsystem_version(&self) -> WindowsVersion {
+        self.subsystem_version
+    }
+
+    /// Later of the subsystem version and every import requirement
+    pub fn minimum(&self) -> WindowsVersion {
+        self.minimum
+    }
+
+    /// Imports needing a newer version than the subsystem version, newest first and then
+    /// in import order
+    pub fn blocking(&self) -> &[BlockingImport] {
+        &self.blocking
+    }
+
+    /// Delay-loaded imports needing a newer version than the subsystem version, in the
+    /// order of [`MinimumOs::blocking`]. They do not stop the image from loading and are
+    /// left out of [`MinimumOs::minimum`]; a call to one fails when it is first made.
+    pub fn delay_loaded(&self) -> &[BlockingImport] {
+        &self.delay_loaded
+    }
+
+    /// Whether the imports need a newer Windows than the header declares
+    pub fn is_understated(&self) -> bool {
+        !self.blocking.is_empty()
+    }
+}
+
+impl PortExe {
+    /// Estimates the oldest Windows the image runs on from the subsystem version in its
+    /// header and the minimum versions `map` gives for its imports; `None` for object
+    /// files.
+    ///
+    /// Imports `map` does not know are assumed to be available everywhere, so the
+    /// estimate is a lower bound. Delay-loaded imports do not stop the image from
+    /// loading, so they are reported apart and do not raise the estimate.
+    pub fn minimum_os(&self, map: &ApiVersionMap) -> io::Result<Option<MinimumOs>> {
+        let header = match self.optional_header() {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let os_version =
+            WindowsVersion::new(header.major_os_version(), header.minor_os_version(), 0);
+        let subsystem_version = WindowsVersion::new(
+            header.major_subsystem_version(),
+            header.minor_subsystem_version(),
+            0,
+        );
+
+        let blocking = newer_imports(&self.imports()?, map, subsystem_version);
+        let delay_loaded = newer_imports(&self.delay_imports()?, map, subsystem_version);
+        let minimum = blocking
+            .first()
+            .map_or(subsystem_version, |import| import.version);
+        Ok(Some(MinimumOs {
+            os_version,
+            subsystem_version,
+            minimum,
+            blocking,
+            delay_loaded,
+        }))
+    }
+}
+
+/// Imports of `descriptors` that `map` says need a newer version than `baseline`,
+/// newest first and then in import order
+fn newer_imports(
+    descriptors: &[ImportDescriptor],
+    map: &ApiVersionMap,
+    baseline: WindowsVersion,
+) -> Vec<BlockingImport> {
+    let mut newer = Vec::new();
+    for descriptor in descriptors {
+        for import in descriptor.imports() {
+            let function = import.name().map(|name| name.to_string());
+            let version = match map.requirement(descriptor.dll_name(), function.as_deref()) {
+                Some(version) if version > baseline => version,
+                _ => continue,
+            };
+            newer.push(BlockingImport {
+                dll: descriptor.dll_name().to_string(),
+                function,
+                version,
+            });
+        }
+    }
+    // Stable, so imports of the same version keep their order
+    newer.sort_by_key(|import| Reverse(import.version));
+    newer
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
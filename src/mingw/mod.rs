@@ -0,0 +1,388 @@
+use crate::dwarf::DwarfSection;
+use crate::endian::Guid;
+use crate::optional_header::IMAGE_DIRECTORY_ENTRY_IMPORT;
+use crate::pe_string::PeString;
+use crate::port_exe::PortExe;
+use crate::{read_u32, read_u64};
+use std::io;
+use std::ops::Range;
+
+/// Upper bound on the pointers read from an initializer list
+pub const MAX_INITIALIZERS: usize = 0x1000;
+pub use crate::debug::CODEVIEW_PDB70_SIGNATURE;
+
+/// Size of an `IMAGE_IMPORT_DESCRIPTOR`
+const IMAGE_SIZEOF_IMPORT_DESCRIPTOR: u32 = 20;
+/// Bits of an import-by-name thunk holding the hint/name RVA
+const HINT_NAME_RVA_MASK: u32 = 0x7FFF_FFFF;
+
+/// Part of the import data GNU ld collects from the grouped `.idata$n` sections
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdataPiece {
+    /// Import descriptors, `.idata$2` and the null descriptor of `.idata$3`
+    Descriptors,
+    /// Import lookup tables, `.idata$4`
+    LookupTables,
+    /// Import address tables, `.idata$5`
+    AddressTables,
+    /// Hint/name entries, `.idata$6`
+    HintNames,
+    /// DLL names, `.idata$7`
+    DllNames,
+}
+
+impl IdataPiece {
+    const ALL: [Self; 5] = [
+        Self::Descriptors,
+        Self::LookupTables,
+        Self::AddressTables,
+        Self::HintNames,
+        Self::DllNames,
+    ];
+
+    /// Grouped section the piece comes from in GNU import libraries
+    pub fn grouped_section(&self) -> &'static str {
+        match self {
+            Self::Descriptors => ".idata$2",
+            Self::LookupTables => ".idata$4",
+            Self::AddressTables => ".idata$5",
+            Self::HintNames => ".idata$6",
+            Self::DllNames => ".idata$7",
+        }
+    }
+}
+
+/// Addresses a piece of the import data spans, from its lowest to its highest byte
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdataSpan {
+    piece: IdataPiece,
+    rva: Range<u32>,
+}
+
+impl IdataSpan {
+    pub fn piece(&self) -> IdataPiece {
+        self.piece
+    }
+
+    pub fn rva(&self) -> Range<u32> {
+        self.rva.clone()
+    }
+}
+
+/// Initializer list of the mingw C runtime, named by its `__x?_a` start symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrtList {
+    /// `__xi_a`: C initializers
+    CInitializers,
+    /// `__xc_a`: C++ constructors
+    CxxInitializers,
+    /// `__xl_a`: TLS callbacks
+    TlsCallbacks,
+    /// `__xp_a`: pre-terminators
+    Preterminators,
+    /// `__xt_a`: terminators
+    Terminators,
+}
+
+impl CrtList {
+    /// Recognises the start symbol, with or without the extra underscore of x86
+    fn from_symbol(name: &str) -> Option<Self> {
+        match name.trim_start_matches('_') {
+            "xi_a" => Some(Self::CInitializers),
+            "xc_a" => Some(Self::CxxInitializers),
+            "xl_a" => Some(Self::TlsCallbacks),
+            "xp_a" => Some(Self::Preterminators),
+            "xt_a" => Some(Self::Terminators),
+            _ => None,
+        }
+    }
+}
+
+/// Run of function pointers in the `.CRT` section, between null sentinels
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitializerList {
+    kind: Option<CrtList>,
+    rva: u32,
+    entries: Vec<u64>,
+}
+
+impl InitializerList {
+    /// Kind of the list, when the COFF symbols name it
+    pub fn kind(&self) -> Option<CrtList> {
+        self.kind
+    }
+
+    /// Address of the first entry
+    pub fn rva(&self) -> u32 {
+        self.rva
+    }
+
+    /// Virtual addresses of the functions, in call order
+    pub fn entries(&self) -> &[u64] {
+        &self.entries
+    }
+}
+
+/// Build ID GNU ld stores as a CodeView record in the `.buildid` section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildId {
+    id: [u8; 16],
+    age: u32,
+    pdb_path: PeString,
+}
+
+impl BuildId {
+    /// Build ID bytes, stored in the GUID field of the record
+    pub fn id(&self) -> [u8; 16] {
+        self.id
+    }
+
+    /// The ID read as the GUID of the record, the way debuggers key PDB lookups
+    pub fn guid(&self) -> Guid {
+        Guid::from_bytes(self.id)
+    }
+
+    pub fn age(&self) -> u32 {
+        self.age
+    }
+
+    /// PDB path of the record, usually empty
+    pub fn pdb_path(&self) -> &PeString {
+        &self.pdb_path
+    }
+}
+
+/// Structures the GNU toolchain leaves in mingw images
+#[derive(Debug, Clone, Default)]
+pub struct MingwSections {
+    idata: Vec<IdataSpan>,
+    crt: Vec<InitializerList>,
+    tls_callbacks: Vec<u64>,
+    build_id: Option<BuildId>,
+    dwarf: Vec<DwarfSection>,
+}
+
+impl MingwSections {
+    /// Pieces of the import data that are present, in `.idata$n` order
+    pub fn idata(&self) -> &[IdataSpan] {
+        &self.idata
+    }
+
+    /// Initializer lists of the `.CRT` section, in section order
+    pub fn crt(&self) -> &[InitializerList] {
+        &self.crt
+    }
+
+    /// Virtual addresses of the TLS callbacks, in call order
+    pub fn tls_callbacks(&self) -> &[u64] {
+        &self.tls_callbacks
+    }
+
+    pub fn build_id(&self) -> Option<&BuildId> {
+        self.build_id.as_ref()
+    }
+
+    pub fn dwarf(&self) -> &[DwarfSection] {
+        &self.dwarf
+    }
+
+    /// Returns `true` if none of the structures were found.
+    pub fn is_empty(&self) -> bool {
+        self.idata.is_empty()
+            && self.crt.is_empty()
+            && self.tls_callbacks.is_empty()
+            && self.build_id.is_none()
+            && self.dwarf.is_empty()
+    }
+}
+
+impl PortExe {
+    /// Decodes the import data pieces, `.CRT` initializer lists, TLS callbacks, build ID
+    /// and DWARF sections GNU linkers produce; see [`PortExe::dwarf_summary`] for the
+    /// contents of the latter.
+    ///
+    /// Each part is looked up on its own, so MSVC images still report their imports
+    /// and TLS callbacks.
+    pub fn mingw_sections(&self) -> io::Result<MingwSections> {
+        let mut sections = MingwSections {
+            idata: self.idata_spans()?,
+            tls_callbacks: self.tls_callbacks(),
+            dwarf: self.dwarf_sections(),
+            ..MingwSections::default()
+        };
+        let symbols = self.coff_symbols().unwrap_or_default();
+        for (index, section) in self.section_headers().iter().enumerate() {
+            let name = self.section_name(section);
+            let data = self.section_data(section);
+            let data = match section.virtual_size().into_value() {
+                0 => data,
+                size => &data[..data.len().min(size as usize)],
+            };
+            if name == ".CRT" {
+                let rva = section.virtual_address().into_value();
+                let starts: Vec<(u32, CrtList)> = symbols
+                    .iter()
+                    .filter(|symbol| symbol.section_number() as usize == index + 1)
+                    .filter_map(|symbol| {
+                        Some((symbol.value(), CrtList::from_symbol(symbol.name())?))
+                    })
+                    .collect();
+                sections.crt = self.initializer_lists(data, rva, &starts);
+            } else if name == ".buildid" {
+                sections.build_id = build_id(data);
+            }
+        }
+        Ok(sections)
+    }
+
+    /// Extent of each piece of the import data, derived from the import directory
+    fn idata_spans(&self) -> io::Result<Vec<IdataSpan>> {
+        let header = match self.optional_header() {
+            Some(header) => header,
+            None => return Ok(Vec::new()),
+        };
+        let directory_rva = match header.data_directory(IMAGE_DIRECTORY_ENTRY_IMPORT) {
+            Some(directory) => directory.virtual_address().into_value(),
+            None => return Ok(Vec::new()),
+        };
+        let descriptors = self.imports()?;
+        if descriptors.is_empty() {
+            return Ok(Vec::new());
+        }
+        let pe64 = header.image_type().is_x64();
+        let thunk_size: u32 = if pe64 { 8 } else { 4 };
+
+        let mut spans: [Option<Range<u32>>; 5] = Default::default();
+        let mut extend = |piece: IdataPiece, start: u32, size: u32| {
+            let end = start.saturating_add(size);
+            let span = &mut spans[piece as usize];
+            *span = Some(match span.take() {
+                Some(span) => span.start.min(start)..span.end.max(end),
+                None => start..end,
+            });
+        };
+        let count = descriptors.len() as u32 + 1;
+        extend(
+            IdataPiece::Descriptors,
+            directory_rva,
+            count.saturating_mul(IMAGE_SIZEOF_IMPORT_DESCRIPTOR),
+        );
+        for descriptor in &descriptors {
+            let table_size = (descriptor.imports().len() as u32 + 1).saturating_mul(thunk_size);
+            let lookup_table = match descriptor.original_first_thunk() {
+                0 => descriptor.first_thunk(),
+                rva => {
+                    extend(IdataPiece::LookupTables, rva, table_size);
+                    rva
+                }
+            };
+            extend(
+                IdataPiece::AddressTables,
+                descriptor.first_thunk(),
+                table_size,
+            );
+            if let Some(name) = self.name_at_rva(descriptor.name_rva()) {
+                extend(
+                    IdataPiece::DllNames,
+                    descriptor.name_rva(),
+                    name.len() as u32 + 1,
+                );
+            }
+            for (index, import) in descriptor.imports().iter().enumerate() {
+                let name = match import.name() {
+                    Some(name) => name,
+                    None => continue,
+                };
+                let hint_name_rva = (index as u32)
+                    .checked_mul(thunk_size)
+                    .and_then(|offset| lookup_table.checked_add(offset))
+                    .and_then(|rva| self.data_at_rva(rva, 4))
+                    .and_then(|thunk| read_u32(thunk, 0));
+                if let Some(hint_name_rva) = hint_name_rva {
+                    extend(
+                        IdataPiece::HintNames,
+                        hint_name_rva & HINT_NAME_RVA_MASK,
+                        name.len() as u32 + 3,
+                    );
+                }
+            }
+        }
+        Ok(IdataPiece::ALL
+            .iter()
+            .zip(spans)
+            .filter_map(|(&piece, rva)| Some(IdataSpan { piece, rva: rva? }))
+            .collect())
+    }
+
+    /// Splits `.CRT` data into runs of non-null pointers. `starts` holds the section
+    /// offsets of the `__x?_a` sentinels, which precede the lists they name.
+    fn initializer_lists(
+        &self,
+        data: &[u8],
+        rva: u32,
+        starts: &[(u32, CrtList)],
+    ) -> Vec<InitializerList> {
+        let pointer_size = self.pointer_size();
+        let mut lists: Vec<InitializerList> = Vec::new();
+        let mut open = false;
+        for (index, slot) in data
+            .chunks_exact(pointer_size)
+            .take(MAX_INITIALIZERS)
+            .enumerate()
+        {
+            let pointer = read_pointer(slot, 0, pointer_size).unwrap_or(0);
+            if pointer == 0 {
+                open = false;
+                continue;
+            }
+            if !open {
+                let offset = (index * pointer_size) as u32;
+                let kind = starts
+                    .iter()
+                    .find(|(start, _)| start.wrapping_add(pointer_size as u32) == offset)
+                    .map(|&(_, kind)| kind);
+                lists.push(InitializerList {
+                    kind,
+                    rva: rva.wrapping_add(offset),
+                    entries: Vec::new(),
+                });
+                open = true;
+            }
+            if let Some(list) = lists.last_mut() {
+                list.entries.push(pointer);
+            }
+        }
+        lists
+    }
+
+    fn pointer_size(&self) -> usize {
+        match self.optional_header() {
+            Some(header) if header.image_type().is_x64() => 8,
+            _ => 4,
+        }
+    }
+}
+
+fn read_pointer(data: &[u8], offset: usize, size: usize) -> Option<u64> {
+    if size == 8 {
+        read_u64(data, offset)
+    } else {
+        read_u32(data, offset).map(u64::from)
+    }
+}
+
+/// Decodes the `RSDS` record at the start of a `.buildid` section.
+fn build_id(data: &[u8]) -> Option<BuildId> {
+    if read_u32(data, 0)? != CODEVIEW_PDB70_SIGNATURE {
+        return None;
+    }
+    let mut id = [0u8; 16];
+    id.copy_from_slice(data.get(4..20)?);
+    let path = data.get(24..)?;
+    let end = path.iter().position(|&b| b == 0).unwrap_or(path.len());
+    Some(BuildId {
+        id,
+        age: read_u32(data, 20)?,
+        pdb_path: PeString::from_bytes(&path[..end]),
+    })
+}
@@ -0,0 +1,336 @@
+use crate::arith::slice_at;
+use crate::endian::{utf16_units, ByteOrder};
+use crate::port_exe::PortExe;
+use crate::{read_u32, read_u64};
+use std::fmt;
+use std::io;
+
+/// `MDMP`
+const MINIDUMP_SIGNATURE: u32 = 0x504D_444D;
+const MODULE_LIST_STREAM: u32 = 4;
+const MEMORY_LIST_STREAM: u32 = 5;
+const MEMORY64_LIST_STREAM: u32 = 9;
+const MINIDUMP_MODULE_SIZE: usize = 108;
+const MINIDUMP_MEMORY_DESCRIPTOR_SIZE: usize = 16;
+/// Largest module image rebuilt from a dump, well above any real `SizeOfImage`
+pub const MAX_MODULE_SIZE: u32 = 512 * 1024 * 1024;
+
+/// Module of a minidump's module list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DumpModule {
+    name: String,
+    base: u64,
+    size: u32,
+    checksum: u32,
+    time_date_stamp: u32,
+}
+
+impl DumpModule {
+    /// Full path of the module, as the dumped process loaded it
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// `CheckSum` of the module's headers when it was loaded
+    pub fn checksum(&self) -> u32 {
+        self.checksum
+    }
+
+    /// `TimeDateStamp` of the module's headers when it was loaded
+    pub fn time_date_stamp(&self) -> u32 {
+        self.time_date_stamp
+    }
+}
+
+/// Range of the dumped process's memory captured in the dump
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRange {
+    address: u64,
+    size: u64,
+    offset: u64,
+}
+
+impl MemoryRange {
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Offset of the captured bytes in the dump file
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+/// Disagreement between a module list entry and the image rebuilt from memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DumpModuleWarning {
+    /// Parts of the image were not captured and read as zeros
+    Incomplete { captured: u64, size: u32 },
+    /// The headers in memory carry a different timestamp than the module list recorded
+    TimestampMismatch { listed: u32, header: u32 },
+    /// The headers in memory declare a different `SizeOfImage`
+    SizeMismatch { listed: u32, header: u32 },
+}
+
+impl fmt::Display for DumpModuleWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Incomplete { captured, size } => {
+                write!(f, "only {:#x} of {:#x} bytes were captured", captured, size)
+            }
+            Self::TimestampMismatch { listed, header } => write!(
+                f,
+                "header timestamp {:#010x} differs from the listed {:#010x}",
+                header, listed
+            ),
+            Self::SizeMismatch { listed, header } => write!(
+                f,
+                "header SizeOfImage {:#x} differs from the listed {:#x}",
+                header, listed
+            ),
+        }
+    }
+}
+
+/// Module of a minidump re-parsed from the captured memory
+#[derive(Debug)]
+pub struct ModuleReport {
+    module: DumpModule,
+    captured: u64,
+    image: io::Result<PortExe>,
+    warnings: Vec<DumpModuleWarning>,
+}
+
+impl ModuleReport {
+    pub fn module(&self) -> &DumpModule {
+        &self.module
+    }
+
+    /// Bytes of the image found in the dump's memory ranges
+    pub fn captured(&self) -> u64 {
+        self.captured
+    }
+
+    /// The image in the mapped layout, or why it could not be parsed
+    pub fn image(&self) -> Result<&PortExe, &io::Error> {
+        self.image.as_ref()
+    }
+
+    pub fn into_image(self) -> io::Result<PortExe> {
+        self.image
+    }
+
+    pub fn warnings(&self) -> &[DumpModuleWarning] {
+        &self.warnings
+    }
+}
+
+/// Windows minidump, as written by `MiniDumpWriteDump`, reduced to the module list
+/// and the captured memory.
+#[derive(Debug)]
+pub struct Minidump {
+    data: Vec<u8>,
+    modules: Vec<DumpModule>,
+    ranges: Vec<MemoryRange>,
+}
+
+impl Minidump {
+    /// Reads the stream directory, module list and memory lists of a dump.
+    pub fn parse(data: Vec<u8>) -> io::Result<Self> {
+        let truncated = || invalid_data("truncated minidump");
+        if read_u32(&data, 0) != Some(MINIDUMP_SIGNATURE) {
+            return Err(invalid_data("missing minidump signature"));
+        }
+        let stream_count = read_u32(&data, 8).ok_or_else(truncated)? as usize;
+        let directory = read_u32(&data, 12).ok_or_else(truncated)? as usize;
+        let mut dump = Self {
+            data,
+            modules: Vec::new(),
+            ranges: Vec::new(),
+        };
+        for index in 0..stream_count {
+            let entry = directory + index * 12;
+            let stream_type = read_u32(&dump.data, entry).ok_or_else(truncated)?;
+            let size = read_u32(&dump.data, entry + 4).ok_or_else(truncated)? as usize;
+            let offset = read_u32(&dump.data, entry + 8).ok_or_else(truncated)? as usize;
+            let stream = slice_at(&dump.data, offset, size)
+                .ok_or_else(|| invalid_data("minidump stream outside the file"))?;
+            match stream_type {
+                MODULE_LIST_STREAM => dump.modules = read_modules(&dump.data, stream)?,
+                MEMORY_LIST_STREAM => dump.ranges.extend(read_memory_list(stream)?),
+                MEMORY64_LIST_STREAM => dump.ranges.extend(read_memory64_list(stream)?),
+                _ => {}
+            }
+        }
+        dump.ranges.sort_by_key(|range| range.address);
+        Ok(dump)
+    }
+
+    /// Modules in the order the module list stores them
+    pub fn modules(&self) -> &[DumpModule] {
+        &self.modules
+    }
+
+    /// Captured memory, sorted by address
+    pub fn memory_ranges(&self) -> &[MemoryRange] {
+        &self.ranges
+    }
+
+    /// Copies the captured bytes of `address..address + size` into a zero-filled
+    /// buffer, returning it with the number of bytes that were captured.
+    pub fn read_memory(&self, address: u64, size: u64) -> (Vec<u8>, u64) {
+        let mut buffer = vec![0u8; size as usize];
+        let end = address.saturating_add(size);
+        let mut captured = 0;
+        for range in &self.ranges {
+            let range_end = range.address.saturating_add(range.size);
+            let start = range.address.max(address);
+            let stop = range_end.min(end);
+            if start >= stop {
+                continue;
+            }
+            let source = range.offset.saturating_add(start - range.address);
+            let length = (stop - start) as usize;
+            if let Some(bytes) = slice_at(&self.data, source as usize, length) {
+                let target = (start - address) as usize;
+                buffer[target..target + length].copy_from_slice(bytes);
+                captured += length as u64;
+            }
+        }
+        (buffer, captured)
+    }
+
+    /// Rebuilds every module of the module list from the captured memory and parses
+    /// it as a mapped image, checking the headers against the module list.
+    pub fn module_reports(&self) -> Vec<ModuleReport> {
+        self.modules
+            .iter()
+            .map(|module| self.module_report(module))
+            .collect()
+    }
+
+    fn module_report(&self, module: &DumpModule) -> ModuleReport {
+        let mut report = ModuleReport {
+            module: module.clone(),
+            captured: 0,
+            image: Err(invalid_data("module image too large")),
+            warnings: Vec::new(),
+        };
+        if module.size > MAX_MODULE_SIZE {
+            return report;
+        }
+        let (image, captured) = self.read_memory(module.base, module.size as u64);
+        report.captured = captured;
+        if captured < module.size as u64 {
+            report.warnings.push(DumpModuleWarning::Incomplete {
+                captured,
+                size: module.size,
+            });
+        }
+        report.image = PortExe::from_mapped_bytes(image);
+        if let Ok(pe) = &report.image {
+            let header = pe.file_header().time_date_stamp().into_value();
+            if header != module.time_date_stamp {
+                report.warnings.push(DumpModuleWarning::TimestampMismatch {
+                    listed: module.time_date_stamp,
+                    header,
+                });
+            }
+            if let Some(optional_header) = pe.optional_header() {
+                let header = optional_header.size_of_image();
+                if header != module.size {
+                    report.warnings.push(DumpModuleWarning::SizeMismatch {
+                        listed: module.size,
+                        header,
+                    });
+                }
+            }
+        }
+        report
+    }
+}
+
+fn read_modules(data: &[u8], stream: &[u8]) -> io::Result<Vec<DumpModule>> {
+    let truncated = || invalid_data("truncated minidump module list");
+    let count = read_u32(stream, 0).ok_or_else(truncated)? as usize;
+    let mut modules = Vec::new();
+    for index in 0..count {
+        let entry = slice_at(
+            stream,
+            4 + index * MINIDUMP_MODULE_SIZE,
+            MINIDUMP_MODULE_SIZE,
+        )
+        .ok_or_else(truncated)?;
+        let field = |offset| read_u32(entry, offset).unwrap_or(0);
+        modules.push(DumpModule {
+            name: read_string(data, field(20) as usize)?,
+            base: read_u64(entry, 0).unwrap_or(0),
+            size: field(8),
+            checksum: field(12),
+            time_date_stamp: field(16),
+        });
+    }
+    Ok(modules)
+}
+
+/// Reads a `MINIDUMP_STRING`: a byte length followed by UTF-16.
+fn read_string(data: &[u8], offset: usize) -> io::Result<String> {
+    let truncated = || invalid_data("truncated minidump string");
+    let length = read_u32(data, offset).ok_or_else(truncated)? as usize;
+    let bytes = slice_at(data, offset + 4, length).ok_or_else(truncated)?;
+    Ok(String::from_utf16_lossy(&utf16_units(
+        bytes,
+        ByteOrder::Little,
+    )))
+}
+
+fn read_memory_list(stream: &[u8]) -> io::Result<Vec<MemoryRange>> {
+    let truncated = || invalid_data("truncated minidump memory list");
+    let count = read_u32(stream, 0).ok_or_else(truncated)? as usize;
+    (0..count)
+        .map(|index| {
+            let entry = 4 + index * MINIDUMP_MEMORY_DESCRIPTOR_SIZE;
+            Ok(MemoryRange {
+                address: read_u64(stream, entry).ok_or_else(truncated)?,
+                size: read_u32(stream, entry + 8).ok_or_else(truncated)? as u64,
+                offset: read_u32(stream, entry + 12).ok_or_else(truncated)? as u64,
+            })
+        })
+        .collect()
+}
+
+/// Reads a `Memory64ListStream`, whose ranges are stored back to back from one offset.
+fn read_memory64_list(stream: &[u8]) -> io::Result<Vec<MemoryRange>> {
+    let truncated = || invalid_data("truncated minidump memory list");
+    let count = read_u64(stream, 0).ok_or_else(truncated)?;
+    let mut offset = read_u64(stream, 8).ok_or_else(truncated)?;
+    let mut ranges = Vec::new();
+    for index in 0..count {
+        let entry = 16 + (index as usize).saturating_mul(MINIDUMP_MEMORY_DESCRIPTOR_SIZE);
+        let range = MemoryRange {
+            address: read_u64(stream, entry).ok_or_else(truncated)?,
+            size: read_u64(stream, entry + 8).ok_or_else(truncated)?,
+            offset,
+        };
+        offset = offset.saturating_add(range.size);
+        ranges.push(range);
+    }
+    Ok(ranges)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
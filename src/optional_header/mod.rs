@@ -1,8 +1,343 @@
-use crate::StructField;
+use crate::endian::{le_u16, le_u32, le_u64};
+use crate::{read_block, write_at, ImageType, StructField};
+use std::io;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::ops::Range;
+
+/// Number of data directories defined by the PE format
+pub const IMAGE_NUMBEROF_DIRECTORY_ENTRIES: u32 = 16;
+
+pub const IMAGE_DIRECTORY_ENTRY_EXPORT: usize = 0;
+pub const IMAGE_DIRECTORY_ENTRY_IMPORT: usize = 1;
+pub const IMAGE_DIRECTORY_ENTRY_RESOURCE: usize = 2;
+pub const IMAGE_DIRECTORY_ENTRY_EXCEPTION: usize = 3;
+pub const IMAGE_DIRECTORY_ENTRY_SECURITY: usize = 4;
+pub const IMAGE_DIRECTORY_ENTRY_BASERELOC: usize = 5;
+pub const IMAGE_DIRECTORY_ENTRY_DEBUG: usize = 6;
+pub const IMAGE_DIRECTORY_ENTRY_ARCHITECTURE: usize = 7;
+pub const IMAGE_DIRECTORY_ENTRY_GLOBALPTR: usize = 8;
+pub const IMAGE_DIRECTORY_ENTRY_TLS: usize = 9;
+pub const IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG: usize = 10;
+pub const IMAGE_DIRECTORY_ENTRY_BOUND_IMPORT: usize = 11;
+pub const IMAGE_DIRECTORY_ENTRY_IAT: usize = 12;
+pub const IMAGE_DIRECTORY_ENTRY_DELAY_IMPORT: usize = 13;
+pub const IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR: usize = 14;
+
+const IMAGE_NT_OPTIONAL_HDR32_MAGIC: u16 = 0x010B;
+const IMAGE_NT_OPTIONAL_HDR64_MAGIC: u16 = 0x020B;
+
+/// Size of the fixed part of the PE32 optional header, up to the data directories
+const SIZEOF_OPTIONAL_HEADER_32: usize = 96;
+/// Size of the fixed part of the PE32+ optional header, up to the data directories
+const SIZEOF_OPTIONAL_HEADER_64: usize = 112;
+
+/// Reads the optional header at `offset`. `size` is the file header's
+/// `SizeOfOptionalHeader`, which bounds the directories kept past the standard 16.
+pub(crate) fn read_optional_header<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+    size: u64,
+) -> io::Result<OptionalHeaderWrapper> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut magic = [0u8; 2];
+    reader.read_exact(&mut magic)?;
+    reader.seek(SeekFrom::Start(offset))?;
+
+    match le_u16(magic) {
+        IMAGE_NT_OPTIONAL_HDR32_MAGIC => {
+            let optional_header_32 = read_optional_header_32(reader, offset, size)?;
+            Ok(OptionalHeaderWrapper::X32(OptionalHeader32Wrapper {
+                optional_header_32,
+            }))
+        }
+        IMAGE_NT_OPTIONAL_HDR64_MAGIC => {
+            let optional_header_64 = read_optional_header_64(reader, offset, size)?;
+            Ok(OptionalHeaderWrapper::X64(OptionalHeader64Wrapper {
+                optional_header_64,
+            }))
+        }
+        magic => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported optional header magic {:#06X}", magic),
+        )),
+    }
+}
+
+/// Reads the `count` data directories the header declares into `room` bytes.
+///
+/// The first [`IMAGE_NUMBEROF_DIRECTORY_ENTRIES`] must be present. Entries past them are
+/// ignored by the loader; they are kept as long as they fit in `room`, the space
+/// `SizeOfOptionalHeader` leaves for the table, and in the file.
+fn read_data_directories<R: Read>(
+    reader: &mut R,
+    count: u32,
+    room: u64,
+) -> io::Result<Vec<DataDirectoryRaw>> {
+    let standard = count.min(IMAGE_NUMBEROF_DIRECTORY_ENTRIES) as usize;
+    let table = read_block(reader, standard * 8)?;
+    let mut directories: Vec<_> = table.chunks_exact(8).map(data_directory_raw).collect();
+
+    let extra = (count as usize)
+        .min((room / 8) as usize)
+        .saturating_sub(standard);
+    for _ in 0..extra {
+        let mut entry = [0u8; 8];
+        if reader.read_exact(&mut entry).is_err() {
+            break;
+        }
+        directories.push(data_directory_raw(&entry));
+    }
+    Ok(directories)
+}
+
+fn data_directory_raw(entry: &[u8]) -> DataDirectoryRaw {
+    DataDirectoryRaw {
+        virtual_address: [entry[0], entry[1], entry[2], entry[3]],
+        size: [entry[4], entry[5], entry[6], entry[7]],
+    }
+}
+
+/// Optional header of either bitness.
+///
+/// Accessors on this type return plain values, widened to the PE32+ width where
+/// the two layouts differ. Use the inner wrappers for per-field offsets and raw bytes.
+#[derive(Debug)]
+pub enum OptionalHeaderWrapper {
+    X32(OptionalHeader32Wrapper),
+    X64(OptionalHeader64Wrapper),
+}
+
+impl OptionalHeaderWrapper {
+    /// Encodes the header back into `data` at the offset it was read from.
+    pub(crate) fn write(&self, data: &mut [u8]) {
+        match self {
+            Self::X32(h) => write_at(
+                data,
+                h.optional_header_32.offset,
+                &h.optional_header_32.optional_header_32_raw.to_bytes(),
+            ),
+            Self::X64(h) => write_at(
+                data,
+                h.optional_header_64.offset,
+                &h.optional_header_64.optional_header_64_raw.to_bytes(),
+            ),
+        }
+    }
+
+    /// File range the header and its data directories were read from
+    pub(crate) fn span(&self) -> Range<usize> {
+        let (offset, size) = match self {
+            Self::X32(h) => (
+                h.optional_header_32.offset,
+                h.optional_header_32.optional_header_32_raw.to_bytes().len(),
+            ),
+            Self::X64(h) => (
+                h.optional_header_64.offset,
+                h.optional_header_64.optional_header_64_raw.to_bytes().len(),
+            ),
+        };
+        offset as usize..offset as usize + size
+    }
+
+    pub(crate) fn set_checksum(&mut self, checksum: u32) {
+        match self {
+            Self::X32(h) => {
+                h.optional_header_32.optional_header_32_raw.checksum = checksum.to_le_bytes()
+            }
+            Self::X64(h) => {
+                h.optional_header_64.optional_header_64_raw.checksum = checksum.to_le_bytes()
+            }
+        }
+    }
+
+    pub(crate) fn set_dll_characteristics(&mut self, dll_characteristics: u16) {
+        let bytes = dll_characteristics.to_le_bytes();
+        match self {
+            Self::X32(h) => {
+                h.optional_header_32
+                    .optional_header_32_raw
+                    .dll_characteristics = bytes
+            }
+            Self::X64(h) => {
+                h.optional_header_64
+                    .optional_header_64_raw
+                    .dll_characteristics = bytes
+            }
+        }
+    }
+
+    pub fn image_type(&self) -> ImageType {
+        match self {
+            Self::X32(_) => ImageType::X32,
+            Self::X64(_) => ImageType::X64,
+        }
+    }
+
+    pub fn address_of_entry_point(&self) -> u32 {
+        match self {
+            Self::X32(h) => h.address_of_entry_point().into_value(),
+            Self::X64(h) => h.address_of_entry_point().into_value(),
+        }
+    }
+
+    pub fn major_os_version(&self) -> u16 {
+        match self {
+            Self::X32(h) => h.major_os_version().into_value(),
+            Self::X64(h) => h.major_os_version().into_value(),
+        }
+    }
+
+    pub fn minor_os_version(&self) -> u16 {
+        match self {
+            Self::X32(h) => h.minor_os_version().into_value(),
+            Self::X64(h) => h.minor_os_version().into_value(),
+        }
+    }
+
+    pub fn major_subsystem_version(&self) -> u16 {
+        match self {
+            Self::X32(h) => h.major_subsystem_version().into_value(),
+            Self::X64(h) => h.major_subsystem_version().into_value(),
+        }
+    }
+
+    pub fn minor_subsystem_version(&self) -> u16 {
+        match self {
+            Self::X32(h) => h.minor_subsystem_version().into_value(),
+            Self::X64(h) => h.minor_subsystem_version().into_value(),
+        }
+    }
 
+    /// `MajorLinkerVersion` and `MinorLinkerVersion`
+    pub fn linker_version(&self) -> (u8, u8) {
+        match self {
+            Self::X32(h) => (
+                h.major_linker_version().into_value(),
+                h.minor_linker_version().into_value(),
+            ),
+            Self::X64(h) => (
+                h.major_linker_version().into_value(),
+                h.minor_linker_version().into_value(),
+            ),
+        }
+    }
+
+    pub fn image_base(&self) -> u64 {
+        match self {
+            Self::X32(h) => h.image_base().into_value() as u64,
+            Self::X64(h) => h.image_base().into_value(),
+        }
+    }
+
+    pub fn section_alignment(&self) -> u32 {
+        match self {
+            Self::X32(h) => h.section_alignment().into_value(),
+            Self::X64(h) => h.section_alignment().into_value(),
+        }
+    }
+
+    pub fn file_alignment(&self) -> u32 {
+        match self {
+            Self::X32(h) => h.file_alignment().into_value(),
+            Self::X64(h) => h.file_alignment().into_value(),
+        }
+    }
+
+    pub fn size_of_image(&self) -> u32 {
+        match self {
+            Self::X32(h) => h.size_of_image().into_value(),
+            Self::X64(h) => h.size_of_image().into_value(),
+        }
+    }
+
+    pub fn size_of_headers(&self) -> u32 {
+        match self {
+            Self::X32(h) => h.size_of_headers().into_value(),
+            Self::X64(h) => h.size_of_headers().into_value(),
+        }
+    }
+
+    pub fn checksum(&self) -> u32 {
+        match self {
+            Self::X32(h) => h.checksum().into_value(),
+            Self::X64(h) => h.checksum().into_value(),
+        }
+    }
+
+    pub fn subsystem(&self) -> WindowsSubsystem {
+        let subsystem = match self {
+            Self::X32(h) => h.subsystem().into_value(),
+            Self::X64(h) => h.subsystem().into_value(),
+        };
+        WindowsSubsystem::from(subsystem)
+    }
+
+    pub fn dll_characteristics(&self) -> DllCharacteristics {
+        let dll_characteristics = match self {
+            Self::X32(h) => h.dll_characteristics().into_value(),
+            Self::X64(h) => h.dll_characteristics().into_value(),
+        };
+        DllCharacteristics::from(dll_characteristics)
+    }
+
+    /// Declared `NumberOfRvaAndSizes`, which may exceed the entries actually read
+    pub fn number_of_rva_and_sizes(&self) -> u32 {
+        match self {
+            Self::X32(h) => h.number_of_rva_and_sizes().into_value(),
+            Self::X64(h) => h.number_of_rva_and_sizes().into_value(),
+        }
+    }
+
+    /// Data directories the header declares, including any past the standard
+    /// [`IMAGE_NUMBEROF_DIRECTORY_ENTRIES`] that fit in `SizeOfOptionalHeader`.
+    pub fn data_directories(&self) -> Vec<DataDirectoryWrapper> {
+        match self {
+            Self::X32(h) => h.data_directories(),
+            Self::X64(h) => h.data_directories(),
+        }
+    }
+
+    /// Returns the data directory at `index` if the header declares it.
+    pub fn data_directory(&self, index: usize) -> Option<DataDirectoryWrapper> {
+        self.data_directories().into_iter().nth(index)
+    }
+
+    /// Virtual address, at `ImageBase`, of the value the loader places in the global
+    /// pointer register, `None` if the `GlobalPtr` directory is absent or zero.
+    ///
+    /// Only IA64 images use it; unlike other directories the entry points at the value
+    /// itself and its `Size` is unused.
+    pub fn global_ptr(&self) -> Option<u64> {
+        let rva = self
+            .data_directory(IMAGE_DIRECTORY_ENTRY_GLOBALPTR)?
+            .virtual_address()
+            .into_value();
+        (rva != 0).then(|| self.image_base().wrapping_add(rva as u64))
+    }
+
+    /// `VirtualAddress` and `Size` of the `Architecture` directory, `None` if it is
+    /// absent or zero.
+    ///
+    /// The specification reserves the entry; only Alpha AXP images stored an
+    /// `IMAGE_ARCHITECTURE_HEADER` of instruction fixups there.
+    pub fn architecture_directory(&self) -> Option<(u32, u32)> {
+        let directory = self.data_directory(IMAGE_DIRECTORY_ENTRY_ARCHITECTURE)?;
+        let entry = (
+            directory.virtual_address().into_value(),
+            directory.size().into_value(),
+        );
+        (entry != (0, 0)).then(|| entry)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum WindowsSubsystem {
     Unknown,
     Native,
+    WindowsGraphicalUI,
     WindowsConsoleUI,
     OS2ConsoleUI,
     POSIXConsoleUI,
@@ -16,21 +351,159 @@ pub enum WindowsSubsystem {
     WindowsBootApplication,
 }
 
-const IMAGE_SUBSYSTEM_UNKNOWN: u16 = 0;
-const IMAGE_SUBSYSTEM_NATIVE: u16 = 1;
-const IMAGE_SUBSYSTEM_WINDOWS_GUI: u16 = 2;
-const IMAGE_SUBSYSTEM_WINDOWS_CUI: u16 = 3;
-const IMAGE_SUBSYSTEM_OS2_CUI: u16 = 5;
-const IMAGE_SUBSYSTEM_POSIX_CUI: u16 = 7;
-const IMAGE_SUBSYSTEM_NATIVE_WINDOWS: u16 = 8;
-const IMAGE_SUBSYSTEM_WINDOWS_CE_GUI: u16 = 9;
-const IMAGE_SUBSYSTEM_EFI_APPLICATION: u16 = 10;
-const IMAGE_SUBSYSTEM_EFI_BOOT_SERVICE_DRIVER: u16 = 11;
-const IMAGE_SUBSYSTEM_EFI_RUNTIME_DRIVER: u16 = 12;
-const IMAGE_SUBSYSTEM_EFI_ROM: u16 = 13;
-const IMAGE_SUBSYSTEM_XBOX: u16 = 14;
-const IMAGE_SUBSYSTEM_WINDOWS_BOOT_APPLICATION: u16 = 16;
+impl From<u16> for WindowsSubsystem {
+    fn from(value: u16) -> Self {
+        match value {
+            IMAGE_SUBSYSTEM_NATIVE => Self::Native,
+            IMAGE_SUBSYSTEM_WINDOWS_GUI => Self::WindowsGraphicalUI,
+            IMAGE_SUBSYSTEM_WINDOWS_CUI => Self::WindowsConsoleUI,
+            IMAGE_SUBSYSTEM_OS2_CUI => Self::OS2ConsoleUI,
+            IMAGE_SUBSYSTEM_POSIX_CUI => Self::POSIXConsoleUI,
+            IMAGE_SUBSYSTEM_NATIVE_WINDOWS => Self::NativeWindows,
+            IMAGE_SUBSYSTEM_WINDOWS_CE_GUI => Self::WindowsCEGraphicalUI,
+            IMAGE_SUBSYSTEM_EFI_APPLICATION => Self::EFIApplication,
+            IMAGE_SUBSYSTEM_EFI_BOOT_SERVICE_DRIVER => Self::EFIBootServiceDriver,
+            IMAGE_SUBSYSTEM_EFI_RUNTIME_DRIVER => Self::EFIRuntimeDriver,
+            IMAGE_SUBSYSTEM_EFI_ROM => Self::EFIROM,
+            IMAGE_SUBSYSTEM_XBOX => Self::Xbox,
+            IMAGE_SUBSYSTEM_WINDOWS_BOOT_APPLICATION => Self::WindowsBootApplication,
+            IMAGE_SUBSYSTEM_UNKNOWN => Self::Unknown,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+pub const IMAGE_SUBSYSTEM_UNKNOWN: u16 = 0;
+pub const IMAGE_SUBSYSTEM_NATIVE: u16 = 1;
+pub const IMAGE_SUBSYSTEM_WINDOWS_GUI: u16 = 2;
+pub const IMAGE_SUBSYSTEM_WINDOWS_CUI: u16 = 3;
+pub const IMAGE_SUBSYSTEM_OS2_CUI: u16 = 5;
+pub const IMAGE_SUBSYSTEM_POSIX_CUI: u16 = 7;
+pub const IMAGE_SUBSYSTEM_NATIVE_WINDOWS: u16 = 8;
+pub const IMAGE_SUBSYSTEM_WINDOWS_CE_GUI: u16 = 9;
+pub const IMAGE_SUBSYSTEM_EFI_APPLICATION: u16 = 10;
+pub const IMAGE_SUBSYSTEM_EFI_BOOT_SERVICE_DRIVER: u16 = 11;
+pub const IMAGE_SUBSYSTEM_EFI_RUNTIME_DRIVER: u16 = 12;
+pub const IMAGE_SUBSYSTEM_EFI_ROM: u16 = 13;
+pub const IMAGE_SUBSYSTEM_XBOX: u16 = 14;
+pub const IMAGE_SUBSYSTEM_WINDOWS_BOOT_APPLICATION: u16 = 16;
+
+fn read_optional_header_32<R: Read>(
+    reader: &mut R,
+    offset: u64,
+    size: u64,
+) -> io::Result<OptionalHeader32> {
+    let mut magic = [0u8; 2];
+    let mut major_linker_version = [0u8; 1];
+    let mut minor_linker_version = [0u8; 1];
+    let mut size_of_code = [0u8; 4];
+    let mut size_of_initialized_data = [0u8; 4];
+    let mut size_of_uninitialized_data = [0u8; 4];
+    let mut address_of_entry_point = [0u8; 4];
+    let mut base_of_code = [0u8; 4];
+    let mut base_of_data = [0u8; 4];
+    let mut image_base = [0u8; 4];
+    let mut section_alignment = [0u8; 4];
+    let mut file_alignment = [0u8; 4];
+    let mut major_os_version = [0u8; 2];
+    let mut minor_os_version = [0u8; 2];
+    let mut major_image_version = [0u8; 2];
+    let mut minor_image_version = [0u8; 2];
+    let mut major_subsystem_version = [0u8; 2];
+    let mut minor_subsystem_version = [0u8; 2];
+    let mut win32_version_value = [0u8; 4];
+    let mut size_of_image = [0u8; 4];
+    let mut size_of_headers = [0u8; 4];
+    let mut checksum = [0u8; 4];
+    let mut subsystem = [0u8; 2];
+    let mut dll_characteristics = [0u8; 2];
+    let mut size_of_stack_reserve = [0u8; 4];
+    let mut size_of_stack_commit = [0u8; 4];
+    let mut size_of_heap_reserve = [0u8; 4];
+    let mut size_of_heap_commit = [0u8; 4];
+    let mut loader_flags = [0u8; 4];
+    let mut number_of_rva_and_sizes = [0u8; 4];
+
+    let block = read_block(reader, SIZEOF_OPTIONAL_HEADER_32)?;
+    let mut fields = &block[..];
+
+    fields.read_exact(&mut magic)?;
+    fields.read_exact(&mut major_linker_version)?;
+    fields.read_exact(&mut minor_linker_version)?;
+    fields.read_exact(&mut size_of_code)?;
+    fields.read_exact(&mut size_of_initialized_data)?;
+    fields.read_exact(&mut size_of_uninitialized_data)?;
+    fields.read_exact(&mut address_of_entry_point)?;
+    fields.read_exact(&mut base_of_code)?;
+    fields.read_exact(&mut base_of_data)?;
+    fields.read_exact(&mut image_base)?;
+    fields.read_exact(&mut section_alignment)?;
+    fields.read_exact(&mut file_alignment)?;
+    fields.read_exact(&mut major_os_version)?;
+    fields.read_exact(&mut minor_os_version)?;
+    fields.read_exact(&mut major_image_version)?;
+    fields.read_exact(&mut minor_image_version)?;
+    fields.read_exact(&mut major_subsystem_version)?;
+    fields.read_exact(&mut minor_subsystem_version)?;
+    fields.read_exact(&mut win32_version_value)?;
+    fields.read_exact(&mut size_of_image)?;
+    fields.read_exact(&mut size_of_headers)?;
+    fields.read_exact(&mut checksum)?;
+    fields.read_exact(&mut subsystem)?;
+    fields.read_exact(&mut dll_characteristics)?;
+    fields.read_exact(&mut size_of_stack_reserve)?;
+    fields.read_exact(&mut size_of_stack_commit)?;
+    fields.read_exact(&mut size_of_heap_reserve)?;
+    fields.read_exact(&mut size_of_heap_commit)?;
+    fields.read_exact(&mut loader_flags)?;
+    fields.read_exact(&mut number_of_rva_and_sizes)?;
+    let data_directories = read_data_directories(
+        reader,
+        le_u32(number_of_rva_and_sizes),
+        size.saturating_sub(SIZEOF_OPTIONAL_HEADER_32 as u64),
+    )?;
+
+    let optional_header_32_raw = OptionalHeader32Raw {
+        magic,
+        major_linker_version,
+        minor_linker_version,
+        size_of_code,
+        size_of_initialized_data,
+        size_of_uninitialized_data,
+        address_of_entry_point,
+        base_of_code,
+        base_of_data,
+        image_base,
+        section_alignment,
+        file_alignment,
+        major_os_version,
+        minor_os_version,
+        major_image_version,
+        minor_image_version,
+        major_subsystem_version,
+        minor_subsystem_version,
+        win32_version_value,
+        size_of_image,
+        size_of_headers,
+        checksum,
+        subsystem,
+        dll_characteristics,
+        size_of_stack_reserve,
+        size_of_stack_commit,
+        size_of_heap_reserve,
+        size_of_heap_commit,
+        loader_flags,
+        number_of_rva_and_sizes,
+        data_directories,
+    };
+
+    Ok(OptionalHeader32 {
+        offset,
+        optional_header_32_raw,
+    })
+}
 
+#[derive(Debug)]
 struct OptionalHeader32Raw {
     magic: [u8; 2],
     major_linker_version: [u8; 1],
@@ -65,6 +538,50 @@ struct OptionalHeader32Raw {
     data_directories: Vec<DataDirectoryRaw>,
 }
 
+impl OptionalHeader32Raw {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = [
+            &self.magic[..],
+            &self.major_linker_version,
+            &self.minor_linker_version,
+            &self.size_of_code,
+            &self.size_of_initialized_data,
+            &self.size_of_uninitialized_data,
+            &self.address_of_entry_point,
+            &self.base_of_code,
+            &self.base_of_data,
+            &self.image_base,
+            &self.section_alignment,
+            &self.file_alignment,
+            &self.major_os_version,
+            &self.minor_os_version,
+            &self.major_image_version,
+            &self.minor_image_version,
+            &self.major_subsystem_version,
+            &self.minor_subsystem_version,
+            &self.win32_version_value,
+            &self.size_of_image,
+            &self.size_of_headers,
+            &self.checksum,
+            &self.subsystem,
+            &self.dll_characteristics,
+            &self.size_of_stack_reserve,
+            &self.size_of_stack_commit,
+            &self.size_of_heap_reserve,
+            &self.size_of_heap_commit,
+            &self.loader_flags,
+            &self.number_of_rva_and_sizes,
+        ]
+        .concat();
+        for data_directory in &self.data_directories {
+            bytes.extend_from_slice(&data_directory.virtual_address);
+            bytes.extend_from_slice(&data_directory.size);
+        }
+        bytes
+    }
+}
+
+#[derive(Debug)]
 struct OptionalHeader32 {
     offset: u64,
     optional_header_32_raw: OptionalHeader32Raw,
@@ -72,133 +589,721 @@ struct OptionalHeader32 {
 
 impl OptionalHeader32 {
     fn magic(&self) -> u16 {
-        u16::from_le_bytes(self.optional_header_32_raw.magic)
+        le_u16(self.optional_header_32_raw.magic)
     }
 
     fn major_linker_version(&self) -> u8 {
-        u8::from_le_bytes(self.optional_header_32_raw.major_linker_version)
+        self.optional_header_32_raw.major_linker_version[0]
     }
 
     fn minor_linker_version(&self) -> u8 {
-        u8::from_le_bytes(self.optional_header_32_raw.minor_linker_version)
+        self.optional_header_32_raw.minor_linker_version[0]
     }
 
     fn size_of_code(&self) -> u32 {
-        u32::from_le_bytes(self.optional_header_32_raw.size_of_code)
+        le_u32(self.optional_header_32_raw.size_of_code)
     }
+
     fn size_of_initialized_data(&self) -> u32 {
-        u32::from_le_bytes(self.optional_header_32_raw.size_of_initialized_data)
+        le_u32(self.optional_header_32_raw.size_of_initialized_data)
     }
 
     fn size_of_uninitialized_data(&self) -> u32 {
-        u32::from_le_bytes(self.optional_header_32_raw.size_of_uninitialized_data)
+        le_u32(self.optional_header_32_raw.size_of_uninitialized_data)
     }
 
     fn address_of_entry_point(&self) -> u32 {
-        u32::from_le_bytes(self.optional_header_32_raw.address_of_entry_point)
+        le_u32(self.optional_header_32_raw.address_of_entry_point)
     }
 
     fn base_of_code(&self) -> u32 {
-        u32::from_le_bytes(self.optional_header_32_raw.base_of_code)
+        le_u32(self.optional_header_32_raw.base_of_code)
     }
 
     fn base_of_data(&self) -> u32 {
-        u32::from_le_bytes(self.optional_header_32_raw.base_of_data)
+        le_u32(self.optional_header_32_raw.base_of_data)
     }
 
     fn image_base(&self) -> u32 {
-        u32::from_le_bytes(self.optional_header_32_raw.image_base)
+        le_u32(self.optional_header_32_raw.image_base)
     }
 
     fn section_alignment(&self) -> u32 {
-        u32::from_le_bytes(self.optional_header_32_raw.section_alignment)
+        le_u32(self.optional_header_32_raw.section_alignment)
     }
 
     fn file_alignment(&self) -> u32 {
-        u32::from_le_bytes(self.optional_header_32_raw.file_alignment)
+        le_u32(self.optional_header_32_raw.file_alignment)
     }
 
     fn major_os_version(&self) -> u16 {
-        u16::from_le_bytes(self.optional_header_32_raw.major_os_version)
+        le_u16(self.optional_header_32_raw.major_os_version)
     }
 
     fn minor_os_version(&self) -> u16 {
-        u16::from_le_bytes(self.optional_header_32_raw.minor_os_version)
+        le_u16(self.optional_header_32_raw.minor_os_version)
     }
 
     fn major_image_version(&self) -> u16 {
-        u16::from_le_bytes(self.optional_header_32_raw.major_image_version)
+        le_u16(self.optional_header_32_raw.major_image_version)
     }
 
     fn minor_image_version(&self) -> u16 {
-        u16::from_le_bytes(self.optional_header_32_raw.minor_image_version)
+        le_u16(self.optional_header_32_raw.minor_image_version)
     }
 
     fn major_subsystem_version(&self) -> u16 {
-        u16::from_le_bytes(self.optional_header_32_raw.major_subsystem_version)
+        le_u16(self.optional_header_32_raw.major_subsystem_version)
     }
 
     fn minor_subsystem_version(&self) -> u16 {
-        u16::from_le_bytes(self.optional_header_32_raw.minor_subsystem_version)
+        le_u16(self.optional_header_32_raw.minor_subsystem_version)
     }
 
     fn win32_version_value(&self) -> u32 {
-        u32::from_le_bytes(self.optional_header_32_raw.win32_version_value)
+        le_u32(self.optional_header_32_raw.win32_version_value)
     }
 
     fn size_of_image(&self) -> u32 {
-        u32::from_le_bytes(self.optional_header_32_raw.size_of_image)
+        le_u32(self.optional_header_32_raw.size_of_image)
     }
 
     fn size_of_headers(&self) -> u32 {
-        u32::from_le_bytes(self.optional_header_32_raw.size_of_headers)
+        le_u32(self.optional_header_32_raw.size_of_headers)
     }
 
     fn checksum(&self) -> u32 {
-        u32::from_le_bytes(self.optional_header_32_raw.checksum)
+        le_u32(self.optional_header_32_raw.checksum)
     }
 
     fn subsystem(&self) -> u16 {
-        u16::from_le_bytes(self.optional_header_32_raw.subsystem)
+        le_u16(self.optional_header_32_raw.subsystem)
     }
 
     fn dll_characteristics(&self) -> u16 {
-        u16::from_le_bytes(self.optional_header_32_raw.dll_characteristics)
+        le_u16(self.optional_header_32_raw.dll_characteristics)
     }
 
     fn size_of_stack_reserve(&self) -> u32 {
-        u32::from_le_bytes(self.optional_header_32_raw.size_of_stack_reserve)
+        le_u32(self.optional_header_32_raw.size_of_stack_reserve)
     }
 
     fn size_of_stack_commit(&self) -> u32 {
-        u32::from_le_bytes(self.optional_header_32_raw.size_of_stack_commit)
+        le_u32(self.optional_header_32_raw.size_of_stack_commit)
     }
 
     fn size_of_heap_reserve(&self) -> u32 {
-        u32::from_le_bytes(self.optional_header_32_raw.size_of_heap_reserve)
+        le_u32(self.optional_header_32_raw.size_of_heap_reserve)
     }
 
     fn size_of_heap_commit(&self) -> u32 {
-        u32::from_le_bytes(self.optional_header_32_raw.size_of_heap_commit)
+        le_u32(self.optional_header_32_raw.size_of_heap_commit)
     }
 
     fn loader_flags(&self) -> u32 {
-        u32::from_le_bytes(self.optional_header_32_raw.loader_flags)
+        le_u32(self.optional_header_32_raw.loader_flags)
     }
 
     fn number_of_rva_and_sizes(&self) -> u32 {
-        u32::from_le_bytes(self.optional_header_32_raw.number_of_rva_and_sizes)
+        le_u32(self.optional_header_32_raw.number_of_rva_and_sizes)
     }
 
     fn data_directories(&self) -> Vec<DataDirectory> {
-        todo!()
+        let offset = self.offset + 96;
+        self.optional_header_32_raw
+            .data_directories
+            .iter()
+            .enumerate()
+            .map(|(index, data_directory_raw)| DataDirectory {
+                index,
+                offset: offset + index as u64 * 8,
+                data_directory_raw: data_directory_raw.clone(),
+            })
+            .collect()
     }
 }
 
-struct OptionalHeader32Wrapper {
+#[derive(Debug)]
+pub struct OptionalHeader32Wrapper {
     optional_header_32: OptionalHeader32,
 }
 
+impl OptionalHeader32Wrapper {
+    pub fn magic(&self) -> StructField<u16, 2> {
+        let offset = self.optional_header_32.offset;
+        let name = String::from("Magic");
+        let raw_bytes = self.optional_header_32.optional_header_32_raw.magic;
+        let value = self.optional_header_32.magic();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn major_linker_version(&self) -> StructField<u8, 1> {
+        let offset = self.optional_header_32.offset + 2;
+        let name = String::from("Major linker version");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .major_linker_version;
+        let value = self.optional_header_32.major_linker_version();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn minor_linker_version(&self) -> StructField<u8, 1> {
+        let offset = self.optional_header_32.offset + 3;
+        let name = String::from("Minor linker version");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .minor_linker_version;
+        let value = self.optional_header_32.minor_linker_version();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn size_of_code(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_32.offset + 4;
+        let name = String::from("Size of code");
+        let raw_bytes = self.optional_header_32.optional_header_32_raw.size_of_code;
+        let value = self.optional_header_32.size_of_code();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn size_of_initialized_data(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_32.offset + 8;
+        let name = String::from("Size of initialized data");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .size_of_initialized_data;
+        let value = self.optional_header_32.size_of_initialized_data();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn size_of_uninitialized_data(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_32.offset + 12;
+        let name = String::from("Size of uninitialized data");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .size_of_uninitialized_data;
+        let value = self.optional_header_32.size_of_uninitialized_data();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn address_of_entry_point(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_32.offset + 16;
+        let name = String::from("Address of entry point");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .address_of_entry_point;
+        let value = self.optional_header_32.address_of_entry_point();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn base_of_code(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_32.offset + 20;
+        let name = String::from("Base of code");
+        let raw_bytes = self.optional_header_32.optional_header_32_raw.base_of_code;
+        let value = self.optional_header_32.base_of_code();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn base_of_data(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_32.offset + 24;
+        let name = String::from("Base of data");
+        let raw_bytes = self.optional_header_32.optional_header_32_raw.base_of_data;
+        let value = self.optional_header_32.base_of_data();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn image_base(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_32.offset + 28;
+        let name = String::from("Image base");
+        let raw_bytes = self.optional_header_32.optional_header_32_raw.image_base;
+        let value = self.optional_header_32.image_base();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn section_alignment(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_32.offset + 32;
+        let name = String::from("Section alignment");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .section_alignment;
+        let value = self.optional_header_32.section_alignment();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn file_alignment(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_32.offset + 36;
+        let name = String::from("File alignment");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .file_alignment;
+        let value = self.optional_header_32.file_alignment();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn major_os_version(&self) -> StructField<u16, 2> {
+        let offset = self.optional_header_32.offset + 40;
+        let name = String::from("Major OS version");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .major_os_version;
+        let value = self.optional_header_32.major_os_version();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn minor_os_version(&self) -> StructField<u16, 2> {
+        let offset = self.optional_header_32.offset + 42;
+        let name = String::from("Minor OS version");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .minor_os_version;
+        let value = self.optional_header_32.minor_os_version();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn major_image_version(&self) -> StructField<u16, 2> {
+        let offset = self.optional_header_32.offset + 44;
+        let name = String::from("Major image version");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .major_image_version;
+        let value = self.optional_header_32.major_image_version();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn minor_image_version(&self) -> StructField<u16, 2> {
+        let offset = self.optional_header_32.offset + 46;
+        let name = String::from("Minor image version");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .minor_image_version;
+        let value = self.optional_header_32.minor_image_version();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn major_subsystem_version(&self) -> StructField<u16, 2> {
+        let offset = self.optional_header_32.offset + 48;
+        let name = String::from("Major subsystem version");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .major_subsystem_version;
+        let value = self.optional_header_32.major_subsystem_version();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn minor_subsystem_version(&self) -> StructField<u16, 2> {
+        let offset = self.optional_header_32.offset + 50;
+        let name = String::from("Minor subsystem version");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .minor_subsystem_version;
+        let value = self.optional_header_32.minor_subsystem_version();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn win32_version_value(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_32.offset + 52;
+        let name = String::from("Win32 version value");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .win32_version_value;
+        let value = self.optional_header_32.win32_version_value();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn size_of_image(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_32.offset + 56;
+        let name = String::from("Size of image");
+        let raw_bytes = self.optional_header_32.optional_header_32_raw.size_of_image;
+        let value = self.optional_header_32.size_of_image();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn size_of_headers(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_32.offset + 60;
+        let name = String::from("Size of headers");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .size_of_headers;
+        let value = self.optional_header_32.size_of_headers();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn checksum(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_32.offset + 64;
+        let name = String::from("Checksum");
+        let raw_bytes = self.optional_header_32.optional_header_32_raw.checksum;
+        let value = self.optional_header_32.checksum();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn subsystem(&self) -> StructField<u16, 2> {
+        let offset = self.optional_header_32.offset + 68;
+        let name = String::from("Subsystem");
+        let raw_bytes = self.optional_header_32.optional_header_32_raw.subsystem;
+        let value = self.optional_header_32.subsystem();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn dll_characteristics(&self) -> StructField<u16, 2> {
+        let offset = self.optional_header_32.offset + 70;
+        let name = String::from("DLL characteristics");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .dll_characteristics;
+        let value = self.optional_header_32.dll_characteristics();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn size_of_stack_reserve(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_32.offset + 72;
+        let name = String::from("Size of stack reserve");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .size_of_stack_reserve;
+        let value = self.optional_header_32.size_of_stack_reserve();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn size_of_stack_commit(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_32.offset + 76;
+        let name = String::from("Size of stack commit");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .size_of_stack_commit;
+        let value = self.optional_header_32.size_of_stack_commit();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn size_of_heap_reserve(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_32.offset + 80;
+        let name = String::from("Size of heap reserve");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .size_of_heap_reserve;
+        let value = self.optional_header_32.size_of_heap_reserve();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn size_of_heap_commit(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_32.offset + 84;
+        let name = String::from("Size of heap commit");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .size_of_heap_commit;
+        let value = self.optional_header_32.size_of_heap_commit();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn loader_flags(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_32.offset + 88;
+        let name = String::from("Loader flags");
+        let raw_bytes = self.optional_header_32.optional_header_32_raw.loader_flags;
+        let value = self.optional_header_32.loader_flags();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn number_of_rva_and_sizes(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_32.offset + 92;
+        let name = String::from("Num. of RVA and sizes");
+        let raw_bytes = self
+            .optional_header_32
+            .optional_header_32_raw
+            .number_of_rva_and_sizes;
+        let value = self.optional_header_32.number_of_rva_and_sizes();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn data_directories(&self) -> Vec<DataDirectoryWrapper> {
+        self.optional_header_32
+            .data_directories()
+            .into_iter()
+            .map(|data_directory| DataDirectoryWrapper { data_directory })
+            .collect()
+    }
+}
+
+fn read_optional_header_64<R: Read>(
+    reader: &mut R,
+    offset: u64,
+    size: u64,
+) -> io::Result<OptionalHeader64> {
+    let mut magic = [0u8; 2];
+    let mut major_linker_version = [0u8; 1];
+    let mut minor_linker_version = [0u8; 1];
+    let mut size_of_code = [0u8; 4];
+    let mut size_of_initialized_data = [0u8; 4];
+    let mut size_of_uninitialized_data = [0u8; 4];
+    let mut address_of_entry_point = [0u8; 4];
+    let mut base_of_code = [0u8; 4];
+    let mut image_base = [0u8; 8];
+    let mut section_alignment = [0u8; 4];
+    let mut file_alignment = [0u8; 4];
+    let mut major_os_version = [0u8; 2];
+    let mut minor_os_version = [0u8; 2];
+    let mut major_image_version = [0u8; 2];
+    let mut minor_image_version = [0u8; 2];
+    let mut major_subsystem_version = [0u8; 2];
+    let mut minor_subsystem_version = [0u8; 2];
+    let mut win32_version_value = [0u8; 4];
+    let mut size_of_image = [0u8; 4];
+    let mut size_of_headers = [0u8; 4];
+    let mut checksum = [0u8; 4];
+    let mut subsystem = [0u8; 2];
+    let mut dll_characteristics = [0u8; 2];
+    let mut size_of_stack_reserve = [0u8; 8];
+    let mut size_of_stack_commit = [0u8; 8];
+    let mut size_of_heap_reserve = [0u8; 8];
+    let mut size_of_heap_commit = [0u8; 8];
+    let mut loader_flags = [0u8; 4];
+    let mut number_of_rva_and_sizes = [0u8; 4];
+
+    let block = read_block(reader, SIZEOF_OPTIONAL_HEADER_64)?;
+    let mut fields = &block[..];
+
+    fields.read_exact(&mut magic)?;
+    fields.read_exact(&mut major_linker_version)?;
+    fields.read_exact(&mut minor_linker_version)?;
+    fields.read_exact(&mut size_of_code)?;
+    fields.read_exact(&mut size_of_initialized_data)?;
+    fields.read_exact(&mut size_of_uninitialized_data)?;
+    fields.read_exact(&mut address_of_entry_point)?;
+    fields.read_exact(&mut base_of_code)?;
+    fields.read_exact(&mut image_base)?;
+    fields.read_exact(&mut section_alignment)?;
+    fields.read_exact(&mut file_alignment)?;
+    fields.read_exact(&mut major_os_version)?;
+    fields.read_exact(&mut minor_os_version)?;
+    fields.read_exact(&mut major_image_version)?;
+    fields.read_exact(&mut minor_image_version)?;
+    fields.read_exact(&mut major_subsystem_version)?;
+    fields.read_exact(&mut minor_subsystem_version)?;
+    fields.read_exact(&mut win32_version_value)?;
+    fields.read_exact(&mut size_of_image)?;
+    fields.read_exact(&mut size_of_headers)?;
+    fields.read_exact(&mut checksum)?;
+    fields.read_exact(&mut subsystem)?;
+    fields.read_exact(&mut dll_characteristics)?;
+    fields.read_exact(&mut size_of_stack_reserve)?;
+    fields.read_exact(&mut size_of_stack_commit)?;
+    fields.read_exact(&mut size_of_heap_reserve)?;
+    fields.read_exact(&mut size_of_heap_commit)?;
+    fields.read_exact(&mut loader_flags)?;
+    fields.read_exact(&mut number_of_rva_and_sizes)?;
+    let data_directories = read_data_directories(
+        reader,
+        le_u32(number_of_rva_and_sizes),
+        size.saturating_sub(SIZEOF_OPTIONAL_HEADER_64 as u64),
+    )?;
+
+    let optional_header_64_raw = OptionalHeader64Raw {
+        magic,
+        major_linker_version,
+        minor_linker_version,
+        size_of_code,
+        size_of_initialized_data,
+        size_of_uninitialized_data,
+        address_of_entry_point,
+        base_of_code,
+        image_base,
+        section_alignment,
+        file_alignment,
+        major_os_version,
+        minor_os_version,
+        major_image_version,
+        minor_image_version,
+        major_subsystem_version,
+        minor_subsystem_version,
+        win32_version_value,
+        size_of_image,
+        size_of_headers,
+        checksum,
+        subsystem,
+        dll_characteristics,
+        size_of_stack_reserve,
+        size_of_stack_commit,
+        size_of_heap_reserve,
+        size_of_heap_commit,
+        loader_flags,
+        number_of_rva_and_sizes,
+        data_directories,
+    };
+
+    Ok(OptionalHeader64 {
+        offset,
+        optional_header_64_raw,
+    })
+}
+
+#[derive(Debug)]
 struct OptionalHeader64Raw {
     magic: [u8; 2],
     major_linker_version: [u8; 1],
@@ -232,6 +1337,49 @@ struct OptionalHeader64Raw {
     data_directories: Vec<DataDirectoryRaw>,
 }
 
+impl OptionalHeader64Raw {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = [
+            &self.magic[..],
+            &self.major_linker_version,
+            &self.minor_linker_version,
+            &self.size_of_code,
+            &self.size_of_initialized_data,
+            &self.size_of_uninitialized_data,
+            &self.address_of_entry_point,
+            &self.base_of_code,
+            &self.image_base,
+            &self.section_alignment,
+            &self.file_alignment,
+            &self.major_os_version,
+            &self.minor_os_version,
+            &self.major_image_version,
+            &self.minor_image_version,
+            &self.major_subsystem_version,
+            &self.minor_subsystem_version,
+            &self.win32_version_value,
+            &self.size_of_image,
+            &self.size_of_headers,
+            &self.checksum,
+            &self.subsystem,
+            &self.dll_characteristics,
+            &self.size_of_stack_reserve,
+            &self.size_of_stack_commit,
+            &self.size_of_heap_reserve,
+            &self.size_of_heap_commit,
+            &self.loader_flags,
+            &self.number_of_rva_and_sizes,
+        ]
+        .concat();
+        for data_directory in &self.data_directories {
+            bytes.extend_from_slice(&data_directory.virtual_address);
+            bytes.extend_from_slice(&data_directory.size);
+        }
+        bytes
+    }
+}
+
+#[derive(Debug)]
 struct OptionalHeader64 {
     offset: u64,
     optional_header_64_raw: OptionalHeader64Raw,
@@ -239,151 +1387,803 @@ struct OptionalHeader64 {
 
 impl OptionalHeader64 {
     fn magic(&self) -> u16 {
-        todo!()
+        le_u16(self.optional_header_64_raw.magic)
     }
 
     fn major_linker_version(&self) -> u8 {
-        todo!()
+        self.optional_header_64_raw.major_linker_version[0]
     }
 
     fn minor_linker_version(&self) -> u8 {
-        todo!()
+        self.optional_header_64_raw.minor_linker_version[0]
     }
 
     fn size_of_code(&self) -> u32 {
-        todo!()
+        le_u32(self.optional_header_64_raw.size_of_code)
     }
+
     fn size_of_initialized_data(&self) -> u32 {
-        todo!()
+        le_u32(self.optional_header_64_raw.size_of_initialized_data)
     }
 
     fn size_of_uninitialized_data(&self) -> u32 {
-        todo!()
+        le_u32(self.optional_header_64_raw.size_of_uninitialized_data)
     }
 
     fn address_of_entry_point(&self) -> u32 {
-        todo!()
+        le_u32(self.optional_header_64_raw.address_of_entry_point)
     }
 
     fn base_of_code(&self) -> u32 {
-        todo!()
+        le_u32(self.optional_header_64_raw.base_of_code)
     }
 
     fn image_base(&self) -> u64 {
-        todo!()
+        le_u64(self.optional_header_64_raw.image_base)
     }
 
     fn section_alignment(&self) -> u32 {
-        todo!()
+        le_u32(self.optional_header_64_raw.section_alignment)
     }
 
     fn file_alignment(&self) -> u32 {
-        todo!()
+        le_u32(self.optional_header_64_raw.file_alignment)
     }
 
     fn major_os_version(&self) -> u16 {
-        todo!()
+        le_u16(self.optional_header_64_raw.major_os_version)
     }
 
     fn minor_os_version(&self) -> u16 {
-        todo!()
+        le_u16(self.optional_header_64_raw.minor_os_version)
     }
 
     fn major_image_version(&self) -> u16 {
-        todo!()
+        le_u16(self.optional_header_64_raw.major_image_version)
     }
 
     fn minor_image_version(&self) -> u16 {
-        todo!()
+        le_u16(self.optional_header_64_raw.minor_image_version)
     }
 
     fn major_subsystem_version(&self) -> u16 {
-        todo!()
+        le_u16(self.optional_header_64_raw.major_subsystem_version)
     }
 
     fn minor_subsystem_version(&self) -> u16 {
-        todo!()
+        le_u16(self.optional_header_64_raw.minor_subsystem_version)
     }
 
     fn win32_version_value(&self) -> u32 {
-        todo!()
+        le_u32(self.optional_header_64_raw.win32_version_value)
     }
 
     fn size_of_image(&self) -> u32 {
-        todo!()
+        le_u32(self.optional_header_64_raw.size_of_image)
     }
 
     fn size_of_headers(&self) -> u32 {
-        todo!()
+        le_u32(self.optional_header_64_raw.size_of_headers)
     }
 
     fn checksum(&self) -> u32 {
-        todo!()
+        le_u32(self.optional_header_64_raw.checksum)
     }
 
     fn subsystem(&self) -> u16 {
-        todo!()
+        le_u16(self.optional_header_64_raw.subsystem)
     }
 
     fn dll_characteristics(&self) -> u16 {
-        todo!()
+        le_u16(self.optional_header_64_raw.dll_characteristics)
     }
 
     fn size_of_stack_reserve(&self) -> u64 {
-        todo!()
+        le_u64(self.optional_header_64_raw.size_of_stack_reserve)
     }
 
     fn size_of_stack_commit(&self) -> u64 {
-        todo!()
+        le_u64(self.optional_header_64_raw.size_of_stack_commit)
     }
 
     fn size_of_heap_reserve(&self) -> u64 {
-        todo!()
+        le_u64(self.optional_header_64_raw.size_of_heap_reserve)
     }
 
     fn size_of_heap_commit(&self) -> u64 {
-        todo!()
+        le_u64(self.optional_header_64_raw.size_of_heap_commit)
     }
 
     fn loader_flags(&self) -> u32 {
-        todo!()
+        le_u32(self.optional_header_64_raw.loader_flags)
     }
 
     fn number_of_rva_and_sizes(&self) -> u32 {
-        todo!()
+        le_u32(self.optional_header_64_raw.number_of_rva_and_sizes)
     }
 
     fn data_directories(&self) -> Vec<DataDirectory> {
-        todo!()
+        let offset = self.offset + 112;
+        self.optional_header_64_raw
+            .data_directories
+            .iter()
+            .enumerate()
+            .map(|(index, data_directory_raw)| DataDirectory {
+                index,
+                offset: offset + index as u64 * 8,
+                data_directory_raw: data_directory_raw.clone(),
+            })
+            .collect()
     }
 }
 
-struct OptionalHeader64Wrapper {
+#[derive(Debug)]
+pub struct OptionalHeader64Wrapper {
     optional_header_64: OptionalHeader64,
 }
 
+impl OptionalHeader64Wrapper {
+    pub fn magic(&self) -> StructField<u16, 2> {
+        let offset = self.optional_header_64.offset;
+        let name = String::from("Magic");
+        let raw_bytes = self.optional_header_64.optional_header_64_raw.magic;
+        let value = self.optional_header_64.magic();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn major_linker_version(&self) -> StructField<u8, 1> {
+        let offset = self.optional_header_64.offset + 2;
+        let name = String::from("Major linker version");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .major_linker_version;
+        let value = self.optional_header_64.major_linker_version();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn minor_linker_version(&self) -> StructField<u8, 1> {
+        let offset = self.optional_header_64.offset + 3;
+        let name = String::from("Minor linker version");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .minor_linker_version;
+        let value = self.optional_header_64.minor_linker_version();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn size_of_code(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_64.offset + 4;
+        let name = String::from("Size of code");
+        let raw_bytes = self.optional_header_64.optional_header_64_raw.size_of_code;
+        let value = self.optional_header_64.size_of_code();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn size_of_initialized_data(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_64.offset + 8;
+        let name = String::from("Size of initialized data");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .size_of_initialized_data;
+        let value = self.optional_header_64.size_of_initialized_data();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn size_of_uninitialized_data(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_64.offset + 12;
+        let name = String::from("Size of uninitialized data");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .size_of_uninitialized_data;
+        let value = self.optional_header_64.size_of_uninitialized_data();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn address_of_entry_point(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_64.offset + 16;
+        let name = String::from("Address of entry point");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .address_of_entry_point;
+        let value = self.optional_header_64.address_of_entry_point();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn base_of_code(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_64.offset + 20;
+        let name = String::from("Base of code");
+        let raw_bytes = self.optional_header_64.optional_header_64_raw.base_of_code;
+        let value = self.optional_header_64.base_of_code();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn image_base(&self) -> StructField<u64, 8> {
+        let offset = self.optional_header_64.offset + 24;
+        let name = String::from("Image base");
+        let raw_bytes = self.optional_header_64.optional_header_64_raw.image_base;
+        let value = self.optional_header_64.image_base();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn section_alignment(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_64.offset + 32;
+        let name = String::from("Section alignment");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .section_alignment;
+        let value = self.optional_header_64.section_alignment();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn file_alignment(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_64.offset + 36;
+        let name = String::from("File alignment");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .file_alignment;
+        let value = self.optional_header_64.file_alignment();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn major_os_version(&self) -> StructField<u16, 2> {
+        let offset = self.optional_header_64.offset + 40;
+        let name = String::from("Major OS version");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .major_os_version;
+        let value = self.optional_header_64.major_os_version();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn minor_os_version(&self) -> StructField<u16, 2> {
+        let offset = self.optional_header_64.offset + 42;
+        let name = String::from("Minor OS version");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .minor_os_version;
+        let value = self.optional_header_64.minor_os_version();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn major_image_version(&self) -> StructField<u16, 2> {
+        let offset = self.optional_header_64.offset + 44;
+        let name = String::from("Major image version");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .major_image_version;
+        let value = self.optional_header_64.major_image_version();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn minor_image_version(&self) -> StructField<u16, 2> {
+        let offset = self.optional_header_64.offset + 46;
+        let name = String::from("Minor image version");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .minor_image_version;
+        let value = self.optional_header_64.minor_image_version();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn major_subsystem_version(&self) -> StructField<u16, 2> {
+        let offset = self.optional_header_64.offset + 48;
+        let name = String::from("Major subsystem version");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .major_subsystem_version;
+        let value = self.optional_header_64.major_subsystem_version();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn minor_subsystem_version(&self) -> StructField<u16, 2> {
+        let offset = self.optional_header_64.offset + 50;
+        let name = String::from("Minor subsystem version");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .minor_subsystem_version;
+        let value = self.optional_header_64.minor_subsystem_version();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn win32_version_value(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_64.offset + 52;
+        let name = String::from("Win32 version value");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .win32_version_value;
+        let value = self.optional_header_64.win32_version_value();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn size_of_image(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_64.offset + 56;
+        let name = String::from("Size of image");
+        let raw_bytes = self.optional_header_64.optional_header_64_raw.size_of_image;
+        let value = self.optional_header_64.size_of_image();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn size_of_headers(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_64.offset + 60;
+        let name = String::from("Size of headers");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .size_of_headers;
+        let value = self.optional_header_64.size_of_headers();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn checksum(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_64.offset + 64;
+        let name = String::from("Checksum");
+        let raw_bytes = self.optional_header_64.optional_header_64_raw.checksum;
+        let value = self.optional_header_64.checksum();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn subsystem(&self) -> StructField<u16, 2> {
+        let offset = self.optional_header_64.offset + 68;
+        let name = String::from("Subsystem");
+        let raw_bytes = self.optional_header_64.optional_header_64_raw.subsystem;
+        let value = self.optional_header_64.subsystem();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn dll_characteristics(&self) -> StructField<u16, 2> {
+        let offset = self.optional_header_64.offset + 70;
+        let name = String::from("DLL characteristics");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .dll_characteristics;
+        let value = self.optional_header_64.dll_characteristics();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn size_of_stack_reserve(&self) -> StructField<u64, 8> {
+        let offset = self.optional_header_64.offset + 72;
+        let name = String::from("Size of stack reserve");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .size_of_stack_reserve;
+        let value = self.optional_header_64.size_of_stack_reserve();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn size_of_stack_commit(&self) -> StructField<u64, 8> {
+        let offset = self.optional_header_64.offset + 80;
+        let name = String::from("Size of stack commit");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .size_of_stack_commit;
+        let value = self.optional_header_64.size_of_stack_commit();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn size_of_heap_reserve(&self) -> StructField<u64, 8> {
+        let offset = self.optional_header_64.offset + 88;
+        let name = String::from("Size of heap reserve");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .size_of_heap_reserve;
+        let value = self.optional_header_64.size_of_heap_reserve();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn size_of_heap_commit(&self) -> StructField<u64, 8> {
+        let offset = self.optional_header_64.offset + 96;
+        let name = String::from("Size of heap commit");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .size_of_heap_commit;
+        let value = self.optional_header_64.size_of_heap_commit();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn loader_flags(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_64.offset + 104;
+        let name = String::from("Loader flags");
+        let raw_bytes = self.optional_header_64.optional_header_64_raw.loader_flags;
+        let value = self.optional_header_64.loader_flags();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn number_of_rva_and_sizes(&self) -> StructField<u32, 4> {
+        let offset = self.optional_header_64.offset + 108;
+        let name = String::from("Num. of RVA and sizes");
+        let raw_bytes = self
+            .optional_header_64
+            .optional_header_64_raw
+            .number_of_rva_and_sizes;
+        let value = self.optional_header_64.number_of_rva_and_sizes();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn data_directories(&self) -> Vec<DataDirectoryWrapper> {
+        self.optional_header_64
+            .data_directories()
+            .into_iter()
+            .map(|data_directory| DataDirectoryWrapper { data_directory })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
 struct DataDirectoryRaw {
     virtual_address: [u8; 4],
     size: [u8; 4],
 }
 
+#[derive(Debug)]
 struct DataDirectory {
+    index: usize,
     offset: u64,
     data_directory_raw: DataDirectoryRaw,
 }
 
-struct DataDirectoryWrapper {
+impl DataDirectory {
+    fn virtual_address(&self) -> u32 {
+        le_u32(self.data_directory_raw.virtual_address)
+    }
+
+    fn size(&self) -> u32 {
+        le_u32(self.data_directory_raw.size)
+    }
+}
+
+#[derive(Debug)]
+pub struct DataDirectoryWrapper {
     data_directory: DataDirectory,
 }
 
 impl DataDirectoryWrapper {
+    /// Position of the entry in the table; indices from
+    /// [`IMAGE_NUMBEROF_DIRECTORY_ENTRIES`] on are extra entries the loader ignores
+    pub fn index(&self) -> usize {
+        self.data_directory.index
+    }
+
+    /// Kind of the directory, given by its position in the table
+    pub fn directory_type(&self) -> DataDirectoryType {
+        DataDirectoryType::from(self.data_directory.index)
+    }
+
     pub fn virtual_address(&self) -> StructField<u32, 4> {
-        todo!()
+        let offset = self.data_directory.offset;
+        let name = String::from("Virtual address");
+        let raw_bytes = self.data_directory.data_directory_raw.virtual_address;
+        let value = self.data_directory.virtual_address();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
     }
 
     pub fn size(&self) -> StructField<u32, 4> {
-        todo!()
+        let offset = self.data_directory.offset + 4;
+        let name = String::from("Size");
+        let raw_bytes = self.data_directory.data_directory_raw.size;
+        let value = self.data_directory.size();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DataDirectoryType {
+    Export,
+    Import,
+    Resource,
+    Exception,
+    Security,
+    BaseRelocation,
+    Debug,
+    /// Reserved and zero; Alpha AXP linkers kept instruction fixups here
+    Architecture,
+    /// RVA of the value loaded into the global pointer register (`gp` on IA64), with a
+    /// `Size` of zero
+    GlobalPtr,
+    TLS,
+    LoadConfig,
+    BoundImport,
+    IAT,
+    DelayImport,
+    ComDescriptor,
+    Reserved,
+}
+
+impl From<usize> for DataDirectoryType {
+    fn from(index: usize) -> Self {
+        match index {
+            IMAGE_DIRECTORY_ENTRY_EXPORT => Self::Export,
+            IMAGE_DIRECTORY_ENTRY_IMPORT => Self::Import,
+            IMAGE_DIRECTORY_ENTRY_RESOURCE => Self::Resource,
+            IMAGE_DIRECTORY_ENTRY_EXCEPTION => Self::Exception,
+            IMAGE_DIRECTORY_ENTRY_SECURITY => Self::Security,
+            IMAGE_DIRECTORY_ENTRY_BASERELOC => Self::BaseRelocation,
+            IMAGE_DIRECTORY_ENTRY_DEBUG => Self::Debug,
+            IMAGE_DIRECTORY_ENTRY_ARCHITECTURE => Self::Architecture,
+            IMAGE_DIRECTORY_ENTRY_GLOBALPTR => Self::GlobalPtr,
+            IMAGE_DIRECTORY_ENTRY_TLS => Self::TLS,
+            IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG => Self::LoadConfig,
+            IMAGE_DIRECTORY_ENTRY_BOUND_IMPORT => Self::BoundImport,
+            IMAGE_DIRECTORY_ENTRY_IAT => Self::IAT,
+            IMAGE_DIRECTORY_ENTRY_DELAY_IMPORT => Self::DelayImport,
+            IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR => Self::ComDescriptor,
+            _ => Self::Reserved,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DllCharacteristics {
+    high_entropy_va: bool,
+    dynamic_base: bool,
+    force_integrity: bool,
+    nx_compat: bool,
+    no_isolation: bool,
+    no_seh: bool,
+    no_bind: bool,
+    appcontainer: bool,
+    wdm_driver: bool,
+    guard_cf: bool,
+    terminal_server_aware: bool,
+}
+
+impl From<u16> for DllCharacteristics {
+    fn from(value: u16) -> Self {
+        Self {
+            high_entropy_va: value & IMAGE_DLLCHARACTERISTICS_HIGH_ENTROPY_VA != 0,
+            dynamic_base: value & IMAGE_DLLCHARACTERISTICS_DYNAMIC_BASE != 0,
+            force_integrity: value & IMAGE_DLLCHARACTERISTICS_FORCE_INTEGRITY != 0,
+            nx_compat: value & IMAGE_DLLCHARACTERISTICS_NX_COMPAT != 0,
+            no_isolation: value & IMAGE_DLLCHARACTERISTICS_NO_ISOLATION != 0,
+            no_seh: value & IMAGE_DLLCHARACTERISTICS_NO_SEH != 0,
+            no_bind: value & IMAGE_DLLCHARACTERISTICS_NO_BIND != 0,
+            appcontainer: value & IMAGE_DLLCHARACTERISTICS_APPCONTAINER != 0,
+            wdm_driver: value & IMAGE_DLLCHARACTERISTICS_WDM_DRIVER != 0,
+            guard_cf: value & IMAGE_DLLCHARACTERISTICS_GUARD_CF != 0,
+            terminal_server_aware: value & IMAGE_DLLCHARACTERISTICS_TERMINAL_SERVER_AWARE != 0,
+        }
+    }
+}
+
+impl DllCharacteristics {
+    /// Image can handle a high entropy 64-bit virtual address space
+    pub fn high_entropy_va(&self) -> bool {
+        self.high_entropy_va
+    }
+
+    /// DLL can be relocated at load time
+    pub fn dynamic_base(&self) -> bool {
+        self.dynamic_base
+    }
+
+    /// Code Integrity checks are enforced
+    pub fn force_integrity(&self) -> bool {
+        self.force_integrity
+    }
+
+    /// Image is NX compatible
+    pub fn nx_compat(&self) -> bool {
+        self.nx_compat
+    }
+
+    /// Isolation aware, but do not isolate the image
+    pub fn no_isolation(&self) -> bool {
+        self.no_isolation
+    }
+
+    /// Does not use structured exception handling
+    pub fn no_seh(&self) -> bool {
+        self.no_seh
+    }
+
+    /// Do not bind the image
+    pub fn no_bind(&self) -> bool {
+        self.no_bind
+    }
+
+    /// Image must execute in an AppContainer
+    pub fn appcontainer(&self) -> bool {
+        self.appcontainer
+    }
+
+    /// A WDM driver
+    pub fn wdm_driver(&self) -> bool {
+        self.wdm_driver
+    }
+
+    /// Image supports Control Flow Guard
+    pub fn guard_cf(&self) -> bool {
+        self.guard_cf
+    }
+
+    /// Terminal Server aware
+    pub fn terminal_server_aware(&self) -> bool {
+        self.terminal_server_aware
     }
 }
 
-struct DllCharacteristics {}
+pub const IMAGE_DLLCHARACTERISTICS_HIGH_ENTROPY_VA: u16 = 0x0020;
+pub const IMAGE_DLLCHARACTERISTICS_DYNAMIC_BASE: u16 = 0x0040;
+pub const IMAGE_DLLCHARACTERISTICS_FORCE_INTEGRITY: u16 = 0x0080;
+pub const IMAGE_DLLCHARACTERISTICS_NX_COMPAT: u16 = 0x0100;
+pub const IMAGE_DLLCHARACTERISTICS_NO_ISOLATION: u16 = 0x0200;
+pub const IMAGE_DLLCHARACTERISTICS_NO_SEH: u16 = 0x0400;
+pub const IMAGE_DLLCHARACTERISTICS_NO_BIND: u16 = 0x0800;
+pub const IMAGE_DLLCHARACTERISTICS_APPCONTAINER: u16 = 0x1000;
+pub const IMAGE_DLLCHARACTERISTICS_WDM_DRIVER: u16 = 0x2000;
+pub const IMAGE_DLLCHARACTERISTICS_GUARD_CF: u16 = 0x4000;
+pub const IMAGE_DLLCHARACTERISTICS_TERMINAL_SERVER_AWARE: u16 = 0x8000;
@@ -0,0 +1,180 @@
+use crate::def::ModuleDefinition;
+use crate::export::Export;
+use crate::import::Import;
+use crate::module_key;
+use crate::port_exe::PortExe;
+use std::collections::HashMap;
+use std::io;
+
+/// Module-definition files shipped with the crate for DLLs commonly imported by ordinal
+const BUNDLED: [&str; 3] = [
+    include_str!("ws2_32.def"),
+    include_str!("wsock32.def"),
+    include_str!("oleaut32.def"),
+];
+
+/// System DLLs that regular toolchains import by name only. An image importing one of
+/// them wholly by ordinal hides which APIs it uses, a common obfuscation trick.
+pub const NAME_IMPORTED_SYSTEM_DLLS: [&str; 14] = [
+    "advapi32",
+    "crypt32",
+    "gdi32",
+    "kernel32",
+    "kernelbase",
+    "msvcrt",
+    "ntdll",
+    "ole32",
+    "shell32",
+    "urlmon",
+    "user32",
+    "wininet",
+    "winhttp",
+    "ws2help",
+];
+
+/// How the functions imported from one DLL are referenced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrdinalCoverage {
+    dll: String,
+    by_name: usize,
+    by_ordinal: usize,
+}
+
+impl OrdinalCoverage {
+    /// DLL name as the first import descriptor for it spells it
+    pub fn dll(&self) -> &str {
+        &self.dll
+    }
+
+    pub fn by_name(&self) -> usize {
+        self.by_name
+    }
+
+    pub fn by_ordinal(&self) -> usize {
+        self.by_ordinal
+    }
+
+    /// Share of the imports that are by ordinal, from 0.0 to 1.0
+    pub fn ordinal_fraction(&self) -> f64 {
+        match self.by_name + self.by_ordinal {
+            0 => 0.0,
+            total => self.by_ordinal as f64 / total as f64,
+        }
+    }
+
+    /// Whether every import from the DLL is by ordinal
+    pub fn is_fully_ordinal(&self) -> bool {
+        self.by_ordinal != 0 && self.by_name == 0
+    }
+
+    /// Whether the DLL is one of [`NAME_IMPORTED_SYSTEM_DLLS`] and imported fully by
+    /// ordinal, an obfuscation indicator
+    pub fn is_suspicious(&self) -> bool {
+        self.is_fully_ordinal() && NAME_IMPORTED_SYSTEM_DLLS.contains(&&module_key(&self.dll)[..])
+    }
+}
+
+/// Export names by ordinal, per DLL, for naming imports by ordinal
+#[derive(Debug, Clone, Default)]
+pub struct OrdinalMap {
+    modules: HashMap<String, HashMap<u16, String>>,
+}
+
+impl OrdinalMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map preloaded with the ordinals of `ws2_32.dll`, `wsock32.dll` and `oleaut32.dll`
+    pub fn bundled() -> Self {
+        let mut map = Self::new();
+        for text in BUNDLED {
+            map.add_definition_text(text)
+                .expect("bundled module-definition files are valid");
+        }
+        map
+    }
+
+    /// Adds the ordinals of a module-definition file, named by its `LIBRARY` statement.
+    ///
+    /// Entries override those already known for the same DLL and ordinal.
+    pub fn add_definition(&mut self, definition: &ModuleDefinition) -> io::Result<()> {
+        let library = definition.library().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "module-definition file has no LIBRARY statement",
+            )
+        })?;
+        let names = self.modules.entry(module_key(library)).or_default();
+        for export in definition.exports() {
+            if let Some(ordinal) = export.ordinal() {
+                names.insert(ordinal, export.name().to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses `text` as a module-definition file and adds its ordinals.
+    pub fn add_definition_text(&mut self, text: &str) -> io::Result<()> {
+        self.add_definition(&ModuleDefinition::parse(text)?)
+    }
+
+    /// Adds the named exports of `dll`, as read from the DLL itself.
+    pub fn add_exports(&mut self, dll: &str, exports: &[Export]) {
+        let names = self.modules.entry(module_key(dll)).or_default();
+        for export in exports {
+            if let (Some(name), Ok(ordinal)) = (export.name(), u16::try_from(export.ordinal())) {
+                names.insert(ordinal, name.to_string());
+            }
+        }
+    }
+
+    /// Name of the export of `dll` with `ordinal`, ignoring case and a `.dll` extension
+    pub fn name(&self, dll: &str, ordinal: u16) -> Option<&str> {
+        self.modules
+            .get(&module_key(dll))?
+            .get(&ordinal)
+            .map(String::as_str)
+    }
+
+    /// Name of `import` from `dll`, falling back to `#ordinal` when the map does not know it
+    pub fn import_name(&self, dll: &str, import: &Import) -> String {
+        match (import.name(), import.ordinal()) {
+            (Some(name), _) => name.to_string(),
+            (None, Some(ordinal)) => self
+                .name(dll, ordinal)
+                .map_or_else(|| format!("#{}", ordinal), str::to_string),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+impl PortExe {
+    /// Counts the imports by name and by ordinal of every imported DLL, delay-loaded or
+    /// not, in import directory order. Descriptors for the same DLL are counted together.
+    pub fn ordinal_coverage(&self) -> io::Result<Vec<OrdinalCoverage>> {
+        let mut coverage: Vec<OrdinalCoverage> = Vec::new();
+        for descriptor in self.all_imports()? {
+            let key = module_key(descriptor.dll_name());
+            let index = match coverage.iter().position(|dll| module_key(&dll.dll) == key) {
+                Some(index) => index,
+                None => {
+                    coverage.push(OrdinalCoverage {
+                        dll: descriptor.dll_name().to_string(),
+                        by_name: 0,
+                        by_ordinal: 0,
+                    });
+                    coverage.len() - 1
+                }
+            };
+            for import in descriptor.imports() {
+                if import.ordinal().is_some() {
+                    coverage[index].by_ordinal += 1;
+                } else {
+                    coverage[index].by_name += 1;
+                }
+            }
+        }
+        Ok(coverage)
+    }
+}
@@ -0,0 +1,156 @@
+use crate::port_exe::PortExe;
+use crate::provenance::{AUTOIT_MAGIC, AUTOIT_SIGNATURE, NSIS_SIGNATURE};
+use std::fmt;
+
+const ZIP_SIGNATURE: &[u8] = b"PK\x03\x04";
+const SEVEN_ZIP_SIGNATURE: &[u8] = b"7z\xBC\xAF\x27\x1C";
+/// `MSCF` followed by the reserved field every cabinet leaves zero
+const CAB_SIGNATURE: &[u8] = b"MSCF\0\0\0\0";
+/// Common prefix of the RAR 1.5 to 4.x and RAR 5 signatures
+const RAR_SIGNATURE: &[u8] = b"Rar!\x1A\x07";
+
+/// Recognizer for one format of data appended to an image
+pub trait OverlaySniffer {
+    /// Short name of the format, such as `zip`
+    fn name(&self) -> &str;
+
+    /// Offset within `overlay` where the format starts, `None` if it is absent.
+    fn sniff(&self, overlay: &[u8]) -> Option<usize>;
+}
+
+/// Sniffer recognizing a format by the first occurrence of a signature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MagicSniffer {
+    name: String,
+    magic: Vec<u8>,
+    offset: usize,
+}
+
+impl MagicSniffer {
+    pub fn new(name: &str, magic: &[u8]) -> Self {
+        Self {
+            name: name.to_string(),
+            magic: magic.to_vec(),
+            offset: 0,
+        }
+    }
+
+    /// Places the signature `offset` bytes into the format rather than at its start.
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+}
+
+impl OverlaySniffer for MagicSniffer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn sniff(&self, overlay: &[u8]) -> Option<usize> {
+        if self.magic.is_empty() {
+            return None;
+        }
+        overlay
+            .windows(self.magic.len())
+            .enumerate()
+            .skip(self.offset)
+            .find(|(_, window)| *window == &self.magic[..])
+            .map(|(position, _)| position - self.offset)
+    }
+}
+
+/// Sniffers run by [`PortExe::sniff_overlay`].
+///
+/// The default set recognizes zip, 7z, cabinet and RAR archives, NSIS installer data
+/// and AutoIt scripts; [`OverlaySniffers::register`] adds more.
+pub struct OverlaySniffers {
+    sniffers: Vec<Box<dyn OverlaySniffer>>,
+}
+
+impl OverlaySniffers {
+    /// Set without any sniffer, for callers that only want their own formats
+    pub fn empty() -> Self {
+        Self {
+            sniffers: Vec::new(),
+        }
+    }
+
+    /// Adds `sniffer` after those already registered.
+    pub fn register<S: OverlaySniffer + 'static>(&mut self, sniffer: S) {
+        self.sniffers.push(Box::new(sniffer));
+    }
+
+    /// Names of the registered sniffers, in registration order
+    pub fn names(&self) -> Vec<&str> {
+        self.sniffers.iter().map(|sniffer| sniffer.name()).collect()
+    }
+}
+
+impl Default for OverlaySniffers {
+    fn default() -> Self {
+        let mut autoit = AUTOIT_MAGIC.to_vec();
+        autoit.extend_from_slice(AUTOIT_SIGNATURE);
+        let mut sniffers = Self::empty();
+        sniffers.register(MagicSniffer::new("zip", ZIP_SIGNATURE));
+        sniffers.register(MagicSniffer::new("7z", SEVEN_ZIP_SIGNATURE));
+        sniffers.register(MagicSniffer::new("cab", CAB_SIGNATURE));
+        sniffers.register(MagicSniffer::new("rar", RAR_SIGNATURE));
+        sniffers.register(MagicSniffer::new("nsis", NSIS_SIGNATURE).with_offset(4));
+        sniffers.register(MagicSniffer::new("au3", &autoit));
+        sniffers
+    }
+}
+
+impl fmt::Debug for OverlaySniffers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+/// Format a sniffer recognized in the overlay
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverlayMatch {
+    format: String,
+    offset: usize,
+}
+
+impl OverlayMatch {
+    /// Name of the sniffer that recognized the format
+    pub fn format(&self) -> &str {
+        &self.format
+    }
+
+    /// File offset where the format starts
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl PortExe {
+    /// Data appended after the image, starting at [`PortExe::overlay_offset`]
+    pub fn overlay(&self) -> &[u8] {
+        &self.data()[self.overlay_offset()..]
+    }
+
+    /// Runs `sniffers` over the overlay and returns the formats they recognized, in
+    /// file order. The attribute certificate table is part of the overlay and is
+    /// sniffed along with the rest.
+    pub fn sniff_overlay(&self, sniffers: &OverlaySniffers) -> Vec<OverlayMatch> {
+        let start = self.overlay_offset();
+        let overlay = self.overlay();
+        let mut matches: Vec<_> = sniffers
+            .sniffers
+            .iter()
+            .filter_map(|sniffer| {
+                let offset = sniffer.sniff(overlay)?;
+                Some(OverlayMatch {
+                    format: sniffer.name().to_string(),
+                    offset: start + offset,
+                })
+            })
+            .collect();
+        matches.sort_by_key(|found| found.offset);
+        matches
+    }
+}
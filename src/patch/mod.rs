@@ -0,0 +1,590 @@
+use crate::digest::md5;
+use crate::pe_string::PeString;
+use crate::port_exe::PortExe;
+use std::collections::HashMap;
+use std::io;
+use std::ops::Range;
+
+/// First bytes of an encoded [`Patch`]
+const MAGIC: &[u8; 8] = b"PEXPATCH";
+const VERSION: u8 = 1;
+
+/// Length of the windows hashed to find matches, and the shortest match used
+const MIN_MATCH: usize = 8;
+/// Distance between the old positions indexed; matches at other positions are found
+/// by extending backwards
+const INDEX_STRIDE: usize = 4;
+/// Old positions remembered per window
+const MAX_CANDIDATES: usize = 8;
+/// Longest mismatch skipped while following the previous match, such as a relocated
+/// pointer inside code that otherwise moved as a whole
+const MAX_MISMATCH: usize = 8;
+
+const SOURCE_HEADERS: u8 = 0;
+const SOURCE_SECTION: u8 = 1;
+const SOURCE_OVERLAY: u8 = 2;
+const SOURCE_FILE: u8 = 3;
+
+const OP_COPY: u8 = 0;
+const OP_INSERT: u8 = 1;
+const OP_ZEROS: u8 = 2;
+
+/// Part of the old file a [`RegionPatch`] copies from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PatchSource {
+    /// Everything before the raw data of the first section
+    Headers,
+    /// Raw data of the section at this index of the old section table
+    Section(usize),
+    /// Everything after the raw data of the last section
+    Overlay,
+    /// The whole file, for regions with no counterpart
+    File,
+}
+
+/// Step of a [`RegionPatch`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchOp {
+    /// Bytes copied from the source, at an offset into it
+    Copy { offset: usize, length: usize },
+    /// Bytes the source does not have
+    Insert(Vec<u8>),
+    /// Zero bytes, such as the padding of a section that grew
+    Zeros(usize),
+}
+
+/// Instructions rebuilding one region of the new file from a part of the old one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionPatch {
+    source: PatchSource,
+    length: usize,
+    ops: Vec<PatchOp>,
+}
+
+impl RegionPatch {
+    pub fn source(&self) -> PatchSource {
+        self.source
+    }
+
+    /// Size of the region in the new file
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    pub fn ops(&self) -> &[PatchOp] {
+        &self.ops
+    }
+
+    /// Number of bytes the region takes from the patch rather than the old file
+    pub fn inserted(&self) -> usize {
+        self.ops
+            .iter()
+            .map(|op| match op {
+                PatchOp::Insert(bytes) => bytes.len(),
+                PatchOp::Copy { .. } | PatchOp::Zeros(_) => 0,
+            })
+            .sum()
+    }
+}
+
+/// Update from one version of an image to the next, built region by region so that
+/// code and data that moved between builds are copied rather than stored again.
+///
+/// The new file is cut at the raw data of its sections. Each section is rebuilt from
+/// the old section of the same name, the headers and overlay from the old headers and
+/// overlay, and anything else from the whole old file. Within a region, runs found in
+/// the source are copied and the rest inserted; copies continue across short
+/// mismatches such as pointers that moved by a few bytes, as in bsdiff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    old_length: usize,
+    old_md5: [u8; 16],
+    new_length: usize,
+    new_md5: [u8; 16],
+    regions: Vec<RegionPatch>,
+}
+
+impl Patch {
+    /// Builds the patch that turns the file contents of `old` into those of `new`.
+    pub fn between(old: &PortExe, new: &PortExe) -> Self {
+        let old_regions = regions(old);
+        let new_regions = regions(new);
+        let old_data = old.data();
+        let new_data = new.data();
+        let patches = new_regions
+            .iter()
+            .map(|(region, range)| {
+                let source = match region {
+                    Region::Headers => PatchSource::Headers,
+                    Region::Overlay => PatchSource::Overlay,
+                    Region::Gap => PatchSource::File,
+                    Region::Section { name, occurrence } => old_regions
+                        .iter()
+                        .find_map(|(old_region, _)| match old_region {
+                            Region::Section {
+                                name: old_name,
+                                occurrence: old_occurrence,
+                            } if old_name == name && old_occurrence == occurrence => {
+                                section_index(old, old_name, *old_occurrence)
+                            }
+                            _ => None,
+                        })
+                        .map_or(PatchSource::File, PatchSource::Section),
+                };
+                let source_range = source_range(&old_regions, old, source);
+                RegionPatch {
+                    source,
+                    length: range.len(),
+                    ops: diff(&old_data[source_range], &new_data[range.clone()]),
+                }
+            })
+            .collect();
+        Self {
+            old_length: old_data.len(),
+            old_md5: md5(old_data),
+            new_length: new_data.len(),
+            new_md5: md5(new_data),
+            regions: patches,
+        }
+    }
+
+    /// Regions of the new file, in file order
+    pub fn regions(&self) -> &[RegionPatch] {
+        &self.regions
+    }
+
+    /// MD5 of the file the patch applies to
+    pub fn old_md5(&self) -> [u8; 16] {
+        self.old_md5
+    }
+
+    /// MD5 of the file the patch produces
+    pub fn new_md5(&self) -> [u8; 16] {
+        self.new_md5
+    }
+
+    /// Rebuilds the new file from `old`, which must be the file the patch was made from.
+    pub fn apply(&self, old: &[u8]) -> io::Result<Vec<u8>> {
+        if old.len() != self.old_length || md5(old) != self.old_md5 {
+            return Err(invalid_data("patch was made from a different file"));
+        }
+        let pe = PortExe::from_bytes(old.to_vec())?;
+        let old_regions = regions(&pe);
+        let mut data = Vec::with_capacity(self.new_length);
+        for region in &self.regions {
+            let source = &old[source_range(&old_regions, &pe, region.source)];
+            let start = data.len();
+            for op in &region.ops {
+                match op {
+                    PatchOp::Copy { offset, length } => {
+                        let bytes = offset
+                            .checked_add(*length)
+                            .and_then(|end| source.get(*offset..end))
+                            .ok_or_else(|| invalid_data("copy past the end of its source"))?;
+                        data.extend_from_slice(bytes);
+                    }
+                    PatchOp::Insert(bytes) => data.extend_from_slice(bytes),
+                    PatchOp::Zeros(length) => {
+                        if *length > region.length.saturating_sub(data.len() - start) {
+                            return Err(invalid_data("region does not add up to its length"));
+                        }
+                        data.resize(data.len() + length, 0);
+                    }
+                }
+            }
+            if data.len() - start != region.length {
+                return Err(invalid_data("region does not add up to its length"));
+            }
+        }
+        if data.len() != self.new_length || md5(&data) != self.new_md5 {
+            return Err(invalid_data(
+                "patched file does not match the expected digest",
+            ));
+        }
+        Ok(data)
+    }
+
+    /// Encodes the patch with variable-length integers; copy offsets are stored relative
+    /// to the end of the previous copy of their region.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+        put_varint(&mut out, self.old_length as u64);
+        out.extend_from_slice(&self.old_md5);
+        put_varint(&mut out, self.new_length as u64);
+        out.extend_from_slice(&self.new_md5);
+        put_varint(&mut out, self.regions.len() as u64);
+        for region in &self.regions {
+            match region.source {
+                PatchSource::Headers => out.push(SOURCE_HEADERS),
+                PatchSource::Section(index) => {
+                    out.push(SOURCE_SECTION);
+                    put_varint(&mut out, index as u64);
+                }
+                PatchSource::Overlay => out.push(SOURCE_OVERLAY),
+                PatchSource::File => out.push(SOURCE_FILE),
+            }
+            put_varint(&mut out, region.length as u64);
+            put_varint(&mut out, region.ops.len() as u64);
+            let mut previous_end = 0i64;
+            for op in &region.ops {
+                match op {
+                    PatchOp::Copy { offset, length } => {
+                        out.push(OP_COPY);
+                        let delta = *offset as i64 - previous_end;
+                        put_varint(&mut out, ((delta << 1) ^ (delta >> 63)) as u64);
+                        put_varint(&mut out, *length as u64);
+                        previous_end = (*offset + *length) as i64;
+                    }
+                    PatchOp::Insert(bytes) => {
+                        out.push(OP_INSERT);
+                        put_varint(&mut out, bytes.len() as u64);
+                        out.extend_from_slice(bytes);
+                    }
+                    PatchOp::Zeros(length) => {
+                        out.push(OP_ZEROS);
+                        put_varint(&mut out, *length as u64);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// Decodes a patch written by [`Patch::to_bytes`].
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        let mut reader = Reader { data, position: 0 };
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err(invalid_data("not a pexp patch"));
+        }
+        if reader.byte()? != VERSION {
+            return Err(invalid_data("unsupported patch version"));
+        }
+        let old_length = reader.length()?;
+        let old_md5 = reader.md5()?;
+        let new_length = reader.length()?;
+        let new_md5 = reader.md5()?;
+        let count = reader.length()?;
+        let mut regions = Vec::new();
+        for _ in 0..count {
+            let source = match reader.byte()? {
+                SOURCE_HEADERS => PatchSource::Headers,
+                SOURCE_SECTION => PatchSource::Section(reader.length()?),
+                SOURCE_OVERLAY => PatchSource::Overlay,
+                SOURCE_FILE => PatchSource::File,
+                source => return Err(invalid_data(&format!("unknown region source {}", source))),
+            };
+            let length = reader.length()?;
+            let op_count = reader.length()?;
+            let mut ops = Vec::new();
+            let mut previous_end = 0i64;
+            for _ in 0..op_count {
+                match reader.byte()? {
+                    OP_COPY => {
+                        let zigzag = reader.varint()?;
+                        let delta = (zigzag >> 1) as i64 ^ -((zigzag & 1) as i64);
+                        let offset = usize::try_from(previous_end.wrapping_add(delta))
+                            .map_err(|_| invalid_data("copy before the start of its source"))?;
+                        let length = reader.length()?;
+                        previous_end = offset.saturating_add(length) as i64;
+                        ops.push(PatchOp::Copy { offset, length });
+                    }
+                    OP_INSERT => {
+                        let length = reader.length()?;
+                        ops.push(PatchOp::Insert(reader.bytes(length)?.to_vec()));
+                    }
+                    OP_ZEROS => ops.push(PatchOp::Zeros(reader.length()?)),
+                    op => return Err(invalid_data(&format!("unknown patch operation {}", op))),
+                }
+            }
+            regions.push(RegionPatch {
+                source,
+                length,
+                ops,
+            });
+        }
+        if reader.position != data.len() {
+            return Err(invalid_data("trailing data after the patch"));
+        }
+        Ok(Self {
+            old_length,
+            old_md5,
+            new_length,
+            new_md5,
+            regions,
+        })
+    }
+}
+
+/// Part of a file a patch region covers
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Region {
+    Headers,
+    /// Raw data of the `occurrence`-th section named `name`
+    Section {
+        name: PeString,
+        occurrence: usize,
+    },
+    /// Bytes between sections
+    Gap,
+    Overlay,
+}
+
+/// Cuts the file at the raw data of its sections, in file order. Sections whose data
+/// overlaps an earlier one are left to the gaps and overlay around them.
+fn regions(pe: &PortExe) -> Vec<(Region, Range<usize>)> {
+    let length = pe.data().len();
+    let mut sections: Vec<(PeString, Range<usize>)> = pe
+        .section_headers()
+        .iter()
+        .filter(|section| section.size_of_raw_data().into_value() != 0)
+        .map(|section| {
+            let start = (section.pointer_to_raw_data().into_value() as usize).min(length);
+            let end = start
+                .saturating_add(section.size_of_raw_data().into_value() as usize)
+                .min(length);
+            (section.name().into_value(), start..end)
+        })
+        .filter(|(_, range)| !range.is_empty())
+        .collect();
+    sections.sort_by_key(|(_, range)| range.start);
+
+    let mut regions = Vec::new();
+    let mut occurrences: HashMap<PeString, usize> = HashMap::new();
+    let mut position = 0;
+    for (name, range) in sections {
+        if range.start < position {
+            continue;
+        }
+        if range.start > position {
+            let region = if position == 0 {
+                Region::Headers
+            } else {
+                Region::Gap
+            };
+            regions.push((region, position..range.start));
+        }
+        let occurrence = occurrences.entry(name.clone()).or_insert(0);
+        regions.push((
+            Region::Section {
+                name,
+                occurrence: *occurrence,
+            },
+            range.clone(),
+        ));
+        *occurrence += 1;
+        position = range.end;
+    }
+    if position < length {
+        let region = if position == 0 {
+            Region::Headers
+        } else {
+            Region::Overlay
+        };
+        regions.push((region, position..length));
+    }
+    regions
+}
+
+/// Index in the section table of the `occurrence`-th section named `name` with raw data
+fn section_index(pe: &PortExe, name: &PeString, occurrence: usize) -> Option<usize> {
+    pe.section_headers()
+        .iter()
+        .enumerate()
+        .filter(|(_, section)| {
+            section.size_of_raw_data().into_value() != 0 && section.name().into_value() == *name
+        })
+        .map(|(index, _)| index)
+        .nth(occurrence)
+}
+
+/// File range of `source` in the old file, empty if it has no such part
+fn source_range(
+    regions: &[(Region, Range<usize>)],
+    pe: &PortExe,
+    source: PatchSource,
+) -> Range<usize> {
+    let find = |wanted: &dyn Fn(&Region) -> bool| {
+        regions
+            .iter()
+            .find(|(region, _)| wanted(region))
+            .map_or(0..0, |(_, range)| range.clone())
+    };
+    match source {
+        PatchSource::Headers => find(&|region| *region == Region::Headers),
+        PatchSource::Overlay => find(&|region| *region == Region::Overlay),
+        PatchSource::File => 0..pe.data().len(),
+        PatchSource::Section(index) => {
+            let section = match pe.section_headers().get(index) {
+                Some(section) => section,
+                None => return 0..0,
+            };
+            let name = section.name().into_value();
+            let occurrence = pe.section_headers()[..index]
+                .iter()
+                .filter(|earlier| {
+                    earlier.size_of_raw_data().into_value() != 0
+                        && earlier.name().into_value() == name
+                })
+                .count();
+            find(&|region| {
+                *region
+                    == Region::Section {
+                        name: name.clone(),
+                        occurrence,
+                    }
+            })
+        }
+    }
+}
+
+/// Copies and inserts rebuilding `new` from `old`
+fn diff(old: &[u8], new: &[u8]) -> Vec<PatchOp> {
+    if old == new {
+        return match new.len() {
+            0 => Vec::new(),
+            length => vec![PatchOp::Copy { offset: 0, length }],
+        };
+    }
+    let mut index: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    if old.len() >= MIN_MATCH {
+        for position in (0..=old.len() - MIN_MATCH).step_by(INDEX_STRIDE) {
+            let candidates = index
+                .entry(&old[position..position + MIN_MATCH])
+                .or_default();
+            if candidates.len() < MAX_CANDIDATES {
+                candidates.push(position);
+            }
+        }
+    }
+    let match_length = |old_start: usize, new_start: usize| {
+        old[old_start.min(old.len())..]
+            .iter()
+            .zip(&new[new_start..])
+            .take_while(|(a, b)| a == b)
+            .count()
+    };
+
+    let mut ops = Vec::new();
+    let mut literal_start = 0;
+    // Old position the previous copy ended at, and the new position it ended at
+    let mut previous = None;
+    let mut position = 0;
+    while position + MIN_MATCH <= new.len() {
+        // Following the previous copy across a short mismatch is cheaper than a new match
+        let diagonal = previous.and_then(|(old_end, new_end): (usize, usize)| {
+            let skipped = position - new_end;
+            let old_position = old_end + skipped;
+            (skipped <= MAX_MISMATCH && match_length(old_position, position) >= MIN_MATCH / 2)
+                .then(|| old_position)
+        });
+        let best = diagonal
+            .map(|old_position| (old_position, match_length(old_position, position)))
+            .or_else(|| {
+                index
+                    .get(&new[position..position + MIN_MATCH])?
+                    .iter()
+                    .map(|&old_position| (old_position, match_length(old_position, position)))
+                    .max_by_key(|&(_, length)| length)
+            });
+        let (mut old_start, length) = match best {
+            Some(found) => found,
+            None => {
+                let zeros = new[position..]
+                    .iter()
+                    .take_while(|&&byte| byte == 0)
+                    .count();
+                if zeros >= MIN_MATCH {
+                    if position > literal_start {
+                        ops.push(PatchOp::Insert(new[literal_start..position].to_vec()));
+                    }
+                    ops.push(PatchOp::Zeros(zeros));
+                    position += zeros;
+                    literal_start = position;
+                    previous = None;
+                } else {
+                    position += 1;
+                }
+                continue;
+            }
+        };
+        let mut new_start = position;
+        while new_start > literal_start && old_start > 0 && old[old_start - 1] == new[new_start - 1]
+        {
+            old_start -= 1;
+            new_start -= 1;
+        }
+        let length = length + (position - new_start);
+        if new_start > literal_start {
+            ops.push(PatchOp::Insert(new[literal_start..new_start].to_vec()));
+        }
+        ops.push(PatchOp::Copy {
+            offset: old_start,
+            length,
+        });
+        position = new_start + length;
+        literal_start = position;
+        previous = Some((old_start + length, position));
+    }
+    if literal_start < new.len() {
+        ops.push(PatchOp::Insert(new[literal_start..].to_vec()));
+    }
+    ops
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .position
+            .checked_add(length)
+            .and_then(|end| self.data.get(self.position..end))
+            .ok_or_else(|| invalid_data("patch is truncated"))?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn md5(&mut self) -> io::Result<[u8; 16]> {
+        let mut digest = [0u8; 16];
+        digest.copy_from_slice(self.bytes(16)?);
+        Ok(digest)
+    }
+
+    fn varint(&mut self) -> io::Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid_data("variable-length integer is too long"))
+    }
+
+    /// Length or count, which cannot exceed the size of the patch by much
+    fn length(&mut self) -> io::Result<usize> {
+        usize::try_from(self.varint()?).map_err(|_| invalid_data("length is too large"))
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
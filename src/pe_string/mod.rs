@@ -0,0 +1,121 @@
+use crate::endian::{utf16_units, ByteOrder};
+use std::borrow::Cow;
+use std::fmt;
+
+/// Name as stored in the file, kept byte for byte.
+///
+/// Section, export and import names are byte strings with no defined encoding, and
+/// resource names are UTF-16 that need not be well formed. Decoding either into a
+/// `String` replaces what does not decode, so names are kept raw: comparisons are
+/// exact, and only display is lossy.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PeString {
+    bytes: Vec<u8>,
+    utf16: bool,
+}
+
+impl PeString {
+    /// Byte string, such as a section or export name
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Self {
+        Self {
+            bytes: bytes.into(),
+            utf16: false,
+        }
+    }
+
+    /// UTF-16 string, such as a resource name, stored as little-endian code units
+    pub fn from_utf16(units: &[u16]) -> Self {
+        Self {
+            bytes: units.iter().flat_map(|unit| unit.to_le_bytes()).collect(),
+            utf16: true,
+        }
+    }
+
+    /// Bytes as stored in the file, without the terminator or padding
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns `true` for UTF-16 strings, whose bytes are little-endian code units.
+    pub fn is_utf16(&self) -> bool {
+        self.utf16
+    }
+
+    /// Length in bytes
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Byte string that is valid UTF-8, borrowed; `None` for anything else
+    pub fn as_str(&self) -> Option<&str> {
+        if self.utf16 {
+            return None;
+        }
+        std::str::from_utf8(&self.bytes).ok()
+    }
+
+    /// Decoded text, with invalid sequences replaced by U+FFFD
+    pub fn to_string_lossy(&self) -> Cow<'_, str> {
+        if self.utf16 {
+            Cow::Owned(String::from_utf16_lossy(&self.units()))
+        } else {
+            String::from_utf8_lossy(&self.bytes)
+        }
+    }
+
+    /// Compares with `text` ignoring ASCII case, the way module names compare
+    pub fn eq_ignore_ascii_case(&self, text: &str) -> bool {
+        if self.utf16 {
+            let units = self.units();
+            units.len() == text.encode_utf16().count()
+                && units.iter().zip(text.encode_utf16()).all(|(&a, b)| {
+                    a == b || (a < 0x80 && b < 0x80 && (a as u8).eq_ignore_ascii_case(&(b as u8)))
+                })
+        } else {
+            self.bytes.eq_ignore_ascii_case(text.as_bytes())
+        }
+    }
+
+    fn units(&self) -> Vec<u16> {
+        utf16_units(&self.bytes, ByteOrder::Little)
+    }
+}
+
+impl fmt::Display for PeString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_string_lossy())
+    }
+}
+
+impl From<&str> for PeString {
+    fn from(text: &str) -> Self {
+        Self::from_bytes(text)
+    }
+}
+
+impl From<String> for PeString {
+    fn from(text: String) -> Self {
+        Self::from_bytes(text)
+    }
+}
+
+/// Exact comparison with `text` encoded the way this string is stored
+impl PartialEq<str> for PeString {
+    fn eq(&self, text: &str) -> bool {
+        if self.utf16 {
+            self.units().into_iter().eq(text.encode_utf16())
+        } else {
+            self.bytes == text.as_bytes()
+        }
+    }
+}
+
+impl PartialEq<&str> for PeString {
+    fn eq(&self, text: &&str) -> bool {
+        *self == **text
+    }
+}
@@ -0,0 +1,227 @@
+use crate::analysis::section_compression;
+use crate::checksec::Protection;
+use crate::messages::Message;
+use crate::port_exe::PortExe;
+use crate::validation::{Finding, Severity, ValidationMode};
+use std::io;
+
+/// Entropy in bits per byte above which a section is reported as compressed or encrypted
+const HIGH_ENTROPY: f64 = 7.2;
+
+/// Check run over every file a [`AnalysisRegistry`] is given.
+///
+/// Implement it to add checks to `scan` output of a tool built on this crate without
+/// changing the tool; analyses see only the image and report through their findings.
+pub trait Analysis {
+    /// Short name selecting the analysis, such as `entropy`
+    fn name(&self) -> &str;
+
+    fn run(&self, pe: &PortExe) -> io::Result<Vec<Finding>>;
+}
+
+/// Sections whose entropy suggests packed or encrypted data
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EntropyAnalysis;
+
+impl Analysis for EntropyAnalysis {
+    fn name(&self) -> &str {
+        "entropy"
+    }
+
+    fn run(&self, pe: &PortExe) -> io::Result<Vec<Finding>> {
+        Ok(section_compression(pe)
+            .iter()
+            .filter(|section| section.entropy() > HIGH_ENTROPY)
+            .map(|section| {
+                Finding::from_message(
+                    Severity::Warning,
+                    Message::new(
+                        "high-entropy-section",
+                        vec![
+                            section.name().to_string_lossy().into_owned(),
+                            format!("{:.2}", section.entropy()),
+                        ],
+                    ),
+                )
+            })
+            .collect())
+    }
+}
+
+/// Entry points placed where no linker puts them, see
+/// [`PortExe::entry_point_anomalies`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EntryPointAnalysis;
+
+impl Analysis for EntryPointAnalysis {
+    fn name(&self) -> &str {
+        "entry-point"
+    }
+
+    fn run(&self, pe: &PortExe) -> io::Result<Vec<Finding>> {
+        Ok(pe
+            .entry_point_anomalies()
+            .iter()
+            .map(|anomaly| Finding::from_message(Severity::Warning, anomaly.message()))
+            .collect())
+    }
+}
+
+/// Header values that break the conventions of the targeted platform, see
+/// [`PortExe::validate`]
+#[derive(Debug, Clone, Copy)]
+pub struct AnomalyAnalysis {
+    mode: ValidationMode,
+}
+
+impl AnomalyAnalysis {
+    pub fn new(mode: ValidationMode) -> Self {
+        Self { mode }
+    }
+}
+
+impl Analysis for AnomalyAnalysis {
+    fn name(&self) -> &str {
+        "anomalies"
+    }
+
+    fn run(&self, pe: &PortExe) -> io::Result<Vec<Finding>> {
+        Ok(pe.validate(self.mode))
+    }
+}
+
+/// Exploit mitigations the image does not enable, see [`PortExe::checksec`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SecurityAnalysis;
+
+impl Analysis for SecurityAnalysis {
+    fn name(&self) -> &str {
+        "security"
+    }
+
+    fn run(&self, pe: &PortExe) -> io::Result<Vec<Finding>> {
+        let checksec = pe.checksec()?;
+        let mut findings: Vec<Finding> = [
+            ("ASLR", checksec.aslr()),
+            ("DEP", checksec.dep()),
+            ("CFG", checksec.cfg()),
+            ("SafeSEH", checksec.safe_seh()),
+            ("GS", checksec.gs()),
+            ("CET", checksec.cet()),
+            ("EHCONT", checksec.eh_continuation()),
+            ("XFG", checksec.xfg()),
+            ("HighEntropyVA", checksec.high_entropy_va()),
+        ]
+        .iter()
+        .filter(|(_, protection)| *protection == Protection::Disabled)
+        .map(|(name, _)| {
+            Finding::from_message(
+                Severity::Warning,
+                Message::new("mitigation-disabled", vec![name.to_string()]),
+            )
+        })
+        .collect();
+        if checksec.signed() == Protection::Disabled {
+            findings.push(Finding::from_message(
+                Severity::Info,
+                Message::new("not-signed", Vec::new()),
+            ));
+        }
+        Ok(findings)
+    }
+}
+
+/// Findings of one analysis of one file
+#[derive(Debug)]
+pub struct AnalysisReport<'a> {
+    analysis: &'a str,
+    findings: io::Result<Vec<Finding>>,
+}
+
+impl<'a> AnalysisReport<'a> {
+    /// Name of the analysis
+    pub fn analysis(&self) -> &'a str {
+        self.analysis
+    }
+
+    pub fn findings(&self) -> Result<&[Finding], &io::Error> {
+        self.findings.as_deref()
+    }
+}
+
+/// Analyses to run, in the order they were registered
+#[derive(Default)]
+pub struct AnalysisRegistry {
+    analyses: Vec<Box<dyn Analysis>>,
+}
+
+impl AnalysisRegistry {
+    /// Registry without analyses
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry with the analyses shipped with the crate: [`EntropyAnalysis`],
+    /// [`AnomalyAnalysis`] in permissive mode, [`SecurityAnalysis`] and
+    /// [`EntryPointAnalysis`]
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        registry.register(Box::new(EntropyAnalysis));
+        registry.register(Box::new(AnomalyAnalysis::new(ValidationMode::Permissive)));
+        registry.register(Box::new(SecurityAnalysis));
+        registry.register(Box::new(EntryPointAnalysis));
+        registry
+    }
+
+    /// Adds an analysis, replacing any registered under the same name.
+    pub fn register(&mut self, analysis: Box<dyn Analysis>) {
+        match self
+            .analyses
+            .iter()
+            .position(|registered| registered.name() == analysis.name())
+        {
+            Some(index) => self.analyses[index] = analysis,
+            None => self.analyses.push(analysis),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Analysis> {
+        self.analyses
+            .iter()
+            .find(|analysis| analysis.name() == name)
+            .map(|analysis| analysis.as_ref())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.analyses
+            .iter()
+            .map(|analysis| analysis.name())
+            .collect()
+    }
+
+    /// Keeps only the analyses named in `names`, in the order given. Fails with the
+    /// first name that is not registered.
+    pub fn select(mut self, names: &[&str]) -> Result<Self, String> {
+        let mut selected = Vec::new();
+        for name in names {
+            let index = self
+                .analyses
+                .iter()
+                .position(|analysis| analysis.name() == *name)
+                .ok_or_else(|| name.to_string())?;
+            selected.push(self.analyses.remove(index));
+        }
+        Ok(Self { analyses: selected })
+    }
+
+    /// Runs every analysis over `pe`; an analysis failing does not stop the others.
+    pub fn run(&self, pe: &PortExe) -> Vec<AnalysisReport<'_>> {
+        self.analyses
+            .iter()
+            .map(|analysis| AnalysisReport {
+                analysis: analysis.name(),
+                findings: analysis.run(pe),
+            })
+            .collect()
+    }
+}
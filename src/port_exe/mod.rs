@@ -0,0 +1,513 @@
+use crate::arith::slice_at;
+use crate::endian::le_u32;
+use crate::file_header::{read_file_header, FileHeaderWrapper};
+use crate::optional_header::{read_optional_header, OptionalHeaderWrapper};
+use crate::section_header::{
+    read_section_headers, SectionHeaderWrapper, IMAGE_SIZEOF_SECTION_HEADER,
+};
+use crate::{read_u32, ImageLayout, PEType};
+use std::fmt;
+use std::io;
+use std::io::Cursor;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::ops::Range;
+
+/// Size of the COFF file header
+pub const IMAGE_SIZEOF_FILE_HEADER: u64 = 20;
+
+const IMAGE_DOS_SIGNATURE: [u8; 2] = [b'M', b'Z'];
+const IMAGE_NT_SIGNATURE: [u8; 4] = [b'P', b'E', 0, 0];
+const E_LFANEW_OFFSET: u64 = 0x3C;
+/// Size of `IMAGE_DOS_HEADER`
+const IMAGE_SIZEOF_DOS_HEADER: usize = 0x40;
+
+/// Header structure [`PortExe`] parses eagerly
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HeaderStructure {
+    /// `IMAGE_DOS_HEADER`, of which only `e_magic` and `e_lfanew` are read
+    DosHeader,
+    /// `PE\0\0` signature
+    Signature,
+    FileHeader,
+    /// Optional header with its data directories
+    OptionalHeader,
+    /// Entry of the section table, by index
+    SectionHeader(usize),
+}
+
+impl fmt::Display for HeaderStructure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DosHeader => f.pad("DOS header"),
+            Self::Signature => f.pad("PE signature"),
+            Self::FileHeader => f.pad("file header"),
+            Self::OptionalHeader => f.pad("optional header"),
+            Self::SectionHeader(index) => f.pad(&format!("section header {}", index)),
+        }
+    }
+}
+
+/// File range a header structure was parsed from, see [`PortExe::header_spans`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderSpan {
+    structure: HeaderStructure,
+    range: Range<usize>,
+}
+
+impl HeaderSpan {
+    pub fn structure(&self) -> HeaderStructure {
+        self.structure
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+}
+
+/// Parsed PE image or COFF object file.
+///
+/// The whole file is kept in memory so that section contents and data
+/// directories can be sliced without going back to the reader.
+#[derive(Debug)]
+pub struct PortExe {
+    data: Vec<u8>,
+    base_offset: u64,
+    layout: ImageLayout,
+    pe_type: PEType,
+    file_header: FileHeaderWrapper,
+    optional_header: Option<OptionalHeaderWrapper>,
+    section_headers: Vec<SectionHeaderWrapper>,
+    /// File contents the headers were parsed from, up to the end of the last of them
+    source: Vec<u8>,
+}
+
+impl PortExe {
+    /// Reads the whole file from `reader` and parses its headers.
+    ///
+    /// Files starting with the `MZ` signature are treated as images, anything else as a COFF object.
+    pub fn parse<R: Read + Seek>(reader: &mut R) -> io::Result<Self> {
+        reader.seek(SeekFrom::Start(0))?;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        Self::from_bytes(data)
+    }
+
+    /// Parses an image embedded in a larger container, such as a firmware volume, an
+    /// installer or a memory dump, starting `base_offset` bytes into `reader`.
+    ///
+    /// Everything from `base_offset` to the end of the reader is read, so whatever the
+    /// container stores after the image shows up as overlay. File offsets reported by
+    /// the analyses stay relative to the image; add [`PortExe::base_offset`] to locate
+    /// them in the container.
+    pub fn parse_at<R: Read + Seek>(reader: &mut R, base_offset: u64) -> io::Result<Self> {
+        reader.seek(SeekFrom::Start(base_offset))?;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        let mut pe = Self::from_bytes(data)?;
+        pe.base_offset = base_offset;
+        Ok(pe)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> io::Result<Self> {
+        let headers = Headers::read(&data)?;
+        let mut pe = Self {
+            data,
+            base_offset: 0,
+            layout: ImageLayout::File,
+            pe_type: headers.pe_type,
+            file_header: headers.file_header,
+            optional_header: headers.optional_header,
+            section_headers: headers.section_headers,
+            source: Vec::new(),
+        };
+        pe.remember_source();
+        Ok(pe)
+    }
+
+    /// Parses an image dumped from memory, where every section sits at its virtual address.
+    ///
+    /// RVAs index the buffer directly, so the analyses read the data as the loader left it.
+    pub fn from_mapped_bytes(data: Vec<u8>) -> io::Result<Self> {
+        let mut pe = Self::from_bytes(data)?;
+        if !pe.pe_type.is_image() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "object files have no mapped layout",
+            ));
+        }
+        pe.layout = ImageLayout::Mapped;
+        Ok(pe)
+    }
+
+    /// Serializes the file, encoding the parsed headers over the original contents.
+    ///
+    /// Only the bytes of the headers that were edited since they were parsed are
+    /// written. Every other byte, including padding between and inside the headers and
+    /// the parts of overlapping headers that were left alone, comes out as it was read,
+    /// so the result differs from the parsed file by no more than the edits. An
+    /// unmodified file serializes to the bytes it was parsed from.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = self.data.clone();
+        self.write_headers(&mut data);
+        data
+    }
+
+    /// File ranges of the headers parsed eagerly, in file order of the structures:
+    /// DOS header and signature for images, file header, optional header and section
+    /// table.
+    ///
+    /// Ranges of malformed files may overlap, such as a section table that starts
+    /// inside the data directories because `SizeOfOptionalHeader` is too small.
+    pub fn header_spans(&self) -> Vec<HeaderSpan> {
+        let mut spans = Vec::new();
+        let file_header = self.file_header.machine().offset() as usize;
+        if self.pe_type.is_image() {
+            spans.push(HeaderSpan {
+                structure: HeaderStructure::DosHeader,
+                range: 0..IMAGE_SIZEOF_DOS_HEADER,
+            });
+            spans.push(HeaderSpan {
+                structure: HeaderStructure::Signature,
+                range: file_header - IMAGE_NT_SIGNATURE.len()..file_header,
+            });
+        }
+        spans.push(HeaderSpan {
+            structure: HeaderStructure::FileHeader,
+            range: file_header..file_header + IMAGE_SIZEOF_FILE_HEADER as usize,
+        });
+        if let Some(header) = &self.optional_header {
+            spans.push(HeaderSpan {
+                structure: HeaderStructure::OptionalHeader,
+                range: header.span(),
+            });
+        }
+        for (index, section) in self.section_headers.iter().enumerate() {
+            let offset = section.name().offset() as usize;
+            spans.push(HeaderSpan {
+                structure: HeaderStructure::SectionHeader(index),
+                range: offset..offset + IMAGE_SIZEOF_SECTION_HEADER as usize,
+            });
+        }
+        spans
+    }
+
+    /// Writes the bytes each parsed header changed relative to the bytes it was parsed from
+    fn write_headers(&self, data: &mut [u8]) {
+        let mut encoded = self.source.clone();
+        let mut patch = |write: &dyn Fn(&mut [u8])| {
+            encoded.copy_from_slice(&self.source);
+            write(&mut encoded);
+            for (index, (&new, &old)) in encoded.iter().zip(&self.source).enumerate() {
+                if new != old {
+                    if let Some(byte) = data.get_mut(index) {
+                        *byte = new;
+                    }
+                }
+            }
+        };
+        patch(&|data| self.file_header.write(data));
+        if let Some(optional_header) = &self.optional_header {
+            patch(&|data| optional_header.write(data));
+        }
+        for section in &self.section_headers {
+            patch(&|data| section.write(data));
+        }
+    }
+
+    /// Keeps a copy of the bytes the headers were just parsed from.
+    fn remember_source(&mut self) {
+        let end = self
+            .header_spans()
+            .iter()
+            .map(|span| span.range.end)
+            .max()
+            .unwrap_or(0)
+            .min(self.data.len());
+        self.source = self.data[..end].to_vec();
+    }
+
+    /// Writes edited headers back into the file contents, so that analyses see them.
+    pub(crate) fn commit_headers(&mut self) {
+        let mut data = std::mem::take(&mut self.data);
+        self.write_headers(&mut data);
+        self.data = data;
+        self.remember_source();
+    }
+
+    pub(crate) fn data_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.data
+    }
+
+    /// Parses the headers again from the file contents, leaving the parsed headers
+    /// untouched if they no longer parse.
+    pub(crate) fn reparse_headers(&mut self) -> io::Result<()> {
+        let headers = Headers::read(&self.data)?;
+        self.pe_type = headers.pe_type;
+        self.file_header = headers.file_header;
+        self.optional_header = headers.optional_header;
+        self.section_headers = headers.section_headers;
+        self.remember_source();
+        Ok(())
+    }
+
+    /// File offset just past the section table, the end of everything parsed eagerly
+    pub(crate) fn headers_end(&self) -> usize {
+        let file_header = &self.file_header;
+        (file_header.machine().offset() + IMAGE_SIZEOF_FILE_HEADER) as usize
+            + file_header.size_of_optional_header().into_value() as usize
+            + self.section_headers.len() * IMAGE_SIZEOF_SECTION_HEADER as usize
+    }
+
+    pub(crate) fn optional_header_mut(&mut self) -> Option<&mut OptionalHeaderWrapper> {
+        self.optional_header.as_mut()
+    }
+
+    pub(crate) fn section_headers_mut(&mut self) -> &mut [SectionHeaderWrapper] {
+        &mut self.section_headers
+    }
+
+    /// Raw bytes of the whole file
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Offset of the image within the reader it was parsed from, zero unless it was
+    /// parsed with [`PortExe::parse_at`]
+    pub fn base_offset(&self) -> u64 {
+        self.base_offset
+    }
+
+    /// File offset where the overlay starts: the end of the headers, the raw data of
+    /// the sections, the COFF symbol and string tables and debug payloads stored outside
+    /// sections, whichever comes last. Equals the file size if there is no overlay.
+    ///
+    /// The attribute certificate table is not part of the image and lies in the overlay.
+    pub fn overlay_offset(&self) -> usize {
+        let len = self.data.len();
+        if self.layout.is_mapped() {
+            return len;
+        }
+        let headers = self
+            .optional_header
+            .as_ref()
+            .map_or(0, |header| header.size_of_headers() as usize);
+        let sections = self.section_headers.iter().map(|section| {
+            (section.pointer_to_raw_data().into_value() as usize)
+                .saturating_add(section.size_of_raw_data().into_value() as usize)
+        });
+        let debug = self.debug_directory().unwrap_or_default();
+        let payloads = debug.iter().map(|entry| {
+            (entry.pointer_to_raw_data() as usize).saturating_add(entry.size_of_data() as usize)
+        });
+        let symbols = self.symbol_tables_extent().map(|extent| extent.end);
+        sections
+            .chain(payloads)
+            .chain(symbols)
+            .fold(headers, usize::max)
+            .min(len)
+    }
+
+    /// Whether sections are laid out as on disk or as in memory
+    pub fn layout(&self) -> ImageLayout {
+        self.layout
+    }
+
+    pub fn pe_type(&self) -> PEType {
+        self.pe_type
+    }
+
+    pub fn file_header(&self) -> &FileHeaderWrapper {
+        &self.file_header
+    }
+
+    /// Optional header, absent for object files
+    pub fn optional_header(&self) -> Option<&OptionalHeaderWrapper> {
+        self.optional_header.as_ref()
+    }
+
+    pub fn section_headers(&self) -> &[SectionHeaderWrapper] {
+        &self.section_headers
+    }
+
+    /// Section whose virtual extent contains `rva`
+    pub fn section_at_rva(&self, rva: u32) -> Option<&SectionHeaderWrapper> {
+        self.section_headers.iter().find(|section| {
+            let virtual_address = section.virtual_address().into_value();
+            let virtual_size = match section.virtual_size().into_value() {
+                0 => section.size_of_raw_data().into_value(),
+                virtual_size => virtual_size,
+            };
+            rva >= virtual_address && rva - virtual_address < virtual_size
+        })
+    }
+
+    /// Translates a relative virtual address to a file offset.
+    ///
+    /// Addresses below the first section map into the headers. Returns `None`
+    /// for addresses that are not backed by file data. In a mapped image the
+    /// offset is the RVA itself.
+    pub fn rva_to_offset(&self, rva: u32) -> Option<usize> {
+        if self.layout.is_mapped() {
+            return ((rva as usize) < self.data.len()).then(|| rva as usize);
+        }
+        for section in &self.section_headers {
+            let virtual_address = section.virtual_address().into_value();
+            let size_of_raw_data = section.size_of_raw_data().into_value();
+            let virtual_size = match section.virtual_size().into_value() {
+                0 => size_of_raw_data,
+                virtual_size => virtual_size,
+            };
+            if rva < virtual_address || rva - virtual_address >= virtual_size {
+                continue;
+            }
+            let delta = rva - virtual_address;
+            if delta >= size_of_raw_data {
+                return None;
+            }
+            let offset = (section.pointer_to_raw_data().into_value() as usize)
+                .checked_add(delta as usize)?;
+            return (offset < self.data.len()).then(|| offset);
+        }
+
+        let size_of_headers = self.optional_header.as_ref()?.size_of_headers();
+        (rva < size_of_headers && (rva as usize) < self.data.len()).then(|| rva as usize)
+    }
+
+    /// Returns `size` bytes of file data starting at `rva`, if all of them are present.
+    pub fn data_at_rva(&self, rva: u32, size: u32) -> Option<&[u8]> {
+        slice_at(&self.data, self.rva_to_offset(rva)?, size as usize)
+    }
+
+    /// Raw data of `section` as stored in the file, truncated at the end of file.
+    ///
+    /// For a mapped image this is the virtual extent of the section instead.
+    pub fn section_data(&self, section: &SectionHeaderWrapper) -> &[u8] {
+        let (start, size) = match self.layout {
+            ImageLayout::File => (
+                section.pointer_to_raw_data().into_value(),
+                section.size_of_raw_data().into_value(),
+            ),
+            ImageLayout::Mapped => (
+                section.virtual_address().into_value(),
+                match section.virtual_size().into_value() {
+                    0 => section.size_of_raw_data().into_value(),
+                    virtual_size => virtual_size,
+                },
+            ),
+        };
+        let (start, size) = (start as usize, size as usize);
+        let start = start.min(self.data.len());
+        let end = start.saturating_add(size).min(self.data.len());
+        &self.data[start..end]
+    }
+
+    /// Number of COFF relocations of `section`, following the overflow convention of
+    /// `IMAGE_SCN_LNK_NRELOC_OVFL` where the real count is stored in the first relocation.
+    pub fn relocation_count(&self, section: &SectionHeaderWrapper) -> u32 {
+        let count = section.number_of_relocations().into_value();
+        if count == u16::MAX && section.characteristics().value().lnk_nreloc_ovfl() {
+            let pointer = section.pointer_to_relocations().into_value() as usize;
+            if let Some(count) = read_u32(&self.data, pointer) {
+                return count.saturating_sub(1);
+            }
+        }
+        count as u32
+    }
+}
+
+/// Headers parsed by [`PortExe::from_bytes`], kept apart from the data so they can be
+/// read again after an edit
+struct Headers {
+    pe_type: PEType,
+    file_header: FileHeaderWrapper,
+    optional_header: Option<OptionalHeaderWrapper>,
+    section_headers: Vec<SectionHeaderWrapper>,
+}
+
+impl Headers {
+    fn read(data: &[u8]) -> io::Result<Self> {
+        let mut cursor = Cursor::new(data);
+
+        let (pe_type, file_header_offset) = if data.starts_with(&IMAGE_DOS_SIGNATURE) {
+            ensure_in_file(data, "DOS header", 0..E_LFANEW_OFFSET + 4)?;
+            cursor.seek(SeekFrom::Start(E_LFANEW_OFFSET))?;
+            let mut e_lfanew = [0u8; 4];
+            cursor.read_exact(&mut e_lfanew)?;
+            let pe_header_offset = le_u32(e_lfanew) as u64;
+            ensure_in_file(data, "PE signature", pe_header_offset..pe_header_offset + 4)?;
+
+            cursor.seek(SeekFrom::Start(pe_header_offset))?;
+            let mut image_signature = [0u8; 4];
+            cursor.read_exact(&mut image_signature)?;
+            if image_signature != IMAGE_NT_SIGNATURE {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "missing PE signature",
+                ));
+            }
+            (PEType::Image, pe_header_offset + 4)
+        } else {
+            (PEType::Object, 0)
+        };
+
+        ensure_in_file(
+            data,
+            "file header",
+            file_header_offset..file_header_offset + IMAGE_SIZEOF_FILE_HEADER,
+        )?;
+        let file_header = read_file_header(&mut cursor, file_header_offset)?;
+        let size_of_optional_header = file_header.size_of_optional_header().into_value() as u64;
+        let optional_header_offset = file_header_offset + IMAGE_SIZEOF_FILE_HEADER;
+        let optional_header = if size_of_optional_header > 0 {
+            ensure_in_file(
+                data,
+                "optional header",
+                optional_header_offset..optional_header_offset + size_of_optional_header,
+            )?;
+            Some(read_optional_header(
+                &mut cursor,
+                optional_header_offset,
+                size_of_optional_header,
+            )?)
+        } else {
+            None
+        };
+
+        let section_table_offset = optional_header_offset + size_of_optional_header;
+        let number_of_sections = file_header.number_of_sections().into_value();
+        ensure_in_file(
+            data,
+            "section table",
+            section_table_offset
+                ..section_table_offset + number_of_sections as u64 * IMAGE_SIZEOF_SECTION_HEADER,
+        )?;
+        let section_headers =
+            read_section_headers(&mut cursor, section_table_offset, number_of_sections)?;
+
+        Ok(Self {
+            pe_type,
+            file_header,
+            optional_header,
+            section_headers,
+        })
+    }
+}
+
+/// Fails with the header the file ends in, instead of the bare end-of-file error a
+/// read would give, so truncated files say what was cut off
+fn ensure_in_file(data: &[u8], structure: &str, range: Range<u64>) -> io::Result<()> {
+    let len = data.len() as u64;
+    if range.end <= len {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        format!(
+            "file ends at {:#x}, inside the {} at {:#x}..{:#x}",
+            len, structure, range.start, range.end
+        ),
+    ))
+}
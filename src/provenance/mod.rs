@@ -0,0 +1,886 @@
+use crate::arith::slice_at;
+use crate::endian::read_be_u32;
+use crate::port_exe::PortExe;
+use crate::resource::{ResourceEntry, ResourceId, RT_RCDATA};
+use crate::{read_u16, read_u32};
+use std::fmt;
+
+/// `VB5!`, the signature of the project header of VB5 and VB6 executables
+const VB_SIGNATURE: &[u8] = b"VB5!";
+const VB_HEADER_SIZE: u32 = 0x68;
+/// `ProjectData`: fields up to `lpNativeCode`
+const VB_PROJECT_DATA_SIZE: u32 = 0x24;
+/// `ObjectTable`: fields up to `lpObjectArray`
+const VB_OBJECT_TABLE_SIZE: u32 = 0x34;
+/// `PublicObjectDescriptor`
+const VB_OBJECT_DESCRIPTOR_SIZE: u32 = 0x30;
+const MAX_VB_OBJECTS: u32 = 4096;
+/// Timestamp Borland linkers before Delphi 2006 stamp on every image, 1992-06-19
+const BORLAND_TIME_DATE_STAMP: u32 = 0x2A42_5E19;
+/// Signature of a form stored as a binary DFM resource
+const FORM_SIGNATURE: &[u8] = b"TPF0";
+const MAX_PACKAGE_UNITS: u32 = 65536;
+/// Longest name read from a NUL-terminated string
+const MAX_NAME_LENGTH: usize = 260;
+/// Marker preceding a compiled AutoIt script, followed by `AU3!` and the format
+pub(crate) const AUTOIT_MAGIC: [u8; 16] = [
+    0xA3, 0x48, 0x4B, 0xBE, 0x98, 0x6C, 0x4A, 0xA9, 0x99, 0x4C, 0x53, 0x0A, 0x86, 0xD6, 0x48, 0x7D,
+];
+pub(crate) const AUTOIT_SIGNATURE: &[u8] = b"AU3!";
+/// `RT_RCDATA` resource AutoIt 3.3 and later store the script in
+const AUTOIT_RESOURCE: &str = "SCRIPT";
+/// Magic of the cookie closing a PyInstaller CArchive
+const PYINSTALLER_MAGIC: &[u8] = b"MEI\x0C\x0B\x0A\x0B\x0E";
+/// Cookie of PyInstaller 2.0: magic, package length, TOC offset, TOC length and
+/// Python version
+const PYINSTALLER_COOKIE_SIZE: usize = 24;
+/// Cookie of PyInstaller 2.1 and later, which adds the 64-byte Python library name
+const PYINSTALLER_COOKIE_SIZE_PYLIB: usize = 88;
+/// TOC entry fields before the name
+const PYINSTALLER_ENTRY_HEADER_SIZE: usize = 18;
+const MAX_ARCHIVE_ENTRIES: usize = 65536;
+/// NSIS looks for its first header at multiples of 512 bytes into the file
+const NSIS_ALIGNMENT: usize = 512;
+/// `firstheader`: flags, the `0xDEADBEEF` signature, `NullsoftInst`, the size of the
+/// installer header and the size of all data following the first header
+const NSIS_FIRST_HEADER_SIZE: usize = 28;
+pub(crate) const NSIS_SIGNATURE: &[u8] = b"\xEF\xBE\xAD\xDENullsoftInst";
+const NSIS_FLAG_UNINSTALL: u32 = 1;
+const NSIS_FLAG_SILENT: u32 = 2;
+/// Resource ID of the Inno Setup loader's offset table
+const INNO_OFFSET_TABLE_RESOURCE: u16 = 11111;
+/// `Inno`, stored at 0x30 by loaders before 5.1.5 followed by the table's offset
+const INNO_LEGACY_SIGNATURE: u32 = 0x6F6E_6E49;
+const INNO_LEGACY_POINTER: usize = 0x30;
+/// Offset table IDs, the first loader version using each and the position of the
+/// setup data offset after the ID
+const INNO_OFFSET_TABLES: [(&[u8; 12], &str, usize); 6] = [
+    (b"rDlPtS02\x87\x65\x56\x78", "1.2.10", 24),
+    (b"rDlPtS04\x87\x65\x56\x78", "4.0.0", 20),
+    (b"rDlPtS05\x87\x65\x56\x78", "4.0.3", 20),
+    (b"rDlPtS06\x87\x65\x56\x78", "4.0.10", 20),
+    (b"rDlPtS07\x87\x65\x56\x78", "4.1.6", 16),
+    (b"rDlPtS\xCD\xE6\xD7\x7B\x0B\x2A", "5.1.5", 20),
+];
+/// Length of the ID, such as `Inno Setup Setup Data (5.5.7) (u)`, starting the setup data
+const INNO_SETUP_ID_SIZE: usize = 64;
+
+/// Visual Basic 5 or 6 project, decoded from the header `ThunRTMain` is started with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VbProject {
+    runtime: String,
+    header_rva: Option<u32>,
+    project_name: Option<String>,
+    exe_name: Option<String>,
+    description: Option<String>,
+    help_file: Option<String>,
+    form_count: u16,
+    native_code: Option<bool>,
+    objects: Vec<String>,
+}
+
+impl VbProject {
+    /// Runtime DLL the image imports, `MSVBVM60.DLL` or `MSVBVM50.DLL`
+    pub fn runtime(&self) -> &str {
+        &self.runtime
+    }
+
+    /// RVA of the `VB5!` header, `None` if the entry point does not pass one
+    pub fn header_rva(&self) -> Option<u32> {
+        self.header_rva
+    }
+
+    pub fn project_name(&self) -> Option<&str> {
+        self.project_name.as_deref()
+    }
+
+    pub fn exe_name(&self) -> Option<&str> {
+        self.exe_name.as_deref()
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn help_file(&self) -> Option<&str> {
+        self.help_file.as_deref()
+    }
+
+    pub fn form_count(&self) -> u16 {
+        self.form_count
+    }
+
+    /// Whether the project was compiled to native code rather than P-Code
+    pub fn native_code(&self) -> Option<bool> {
+        self.native_code
+    }
+
+    /// Names of the forms, modules and classes of the project, in object table order
+    pub fn objects(&self) -> &[String] {
+        &self.objects
+    }
+}
+
+/// Trace of the Borland toolchain or the VCL in an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DelphiMarker {
+    /// `PACKAGEINFO` resource listing the units linked in
+    PackageInfo,
+    /// `DVCLAL` resource holding the VCL license level
+    Dvclal,
+    /// Forms stored as binary DFM resources
+    Forms,
+    /// `CODE`, `DATA` and `BSS` sections laid out by the Borland linker
+    BorlandSections,
+    /// `.itext` section of Delphi 2009 and later
+    ItextSection,
+    /// The fixed timestamp of Borland linkers before Delphi 2006
+    BorlandTimestamp,
+}
+
+/// Form stored as a binary DFM resource
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DelphiForm {
+    resource: String,
+    class_name: String,
+    name: String,
+}
+
+impl DelphiForm {
+    /// Name of the `RT_RCDATA` resource, the upper-cased class name
+    pub fn resource(&self) -> &str {
+        &self.resource
+    }
+
+    /// Class of the form, such as `TMainForm`
+    pub fn class_name(&self) -> &str {
+        &self.class_name
+    }
+
+    /// Name of the form instance, such as `MainForm`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Delphi or C++Builder image, recognized by the markers the Borland toolchain and
+/// the VCL leave behind
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DelphiInfo {
+    markers: Vec<DelphiMarker>,
+    required_packages: Vec<String>,
+    units: Vec<String>,
+    forms: Vec<DelphiForm>,
+}
+
+impl DelphiInfo {
+    pub fn markers(&self) -> &[DelphiMarker] {
+        &self.markers
+    }
+
+    /// Runtime packages the image needs, from `PACKAGEINFO`
+    pub fn required_packages(&self) -> &[String] {
+        &self.required_packages
+    }
+
+    /// Units linked into the image, from `PACKAGEINFO`
+    pub fn units(&self) -> &[String] {
+        &self.units
+    }
+
+    pub fn forms(&self) -> &[DelphiForm] {
+        &self.forms
+    }
+}
+
+/// Compiled AutoIt script carried by an image.
+///
+/// The script itself is encrypted, so only its location is reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AutoItScript {
+    format: String,
+    offset: usize,
+    size: usize,
+    resource: bool,
+}
+
+impl AutoItScript {
+    /// Format tag following `AU3!`: `EA05` for AutoIt 3.0 to 3.2, `EA06` after
+    pub fn format(&self) -> &str {
+        &self.format
+    }
+
+    /// File offset of the marker starting the script
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Whether the script is the `SCRIPT` resource rather than appended as overlay
+    pub fn resource(&self) -> bool {
+        self.resource
+    }
+}
+
+/// Entry of the table of contents of a PyInstaller archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PyInstallerEntry {
+    name: String,
+    kind: char,
+    offset: usize,
+    size: u32,
+    uncompressed_size: u32,
+    compressed: bool,
+}
+
+impl PyInstallerEntry {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Type code: `b` binary, `z` PYZ archive, `m` module, `M` package, `s` script,
+    /// `x` data file, `d` dependency, `o` runtime option
+    pub fn kind(&self) -> char {
+        self.kind
+    }
+
+    /// File offset of the stored data
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Size of the stored data
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn uncompressed_size(&self) -> u32 {
+        self.uncompressed_size
+    }
+
+    /// Whether the stored data is a zlib stream
+    pub fn compressed(&self) -> bool {
+        self.compressed
+    }
+}
+
+/// PyInstaller CArchive appended to its bootloader
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PyInstallerArchive {
+    offset: usize,
+    size: usize,
+    python_version: u32,
+    python_library: Option<String>,
+    entries: Vec<PyInstallerEntry>,
+}
+
+impl PyInstallerArchive {
+    /// File offset where the archive starts
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Size of the archive up to the end of its cookie
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Python version the archive was built for, as major and minor
+    pub fn python_version(&self) -> (u32, u32) {
+        match self.python_version {
+            version if version >= 100 => (version / 100, version % 100),
+            version => (version / 10, version % 10),
+        }
+    }
+
+    /// Python DLL the bootloader loads, absent from PyInstaller 2.0 archives
+    pub fn python_library(&self) -> Option<&str> {
+        self.python_library.as_deref()
+    }
+
+    /// Table of contents, empty if it cannot be read
+    pub fn entries(&self) -> &[PyInstallerEntry] {
+        &self.entries
+    }
+}
+
+/// Compression of the data of an NSIS installer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NsisCompression {
+    None,
+    Deflate,
+    Bzip2,
+    Lzma,
+}
+
+impl fmt::Display for NsisCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::None => "none",
+            Self::Deflate => "deflate",
+            Self::Bzip2 => "bzip2",
+            Self::Lzma => "lzma",
+        };
+        f.write_str(name)
+    }
+}
+
+/// NSIS installer data appended to its stub, located by the first header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NsisInstaller {
+    offset: usize,
+    flags: u32,
+    header_size: u32,
+    data_size: u32,
+    compression: NsisCompression,
+    solid: bool,
+}
+
+impl NsisInstaller {
+    /// File offset of the first header, where the installer data starts
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// `FH_FLAGS_*` of the first header
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// Whether the data belongs to an uninstaller written out by an installer
+    pub fn uninstaller(&self) -> bool {
+        self.flags & NSIS_FLAG_UNINSTALL != 0
+    }
+
+    pub fn silent(&self) -> bool {
+        self.flags & NSIS_FLAG_SILENT != 0
+    }
+
+    /// Uncompressed size of the installer header
+    pub fn header_size(&self) -> u32 {
+        self.header_size
+    }
+
+    /// Size of the data from the first header on, including the trailing CRC
+    pub fn data_size(&self) -> u32 {
+        self.data_size
+    }
+
+    pub fn compression(&self) -> NsisCompression {
+        self.compression
+    }
+
+    /// Whether header and files are compressed as a single stream
+    pub fn solid(&self) -> bool {
+        self.solid
+    }
+}
+
+/// Inno Setup installer, located by the offset table of its loader
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InnoSetupInstaller {
+    loader_version: &'static str,
+    table_offset: usize,
+    setup_offset: u32,
+    data_offset: u32,
+    setup_id: Option<String>,
+}
+
+impl InnoSetupInstaller {
+    /// Earliest Inno Setup version writing this offset table layout
+    pub fn loader_version(&self) -> &'static str {
+        self.loader_version
+    }
+
+    /// File offset of the offset table
+    pub fn table_offset(&self) -> usize {
+        self.table_offset
+    }
+
+    /// File offset of the compressed setup data, which starts with the setup ID
+    pub fn setup_offset(&self) -> u32 {
+        self.setup_offset
+    }
+
+    /// File offset of the file data, zero if the files are stored outside the image
+    pub fn data_offset(&self) -> u32 {
+        self.data_offset
+    }
+
+    /// ID at the start of the setup data, such as `Inno Setup Setup Data (5.5.7) (u)`,
+    /// which names the version that compiled the installer
+    pub fn setup_id(&self) -> Option<&str> {
+        self.setup_id.as_deref()
+    }
+}
+
+/// Language runtimes and wrappers an image was recognized to be built with
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Provenance {
+    visual_basic: Option<VbProject>,
+    delphi: Option<DelphiInfo>,
+    autoit: Option<AutoItScript>,
+    pyinstaller: Option<PyInstallerArchive>,
+    nsis: Option<NsisInstaller>,
+    inno_setup: Option<InnoSetupInstaller>,
+}
+
+impl Provenance {
+    pub fn visual_basic(&self) -> Option<&VbProject> {
+        self.visual_basic.as_ref()
+    }
+
+    pub fn delphi(&self) -> Option<&DelphiInfo> {
+        self.delphi.as_ref()
+    }
+
+    pub fn autoit(&self) -> Option<&AutoItScript> {
+        self.autoit.as_ref()
+    }
+
+    pub fn pyinstaller(&self) -> Option<&PyInstallerArchive> {
+        self.pyinstaller.as_ref()
+    }
+
+    pub fn nsis(&self) -> Option<&NsisInstaller> {
+        self.nsis.as_ref()
+    }
+
+    pub fn inno_setup(&self) -> Option<&InnoSetupInstaller> {
+        self.inno_setup.as_ref()
+    }
+
+    /// Whether no runtime or wrapper was recognized
+    pub fn is_empty(&self) -> bool {
+        self.visual_basic.is_none()
+            && self.delphi.is_none()
+            && self.autoit.is_none()
+            && self.pyinstaller.is_none()
+            && self.nsis.is_none()
+            && self.inno_setup.is_none()
+    }
+}
+
+impl PortExe {
+    /// Recognizes classic runtimes from their imports, headers, sections and resources:
+    /// Visual Basic 5 and 6, and Delphi or C++Builder. Also recognizes the AutoIt and
+    /// PyInstaller wrappers and the NSIS and Inno Setup installers from the payloads
+    /// they embed. Structures that cannot be decoded are left out rather than reported
+    /// as errors.
+    pub fn provenance(&self) -> Provenance {
+        Provenance {
+            visual_basic: self.visual_basic(),
+            delphi: self.delphi(),
+            autoit: self.autoit(),
+            pyinstaller: self.pyinstaller(),
+            nsis: self.nsis(),
+            inno_setup: self.inno_setup(),
+        }
+    }
+
+    fn visual_basic(&self) -> Option<VbProject> {
+        let runtime = self
+            .imports()
+            .ok()?
+            .into_iter()
+            .map(|descriptor| descriptor.dll_name().to_string())
+            .find(|dll| {
+                dll.eq_ignore_ascii_case("MSVBVM60.DLL") || dll.eq_ignore_ascii_case("MSVBVM50.DLL")
+            })?;
+        let mut project = VbProject {
+            runtime,
+            header_rva: None,
+            project_name: None,
+            exe_name: None,
+            description: None,
+            help_file: None,
+            form_count: 0,
+            native_code: None,
+            objects: Vec::new(),
+        };
+        let header = match self.vb_header_rva() {
+            Some(header) => header,
+            None => return Some(project),
+        };
+        let data = match self.data_at_rva(header, VB_HEADER_SIZE) {
+            Some(data) if data.starts_with(VB_SIGNATURE) => data,
+            _ => return Some(project),
+        };
+        project.header_rva = Some(header);
+        let string = |field| {
+            let offset = read_u32(data, field).unwrap_or(0);
+            if offset == 0 {
+                return None;
+            }
+            c_string(self, header.checked_add(offset)?).filter(|text| !text.is_empty())
+        };
+        project.description = string(0x58);
+        project.exe_name = string(0x5C);
+        project.help_file = string(0x60);
+        project.project_name = string(0x64);
+        project.form_count = read_u16(data, 0x44).unwrap_or(0);
+
+        let project_data = read_u32(data, 0x30)
+            .and_then(|va| self.va_to_rva(va))
+            .and_then(|rva| self.data_at_rva(rva, VB_PROJECT_DATA_SIZE));
+        if let Some(project_data) = project_data {
+            project.native_code = read_u32(project_data, 0x20).map(|native| native != 0);
+            project.objects = read_u32(project_data, 0x04)
+                .and_then(|va| self.va_to_rva(va))
+                .map(|table| self.vb_objects(table))
+                .unwrap_or_default();
+        }
+        Some(project)
+    }
+
+    /// Follows the `push <header>; call ThunRTMain` every VB entry point starts with.
+    fn vb_header_rva(&self) -> Option<u32> {
+        let entry = self.optional_header()?.address_of_entry_point();
+        let code = self.data_at_rva(entry, 10)?;
+        if code[0] != 0x68 || code[5] != 0xE8 {
+            return None;
+        }
+        self.va_to_rva(read_u32(code, 1)?)
+    }
+
+    fn vb_objects(&self, table: u32) -> Vec<String> {
+        let table = match self.data_at_rva(table, VB_OBJECT_TABLE_SIZE) {
+            Some(table) => table,
+            None => return Vec::new(),
+        };
+        let count = (read_u16(table, 0x2A).unwrap_or(0) as u32).min(MAX_VB_OBJECTS);
+        let array = match read_u32(table, 0x30).and_then(|va| self.va_to_rva(va)) {
+            Some(array) => array,
+            None => return Vec::new(),
+        };
+        (0..count)
+            .map_while(|index| {
+                let descriptor = self.data_at_rva(
+                    array.checked_add(index * VB_OBJECT_DESCRIPTOR_SIZE)?,
+                    VB_OBJECT_DESCRIPTOR_SIZE,
+                )?;
+                let name = read_u32(descriptor, 0x18).and_then(|va| self.va_to_rva(va));
+                Some(name.and_then(|rva| c_string(self, rva)).unwrap_or_default())
+            })
+            .collect()
+    }
+
+    fn delphi(&self) -> Option<DelphiInfo> {
+        let mut info = DelphiInfo {
+            markers: Vec::new(),
+            required_packages: Vec::new(),
+            units: Vec::new(),
+            forms: Vec::new(),
+        };
+        let resources = self.resources().unwrap_or_default();
+        for entry in resources
+            .iter()
+            .filter(|entry| entry.type_id() == &ResourceId::Id(RT_RCDATA))
+        {
+            let name = match entry.name_id() {
+                ResourceId::Name(name) => name.to_string_lossy().into_owned(),
+                ResourceId::Id(_) => continue,
+            };
+            let data = self.resource_data(entry).unwrap_or(&[]);
+            if name == "PACKAGEINFO" {
+                push_marker(&mut info.markers, DelphiMarker::PackageInfo);
+                if let Some((required, units)) = package_info(data) {
+                    info.required_packages = required;
+                    info.units = units;
+                }
+            } else if name == "DVCLAL" {
+                push_marker(&mut info.markers, DelphiMarker::Dvclal);
+            } else if let Some(form) = form(entry, data) {
+                push_marker(&mut info.markers, DelphiMarker::Forms);
+                info.forms.push(form);
+            }
+        }
+
+        let section = |wanted: &str| {
+            self.section_headers()
+                .iter()
+                .any(|section| section.name().into_value().as_bytes() == wanted.as_bytes())
+        };
+        if section("CODE") && section("DATA") && section("BSS") {
+            push_marker(&mut info.markers, DelphiMarker::BorlandSections);
+        }
+        if section(".itext") {
+            push_marker(&mut info.markers, DelphiMarker::ItextSection);
+        }
+        if self.file_header().time_date_stamp().into_value() == BORLAND_TIME_DATE_STAMP {
+            push_marker(&mut info.markers, DelphiMarker::BorlandTimestamp);
+        }
+        (!info.markers.is_empty()).then(|| info)
+    }
+
+    /// Finds the script in the `SCRIPT` resource or, for AutoIt before 3.3, in the
+    /// overlay.
+    fn autoit(&self) -> Option<AutoItScript> {
+        let resource = self
+            .resources()
+            .unwrap_or_default()
+            .into_iter()
+            .find(|entry| {
+                entry.type_id() == &ResourceId::Id(RT_RCDATA)
+                    && match entry.name_id() {
+                        ResourceId::Name(name) => name.eq_ignore_ascii_case(AUTOIT_RESOURCE),
+                        ResourceId::Id(_) => false,
+                    }
+            });
+        if let Some(entry) = resource {
+            let offset = self.rva_to_offset(entry.data_rva())?;
+            let data = self.resource_data(&entry)?;
+            let format = autoit_format(data)?;
+            return Some(AutoItScript {
+                format,
+                offset,
+                size: data.len(),
+                resource: true,
+            });
+        }
+
+        let overlay = self.overlay_offset();
+        let data = &self.data()[overlay..];
+        let start = find(data, &AUTOIT_MAGIC)?;
+        Some(AutoItScript {
+            format: autoit_format(&data[start..])?,
+            offset: overlay + start,
+            size: data.len() - start,
+            resource: false,
+        })
+    }
+
+    /// Decodes the archive closed by the last cookie in the overlay, which an
+    /// Authenticode signature may follow.
+    fn pyinstaller(&self) -> Option<PyInstallerArchive> {
+        let overlay = self.overlay_offset();
+        let data = self.data();
+        let cookie = overlay + rfind(&data[overlay..], PYINSTALLER_MAGIC)?;
+        let cookie_size = if data.len() - cookie >= PYINSTALLER_COOKIE_SIZE_PYLIB {
+            PYINSTALLER_COOKIE_SIZE_PYLIB
+        } else {
+            PYINSTALLER_COOKIE_SIZE
+        };
+        let size = read_be_u32(data, cookie + 8)? as usize;
+        let toc_offset = read_be_u32(data, cookie + 12)? as usize;
+        let toc_size = read_be_u32(data, cookie + 16)? as usize;
+        let python_version = read_be_u32(data, cookie + 20)?;
+        let offset = (cookie + cookie_size).checked_sub(size)?;
+        let python_library = data
+            .get(cookie + PYINSTALLER_COOKIE_SIZE..cookie + cookie_size)
+            .and_then(|name| {
+                let end = name
+                    .iter()
+                    .position(|&byte| byte == 0)
+                    .unwrap_or(name.len());
+                (end > 0).then(|| String::from_utf8_lossy(&name[..end]).into_owned())
+            });
+        let entries = offset
+            .checked_add(toc_offset)
+            .and_then(|start| slice_at(data, start, toc_size))
+            .map(|toc| pyinstaller_entries(toc, offset))
+            .unwrap_or_default();
+        Some(PyInstallerArchive {
+            offset,
+            size,
+            python_version,
+            python_library,
+            entries,
+        })
+    }
+
+    /// Looks for the first header where the NSIS stub does: at 512-byte boundaries,
+    /// here only those in the overlay.
+    fn nsis(&self) -> Option<NsisInstaller> {
+        let data = self.data();
+        let start = (self.overlay_offset() + NSIS_ALIGNMENT - 1) / NSIS_ALIGNMENT * NSIS_ALIGNMENT;
+        let offset = (start..data.len())
+            .step_by(NSIS_ALIGNMENT)
+            .find(|&offset| data[offset..].get(4..20) == Some(NSIS_SIGNATURE))?;
+        let header_size = read_u32(data, offset + 20)?;
+        let compressed = &data[(offset + NSIS_FIRST_HEADER_SIZE).min(data.len())..];
+        let (compression, solid) = match read_u32(compressed, 0) {
+            Some(size) if size & 0x8000_0000 != 0 => (nsis_compression(&compressed[4..]), false),
+            Some(size) if size == header_size => (NsisCompression::None, false),
+            _ => (nsis_compression(compressed), true),
+        };
+        Some(NsisInstaller {
+            offset,
+            flags: read_u32(data, offset)?,
+            header_size,
+            data_size: read_u32(data, offset + 24)?,
+            compression,
+            solid,
+        })
+    }
+
+    /// Reads the loader's offset table from resource 11111 or, for loaders before
+    /// 5.1.5, from the offset the DOS header points to.
+    fn inno_setup(&self) -> Option<InnoSetupInstaller> {
+        let data = self.data();
+        let resource = self
+            .resources()
+            .unwrap_or_default()
+            .into_iter()
+            .find(|entry| {
+                entry.type_id() == &ResourceId::Id(RT_RCDATA)
+                    && entry.name_id() == &ResourceId::Id(INNO_OFFSET_TABLE_RESOURCE)
+            });
+        let table_offset = match resource {
+            Some(entry) => self.rva_to_offset(entry.data_rva())?,
+            None => match read_u32(data, INNO_LEGACY_POINTER) {
+                Some(INNO_LEGACY_SIGNATURE) => read_u32(data, INNO_LEGACY_POINTER + 4)? as usize,
+                _ => return None,
+            },
+        };
+        let table = data.get(table_offset..)?;
+        let &(_, loader_version, setup) = INNO_OFFSET_TABLES
+            .iter()
+            .find(|(id, _, _)| table.starts_with(&id[..]))?;
+        let setup_offset = read_u32(table, 12 + setup)?;
+        let data_offset = read_u32(table, 12 + setup + 4)?;
+        let setup_id = slice_at(data, setup_offset as usize, INNO_SETUP_ID_SIZE).and_then(|id| {
+            let end = id.iter().position(|&byte| byte == 0).unwrap_or(id.len());
+            (end > 0).then(|| String::from_utf8_lossy(&id[..end]).into_owned())
+        });
+        Some(InnoSetupInstaller {
+            loader_version,
+            table_offset,
+            setup_offset,
+            data_offset,
+            setup_id,
+        })
+    }
+
+    fn va_to_rva(&self, va: u32) -> Option<u32> {
+        let image_base = self.optional_header()?.image_base();
+        (va as u64).checked_sub(image_base).map(|rva| rva as u32)
+    }
+}
+
+fn push_marker(markers: &mut Vec<DelphiMarker>, marker: DelphiMarker) {
+    if !markers.contains(&marker) {
+        markers.push(marker);
+    }
+}
+
+/// Reads the ASCII string at `rva` up to its NUL terminator.
+fn c_string(pe: &PortExe, rva: u32) -> Option<String> {
+    let offset = pe.rva_to_offset(rva)?;
+    let data = pe.data().get(offset..)?;
+    let data = &data[..data.len().min(MAX_NAME_LENGTH)];
+    let end = data.iter().position(|&byte| byte == 0)?;
+    Some(String::from_utf8_lossy(&data[..end]).into_owned())
+}
+
+/// Decodes a `PACKAGEINFO` resource: flags, the required packages as a hash byte and
+/// a NUL-terminated name each, then the contained units as flags, a hash byte and a
+/// NUL-terminated name each.
+fn package_info(data: &[u8]) -> Option<(Vec<String>, Vec<String>)> {
+    let mut offset = 4;
+    let mut list = |prefix: usize| -> Option<Vec<String>> {
+        let count = read_u32(data, offset)?.min(MAX_PACKAGE_UNITS);
+        offset += 4;
+        let mut names = Vec::new();
+        for _ in 0..count {
+            let name = data.get(offset + prefix..)?;
+            let end = name.iter().position(|&byte| byte == 0)?;
+            names.push(String::from_utf8_lossy(&name[..end]).into_owned());
+            offset += prefix + end + 1;
+        }
+        Some(names)
+    };
+    let required = list(1)?;
+    let units = list(2)?;
+    Some((required, units))
+}
+
+/// Reads the class and instance name from the start of a binary DFM.
+fn form(entry: &ResourceEntry, data: &[u8]) -> Option<DelphiForm> {
+    let rest = data.strip_prefix(FORM_SIGNATURE)?;
+    let (class_name, rest) = short_string(rest)?;
+    let (name, _) = short_string(rest)?;
+    let resource = match entry.name_id() {
+        ResourceId::Name(name) => name.to_string_lossy().into_owned(),
+        ResourceId::Id(id) => id.to_string(),
+    };
+    Some(DelphiForm {
+        resource,
+        class_name,
+        name,
+    })
+}
+
+/// Format tag of the AutoIt script starting at `data`
+fn autoit_format(data: &[u8]) -> Option<String> {
+    let rest = data.strip_prefix(&AUTOIT_MAGIC[..])?;
+    let tag = rest.strip_prefix(AUTOIT_SIGNATURE)?.get(..4)?;
+    Some(String::from_utf8_lossy(tag).into_owned())
+}
+
+/// Decodes the TOC entries: entry length, offset, stored size and uncompressed size
+/// as big-endian 32-bit values, a compression flag, a type code and a NUL-padded name.
+fn pyinstaller_entries(toc: &[u8], archive: usize) -> Vec<PyInstallerEntry> {
+    let mut entries = Vec::new();
+    let mut position = 0;
+    while position < toc.len() && entries.len() < MAX_ARCHIVE_ENTRIES {
+        let length = match read_be_u32(toc, position) {
+            Some(length) if length as usize >= PYINSTALLER_ENTRY_HEADER_SIZE => length as usize,
+            _ => break,
+        };
+        let entry = match slice_at(toc, position, length) {
+            Some(entry) => entry,
+            None => break,
+        };
+        let name = &entry[PYINSTALLER_ENTRY_HEADER_SIZE..];
+        let end = name
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(name.len());
+        entries.push(PyInstallerEntry {
+            name: String::from_utf8_lossy(&name[..end]).into_owned(),
+            kind: entry[17] as char,
+            offset: archive.saturating_add(read_be_u32(entry, 4).unwrap_or(0) as usize),
+            size: read_be_u32(entry, 8).unwrap_or(0),
+            uncompressed_size: read_be_u32(entry, 12).unwrap_or(0),
+            compressed: entry[16] != 0,
+        });
+        position += length;
+    }
+    entries
+}
+
+/// Guesses the compression method from the start of a stream, as the NSIS stub does
+/// not record it: LZMA properties, the bzip2 block size digit, or deflate otherwise.
+fn nsis_compression(stream: &[u8]) -> NsisCompression {
+    match stream {
+        [0x5D, 0, 0, ..] => NsisCompression::Lzma,
+        [b'1', level, ..] if *level < 14 => NsisCompression::Bzip2,
+        _ => NsisCompression::Deflate,
+    }
+}
+
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn rfind(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len())
+        .rposition(|window| window == needle)
+}
+
+/// Reads a length-prefixed Pascal string, returning it with what follows.
+fn short_string(data: &[u8]) -> Option<(String, &[u8])> {
+    let length = *data.first()? as usize;
+    let text = data.get(1..1 + length)?;
+    Some((
+        String::from_utf8_lossy(text).into_owned(),
+        &data[1 + length..],
+    ))
+}
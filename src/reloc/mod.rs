@@ -0,0 +1,96 @@
+use crate::optional_header::IMAGE_DIRECTORY_ENTRY_BASERELOC;
+use crate::port_exe::PortExe;
+use crate::{read_u16, read_u32};
+use std::io;
+
+/// Padding entry that aligns a block to 32 bits
+pub const IMAGE_REL_BASED_ABSOLUTE: u8 = 0;
+pub const IMAGE_REL_BASED_HIGH: u8 = 1;
+pub const IMAGE_REL_BASED_LOW: u8 = 2;
+/// 32-bit address, used by PE32 images
+pub const IMAGE_REL_BASED_HIGHLOW: u8 = 3;
+pub const IMAGE_REL_BASED_HIGHADJ: u8 = 4;
+/// 64-bit address, used by PE32+ images
+pub const IMAGE_REL_BASED_DIR64: u8 = 10;
+
+/// Upper bound on the relocation entries read from the directory
+pub const MAX_BASE_RELOCATIONS: usize = 0x100000;
+
+/// Location the loader patches when the image is not loaded at its preferred base
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BaseRelocation {
+    rva: u32,
+    kind: u8,
+}
+
+impl BaseRelocation {
+    /// Address of the patched value
+    pub fn rva(&self) -> u32 {
+        self.rva
+    }
+
+    /// `IMAGE_REL_BASED_*` type
+    pub fn kind(&self) -> u8 {
+        self.kind
+    }
+
+    /// Size of the patched value, for the types that hold a whole address
+    pub fn size(&self) -> Option<u32> {
+        match self.kind {
+            IMAGE_REL_BASED_HIGHLOW => Some(4),
+            IMAGE_REL_BASED_DIR64 => Some(8),
+            _ => None,
+        }
+    }
+}
+
+impl PortExe {
+    /// Reads the base relocation blocks, skipping padding entries.
+    pub fn base_relocations(&self) -> io::Result<Vec<BaseRelocation>> {
+        let directory = match self
+            .optional_header()
+            .and_then(|h| h.data_directory(IMAGE_DIRECTORY_ENTRY_BASERELOC))
+        {
+            Some(directory) => directory,
+            None => return Ok(Vec::new()),
+        };
+        let rva = directory.virtual_address().into_value();
+        let size = directory.size().into_value();
+        if rva == 0 || size == 0 {
+            return Ok(Vec::new());
+        }
+        let data = self
+            .data_at_rva(rva, size)
+            .ok_or_else(|| invalid_data("base relocation directory is not backed by file data"))?;
+
+        let mut relocations = Vec::new();
+        let mut offset = 0;
+        while offset + 8 <= data.len() {
+            let page = read_u32(data, offset).unwrap_or(0);
+            let block_size = read_u32(data, offset + 4).unwrap_or(0) as usize;
+            if block_size < 8 || offset + block_size > data.len() {
+                return Err(invalid_data("malformed base relocation block"));
+            }
+            for entry in (offset + 8..offset + block_size - 1).step_by(2) {
+                let entry = read_u16(data, entry).unwrap_or(0);
+                let kind = (entry >> 12) as u8;
+                if kind == IMAGE_REL_BASED_ABSOLUTE {
+                    continue;
+                }
+                if relocations.len() == MAX_BASE_RELOCATIONS {
+                    return Err(invalid_data("too many base relocations"));
+                }
+                relocations.push(BaseRelocation {
+                    rva: page.wrapping_add(u32::from(entry & 0x0FFF)),
+                    kind,
+                });
+            }
+            offset += block_size;
+        }
+        Ok(relocations)
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,459 @@
+use crate::debug::{
+    CODEVIEW_PDB70_SIGNATURE, IMAGE_DEBUG_TYPE_CODEVIEW, IMAGE_DEBUG_TYPE_REPRO,
+    IMAGE_SIZEOF_DEBUG_DIRECTORY,
+};
+use crate::optional_header::{
+    DataDirectoryType, IMAGE_DIRECTORY_ENTRY_DEBUG, IMAGE_DIRECTORY_ENTRY_EXPORT,
+    IMAGE_DIRECTORY_ENTRY_SECURITY, IMAGE_NUMBEROF_DIRECTORY_ENTRIES,
+};
+use crate::pe_string::PeString;
+use crate::port_exe::PortExe;
+use crate::read_u32;
+use crate::rich::rich_header_range;
+use crate::section_header::IMAGE_SIZEOF_SECTION_HEADER;
+use std::fmt;
+use std::io;
+use std::ops::Range;
+
+/// Size of the DOS header, up to and including `e_lfanew`
+const DOS_HEADER_SIZE: usize = 0x40;
+/// Size of the PE signature and the file header
+const PE_HEADERS_SIZE: usize = 4 + 20;
+
+/// Field a build sets differently every time, left out of [`compare_builds`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum VolatileField {
+    /// `TimeDateStamp` of the file header
+    TimeDateStamp,
+    /// `CheckSum` of the optional header
+    CheckSum,
+    /// Rich header, which the linker derives from the objects and tools of the build
+    RichHeader,
+    /// `TimeDateStamp` of the export directory
+    ExportTimeDateStamp,
+    /// `TimeDateStamp` of a debug directory entry
+    DebugTimeDateStamp,
+    /// GUID and age of an `RSDS` CodeView record, which key the PDB of the build
+    DebugGuid,
+    /// Hash payload of an `IMAGE_DEBUG_TYPE_REPRO` entry
+    ReproHash,
+    /// Security entry of the data directories
+    SecurityDirectory,
+    /// Attribute certificate table holding the signature
+    Signature,
+}
+
+impl fmt::Display for VolatileField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::TimeDateStamp => "TimeDateStamp",
+            Self::CheckSum => "CheckSum",
+            Self::RichHeader => "Rich header",
+            Self::ExportTimeDateStamp => "export TimeDateStamp",
+            Self::DebugTimeDateStamp => "debug TimeDateStamp",
+            Self::DebugGuid => "PDB GUID and age",
+            Self::ReproHash => "repro hash",
+            Self::SecurityDirectory => "Security directory entry",
+            Self::Signature => "signature",
+        })
+    }
+}
+
+/// File range of a [`VolatileField`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolatileRange {
+    field: VolatileField,
+    range: Range<usize>,
+}
+
+impl VolatileRange {
+    pub fn field(&self) -> VolatileField {
+        self.field
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+}
+
+/// Structure of the file a byte belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FileRegion {
+    DosHeader,
+    /// DOS stub program and anything else before the PE signature
+    DosStub,
+    RichHeader,
+    /// PE signature and file header
+    FileHeader,
+    /// Optional header fields before the data directories
+    OptionalHeader,
+    DataDirectories,
+    SectionTable,
+    /// Rest of the headers, up to `SizeOfHeaders`
+    HeaderPadding,
+    /// Raw data of a section, and the data directory there if any
+    Section {
+        section: PeString,
+        directory: Option<DataDirectoryType>,
+    },
+    /// Raw data of a section past its `VirtualSize`, which is never mapped
+    SectionPadding {
+        section: PeString,
+    },
+    /// Data before the overlay that the headers and sections do not cover, such as
+    /// COFF symbols and debug payloads stored outside sections
+    Unmapped,
+    /// Data after the image, see [`PortExe::overlay_offset`]
+    Overlay,
+}
+
+impl fmt::Display for FileRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DosHeader => write!(f, "DOS header"),
+            Self::DosStub => write!(f, "DOS stub"),
+            Self::RichHeader => write!(f, "Rich header"),
+            Self::FileHeader => write!(f, "file header"),
+            Self::OptionalHeader => write!(f, "optional header"),
+            Self::DataDirectories => write!(f, "data directories"),
+            Self::SectionTable => write!(f, "section table"),
+            Self::HeaderPadding => write!(f, "header padding"),
+            Self::Section {
+                section,
+                directory: Some(directory),
+            } => write!(
+                f,
+                "{:?} directory in {}",
+                directory,
+                section.to_string_lossy()
+            ),
+            Self::Section { section, .. } => write!(f, "{}", section.to_string_lossy()),
+            Self::SectionPadding { section } => {
+                write!(f, "padding of {}", section.to_string_lossy())
+            }
+            Self::Unmapped => write!(f, "data outside the sections"),
+            Self::Overlay => write!(f, "overlay"),
+        }
+    }
+}
+
+/// Run of bytes that differs between two builds once the volatile fields are masked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedRange {
+    range: Range<usize>,
+    region: FileRegion,
+    zero_before: bool,
+    zero_after: bool,
+}
+
+impl ChangedRange {
+    /// File range, in the earlier build as long as it is that long
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// Structure the range lies in, in the build that has its bytes
+    pub fn region(&self) -> &FileRegion {
+        &self.region
+    }
+
+    /// Whether the range is header padding, section padding or trailing data where one
+    /// of the builds has only zeros or ends: the builds differ by null padding rather
+    /// than by content
+    pub fn is_zero_padding(&self) -> bool {
+        let padding = matches!(
+            self.region,
+            FileRegion::HeaderPadding
+                | FileRegion::SectionPadding { .. }
+                | FileRegion::Unmapped
+                | FileRegion::Overlay
+        );
+        padding && (self.zero_before || self.zero_after)
+    }
+}
+
+/// Result of [`compare_builds`]
+#[derive(Debug, Clone, Default)]
+pub struct BuildComparison {
+    before_size: usize,
+    after_size: usize,
+    ignored: Vec<VolatileField>,
+    changes: Vec<ChangedRange>,
+}
+
+impl BuildComparison {
+    /// Size of the earlier build, without a signature that ends it
+    pub fn before_size(&self) -> usize {
+        self.before_size
+    }
+
+    /// Size of the later build, without a signature that ends it
+    pub fn after_size(&self) -> usize {
+        self.after_size
+    }
+
+    /// Volatile fields masked in either build, in [`PortExe::volatile_ranges`] order
+    pub fn ignored(&self) -> &[VolatileField] {
+        &self.ignored
+    }
+
+    /// Differing ranges, by offset
+    pub fn changes(&self) -> &[ChangedRange] {
+        &self.changes
+    }
+
+    /// Whether the builds are identical apart from the volatile fields
+    pub fn is_reproducible(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Whether every difference is null padding, as when only the alignment of the
+    /// builds differs
+    pub fn differs_only_in_padding(&self) -> bool {
+        self.changes.iter().all(ChangedRange::is_zero_padding)
+    }
+}
+
+impl PortExe {
+    /// File ranges of the fields builds set differently every time: timestamps, the
+    /// checksum, the Rich header, the PDB GUID, the repro hash and the signature, by
+    /// offset.
+    pub fn volatile_ranges(&self) -> io::Result<Vec<VolatileRange>> {
+        let header = self
+            .optional_header()
+            .ok_or_else(|| invalid_input("object files are not builds of an image"))?;
+        let data = self.data();
+        let mut ranges = Vec::new();
+        let mut push = |field, offset: usize, size: usize| {
+            let end = offset.saturating_add(size).min(data.len());
+            if offset < end {
+                ranges.push(VolatileRange {
+                    field,
+                    range: offset..end,
+                });
+            }
+        };
+
+        if let Some(rich) = rich_header_range(data) {
+            push(VolatileField::RichHeader, rich.start, rich.len());
+        }
+        let timestamp = self.file_header().time_date_stamp().offset() as usize;
+        push(VolatileField::TimeDateStamp, timestamp, 4);
+        push(VolatileField::CheckSum, checksum_offset(timestamp), 4);
+        if let Some(directory) = header.data_directory(IMAGE_DIRECTORY_ENTRY_SECURITY) {
+            let entry = directory.virtual_address().offset() as usize;
+            let offset = directory.virtual_address().into_value() as usize;
+            let size = directory.size().into_value() as usize;
+            if offset != 0 || size != 0 {
+                push(VolatileField::SecurityDirectory, entry, 8);
+            }
+            if offset != 0 {
+                push(VolatileField::Signature, offset, size);
+            }
+        }
+        if let Some(offset) = header
+            .data_directory(IMAGE_DIRECTORY_ENTRY_EXPORT)
+            .map(|directory| directory.virtual_address().into_value())
+            .filter(|&rva| rva != 0)
+            .and_then(|rva| self.rva_to_offset(rva))
+        {
+            push(VolatileField::ExportTimeDateStamp, offset + 4, 4);
+        }
+        let debug = header
+            .data_directory(IMAGE_DIRECTORY_ENTRY_DEBUG)
+            .map(|directory| directory.virtual_address().into_value())
+            .filter(|&rva| rva != 0)
+            .and_then(|rva| self.rva_to_offset(rva));
+        if let Some(directory) = debug {
+            for (index, entry) in self.debug_directory()?.iter().enumerate() {
+                let offset = directory + index * IMAGE_SIZEOF_DEBUG_DIRECTORY;
+                push(VolatileField::DebugTimeDateStamp, offset + 4, 4);
+                let payload = entry.pointer_to_raw_data() as usize;
+                let size = entry.size_of_data() as usize;
+                match entry.debug_type() {
+                    IMAGE_DEBUG_TYPE_CODEVIEW
+                        if self
+                            .debug_data(entry)
+                            .and_then(|record| read_u32(record, 0))
+                            == Some(CODEVIEW_PDB70_SIGNATURE) =>
+                    {
+                        push(VolatileField::DebugGuid, payload + 4, 20);
+                    }
+                    IMAGE_DEBUG_TYPE_REPRO => push(VolatileField::ReproHash, payload, size),
+                    _ => {}
+                }
+            }
+        }
+        ranges.sort_by_key(|range| range.range.start);
+        Ok(ranges)
+    }
+
+    /// Structure of the file the byte at `offset` belongs to.
+    pub fn file_region(&self, offset: usize) -> FileRegion {
+        let data = self.data();
+        let pe_header = read_u32(data, 0x3C).map_or(DOS_HEADER_SIZE, |offset| offset as usize);
+        let optional_header = pe_header + PE_HEADERS_SIZE;
+        let section_table =
+            optional_header + self.file_header().size_of_optional_header().into_value() as usize;
+        let section_table_end =
+            section_table + self.section_headers().len() * IMAGE_SIZEOF_SECTION_HEADER as usize;
+        let directories = self
+            .optional_header()
+            .map(|header| header.data_directories())
+            .unwrap_or_default();
+        let directories_start = directories.first().map_or(section_table, |directory| {
+            directory.virtual_address().offset() as usize
+        });
+        let size_of_headers = self.optional_header().map_or(section_table_end, |header| {
+            header.size_of_headers() as usize
+        });
+
+        if offset < DOS_HEADER_SIZE {
+            return FileRegion::DosHeader;
+        }
+        if offset < pe_header {
+            return match rich_header_range(data) {
+                Some(rich) if rich.contains(&offset) => FileRegion::RichHeader,
+                _ => FileRegion::DosStub,
+            };
+        }
+        if offset < optional_header {
+            return FileRegion::FileHeader;
+        }
+        if offset < directories_start {
+            return FileRegion::OptionalHeader;
+        }
+        if offset < section_table {
+            return FileRegion::DataDirectories;
+        }
+        if offset < section_table_end {
+            return FileRegion::SectionTable;
+        }
+        if offset < size_of_headers {
+            return FileRegion::HeaderPadding;
+        }
+
+        for section in self.section_headers() {
+            let start = section.pointer_to_raw_data().into_value() as usize;
+            let size = section.size_of_raw_data().into_value() as usize;
+            if offset < start || offset - start >= size {
+                continue;
+            }
+            let delta = offset - start;
+            let name = self.section_name(section);
+            let virtual_size = section.virtual_size().into_value() as usize;
+            if virtual_size != 0 && delta >= virtual_size {
+                return FileRegion::SectionPadding { section: name };
+            }
+            let rva = section
+                .virtual_address()
+                .into_value()
+                .wrapping_add(delta as u32);
+            let directory = directories
+                .iter()
+                .take(IMAGE_NUMBEROF_DIRECTORY_ENTRIES as usize)
+                .filter(|directory| directory.directory_type() != DataDirectoryType::Security)
+                .find(|directory| {
+                    let start = directory.virtual_address().into_value();
+                    let size = directory.size().into_value();
+                    start != 0 && rva >= start && rva - start < size
+                })
+                .map(|directory| directory.directory_type());
+            return FileRegion::Section {
+                section: name,
+                directory,
+            };
+        }
+        if offset < self.overlay_offset() {
+            FileRegion::Unmapped
+        } else {
+            FileRegion::Overlay
+        }
+    }
+}
+
+/// Compares two builds of an image byte by byte, ignoring their
+/// [`PortExe::volatile_ranges`], and maps each differing run of bytes to the structure
+/// it lies in.
+///
+/// A signature that ends a file is dropped before the comparison, so a signed and an
+/// unsigned build of the same sources compare equal. Bytes past the end of the shorter
+/// build count as differing.
+pub fn compare_builds(before: &PortExe, after: &PortExe) -> io::Result<BuildComparison> {
+    let before_ranges = before.volatile_ranges()?;
+    let after_ranges = after.volatile_ranges()?;
+    let before_data = masked(before, &before_ranges);
+    let after_data = masked(after, &after_ranges);
+
+    let mut ignored: Vec<VolatileField> = Vec::new();
+    for range in before_ranges.iter().chain(&after_ranges) {
+        if !ignored.contains(&range.field) {
+            ignored.push(range.field);
+        }
+    }
+
+    let mut changes: Vec<ChangedRange> = Vec::new();
+    for offset in 0..before_data.len().max(after_data.len()) {
+        let old = before_data.get(offset);
+        let new = after_data.get(offset);
+        if old == new {
+            continue;
+        }
+        let region = if offset < before_data.len() {
+            before.file_region(offset)
+        } else {
+            after.file_region(offset)
+        };
+        let zero_before = old.map_or(true, |&byte| byte == 0);
+        let zero_after = new.map_or(true, |&byte| byte == 0);
+        match changes.last_mut() {
+            Some(last) if last.range.end == offset && last.region == region => {
+                last.range.end += 1;
+                last.zero_before &= zero_before;
+                last.zero_after &= zero_after;
+            }
+            _ => changes.push(ChangedRange {
+                range: offset..offset + 1,
+                region,
+                zero_before,
+                zero_after,
+            }),
+        }
+    }
+    Ok(BuildComparison {
+        before_size: before_data.len(),
+        after_size: after_data.len(),
+        ignored,
+        changes,
+    })
+}
+
+/// Data of `pe` with the volatile ranges zeroed and a signature ending the file cut off
+fn masked(pe: &PortExe, ranges: &[VolatileRange]) -> Vec<u8> {
+    let mut data = pe.data().to_vec();
+    for range in ranges {
+        if range.field == VolatileField::Signature && range.range.end == data.len() {
+            data.truncate(range.range.start);
+            break;
+        }
+    }
+    for range in ranges {
+        let end = range.range.end.min(data.len());
+        if range.range.start < end {
+            data[range.range.start..end].fill(0);
+        }
+    }
+    data
+}
+
+/// Offset of `CheckSum` from that of the file header `TimeDateStamp`: the rest of the
+/// file header, then 64 bytes into the optional header in both PE32 and PE32+
+fn checksum_offset(timestamp: usize) -> usize {
+    timestamp + 16 + 64
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
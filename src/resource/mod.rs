@@ -0,0 +1,476 @@
+use crate::decompress::{decompress, detect_compression, CompressionFormat, Payload};
+use crate::endian::{utf16_units, ByteOrder};
+use crate::optional_header::IMAGE_DIRECTORY_ENTRY_RESOURCE;
+use crate::pe_string::PeString;
+use crate::port_exe::PortExe;
+use crate::{read_u16, read_u32};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+
+pub const RT_CURSOR: u16 = 1;
+pub const RT_BITMAP: u16 = 2;
+pub const RT_ICON: u16 = 3;
+pub const RT_MENU: u16 = 4;
+pub const RT_DIALOG: u16 = 5;
+pub const RT_STRING: u16 = 6;
+pub const RT_FONTDIR: u16 = 7;
+pub const RT_FONT: u16 = 8;
+pub const RT_ACCELERATOR: u16 = 9;
+pub const RT_RCDATA: u16 = 10;
+pub const RT_MESSAGETABLE: u16 = 11;
+pub const RT_GROUP_CURSOR: u16 = 12;
+pub const RT_GROUP_ICON: u16 = 14;
+pub const RT_VERSION: u16 = 16;
+pub const RT_DLGINCLUDE: u16 = 17;
+pub const RT_PLUGPLAY: u16 = 19;
+pub const RT_VXD: u16 = 20;
+pub const RT_ANICURSOR: u16 = 21;
+pub const RT_ANIICON: u16 = 22;
+pub const RT_HTML: u16 = 23;
+pub const RT_MANIFEST: u16 = 24;
+
+const IMAGE_RESOURCE_NAME_IS_STRING: u32 = 0x80000000;
+const IMAGE_RESOURCE_DATA_IS_DIRECTORY: u32 = 0x80000000;
+const IMAGE_SIZEOF_RESOURCE_DIRECTORY: usize = 16;
+const IMAGE_SIZEOF_RESOURCE_DIRECTORY_ENTRY: usize = 8;
+const IMAGE_SIZEOF_RESOURCE_DATA_ENTRY: usize = 16;
+const STRINGS_PER_TABLE: u16 = 16;
+/// Default budget of directory entries, far beyond what resource compilers produce
+pub const DEFAULT_MAX_RESOURCE_ENTRIES: usize = 0x40000;
+/// Highest string table number, whose strings end at ID 65535
+const MAX_STRING_TABLE: u16 = 4096;
+
+/// Identifier of a resource directory entry: either numeric or a UTF-16 name
+///
+/// Identifiers order the way [`PortExe::resource_directory`] walks them: numeric IDs
+/// ascending, then names by UTF-16 code unit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResourceId {
+    Id(u16),
+    Name(PeString),
+}
+
+impl Ord for ResourceId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Id(a), Self::Id(b)) => a.cmp(b),
+            (Self::Id(_), Self::Name(_)) => Ordering::Less,
+            (Self::Name(_), Self::Id(_)) => Ordering::Greater,
+            (Self::Name(a), Self::Name(b)) => utf16_units(a.as_bytes(), ByteOrder::Little)
+                .cmp(&utf16_units(b.as_bytes(), ByteOrder::Little)),
+        }
+    }
+}
+
+impl PartialOrd for ResourceId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Leaf of the resource tree (type / name / language)
+#[derive(Debug, Clone)]
+pub struct ResourceEntry {
+    type_id: ResourceId,
+    name_id: ResourceId,
+    language: ResourceId,
+    data_rva: u32,
+    size: u32,
+    code_page: u32,
+    entry_offset: usize,
+}
+
+impl ResourceEntry {
+    pub fn type_id(&self) -> &ResourceId {
+        &self.type_id
+    }
+
+    pub fn name_id(&self) -> &ResourceId {
+        &self.name_id
+    }
+
+    pub fn language(&self) -> &ResourceId {
+        &self.language
+    }
+
+    /// RVA of the resource data
+    pub fn data_rva(&self) -> u32 {
+        self.data_rva
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn code_page(&self) -> u32 {
+        self.code_page
+    }
+
+    /// File offset of the `IMAGE_RESOURCE_DATA_ENTRY`
+    pub(crate) fn entry_offset(&self) -> usize {
+        self.entry_offset
+    }
+}
+
+/// Bounds of [`PortExe::resource_directory`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// Directory tables followed from the root; the loader uses three: type, name and
+    /// language
+    pub max_depth: usize,
+    /// Directory entries read before the walk gives up
+    pub max_entries: usize,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 3,
+            max_entries: DEFAULT_MAX_RESOURCE_ENTRIES,
+        }
+    }
+}
+
+/// Structural problem [`PortExe::resource_directory`] skipped.
+///
+/// Offsets are relative to the start of the resource directory, as stored in its entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ResourceWarning {
+    /// Subdirectory that is also one of its own ancestors
+    Cycle { offset: u32 },
+    /// Subdirectory an earlier entry already led to
+    SharedDirectory { offset: u32 },
+    /// Subdirectory beyond [`ResourceLimits::max_depth`]
+    TooDeep { offset: u32 },
+    /// Data entry at the type or name level, which the loader never reads
+    MisplacedData { offset: u32 },
+    /// [`ResourceLimits::max_entries`] were read; the rest of the tree was not walked
+    EntryBudget,
+}
+
+impl fmt::Display for ResourceWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cycle { offset } => write!(f, "directory at {:#x} contains itself", offset),
+            Self::SharedDirectory { offset } => {
+                write!(f, "directory at {:#x} is referenced more than once", offset)
+            }
+            Self::TooDeep { offset } => {
+                write!(f, "directory at {:#x} is nested too deeply", offset)
+            }
+            Self::MisplacedData { offset } => {
+                write!(
+                    f,
+                    "data entry at {:#x} is outside the language level",
+                    offset
+                )
+            }
+            Self::EntryBudget => f.write_str("too many directory entries"),
+        }
+    }
+}
+
+/// Result of [`PortExe::resource_directory`]
+#[derive(Debug, Clone, Default)]
+pub struct ResourceDirectory {
+    entries: Vec<ResourceEntry>,
+    warnings: Vec<ResourceWarning>,
+    end: usize,
+}
+
+impl ResourceDirectory {
+    /// Data entries in the order [`PortExe::resource_directory`] walks them
+    pub fn entries(&self) -> &[ResourceEntry] {
+        &self.entries
+    }
+
+    pub fn into_entries(self) -> Vec<ResourceEntry> {
+        self.entries
+    }
+
+    pub fn warnings(&self) -> &[ResourceWarning] {
+        &self.warnings
+    }
+
+    /// File offset where the directory tables, names and data entries end
+    pub(crate) fn metadata_end(&self) -> usize {
+        self.end
+    }
+}
+
+/// String of an `RT_STRING` table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StringResource {
+    id: u16,
+    language: u16,
+    value: String,
+    rva: u32,
+}
+
+impl StringResource {
+    /// String ID, as passed to `LoadString`
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    pub fn language(&self) -> u16 {
+        self.language
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Address of the first UTF-16 unit of the string, after its length
+    pub fn rva(&self) -> u32 {
+        self.rva
+    }
+}
+
+/// Resource data as stored in the image together with its decompressed form, if any
+#[derive(Debug)]
+pub struct ExtractedResource<'a> {
+    raw: &'a [u8],
+    format: Option<CompressionFormat>,
+    decompressed: Option<Vec<Payload>>,
+}
+
+impl<'a> ExtractedResource<'a> {
+    pub fn raw(&self) -> &'a [u8] {
+        self.raw
+    }
+
+    /// Compression format recognised in the raw data
+    pub fn format(&self) -> Option<CompressionFormat> {
+        self.format
+    }
+
+    /// Decompressed payloads, `None` if the data is not compressed or could not be decompressed
+    pub fn decompressed(&self) -> Option<&[Payload]> {
+        self.decompressed.as_deref()
+    }
+}
+
+impl PortExe {
+    /// Walks the resource directory and returns every data entry, depth first and
+    /// ordered by ID then name at each level.
+    ///
+    /// Uses the default [`ResourceLimits`]; see [`PortExe::resource_directory`] for the
+    /// structural problems skipped along the way.
+    pub fn resources(&self) -> io::Result<Vec<ResourceEntry>> {
+        Ok(self.resource_directory(&ResourceLimits::default())?.entries)
+    }
+
+    /// Walks the resource directory within `limits`.
+    ///
+    /// The walk is depth first and visits the entries of each directory in
+    /// [`ResourceId`] order, numeric IDs before names, whatever order the file stores
+    /// them in, so the result does not depend on how the resource compiler sorted them.
+    ///
+    /// Subdirectories that loop back to a directory on their path, that are shared with
+    /// an earlier entry or that are nested too deeply are skipped, as are data entries
+    /// outside the language level. Each is reported as a [`ResourceWarning`], as is
+    /// running out of the entry budget, so crafted trees cannot make the walk hang.
+    pub fn resource_directory(&self, limits: &ResourceLimits) -> io::Result<ResourceDirectory> {
+        let mut tree = ResourceDirectory::default();
+        let directory = match self
+            .optional_header()
+            .and_then(|h| h.data_directory(IMAGE_DIRECTORY_ENTRY_RESOURCE))
+        {
+            Some(directory) => directory,
+            None => return Ok(tree),
+        };
+        let rva = directory.virtual_address().into_value();
+        let size = directory.size().into_value();
+        if rva == 0 || size == 0 {
+            return Ok(tree);
+        }
+        let base = self
+            .rva_to_offset(rva)
+            .ok_or_else(|| invalid_data("resource directory is not backed by file data"))?;
+        let section = &self.data()[base..];
+
+        // Directory offset, IDs of the entries leading to it and offsets of its ancestors
+        let mut pending = vec![(0, Vec::new(), Vec::new())];
+        let mut visited = HashSet::new();
+        visited.insert(0);
+        let mut budget = limits.max_entries;
+        let mut end = 0;
+        while let Some((offset, path, ancestors)) = pending.pop() {
+            let mut subdirectories = Vec::new();
+            let mut entries = read_directory(section, offset, &mut end)?;
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            for (id, offset_to_data) in entries {
+                if budget == 0 {
+                    tree.warnings.push(ResourceWarning::EntryBudget);
+                    pending.clear();
+                    subdirectories.clear();
+                    break;
+                }
+                budget -= 1;
+                let target = offset_to_data & !IMAGE_RESOURCE_DATA_IS_DIRECTORY;
+                if offset_to_data & IMAGE_RESOURCE_DATA_IS_DIRECTORY != 0 {
+                    let child = target as usize;
+                    if child == offset || ancestors.contains(&child) {
+                        tree.warnings
+                            .push(ResourceWarning::Cycle { offset: target });
+                    } else if !visited.insert(child) {
+                        tree.warnings
+                            .push(ResourceWarning::SharedDirectory { offset: target });
+                    } else if path.len() + 2 > limits.max_depth {
+                        tree.warnings
+                            .push(ResourceWarning::TooDeep { offset: target });
+                    } else {
+                        let mut child_path: Vec<ResourceId> = path.clone();
+                        child_path.push(id);
+                        let mut child_ancestors: Vec<usize> = ancestors.clone();
+                        child_ancestors.push(offset);
+                        subdirectories.push((child, child_path, child_ancestors));
+                    }
+                    continue;
+                }
+                if path.len() != 2 {
+                    tree.warnings
+                        .push(ResourceWarning::MisplacedData { offset: target });
+                    continue;
+                }
+                let data_offset = target as usize;
+                let field = |index: usize| {
+                    read_u32(section, data_offset + index * 4)
+                        .ok_or_else(|| invalid_data("truncated resource data entry"))
+                };
+                tree.entries.push(ResourceEntry {
+                    type_id: path[0].clone(),
+                    name_id: path[1].clone(),
+                    language: id,
+                    data_rva: field(0)?,
+                    size: field(1)?,
+                    code_page: field(2)?,
+                    entry_offset: base + data_offset,
+                });
+                end = end.max(data_offset + IMAGE_SIZEOF_RESOURCE_DATA_ENTRY);
+            }
+            // Depth first, so each subtree is listed before the next sibling
+            pending.extend(subdirectories.into_iter().rev());
+        }
+        tree.end = base + end;
+        Ok(tree)
+    }
+
+    /// Raw bytes of a resource, `None` if they lie outside the file.
+    pub fn resource_data(&self, entry: &ResourceEntry) -> Option<&[u8]> {
+        self.data_at_rva(entry.data_rva, entry.size)
+    }
+
+    /// Non-empty strings of every `RT_STRING` table, in resource order.
+    ///
+    /// Each table holds 16 length-prefixed UTF-16 strings; table `n` holds the strings
+    /// with IDs `(n - 1) * 16` to `(n - 1) * 16 + 15`.
+    pub fn string_resources(&self) -> io::Result<Vec<StringResource>> {
+        let mut strings = Vec::new();
+        for entry in self.resources()? {
+            let block = match (&entry.type_id, &entry.name_id) {
+                (ResourceId::Id(RT_STRING), ResourceId::Id(block))
+                    if (1..=MAX_STRING_TABLE).contains(block) =>
+                {
+                    *block
+                }
+                _ => continue,
+            };
+            let language = match entry.language {
+                ResourceId::Id(language) => language,
+                ResourceId::Name(_) => continue,
+            };
+            let data = self
+                .resource_data(&entry)
+                .ok_or_else(|| invalid_data("string table is not backed by file data"))?;
+            let mut offset = 0;
+            for index in 0..STRINGS_PER_TABLE {
+                let length = match read_u16(data, offset) {
+                    Some(length) => length as usize,
+                    None => break,
+                };
+                let units = data
+                    .get(offset + 2..offset + 2 + length * 2)
+                    .ok_or_else(|| invalid_data("truncated string table"))?;
+                let rva = entry.data_rva.wrapping_add(offset as u32 + 2);
+                offset += 2 + length * 2;
+                if length == 0 {
+                    continue;
+                }
+                let units = utf16_units(units, ByteOrder::Little);
+                strings.push(StringResource {
+                    id: (block - 1) * STRINGS_PER_TABLE + index,
+                    language,
+                    value: String::from_utf16_lossy(&units),
+                    rva,
+                });
+            }
+        }
+        Ok(strings)
+    }
+
+    /// Returns the raw bytes of a resource and, when they hold a recognised
+    /// compressed stream or archive, the decompressed payloads.
+    pub fn extract_resource(&self, entry: &ResourceEntry) -> Option<ExtractedResource<'_>> {
+        let raw = self.resource_data(entry)?;
+        let format = detect_compression(raw);
+        let decompressed = format.and_then(|_| decompress(raw).ok());
+        Some(ExtractedResource {
+            raw,
+            format,
+            decompressed,
+        })
+    }
+}
+
+/// Reads the entries of a directory table, extending `end` past the table and the
+/// names it refers to.
+fn read_directory(
+    section: &[u8],
+    offset: usize,
+    end: &mut usize,
+) -> io::Result<Vec<(ResourceId, u32)>> {
+    let truncated = || invalid_data("truncated resource directory");
+    let number_of_named_entries = read_u16(section, offset + 12).ok_or_else(truncated)?;
+    let number_of_id_entries = read_u16(section, offset + 14).ok_or_else(truncated)?;
+    let count = number_of_named_entries as usize + number_of_id_entries as usize;
+
+    let mut entries = Vec::with_capacity(count);
+    *end = (*end).max(
+        offset + IMAGE_SIZEOF_RESOURCE_DIRECTORY + count * IMAGE_SIZEOF_RESOURCE_DIRECTORY_ENTRY,
+    );
+    for index in 0..count {
+        let entry_offset = offset
+            + IMAGE_SIZEOF_RESOURCE_DIRECTORY
+            + index * IMAGE_SIZEOF_RESOURCE_DIRECTORY_ENTRY;
+        let name = read_u32(section, entry_offset).ok_or_else(truncated)?;
+        let offset_to_data = read_u32(section, entry_offset + 4).ok_or_else(truncated)?;
+        let id = if name & IMAGE_RESOURCE_NAME_IS_STRING != 0 {
+            let name_offset = (name & !IMAGE_RESOURCE_NAME_IS_STRING) as usize;
+            let name = read_name(section, name_offset)?;
+            *end = (*end).max(name_offset + 2 + name.len());
+            ResourceId::Name(name)
+        } else {
+            ResourceId::Id(name as u16)
+        };
+        entries.push((id, offset_to_data));
+    }
+    Ok(entries)
+}
+
+fn read_name(section: &[u8], offset: usize) -> io::Result<PeString> {
+    let truncated = || invalid_data("truncated resource name");
+    let length = read_u16(section, offset).ok_or_else(truncated)? as usize;
+    let units = (0..length)
+        .map(|index| read_u16(section, offset + 2 + index * 2).ok_or_else(truncated))
+        .collect::<io::Result<Vec<u16>>>()?;
+    Ok(PeString::from_utf16(&units))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
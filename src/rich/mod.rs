@@ -0,0 +1,121 @@
+use crate::port_exe::PortExe;
+use crate::read_u32;
+use std::ops::Range;
+
+/// `"Rich"`, ending the Rich header
+const RICH_SIGNATURE: u32 = 0x6863_6952;
+/// `"DanS"`, starting the Rich header once XORed with its key
+const DANS_SIGNATURE: u32 = 0x536E_6144;
+/// Product ID counting the imported functions rather than objects
+pub const RICH_PRODUCT_IMPORT: u16 = 1;
+
+/// Key-sized padding between the start marker and the first entry
+const RICH_PADDING: usize = 12;
+
+/// Tool that contributed objects to the image, as recorded by the Microsoft linker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RichEntry {
+    product_id: u16,
+    build: u16,
+    count: u32,
+}
+
+impl RichEntry {
+    /// Identifier of the tool and its version, e.g. the C++ compiler of one Visual Studio release
+    pub fn product_id(&self) -> u16 {
+        self.product_id
+    }
+
+    /// Build number of the tool
+    pub fn build(&self) -> u16 {
+        self.build
+    }
+
+    /// Number of objects the tool produced
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+/// Undocumented header the Microsoft linker writes between the DOS stub and the PE header
+#[derive(Debug, Clone)]
+pub struct RichHeader {
+    key: u32,
+    offset: usize,
+    entries: Vec<RichEntry>,
+}
+
+impl RichHeader {
+    /// XOR key, a checksum over the DOS header and the entries
+    pub fn key(&self) -> u32 {
+        self.key
+    }
+
+    /// File offset of the `DanS` marker
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn entries(&self) -> &[RichEntry] {
+        &self.entries
+    }
+
+    /// Entry of the linker that produced the image, which it records last
+    pub fn linker(&self) -> Option<&RichEntry> {
+        self.entries.last()
+    }
+
+    /// Tool that produced the most objects, usually the compiler of the project's own code
+    pub fn primary_tool(&self) -> Option<&RichEntry> {
+        let tools = match self.entries.split_last() {
+            Some((_, tools)) => tools,
+            None => return None,
+        };
+        tools
+            .iter()
+            .filter(|entry| entry.product_id != RICH_PRODUCT_IMPORT)
+            .max_by_key(|entry| entry.count)
+    }
+}
+
+impl PortExe {
+    pub fn rich_header(&self) -> Option<RichHeader> {
+        let data = self.data();
+        let range = rich_header_range(data)?;
+        let key = read_u32(data, range.end - 4)?;
+        let entries = data[range.start + 4 + RICH_PADDING..range.end - 8]
+            .chunks_exact(8)
+            .map(|entry| {
+                let id = read_u32(entry, 0).unwrap_or(0) ^ key;
+                RichEntry {
+                    product_id: (id >> 16) as u16,
+                    build: id as u16,
+                    count: read_u32(entry, 4).unwrap_or(0) ^ key,
+                }
+            })
+            .collect();
+        Some(RichHeader {
+            key,
+            offset: range.start,
+            entries,
+        })
+    }
+}
+
+/// Location of the Rich header between the DOS stub and the PE header
+pub(crate) fn rich_header_range(data: &[u8]) -> Option<Range<usize>> {
+    let pe_header = read_u32(data, 0x3C)? as usize;
+    let stub = data.get(0x40..pe_header.min(data.len()))?;
+    let rich = (0..stub.len().saturating_sub(7))
+        .rev()
+        .find(|&offset| read_u32(stub, offset) == Some(RICH_SIGNATURE))?;
+    let key = read_u32(stub, rich + 4)?;
+    let start = (0..rich)
+        .step_by(4)
+        .rev()
+        .find(|&offset| read_u32(stub, offset) == Some(DANS_SIGNATURE ^ key))?;
+    if rich < start + 4 + RICH_PADDING {
+        return None;
+    }
+    Some(0x40 + start..0x40 + rich + 8)
+}
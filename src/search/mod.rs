@@ -0,0 +1,206 @@
+use crate::export::Export;
+use crate::import::Import;
+use crate::module_key;
+use crate::port_exe::PortExe;
+use std::io;
+
+/// How [`NameMatcher`] compares names with its pattern
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchMode {
+    Exact,
+    /// Exact apart from ASCII case, the way module names compare
+    IgnoreCase,
+    /// Names starting with the pattern, ignoring ASCII case
+    Prefix,
+    /// Names a regular expression matches anywhere; needs the `regex-search` feature
+    Regex,
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    Text(String),
+    #[cfg(feature = "regex-search")]
+    Regex(regex::Regex),
+}
+
+/// Symbol name pattern for export and import searches
+#[derive(Debug, Clone)]
+pub struct NameMatcher {
+    mode: MatchMode,
+    pattern: Pattern,
+}
+
+impl NameMatcher {
+    /// Fails for invalid regular expressions, and for [`MatchMode::Regex`] when the
+    /// crate is built without the `regex-search` feature.
+    pub fn new(mode: MatchMode, pattern: &str) -> io::Result<Self> {
+        let pattern = match mode {
+            MatchMode::Exact => Pattern::Text(pattern.to_string()),
+            MatchMode::IgnoreCase | MatchMode::Prefix => {
+                Pattern::Text(pattern.to_ascii_lowercase())
+            }
+            MatchMode::Regex => compile(pattern)?,
+        };
+        Ok(Self { mode, pattern })
+    }
+
+    pub fn mode(&self) -> MatchMode {
+        self.mode
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        match &self.pattern {
+            Pattern::Text(pattern) => match self.mode {
+                MatchMode::Exact => name == pattern,
+                MatchMode::Prefix => name
+                    .get(..pattern.len())
+                    .map_or(false, |prefix| prefix.eq_ignore_ascii_case(pattern)),
+                _ => name.eq_ignore_ascii_case(pattern),
+            },
+            #[cfg(feature = "regex-search")]
+            Pattern::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+/// Imported function [`ImportQuery`] looks for
+#[derive(Debug, Clone)]
+pub enum ImportSymbol {
+    /// Every function of the DLL
+    Any,
+    Name(NameMatcher),
+    Ordinal(u16),
+}
+
+/// Query of [`PortExe::find_imports`]: a DLL, a function or a function of a DLL
+#[derive(Debug, Clone)]
+pub struct ImportQuery {
+    module: Option<String>,
+    symbol: ImportSymbol,
+}
+
+impl ImportQuery {
+    /// `module` is compared ignoring case and a `.dll` extension; `None` matches any DLL.
+    pub fn new(module: Option<&str>, symbol: ImportSymbol) -> Self {
+        Self {
+            module: module.map(module_key),
+            symbol,
+        }
+    }
+
+    /// Parses `dll!name`, `dll!#ordinal`, `dll` or `!name`, matching names in `mode`.
+    pub fn parse(query: &str, mode: MatchMode) -> io::Result<Self> {
+        let (module, symbol) = match query.split_once('!') {
+            Some((module, symbol)) => (module, Some(symbol)),
+            None => (query, None),
+        };
+        let symbol = match symbol {
+            None => ImportSymbol::Any,
+            Some(symbol) => match symbol.strip_prefix('#') {
+                Some(ordinal) => ImportSymbol::Ordinal(
+                    ordinal
+                        .parse()
+                        .map_err(|_| invalid_input("ordinal must be a number below 65536"))?,
+                ),
+                None if symbol.is_empty() => {
+                    return Err(invalid_input("missing function name after '!'"))
+                }
+                None => ImportSymbol::Name(NameMatcher::new(mode, symbol)?),
+            },
+        };
+        if module.is_empty() && matches!(symbol, ImportSymbol::Any) {
+            return Err(invalid_input("query names neither a DLL nor a function"));
+        }
+        Ok(Self::new((!module.is_empty()).then(|| module), symbol))
+    }
+
+    pub fn symbol(&self) -> &ImportSymbol {
+        &self.symbol
+    }
+
+    pub fn matches(&self, dll: &str, import: &Import) -> bool {
+        if let Some(module) = &self.module {
+            if module_key(dll) != *module {
+                return false;
+            }
+        }
+        match &self.symbol {
+            ImportSymbol::Any => true,
+            ImportSymbol::Name(matcher) => import
+                .name()
+                .map_or(false, |name| matcher.matches(&name.to_string_lossy())),
+            ImportSymbol::Ordinal(ordinal) => import.ordinal() == Some(*ordinal),
+        }
+    }
+}
+
+/// Import [`PortExe::find_imports`] found, with the DLL it comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportMatch {
+    dll: String,
+    import: Import,
+}
+
+impl ImportMatch {
+    pub(crate) fn new(dll: &str, import: &Import) -> Self {
+        Self {
+            dll: dll.to_string(),
+            import: import.clone(),
+        }
+    }
+
+    /// DLL name as the import descriptor spells it
+    pub fn dll(&self) -> &str {
+        &self.dll
+    }
+
+    pub fn import(&self) -> &Import {
+        &self.import
+    }
+}
+
+impl PortExe {
+    /// Imports `query` accepts, in import table order followed by the delay-loaded ones
+    pub fn find_imports(&self, query: &ImportQuery) -> io::Result<Vec<ImportMatch>> {
+        let mut found = Vec::new();
+        for descriptor in self.all_imports()? {
+            for import in descriptor.imports() {
+                if query.matches(descriptor.dll_name(), import) {
+                    found.push(ImportMatch::new(descriptor.dll_name(), import));
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    /// Named exports whose name `matcher` accepts, in export table order
+    pub fn find_exports(&self, matcher: &NameMatcher) -> io::Result<Vec<Export>> {
+        Ok(self
+            .exports()?
+            .into_iter()
+            .filter(|export| {
+                export
+                    .name()
+                    .map_or(false, |name| matcher.matches(&name.to_string_lossy()))
+            })
+            .collect())
+    }
+}
+
+#[cfg(feature = "regex-search")]
+fn compile(pattern: &str) -> io::Result<Pattern> {
+    regex::Regex::new(pattern)
+        .map(Pattern::Regex)
+        .map_err(|e| invalid_input(&format!("invalid regular expression: {}", e)))
+}
+
+#[cfg(not(feature = "regex-search"))]
+fn compile(_pattern: &str) -> io::Result<Pattern> {
+    Err(invalid_input(
+        "regular expressions need the `regex-search` feature",
+    ))
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
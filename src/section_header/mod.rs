@@ -0,0 +1,438 @@
+use crate::endian::{le_u16, le_u32};
+use crate::pe_string::PeString;
+use crate::{read_block, write_at, StructField};
+use std::io;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+
+/// Size of a single section table entry
+pub const IMAGE_SIZEOF_SECTION_HEADER: u64 = 40;
+
+pub(crate) fn read_section_headers<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+    count: u16,
+) -> io::Result<Vec<SectionHeaderWrapper>> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let table = read_block(
+        reader,
+        count as usize * IMAGE_SIZEOF_SECTION_HEADER as usize,
+    )?;
+    let mut entries = &table[..];
+
+    let mut section_headers = Vec::with_capacity(count as usize);
+    for index in 0..count as u64 {
+        let section_header =
+            read_section_header(&mut entries, offset + index * IMAGE_SIZEOF_SECTION_HEADER)?;
+        section_headers.push(SectionHeaderWrapper { section_header });
+    }
+    Ok(section_headers)
+}
+
+fn read_section_header<R: Read>(reader: &mut R, offset: u64) -> io::Result<SectionHeader> {
+    let mut name = [0u8; 8];
+    let mut virtual_size = [0u8; 4];
+    let mut virtual_address = [0u8; 4];
+    let mut size_of_raw_data = [0u8; 4];
+    let mut pointer_to_raw_data = [0u8; 4];
+    let mut pointer_to_relocations = [0u8; 4];
+    let mut pointer_to_linenumbers = [0u8; 4];
+    let mut number_of_relocations = [0u8; 2];
+    let mut number_of_linenumbers = [0u8; 2];
+    let mut characteristics = [0u8; 4];
+
+    reader.read_exact(&mut name)?;
+    reader.read_exact(&mut virtual_size)?;
+    reader.read_exact(&mut virtual_address)?;
+    reader.read_exact(&mut size_of_raw_data)?;
+    reader.read_exact(&mut pointer_to_raw_data)?;
+    reader.read_exact(&mut pointer_to_relocations)?;
+    reader.read_exact(&mut pointer_to_linenumbers)?;
+    reader.read_exact(&mut number_of_relocations)?;
+    reader.read_exact(&mut number_of_linenumbers)?;
+    reader.read_exact(&mut characteristics)?;
+
+    let section_header_raw = SectionHeaderRaw {
+        name,
+        virtual_size,
+        virtual_address,
+        size_of_raw_data,
+        pointer_to_raw_data,
+        pointer_to_relocations,
+        pointer_to_linenumbers,
+        number_of_relocations,
+        number_of_linenumbers,
+        characteristics,
+    };
+
+    Ok(SectionHeader {
+        offset,
+        section_header_raw,
+    })
+}
+
+#[derive(Debug)]
+struct SectionHeaderRaw {
+    name: [u8; 8],
+    virtual_size: [u8; 4],
+    virtual_address: [u8; 4],
+    size_of_raw_data: [u8; 4],
+    pointer_to_raw_data: [u8; 4],
+    pointer_to_relocations: [u8; 4],
+    pointer_to_linenumbers: [u8; 4],
+    number_of_relocations: [u8; 2],
+    number_of_linenumbers: [u8; 2],
+    characteristics: [u8; 4],
+}
+
+impl SectionHeaderRaw {
+    fn to_bytes(&self) -> Vec<u8> {
+        [
+            &self.name[..],
+            &self.virtual_size,
+            &self.virtual_address,
+            &self.size_of_raw_data,
+            &self.pointer_to_raw_data,
+            &self.pointer_to_relocations,
+            &self.pointer_to_linenumbers,
+            &self.number_of_relocations,
+            &self.number_of_linenumbers,
+            &self.characteristics,
+        ]
+        .concat()
+    }
+}
+
+#[derive(Debug)]
+struct SectionHeader {
+    offset: u64,
+    section_header_raw: SectionHeaderRaw,
+}
+
+impl SectionHeader {
+    fn name(&self) -> PeString {
+        let name = &self.section_header_raw.name;
+        let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        PeString::from_bytes(&name[..len])
+    }
+
+    fn virtual_size(&self) -> u32 {
+        le_u32(self.section_header_raw.virtual_size)
+    }
+
+    fn virtual_address(&self) -> u32 {
+        le_u32(self.section_header_raw.virtual_address)
+    }
+
+    fn size_of_raw_data(&self) -> u32 {
+        le_u32(self.section_header_raw.size_of_raw_data)
+    }
+
+    fn pointer_to_raw_data(&self) -> u32 {
+        le_u32(self.section_header_raw.pointer_to_raw_data)
+    }
+
+    fn pointer_to_relocations(&self) -> u32 {
+        le_u32(self.section_header_raw.pointer_to_relocations)
+    }
+
+    fn pointer_to_linenumbers(&self) -> u32 {
+        le_u32(self.section_header_raw.pointer_to_linenumbers)
+    }
+
+    fn number_of_relocations(&self) -> u16 {
+        le_u16(self.section_header_raw.number_of_relocations)
+    }
+
+    fn number_of_linenumbers(&self) -> u16 {
+        le_u16(self.section_header_raw.number_of_linenumbers)
+    }
+
+    fn characteristics(&self) -> u32 {
+        le_u32(self.section_header_raw.characteristics)
+    }
+}
+
+#[derive(Debug)]
+pub struct SectionHeaderWrapper {
+    section_header: SectionHeader,
+}
+
+impl SectionHeaderWrapper {
+    /// Encodes the section table entry back into `data` at the offset it was read from.
+    pub(crate) fn write(&self, data: &mut [u8]) {
+        write_at(
+            data,
+            self.section_header.offset,
+            &self.section_header.section_header_raw.to_bytes(),
+        );
+    }
+
+    pub(crate) fn set_characteristics(&mut self, characteristics: u32) {
+        self.section_header.section_header_raw.characteristics = characteristics.to_le_bytes();
+    }
+
+    /// Name up to the first NUL; long names of object files read `/<offset>`
+    pub fn name(&self) -> StructField<PeString, 8> {
+        let offset = self.section_header.offset;
+        let name = String::from("Name");
+        let raw_bytes = self.section_header.section_header_raw.name;
+        let value = self.section_header.name();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn virtual_size(&self) -> StructField<u32, 4> {
+        let offset = self.section_header.offset + 8;
+        let name = String::from("Virtual size");
+        let raw_bytes = self.section_header.section_header_raw.virtual_size;
+        let value = self.section_header.virtual_size();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn virtual_address(&self) -> StructField<u32, 4> {
+        let offset = self.section_header.offset + 12;
+        let name = String::from("Virtual address");
+        let raw_bytes = self.section_header.section_header_raw.virtual_address;
+        let value = self.section_header.virtual_address();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn size_of_raw_data(&self) -> StructField<u32, 4> {
+        let offset = self.section_header.offset + 16;
+        let name = String::from("Size of raw data");
+        let raw_bytes = self.section_header.section_header_raw.size_of_raw_data;
+        let value = self.section_header.size_of_raw_data();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn pointer_to_raw_data(&self) -> StructField<u32, 4> {
+        let offset = self.section_header.offset + 20;
+        let name = String::from("Pointer to raw data");
+        let raw_bytes = self.section_header.section_header_raw.pointer_to_raw_data;
+        let value = self.section_header.pointer_to_raw_data();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn pointer_to_relocations(&self) -> StructField<u32, 4> {
+        let offset = self.section_header.offset + 24;
+        let name = String::from("Pointer to relocations");
+        let raw_bytes = self
+            .section_header
+            .section_header_raw
+            .pointer_to_relocations;
+        let value = self.section_header.pointer_to_relocations();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn pointer_to_linenumbers(&self) -> StructField<u32, 4> {
+        let offset = self.section_header.offset + 28;
+        let name = String::from("Pointer to line numbers");
+        let raw_bytes = self
+            .section_header
+            .section_header_raw
+            .pointer_to_linenumbers;
+        let value = self.section_header.pointer_to_linenumbers();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn number_of_relocations(&self) -> StructField<u16, 2> {
+        let offset = self.section_header.offset + 32;
+        let name = String::from("Num. of relocations");
+        let raw_bytes = self.section_header.section_header_raw.number_of_relocations;
+        let value = self.section_header.number_of_relocations();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn number_of_linenumbers(&self) -> StructField<u16, 2> {
+        let offset = self.section_header.offset + 34;
+        let name = String::from("Num. of line numbers");
+        let raw_bytes = self.section_header.section_header_raw.number_of_linenumbers;
+        let value = self.section_header.number_of_linenumbers();
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+
+    pub fn characteristics(&self) -> StructField<SectionCharacteristics, 4> {
+        let offset = self.section_header.offset + 36;
+        let name = String::from("Characteristics");
+        let raw_bytes = self.section_header.section_header_raw.characteristics;
+        let value = SectionCharacteristics::from(self.section_header.characteristics());
+        StructField {
+            offset,
+            name,
+            raw_bytes,
+            value,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SectionCharacteristics {
+    cnt_code: bool,
+    cnt_initialized_data: bool,
+    cnt_uninitialized_data: bool,
+    lnk_info: bool,
+    lnk_remove: bool,
+    lnk_comdat: bool,
+    lnk_nreloc_ovfl: bool,
+    mem_discardable: bool,
+    mem_not_cached: bool,
+    mem_not_paged: bool,
+    mem_shared: bool,
+    mem_execute: bool,
+    mem_read: bool,
+    mem_write: bool,
+}
+
+impl From<u32> for SectionCharacteristics {
+    fn from(value: u32) -> Self {
+        Self {
+            cnt_code: value & IMAGE_SCN_CNT_CODE != 0,
+            cnt_initialized_data: value & IMAGE_SCN_CNT_INITIALIZED_DATA != 0,
+            cnt_uninitialized_data: value & IMAGE_SCN_CNT_UNINITIALIZED_DATA != 0,
+            lnk_info: value & IMAGE_SCN_LNK_INFO != 0,
+            lnk_remove: value & IMAGE_SCN_LNK_REMOVE != 0,
+            lnk_comdat: value & IMAGE_SCN_LNK_COMDAT != 0,
+            lnk_nreloc_ovfl: value & IMAGE_SCN_LNK_NRELOC_OVFL != 0,
+            mem_discardable: value & IMAGE_SCN_MEM_DISCARDABLE != 0,
+            mem_not_cached: value & IMAGE_SCN_MEM_NOT_CACHED != 0,
+            mem_not_paged: value & IMAGE_SCN_MEM_NOT_PAGED != 0,
+            mem_shared: value & IMAGE_SCN_MEM_SHARED != 0,
+            mem_execute: value & IMAGE_SCN_MEM_EXECUTE != 0,
+            mem_read: value & IMAGE_SCN_MEM_READ != 0,
+            mem_write: value & IMAGE_SCN_MEM_WRITE != 0,
+        }
+    }
+}
+
+impl SectionCharacteristics {
+    /// The section contains executable code
+    pub fn cnt_code(&self) -> bool {
+        self.cnt_code
+    }
+
+    /// The section contains initialized data
+    pub fn cnt_initialized_data(&self) -> bool {
+        self.cnt_initialized_data
+    }
+
+    /// The section contains uninitialized data
+    pub fn cnt_uninitialized_data(&self) -> bool {
+        self.cnt_uninitialized_data
+    }
+
+    /// The section contains comments or other information (object files only)
+    pub fn lnk_info(&self) -> bool {
+        self.lnk_info
+    }
+
+    /// The section will not become part of the image (object files only)
+    pub fn lnk_remove(&self) -> bool {
+        self.lnk_remove
+    }
+
+    /// The section contains COMDAT data (object files only)
+    pub fn lnk_comdat(&self) -> bool {
+        self.lnk_comdat
+    }
+
+    /// The section contains extended relocations
+    pub fn lnk_nreloc_ovfl(&self) -> bool {
+        self.lnk_nreloc_ovfl
+    }
+
+    /// The section can be discarded as needed
+    pub fn mem_discardable(&self) -> bool {
+        self.mem_discardable
+    }
+
+    /// The section cannot be cached
+    pub fn mem_not_cached(&self) -> bool {
+        self.mem_not_cached
+    }
+
+    /// The section is not pageable
+    pub fn mem_not_paged(&self) -> bool {
+        self.mem_not_paged
+    }
+
+    /// The section can be shared in memory
+    pub fn mem_shared(&self) -> bool {
+        self.mem_shared
+    }
+
+    /// The section can be executed as code
+    pub fn mem_execute(&self) -> bool {
+        self.mem_execute
+    }
+
+    /// The section can be read
+    pub fn mem_read(&self) -> bool {
+        self.mem_read
+    }
+
+    /// The section can be written to
+    pub fn mem_write(&self) -> bool {
+        self.mem_write
+    }
+}
+
+pub const IMAGE_SCN_CNT_CODE: u32 = 0x00000020;
+pub const IMAGE_SCN_CNT_INITIALIZED_DATA: u32 = 0x00000040;
+pub const IMAGE_SCN_CNT_UNINITIALIZED_DATA: u32 = 0x00000080;
+pub const IMAGE_SCN_LNK_INFO: u32 = 0x00000200;
+pub const IMAGE_SCN_LNK_REMOVE: u32 = 0x00000800;
+pub const IMAGE_SCN_LNK_COMDAT: u32 = 0x00001000;
+pub const IMAGE_SCN_LNK_NRELOC_OVFL: u32 = 0x01000000;
+pub const IMAGE_SCN_MEM_DISCARDABLE: u32 = 0x02000000;
+pub const IMAGE_SCN_MEM_NOT_CACHED: u32 = 0x04000000;
+pub const IMAGE_SCN_MEM_NOT_PAGED: u32 = 0x08000000;
+pub const IMAGE_SCN_MEM_SHARED: u32 = 0x10000000;
+pub const IMAGE_SCN_MEM_EXECUTE: u32 = 0x20000000;
+pub const IMAGE_SCN_MEM_READ: u32 = 0x40000000;
+pub const IMAGE_SCN_MEM_WRITE: u32 = 0x80000000;
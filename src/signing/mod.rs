@@ -0,0 +1,342 @@
+use crate::port_exe::PortExe;
+use crate::read_u16;
+use crate::resource::ResourceId;
+use std::fmt;
+use std::io;
+
+/// Code signing, carried by ordinary Authenticode certificates
+pub const EKU_CODE_SIGNING: &str = "1.3.6.1.5.5.7.3.3";
+/// Windows Hardware Driver Verification, the WHQL attestation signature
+pub const EKU_WHQL: &str = "1.3.6.1.4.1.311.10.3.5";
+pub const EKU_WINDOWS_SYSTEM_COMPONENT: &str = "1.3.6.1.4.1.311.10.3.6";
+pub const EKU_PROTECTED_PROCESS_LIGHT: &str = "1.3.6.1.4.1.311.10.3.22";
+pub const EKU_PROTECTED_PROCESS: &str = "1.3.6.1.4.1.311.10.3.24";
+pub const EKU_KERNEL_MODE_CODE_SIGNING: &str = "1.3.6.1.4.1.311.61.1.1";
+/// Early Launch Antimalware Driver
+pub const EKU_EARLY_LAUNCH_ANTIMALWARE: &str = "1.3.6.1.4.1.311.61.4.1";
+
+/// Resource type of `MICROSOFT_ELAMCERTIFICATEINFO`
+pub const ELAM_RESOURCE_TYPE: &str = "MSElamCertInfoID";
+pub const CALG_SHA_256: u16 = 0x800C;
+pub const CALG_SHA_384: u16 = 0x800D;
+pub const CALG_SHA_512: u16 = 0x800E;
+
+/// Certificate an ELAM driver allows to sign its user-mode antimalware services
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElamCertificate {
+    hash: String,
+    algorithm: u16,
+    extended_key_usages: Vec<String>,
+}
+
+impl ElamCertificate {
+    /// Hex-encoded hash of the certificate's to-be-signed part
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    /// One of the `CALG_SHA_*` constants
+    pub fn algorithm(&self) -> u16 {
+        self.algorithm
+    }
+
+    /// EKUs the signing certificate must also carry, empty for any
+    pub fn extended_key_usages(&self) -> &[String] {
+        &self.extended_key_usages
+    }
+}
+
+/// Words of company names that say nothing about who the company is
+const LEGAL_FORMS: &[&str] = &[
+    "ab",
+    "ag",
+    "bv",
+    "co",
+    "company",
+    "corp",
+    "corporation",
+    "gmbh",
+    "inc",
+    "incorporated",
+    "kg",
+    "kk",
+    "limited",
+    "llc",
+    "ltd",
+    "nv",
+    "oy",
+    "plc",
+    "pty",
+    "sa",
+    "sarl",
+    "sas",
+    "spa",
+    "srl",
+    "the",
+];
+
+/// Unmet requirement of the signing that ELAM drivers and protected processes need, or
+/// a signer that does not match the identity the image claims
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SigningIssue {
+    /// The `MICROSOFT_ELAMCERTIFICATEINFO` resource cannot be decoded
+    MalformedElamResource,
+    /// An ELAM certificate entry names a hash algorithm other than SHA-2
+    UnknownElamAlgorithm { algorithm: u16 },
+    /// ELAM drivers must be linked with `/INTEGRITYCHECK`
+    ElamWithoutIntegrityCheck,
+    /// ELAM drivers must be signed by a certificate with the ELAM EKU
+    ElamSignerWithoutElamEku,
+    /// `/INTEGRITYCHECK` images are only loaded if signed
+    IntegrityCheckWithoutSignature,
+    /// The signer is entitled to protected processes, which only load images linked
+    /// with `/INTEGRITYCHECK`
+    ProtectedSignerWithoutIntegrityCheck { eku: &'static str },
+    /// Neither `CompanyName` nor `ProductName` of the version resource names the
+    /// organization of the signer, as when a file claiming to be Microsoft's is signed
+    /// by someone else
+    SignerIdentityMismatch {
+        company: Option<String>,
+        product: Option<String>,
+        signer: String,
+    },
+}
+
+impl fmt::Display for SigningIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MalformedElamResource => {
+                write!(f, "MICROSOFT_ELAMCERTIFICATEINFO resource is malformed")
+            }
+            Self::UnknownElamAlgorithm { algorithm } => write!(
+                f,
+                "ELAM certificate entry uses unknown hash algorithm {:#06x}",
+                algorithm
+            ),
+            Self::ElamWithoutIntegrityCheck => {
+                write!(f, "ELAM driver is not linked with /INTEGRITYCHECK")
+            }
+            Self::ElamSignerWithoutElamEku => write!(
+                f,
+                "ELAM driver signer lacks the Early Launch Antimalware EKU"
+            ),
+            Self::IntegrityCheckWithoutSignature => {
+                write!(f, "/INTEGRITYCHECK is set but the image is not signed")
+            }
+            Self::ProtectedSignerWithoutIntegrityCheck { eku } => write!(
+                f,
+                "signer carries protected process EKU {} but the image is not linked with /INTEGRITYCHECK",
+                eku
+            ),
+            Self::SignerIdentityMismatch {
+                company,
+                product,
+                signer,
+            } => {
+                let claimed: Vec<&str> = company.iter().chain(product).map(String::as_str).collect();
+                write!(
+                    f,
+                    "version resource names {} but the image is signed by {}",
+                    claimed.join(" / "),
+                    signer
+                )
+            }
+        }
+    }
+}
+
+/// Signing-related properties of an image and the requirements they leave unmet
+#[derive(Debug, Clone)]
+pub struct SigningRequirements {
+    elam_certificates: Option<Vec<ElamCertificate>>,
+    integrity_check: bool,
+    signed: bool,
+    signer_extended_key_usages: Vec<String>,
+    issues: Vec<SigningIssue>,
+}
+
+impl SigningRequirements {
+    /// Entries of the `MICROSOFT_ELAMCERTIFICATEINFO` resource, `None` if the image has
+    /// none and so is no ELAM driver
+    pub fn elam_certificates(&self) -> Option<&[ElamCertificate]> {
+        self.elam_certificates.as_deref()
+    }
+
+    /// Whether `IMAGE_DLLCHARACTERISTICS_FORCE_INTEGRITY` (`/INTEGRITYCHECK`) is set
+    pub fn integrity_check(&self) -> bool {
+        self.integrity_check
+    }
+
+    /// Whether the image has an attribute certificate table
+    pub fn signed(&self) -> bool {
+        self.signed
+    }
+
+    /// EKUs of the Authenticode signer's certificate
+    pub fn signer_extended_key_usages(&self) -> &[String] {
+        &self.signer_extended_key_usages
+    }
+
+    pub fn issues(&self) -> &[SigningIssue] {
+        &self.issues
+    }
+}
+
+impl PortExe {
+    /// Checks the requirements Windows places on ELAM drivers and on images loaded into
+    /// protected processes: the `MICROSOFT_ELAMCERTIFICATEINFO` resource, the
+    /// `/INTEGRITYCHECK` flag and the EKUs of the signing certificate.
+    ///
+    /// Also compares the common name of the signer with the `CompanyName` and
+    /// `ProductName` the version resource claims; see [`same_organization`].
+    pub fn signing_requirements(&self) -> io::Result<SigningRequirements> {
+        let header = self
+            .optional_header()
+            .ok_or_else(|| invalid_input("object files are not signed"))?;
+        let integrity_check = header.dll_characteristics().force_integrity();
+        let signed = !self.certificates()?.is_empty();
+        let signer = self.signer()?;
+        let signer_extended_key_usages = signer
+            .as_ref()
+            .map(|signer| signer.extended_key_usages().to_vec())
+            .unwrap_or_default();
+        let has_eku = |eku: &str| signer_extended_key_usages.iter().any(|usage| usage == eku);
+
+        let mut issues = Vec::new();
+        let elam = self
+            .resources()
+            .unwrap_or_default()
+            .into_iter()
+            .find(|entry| match entry.type_id() {
+                ResourceId::Name(name) => name.eq_ignore_ascii_case(ELAM_RESOURCE_TYPE),
+                ResourceId::Id(_) => false,
+            });
+        let elam_certificates = elam.map(|entry| {
+            let certificates = self
+                .resource_data(&entry)
+                .and_then(elam_certificates)
+                .unwrap_or_else(|| {
+                    issues.push(SigningIssue::MalformedElamResource);
+                    Vec::new()
+                });
+            for certificate in &certificates {
+                if !matches!(
+                    certificate.algorithm,
+                    CALG_SHA_256 | CALG_SHA_384 | CALG_SHA_512
+                ) {
+                    issues.push(SigningIssue::UnknownElamAlgorithm {
+                        algorithm: certificate.algorithm,
+                    });
+                }
+            }
+            if !integrity_check {
+                issues.push(SigningIssue::ElamWithoutIntegrityCheck);
+            }
+            if signed && !has_eku(EKU_EARLY_LAUNCH_ANTIMALWARE) {
+                issues.push(SigningIssue::ElamSignerWithoutElamEku);
+            }
+            certificates
+        });
+        if integrity_check && !signed {
+            issues.push(SigningIssue::IntegrityCheckWithoutSignature);
+        }
+        if !integrity_check {
+            for eku in [EKU_PROTECTED_PROCESS_LIGHT, EKU_PROTECTED_PROCESS] {
+                if has_eku(eku) {
+                    issues.push(SigningIssue::ProtectedSignerWithoutIntegrityCheck { eku });
+                }
+            }
+        }
+        let signer_name = signer.as_ref().and_then(|signer| signer.common_name());
+        if let (Some(signer), Ok(Some(version))) = (signer_name, self.version_info()) {
+            let company = version
+                .string("CompanyName")
+                .filter(|name| !name.trim().is_empty());
+            let product = version
+                .string("ProductName")
+                .filter(|name| !name.trim().is_empty());
+            let claimed = company.iter().chain(&product).copied();
+            let matched = claimed.clone().any(|name| same_organization(name, signer));
+            if claimed.count() > 0 && !matched {
+                issues.push(SigningIssue::SignerIdentityMismatch {
+                    company: company.map(str::to_string),
+                    product: product.map(str::to_string),
+                    signer: signer.to_string(),
+                });
+            }
+        }
+
+        Ok(SigningRequirements {
+            elam_certificates,
+            integrity_check,
+            signed,
+            signer_extended_key_usages,
+            issues,
+        })
+    }
+}
+
+/// Whether `claimed` and `signer` name the same organization: after dropping case,
+/// punctuation and legal forms such as `Corporation` or `GmbH`, every word of the
+/// shorter name appears in the longer one. `Microsoft Corporation` thus matches the
+/// `Microsoft Windows` signer, and `Google LLC` the product `Google Chrome`.
+pub fn same_organization(claimed: &str, signer: &str) -> bool {
+    let words = |name: &str| -> Vec<String> {
+        name.to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty() && !LEGAL_FORMS.contains(word))
+            .map(str::to_string)
+            .collect()
+    };
+    let (claimed, signer) = (words(claimed), words(signer));
+    let (shorter, longer) = if claimed.len() <= signer.len() {
+        (&claimed, &signer)
+    } else {
+        (&signer, &claimed)
+    };
+    // A name of legal forms alone identifies nobody
+    shorter.is_empty() || shorter.iter().all(|word| longer.contains(word))
+}
+
+/// Decodes a `MICROSOFT_ELAMCERTIFICATEINFO` resource: a count, then per certificate
+/// a NUL-terminated UTF-16 hash, a `CALG_*` algorithm and NUL-terminated UTF-16 EKUs
+/// separated by semicolons.
+fn elam_certificates(data: &[u8]) -> Option<Vec<ElamCertificate>> {
+    let count = read_u16(data, 0)?;
+    let mut offset = 2;
+    let mut certificates = Vec::new();
+    for _ in 0..count {
+        let (hash, next) = wide_string(data, offset)?;
+        let algorithm = read_u16(data, next)?;
+        let (ekus, next) = wide_string(data, next + 2)?;
+        offset = next;
+        certificates.push(ElamCertificate {
+            hash,
+            algorithm,
+            extended_key_usages: ekus
+                .split(';')
+                .filter(|eku| !eku.is_empty())
+                .map(str::to_string)
+                .collect(),
+        });
+    }
+    Some(certificates)
+}
+
+/// Reads a NUL-terminated UTF-16 string, returning it with the offset after the NUL.
+fn wide_string(data: &[u8], offset: usize) -> Option<(String, usize)> {
+    let mut units = Vec::new();
+    let mut position = offset;
+    loop {
+        match read_u16(data, position)? {
+            0 => return Some((String::from_utf16_lossy(&units), position + 2)),
+            unit => units.push(unit),
+        }
+        position += 2;
+    }
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
@@ -0,0 +1,265 @@
+use crate::digest::{md5, to_hex};
+use crate::import::ImportDescriptor;
+use crate::ordinals::OrdinalMap;
+use crate::pe_string::PeString;
+use crate::port_exe::PortExe;
+use crate::read_u32;
+use crate::rich::rich_header_range;
+use std::collections::BTreeSet;
+use std::io;
+
+/// Distance below which [`cluster`] links files by default
+pub const DEFAULT_CLUSTER_THRESHOLD: f64 = 0.3;
+
+/// Weights of the import, Rich header, section and export distances
+const WEIGHTS: [f64; 4] = [0.3, 0.2, 0.3, 0.2];
+/// DLL extensions imphash drops from library names
+const IMPHASH_EXTENSIONS: [&str; 3] = ["dll", "ocx", "sys"];
+
+/// Structural features of one file, compared by [`distance`]
+#[derive(Debug, Clone)]
+pub struct Fingerprint {
+    name: String,
+    imports: Vec<String>,
+    delay_imports: Vec<String>,
+    rich_hash: Option<String>,
+    sections: Vec<(PeString, u32)>,
+    exports: BTreeSet<String>,
+}
+
+impl Fingerprint {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Imports as `library.function` in import table order, as imphash joins them
+    pub fn imports(&self) -> &[String] {
+        &self.imports
+    }
+
+    /// Delay-loaded imports, normalized as [`Fingerprint::imports`]
+    pub fn delay_imports(&self) -> &[String] {
+        &self.delay_imports
+    }
+
+    /// MD5 of the import list, compatible with `pefile`'s `get_imphash`, which reads
+    /// only the import directory
+    pub fn imphash(&self) -> Option<String> {
+        import_hash(&self.imports)
+    }
+
+    /// MD5 of the delay-loaded import list, computed the way [`Fingerprint::imphash`] is
+    pub fn delay_imphash(&self) -> Option<String> {
+        import_hash(&self.delay_imports)
+    }
+
+    /// MD5 of the decoded Rich header, from `DanS` up to `Rich`
+    pub fn rich_hash(&self) -> Option<&str> {
+        self.rich_hash.as_deref()
+    }
+
+    /// Section names and virtual sizes, in section table order
+    pub fn sections(&self) -> &[(PeString, u32)] {
+        &self.sections
+    }
+
+    pub fn exports(&self) -> &BTreeSet<String> {
+        &self.exports
+    }
+}
+
+/// Files [`cluster`] linked, directly or through other members
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cluster {
+    members: Vec<String>,
+}
+
+impl Cluster {
+    /// Names of the files, in the order they were given
+    pub fn members(&self) -> &[String] {
+        &self.members
+    }
+}
+
+impl PortExe {
+    /// Collects the features [`distance`] compares, naming the file `name`.
+    pub fn fingerprint(&self, name: &str) -> io::Result<Fingerprint> {
+        let ordinals = OrdinalMap::bundled();
+        let imports = imphash_names(&self.imports()?, &ordinals);
+        let delay_imports = imphash_names(&self.delay_imports()?, &ordinals);
+
+        let data = self.data();
+        let rich_hash = rich_header_range(data).and_then(|range| {
+            let key = read_u32(data, range.end - 4)?;
+            let clear: Vec<u8> = data[range.start..range.end - 8]
+                .chunks_exact(4)
+                .flat_map(|word| (read_u32(word, 0).unwrap_or(0) ^ key).to_le_bytes())
+                .collect();
+            Some(to_hex(&md5(&clear)))
+        });
+
+        let sections = self
+            .section_headers()
+            .iter()
+            .map(|section| {
+                (
+                    section.name().into_value(),
+                    section.virtual_size().into_value(),
+                )
+            })
+            .collect();
+        let exports = self
+            .exports()?
+            .into_iter()
+            .filter_map(|export| export.name().map(ToString::to_string))
+            .collect();
+
+        Ok(Fingerprint {
+            name: name.to_string(),
+            imports,
+            delay_imports,
+            rich_hash,
+            sections,
+            exports,
+        })
+    }
+}
+
+/// Names `descriptors` import as `library.function`, lowercased, with the library
+/// extensions imphash drops removed and ordinals named where `ordinals` knows them
+fn imphash_names(descriptors: &[ImportDescriptor], ordinals: &OrdinalMap) -> Vec<String> {
+    let mut names = Vec::new();
+    for descriptor in descriptors {
+        let library = descriptor.dll_name().to_ascii_lowercase();
+        let library = match library.rsplit_once('.') {
+            Some((stem, extension)) if IMPHASH_EXTENSIONS.contains(&extension) => stem,
+            _ => &library,
+        };
+        for import in descriptor.imports() {
+            let function = match (import.name(), import.ordinal()) {
+                (Some(name), _) => name.to_string(),
+                (None, Some(ordinal)) => ordinals
+                    .name(library, ordinal)
+                    .map_or_else(|| format!("ord{}", ordinal), str::to_string),
+                (None, None) => continue,
+            };
+            names.push(format!("{}.{}", library, function.to_ascii_lowercase()));
+        }
+    }
+    names
+}
+
+fn import_hash(names: &[String]) -> Option<String> {
+    (!names.is_empty()).then(|| to_hex(&md5(names.join(",").as_bytes())))
+}
+
+/// Weighted distance between two files, from 0 for structurally identical files to 1.
+///
+/// Imports, delay-loaded ones included, and exports are compared as sets (Jaccard
+/// distance), Rich headers by hash, and sections by name with the relative difference
+/// of their sizes. A feature neither file has is left out of the weighting; one only a
+/// single file has counts as distance 1.
+pub fn distance(a: &Fingerprint, b: &Fingerprint) -> f64 {
+    let imports = |fingerprint: &Fingerprint| -> BTreeSet<String> {
+        fingerprint
+            .imports
+            .iter()
+            .chain(&fingerprint.delay_imports)
+            .cloned()
+            .collect()
+    };
+    let features = [
+        jaccard(&imports(a), &imports(b)),
+        match (&a.rich_hash, &b.rich_hash) {
+            (None, None) => None,
+            (a, b) => Some(if a == b { 0.0 } else { 1.0 }),
+        },
+        section_distance(&a.sections, &b.sections),
+        jaccard(&a.exports, &b.exports),
+    ];
+    let (total, weight) = features
+        .iter()
+        .zip(WEIGHTS)
+        .filter_map(|(feature, weight)| feature.map(|distance| (distance * weight, weight)))
+        .fold((0.0, 0.0), |(total, weights), (distance, weight)| {
+            (total + distance, weights + weight)
+        });
+    if weight == 0.0 {
+        0.0
+    } else {
+        total / weight
+    }
+}
+
+/// Groups files whose [`distance`] is below `threshold`, linking clusters through any
+/// close pair (single linkage). Clusters are ordered by their first member; files close
+/// to no other file form clusters of their own.
+pub fn cluster(fingerprints: &[Fingerprint], threshold: f64) -> Vec<Cluster> {
+    let mut parents: Vec<usize> = (0..fingerprints.len()).collect();
+    fn root(parents: &mut [usize], mut index: usize) -> usize {
+        while parents[index] != index {
+            parents[index] = parents[parents[index]];
+            index = parents[index];
+        }
+        index
+    }
+    for (i, a) in fingerprints.iter().enumerate() {
+        for (j, b) in fingerprints.iter().enumerate().skip(i + 1) {
+            if distance(a, b) < threshold {
+                let (i, j) = (root(&mut parents, i), root(&mut parents, j));
+                parents[i.max(j)] = i.min(j);
+            }
+        }
+    }
+    let mut clusters: Vec<(usize, Cluster)> = Vec::new();
+    for (index, fingerprint) in fingerprints.iter().enumerate() {
+        let root = root(&mut parents, index);
+        match clusters.iter_mut().find(|(other, _)| *other == root) {
+            Some((_, cluster)) => cluster.members.push(fingerprint.name.clone()),
+            None => clusters.push((
+                root,
+                Cluster {
+                    members: vec![fingerprint.name.clone()],
+                },
+            )),
+        }
+    }
+    clusters.into_iter().map(|(_, cluster)| cluster).collect()
+}
+
+/// Jaccard distance of two sets, `None` if both are empty
+fn jaccard(a: &BTreeSet<String>, b: &BTreeSet<String>) -> Option<f64> {
+    let union = a.union(b).count();
+    if union == 0 {
+        return None;
+    }
+    let shared = a.intersection(b).count();
+    Some(1.0 - shared as f64 / union as f64)
+}
+
+/// Mean over all section names of the relative size difference, 1 for a section only
+/// one file has. Repeated names are paired in table order.
+fn section_distance(a: &[(PeString, u32)], b: &[(PeString, u32)]) -> Option<f64> {
+    if a.is_empty() && b.is_empty() {
+        return None;
+    }
+    let mut unmatched: Vec<&(PeString, u32)> = b.iter().collect();
+    let mut total = 0.0;
+    let mut count = 0;
+    for (name, size) in a {
+        count += 1;
+        match unmatched.iter().position(|(other, _)| other == name) {
+            Some(index) => {
+                let other = unmatched.remove(index).1;
+                let larger = (*size).max(other);
+                if larger != 0 {
+                    total += (*size as f64 - other as f64).abs() / larger as f64;
+                }
+            }
+            None => total += 1.0,
+        }
+    }
+    count += unmatched.len();
+    total += unmatched.len() as f64;
+    Some(total / count as f64)
+}
@@ -0,0 +1,274 @@
+use crate::analysis::entropy;
+use crate::optional_header::{
+    IMAGE_DIRECTORY_ENTRY_BOUND_IMPORT, IMAGE_DIRECTORY_ENTRY_GLOBALPTR,
+    IMAGE_DIRECTORY_ENTRY_SECURITY, IMAGE_NUMBEROF_DIRECTORY_ENTRIES,
+};
+use crate::pe_string::PeString;
+use crate::port_exe::PortExe;
+use crate::section_header::IMAGE_SIZEOF_SECTION_HEADER;
+use std::fmt;
+use std::io;
+use std::ops::Range;
+
+/// Shortest run of printable ASCII reported as a string
+pub const MIN_STRING_LENGTH: usize = 4;
+/// Longest gap between two data directories still taken as alignment padding
+pub const MAX_DIRECTORY_GAP: usize = 16;
+
+/// Where a region the loader ignores lies
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SlackKind {
+    /// Between the section table (or bound imports) and the end of the headers
+    HeaderPadding,
+    /// Raw data of a section past its `VirtualSize`, which is never mapped
+    SectionTail { section: PeString },
+    /// File data before the last section that neither the headers, a section nor a
+    /// file-offset payload covers
+    FileGap,
+    /// Padding between two data directories of one section, by directory index
+    DirectoryGap { before: usize, after: usize },
+}
+
+impl fmt::Display for SlackKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HeaderPadding => write!(f, "header padding"),
+            Self::SectionTail { section } => {
+                write!(f, "tail of {} past VirtualSize", section.to_string_lossy())
+            }
+            Self::FileGap => write!(f, "gap between sections"),
+            Self::DirectoryGap { before, after } => {
+                write!(f, "gap between directories {} and {}", before, after)
+            }
+        }
+    }
+}
+
+/// Range of the file the loader does not read, with figures on what it holds
+#[derive(Debug, Clone)]
+pub struct SlackRegion {
+    kind: SlackKind,
+    range: Range<usize>,
+    nonzero: usize,
+    entropy: f64,
+    strings: Vec<(usize, String)>,
+}
+
+impl SlackRegion {
+    pub fn kind(&self) -> &SlackKind {
+        &self.kind
+    }
+
+    /// File range of the region
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// Number of bytes that are not zero
+    pub fn nonzero_bytes(&self) -> usize {
+        self.nonzero
+    }
+
+    /// Whether the region holds nothing but zeros, as linkers leave it
+    pub fn is_zero(&self) -> bool {
+        self.nonzero == 0
+    }
+
+    /// Shannon entropy of the region in bits per byte
+    pub fn entropy(&self) -> f64 {
+        self.entropy
+    }
+
+    /// Runs of at least [`MIN_STRING_LENGTH`] printable ASCII characters, with their
+    /// file offsets
+    pub fn strings(&self) -> &[(usize, String)] {
+        &self.strings
+    }
+}
+
+impl PortExe {
+    /// Regions of the file the loader never reads: padding after the headers, section
+    /// data past `VirtualSize`, gaps between sections and alignment gaps between data
+    /// directories, sorted by offset. Tools and malware hide data there, so zero-filled
+    /// regions are listed too and [`SlackRegion::is_zero`] tells them apart.
+    ///
+    /// The overlay after the last section is not included.
+    pub fn slack_regions(&self) -> io::Result<Vec<SlackRegion>> {
+        if self.layout().is_mapped() {
+            return Err(invalid_input("mapped images have no file layout"));
+        }
+        let header = self
+            .optional_header()
+            .ok_or_else(|| invalid_input("object files are not loaded"))?;
+        let data = self.data();
+        let size_of_headers = (header.size_of_headers() as usize).min(data.len());
+        let raw_sections: Vec<Range<usize>> = self
+            .section_headers()
+            .iter()
+            .filter(|section| section.size_of_raw_data().into_value() != 0)
+            .map(|section| {
+                let start = (section.pointer_to_raw_data().into_value() as usize).min(data.len());
+                let size = section.size_of_raw_data().into_value() as usize;
+                start..start.saturating_add(size).min(data.len())
+            })
+            .collect();
+
+        let mut regions = Vec::new();
+        let table_end = self
+            .section_headers()
+            .last()
+            .map_or(size_of_headers, |section| {
+                section.name().offset() as usize + IMAGE_SIZEOF_SECTION_HEADER as usize
+            });
+        let bound_end = header
+            .data_directory(IMAGE_DIRECTORY_ENTRY_BOUND_IMPORT)
+            .map_or(0, |directory| {
+                directory.virtual_address().into_value() as usize
+                    + directory.size().into_value() as usize
+            });
+        let headers_end = raw_sections
+            .iter()
+            .map(|range| range.start)
+            .fold(size_of_headers, usize::min);
+        regions.push((
+            SlackKind::HeaderPadding,
+            table_end.max(bound_end)..headers_end,
+        ));
+
+        for section in self.section_headers() {
+            let virtual_size = section.virtual_size().into_value() as usize;
+            let raw = section.size_of_raw_data().into_value() as usize;
+            if virtual_size == 0 || virtual_size >= raw {
+                continue;
+            }
+            let start = section.pointer_to_raw_data().into_value() as usize;
+            regions.push((
+                SlackKind::SectionTail {
+                    section: section.name().into_value(),
+                },
+                start.saturating_add(virtual_size)..start.saturating_add(raw),
+            ));
+        }
+
+        // Payloads located by file offset count as used wherever they are
+        let mut covered = raw_sections.clone();
+        covered.push(0..size_of_headers);
+        covered.extend(self.symbol_tables_extent());
+        if let Some(directory) = header.data_directory(IMAGE_DIRECTORY_ENTRY_SECURITY) {
+            let offset = directory.virtual_address().into_value() as usize;
+            covered.push(offset..offset.saturating_add(directory.size().into_value() as usize));
+        }
+        for entry in self.debug_directory().unwrap_or_default() {
+            let offset = entry.pointer_to_raw_data() as usize;
+            covered.push(offset..offset.saturating_add(entry.size_of_data() as usize));
+        }
+        covered.sort_by_key(|range| range.start);
+        let sections_end = raw_sections
+            .iter()
+            .map(|range| range.end)
+            .max()
+            .unwrap_or(0);
+        let mut position = 0;
+        for range in &covered {
+            if range.start > position && range.start <= sections_end {
+                regions.push((SlackKind::FileGap, position..range.start));
+            }
+            position = position.max(range.end);
+        }
+
+        regions.extend(self.directory_gaps());
+
+        let mut regions: Vec<SlackRegion> = regions
+            .into_iter()
+            .filter_map(|(kind, range)| {
+                let range = range.start..range.end.min(data.len());
+                let bytes = data.get(range.clone()).filter(|bytes| !bytes.is_empty())?;
+                Some(SlackRegion {
+                    kind,
+                    nonzero: bytes.iter().filter(|&&byte| byte != 0).count(),
+                    entropy: entropy(bytes),
+                    strings: printable_strings(bytes, range.start),
+                    range,
+                })
+            })
+            .collect();
+        regions.sort_by_key(|region| region.range.start);
+        Ok(regions)
+    }
+
+    /// Bytes of a region returned by [`PortExe::slack_regions`]
+    pub fn slack_data(&self, region: &SlackRegion) -> &[u8] {
+        self.data().get(region.range()).unwrap_or(&[])
+    }
+
+    /// Gaps of at most [`MAX_DIRECTORY_GAP`] bytes between consecutive data directories
+    /// of the same section
+    fn directory_gaps(&self) -> Vec<(SlackKind, Range<usize>)> {
+        let header = match self.optional_header() {
+            Some(header) => header,
+            None => return Vec::new(),
+        };
+        let mut directories: Vec<(usize, u32, u32)> = header
+            .data_directories()
+            .iter()
+            .take(IMAGE_NUMBEROF_DIRECTORY_ENTRIES as usize)
+            .enumerate()
+            .filter(|&(index, _)| {
+                // File offsets, and a GlobalPtr entry whose Size means nothing
+                index != IMAGE_DIRECTORY_ENTRY_SECURITY
+                    && index != IMAGE_DIRECTORY_ENTRY_BOUND_IMPORT
+                    && index != IMAGE_DIRECTORY_ENTRY_GLOBALPTR
+            })
+            .map(|(index, directory)| {
+                let rva = directory.virtual_address().into_value();
+                (
+                    index,
+                    rva,
+                    rva.saturating_add(directory.size().into_value()),
+                )
+            })
+            .filter(|&(_, rva, end)| rva != 0 && end > rva)
+            .collect();
+        directories.sort_by_key(|&(index, rva, _)| (rva, index));
+
+        let mut gaps = Vec::new();
+        for pair in directories.windows(2) {
+            let (before, _, end) = pair[0];
+            let (after, next, _) = pair[1];
+            if next <= end || (next - end) as usize > MAX_DIRECTORY_GAP {
+                continue;
+            }
+            let same_section = match (self.section_at_rva(end), self.section_at_rva(next - 1)) {
+                (Some(first), Some(second)) => std::ptr::eq(first, second),
+                _ => false,
+            };
+            let start = self.rva_to_offset(end);
+            let stop = self.rva_to_offset(next - 1).map(|offset| offset + 1);
+            if let (true, Some(start), Some(stop)) = (same_section, start, stop) {
+                gaps.push((SlackKind::DirectoryGap { before, after }, start..stop));
+            }
+        }
+        gaps
+    }
+}
+
+fn printable_strings(data: &[u8], base: usize) -> Vec<(usize, String)> {
+    let mut strings = Vec::new();
+    let mut start = 0;
+    for (index, &byte) in data.iter().chain(&[0]).enumerate() {
+        if byte == b' ' || byte.is_ascii_graphic() {
+            continue;
+        }
+        if index - start >= MIN_STRING_LENGTH {
+            let text = String::from_utf8_lossy(&data[start..index]).into_owned();
+            strings.push((base + start, text));
+        }
+        start = index + 1;
+    }
+    strings
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
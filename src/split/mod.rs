@@ -0,0 +1,309 @@
+use crate::arith::slice_at;
+use crate::debug::{
+    DebugDirectoryEntry, IMAGE_DEBUG_TYPE_CODEVIEW, IMAGE_DEBUG_TYPE_EX_DLLCHARACTERISTICS,
+    IMAGE_DEBUG_TYPE_MISC, IMAGE_SIZEOF_DEBUG_DIRECTORY,
+};
+use crate::file_header::IMAGE_FILE_DEBUG_STRIPPED;
+use crate::optional_header::IMAGE_DIRECTORY_ENTRY_DEBUG;
+use crate::port_exe::PortExe;
+use crate::strip::{truncate_trailing, zero};
+use crate::{read_u16, read_u32, write_at};
+use std::io;
+
+/// `IMAGE_DEBUG_MISC` data naming the file the debug information was moved to
+pub const IMAGE_DEBUG_MISC_EXENAME: u32 = 1;
+/// Magic bytes starting a debug container
+pub const DEBUG_CONTAINER_MAGIC: [u8; 8] = *b"PEXPDBG\0";
+/// Layout version of debug containers written by this crate
+pub const DEBUG_CONTAINER_VERSION: u32 = 1;
+
+const CONTAINER_HEADER_SIZE: usize = 32;
+/// Size of `IMAGE_DEBUG_MISC` without its name
+const IMAGE_SIZEOF_DEBUG_MISC: usize = 12;
+
+/// Debug directory entry moved out of an image, with its payload
+#[derive(Debug, Clone)]
+pub struct DebugPayload {
+    entry: DebugDirectoryEntry,
+    data: Vec<u8>,
+}
+
+impl DebugPayload {
+    /// The entry as it was in the image
+    pub fn entry(&self) -> &DebugDirectoryEntry {
+        &self.entry
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// Sidecar file holding the debug information split off an image.
+///
+/// All fields are little-endian:
+///
+/// | Offset | Size | Field                                                |
+/// |--------|------|------------------------------------------------------|
+/// | 0      | 8    | [`DEBUG_CONTAINER_MAGIC`]                            |
+/// | 8      | 4    | [`DEBUG_CONTAINER_VERSION`]                          |
+/// | 12     | 4    | `TimeDateStamp` of the image                         |
+/// | 16     | 4    | `SizeOfImage` of the image                           |
+/// | 20     | 4    | Number of debug directory entries                    |
+/// | 24     | 4    | `NumberOfSymbols` of the COFF symbol table           |
+/// | 28     | 4    | Size of the COFF symbol and string tables            |
+/// | 32     |      | Each `IMAGE_DEBUG_DIRECTORY` entry followed by its payload, then the symbol and string tables |
+///
+/// The timestamp and image size identify the image, as they did for `.dbg` files.
+#[derive(Debug, Clone, Default)]
+pub struct DebugContainer {
+    time_date_stamp: u32,
+    size_of_image: u32,
+    payloads: Vec<DebugPayload>,
+    number_of_symbols: u32,
+    symbols: Vec<u8>,
+}
+
+impl DebugContainer {
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        if data.get(..8) != Some(&DEBUG_CONTAINER_MAGIC[..]) {
+            return Err(invalid_data("not a debug container"));
+        }
+        let field = |offset| {
+            read_u32(data, offset).ok_or_else(|| invalid_data("debug container is truncated"))
+        };
+        if field(8)? != DEBUG_CONTAINER_VERSION {
+            return Err(invalid_data("unsupported debug container version"));
+        }
+        let count = field(20)? as usize;
+        let mut offset = CONTAINER_HEADER_SIZE;
+        let mut payloads = Vec::new();
+        for _ in 0..count {
+            let entry = slice_at(data, offset, IMAGE_SIZEOF_DEBUG_DIRECTORY)
+                .map(DebugDirectoryEntry::parse)
+                .ok_or_else(|| invalid_data("debug container entry is truncated"))?;
+            offset += IMAGE_SIZEOF_DEBUG_DIRECTORY;
+            let size = entry.size_of_data() as usize;
+            let payload = slice_at(data, offset, size)
+                .ok_or_else(|| invalid_data("debug container payload is truncated"))?;
+            offset += size;
+            payloads.push(DebugPayload {
+                entry,
+                data: payload.to_vec(),
+            });
+        }
+        let symbols = slice_at(data, offset, field(28)? as usize)
+            .ok_or_else(|| invalid_data("debug container symbols are truncated"))?;
+        Ok(Self {
+            time_date_stamp: field(12)?,
+            size_of_image: field(16)?,
+            payloads,
+            number_of_symbols: field(24)?,
+            symbols: symbols.to_vec(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = DEBUG_CONTAINER_MAGIC.to_vec();
+        for field in [
+            DEBUG_CONTAINER_VERSION,
+            self.time_date_stamp,
+            self.size_of_image,
+            self.payloads.len() as u32,
+            self.number_of_symbols,
+            self.symbols.len() as u32,
+        ] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        for payload in &self.payloads {
+            data.extend_from_slice(&payload.entry.to_bytes());
+            data.extend_from_slice(&payload.data);
+        }
+        data.extend_from_slice(&self.symbols);
+        data
+    }
+
+    pub fn time_date_stamp(&self) -> u32 {
+        self.time_date_stamp
+    }
+
+    pub fn size_of_image(&self) -> u32 {
+        self.size_of_image
+    }
+
+    pub fn payloads(&self) -> &[DebugPayload] {
+        &self.payloads
+    }
+
+    pub fn number_of_symbols(&self) -> u32 {
+        self.number_of_symbols
+    }
+
+    /// COFF symbol table followed by its string table
+    pub fn symbols(&self) -> &[u8] {
+        &self.symbols
+    }
+
+    /// Whether the container was split off `pe`
+    pub fn matches(&self, pe: &PortExe) -> bool {
+        let size_of_image = pe.optional_header().map_or(0, |h| h.size_of_image());
+        self.time_date_stamp == pe.file_header().time_date_stamp().into_value()
+            && self.size_of_image == size_of_image
+    }
+}
+
+impl PortExe {
+    /// Moves debug directory payloads and the COFF symbol table into a [`DebugContainer`].
+    ///
+    /// `EX_DLLCHARACTERISTICS` entries stay, as do CodeView entries when `keep_codeview`
+    /// is set so debuggers can still find the PDB. The moved entries are replaced by a
+    /// `MISC` entry naming `sidecar`, written over the first moved payload that is
+    /// mapped and large enough, and `IMAGE_FILE_DEBUG_STRIPPED` is set.
+    pub fn split_debug(
+        &mut self,
+        sidecar: &str,
+        keep_codeview: bool,
+    ) -> io::Result<DebugContainer> {
+        if self.layout().is_mapped() {
+            return Err(invalid_input("mapped images cannot be edited"));
+        }
+        let header = self
+            .optional_header()
+            .ok_or_else(|| invalid_input("object files keep their debug information"))?;
+        self.refuse_signed()?;
+
+        let mut container = DebugContainer {
+            time_date_stamp: self.file_header().time_date_stamp().into_value(),
+            size_of_image: header.size_of_image(),
+            ..DebugContainer::default()
+        };
+        let mut data = self.to_bytes();
+        let mut removed = Vec::new();
+
+        let directory = header.data_directory(IMAGE_DIRECTORY_ENTRY_DEBUG);
+        let entries = self.debug_directory()?;
+        let table = directory
+            .as_ref()
+            .and_then(|directory| self.rva_to_offset(directory.virtual_address().into_value()));
+        if let (Some(directory), Some(table)) = (directory, table) {
+            let misc = misc_entry_data(sidecar);
+            let mut kept = Vec::new();
+            let mut slot = None;
+            for entry in &entries {
+                let keep = entry.debug_type() == IMAGE_DEBUG_TYPE_EX_DLLCHARACTERISTICS
+                    || (keep_codeview && entry.debug_type() == IMAGE_DEBUG_TYPE_CODEVIEW);
+                if keep {
+                    kept.push(entry.clone());
+                    continue;
+                }
+                container.payloads.push(DebugPayload {
+                    entry: entry.clone(),
+                    data: self.debug_data(entry).unwrap_or(&[]).to_vec(),
+                });
+                if entry.pointer_to_raw_data() == 0 {
+                    continue;
+                }
+                let payload = entry.pointer_to_raw_data() as usize;
+                let payload = payload..payload.saturating_add(entry.size_of_data() as usize);
+                zero(&mut data, &payload);
+                if slot.is_none()
+                    && entry.address_of_raw_data() != 0
+                    && entry.size_of_data() as usize >= misc.len()
+                {
+                    slot = Some(entry.clone());
+                } else {
+                    removed.push(payload);
+                }
+            }
+
+            if let Some(slot) = slot {
+                write_at(&mut data, slot.pointer_to_raw_data() as u64, &misc);
+                let mut entry = [0u8; IMAGE_SIZEOF_DEBUG_DIRECTORY];
+                entry[4..8].copy_from_slice(&container.time_date_stamp.to_le_bytes());
+                entry[12..16].copy_from_slice(&IMAGE_DEBUG_TYPE_MISC.to_le_bytes());
+                entry[16..20].copy_from_slice(&(misc.len() as u32).to_le_bytes());
+                entry[20..24].copy_from_slice(&slot.address_of_raw_data().to_le_bytes());
+                entry[24..28].copy_from_slice(&slot.pointer_to_raw_data().to_le_bytes());
+                kept.push(DebugDirectoryEntry::parse(&entry));
+            }
+            let encoded: Vec<u8> = kept.iter().flat_map(|entry| entry.to_bytes()).collect();
+            let table = table..table + entries.len() * IMAGE_SIZEOF_DEBUG_DIRECTORY;
+            zero(&mut data, &table);
+            write_at(&mut data, table.start as u64, &encoded);
+            if encoded.is_empty() {
+                write_at(&mut data, directory.virtual_address().offset(), &[0; 4]);
+            }
+            write_at(
+                &mut data,
+                directory.size().offset(),
+                &(encoded.len() as u32).to_le_bytes(),
+            );
+        }
+
+        if let Some(symbols) = self.symbol_tables_to_split() {
+            let file_header = self.file_header();
+            container.number_of_symbols = file_header.number_of_symbols().into_value();
+            container.symbols = self.data()[symbols.clone()].to_vec();
+            zero(&mut data, &symbols);
+            removed.push(symbols);
+            write_at(
+                &mut data,
+                file_header.pointer_to_symbol_table().offset(),
+                &[0; 4],
+            );
+            write_at(&mut data, file_header.number_of_symbols().offset(), &[0; 4]);
+        }
+        if container.payloads.is_empty() && container.symbols.is_empty() {
+            return Err(invalid_input("image has no debug information to split off"));
+        }
+
+        let characteristics = self.file_header().characteristics();
+        let flags = read_u16(&data, characteristics.offset() as usize).unwrap_or(0)
+            | IMAGE_FILE_DEBUG_STRIPPED;
+        write_at(&mut data, characteristics.offset(), &flags.to_le_bytes());
+
+        let sections_end = self
+            .section_headers()
+            .iter()
+            .map(|section| {
+                section.pointer_to_raw_data().into_value() as usize
+                    + section.size_of_raw_data().into_value() as usize
+            })
+            .fold(header.size_of_headers() as usize, usize::max);
+        truncate_trailing(&mut data, &removed, sections_end);
+
+        *self = PortExe::from_bytes(data)?;
+        self.refresh_checksum();
+        Ok(container)
+    }
+
+    /// Symbol and string tables, if they can be moved without breaking section names
+    fn symbol_tables_to_split(&self) -> Option<std::ops::Range<usize>> {
+        let symbols = self.symbol_tables_extent()?;
+        if self.has_long_section_names() || symbols.end > self.data().len() {
+            return None;
+        }
+        Some(symbols)
+    }
+}
+
+/// `IMAGE_DEBUG_MISC` record naming `name`, padded to a multiple of 4 bytes
+fn misc_entry_data(name: &str) -> Vec<u8> {
+    let length = (IMAGE_SIZEOF_DEBUG_MISC + name.len() + 1 + 3) & !3;
+    let mut data = Vec::with_capacity(length);
+    data.extend_from_slice(&IMAGE_DEBUG_MISC_EXENAME.to_le_bytes());
+    data.extend_from_slice(&(length as u32).to_le_bytes());
+    // Unicode flag and three reserved bytes
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(name.as_bytes());
+    data.resize(length, 0);
+    data
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
@@ -0,0 +1,350 @@
+use crate::debug::{IMAGE_DEBUG_TYPE_EX_DLLCHARACTERISTICS, IMAGE_SIZEOF_DEBUG_DIRECTORY};
+use crate::file_header::IMAGE_FILE_RELOCS_STRIPPED;
+use crate::optional_header::{
+    OptionalHeaderWrapper, IMAGE_DIRECTORY_ENTRY_BASERELOC, IMAGE_DIRECTORY_ENTRY_DEBUG,
+    IMAGE_DIRECTORY_ENTRY_SECURITY, IMAGE_DLLCHARACTERISTICS_DYNAMIC_BASE,
+    IMAGE_DLLCHARACTERISTICS_HIGH_ENTROPY_VA,
+};
+use crate::port_exe::PortExe;
+use crate::rich::rich_header_range;
+use crate::section_header::IMAGE_SIZEOF_SECTION_HEADER;
+use crate::symbol::IMAGE_SIZEOF_SYMBOL;
+use crate::{read_u16, read_u32, write_at};
+use std::io;
+use std::ops::Range;
+
+/// What [`PortExe::strip`] removes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StripOptions {
+    /// Debug directory entries and their payloads, except `EX_DLLCHARACTERISTICS`
+    pub debug: bool,
+    /// The Rich header the Microsoft linker writes after the DOS stub
+    pub rich_header: bool,
+    /// The COFF symbol and string tables
+    pub symbols: bool,
+    /// Base relocations of executables, which then always load at their preferred base
+    pub relocations: bool,
+}
+
+impl Default for StripOptions {
+    fn default() -> Self {
+        Self {
+            debug: true,
+            rich_header: true,
+            symbols: true,
+            relocations: false,
+        }
+    }
+}
+
+/// What [`PortExe::strip`] removed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StripReport {
+    debug_entries: usize,
+    rich_header: bool,
+    symbols: u32,
+    relocations: bool,
+    bytes_removed: usize,
+    skipped: Vec<String>,
+}
+
+impl StripReport {
+    /// Number of debug directory entries removed
+    pub fn debug_entries(&self) -> usize {
+        self.debug_entries
+    }
+
+    pub fn rich_header(&self) -> bool {
+        self.rich_header
+    }
+
+    /// Number of COFF symbol records removed
+    pub fn symbols(&self) -> u32 {
+        self.symbols
+    }
+
+    pub fn relocations(&self) -> bool {
+        self.relocations
+    }
+
+    /// How much smaller the file became
+    pub fn bytes_removed(&self) -> usize {
+        self.bytes_removed
+    }
+
+    /// Reasons requested parts were left in place
+    pub fn skipped(&self) -> &[String] {
+        &self.skipped
+    }
+
+    /// Whether anything was removed
+    pub fn is_empty(&self) -> bool {
+        self.debug_entries == 0 && !self.rich_header && self.symbols == 0 && !self.relocations
+    }
+}
+
+impl PortExe {
+    /// Removes the build metadata `options` selects from an image.
+    ///
+    /// Removed data is zeroed where it lives inside a section and cut from the file where
+    /// it trails the last section, and the headers that referenced it are cleared.
+    /// Signed files are refused, as any change invalidates their signature.
+    pub fn strip(&mut self, options: &StripOptions) -> io::Result<StripReport> {
+        if self.layout().is_mapped() {
+            return Err(invalid_input("mapped images cannot be edited"));
+        }
+        let header = self
+            .optional_header()
+            .ok_or_else(|| invalid_input("object files need their symbols to be linked"))?;
+        let signed = header
+            .data_directory(IMAGE_DIRECTORY_ENTRY_SECURITY)
+            .map_or(false, |directory| directory.size().into_value() != 0);
+        if signed {
+            return Err(invalid_input(
+                "file is signed, stripping it would invalidate the signature",
+            ));
+        }
+
+        let mut data = self.to_bytes();
+        let mut removed = Vec::new();
+        let mut report = StripReport::default();
+        let mut removed_section = None;
+        if options.debug {
+            report.debug_entries = self.strip_debug(&mut data, &mut removed)?;
+        }
+        if options.rich_header {
+            if let Some(range) = rich_header_range(&data) {
+                zero(&mut data, &range);
+                report.rich_header = true;
+            }
+        }
+        if options.symbols {
+            self.strip_symbols(&mut data, &mut removed, &mut report);
+        }
+        if options.relocations {
+            report.relocations =
+                self.strip_relocations(&mut data, &mut removed, &mut removed_section)?;
+        }
+
+        let sections_end = self
+            .section_headers()
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| Some(*index) != removed_section)
+            .map(|(_, section)| {
+                section.pointer_to_raw_data().into_value() as usize
+                    + section.size_of_raw_data().into_value() as usize
+            })
+            .fold(header.size_of_headers() as usize, usize::max);
+        let length = data.len();
+        truncate_trailing(&mut data, &removed, sections_end);
+        report.bytes_removed = length - data.len();
+
+        *self = PortExe::from_bytes(data)?;
+        self.refresh_checksum();
+        Ok(report)
+    }
+
+    /// Keeps only the `EX_DLLCHARACTERISTICS` entries, which carry mitigation flags
+    /// rather than build metadata, and returns how many entries were dropped.
+    fn strip_debug(&self, data: &mut [u8], removed: &mut Vec<Range<usize>>) -> io::Result<usize> {
+        let directory = match self
+            .optional_header()
+            .and_then(|h| h.data_directory(IMAGE_DIRECTORY_ENTRY_DEBUG))
+        {
+            Some(directory) => directory,
+            None => return Ok(0),
+        };
+        let entries = self.debug_directory()?;
+        let table = match self.rva_to_offset(directory.virtual_address().into_value()) {
+            Some(offset) if !entries.is_empty() => offset,
+            _ => return Ok(0),
+        };
+
+        let mut kept = Vec::new();
+        for (index, entry) in entries.iter().enumerate() {
+            let start = table + index * IMAGE_SIZEOF_DEBUG_DIRECTORY;
+            if entry.debug_type() == IMAGE_DEBUG_TYPE_EX_DLLCHARACTERISTICS {
+                kept.extend_from_slice(&data[start..start + IMAGE_SIZEOF_DEBUG_DIRECTORY]);
+            } else if entry.pointer_to_raw_data() != 0 {
+                let payload = entry.pointer_to_raw_data() as usize;
+                let payload = payload..payload.saturating_add(entry.size_of_data() as usize);
+                zero(data, &payload);
+                removed.push(payload);
+            }
+        }
+        let table = table..table + entries.len() * IMAGE_SIZEOF_DEBUG_DIRECTORY;
+        zero(data, &table);
+        write_at(data, table.start as u64, &kept);
+
+        let size = kept.len() as u32;
+        if size == 0 {
+            write_at(data, directory.virtual_address().offset(), &[0; 4]);
+        }
+        write_at(data, directory.size().offset(), &size.to_le_bytes());
+        Ok(entries.len() - kept.len() / IMAGE_SIZEOF_DEBUG_DIRECTORY)
+    }
+
+    fn strip_symbols(
+        &self,
+        data: &mut [u8],
+        removed: &mut Vec<Range<usize>>,
+        report: &mut StripReport,
+    ) {
+        let table = match self.symbol_tables_extent() {
+            Some(table) => table,
+            None => return,
+        };
+        if self.has_long_section_names() {
+            report
+                .skipped
+                .push("symbols: section names refer to the string table".to_string());
+            return;
+        }
+        zero(data, &table);
+        removed.push(table);
+        let file_header = self.file_header();
+        write_at(
+            data,
+            file_header.pointer_to_symbol_table().offset(),
+            &[0; 4],
+        );
+        write_at(data, file_header.number_of_symbols().offset(), &[0; 4]);
+        report.symbols = file_header.number_of_symbols().into_value();
+    }
+
+    /// File range of the COFF symbol table together with the string table after it
+    pub(crate) fn symbol_tables_extent(&self) -> Option<Range<usize>> {
+        let file_header = self.file_header();
+        let pointer = file_header.pointer_to_symbol_table().into_value() as usize;
+        let count = file_header.number_of_symbols().into_value() as usize;
+        if pointer == 0 {
+            return None;
+        }
+        let strings = pointer.saturating_add(count.saturating_mul(IMAGE_SIZEOF_SYMBOL));
+        let strings_size = read_u32(self.data(), strings).map_or(0, |size| size as usize);
+        Some(pointer..strings.saturating_add(strings_size.max(4)))
+    }
+
+    /// Whether section names longer than 8 bytes, which GNU linkers store in the string
+    /// table, keep the symbol table from being removed
+    pub(crate) fn has_long_section_names(&self) -> bool {
+        self.section_headers()
+            .iter()
+            .any(|section| section.name().into_value().as_bytes().starts_with(b"/"))
+    }
+
+    /// Clears the base relocation directory and marks the image as fixed at its base.
+    ///
+    /// A relocation section at the end of the image is removed altogether, and its
+    /// index stored in `removed_section`. Returns `false` if there were no relocations.
+    fn strip_relocations(
+        &self,
+        data: &mut [u8],
+        removed: &mut Vec<Range<usize>>,
+        removed_section: &mut Option<usize>,
+    ) -> io::Result<bool> {
+        let file_header = self.file_header();
+        let characteristics = file_header.characteristics();
+        if characteristics.value().dynamic_link_library() {
+            return Err(invalid_input(
+                "DLLs need their relocations to load at another base",
+            ));
+        }
+        let header = self
+            .optional_header()
+            .ok_or_else(|| invalid_input("object files have no base relocations"))?;
+        let directory = match header.data_directory(IMAGE_DIRECTORY_ENTRY_BASERELOC) {
+            Some(directory) => directory,
+            None => return Ok(false),
+        };
+        let rva = directory.virtual_address().into_value();
+        let size = directory.size().into_value();
+        if rva == 0 || size == 0 {
+            return Ok(false);
+        }
+
+        let sections = self.section_headers();
+        let last = sections.len().checked_sub(1);
+        let section = sections
+            .iter()
+            .position(|section| section.virtual_address().into_value() == rva);
+        let whole_section = section.filter(|&index| {
+            Some(index) == last
+                && sections.iter().all(|other| {
+                    other.pointer_to_raw_data().into_value()
+                        <= sections[index].pointer_to_raw_data().into_value()
+                })
+        });
+        match whole_section {
+            Some(index) => {
+                let section = &sections[index];
+                let start = section.pointer_to_raw_data().into_value() as usize;
+                let raw = start..start + section.size_of_raw_data().into_value() as usize;
+                zero(data, &raw);
+                removed.push(raw);
+                let entry = section.name().offset() as usize;
+                zero(data, &(entry..entry + IMAGE_SIZEOF_SECTION_HEADER as usize));
+
+                let count = (sections.len() - 1) as u16;
+                write_at(
+                    data,
+                    file_header.number_of_sections().offset(),
+                    &count.to_le_bytes(),
+                );
+                let size_of_image = match header {
+                    OptionalHeaderWrapper::X32(h) => h.size_of_image().offset(),
+                    OptionalHeaderWrapper::X64(h) => h.size_of_image().offset(),
+                };
+                write_at(
+                    data,
+                    size_of_image,
+                    &section.virtual_address().into_value().to_le_bytes(),
+                );
+            }
+            None => {
+                if let Some(offset) = self.rva_to_offset(rva) {
+                    zero(data, &(offset..offset.saturating_add(size as usize)));
+                }
+            }
+        }
+        write_at(data, directory.virtual_address().offset(), &[0; 8]);
+
+        let flags = read_u16(data, characteristics.offset() as usize).unwrap_or(0)
+            | IMAGE_FILE_RELOCS_STRIPPED;
+        write_at(data, characteristics.offset(), &flags.to_le_bytes());
+        let dll_characteristics = match header {
+            OptionalHeaderWrapper::X32(h) => h.dll_characteristics(),
+            OptionalHeaderWrapper::X64(h) => h.dll_characteristics(),
+        };
+        let flags = *dll_characteristics.value()
+            & !(IMAGE_DLLCHARACTERISTICS_DYNAMIC_BASE | IMAGE_DLLCHARACTERISTICS_HIGH_ENTROPY_VA);
+        write_at(data, dll_characteristics.offset(), &flags.to_le_bytes());
+        *removed_section = whole_section;
+        Ok(true)
+    }
+}
+
+pub(crate) fn zero(data: &mut [u8], range: &Range<usize>) {
+    let end = range.end.min(data.len());
+    let start = range.start.min(end);
+    data[start..end].iter_mut().for_each(|byte| *byte = 0);
+}
+
+/// Cuts removed ranges, and the zero padding after them, from the end of the file
+/// as long as they lie past the last section.
+pub(crate) fn truncate_trailing(data: &mut Vec<u8>, removed: &[Range<usize>], sections_end: usize) {
+    while let Some(start) = removed
+        .iter()
+        .map(|range| range.start)
+        .filter(|&start| start >= sections_end && start < data.len())
+        .filter(|&start| data[start..].iter().all(|&byte| byte == 0))
+        .min()
+    {
+        data.truncate(start);
+    }
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
@@ -0,0 +1,269 @@
+use crate::arith::{table_at, table_range};
+use crate::pe_string::PeString;
+use crate::port_exe::PortExe;
+use crate::section_header::SectionHeaderWrapper;
+use crate::{read_u16, read_u32};
+use std::io;
+
+/// Size of a symbol table record, including auxiliary records
+pub const IMAGE_SIZEOF_SYMBOL: usize = 18;
+
+/// Symbol is not yet assigned a section
+pub const IMAGE_SYM_UNDEFINED: i16 = 0;
+/// Symbol has an absolute (non-relocatable) value
+pub const IMAGE_SYM_ABSOLUTE: i16 = -1;
+/// Symbol provides general type or debugging information
+pub const IMAGE_SYM_DEBUG: i16 = -2;
+
+/// Complex type of a function symbol, stored in bits 4-5 of the symbol type
+pub const IMAGE_SYM_DTYPE_FUNCTION: u16 = 2;
+
+pub const IMAGE_SYM_CLASS_END_OF_FUNCTION: u8 = 0xFF;
+pub const IMAGE_SYM_CLASS_NULL: u8 = 0;
+pub const IMAGE_SYM_CLASS_AUTOMATIC: u8 = 1;
+pub const IMAGE_SYM_CLASS_EXTERNAL: u8 = 2;
+pub const IMAGE_SYM_CLASS_STATIC: u8 = 3;
+pub const IMAGE_SYM_CLASS_REGISTER: u8 = 4;
+pub const IMAGE_SYM_CLASS_EXTERNAL_DEF: u8 = 5;
+pub const IMAGE_SYM_CLASS_LABEL: u8 = 6;
+pub const IMAGE_SYM_CLASS_UNDEFINED_LABEL: u8 = 7;
+pub const IMAGE_SYM_CLASS_MEMBER_OF_STRUCT: u8 = 8;
+pub const IMAGE_SYM_CLASS_ARGUMENT: u8 = 9;
+pub const IMAGE_SYM_CLASS_STRUCT_TAG: u8 = 10;
+pub const IMAGE_SYM_CLASS_MEMBER_OF_UNION: u8 = 11;
+pub const IMAGE_SYM_CLASS_UNION_TAG: u8 = 12;
+pub const IMAGE_SYM_CLASS_TYPE_DEFINITION: u8 = 13;
+pub const IMAGE_SYM_CLASS_UNDEFINED_STATIC: u8 = 14;
+pub const IMAGE_SYM_CLASS_ENUM_TAG: u8 = 15;
+pub const IMAGE_SYM_CLASS_MEMBER_OF_ENUM: u8 = 16;
+pub const IMAGE_SYM_CLASS_REGISTER_PARAM: u8 = 17;
+pub const IMAGE_SYM_CLASS_BIT_FIELD: u8 = 18;
+pub const IMAGE_SYM_CLASS_BLOCK: u8 = 100;
+pub const IMAGE_SYM_CLASS_FUNCTION: u8 = 101;
+pub const IMAGE_SYM_CLASS_END_OF_STRUCT: u8 = 102;
+pub const IMAGE_SYM_CLASS_FILE: u8 = 103;
+pub const IMAGE_SYM_CLASS_SECTION: u8 = 104;
+pub const IMAGE_SYM_CLASS_WEAK_EXTERNAL: u8 = 105;
+pub const IMAGE_SYM_CLASS_CLR_TOKEN: u8 = 107;
+
+/// Auxiliary section definition record (auxiliary format 5) of a section symbol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionDefinition {
+    length: u32,
+    number_of_relocations: u16,
+    number_of_linenumbers: u16,
+    checksum: u32,
+    number: u16,
+    selection: u8,
+}
+
+impl SectionDefinition {
+    /// Size of the section data
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    pub fn number_of_relocations(&self) -> u16 {
+        self.number_of_relocations
+    }
+
+    pub fn number_of_linenumbers(&self) -> u16 {
+        self.number_of_linenumbers
+    }
+
+    /// Checksum of the section data, used by `IMAGE_COMDAT_SELECT_EXACT_MATCH`
+    pub fn checksum(&self) -> u32 {
+        self.checksum
+    }
+
+    /// One-based index of the associated section for `IMAGE_COMDAT_SELECT_ASSOCIATIVE`
+    pub fn number(&self) -> u16 {
+        self.number
+    }
+
+    /// `IMAGE_COMDAT_SELECT_*` value, zero for sections that are not COMDAT
+    pub fn selection(&self) -> u8 {
+        self.selection
+    }
+}
+
+/// Record of the COFF symbol table together with its auxiliary records
+#[derive(Debug, Clone)]
+pub struct CoffSymbol {
+    index: u32,
+    name: String,
+    value: u32,
+    section_number: i16,
+    symbol_type: u16,
+    storage_class: u8,
+    aux_data: Vec<u8>,
+}
+
+impl CoffSymbol {
+    /// Index of the symbol in the table, as referenced by relocations
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+
+    /// One-based section index, or one of the `IMAGE_SYM_*` special values
+    pub fn section_number(&self) -> i16 {
+        self.section_number
+    }
+
+    pub fn symbol_type(&self) -> u16 {
+        self.symbol_type
+    }
+
+    /// `IMAGE_SYM_CLASS_*` value
+    pub fn storage_class(&self) -> u8 {
+        self.storage_class
+    }
+
+    /// Raw auxiliary records following the symbol, `IMAGE_SIZEOF_SYMBOL` bytes each
+    pub fn aux_data(&self) -> &[u8] {
+        &self.aux_data
+    }
+
+    pub fn number_of_aux_symbols(&self) -> usize {
+        self.aux_data.len() / IMAGE_SIZEOF_SYMBOL
+    }
+
+    pub fn is_function(&self) -> bool {
+        (self.symbol_type >> 4) & 0x3 == IMAGE_SYM_DTYPE_FUNCTION
+    }
+
+    pub fn is_external(&self) -> bool {
+        self.storage_class == IMAGE_SYM_CLASS_EXTERNAL
+    }
+
+    /// Section symbol carrying an auxiliary section definition record
+    pub fn is_section_definition(&self) -> bool {
+        (self.storage_class == IMAGE_SYM_CLASS_STATIC
+            || self.storage_class == IMAGE_SYM_CLASS_SECTION)
+            && self.value == 0
+            && self.number_of_aux_symbols() > 0
+            && !self.is_function()
+    }
+
+    /// Decodes the auxiliary section definition record of a section symbol
+    pub fn section_definition(&self) -> Option<SectionDefinition> {
+        if !self.is_section_definition() {
+            return None;
+        }
+        let aux = &self.aux_data;
+        Some(SectionDefinition {
+            length: read_u32(aux, 0)?,
+            number_of_relocations: read_u16(aux, 4)?,
+            number_of_linenumbers: read_u16(aux, 6)?,
+            checksum: read_u32(aux, 8)?,
+            number: read_u16(aux, 12)?,
+            selection: *aux.get(14)?,
+        })
+    }
+
+    /// Symbol is defined in a section of this file
+    pub fn is_defined(&self) -> bool {
+        self.section_number > 0
+    }
+}
+
+impl PortExe {
+    /// Reads the COFF symbol table, present in object files and in some unstripped images.
+    pub fn coff_symbols(&self) -> io::Result<Vec<CoffSymbol>> {
+        let (table, count) = match self.symbol_table_range() {
+            Some(range) => range,
+            None => return Ok(Vec::new()),
+        };
+        let data = table_at(self.data(), table, count, IMAGE_SIZEOF_SYMBOL)
+            .ok_or_else(|| invalid_data("symbol table is truncated"))?;
+
+        let mut symbols = Vec::new();
+        let mut index = 0;
+        while index < count {
+            let record = &data[index * IMAGE_SIZEOF_SYMBOL..(index + 1) * IMAGE_SIZEOF_SYMBOL];
+            let name = if read_u32(record, 0) == Some(0) {
+                let offset = read_u32(record, 4).unwrap_or(0);
+                self.string_table_entry(offset)
+                    .ok_or_else(|| invalid_data("symbol name is outside the string table"))?
+            } else {
+                let end = record[..8].iter().position(|&b| b == 0).unwrap_or(8);
+                String::from_utf8_lossy(&record[..end]).into_owned()
+            };
+            let number_of_aux_symbols = (record[17] as usize).min(count - index - 1);
+            let aux_start = (index + 1) * IMAGE_SIZEOF_SYMBOL;
+            let aux_end = aux_start + number_of_aux_symbols * IMAGE_SIZEOF_SYMBOL;
+            symbols.push(CoffSymbol {
+                index: index as u32,
+                name,
+                value: read_u32(record, 8).unwrap_or(0),
+                section_number: read_u16(record, 12).unwrap_or(0) as i16,
+                symbol_type: read_u16(record, 14).unwrap_or(0),
+                storage_class: record[16],
+                aux_data: data[aux_start..aux_end].to_vec(),
+            });
+            index += 1 + number_of_aux_symbols;
+        }
+        Ok(symbols)
+    }
+
+    /// String table following the symbol table, including its 4-byte size field.
+    ///
+    /// Stripped GNU images may keep it behind an empty symbol table for their long
+    /// section names.
+    pub fn string_table(&self) -> Option<&[u8]> {
+        let table = self.file_header().pointer_to_symbol_table().into_value() as usize;
+        let count = self.file_header().number_of_symbols().into_value() as usize;
+        if table == 0 {
+            return None;
+        }
+        let start = table_range(table, count, IMAGE_SIZEOF_SYMBOL, usize::MAX)?.end;
+        let size = read_u32(self.data(), start)? as usize;
+        let end = start.checked_add(size)?.min(self.data().len());
+        self.data().get(start..end)
+    }
+
+    /// Zero-terminated string at `offset` in the string table
+    pub fn string_table_entry(&self, offset: u32) -> Option<String> {
+        let table = self.string_table()?;
+        let data = table.get(offset as usize..)?;
+        let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+        Some(String::from_utf8_lossy(&data[..end]).into_owned())
+    }
+
+    /// Full name of `section`, resolving the `/offset` names GNU linkers give sections
+    /// whose names do not fit in 8 bytes. An offset outside the string table leaves the
+    /// name as stored.
+    pub fn section_name(&self, section: &SectionHeaderWrapper) -> PeString {
+        let name = section.name().into_value();
+        let offset = name
+            .as_str()
+            .and_then(|name| name.strip_prefix('/'))
+            .and_then(|offset| offset.parse::<usize>().ok());
+        let entry = offset.and_then(|offset| self.string_table()?.get(offset..));
+        match entry {
+            Some(data) => {
+                let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+                PeString::from_bytes(&data[..end])
+            }
+            None => name,
+        }
+    }
+
+    fn symbol_table_range(&self) -> Option<(usize, usize)> {
+        let table = self.file_header().pointer_to_symbol_table().into_value() as usize;
+        let count = self.file_header().number_of_symbols().into_value() as usize;
+        (table != 0 && count != 0).then(|| (table, count))
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,861 @@
+use crate::file_header::{
+    IMAGE_FILE_32BIT_MACHINE, IMAGE_FILE_DLL, IMAGE_FILE_EXECUTABLE_IMAGE,
+    IMAGE_FILE_LARGE_ADDRESS_AWARE, IMAGE_FILE_MACHINE_AMD64, IMAGE_FILE_MACHINE_I386,
+};
+use crate::optional_header::{
+    OptionalHeader32Wrapper, OptionalHeader64Wrapper, OptionalHeaderWrapper,
+};
+use crate::optional_header::{
+    IMAGE_DIRECTORY_ENTRY_IAT, IMAGE_DIRECTORY_ENTRY_IMPORT, IMAGE_DIRECTORY_ENTRY_RESOURCE,
+    IMAGE_DLLCHARACTERISTICS_DYNAMIC_BASE, IMAGE_DLLCHARACTERISTICS_NX_COMPAT,
+    IMAGE_SUBSYSTEM_WINDOWS_CUI,
+};
+use crate::port_exe::PortExe;
+use crate::section_header::{
+    IMAGE_SCN_CNT_INITIALIZED_DATA, IMAGE_SCN_MEM_EXECUTE, IMAGE_SCN_MEM_READ, IMAGE_SCN_MEM_WRITE,
+};
+use crate::{read_u32, PEType, StructField};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+
+/// Section alignment of fixture images; sections are placed back to back from this RVA on
+pub const FIXTURE_SECTION_ALIGNMENT: u32 = 0x1000;
+/// File alignment of fixture images
+pub const FIXTURE_FILE_ALIGNMENT: u32 = 0x200;
+
+const NUMBER_OF_DIRECTORY_ENTRIES: usize = 16;
+const IMAGE_SIZEOF_IMPORT_DESCRIPTOR: usize = 20;
+const IMAGE_RESOURCE_DATA_IS_DIRECTORY: u32 = 0x80000000;
+
+#[derive(Debug, Clone)]
+struct FixtureSection {
+    name: String,
+    characteristics: u32,
+    data: Vec<u8>,
+    virtual_size: u32,
+}
+
+#[derive(Debug, Clone)]
+enum FixtureImport {
+    Name(String),
+    Ordinal(u16),
+}
+
+/// Builder for minimal, loadable PE32 and PE32+ images.
+///
+/// User sections are laid out first, in the order they were added, at
+/// [`FIXTURE_SECTION_ALIGNMENT`] intervals starting at RVA `0x1000`. Imports and
+/// resources are placed in generated `.idata` and `.rsrc` sections after them, and the
+/// matching data directories are filled in.
+#[derive(Debug, Clone)]
+pub struct PeFixture {
+    pe64: bool,
+    machine: u16,
+    characteristics: u16,
+    time_date_stamp: u32,
+    image_base: u64,
+    entry_point: Option<u32>,
+    subsystem: u16,
+    dll_characteristics: u16,
+    sections: Vec<FixtureSection>,
+    directories: [(u32, u32); NUMBER_OF_DIRECTORY_ENTRIES],
+    imports: Vec<(String, Vec<FixtureImport>)>,
+    resources: BTreeMap<u16, BTreeMap<u16, BTreeMap<u16, Vec<u8>>>>,
+}
+
+impl PeFixture {
+    /// 32-bit console executable for i386
+    pub fn pe32() -> Self {
+        Self {
+            pe64: false,
+            machine: IMAGE_FILE_MACHINE_I386,
+            characteristics: IMAGE_FILE_EXECUTABLE_IMAGE | IMAGE_FILE_32BIT_MACHINE,
+            time_date_stamp: 0,
+            image_base: 0x400000,
+            entry_point: None,
+            subsystem: IMAGE_SUBSYSTEM_WINDOWS_CUI,
+            dll_characteristics: IMAGE_DLLCHARACTERISTICS_DYNAMIC_BASE
+                | IMAGE_DLLCHARACTERISTICS_NX_COMPAT,
+            sections: Vec::new(),
+            directories: [(0, 0); NUMBER_OF_DIRECTORY_ENTRIES],
+            imports: Vec::new(),
+            resources: BTreeMap::new(),
+        }
+    }
+
+    /// 64-bit console executable for x64
+    pub fn pe64() -> Self {
+        Self {
+            pe64: true,
+            machine: IMAGE_FILE_MACHINE_AMD64,
+            characteristics: IMAGE_FILE_EXECUTABLE_IMAGE | IMAGE_FILE_LARGE_ADDRESS_AWARE,
+            image_base: 0x140000000,
+            ..Self::pe32()
+        }
+    }
+
+    /// `IMAGE_FILE_MACHINE_*` value of the file header
+    pub fn machine(mut self, machine: u16) -> Self {
+        self.machine = machine;
+        self
+    }
+
+    /// `IMAGE_FILE_*` flags of the file header
+    pub fn characteristics(mut self, characteristics: u16) -> Self {
+        self.characteristics = characteristics;
+        self
+    }
+
+    /// Marks the image as a DLL.
+    pub fn dll(mut self) -> Self {
+        self.characteristics |= IMAGE_FILE_DLL;
+        self
+    }
+
+    pub fn time_date_stamp(mut self, time_date_stamp: u32) -> Self {
+        self.time_date_stamp = time_date_stamp;
+        self
+    }
+
+    pub fn image_base(mut self, image_base: u64) -> Self {
+        self.image_base = image_base;
+        self
+    }
+
+    /// Entry point RVA; defaults to the first executable section
+    pub fn entry_point(mut self, rva: u32) -> Self {
+        self.entry_point = Some(rva);
+        self
+    }
+
+    /// `IMAGE_SUBSYSTEM_*` value of the optional header
+    pub fn subsystem(mut self, subsystem: u16) -> Self {
+        self.subsystem = subsystem;
+        self
+    }
+
+    /// `IMAGE_DLLCHARACTERISTICS_*` flags of the optional header
+    pub fn dll_characteristics(mut self, dll_characteristics: u16) -> Self {
+        self.dll_characteristics = dll_characteristics;
+        self
+    }
+
+    /// Adds a section holding `data`; names longer than eight bytes are truncated.
+    pub fn section(mut self, name: &str, characteristics: u32, data: &[u8]) -> Self {
+        self.sections.push(FixtureSection {
+            name: name.to_string(),
+            characteristics,
+            data: data.to_vec(),
+            virtual_size: data.len() as u32,
+        });
+        self
+    }
+
+    /// Adds a section holding `data` followed by `bss` zero-filled bytes that are mapped
+    /// but not stored in the file.
+    pub fn section_with_bss(
+        mut self,
+        name: &str,
+        characteristics: u32,
+        data: &[u8],
+        bss: u32,
+    ) -> Self {
+        self.sections.push(FixtureSection {
+            name: name.to_string(),
+            characteristics,
+            data: data.to_vec(),
+            virtual_size: data.len() as u32 + bss,
+        });
+        self
+    }
+
+    /// Adds a section of `virtual_size` bytes without file data, such as `.bss`.
+    pub fn uninitialized_section(
+        mut self,
+        name: &str,
+        characteristics: u32,
+        virtual_size: u32,
+    ) -> Self {
+        self.sections.push(FixtureSection {
+            name: name.to_string(),
+            characteristics,
+            data: Vec::new(),
+            virtual_size,
+        });
+        self
+    }
+
+    /// Points data directory `index` at `size` bytes from `rva`. The import, IAT and
+    /// resource entries are overwritten when imports or resources are added.
+    pub fn directory(mut self, index: usize, rva: u32, size: u32) -> Self {
+        self.directories[index] = (rva, size);
+        self
+    }
+
+    /// Imports `function` by name from `dll`.
+    pub fn import(mut self, dll: &str, function: &str) -> Self {
+        self.imports_of(dll)
+            .push(FixtureImport::Name(function.to_string()));
+        self
+    }
+
+    /// Imports `ordinal` from `dll`.
+    pub fn import_ordinal(mut self, dll: &str, ordinal: u16) -> Self {
+        self.imports_of(dll).push(FixtureImport::Ordinal(ordinal));
+        self
+    }
+
+    /// Adds a resource with numeric type, name and language identifiers.
+    pub fn resource(mut self, type_id: u16, name_id: u16, language: u16, data: &[u8]) -> Self {
+        self.resources
+            .entry(type_id)
+            .or_default()
+            .entry(name_id)
+            .or_default()
+            .insert(language, data.to_vec());
+        self
+    }
+
+    /// Lays out the image.
+    pub fn build(&self) -> Vec<u8> {
+        let mut sections = self.sections.clone();
+        let mut directories = self.directories;
+
+        let mut next_rva = FIXTURE_SECTION_ALIGNMENT;
+        for section in &sections {
+            next_rva += align(section.virtual_size.max(1), FIXTURE_SECTION_ALIGNMENT);
+        }
+        if !self.imports.is_empty() {
+            let (data, import_table, iat) = self.import_section(next_rva);
+            directories[IMAGE_DIRECTORY_ENTRY_IMPORT] = import_table;
+            directories[IMAGE_DIRECTORY_ENTRY_IAT] = iat;
+            next_rva += align(data.len() as u32, FIXTURE_SECTION_ALIGNMENT);
+            sections.push(FixtureSection {
+                name: String::from(".idata"),
+                characteristics: IMAGE_SCN_CNT_INITIALIZED_DATA
+                    | IMAGE_SCN_MEM_READ
+                    | IMAGE_SCN_MEM_WRITE,
+                virtual_size: data.len() as u32,
+                data,
+            });
+        }
+        if !self.resources.is_empty() {
+            let data = self.resource_section(next_rva);
+            directories[IMAGE_DIRECTORY_ENTRY_RESOURCE] = (next_rva, data.len() as u32);
+            sections.push(FixtureSection {
+                name: String::from(".rsrc"),
+                characteristics: IMAGE_SCN_CNT_INITIALIZED_DATA | IMAGE_SCN_MEM_READ,
+                virtual_size: data.len() as u32,
+                data,
+            });
+        }
+
+        let size_of_optional_header: u32 = if self.pe64 { 240 } else { 224 };
+        let size_of_headers = align(
+            0x80 + 4 + 20 + size_of_optional_header + 40 * sections.len() as u32,
+            FIXTURE_FILE_ALIGNMENT,
+        );
+
+        let mut section_table = Vec::new();
+        let mut raw_data = Vec::new();
+        let mut rva = FIXTURE_SECTION_ALIGNMENT;
+        let mut entry_point = self.entry_point;
+        for section in &sections {
+            let size_of_raw_data = align(section.data.len() as u32, FIXTURE_FILE_ALIGNMENT);
+            let pointer_to_raw_data = match size_of_raw_data {
+                0 => 0,
+                _ => size_of_headers + raw_data.len() as u32,
+            };
+            let mut name = [0u8; 8];
+            let length = section.name.len().min(8);
+            name[..length].copy_from_slice(&section.name.as_bytes()[..length]);
+            section_table.extend_from_slice(&name);
+            for field in [
+                section.virtual_size,
+                rva,
+                size_of_raw_data,
+                pointer_to_raw_data,
+                0,
+                0,
+                0,
+                section.characteristics,
+            ] {
+                section_table.extend_from_slice(&field.to_le_bytes());
+            }
+            raw_data.extend_from_slice(&section.data);
+            raw_data.resize(
+                raw_data.len() + (size_of_raw_data as usize - section.data.len()),
+                0,
+            );
+
+            if entry_point.is_none() && section.characteristics & IMAGE_SCN_MEM_EXECUTE != 0 {
+                entry_point = Some(rva);
+            }
+            rva += align(section.virtual_size.max(1), FIXTURE_SECTION_ALIGNMENT);
+        }
+
+        let mut image = vec![0u8; 0x80];
+        image[..2].copy_from_slice(b"MZ");
+        image[0x3C..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+        image.extend_from_slice(b"PE\0\0");
+        put16(&mut image, self.machine);
+        put16(&mut image, sections.len() as u16);
+        put32(&mut image, self.time_date_stamp);
+        put32(&mut image, 0);
+        put32(&mut image, 0);
+        put16(&mut image, size_of_optional_header as u16);
+        put16(&mut image, self.characteristics);
+
+        put16(&mut image, if self.pe64 { 0x20B } else { 0x10B });
+        image.extend_from_slice(&[14, 0]);
+        let size_of = |flag| {
+            sections
+                .iter()
+                .filter(|s| s.characteristics & flag != 0)
+                .map(|s| align(s.data.len() as u32, FIXTURE_FILE_ALIGNMENT))
+                .sum::<u32>()
+        };
+        put32(&mut image, size_of(IMAGE_SCN_MEM_EXECUTE));
+        put32(&mut image, size_of(IMAGE_SCN_CNT_INITIALIZED_DATA));
+        put32(&mut image, 0);
+        put32(&mut image, entry_point.unwrap_or(0));
+        put32(&mut image, FIXTURE_SECTION_ALIGNMENT);
+        if self.pe64 {
+            image.extend_from_slice(&self.image_base.to_le_bytes());
+        } else {
+            put32(&mut image, FIXTURE_SECTION_ALIGNMENT);
+            put32(&mut image, self.image_base as u32);
+        }
+        put32(&mut image, FIXTURE_SECTION_ALIGNMENT);
+        put32(&mut image, FIXTURE_FILE_ALIGNMENT);
+        // Operating system, image and subsystem versions
+        for version in [6, 0, 0, 0, 6, 0] {
+            put16(&mut image, version);
+        }
+        put32(&mut image, 0);
+        put32(&mut image, rva);
+        put32(&mut image, size_of_headers);
+        put32(&mut image, 0);
+        put16(&mut image, self.subsystem);
+        put16(&mut image, self.dll_characteristics);
+        // Stack and heap reserve and commit sizes
+        for size in [0x100000u64, 0x1000, 0x100000, 0x1000] {
+            if self.pe64 {
+                image.extend_from_slice(&size.to_le_bytes());
+            } else {
+                put32(&mut image, size as u32);
+            }
+        }
+        put32(&mut image, 0);
+        put32(&mut image, NUMBER_OF_DIRECTORY_ENTRIES as u32);
+        for (virtual_address, size) in directories {
+            put32(&mut image, virtual_address);
+            put32(&mut image, size);
+        }
+
+        image.extend_from_slice(&section_table);
+        image.resize(size_of_headers as usize, 0);
+        image.extend_from_slice(&raw_data);
+        image
+    }
+
+    /// Lays out the image and parses it.
+    pub fn parse(&self) -> io::Result<PortExe> {
+        PortExe::from_bytes(self.build())
+    }
+
+    fn imports_of(&mut self, dll: &str) -> &mut Vec<FixtureImport> {
+        let index = match self.imports.iter().position(|(name, _)| name == dll) {
+            Some(index) => index,
+            None => {
+                self.imports.push((dll.to_string(), Vec::new()));
+                self.imports.len() - 1
+            }
+        };
+        &mut self.imports[index].1
+    }
+
+    /// Import descriptors, lookup tables, IATs, hint/name entries and DLL names placed at
+    /// `rva`, with the ranges of the import directory and the IAT.
+    fn import_section(&self, rva: u32) -> (Vec<u8>, (u32, u32), (u32, u32)) {
+        let thunk_size = if self.pe64 { 8 } else { 4 };
+        let descriptors_size = (self.imports.len() + 1) * IMAGE_SIZEOF_IMPORT_DESCRIPTOR;
+        let thunks: usize = self
+            .imports
+            .iter()
+            .map(|(_, functions)| functions.len() + 1)
+            .sum();
+        let lookup_offset = descriptors_size;
+        let iat_offset = lookup_offset + thunks * thunk_size;
+        let strings_offset = iat_offset + thunks * thunk_size;
+
+        let mut descriptors = Vec::new();
+        let mut lookup = Vec::new();
+        let mut strings = Vec::new();
+        for (dll, functions) in &self.imports {
+            let first_thunk = lookup.len();
+            for function in functions {
+                let thunk = match function {
+                    FixtureImport::Ordinal(ordinal) if self.pe64 => (1 << 63) | *ordinal as u64,
+                    FixtureImport::Ordinal(ordinal) => (1 << 31) | *ordinal as u64,
+                    FixtureImport::Name(name) => {
+                        let hint_name = rva as u64 + (strings_offset + strings.len()) as u64;
+                        strings.extend_from_slice(&[0, 0]);
+                        strings.extend_from_slice(name.as_bytes());
+                        strings.push(0);
+                        if strings.len() % 2 != 0 {
+                            strings.push(0);
+                        }
+                        hint_name
+                    }
+                };
+                lookup.extend_from_slice(&thunk.to_le_bytes()[..thunk_size]);
+            }
+            lookup.extend_from_slice(&vec![0; thunk_size]);
+
+            let name_rva = rva + (strings_offset + strings.len()) as u32;
+            strings.extend_from_slice(dll.as_bytes());
+            strings.push(0);
+            for field in [
+                rva + (lookup_offset + first_thunk) as u32,
+                0,
+                0,
+                name_rva,
+                rva + (iat_offset + first_thunk) as u32,
+            ] {
+                descriptors.extend_from_slice(&field.to_le_bytes());
+            }
+        }
+        descriptors.resize(descriptors_size, 0);
+
+        let mut data = descriptors;
+        data.extend_from_slice(&lookup);
+        data.extend_from_slice(&lookup);
+        data.extend_from_slice(&strings);
+        (
+            data,
+            (rva, descriptors_size as u32),
+            (rva + iat_offset as u32, lookup.len() as u32),
+        )
+    }
+
+    /// Three-level resource tree placed at `rva`: directories first, then the data
+    /// entries, then the data.
+    fn resource_section(&self, rva: u32) -> Vec<u8> {
+        let directory_size = |entries: usize| 16 + entries * 8;
+        let leaves: usize = self
+            .resources
+            .values()
+            .flat_map(BTreeMap::values)
+            .map(BTreeMap::len)
+            .sum();
+
+        let mut type_offset = directory_size(self.resources.len());
+        let mut name_offset = type_offset
+            + self
+                .resources
+                .values()
+                .map(|names| directory_size(names.len()))
+                .sum::<usize>();
+        let mut entry_offset = name_offset
+            + self
+                .resources
+                .values()
+                .flat_map(BTreeMap::values)
+                .map(|languages| directory_size(languages.len()))
+                .sum::<usize>();
+        let data_start = entry_offset + leaves * 16;
+
+        let mut root = Vec::new();
+        let mut types = Vec::new();
+        let mut languages = Vec::new();
+        let mut entries = Vec::new();
+        let mut blobs = Vec::new();
+        for (type_id, by_name) in &self.resources {
+            root.push((*type_id, type_offset));
+            let mut name_entries = Vec::new();
+            for (name_id, by_language) in by_name {
+                name_entries.push((*name_id, name_offset));
+                let mut language_entries = Vec::new();
+                for (language, data) in by_language {
+                    language_entries.push((*language, entry_offset));
+                    let data_rva = rva + (data_start + blobs.len()) as u32;
+                    for field in [data_rva, data.len() as u32, 0, 0] {
+                        entries.extend_from_slice(&field.to_le_bytes());
+                    }
+                    blobs.extend_from_slice(data);
+                    blobs.resize(align(blobs.len() as u32, 8) as usize, 0);
+                    entry_offset += 16;
+                }
+                name_offset += directory_size(language_entries.len());
+                write_directory(&mut languages, &language_entries, false);
+            }
+            type_offset += directory_size(name_entries.len());
+            write_directory(&mut types, &name_entries, true);
+        }
+
+        let mut data = Vec::new();
+        write_directory(&mut data, &root, true);
+        data.extend_from_slice(&types);
+        data.extend_from_slice(&languages);
+        data.extend_from_slice(&entries);
+        data.extend_from_slice(&blobs);
+        data
+    }
+}
+
+/// Ways [`mutate`] corrupts a header field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Corruption {
+    /// Every byte cleared
+    Zero,
+    /// Every byte set, the largest unsigned value of the field
+    AllOnes,
+    /// Value plus one, wrapping
+    Increment,
+    /// Value minus one, wrapping
+    Decrement,
+    /// Most significant bit flipped, which turns sizes and offsets into huge values
+    HighBit,
+}
+
+/// Every [`Corruption`], in the order [`mutate`] applies them to a field
+pub const CORRUPTIONS: [Corruption; 5] = [
+    Corruption::Zero,
+    Corruption::AllOnes,
+    Corruption::Increment,
+    Corruption::Decrement,
+    Corruption::HighBit,
+];
+
+impl Corruption {
+    /// Applies the corruption to the little-endian `field` in place.
+    pub fn apply(self, field: &mut [u8]) {
+        match self {
+            Corruption::Zero => field.iter_mut().for_each(|byte| *byte = 0),
+            Corruption::AllOnes => field.iter_mut().for_each(|byte| *byte = 0xFF),
+            Corruption::Increment => {
+                for byte in field.iter_mut() {
+                    *byte = byte.wrapping_add(1);
+                    if *byte != 0 {
+                        break;
+                    }
+                }
+            }
+            Corruption::Decrement => {
+                for byte in field.iter_mut() {
+                    *byte = byte.wrapping_sub(1);
+                    if *byte != 0xFF {
+                        break;
+                    }
+                }
+            }
+            Corruption::HighBit => {
+                if let Some(byte) = field.last_mut() {
+                    *byte ^= 0x80;
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Corruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Corruption::Zero => "zero",
+            Corruption::AllOnes => "all ones",
+            Corruption::Increment => "+1",
+            Corruption::Decrement => "-1",
+            Corruption::HighBit => "high bit flipped",
+        };
+        f.pad(name)
+    }
+}
+
+/// Header field that [`mutate`] corrupts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MutationTarget {
+    field: String,
+    offset: usize,
+    size: usize,
+}
+
+impl MutationTarget {
+    /// Structure and field name, such as `Section 0 .text: Pointer to raw data`
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// File offset of the field
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Width of the field in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+/// Copy of a fixture with one field corrupted
+#[derive(Debug, Clone)]
+pub struct Mutation {
+    target: MutationTarget,
+    corruption: Corruption,
+    data: Vec<u8>,
+}
+
+impl Mutation {
+    pub fn target(&self) -> &MutationTarget {
+        &self.target
+    }
+
+    pub fn corruption(&self) -> Corruption {
+        self.corruption
+    }
+
+    /// The corrupted file
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at {:#x}: {}",
+            self.target.field, self.target.offset, self.corruption
+        )
+    }
+}
+
+/// Iterator over the mutations of a fixture, see [`mutate`]
+#[derive(Debug, Clone)]
+pub struct Mutations {
+    data: Vec<u8>,
+    targets: Vec<MutationTarget>,
+    next: usize,
+}
+
+impl Iterator for Mutations {
+    type Item = Mutation;
+
+    fn next(&mut self) -> Option<Mutation> {
+        while self.next < self.targets.len() * CORRUPTIONS.len() {
+            let target = &self.targets[self.next / CORRUPTIONS.len()];
+            let corruption = CORRUPTIONS[self.next % CORRUPTIONS.len()];
+            self.next += 1;
+
+            let mut data = self.data.clone();
+            let field = &mut data[target.offset..target.offset + target.size];
+            corruption.apply(field);
+            if data != self.data {
+                return Some(Mutation {
+                    target: target.clone(),
+                    corruption,
+                    data,
+                });
+            }
+        }
+        None
+    }
+}
+
+/// Header fields of `data` that [`mutate`] corrupts, in file order: the DOS header's
+/// `e_magic` and `e_lfanew` and the PE signature of images, every file header and
+/// optional header field, every data directory's address and size, and every field of
+/// each section header.
+pub fn mutation_targets(data: &[u8]) -> io::Result<Vec<MutationTarget>> {
+    let pe = PortExe::from_bytes(data.to_vec())?;
+    let mut targets = Vec::new();
+    if pe.pe_type() == PEType::Image {
+        targets.push(target("DOS header: e_magic", 0, 2));
+        targets.push(target("DOS header: e_lfanew", 0x3C, 4));
+        let signature = read_u32(data, 0x3C).unwrap_or_default() as usize;
+        targets.push(target("PE signature", signature, 4));
+    }
+
+    let header = pe.file_header();
+    push(&mut targets, "File header", header.machine());
+    push(&mut targets, "File header", header.number_of_sections());
+    push(&mut targets, "File header", header.time_date_stamp());
+    push(
+        &mut targets,
+        "File header",
+        header.pointer_to_symbol_table(),
+    );
+    push(&mut targets, "File header", header.number_of_symbols());
+    push(
+        &mut targets,
+        "File header",
+        header.size_of_optional_header(),
+    );
+    push(&mut targets, "File header", header.characteristics());
+
+    match pe.optional_header() {
+        Some(OptionalHeaderWrapper::X32(header)) => push_optional_header_32(&mut targets, header),
+        Some(OptionalHeaderWrapper::X64(header)) => push_optional_header_64(&mut targets, header),
+        None => {}
+    }
+    if let Some(header) = pe.optional_header() {
+        for directory in header.data_directories() {
+            let group = format!("Data directory {}", directory.index());
+            push(&mut targets, &group, directory.virtual_address());
+            push(&mut targets, &group, directory.size());
+        }
+    }
+
+    for (index, section) in pe.section_headers().iter().enumerate() {
+        let group = format!("Section {} {}", index, section.name().into_value());
+        push(&mut targets, &group, section.name());
+        push(&mut targets, &group, section.virtual_size());
+        push(&mut targets, &group, section.virtual_address());
+        push(&mut targets, &group, section.size_of_raw_data());
+        push(&mut targets, &group, section.pointer_to_raw_data());
+        push(&mut targets, &group, section.pointer_to_relocations());
+        push(&mut targets, &group, section.pointer_to_linenumbers());
+        push(&mut targets, &group, section.number_of_relocations());
+        push(&mut targets, &group, section.number_of_linenumbers());
+        push(&mut targets, &group, section.characteristics());
+    }
+    Ok(targets)
+}
+
+/// Corrupts each of the [`mutation_targets`] of the valid image or object `data` one at
+/// a time with every [`Corruption`], yielding a corpus of near-valid files for negative
+/// testing.
+///
+/// Corruptions that leave a field unchanged are skipped. Each mutation is a full copy of
+/// `data`, made only when the iterator reaches it.
+pub fn mutate(data: &[u8]) -> io::Result<Mutations> {
+    Ok(Mutations {
+        targets: mutation_targets(data)?,
+        data: data.to_vec(),
+        next: 0,
+    })
+}
+
+fn target(field: &str, offset: usize, size: usize) -> MutationTarget {
+    MutationTarget {
+        field: field.to_string(),
+        offset,
+        size,
+    }
+}
+
+fn push<T, const N: usize>(
+    targets: &mut Vec<MutationTarget>,
+    group: &str,
+    field: StructField<T, N>,
+) {
+    targets.push(target(
+        &format!("{}: {}", group, field.name()),
+        field.offset() as usize,
+        N,
+    ));
+}
+
+fn push_optional_header_32(targets: &mut Vec<MutationTarget>, header: &OptionalHeader32Wrapper) {
+    let group = "Optional header";
+    push(targets, group, header.magic());
+    push(targets, group, header.major_linker_version());
+    push(targets, group, header.minor_linker_version());
+    push(targets, group, header.size_of_code());
+    push(targets, group, header.size_of_initialized_data());
+    push(targets, group, header.size_of_uninitialized_data());
+    push(targets, group, header.address_of_entry_point());
+    push(targets, group, header.base_of_code());
+    push(targets, group, header.base_of_data());
+    push(targets, group, header.image_base());
+    push(targets, group, header.section_alignment());
+    push(targets, group, header.file_alignment());
+    push(targets, group, header.major_os_version());
+    push(targets, group, header.minor_os_version());
+    push(targets, group, header.major_image_version());
+    push(targets, group, header.minor_image_version());
+    push(targets, group, header.major_subsystem_version());
+    push(targets, group, header.minor_subsystem_version());
+    push(targets, group, header.win32_version_value());
+    push(targets, group, header.size_of_image());
+    push(targets, group, header.size_of_headers());
+    push(targets, group, header.checksum());
+    push(targets, group, header.subsystem());
+    push(targets, group, header.dll_characteristics());
+    push(targets, group, header.size_of_stack_reserve());
+    push(targets, group, header.size_of_stack_commit());
+    push(targets, group, header.size_of_heap_reserve());
+    push(targets, group, header.size_of_heap_commit());
+    push(targets, group, header.loader_flags());
+    push(targets, group, header.number_of_rva_and_sizes());
+}
+
+fn push_optional_header_64(targets: &mut Vec<MutationTarget>, header: &OptionalHeader64Wrapper) {
+    let group = "Optional header";
+    push(targets, group, header.magic());
+    push(targets, group, header.major_linker_version());
+    push(targets, group, header.minor_linker_version());
+    push(targets, group, header.size_of_code());
+    push(targets, group, header.size_of_initialized_data());
+    push(targets, group, header.size_of_uninitialized_data());
+    push(targets, group, header.address_of_entry_point());
+    push(targets, group, header.base_of_code());
+    push(targets, group, header.image_base());
+    push(targets, group, header.section_alignment());
+    push(targets, group, header.file_alignment());
+    push(targets, group, header.major_os_version());
+    push(targets, group, header.minor_os_version());
+    push(targets, group, header.major_image_version());
+    push(targets, group, header.minor_image_version());
+    push(targets, group, header.major_subsystem_version());
+    push(targets, group, header.minor_subsystem_version());
+    push(targets, group, header.win32_version_value());
+    push(targets, group, header.size_of_image());
+    push(targets, group, header.size_of_headers());
+    push(targets, group, header.checksum());
+    push(targets, group, header.subsystem());
+    push(targets, group, header.dll_characteristics());
+    push(targets, group, header.size_of_stack_reserve());
+    push(targets, group, header.size_of_stack_commit());
+    push(targets, group, header.size_of_heap_reserve());
+    push(targets, group, header.size_of_heap_commit());
+    push(targets, group, header.loader_flags());
+    push(targets, group, header.number_of_rva_and_sizes());
+}
+
+/// Appends an `IMAGE_RESOURCE_DIRECTORY` with ID entries pointing at `(id, offset)`.
+fn write_directory(out: &mut Vec<u8>, entries: &[(u16, usize)], subdirectories: bool) {
+    out.extend_from_slice(&[0; 12]);
+    put16(out, 0);
+    put16(out, entries.len() as u16);
+    for &(id, offset) in entries {
+        put32(out, id as u32);
+        let offset = offset as u32;
+        put32(
+            out,
+            if subdirectories {
+                offset | IMAGE_RESOURCE_DATA_IS_DIRECTORY
+            } else {
+                offset
+            },
+        );
+    }
+}
+
+fn align(value: u32, alignment: u32) -> u32 {
+    (value + alignment - 1) / alignment * alignment
+}
+
+fn put16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
@@ -0,0 +1,451 @@
+use crate::certificate::{tlv, WIN_CERT_TYPE_PKCS_SIGNED_DATA};
+use crate::debug::IMAGE_DEBUG_TYPE_REPRO;
+use crate::optional_header::IMAGE_DIRECTORY_ENTRY_RESOURCE;
+use crate::port_exe::PortExe;
+use crate::read_u32;
+use std::fmt;
+use std::io;
+
+/// 1993-01-01, before which no PE file was linked
+pub const EARLIEST_PLAUSIBLE_TIMESTAMP: i64 = 725_846_400;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+/// Timestamps of one build written by different tools may drift apart by this much
+const MAX_BUILD_SKEW: i64 = SECONDS_PER_DAY;
+/// Build times closer than this to the corpus median are never outliers
+const MIN_OUTLIER_DISTANCE: i64 = 30 * SECONDS_PER_DAY;
+/// Multiple of the median absolute deviation beyond which a build time is an outlier
+const OUTLIER_DEVIATIONS: i64 = 5;
+/// Nesting depth of the PKCS#7 structures searched for signing times
+const MAX_DER_DEPTH: usize = 16;
+
+const OID_SIGNING_TIME: [u8; 9] = [0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x09, 0x05];
+const DER_INTEGER: u8 = 0x02;
+const DER_OCTET_STRING: u8 = 0x04;
+const DER_OID: u8 = 0x06;
+const DER_UTC_TIME: u8 = 0x17;
+const DER_GENERALIZED_TIME: u8 = 0x18;
+const DER_SEQUENCE: u8 = 0x30;
+const DER_CONSTRUCTED: u8 = 0x20;
+
+/// Structure a [`Timestamp`] was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TimestampSource {
+    FileHeader,
+    /// Debug directory entry of the given `IMAGE_DEBUG_TYPE_*`
+    Debug(u32),
+    ExportDirectory,
+    ResourceDirectory,
+    LoadConfig,
+    /// PKCS#9 `signingTime` attribute, the signer's own clock
+    SigningTime,
+    /// RFC 3161 timestamp token of a countersignature
+    TimestampToken,
+}
+
+impl fmt::Display for TimestampSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FileHeader => f.write_str("file header"),
+            Self::Debug(debug_type) => write!(f, "debug entry type {}", debug_type),
+            Self::ExportDirectory => f.write_str("export directory"),
+            Self::ResourceDirectory => f.write_str("resource directory"),
+            Self::LoadConfig => f.write_str("load config"),
+            Self::SigningTime => f.write_str("signing time"),
+            Self::TimestampToken => f.write_str("timestamp token"),
+        }
+    }
+}
+
+/// Nonzero time recorded in a file, in seconds since the Unix epoch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    source: TimestampSource,
+    seconds: i64,
+}
+
+impl Timestamp {
+    pub fn source(&self) -> TimestampSource {
+        self.source
+    }
+
+    pub fn seconds(&self) -> i64 {
+        self.seconds
+    }
+
+    fn is_signature(&self) -> bool {
+        matches!(
+            self.source,
+            TimestampSource::SigningTime | TimestampSource::TimestampToken
+        )
+    }
+}
+
+/// Reason a [`TimelineEntry`] deserves a closer look
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TimelineFlag {
+    /// The file header timestamp is a build hash, so the build time is unknown
+    Reproducible,
+    /// The file header timestamp is zero
+    NoTimestamp,
+    /// Build time before [`EARLIEST_PLAUSIBLE_TIMESTAMP`]
+    BeforePe,
+    /// Build or signing time after the time of the report
+    InFuture,
+    /// Build timestamps of the file disagree by more than a day
+    Inconsistent,
+    /// Signed before it was built
+    SignedBeforeBuild,
+    /// Build time far from the rest of the corpus
+    Outlier,
+}
+
+impl fmt::Display for TimelineFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Reproducible => "reproducible build",
+            Self::NoTimestamp => "no timestamp",
+            Self::BeforePe => "before 1993",
+            Self::InFuture => "in the future",
+            Self::Inconsistent => "inconsistent timestamps",
+            Self::SignedBeforeBuild => "signed before build",
+            Self::Outlier => "outlier",
+        })
+    }
+}
+
+/// One file of a [`timeline`]
+#[derive(Debug, Clone)]
+pub struct TimelineEntry {
+    name: String,
+    timestamps: Vec<Timestamp>,
+    build_time: Option<i64>,
+    signing_time: Option<i64>,
+    flags: Vec<TimelineFlag>,
+}
+
+impl TimelineEntry {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Every timestamp of the file, in the order the structures were read
+    pub fn timestamps(&self) -> &[Timestamp] {
+        &self.timestamps
+    }
+
+    /// File header timestamp, or the earliest other build timestamp of a
+    /// reproducible build
+    pub fn build_time(&self) -> Option<i64> {
+        self.build_time
+    }
+
+    /// Countersigned time if there is one, otherwise the signer's claimed time
+    pub fn signing_time(&self) -> Option<i64> {
+        self.signing_time
+    }
+
+    pub fn flags(&self) -> &[TimelineFlag] {
+        &self.flags
+    }
+
+    fn flag(&mut self, flag: TimelineFlag) {
+        if !self.flags.contains(&flag) {
+            self.flags.push(flag);
+        }
+    }
+}
+
+impl PortExe {
+    /// Build and signing times recorded in the headers, directories and signature.
+    ///
+    /// Zero timestamps are left out, as are the `0xFFFFFFFF` markers of bound imports.
+    pub fn timestamps(&self) -> io::Result<Vec<Timestamp>> {
+        let mut timestamps = Vec::new();
+        let mut push = |source, value: u32| {
+            if value != 0 && value != u32::MAX {
+                timestamps.push(Timestamp {
+                    source,
+                    seconds: value as i64,
+                });
+            }
+        };
+        push(
+            TimestampSource::FileHeader,
+            self.file_header().time_date_stamp().into_value(),
+        );
+        for entry in self.debug_directory()? {
+            push(
+                TimestampSource::Debug(entry.debug_type()),
+                entry.time_date_stamp(),
+            );
+        }
+        if let Some(directory) = self.export_directory()? {
+            push(
+                TimestampSource::ExportDirectory,
+                directory.time_date_stamp(),
+            );
+        }
+        let resources = self
+            .optional_header()
+            .and_then(|h| h.data_directory(IMAGE_DIRECTORY_ENTRY_RESOURCE))
+            .map(|directory| directory.virtual_address().into_value())
+            .filter(|&rva| rva != 0)
+            .and_then(|rva| self.rva_to_offset(rva))
+            .and_then(|offset| read_u32(self.data(), offset + 4));
+        if let Some(value) = resources {
+            push(TimestampSource::ResourceDirectory, value);
+        }
+        if let Some(value) = self
+            .load_config()?
+            .and_then(|config| config.time_date_stamp())
+        {
+            push(TimestampSource::LoadConfig, value);
+        }
+
+        for certificate in self.certificates()? {
+            if certificate.certificate_type() == WIN_CERT_TYPE_PKCS_SIGNED_DATA {
+                signature_times(certificate.data(), 0, &mut timestamps);
+            }
+        }
+        Ok(timestamps)
+    }
+
+    /// Collects the timestamps of the file for [`timeline`], naming it `name`.
+    pub fn timeline_entry(&self, name: &str) -> io::Result<TimelineEntry> {
+        let timestamps = self.timestamps()?;
+        let reproducible = self
+            .debug_directory()?
+            .iter()
+            .any(|entry| entry.debug_type() == IMAGE_DEBUG_TYPE_REPRO);
+        let mut entry = TimelineEntry {
+            name: name.to_string(),
+            timestamps,
+            build_time: None,
+            signing_time: None,
+            flags: Vec::new(),
+        };
+
+        // Reproducible builds write a hash to every build timestamp the linker controls
+        let build_times: Vec<i64> = entry
+            .timestamps
+            .iter()
+            .filter(|timestamp| !timestamp.is_signature())
+            .filter(|timestamp| !reproducible || timestamp.source == TimestampSource::LoadConfig)
+            .map(Timestamp::seconds)
+            .collect();
+        let header = entry
+            .timestamps
+            .iter()
+            .find(|timestamp| timestamp.source == TimestampSource::FileHeader)
+            .map(Timestamp::seconds);
+        entry.build_time = match (reproducible, header) {
+            (false, Some(header)) => Some(header),
+            _ => build_times.iter().copied().min(),
+        };
+        if reproducible {
+            entry.flag(TimelineFlag::Reproducible);
+        } else if header.is_none() {
+            entry.flag(TimelineFlag::NoTimestamp);
+        }
+        if let (Some(first), Some(last)) = (build_times.iter().min(), build_times.iter().max()) {
+            if last - first > MAX_BUILD_SKEW {
+                entry.flag(TimelineFlag::Inconsistent);
+            }
+        }
+        if entry
+            .build_time
+            .map_or(false, |time| time < EARLIEST_PLAUSIBLE_TIMESTAMP)
+        {
+            entry.flag(TimelineFlag::BeforePe);
+        }
+
+        let signed = |source| {
+            entry
+                .timestamps
+                .iter()
+                .filter(|timestamp| timestamp.source == source)
+                .map(Timestamp::seconds)
+                .min()
+        };
+        entry.signing_time = signed(TimestampSource::TimestampToken)
+            .or_else(|| signed(TimestampSource::SigningTime));
+        if let (Some(signed), Some(built)) = (entry.signing_time, entry.build_time) {
+            if signed + MAX_BUILD_SKEW < built {
+                entry.flag(TimelineFlag::SignedBeforeBuild);
+            }
+        }
+        Ok(entry)
+    }
+}
+
+/// Orders files by build time, files without one last, and flags build times in the
+/// future of `now` or far from the corpus median.
+///
+/// A build time is an outlier when its distance to the median exceeds five median
+/// absolute deviations and 30 days.
+pub fn timeline(mut entries: Vec<TimelineEntry>, now: i64) -> Vec<TimelineEntry> {
+    entries.sort_by(|a, b| {
+        (a.build_time.is_none(), a.build_time, &a.name).cmp(&(
+            b.build_time.is_none(),
+            b.build_time,
+            &b.name,
+        ))
+    });
+    let mut plausible: Vec<i64> = entries
+        .iter()
+        .filter_map(|entry| entry.build_time)
+        .filter(|&time| time >= EARLIEST_PLAUSIBLE_TIMESTAMP && time <= now)
+        .collect();
+    plausible.sort_unstable();
+    let center = median(&plausible);
+    let mut deviations: Vec<i64> = center
+        .map(|median| plausible.iter().map(|time| (time - median).abs()).collect())
+        .unwrap_or_default();
+    deviations.sort_unstable();
+    let limit = median(&deviations).map_or(MIN_OUTLIER_DISTANCE, |deviation| {
+        (deviation * OUTLIER_DEVIATIONS).max(MIN_OUTLIER_DISTANCE)
+    });
+
+    for entry in &mut entries {
+        let future = entry.build_time.map_or(false, |time| time > now)
+            || entry.signing_time.map_or(false, |time| time > now);
+        if future {
+            entry.flag(TimelineFlag::InFuture);
+        }
+        if let (Some(time), Some(median)) = (entry.build_time, center) {
+            // A corpus of one or two files has no meaningful spread
+            if plausible.len() > 2 && (time - median).abs() > limit {
+                entry.flag(TimelineFlag::Outlier);
+            }
+        }
+    }
+    entries
+}
+
+/// Formats seconds since the Unix epoch as `YYYY-MM-DD HH:MM:SS` UTC.
+pub fn format_timestamp(seconds: i64) -> String {
+    let days = seconds.div_euclid(SECONDS_PER_DAY);
+    let time = seconds.rem_euclid(SECONDS_PER_DAY);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+fn median(sorted: &[i64]) -> Option<i64> {
+    sorted.get(sorted.len() / 2).copied()
+}
+
+/// Searches DER data for `signingTime` attributes and RFC 3161 `TSTInfo` structures,
+/// descending into constructed elements and octet strings that hold DER.
+fn signature_times(mut data: &[u8], depth: usize, timestamps: &mut Vec<Timestamp>) {
+    if depth == MAX_DER_DEPTH {
+        return;
+    }
+    while let Some((tag, value, rest)) = tlv(data) {
+        data = rest;
+        if tag == DER_SEQUENCE {
+            if let Some(timestamp) = signing_time(value).or_else(|| token_time(value)) {
+                timestamps.push(timestamp);
+                continue;
+            }
+        }
+        if tag & DER_CONSTRUCTED != 0 {
+            signature_times(value, depth + 1, timestamps);
+        } else if tag == DER_OCTET_STRING {
+            // Encapsulated content such as the TSTInfo of a timestamp token
+            if tlv(value).map_or(false, |(_, _, rest)| rest.is_empty()) {
+                signature_times(value, depth + 1, timestamps);
+            }
+        }
+    }
+}
+
+/// `Attribute ::= SEQUENCE { OID signingTime, SET { Time } }`
+fn signing_time(attribute: &[u8]) -> Option<Timestamp> {
+    let (tag, oid, rest) = tlv(attribute)?;
+    if tag != DER_OID || oid != OID_SIGNING_TIME {
+        return None;
+    }
+    let (_, set, _) = tlv(rest)?;
+    let (tag, time, _) = tlv(set)?;
+    Some(Timestamp {
+        source: TimestampSource::SigningTime,
+        seconds: der_time(tag, time)?,
+    })
+}
+
+/// `TSTInfo ::= SEQUENCE { version INTEGER, policy OID, messageImprint SEQUENCE,
+/// serialNumber INTEGER, genTime GeneralizedTime, ... }`
+fn token_time(info: &[u8]) -> Option<Timestamp> {
+    let mut fields = info;
+    let mut next = |expected: u8| {
+        let (tag, value, rest) = tlv(fields)?;
+        fields = rest;
+        (tag == expected).then(|| value)
+    };
+    next(DER_INTEGER)?;
+    next(DER_OID)?;
+    next(DER_SEQUENCE)?;
+    next(DER_INTEGER)?;
+    let time = next(DER_GENERALIZED_TIME)?;
+    Some(Timestamp {
+        source: TimestampSource::TimestampToken,
+        seconds: der_time(DER_GENERALIZED_TIME, time)?,
+    })
+}
+
+/// Decodes `YYMMDDHHMMSSZ` UTCTime and `YYYYMMDDHHMMSS[.fff]Z` GeneralizedTime.
+fn der_time(tag: u8, value: &[u8]) -> Option<i64> {
+    let text = std::str::from_utf8(value).ok()?;
+    let (year, rest) = match tag {
+        DER_UTC_TIME => {
+            let year: i64 = text.get(..2)?.parse().ok()?;
+            // RFC 5280: two-digit years from 50 onwards are in the 20th century
+            (
+                if year < 50 { 2000 + year } else { 1900 + year },
+                &text[2..],
+            )
+        }
+        DER_GENERALIZED_TIME => (text.get(..4)?.parse().ok()?, &text[4..]),
+        _ => return None,
+    };
+    let field = |index: usize| -> Option<i64> { rest.get(index * 2..index * 2 + 2)?.parse().ok() };
+    let (month, day) = (field(0)?, field(1)?);
+    let (hour, minute, second) = (field(2)?, field(3)?, field(4)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Days since 1970-01-01 of the civil date (Howard Hinnant's algorithm)
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_index = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    Some(days * SECONDS_PER_DAY + hour * 3600 + minute * 60 + second)
+}
@@ -0,0 +1,286 @@
+use crate::analysis::entropy;
+use crate::optional_header::IMAGE_DIRECTORY_ENTRY_TLS;
+use crate::pe_string::PeString;
+use crate::port_exe::PortExe;
+use crate::{read_u32, read_u64};
+use std::io;
+
+/// Upper bound on the number of TLS callbacks read
+pub const MAX_TLS_CALLBACKS: usize = 0x1000;
+/// Bits of the TLS `Characteristics` holding the alignment of the TLS block, encoded
+/// like the `IMAGE_SCN_ALIGN_*` section flags
+const TLS_ALIGNMENT_MASK: u32 = 0x00F0_0000;
+
+/// `IMAGE_TLS_DIRECTORY32` or `IMAGE_TLS_DIRECTORY64`, with the addresses widened to
+/// 64 bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TlsDirectory {
+    start_address_of_raw_data: u64,
+    end_address_of_raw_data: u64,
+    address_of_index: u64,
+    address_of_callbacks: u64,
+    size_of_zero_fill: u32,
+    characteristics: u32,
+}
+
+impl TlsDirectory {
+    /// Virtual address of the first byte of the TLS template
+    pub fn start_address_of_raw_data(&self) -> u64 {
+        self.start_address_of_raw_data
+    }
+
+    /// Virtual address one past the last byte of the TLS template
+    pub fn end_address_of_raw_data(&self) -> u64 {
+        self.end_address_of_raw_data
+    }
+
+    /// Virtual address of the variable the loader stores the TLS slot index in
+    pub fn address_of_index(&self) -> u64 {
+        self.address_of_index
+    }
+
+    /// Virtual address of the null-terminated array of callback addresses, zero if the
+    /// image has no callbacks
+    pub fn address_of_callbacks(&self) -> u64 {
+        self.address_of_callbacks
+    }
+
+    pub fn size_of_zero_fill(&self) -> u32 {
+        self.size_of_zero_fill
+    }
+
+    pub fn characteristics(&self) -> u32 {
+        self.characteristics
+    }
+
+    /// Alignment of the TLS block in bytes, `None` if the characteristics leave it to
+    /// the loader
+    pub fn alignment(&self) -> Option<u32> {
+        match (self.characteristics & TLS_ALIGNMENT_MASK) >> 20 {
+            0 => None,
+            bits => Some(1 << (bits - 1)),
+        }
+    }
+
+    /// Addresses in the callback array of `pe`, in call order, stopping at the null
+    /// terminator, the end of the file data or [`MAX_TLS_CALLBACKS`] entries
+    pub fn callbacks<'a>(&self, pe: &'a PortExe) -> TlsCallbacks<'a> {
+        let rva = pe.optional_header().and_then(|header| {
+            self.address_of_callbacks
+                .checked_sub(header.image_base())
+                .and_then(|rva| u32::try_from(rva).ok())
+        });
+        TlsCallbacks {
+            pe,
+            pe64: pe
+                .optional_header()
+                .map_or(false, |header| header.image_type().is_x64()),
+            rva: rva.filter(|_| self.address_of_callbacks != 0),
+            count: 0,
+        }
+    }
+}
+
+/// Iterator over the TLS callback addresses of an image, see [`TlsDirectory::callbacks`]
+#[derive(Debug, Clone)]
+pub struct TlsCallbacks<'a> {
+    pe: &'a PortExe,
+    pe64: bool,
+    /// RVA of the next slot, `None` once the array ended
+    rva: Option<u32>,
+    count: usize,
+}
+
+impl Iterator for TlsCallbacks<'_> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        let rva = self.rva.take()?;
+        if self.count == MAX_TLS_CALLBACKS {
+            return None;
+        }
+        let pointer_size = if self.pe64 { 8 } else { 4 };
+        let slot = self.pe.data_at_rva(rva, pointer_size)?;
+        let callback = if self.pe64 {
+            read_u64(slot, 0)
+        } else {
+            read_u32(slot, 0).map(u64::from)
+        }
+        .filter(|&callback| callback != 0)?;
+        self.rva = rva.checked_add(pointer_size);
+        self.count += 1;
+        Some(callback)
+    }
+}
+
+/// Initial contents of every thread's TLS block, copied from the image by the loader
+#[derive(Debug, Clone)]
+pub struct TlsTemplate {
+    start_address: u64,
+    end_address: u64,
+    size_of_zero_fill: u32,
+    offset: Option<usize>,
+    section: Option<PeString>,
+    data: Vec<u8>,
+    entropy: f64,
+}
+
+impl TlsTemplate {
+    /// `StartAddressOfRawData`
+    pub fn start_address(&self) -> u64 {
+        self.start_address
+    }
+
+    /// `EndAddressOfRawData`, one past the last byte of the template
+    pub fn end_address(&self) -> u64 {
+        self.end_address
+    }
+
+    /// Number of bytes between the start and end addresses
+    pub fn size(&self) -> u64 {
+        self.end_address - self.start_address
+    }
+
+    /// Zero bytes the loader appends after the template in each TLS block
+    pub fn size_of_zero_fill(&self) -> u32 {
+        self.size_of_zero_fill
+    }
+
+    /// File offset of the template, `None` if it is not backed by file data
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    /// Name of the section holding the template
+    pub fn section(&self) -> Option<&PeString> {
+        self.section.as_ref()
+    }
+
+    /// Template bytes present in the file, which may be fewer than [`TlsTemplate::size`]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Whether part of the template lies past the raw data of its section or the file
+    pub fn is_truncated(&self) -> bool {
+        (self.data.len() as u64) < self.size()
+    }
+
+    /// Shannon entropy of [`TlsTemplate::data`] in bits per byte
+    pub fn entropy(&self) -> f64 {
+        self.entropy
+    }
+}
+
+impl PortExe {
+    /// Fields of the TLS directory, `None` if the image has no TLS directory.
+    pub fn tls_directory(&self) -> io::Result<Option<TlsDirectory>> {
+        let header = match self.optional_header() {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let rva = match header
+            .data_directory(IMAGE_DIRECTORY_ENTRY_TLS)
+            .map(|directory| directory.virtual_address().into_value())
+        {
+            Some(rva) if rva != 0 => rva,
+            _ => return Ok(None),
+        };
+        let pe64 = header.image_type().is_x64();
+        let pointer_size = if pe64 { 8 } else { 4 };
+        let directory = self
+            .data_at_rva(rva, 4 * pointer_size as u32 + 8)
+            .ok_or_else(|| invalid_data("TLS directory is outside the file"))?;
+        let pointer = |offset: usize| {
+            if pe64 {
+                read_u64(directory, offset)
+            } else {
+                read_u32(directory, offset).map(u64::from)
+            }
+        };
+        Ok(Some(TlsDirectory {
+            start_address_of_raw_data: pointer(0).unwrap_or(0),
+            end_address_of_raw_data: pointer(pointer_size).unwrap_or(0),
+            address_of_index: pointer(2 * pointer_size).unwrap_or(0),
+            address_of_callbacks: pointer(3 * pointer_size).unwrap_or(0),
+            size_of_zero_fill: read_u32(directory, 4 * pointer_size).unwrap_or(0),
+            characteristics: read_u32(directory, 4 * pointer_size + 4).unwrap_or(0),
+        }))
+    }
+
+    /// Template data range of the TLS directory, `None` if the image has no TLS directory.
+    pub fn tls_template(&self) -> io::Result<Option<TlsTemplate>> {
+        let (header, directory) = match (self.optional_header(), self.tls_directory()?) {
+            (Some(header), Some(directory)) => (header, directory),
+            _ => return Ok(None),
+        };
+        let start_address = directory.start_address_of_raw_data();
+        let end_address = directory.end_address_of_raw_data();
+        let size_of_zero_fill = directory.size_of_zero_fill();
+        if end_address < start_address {
+            return Err(invalid_data("TLS template ends before it starts"));
+        }
+        let size = end_address - start_address;
+
+        let (offset, section, data) = if size == 0 {
+            (None, None, Vec::new())
+        } else {
+            let rva = start_address
+                .checked_sub(header.image_base())
+                .and_then(|rva| u32::try_from(rva).ok())
+                .ok_or_else(|| invalid_data("TLS template is outside the image"))?;
+            let section = self
+                .section_at_rva(rva)
+                .map(|section| section.name().into_value());
+            let offset = self.rva_to_offset(rva);
+            let data = offset
+                .map(|offset| self.backed_data(rva, offset, size).to_vec())
+                .unwrap_or_default();
+            (offset, section, data)
+        };
+        Ok(Some(TlsTemplate {
+            start_address,
+            end_address,
+            size_of_zero_fill,
+            offset,
+            section,
+            entropy: entropy(&data),
+            data,
+        }))
+    }
+
+    /// Virtual addresses of the TLS callbacks, in call order, from the null-terminated
+    /// array `AddressOfCallBacks` points at. The loader runs them before the entry point.
+    pub fn tls_callbacks(&self) -> Vec<u64> {
+        match self.tls_directory() {
+            Ok(Some(directory)) => directory.callbacks(self).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Up to `size` bytes at `rva`, which maps to `offset`, stopping where the file
+    /// data of the section, the headers or the file ends.
+    fn backed_data(&self, rva: u32, offset: usize, size: u64) -> &[u8] {
+        let available = if self.layout().is_mapped() {
+            self.data().len() - offset
+        } else {
+            match self.section_at_rva(rva) {
+                Some(section) => {
+                    let delta = rva - section.virtual_address().into_value();
+                    (section.size_of_raw_data().into_value() - delta) as usize
+                }
+                None => self
+                    .optional_header()
+                    .map_or(0, |header| header.size_of_headers().saturating_sub(rva))
+                    as usize,
+            }
+        };
+        let length = available.min(usize::try_from(size).unwrap_or(usize::MAX));
+        let end = offset.saturating_add(length).min(self.data().len());
+        &self.data()[offset..end]
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
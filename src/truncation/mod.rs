@@ -0,0 +1,217 @@
+use crate::arith::align_up;
+use crate::optional_header::{DataDirectoryType, IMAGE_DIRECTORY_ENTRY_SECURITY};
+use crate::pe_string::PeString;
+use crate::port_exe::PortExe;
+use crate::symbol::IMAGE_SIZEOF_SYMBOL;
+use std::fmt;
+use std::ops::Range;
+
+/// Part of a file that a [`Truncation`] cut short
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TruncatedPart {
+    /// Headers up to `SizeOfHeaders`
+    Headers,
+    /// Raw data of the section with this name
+    Section(PeString),
+    /// Data directory, located through the section holding it
+    Directory(DataDirectoryType),
+    /// Attribute certificates, which the security directory gives by file offset
+    CertificateTable,
+    /// COFF symbol table and the size field of the string table after it
+    SymbolTable,
+}
+
+impl fmt::Display for TruncatedPart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Headers => f.pad("headers"),
+            Self::Section(name) => f.pad(&format!("section {}", name.to_string_lossy())),
+            Self::Directory(directory) => f.pad(&format!("{:?} directory", directory)),
+            Self::CertificateTable => f.pad("certificate table"),
+            Self::SymbolTable => f.pad("COFF symbol table"),
+        }
+    }
+}
+
+/// Structure that extends past the end of a truncated file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TruncatedStructure {
+    part: TruncatedPart,
+    range: Range<u64>,
+    present: u64,
+}
+
+impl TruncatedStructure {
+    pub fn part(&self) -> &TruncatedPart {
+        &self.part
+    }
+
+    /// File range the headers give the structure
+    pub fn range(&self) -> Range<u64> {
+        self.range.clone()
+    }
+
+    /// Bytes of the structure that are in the file
+    pub fn present(&self) -> u64 {
+        self.present
+    }
+
+    /// Bytes of the structure past the end of the file
+    pub fn missing(&self) -> u64 {
+        self.range.end - self.range.start - self.present
+    }
+}
+
+/// Layout a file shorter than its headers describe was meant to have, see
+/// [`PortExe::truncation`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Truncation {
+    file_size: u64,
+    expected_size: u64,
+    inferred_size_of_image: u32,
+    inferred_size_of_headers: u32,
+    structures: Vec<TruncatedStructure>,
+}
+
+impl Truncation {
+    pub fn file_size(&self) -> u64 {
+        self.file_size
+    }
+
+    /// Size the file needs to hold everything its headers point to
+    pub fn expected_size(&self) -> u64 {
+        self.expected_size
+    }
+
+    /// Bytes missing from the end of the file
+    pub fn missing(&self) -> u64 {
+        self.expected_size - self.file_size
+    }
+
+    /// `SizeOfImage` implied by the section table: the end of the last section in
+    /// memory, aligned to `SectionAlignment`
+    pub fn inferred_size_of_image(&self) -> u32 {
+        self.inferred_size_of_image
+    }
+
+    /// `SizeOfHeaders` implied by the section table: its end, aligned to `FileAlignment`
+    pub fn inferred_size_of_headers(&self) -> u32 {
+        self.inferred_size_of_headers
+    }
+
+    /// Structures extending past the end of the file, in file order
+    pub fn structures(&self) -> &[TruncatedStructure] {
+        &self.structures
+    }
+}
+
+impl PortExe {
+    /// Layout of a file that ends before the data its headers point to, as files
+    /// carved from disk images or cut short by a download often do; `None` if the file
+    /// is complete.
+    ///
+    /// The section table is trusted over `SizeOfImage` and `SizeOfHeaders`, which the
+    /// result gives as the section table implies them. Data directories are located
+    /// through the sections holding them. Mapped images and object files have none.
+    pub fn truncation(&self) -> Option<Truncation> {
+        if self.layout().is_mapped() {
+            return None;
+        }
+        let header = self.optional_header()?;
+        let file_size = self.data().len() as u64;
+        let mut expected = Vec::new();
+
+        let inferred_size_of_headers =
+            align_up(self.headers_end() as u64, header.file_alignment() as u64).unwrap_or(u64::MAX);
+        expected.push((
+            TruncatedPart::Headers,
+            0..(header.size_of_headers() as u64).min(inferred_size_of_headers),
+        ));
+
+        let mut image_end = inferred_size_of_headers;
+        for section in self.section_headers() {
+            let virtual_address = section.virtual_address().into_value() as u64;
+            let raw_size = section.size_of_raw_data().into_value() as u64;
+            let virtual_size = match section.virtual_size().into_value() as u64 {
+                0 => raw_size,
+                size => size,
+            };
+            image_end = image_end.max(virtual_address + virtual_size);
+            let start = section.pointer_to_raw_data().into_value() as u64;
+            if raw_size != 0 {
+                expected.push((
+                    TruncatedPart::Section(self.section_name(section)),
+                    start..start + raw_size,
+                ));
+            }
+        }
+        let inferred_size_of_image =
+            align_up(image_end, header.section_alignment() as u64).unwrap_or(u64::MAX);
+
+        for directory in header.data_directories() {
+            let rva = directory.virtual_address().into_value();
+            let size = directory.size().into_value() as u64;
+            if rva == 0 || size == 0 {
+                continue;
+            }
+            if directory.index() == IMAGE_DIRECTORY_ENTRY_SECURITY {
+                expected.push((
+                    TruncatedPart::CertificateTable,
+                    rva as u64..rva as u64 + size,
+                ));
+                continue;
+            }
+            let section = match self.section_at_rva(rva) {
+                Some(section) => section,
+                None => continue,
+            };
+            let start = section.pointer_to_raw_data().into_value() as u64
+                + (rva - section.virtual_address().into_value()) as u64;
+            let raw_end = section.pointer_to_raw_data().into_value() as u64
+                + section.size_of_raw_data().into_value() as u64;
+            if start < raw_end {
+                expected.push((
+                    TruncatedPart::Directory(directory.directory_type()),
+                    start..(start + size).min(raw_end),
+                ));
+            }
+        }
+
+        let symbols = self.file_header().pointer_to_symbol_table().into_value() as u64;
+        if symbols != 0 {
+            let count = self.file_header().number_of_symbols().into_value() as u64;
+            expected.push((
+                TruncatedPart::SymbolTable,
+                symbols..symbols + count * IMAGE_SIZEOF_SYMBOL as u64 + 4,
+            ));
+        }
+
+        let expected_size = expected
+            .iter()
+            .map(|(_, range)| range.end)
+            .fold(0, u64::max);
+        if expected_size <= file_size {
+            return None;
+        }
+        let mut structures: Vec<TruncatedStructure> = expected
+            .into_iter()
+            .filter(|(_, range)| range.end > file_size)
+            .map(|(part, range)| TruncatedStructure {
+                present: file_size
+                    .saturating_sub(range.start)
+                    .min(range.end - range.start),
+                part,
+                range,
+            })
+            .collect();
+        structures.sort_by_key(|structure| structure.range.start);
+        Some(Truncation {
+            file_size,
+            expected_size,
+            inferred_size_of_image: u32::try_from(inferred_size_of_image).unwrap_or(u32::MAX),
+            inferred_size_of_headers: u32::try_from(inferred_size_of_headers).unwrap_or(u32::MAX),
+            structures,
+        })
+    }
+}
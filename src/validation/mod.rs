@@ -0,0 +1,594 @@
+use crate::arith::is_aligned;
+use crate::file_header::Machine;
+use crate::messages::{Catalog, Message};
+use crate::optional_header::{
+    OptionalHeaderWrapper, WindowsSubsystem, IMAGE_DIRECTORY_ENTRY_GLOBALPTR,
+    IMAGE_NUMBEROF_DIRECTORY_ENTRIES,
+};
+use crate::port_exe::PortExe;
+use std::fmt;
+
+/// Smallest file alignment accepted by the desktop loader
+const MIN_FILE_ALIGNMENT: u32 = 0x200;
+/// Largest file alignment allowed by the specification
+const MAX_FILE_ALIGNMENT: u32 = 0x10000;
+/// Page size below which section and file alignment must match
+const PAGE_SIZE: u32 = 0x1000;
+/// Oldest subsystem version the NT loader runs for Windows subsystems (3.10)
+const MIN_WINDOWS_SUBSYSTEM_VERSION: (u16, u16) = (3, 10);
+/// Major subsystem versions used by Windows CE 1.0 through Windows Embedded Compact 2013
+const CE_SUBSYSTEM_MAJOR_VERSIONS: std::ops::RangeInclusive<u16> = 1..=8;
+/// Lowest address of the 64-bit linker default image bases, 0x1_4000_0000 for EXEs and
+/// 0x1_8000_0000 for DLLs
+const MIN_PE64_DEFAULT_IMAGE_BASE: u64 = 0x1_0000_0000;
+
+/// How deviations caused by known legacy toolchains are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationMode {
+    /// Every deviation from the specification is reported as a warning or error.
+    Strict,
+    /// Deviations covered by a [`LegacyQuirk`] are reported as informational only.
+    Permissive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// Known deviations of legacy platforms from the desktop PE conventions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LegacyQuirk {
+    /// Windows CE toolchains stamp `WINDOWS_GUI` or `WINDOWS_CUI` on images built for
+    /// CE-only machines such as `WCEMIPSV2` or the Hitachi SH family.
+    CeMachineWithDesktopSubsystem,
+    /// Windows CE images carry the CE release (1.0 to 8.0) as subsystem version, which
+    /// may be below the 3.10 minimum of the desktop loader.
+    CeSubsystemVersion,
+    /// Xbox images are loaded by the title loader rather than the NT loader and may use
+    /// file alignments below 512 bytes.
+    XboxFileAlignment,
+    /// Xbox images are stored memory-laid, so section data and headers need not be
+    /// aligned to `FileAlignment`.
+    XboxUnalignedRawData,
+    /// Xbox images are not checksummed and often have a `SizeOfImage` that is not a
+    /// multiple of `SectionAlignment`.
+    XboxImageSize,
+}
+
+/// Single result of [`PortExe::validate`]
+#[derive(Debug, Clone)]
+pub struct Finding {
+    severity: Severity,
+    message: String,
+    /// Catalog message `message` is the English text of
+    catalog_message: Option<Message>,
+    quirk: Option<LegacyQuirk>,
+}
+
+impl Finding {
+    /// Finding reported by an analysis outside this module, such as an
+    /// [`Analysis`](crate::plugin::Analysis) plugin
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            catalog_message: None,
+            quirk: None,
+        }
+    }
+
+    /// Finding whose text can be translated through a [`Catalog`]
+    pub fn from_message(severity: Severity, message: Message) -> Self {
+        Self {
+            severity,
+            message: message.english(),
+            catalog_message: Some(message),
+            quirk: None,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Text of the finding in English
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Key and arguments of the text, `None` for findings made from plain text
+    pub fn catalog_message(&self) -> Option<&Message> {
+        self.catalog_message.as_ref()
+    }
+
+    /// Text of the finding in the locale of `catalog`
+    pub fn localize(&self, catalog: &Catalog) -> String {
+        match &self.catalog_message {
+            Some(message) => catalog.format(message),
+            None => self.message.clone(),
+        }
+    }
+
+    /// Legacy convention that explains the deviation, if any
+    pub fn quirk(&self) -> Option<LegacyQuirk> {
+        self.quirk
+    }
+}
+
+struct Findings {
+    mode: ValidationMode,
+    findings: Vec<Finding>,
+}
+
+impl Findings {
+    fn push(&mut self, severity: Severity, message: Message) {
+        self.findings.push(Finding::from_message(severity, message));
+    }
+
+    fn quirk(&mut self, quirk: LegacyQuirk, message: Message) {
+        let severity = match self.mode {
+            ValidationMode::Strict => Severity::Warning,
+            ValidationMode::Permissive => Severity::Info,
+        };
+        self.findings.push(Finding {
+            quirk: Some(quirk),
+            ..Finding::from_message(severity, message)
+        });
+    }
+}
+
+impl PortExe {
+    /// Checks header values against the conventions of the targeted platform.
+    ///
+    /// Windows CE and Xbox images follow their own rules; in [`ValidationMode::Permissive`]
+    /// their known deviations are reported as [`Severity::Info`] instead of warnings.
+    /// Files shorter than their headers describe are reported as an error; in
+    /// [`ValidationMode::Permissive`] the error is followed by the layout inferred from
+    /// the section table, see [`PortExe::truncation`].
+    /// System DLLs imported only by ordinal, see
+    /// [`OrdinalCoverage::is_suspicious`](crate::ordinals::OrdinalCoverage::is_suspicious),
+    /// are reported as warnings.
+    pub fn validate(&self, mode: ValidationMode) -> Vec<Finding> {
+        let mut findings = self.validate_headers(mode);
+        findings.extend(self.validate_imports(mode));
+        findings
+    }
+
+    /// Checks of [`PortExe::validate`] that only read the headers
+    pub(crate) fn validate_headers(&self, mode: ValidationMode) -> Vec<Finding> {
+        let mut findings = Findings {
+            mode,
+            findings: Vec::new(),
+        };
+        if let Some(header) = self.optional_header() {
+            let machine = self.file_header().machine().into_value();
+            let subsystem = header.subsystem();
+            let ce = is_ce_machine(machine) || subsystem == WindowsSubsystem::WindowsCEGraphicalUI;
+            let xbox = subsystem == WindowsSubsystem::Xbox || machine == Machine::PowerPCBE;
+
+            check_subsystem(&mut findings, machine, subsystem, header, ce);
+            check_bitness(&mut findings, self, machine, header);
+            check_alignment(&mut findings, header, xbox);
+            check_sections(&mut findings, self, header, xbox);
+            check_reserved_directories(&mut findings, machine, header);
+            check_truncation(&mut findings, self, header);
+        }
+        findings.findings
+    }
+
+    /// Checks of [`PortExe::validate`] that read the import directory
+    pub(crate) fn validate_imports(&self, mode: ValidationMode) -> Vec<Finding> {
+        let mut findings = Findings {
+            mode,
+            findings: Vec::new(),
+        };
+        if self.optional_header().is_some() {
+            check_ordinal_imports(&mut findings, self);
+        }
+        findings.findings
+    }
+}
+
+fn check_subsystem(
+    findings: &mut Findings,
+    machine: Machine,
+    subsystem: WindowsSubsystem,
+    header: &OptionalHeaderWrapper,
+    ce: bool,
+) {
+    let version = (
+        header.major_subsystem_version(),
+        header.minor_subsystem_version(),
+    );
+    let version_text = format!("{}.{}", version.0, version.1);
+    let desktop = matches!(
+        subsystem,
+        WindowsSubsystem::WindowsGraphicalUI | WindowsSubsystem::WindowsConsoleUI
+    );
+
+    if is_ce_machine(machine) && desktop {
+        findings.quirk(
+            LegacyQuirk::CeMachineWithDesktopSubsystem,
+            Message::new(
+                "ce-desktop-subsystem",
+                vec![format!("{:?}", machine), format!("{:?}", subsystem)],
+            ),
+        );
+    }
+
+    if ce {
+        if !CE_SUBSYSTEM_MAJOR_VERSIONS.contains(&version.0) {
+            findings.push(
+                Severity::Warning,
+                Message::new("ce-subsystem-version-unknown", vec![version_text]),
+            );
+        } else if desktop && version < MIN_WINDOWS_SUBSYSTEM_VERSION {
+            findings.quirk(
+                LegacyQuirk::CeSubsystemVersion,
+                Message::new("ce-subsystem-version-low", vec![version_text]),
+            );
+        }
+    } else if desktop && version < MIN_WINDOWS_SUBSYSTEM_VERSION {
+        findings.push(
+            Severity::Error,
+            Message::new("subsystem-version-low", vec![version_text]),
+        );
+    }
+}
+
+/// Checks the optional header magic against the machine, and the image base and large
+/// address flags against what linkers set for that bitness.
+fn check_bitness(
+    findings: &mut Findings,
+    pe: &PortExe,
+    machine: Machine,
+    header: &OptionalHeaderWrapper,
+) {
+    let pe64 = header.image_type().is_x64();
+    match machine_is_64_bit(machine) {
+        Some(expected) if expected != pe64 => {
+            let (magic, required) = if pe64 {
+                ("PE32+", "PE32")
+            } else {
+                ("PE32", "PE32+")
+            };
+            findings.push(
+                Severity::Error,
+                Message::new(
+                    "magic-mismatch",
+                    vec![
+                        format!("{:?}", machine),
+                        magic.to_string(),
+                        required.to_string(),
+                    ],
+                ),
+            );
+            // The bitness of the image is unclear, so its defaults cannot be checked
+            return;
+        }
+        _ => {}
+    }
+
+    let large_address_aware = pe
+        .file_header()
+        .characteristics()
+        .value()
+        .large_address_aware();
+    let high_entropy_va = header.dll_characteristics().high_entropy_va();
+    if pe64 {
+        let image_base = header.image_base();
+        if image_base < MIN_PE64_DEFAULT_IMAGE_BASE {
+            findings.push(
+                Severity::Info,
+                Message::new("pe64-low-image-base", vec![format!("{:#x}", image_base)]),
+            );
+        }
+        if !large_address_aware {
+            findings.push(
+                Severity::Warning,
+                Message::new("pe64-not-large-address-aware", Vec::new()),
+            );
+        }
+    } else if high_entropy_va {
+        findings.push(
+            Severity::Warning,
+            Message::new("pe32-high-entropy-va", Vec::new()),
+        );
+    }
+}
+
+fn check_alignment(findings: &mut Findings, header: &OptionalHeaderWrapper, xbox: bool) {
+    let file_alignment = header.file_alignment();
+    let section_alignment = header.section_alignment();
+    let file_alignment_text = format!("{:#x}", file_alignment);
+    let section_alignment_text = format!("{:#x}", section_alignment);
+
+    if !file_alignment.is_power_of_two() {
+        findings.push(
+            Severity::Error,
+            Message::new(
+                "file-alignment-not-power-of-two",
+                vec![file_alignment_text.clone()],
+            ),
+        );
+    } else if file_alignment > MAX_FILE_ALIGNMENT {
+        findings.push(
+            Severity::Warning,
+            Message::new(
+                "file-alignment-too-large",
+                vec![file_alignment_text.clone()],
+            ),
+        );
+    } else if file_alignment < MIN_FILE_ALIGNMENT && section_alignment >= PAGE_SIZE {
+        let message = Message::new(
+            "file-alignment-too-small",
+            vec![file_alignment_text.clone()],
+        );
+        if xbox {
+            findings.quirk(LegacyQuirk::XboxFileAlignment, message);
+        } else {
+            findings.push(Severity::Warning, message);
+        }
+    }
+
+    if section_alignment < file_alignment {
+        findings.push(
+            Severity::Error,
+            Message::new(
+                "section-alignment-below-file-alignment",
+                vec![section_alignment_text, file_alignment_text],
+            ),
+        );
+    } else if section_alignment < PAGE_SIZE && section_alignment != file_alignment {
+        findings.push(
+            Severity::Error,
+            Message::new(
+                "section-alignment-mismatch",
+                vec![section_alignment_text, file_alignment_text],
+            ),
+        );
+    }
+
+    let size_of_image = header.size_of_image();
+    if section_alignment.is_power_of_two()
+        && !is_aligned(size_of_image as u64, section_alignment as u64)
+    {
+        let message = Message::new(
+            "size-of-image-unaligned",
+            vec![format!("{:#x}", size_of_image)],
+        );
+        if xbox {
+            findings.quirk(LegacyQuirk::XboxImageSize, message);
+        } else {
+            findings.push(Severity::Warning, message);
+        }
+    }
+}
+
+fn check_sections(
+    findings: &mut Findings,
+    pe: &PortExe,
+    header: &OptionalHeaderWrapper,
+    xbox: bool,
+) {
+    let file_alignment = header.file_alignment();
+    if !file_alignment.is_power_of_two() {
+        return;
+    }
+
+    let mut unaligned = Vec::new();
+    let size_of_headers = header.size_of_headers();
+    if !is_aligned(size_of_headers as u64, file_alignment as u64) {
+        unaligned.push(Message::new(
+            "size-of-headers-unaligned",
+            vec![format!("{:#x}", size_of_headers)],
+        ));
+    }
+    for section in pe.section_headers() {
+        let pointer = section.pointer_to_raw_data().into_value();
+        if section.size_of_raw_data().into_value() != 0
+            && !is_aligned(pointer as u64, file_alignment as u64)
+        {
+            unaligned.push(Message::new(
+                "raw-data-unaligned",
+                vec![
+                    format!("{:#x}", pointer),
+                    section.name().value().to_string(),
+                ],
+            ));
+        }
+    }
+
+    for message in unaligned {
+        if xbox {
+            findings.quirk(LegacyQuirk::XboxUnalignedRawData, message);
+        } else {
+            findings.push(Severity::Warning, message);
+        }
+    }
+}
+
+fn check_truncation(findings: &mut Findings, pe: &PortExe, header: &OptionalHeaderWrapper) {
+    let truncation = match pe.truncation() {
+        Some(truncation) => truncation,
+        None => return,
+    };
+    findings.push(
+        Severity::Error,
+        Message::new(
+            "file-truncated",
+            vec![
+                format!("{:#x}", truncation.file_size()),
+                format!("{:#x}", truncation.missing()),
+                format!("{:#x}", truncation.expected_size()),
+            ],
+        ),
+    );
+    if findings.mode == ValidationMode::Strict {
+        return;
+    }
+    for structure in truncation.structures() {
+        let range = structure.range();
+        findings.push(
+            Severity::Info,
+            Message::new(
+                "structure-truncated",
+                vec![
+                    structure.part().to_string(),
+                    format!("{:#x}", range.start),
+                    format!("{:#x}", range.end),
+                    format!("{:#x}", structure.present()),
+                    format!("{:#x}", range.end - range.start),
+                ],
+            ),
+        );
+    }
+    for (key, inferred, declared) in [
+        (
+            "truncated-size-of-image",
+            truncation.inferred_size_of_image(),
+            header.size_of_image(),
+        ),
+        (
+            "truncated-size-of-headers",
+            truncation.inferred_size_of_headers(),
+            header.size_of_headers(),
+        ),
+    ] {
+        if inferred != declared {
+            findings.push(
+                Severity::Info,
+                Message::new(
+                    key,
+                    vec![format!("{:#x}", inferred), format!("{:#x}", declared)],
+                ),
+            );
+        }
+    }
+}
+
+fn check_reserved_directories(
+    findings: &mut Findings,
+    machine: Machine,
+    header: &OptionalHeaderWrapper,
+) {
+    if let Some((rva, size)) = header.architecture_directory() {
+        if !matches!(machine, Machine::AlphaAXP | Machine::Alpha64) {
+            findings.push(
+                Severity::Warning,
+                Message::new(
+                    "architecture-directory-set",
+                    vec![
+                        format!("{:#x}", rva),
+                        format!("{:#x}", size),
+                        format!("{:?}", machine),
+                    ],
+                ),
+            );
+        }
+    }
+    if let Some(directory) = header.data_directory(IMAGE_DIRECTORY_ENTRY_GLOBALPTR) {
+        let size = directory.size().into_value();
+        if size != 0 {
+            findings.push(
+                Severity::Warning,
+                Message::new("global-ptr-size", vec![format!("{:#x}", size)]),
+            );
+        }
+    }
+
+    let count = header.number_of_rva_and_sizes();
+    if count > IMAGE_NUMBEROF_DIRECTORY_ENTRIES {
+        findings.push(
+            Severity::Warning,
+            Message::new(
+                "too-many-directories",
+                vec![
+                    count.to_string(),
+                    IMAGE_NUMBEROF_DIRECTORY_ENTRIES.to_string(),
+                ],
+            ),
+        );
+        for directory in header
+            .data_directories()
+            .iter()
+            .skip(IMAGE_NUMBEROF_DIRECTORY_ENTRIES as usize)
+        {
+            let rva = directory.virtual_address().into_value();
+            let size = directory.size().into_value();
+            if rva != 0 || size != 0 {
+                findings.push(
+                    Severity::Warning,
+                    Message::new(
+                        "extra-directory-set",
+                        vec![
+                            directory.index().to_string(),
+                            format!("{:#x}", rva),
+                            format!("{:#x}", size),
+                        ],
+                    ),
+                );
+            }
+        }
+    }
+}
+
+fn check_ordinal_imports(findings: &mut Findings, pe: &PortExe) {
+    // Malformed imports are left to the import parser to report
+    let coverage = pe.ordinal_coverage().unwrap_or_default();
+    for dll in coverage.iter().filter(|dll| dll.is_suspicious()) {
+        findings.push(
+            Severity::Warning,
+            Message::new(
+                "system-dll-by-ordinal",
+                vec![dll.by_ordinal().to_string(), dll.dll().to_string()],
+            ),
+        );
+    }
+}
+
+/// Whether images for `machine` use PE32+, `None` for machines whose images may use
+/// either or that are unknown
+fn machine_is_64_bit(machine: Machine) -> Option<bool> {
+    match machine {
+        Machine::X64
+        | Machine::ARM64LittleEndian
+        | Machine::Arm64Ec
+        | Machine::Arm64X
+        | Machine::Itanium
+        | Machine::Alpha64
+        | Machine::LoongArch64
+        | Machine::RISCV64 => Some(true),
+        Machine::Unknown | Machine::EFIByteCode | Machine::RISCV128 => None,
+        _ => Some(false),
+    }
+}
+
+/// Machines that only ever shipped with Windows CE
+fn is_ce_machine(machine: Machine) -> bool {
+    matches!(
+        machine,
+        Machine::WCEMIPSV2
+            | Machine::HitachiSH3
+            | Machine::HitachiSH3DSP
+            | Machine::HitachiSH4
+            | Machine::HitachiSH5
+            | Machine::MIPS16
+            | Machine::MIPSFPU16
+            | Machine::Thumb
+    )
+}
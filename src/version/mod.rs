@@ -0,0 +1,222 @@
+use crate::endian::{utf16_units, ByteOrder};
+use crate::port_exe::PortExe;
+use crate::resource::{ResourceId, RT_VERSION};
+use crate::{read_u16, read_u32};
+use std::io;
+
+/// `VS_FIXEDFILEINFO` signature
+pub const VS_FFI_SIGNATURE: u32 = 0xFEEF_04BD;
+
+const VS_FIXEDFILEINFO_SIZE: usize = 52;
+/// Header of every version node: `wLength`, `wValueLength` and `wType`
+const NODE_HEADER_SIZE: usize = 6;
+
+/// Entry of a `StringFileInfo` string table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionString {
+    table: String,
+    key: String,
+    value: String,
+}
+
+impl VersionString {
+    /// Language and code page of the table, as eight hex digits such as `040904b0`
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+/// Contents of the `VS_VERSIONINFO` resource
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VersionInfo {
+    file_version: Option<[u16; 4]>,
+    product_version: Option<[u16; 4]>,
+    strings: Vec<VersionString>,
+    translations: Vec<(u16, u16)>,
+}
+
+impl VersionInfo {
+    /// Parses a `VS_VERSIONINFO` structure.
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        let root = Node::parse(data, 0)
+            .filter(|node| node.key == "VS_VERSION_INFO")
+            .ok_or_else(|| invalid_data("not a VS_VERSIONINFO resource"))?;
+        let mut info = VersionInfo::default();
+        if root.value.len() >= VS_FIXEDFILEINFO_SIZE
+            && read_u32(root.value, 0) == Some(VS_FFI_SIGNATURE)
+        {
+            let version = |offset| {
+                let high = read_u32(root.value, offset).unwrap_or(0);
+                let low = read_u32(root.value, offset + 4).unwrap_or(0);
+                [
+                    (high >> 16) as u16,
+                    high as u16,
+                    (low >> 16) as u16,
+                    low as u16,
+                ]
+            };
+            info.file_version = Some(version(8));
+            info.product_version = Some(version(16));
+        }
+
+        for child in root.children(data) {
+            match child.key.as_str() {
+                "StringFileInfo" => {
+                    for table in child.children(data) {
+                        for string in table.children(data) {
+                            info.strings.push(VersionString {
+                                table: table.key.clone(),
+                                key: string.key.clone(),
+                                value: utf16_string(string.value),
+                            });
+                        }
+                    }
+                }
+                "VarFileInfo" => {
+                    for var in child.children(data) {
+                        if var.key == "Translation" {
+                            info.translations
+                                .extend(var.value.chunks_exact(4).map(|pair| {
+                                    (
+                                        read_u16(pair, 0).unwrap_or(0),
+                                        read_u16(pair, 2).unwrap_or(0),
+                                    )
+                                }));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(info)
+    }
+
+    /// `dwFileVersionMS` and `dwFileVersionLS` as four 16-bit parts
+    pub fn file_version(&self) -> Option<[u16; 4]> {
+        self.file_version
+    }
+
+    /// `dwProductVersionMS` and `dwProductVersionLS` as four 16-bit parts
+    pub fn product_version(&self) -> Option<[u16; 4]> {
+        self.product_version
+    }
+
+    pub fn strings(&self) -> &[VersionString] {
+        &self.strings
+    }
+
+    /// Value of `key` in the first string table that has it, such as `ProductName`
+    pub fn string(&self, key: &str) -> Option<&str> {
+        self.strings
+            .iter()
+            .find(|string| string.key == key)
+            .map(|string| string.value.as_str())
+    }
+
+    /// Language and code page pairs from `VarFileInfo\Translation`
+    pub fn translations(&self) -> &[(u16, u16)] {
+        &self.translations
+    }
+}
+
+/// Formats a version as `major.minor.build.revision`.
+pub fn format_version(version: [u16; 4]) -> String {
+    format!(
+        "{}.{}.{}.{}",
+        version[0], version[1], version[2], version[3]
+    )
+}
+
+impl PortExe {
+    /// The first `RT_VERSION` resource, `None` if the image has none.
+    pub fn version_info(&self) -> io::Result<Option<VersionInfo>> {
+        Ok(self.version_info_at()?.map(|(info, _)| info))
+    }
+
+    /// The first `RT_VERSION` resource with the address of its data.
+    pub(crate) fn version_info_at(&self) -> io::Result<Option<(VersionInfo, u32)>> {
+        let entry = match self
+            .resources()?
+            .into_iter()
+            .find(|entry| *entry.type_id() == ResourceId::Id(RT_VERSION))
+        {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let data = self
+            .resource_data(&entry)
+            .ok_or_else(|| invalid_data("version resource is not backed by file data"))?;
+        Ok(Some((VersionInfo::parse(data)?, entry.data_rva())))
+    }
+}
+
+/// Node of the version resource tree
+struct Node<'a> {
+    key: String,
+    value: &'a [u8],
+    children: usize,
+    end: usize,
+}
+
+impl<'a> Node<'a> {
+    fn parse(data: &'a [u8], offset: usize) -> Option<Self> {
+        let length = read_u16(data, offset)? as usize;
+        let value_length = read_u16(data, offset + 2)? as usize;
+        let text = read_u16(data, offset + 4)? == 1;
+        let end = offset.checked_add(length)?.min(data.len());
+        if length < NODE_HEADER_SIZE {
+            return None;
+        }
+        let key_start = offset + NODE_HEADER_SIZE;
+        let key_units = data
+            .get(key_start..end)?
+            .chunks_exact(2)
+            .position(|unit| unit == [0, 0])?;
+        let key = utf16_string(&data[key_start..key_start + key_units * 2]);
+        let value_start = align4(key_start + (key_units + 1) * 2);
+        // Text values count UTF-16 units, binary ones bytes
+        let value_size = if text { value_length * 2 } else { value_length };
+        let value_end = (value_start + value_size).min(end);
+        Some(Node {
+            key,
+            value: data.get(value_start..value_end).unwrap_or(&[]),
+            children: align4(value_end).min(end),
+            end,
+        })
+    }
+
+    fn children(&self, data: &'a [u8]) -> Vec<Node<'a>> {
+        let data = &data[..self.end];
+        let mut children = Vec::new();
+        let mut offset = self.children;
+        while let Some(child) = Node::parse(data, offset) {
+            offset = align4(child.end);
+            children.push(child);
+        }
+        children
+    }
+}
+
+fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+fn utf16_string(data: &[u8]) -> String {
+    let units: Vec<u16> = utf16_units(data, ByteOrder::Little)
+        .into_iter()
+        .take_while(|&unit| unit != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,236 @@
+use crate::file_header::Machine;
+use crate::optional_header::WindowsSubsystem;
+use crate::port_exe::PortExe;
+use std::fmt;
+use std::io;
+
+/// User address space of a 32-bit image that is not LARGE_ADDRESS_AWARE, and of a
+/// 64-bit one that is not
+const LOW_ADDRESS_SPACE: u64 = 2 << 30;
+/// User address space of a LARGE_ADDRESS_AWARE image on 32-bit Windows booted with
+/// `increaseuserva`
+const X86_LARGE_ADDRESS_SPACE: u64 = 3 << 30;
+/// User address space of a LARGE_ADDRESS_AWARE 32-bit image under WoW64
+const WOW64_LARGE_ADDRESS_SPACE: u64 = 4 << 30;
+/// User address space of a LARGE_ADDRESS_AWARE 64-bit image
+const LARGE_ADDRESS_SPACE: u64 = 128 << 40;
+
+/// Windows platforms [`PortExe::platform_compatibility`] reports on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Platform {
+    /// 32-bit x86 Windows
+    X86,
+    X64,
+    Arm64,
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::X86 => "x86",
+            Self::X64 => "x64",
+            Self::Arm64 => "ARM64",
+        })
+    }
+}
+
+/// Hybrid code an image carries next to the code of its machine, found through the
+/// CHPE metadata of its load configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Hybrid {
+    /// x86 image with ARM64 code compiled in, as shipped by ARM64 Windows in SysWOW64
+    Chpe,
+    /// x64 image whose code is partly or entirely ARM64EC
+    Arm64Ec,
+    /// ARM64 image that also holds ARM64EC code, loadable by both kinds of processes
+    Arm64X,
+}
+
+impl fmt::Display for Hybrid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Chpe => "CHPE",
+            Self::Arm64Ec => "ARM64EC",
+            Self::Arm64X => "ARM64X",
+        })
+    }
+}
+
+/// How an image runs on a [`Platform`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Support {
+    Native,
+    /// 32-bit image under WoW64 on 64-bit Windows of the same architecture family
+    Wow64,
+    /// x86 image under WoW64 with x86 emulation, running its CHPE code natively
+    Wow64Chpe,
+    /// Runs through x86 or x64 emulation
+    Emulated,
+    /// ARM64EC code runs natively and x64 code in the image is emulated
+    Arm64Ec,
+    Unsupported,
+}
+
+impl fmt::Display for Support {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Native => "native",
+            Self::Wow64 => "WoW64",
+            Self::Wow64Chpe => "WoW64, CHPE",
+            Self::Emulated => "emulated",
+            Self::Arm64Ec => "ARM64EC",
+            Self::Unsupported => "unsupported",
+        })
+    }
+}
+
+/// How an image runs on one platform
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlatformSupport {
+    platform: Platform,
+    support: Support,
+    address_space: Option<u64>,
+    note: Option<&'static str>,
+}
+
+impl PlatformSupport {
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    pub fn support(&self) -> Support {
+        self.support
+    }
+
+    /// Most bytes of user address space the image gets, `None` if it does not run.
+    ///
+    /// On x86 Windows a LARGE_ADDRESS_AWARE image gets 3 GiB only when the system is
+    /// booted with `increaseuserva`, and 2 GiB otherwise.
+    pub fn address_space(&self) -> Option<u64> {
+        self.address_space
+    }
+
+    /// Release constraint on the support, such as the first Windows that offers it
+    pub fn note(&self) -> Option<&'static str> {
+        self.note
+    }
+}
+
+/// Where an image can run, see [`PortExe::platform_compatibility`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlatformCompatibility {
+    machine: Machine,
+    hybrid: Option<Hybrid>,
+    large_address_aware: bool,
+    platforms: Vec<PlatformSupport>,
+}
+
+impl PlatformCompatibility {
+    pub fn machine(&self) -> Machine {
+        self.machine
+    }
+
+    pub fn hybrid(&self) -> Option<Hybrid> {
+        self.hybrid
+    }
+
+    pub fn large_address_aware(&self) -> bool {
+        self.large_address_aware
+    }
+
+    /// Support on x86, x64 and ARM64 Windows, in that order
+    pub fn platforms(&self) -> &[PlatformSupport] {
+        &self.platforms
+    }
+}
+
+impl PortExe {
+    /// Reports whether the image runs on x86, x64 and ARM64 Windows, natively, under
+    /// WoW64 or through emulation, from its machine, its CHPE metadata and
+    /// `LARGE_ADDRESS_AWARE`; `None` for object files. An ARM64EC or ARM64X machine in
+    /// the file header names the hybrid without the metadata.
+    ///
+    /// Native subsystem images, drivers among them, only run natively.
+    pub fn platform_compatibility(&self) -> io::Result<Option<PlatformCompatibility>> {
+        let header = match self.optional_header() {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let machine = self.file_header().machine().into_value();
+        let chpe = self
+            .load_config()?
+            .and_then(|config| config.chpe_metadata_pointer())
+            .map_or(false, |pointer| pointer != 0);
+        // Linkers stamp ARM64EC images as x64, so the machine only names the hybrid of
+        // some images and the CHPE metadata marks the rest
+        let hybrid = match machine {
+            Machine::Arm64Ec => Some(Hybrid::Arm64Ec),
+            Machine::Arm64X => Some(Hybrid::Arm64X),
+            Machine::Intel386 if chpe => Some(Hybrid::Chpe),
+            Machine::X64 if chpe => Some(Hybrid::Arm64Ec),
+            Machine::ARM64LittleEndian if chpe => Some(Hybrid::Arm64X),
+            _ => None,
+        };
+        let large_address_aware = self
+            .file_header()
+            .characteristics()
+            .value()
+            .large_address_aware();
+        let translated = header.subsystem() != WindowsSubsystem::Native;
+
+        let support = |platform| match (platform, machine, hybrid) {
+            (Platform::X86, Machine::Intel386, _) => (Support::Native, None),
+            (Platform::X64, Machine::Intel386, _) if translated => (Support::Wow64, None),
+            (Platform::X64, Machine::X64, None) => (Support::Native, None),
+            (Platform::Arm64, Machine::ARM64LittleEndian | Machine::Arm64X, _) => {
+                (Support::Native, None)
+            }
+            (Platform::Arm64, Machine::X64 | Machine::Arm64Ec, Some(Hybrid::Arm64Ec)) => {
+                (Support::Arm64Ec, Some("ARM64EC needs Windows 11"))
+            }
+            (Platform::Arm64, Machine::X64, _) if translated => {
+                (Support::Emulated, Some("x64 emulation needs Windows 11"))
+            }
+            (Platform::Arm64, Machine::Intel386, Some(Hybrid::Chpe)) if translated => {
+                (Support::Wow64Chpe, None)
+            }
+            (Platform::Arm64, Machine::Intel386, _) if translated => (Support::Emulated, None),
+            (Platform::Arm64, Machine::ARMThumb2, _) if translated => (
+                Support::Wow64,
+                Some("ARM32 support was removed in Windows 11 24H2"),
+            ),
+            _ => (Support::Unsupported, None),
+        };
+        let pe32 = !header.image_type().is_x64();
+        let platforms = [Platform::X86, Platform::X64, Platform::Arm64]
+            .iter()
+            .map(|&platform| {
+                let (support, note) = support(platform);
+                let address_space = match (support, pe32, large_address_aware) {
+                    (Support::Unsupported, _, _) => None,
+                    (_, _, false) => Some(LOW_ADDRESS_SPACE),
+                    (Support::Native, true, true) if platform == Platform::X86 => {
+                        Some(X86_LARGE_ADDRESS_SPACE)
+                    }
+                    (_, true, true) => Some(WOW64_LARGE_ADDRESS_SPACE),
+                    (_, false, true) => Some(LARGE_ADDRESS_SPACE),
+                };
+                PlatformSupport {
+                    platform,
+                    support,
+                    address_space,
+                    note,
+                }
+            })
+            .collect();
+        Ok(Some(PlatformCompatibility {
+            machine,
+            hybrid,
+            large_address_aware,
+            platforms,
+        }))
+    }
+}
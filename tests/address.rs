@@ -0,0 +1,91 @@
+use pexp::address::{AddressLocation, RelativeVirtualAddress, VirtualAddress};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+fn rva(rva: u32) -> RelativeVirtualAddress {
+    RelativeVirtualAddress::new(rva)
+}
+
+/// `.text` with 0x300 bytes at RVA 0x1000, file offset 0x200, and `.bss` with 0x100
+/// bytes of data and 0x1000 more mapped at RVA 0x2000, file offset 0x600
+fn image() -> Vec<u8> {
+    PeFixture::pe64()
+        .dll_characteristics(0)
+        .section(".text", 0x6000_0020, &[0xCC; 0x300])
+        .section_with_bss(".bss", 0xC000_0080, &[1; 0x100], 0x1000)
+        .build()
+}
+
+#[test]
+fn addresses_translate_through_the_section_table() {
+    let pe = PortExe::from_bytes(image()).unwrap();
+    let resolver = pe.address_resolver();
+
+    assert_eq!(
+        resolver.locate(rva(0x40)),
+        AddressLocation::Headers { offset: 0x40 }
+    );
+    assert_eq!(
+        resolver.locate(rva(0x1010)),
+        AddressLocation::Section {
+            section: 0,
+            offset: 0x210
+        }
+    );
+    // Raw data of .text past its virtual size is padding the loader does not map
+    assert_eq!(resolver.rva_to_offset(rva(0x1300)), None);
+    assert_eq!(resolver.offset_to_rva(0x500), None);
+    assert_eq!(
+        resolver.locate(rva(0x2080)),
+        AddressLocation::Section {
+            section: 1,
+            offset: 0x680
+        }
+    );
+    assert_eq!(
+        resolver.locate(rva(0x2800)),
+        AddressLocation::Virtual { section: 1 }
+    );
+    assert_eq!(resolver.locate(rva(0x4000)), AddressLocation::Unmapped);
+    assert_eq!(resolver.locate(rva(0x800)), AddressLocation::Unmapped);
+
+    assert_eq!(resolver.offset_to_rva(0x40), Some(rva(0x40)));
+    assert_eq!(resolver.offset_to_rva(0x210), Some(rva(0x1010)));
+    assert_eq!(resolver.offset_to_rva(0x680), Some(rva(0x2080)));
+    assert_eq!(resolver.offset_to_rva(0x10_0000), None);
+
+    // The translation agrees with the one the rest of the crate uses
+    for value in (0..0x4000).step_by(0x10) {
+        assert_eq!(resolver.rva_to_offset(rva(value)), pe.rva_to_offset(value));
+    }
+
+    let va = resolver.rva_to_va(rva(0x1010));
+    assert_eq!(va, VirtualAddress::new(resolver.image_base() + 0x1010));
+    assert_eq!(resolver.va_to_rva(va), Some(rva(0x1010)));
+    assert_eq!(resolver.va_to_rva(VirtualAddress::new(0x1000)), None);
+    assert_eq!(rva(0x1010).to_string(), "0x1010");
+}
+
+#[test]
+fn mapped_images_use_rvas_as_offsets() {
+    let file = image();
+    let mut mapped = vec![0u8; 0x3000];
+    mapped[..0x200].copy_from_slice(&file[..0x200]);
+    mapped[0x1000..0x1300].copy_from_slice(&file[0x200..0x500]);
+    mapped[0x2000..0x2100].copy_from_slice(&file[0x600..0x700]);
+    let pe = PortExe::from_mapped_bytes(mapped).unwrap();
+    let resolver = pe.address_resolver();
+
+    assert_eq!(
+        resolver.locate(rva(0x2800)),
+        AddressLocation::Section {
+            section: 1,
+            offset: 0x2800
+        }
+    );
+    assert_eq!(resolver.offset_to_rva(0x1010), Some(rva(0x1010)));
+    assert_eq!(
+        resolver.locate(rva(0x3000)),
+        AddressLocation::Virtual { section: 1 }
+    );
+}
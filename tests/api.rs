@@ -0,0 +1,78 @@
+use pexp::testing::PeFixture;
+use pexp::{DataDirectoryType, Machine, PortExe, Severity, ValidationMode, WindowsSubsystem};
+
+fn directory_types(pe: &PortExe) -> Vec<(DataDirectoryType, u32)> {
+    pe.optional_header()
+        .unwrap()
+        .data_directories()
+        .iter()
+        .map(|directory| {
+            (
+                directory.directory_type(),
+                directory.virtual_address().into_value(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn core_types_are_usable_from_the_crate_root() {
+    let pe: PortExe = PeFixture::pe64()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .parse()
+        .unwrap();
+    assert_eq!(pe.file_header().machine().into_value(), Machine::X64);
+    assert_eq!(
+        pe.optional_header().unwrap().subsystem(),
+        WindowsSubsystem::WindowsConsoleUI
+    );
+    let findings = pe.validate(ValidationMode::Strict);
+    assert!(findings
+        .iter()
+        .all(|finding| finding.severity() != Severity::Error));
+}
+
+#[test]
+fn data_directories_are_named_by_their_position() {
+    for fixture in [PeFixture::pe32(), PeFixture::pe64()] {
+        let pe = fixture
+            .section(".text", 0x6000_0020, &[0xC3])
+            .directory(1, 0x1000, 0x28)
+            .directory(14, 0x1040, 0x48)
+            .parse()
+            .unwrap();
+        let types = directory_types(&pe);
+        assert_eq!(types.len(), 16);
+        assert_eq!(types[0], (DataDirectoryType::Export, 0));
+        assert_eq!(types[1], (DataDirectoryType::Import, 0x1000));
+        assert_eq!(types[9].0, DataDirectoryType::TLS);
+        assert_eq!(types[14], (DataDirectoryType::ComDescriptor, 0x1040));
+        assert_eq!(types[15].0, DataDirectoryType::Reserved);
+    }
+    assert_eq!(DataDirectoryType::from(16), DataDirectoryType::Reserved);
+}
+
+#[test]
+fn time_date_stamps_are_read_raw() {
+    // Reproducible builds store a hash of the output, which is no date at all
+    for stamp in [0x5F00_0000, 0xFFFF_FFFF] {
+        let pe = PeFixture::pe32()
+            .time_date_stamp(stamp)
+            .section(".text", 0x6000_0020, &[0xC3])
+            .parse()
+            .unwrap();
+        assert_eq!(pe.file_header().time_date_stamp().into_value(), stamp);
+    }
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn time_date_stamps_are_decoded_with_chrono() {
+    let pe = PeFixture::pe32()
+        .time_date_stamp(0x5F00_0000)
+        .section(".text", 0x6000_0020, &[0xC3])
+        .parse()
+        .unwrap();
+    let timestamp = pe.file_header().timestamp().unwrap();
+    assert_eq!(timestamp.to_rfc3339(), "2020-07-04T04:05:20+00:00");
+}
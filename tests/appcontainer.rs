@@ -0,0 +1,118 @@
+use pexp::appcontainer::{ApiAllowlist, StoreAppTrait};
+use pexp::testing::PeFixture;
+use std::io::ErrorKind;
+
+/// `IMAGE_DLLCHARACTERISTICS_APPCONTAINER`
+const APPCONTAINER: u16 = 0x1000;
+
+const ALLOWLIST: &str = "# Store API surface
+api-ms-win-core-synch-l1-2-0.dll
+
+API-MS-WIN-CORE-WINRT-L1-1-0.DLL!roinitialize
+vcruntime140_app.dll!#7
+";
+
+fn fixture() -> PeFixture {
+    PeFixture::pe64().section(".text", 0x6000_0020, &[0xC3])
+}
+
+#[test]
+fn app_platform_traits_are_reported() {
+    let pe = fixture()
+        .dll_characteristics(APPCONTAINER)
+        .import("api-ms-win-core-synch-l1-2-0.dll", "Sleep")
+        .import("api-ms-win-core-winrt-l1-1-0.dll", "RoInitialize")
+        .parse()
+        .unwrap();
+    let report = pe.store_app_report(None).unwrap();
+    assert_eq!(
+        report.traits(),
+        [
+            StoreAppTrait::AppContainer,
+            StoreAppTrait::CoreApiSetsOnly,
+            StoreAppTrait::WinRtActivation,
+        ]
+    );
+    assert!(report.targets_app_platform());
+    assert_eq!(
+        report.api_sets(),
+        [
+            "api-ms-win-core-synch-l1-2-0.dll",
+            "api-ms-win-core-winrt-l1-1-0.dll"
+        ]
+    );
+    assert!(report.disallowed().is_empty());
+
+    // Activation through combase and the app CRT, outside the core contracts
+    let pe = fixture()
+        .import("combase.dll", "RoActivateInstance")
+        .import("VCRUNTIME140_APP.dll", "memcpy")
+        .import("ext-ms-win-ntuser-window-l1-1-0.dll", "GetWindow")
+        .parse()
+        .unwrap();
+    let report = pe.store_app_report(None).unwrap();
+    assert_eq!(
+        report.traits(),
+        [StoreAppTrait::WinRtActivation, StoreAppTrait::AppCrt]
+    );
+    assert_eq!(report.api_sets(), ["ext-ms-win-ntuser-window-l1-1-0.dll"]);
+
+    let pe = fixture()
+        .import("kernel32.dll", "Sleep")
+        .import("msvcp140.dll", "_Xbad_alloc")
+        .parse()
+        .unwrap();
+    let report = pe.store_app_report(None).unwrap();
+    assert!(!report.targets_app_platform());
+    assert!(report.api_sets().is_empty());
+    assert!(!fixture()
+        .parse()
+        .unwrap()
+        .store_app_report(None)
+        .unwrap()
+        .targets_app_platform());
+    assert_eq!(
+        StoreAppTrait::CoreApiSetsOnly.to_string(),
+        "core API sets only"
+    );
+}
+
+#[test]
+fn imports_outside_the_allowlist_are_listed() {
+    let allowlist = ApiAllowlist::parse(ALLOWLIST).unwrap();
+    assert_eq!(allowlist.queries().len(), 3);
+    let pe = fixture()
+        .import("api-ms-win-core-synch-l1-2-0.dll", "Sleep")
+        .import("api-ms-win-core-synch-l1-2-0.dll", "WaitOnAddress")
+        .import("api-ms-win-core-winrt-l1-1-0.dll", "RoInitialize")
+        .import("api-ms-win-core-winrt-l1-1-0.dll", "RoUninitialize")
+        .import_ordinal("vcruntime140_app.dll", 7)
+        .import_ordinal("vcruntime140_app.dll", 8)
+        .import("kernel32.dll", "CreateFileA")
+        .parse()
+        .unwrap();
+    let report = pe.store_app_report(Some(&allowlist)).unwrap();
+    let disallowed: Vec<_> = report
+        .disallowed()
+        .iter()
+        .map(|import| match import.import().name() {
+            Some(name) => format!("{}!{}", import.dll(), name),
+            None => format!("{}!#{}", import.dll(), import.import().ordinal().unwrap()),
+        })
+        .collect();
+    assert_eq!(
+        disallowed,
+        [
+            "api-ms-win-core-winrt-l1-1-0.dll!RoUninitialize",
+            "vcruntime140_app.dll!#8",
+            "kernel32.dll!CreateFileA",
+        ]
+    );
+
+    // An empty allowlist permits nothing
+    let report = pe.store_app_report(Some(&ApiAllowlist::default())).unwrap();
+    assert_eq!(report.disallowed().len(), 7);
+
+    let error = ApiAllowlist::parse("kernel32.dll!#x").unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::{fixtures, resource_section, ResourceKey, DATA_DIRECTORIES};
+use pexp::artifacts::{Artifact, ArtifactKind};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+/// Address of the second section, holding the resources
+const RESOURCE_RVA: u32 = 0x2000;
+
+fn artifacts(data: &[u8]) -> Vec<Artifact> {
+    PortExe::from_bytes(data.to_vec())
+        .unwrap()
+        .artifacts()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[test]
+fn byte_strings_are_found_at_their_offsets() {
+    for data in [
+        fixtures::PE32_EXE,
+        fixtures::PE32PLUS_EXE,
+        fixtures::PE32_DLL,
+        fixtures::PE32PLUS_DRIVER,
+        fixtures::AMD64_OBJECT,
+    ] {
+        for artifact in artifacts(data) {
+            let offset = artifact.offset().unwrap();
+            let value = artifact.value().as_bytes();
+            match artifact.kind() {
+                ArtifactKind::SectionName { .. } if value.len() <= 8 => {}
+                ArtifactKind::ModuleName
+                | ArtifactKind::ExportName { .. }
+                | ArtifactKind::Forwarder { .. }
+                | ArtifactKind::ImportDll
+                | ArtifactKind::ImportName { .. }
+                | ArtifactKind::PdbPath => {}
+                _ => continue,
+            }
+            assert_eq!(&data[offset..offset + value.len()], value, "{:?}", artifact);
+        }
+    }
+}
+
+#[test]
+fn resource_strings_and_manifests_are_located() {
+    // String table 1 holding "Hi" as string 1, after the empty string 0
+    let strings = [0, 0, 2, 0, b'H', 0, b'i', 0];
+    let manifest = "\u{feff}<assembly/>".as_bytes();
+    let rsrc = resource_section(
+        RESOURCE_RVA,
+        &[
+            (ResourceKey::Id(6), ResourceKey::Id(1), &strings),
+            (ResourceKey::Id(24), ResourceKey::Id(1), manifest),
+        ],
+    );
+    let mut data = PeFixture::pe32()
+        .subsystem(2)
+        .dll_characteristics(0)
+        .section(".text", 0x6000_0020, &[0xC3; 0x10])
+        .section(".rsrc", 0x4000_0040, &rsrc)
+        .build();
+    let directory = DATA_DIRECTORIES + 2 * 8;
+    data[directory..directory + 4].copy_from_slice(&RESOURCE_RVA.to_le_bytes());
+    data[directory + 4..directory + 8].copy_from_slice(&(rsrc.len() as u32).to_le_bytes());
+
+    let artifacts = artifacts(&data);
+    let kinds: Vec<String> = artifacts
+        .iter()
+        .map(|artifact| artifact.kind().to_string())
+        .collect();
+    assert_eq!(kinds, ["section", "section", "string", "manifest"]);
+
+    let string = &artifacts[2];
+    assert_eq!(
+        string.kind(),
+        &ArtifactKind::ResourceString {
+            id: 1,
+            language: 0x409
+        }
+    );
+    assert_eq!(string.value(), "Hi");
+    let offset = string.offset().unwrap();
+    assert_eq!(&data[offset..offset + 4], [b'H', 0, b'i', 0]);
+    assert_eq!(string.rva().unwrap() - RESOURCE_RVA, offset as u32 - 0x400);
+
+    let manifest = &artifacts[3];
+    assert_eq!(manifest.value(), "<assembly/>");
+    let offset = manifest.offset().unwrap();
+    assert_eq!(&data[offset + 3..offset + 14], b"<assembly/>");
+}
@@ -0,0 +1,121 @@
+use pexp::audit::{audit_pdb_paths, audit_release, PdbPathIssue};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+/// File offset of the debug entry of the data directories of a PE32 image
+const DEBUG_DIRECTORY: usize = 0x80 + 4 + 20 + 96 + 6 * 8;
+
+/// Image whose `.rdata`, at RVA 0x1000 and file offset 0x200, holds a debug directory
+/// with one CodeView entry pointing at `record`.
+fn image(record: &[u8]) -> PortExe {
+    let mut data = vec![0u8; 0x20];
+    data[12..16].copy_from_slice(&2u32.to_le_bytes());
+    data[16..20].copy_from_slice(&(record.len() as u32).to_le_bytes());
+    data[20..24].copy_from_slice(&0x1020u32.to_le_bytes());
+    data[24..28].copy_from_slice(&0x220u32.to_le_bytes());
+    data.extend_from_slice(record);
+    let mut image = PeFixture::pe32()
+        .dll_characteristics(0)
+        .section(".rdata", 0x4000_0040, &data)
+        .build();
+    image[DEBUG_DIRECTORY..DEBUG_DIRECTORY + 4].copy_from_slice(&0x1000u32.to_le_bytes());
+    image[DEBUG_DIRECTORY + 4..DEBUG_DIRECTORY + 8].copy_from_slice(&28u32.to_le_bytes());
+    PortExe::from_bytes(image).unwrap()
+}
+
+#[test]
+fn pdb_paths_are_read_from_codeview_records() {
+    let mut rsds = b"RSDS".to_vec();
+    rsds.extend_from_slice(&[0xAB; 16]);
+    rsds.extend_from_slice(&1u32.to_le_bytes());
+    rsds.extend_from_slice(b"D:\\build\\out\\app.pdb\0");
+    let path = image(&rsds).pdb_path().unwrap().unwrap();
+    assert_eq!(path.to_string_lossy(), "D:\\build\\out\\app.pdb");
+
+    let mut nb10 = b"NB10".to_vec();
+    nb10.extend_from_slice(&[0; 12]);
+    nb10.extend_from_slice(b"app.pdb\0");
+    let path = image(&nb10).pdb_path().unwrap().unwrap();
+    assert_eq!(path.to_string_lossy(), "app.pdb");
+
+    assert!(image(b"XXXX").pdb_path().unwrap().is_none());
+}
+
+#[test]
+fn pdb_paths_in_user_profiles_are_reported() {
+    let findings = audit_pdb_paths(&[
+        ("a.dll", r"C:\Users\alice\source\a\Release\a.pdb"),
+        ("b.dll", "/home/bob/src/b/b.pdb"),
+        ("c.dll", r"C:\Documents and Settings\carol\c.pdb"),
+        ("d.dll", r"C:\Users\Public\d.pdb"),
+        ("e.dll", "e.pdb"),
+    ]);
+    let users: Vec<(&str, &PdbPathIssue)> = findings
+        .iter()
+        .map(|finding| (finding.file(), finding.issue()))
+        .collect();
+    let user = |name: &str| PdbPathIssue::UserDirectory {
+        user: name.to_string(),
+    };
+    assert_eq!(
+        users,
+        [
+            ("a.dll", &user("alice")),
+            ("b.dll", &user("bob")),
+            ("c.dll", &user("carol"))
+        ]
+    );
+}
+
+#[test]
+fn pdb_paths_outside_the_common_build_root_are_reported() {
+    let findings = audit_pdb_paths(&[
+        ("a.dll", r"D:\build\out\a.pdb"),
+        ("b.dll", r"d:/Build/out/b.pdb"),
+        ("c.dll", r"\\ci\drop\nightly\c.pdb"),
+        ("d.dll", r"D:\build\out\d.pdb"),
+    ]);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].file(), "c.dll");
+    assert_eq!(
+        findings[0].issue().to_string(),
+        r"built under \\ci\drop\nightly instead of D:\build"
+    );
+
+    assert!(audit_pdb_paths(&[("a.dll", r"D:\build\a.pdb"), ("b.dll", "b.pdb")]).is_empty());
+}
+
+#[test]
+fn release_audit_groups_files_by_differing_values() {
+    let profile = |name: &str, dll_characteristics: u16| {
+        let data = PeFixture::pe32()
+            .dll_characteristics(dll_characteristics)
+            .section(".text", 0x6000_0020, &[0xC3])
+            .build();
+        PortExe::from_bytes(data)
+            .unwrap()
+            .release_profile(name)
+            .unwrap()
+    };
+    let audit = audit_release(&[
+        profile("a.dll", 0x0140),
+        profile("b.dll", 0x0100),
+        profile("c.dll", 0x0140),
+    ]);
+    assert_eq!(audit.files(), ["a.dll", "b.dll", "c.dll"]);
+    assert!(!audit.is_consistent());
+    assert_eq!(audit.inconsistencies().len(), 1);
+    let inconsistency = &audit.inconsistencies()[0];
+    assert_eq!(inconsistency.property(), "ASLR");
+    let variants: Vec<(&str, &[String])> = inconsistency
+        .variants()
+        .iter()
+        .map(|variant| (variant.value(), variant.files()))
+        .collect();
+    assert_eq!(variants[0].1, ["a.dll", "c.dll"]);
+    assert_eq!(variants[1].1, ["b.dll"]);
+    assert!(audit.consistent().contains(&"Signer"));
+    assert!(audit.pdb_paths().is_empty());
+
+    assert!(audit_release(&[profile("a.dll", 0x0140), profile("c.dll", 0x0140)]).is_consistent());
+}
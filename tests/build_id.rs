@@ -0,0 +1,151 @@
+use pexp::build_id::BuildIdKind;
+use pexp::debug::{CodeViewFormat, CodeViewRecord, IMAGE_DEBUG_TYPE_CODEVIEW};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+/// File offset of the debug entry of the data directories of a PE32 image laid out by
+/// [`PeFixture`]
+const DEBUG_DIRECTORY: usize = 0x80 + 4 + 20 + 96 + 6 * 8;
+
+/// Image with `text` in `.text` at RVA 0x1000, file offset 0x200, and a debug
+/// directory with an entry per `(type, data)` in `.rdata` at RVA 0x2000, file offset 0x400
+fn image(text: Vec<u8>, entries: &[(u32, &[u8])]) -> PortExe {
+    let mut rdata = vec![0u8; 28 * entries.len()];
+    for (index, (debug_type, payload)) in entries.iter().enumerate() {
+        let entry = &mut rdata[index * 28..];
+        let offset = 28 * entries.len() + 0x40 * index;
+        entry[12..16].copy_from_slice(&debug_type.to_le_bytes());
+        entry[16..20].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+        entry[20..24].copy_from_slice(&(0x2000 + offset as u32).to_le_bytes());
+        entry[24..28].copy_from_slice(&(0x400 + offset as u32).to_le_bytes());
+    }
+    for (_, payload) in entries {
+        let start = rdata.len();
+        rdata.extend_from_slice(payload);
+        rdata.resize(start + 0x40, 0);
+    }
+    let mut data = PeFixture::pe32()
+        .dll_characteristics(0)
+        .section(".text", 0x6000_0020, &text)
+        .section(".rdata", 0x4000_0040, &rdata)
+        .build();
+    if !entries.is_empty() {
+        let size = 28 * entries.len() as u32;
+        data[DEBUG_DIRECTORY..DEBUG_DIRECTORY + 4].copy_from_slice(&0x2000u32.to_le_bytes());
+        data[DEBUG_DIRECTORY + 4..DEBUG_DIRECTORY + 8].copy_from_slice(&size.to_le_bytes());
+    }
+    PortExe::from_bytes(data).unwrap()
+}
+
+fn rsds() -> Vec<u8> {
+    let mut record = b"RSDS".to_vec();
+    record.extend_from_slice(&[
+        0x40, 0xFC, 0x29, 0x6B, 0x47, 0xCA, 0x67, 0x10, 0xB3, 0x1D, 0x00, 0xDD, 0x01, 0x06, 0x62,
+        0xDA,
+    ]);
+    record.extend_from_slice(&0x1Au32.to_le_bytes());
+    record.extend_from_slice(b"app.pdb\0");
+    record
+}
+
+#[test]
+fn build_ids_are_normalized() {
+    let mut repro = 4u32.to_le_bytes().to_vec();
+    repro.extend_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]);
+    let pe = image(vec![0xCC; 0x10], &[(16, &repro), (2, &rsds())]);
+
+    let ids = pe.build_ids().unwrap();
+    assert_eq!(ids.len(), 2);
+    assert_eq!(ids[0].kind(), BuildIdKind::CodeView);
+    assert_eq!(ids[0].canonical(), "pdb:6B29FC40CA471067B31D00DD010662DA1A");
+    assert_eq!(ids[0].offset(), Some(0x400 + 56 + 0x40 + 4));
+    assert_eq!(ids[1].kind(), BuildIdKind::Repro);
+    assert_eq!(ids[1].to_string(), "repro:deadbeef");
+    assert_eq!(ids[1].value(), "deadbeef");
+    assert_eq!(ids[1].offset(), Some(0x400 + 56 + 4));
+
+    assert_eq!(pe.build_id().unwrap(), Some(ids[0].clone()));
+}
+
+#[test]
+fn go_build_ids_are_read_from_the_text_section() {
+    let mut text = b"\xff Go build ID: \"abc/def\"\n \xff".to_vec();
+    text.resize(0x40, 0xCC);
+    let pe = image(text, &[]);
+    let id = pe.build_id().unwrap().unwrap();
+    assert_eq!(id.kind(), BuildIdKind::Go);
+    assert_eq!(id.canonical(), "go:abc/def");
+    assert_eq!(id.offset(), Some(0x200 + 16));
+
+    assert!(image(vec![0xCC; 0x10], &[]).build_id().unwrap().is_none());
+}
+
+#[test]
+fn symbol_server_paths_key_the_image_and_its_pdb() {
+    let mut rsds = rsds();
+    rsds.truncate(24);
+    rsds.extend_from_slice(b"C:\\build\\out\\app.pdb\0");
+    let mut data = image(vec![0xCC; 0x10], &[(2, &rsds)]).data().to_vec();
+    data[0x88..0x8C].copy_from_slice(&0x4A5B_DADBu32.to_le_bytes());
+    let pe = PortExe::from_bytes(data).unwrap();
+
+    assert_eq!(pe.image_symbol_key().unwrap(), "4A5BDADB3000");
+    assert_eq!(
+        pe.image_symbol_path("app.exe").unwrap(),
+        "app.exe/4A5BDADB3000/app.exe"
+    );
+    assert_eq!(
+        pe.pdb_symbol_path().unwrap().unwrap(),
+        "app.pdb/6B29FC40CA471067B31D00DD010662DA1A/app.pdb"
+    );
+    assert!(image(vec![0xCC; 0x10], &[])
+        .pdb_symbol_path()
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn codeview_records_decode_rsds_and_nb10() {
+    let mut nb10 = b"NB10".to_vec();
+    for field in [0u32, 0x3B9A_CA00, 3] {
+        nb10.extend_from_slice(&field.to_le_bytes());
+    }
+    nb10.extend_from_slice(b"D:\\legacy\\old.pdb\0");
+    let pe = image(vec![0xCC; 0x10], &[(2, &nb10), (2, &rsds()), (2, b"XXXX")]);
+
+    let entries = pe.debug_directory().unwrap();
+    assert_eq!(entries.len(), 3);
+    assert!(entries
+        .iter()
+        .all(|entry| entry.debug_type() == IMAGE_DEBUG_TYPE_CODEVIEW));
+
+    let records = pe.codeview_records().unwrap();
+    assert_eq!(records.len(), 2);
+    let old = &records[0];
+    assert_eq!(old.format(), CodeViewFormat::Pdb20);
+    assert_eq!(old.guid(), None);
+    assert_eq!(old.signature(), 0x3B9A_CA00);
+    assert_eq!(old.age(), 3);
+    assert_eq!(old.pdb_path().to_string_lossy(), "D:\\legacy\\old.pdb");
+    assert_eq!(old.pdb_name(), "old.pdb");
+    assert_eq!(
+        old.symbol_server_url("https://symbols.example/"),
+        Some("https://symbols.example/old.pdb/3B9ACA003/old.pdb".to_string())
+    );
+
+    let new = &records[1];
+    assert_eq!(new.format(), CodeViewFormat::Pdb70);
+    assert_eq!(
+        new.guid().unwrap().to_string(),
+        "6B29FC40-CA47-1067-B31D-00DD010662DA"
+    );
+    assert_eq!(new.age(), 0x1A);
+    assert_eq!(new.symbol_key(), "6B29FC40CA471067B31D00DD010662DA1A");
+    // The NB10 record comes first, so it keys the PDB path
+    assert_eq!(
+        pe.pdb_symbol_path().unwrap().unwrap(),
+        "old.pdb/3B9ACA003/old.pdb"
+    );
+
+    assert!(CodeViewRecord::parse(b"RSDS\0\0").is_none());
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use common::fixtures;
+use pexp::checksec::Protection;
+use pexp::load_config::{
+    IMAGE_GUARD_CF_INSTRUMENTED, IMAGE_GUARD_EH_CONTINUATION_TABLE_PRESENT, IMAGE_GUARD_XFG_ENABLED,
+};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+/// File offset of the load configuration entry of the data directories of a PE32+ image
+const LOAD_CONFIG_64: usize = 0x80 + 4 + 20 + 112 + 10 * 8;
+const IMAGE_BASE_64: u64 = 0x1_4000_0000;
+
+fn put64(data: &mut [u8], offset: usize, value: u64) {
+    data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+/// PE32+ image for `machine` with CFG requested, whose load configuration at RVA 0x1000 sets
+/// `guard_flags` and, if `tables`, points at two EH continuation targets and an XFG
+/// check function
+fn image(machine: u16, guard_flags: u32, tables: bool) -> PortExe {
+    let mut section = vec![0u8; 0x200];
+    section[..4].copy_from_slice(&312u32.to_le_bytes());
+    section[144..148].copy_from_slice(&guard_flags.to_le_bytes());
+    if tables {
+        put64(&mut section, 264, IMAGE_BASE_64 + 0x1180);
+        put64(&mut section, 272, 2);
+        put64(&mut section, 280, IMAGE_BASE_64 + 0x1190);
+        section[0x180..0x184].copy_from_slice(&0x1010u32.to_le_bytes());
+        section[0x184..0x188].copy_from_slice(&0x1020u32.to_le_bytes());
+    }
+    let mut data = PeFixture::pe64()
+        .machine(machine)
+        .dll_characteristics(0x4140)
+        .section(".rdata", 0x4000_0040, &section)
+        .build();
+    data[LOAD_CONFIG_64..LOAD_CONFIG_64 + 4].copy_from_slice(&0x1000u32.to_le_bytes());
+    data[LOAD_CONFIG_64 + 4..LOAD_CONFIG_64 + 8].copy_from_slice(&312u32.to_le_bytes());
+    PortExe::from_bytes(data).unwrap()
+}
+
+#[test]
+fn eh_continuation_and_xfg_are_read_from_the_load_config() {
+    let flags = IMAGE_GUARD_CF_INSTRUMENTED
+        | IMAGE_GUARD_EH_CONTINUATION_TABLE_PRESENT
+        | IMAGE_GUARD_XFG_ENABLED;
+    let pe = image(0x8664, flags, true);
+    let checksec = pe.checksec().unwrap();
+    assert_eq!(checksec.cfg(), Protection::Enabled);
+    assert_eq!(checksec.eh_continuation(), Protection::Enabled);
+    assert_eq!(checksec.xfg(), Protection::Enabled);
+    assert_eq!(
+        pe.guard_eh_continuation_targets().unwrap(),
+        [0x1010, 0x1020]
+    );
+
+    // The flags alone do not make an image ready, nor XFG without CFG
+    let checksec = image(0x8664, flags, false).checksec().unwrap();
+    assert_eq!(checksec.eh_continuation(), Protection::Disabled);
+    assert_eq!(checksec.xfg(), Protection::Disabled);
+    let checksec = image(0x8664, flags & !IMAGE_GUARD_CF_INSTRUMENTED, true)
+        .checksec()
+        .unwrap();
+    assert_eq!(checksec.eh_continuation(), Protection::Enabled);
+    assert_eq!(checksec.xfg(), Protection::Disabled);
+}
+
+#[test]
+fn xfg_is_x64_only() {
+    let flags = IMAGE_GUARD_CF_INSTRUMENTED
+        | IMAGE_GUARD_EH_CONTINUATION_TABLE_PRESENT
+        | IMAGE_GUARD_XFG_ENABLED;
+    let checksec = image(0xAA64, flags, true).checksec().unwrap();
+    assert_eq!(checksec.eh_continuation(), Protection::Enabled);
+    assert_eq!(checksec.xfg(), Protection::NotApplicable);
+}
+
+/// Every mitigation of `pe` in report order: ASLR, DEP, CFG, SafeSEH, GS, CET, signed
+/// and high entropy ASLR
+fn summary(pe: &PortExe) -> [Protection; 8] {
+    let checksec = pe.checksec().unwrap();
+    [
+        checksec.aslr(),
+        checksec.dep(),
+        checksec.cfg(),
+        checksec.safe_seh(),
+        checksec.gs(),
+        checksec.cet(),
+        checksec.signed(),
+        checksec.high_entropy_va(),
+    ]
+}
+
+#[test]
+fn mitigations_are_read_from_the_headers() {
+    use Protection::{Disabled as No, Enabled as Yes, NotApplicable as Na};
+    let image = |fixture: PeFixture| {
+        PortExe::from_bytes(fixture.section(".text", 0x6000_0020, &[0xC3]).build()).unwrap()
+    };
+    assert_eq!(
+        summary(&image(PeFixture::pe32())),
+        [Yes, Yes, No, No, No, No, No, Na]
+    );
+    // Stripped relocations leave nothing to rebase with; NO_SEH satisfies SafeSEH
+    assert_eq!(
+        summary(&image(
+            PeFixture::pe32()
+                .characteristics(0x0103)
+                .dll_characteristics(0x0540)
+        )),
+        [No, Yes, No, Yes, No, No, No, Na]
+    );
+    assert_eq!(
+        summary(&image(PeFixture::pe64().dll_characteristics(0x0160))),
+        [Yes, Yes, No, Na, No, No, No, Yes]
+    );
+    // High entropy ASLR is nothing without ASLR
+    assert_eq!(
+        summary(&image(PeFixture::pe64().dll_characteristics(0x0020))),
+        [No, No, No, Na, No, No, No, No]
+    );
+
+    let object = PortExe::from_bytes(fixtures::AMD64_OBJECT.to_vec()).unwrap();
+    assert_eq!(summary(&object), [Na; 8]);
+}
+
+#[test]
+fn load_config_debug_entry_and_certificate_table_add_mitigations() {
+    let mut rdata = vec![0u8; 0x200];
+    // PE32 load config: size, security cookie and a one-entry SafeSEH table
+    for (offset, value) in [(0, 92u32), (60, 0x40_3000), (64, 0x40_1100), (68, 1)] {
+        rdata[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    // Debug entry of type EX_DLLCHARACTERISTICS holding CET_COMPAT
+    for (offset, value) in [
+        (0x8C, 20u32),
+        (0x90, 4),
+        (0x94, 0x10A0),
+        (0x98, 0x2A0),
+        (0xA0, 1),
+    ] {
+        rdata[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    let mut data = PeFixture::pe32()
+        .section(".rdata", 0x4000_0040, &rdata)
+        .directory(10, 0x1000, 92)
+        .directory(6, 0x1080, 28)
+        .directory(4, 0x400, 8)
+        .build();
+    data.extend_from_slice(&[8, 0, 0, 0, 0, 2, 2, 0]);
+    let pe = PortExe::from_bytes(data).unwrap();
+    let checksec = pe.checksec().unwrap();
+    assert_eq!(checksec.safe_seh(), Protection::Enabled);
+    assert_eq!(checksec.gs(), Protection::Enabled);
+    assert_eq!(checksec.cet(), Protection::Enabled);
+    assert_eq!(checksec.signed(), Protection::Enabled);
+    assert_eq!(checksec.cfg(), Protection::Disabled);
+    assert_eq!(checksec.eh_continuation(), Protection::NotApplicable);
+    assert_eq!(checksec.signed().to_string(), "Yes");
+}
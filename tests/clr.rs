@@ -0,0 +1,291 @@
+mod common;
+
+use common::put32;
+use pexp::clr::{
+    table_name, ManagedResourceData, COR_VTABLE_32BIT, COR_VTABLE_FROM_UNMANAGED,
+    TABLE_MANIFEST_RESOURCE, TABLE_METHOD_DEF, TABLE_MODULE,
+};
+use pexp::export::{build_export_table, ExportSpec, ExportTarget};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+/// Offset of the export entry in the data directories of a PE32 image
+const EXPORT_DIRECTORY: usize = 0x80 + 4 + 20 + 96;
+/// Offset of the COM descriptor entry in the data directories of a PE32 image
+const COM_DESCRIPTOR: usize = 0x80 + 4 + 20 + 96 + 14 * 8;
+
+fn pad4(data: &mut Vec<u8>) {
+    data.resize((data.len() + 3) & !3, 0);
+}
+
+/// Metadata root with a `#~` stream holding a Module row, a MethodDef row named
+/// `Main` and a ManifestResource row for each of `resources`, given as a name and an
+/// offset into the resources directory, then a `#Strings` heap and a `#GUID` heap
+fn metadata_root(resources: &[(&str, u32)]) -> Vec<u8> {
+    let mut strings = b"\0Test.dll\0Main\0".to_vec();
+    let mut names = Vec::new();
+    for (name, _) in resources {
+        names.push(strings.len() as u16);
+        strings.extend_from_slice(name.as_bytes());
+        strings.push(0);
+    }
+    pad4(&mut strings);
+
+    // Table stream header: version 2.0, 2-byte heap indexes
+    let mut tables = vec![0, 0, 0, 0, 2, 0, 0, 1];
+    let mut valid = 1u64 | 1 << TABLE_METHOD_DEF;
+    if !resources.is_empty() {
+        valid |= 1 << TABLE_MANIFEST_RESOURCE;
+    }
+    tables.extend_from_slice(&valid.to_le_bytes());
+    tables.extend_from_slice(&0u64.to_le_bytes());
+    tables.extend_from_slice(&[1, 0, 0, 0, 1, 0, 0, 0]);
+    if !resources.is_empty() {
+        tables.extend_from_slice(&(resources.len() as u32).to_le_bytes());
+    }
+    // Module: generation, name, MVID, EncId, EncBaseId
+    tables.extend_from_slice(&[0, 0, 1, 0, 1, 0, 0, 0, 0, 0]);
+    // MethodDef: RVA, ImplFlags, Flags, Name, Signature, ParamList
+    tables.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 10, 0, 0, 0, 1, 0]);
+    // ManifestResource: Offset, Flags (public), Name, Implementation (this file)
+    for ((_, offset), name) in resources.iter().zip(names) {
+        tables.extend_from_slice(&offset.to_le_bytes());
+        tables.extend_from_slice(&1u32.to_le_bytes());
+        tables.extend_from_slice(&name.to_le_bytes());
+        tables.extend_from_slice(&[0, 0]);
+    }
+    pad4(&mut tables);
+
+    let mut root = b"BSJB".to_vec();
+    root.extend_from_slice(&[1, 0, 1, 0, 0, 0, 0, 0, 12, 0, 0, 0]);
+    root.extend_from_slice(b"v4.0.30319\0\0");
+    root.extend_from_slice(&[0, 0, 3, 0]);
+    let mut offset = 80;
+    for (size, name) in [
+        (tables.len(), &b"#~\0\0"[..]),
+        (strings.len(), b"#Strings\0\0\0\0"),
+        (16, b"#GUID\0\0\0"),
+    ] {
+        root.extend_from_slice(&(offset as u32).to_le_bytes());
+        root.extend_from_slice(&(size as u32).to_le_bytes());
+        root.extend_from_slice(name);
+        offset += size;
+    }
+    assert_eq!(root.len(), 80);
+    root.extend_from_slice(&tables);
+    root.extend_from_slice(&strings);
+    root.extend_from_slice(&[0x11; 16]);
+    root
+}
+
+/// Assembly with the CLR header at RVA 0x1000, the metadata after it, and the entry
+/// point at RVA 0x1240 running `entry_code`
+fn assembly(flags: u32, entry_point_token: u32, entry_code: &[u8]) -> PortExe {
+    assembly_with_resources(flags, entry_point_token, entry_code, &[])
+}
+
+/// [`assembly`] embedding each of `resources`, a name and data, in a resources
+/// directory at RVA 0x1180
+fn assembly_with_resources(
+    flags: u32,
+    entry_point_token: u32,
+    entry_code: &[u8],
+    resources: &[(&str, &[u8])],
+) -> PortExe {
+    PortExe::from_bytes(assembly_data(
+        flags,
+        entry_point_token,
+        entry_code,
+        resources,
+    ))
+    .unwrap()
+}
+
+/// File contents of [`assembly_with_resources`], whose `.text` section is mapped at RVA
+/// 0x1000 from file offset 0x200 and leaves 0x1260 to 0x1300 free
+fn assembly_data(
+    flags: u32,
+    entry_point_token: u32,
+    entry_code: &[u8],
+    resources: &[(&str, &[u8])],
+) -> Vec<u8> {
+    let mut directory = Vec::new();
+    let mut rows = Vec::new();
+    for (name, data) in resources {
+        rows.push((*name, directory.len() as u32));
+        directory.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        directory.extend_from_slice(data);
+        pad4(&mut directory);
+    }
+    let root = metadata_root(&rows);
+    let mut text = vec![0u8; 0x300];
+    for (offset, value) in [
+        (0, 72),
+        (4, 0x0005_0002),
+        (8, 0x1048),
+        (12, root.len() as u32),
+        (16, flags),
+        (20, entry_point_token),
+        (24, 0x1180),
+        (28, directory.len() as u32),
+        (32, 0x1200),
+        (36, 0x80),
+    ] {
+        put32(&mut text, offset, value);
+    }
+    text[0x48..0x48 + root.len()].copy_from_slice(&root);
+    text[0x180..0x180 + directory.len()].copy_from_slice(&directory);
+    text[0x240..0x240 + entry_code.len()].copy_from_slice(entry_code);
+    let mut data = PeFixture::pe32()
+        .characteristics(0x2102)
+        .dll_characteristics(0x8540)
+        .entry_point(0x1240)
+        .section(".text", 0x6000_0020, &text)
+        .build();
+    put32(&mut data, COM_DESCRIPTOR, 0x1000);
+    put32(&mut data, COM_DESCRIPTOR + 4, 72);
+    data
+}
+
+/// `jmp [_CorDllMain]` through the IAT
+const COR_MAIN_STUB: [u8; 6] = [0xFF, 0x25, 0x00, 0x20, 0x40, 0x00];
+/// `push ebp; mov ebp, esp`
+const NATIVE_CODE: [u8; 3] = [0x55, 0x8B, 0xEC];
+
+/// IL-only, strong-name signed assembly entering at the `Main` method
+fn managed() -> PortExe {
+    assembly(0x0000_0009, 0x0600_0001, &COR_MAIN_STUB)
+}
+
+#[test]
+fn clr_header_exposes_entry_point_and_strong_name() {
+    let pe = managed();
+    let header = pe.clr_header().unwrap();
+    assert_eq!(
+        (
+            header.major_runtime_version(),
+            header.minor_runtime_version()
+        ),
+        (2, 5)
+    );
+    assert!(header.il_only());
+    assert_eq!(header.flag_names(), ["ILONLY", "STRONGNAMESIGNED"]);
+    assert_eq!(header.entry_point_row(), Some((TABLE_METHOD_DEF, 1)));
+    assert_eq!(header.metadata().virtual_address(), 0x1048);
+    let signature = header.strong_name_signature();
+    assert_eq!(
+        (signature.virtual_address(), signature.size()),
+        (0x1200, 0x80)
+    );
+    assert_eq!(pe.rva_to_offset(signature.virtual_address()), Some(0x400));
+    assert!(pe.managed_resources().unwrap().is_empty());
+}
+
+#[test]
+fn metadata_streams_and_tables_are_listed() {
+    let pe = managed();
+    let metadata = pe.metadata().unwrap().unwrap();
+    assert_eq!(metadata.version(), "v4.0.30319");
+    let streams: Vec<&str> = metadata.streams().iter().map(|s| s.name()).collect();
+    assert_eq!(streams, ["#~", "#Strings", "#GUID"]);
+    assert_eq!(metadata.table_stream_version(), (2, 0));
+    assert_eq!(metadata.heap_sizes(), 0);
+    assert_eq!(
+        metadata.tables().collect::<Vec<_>>(),
+        [(TABLE_MODULE, 1), (TABLE_METHOD_DEF, 1)]
+    );
+    assert_eq!(table_name(TABLE_METHOD_DEF), Some("MethodDef"));
+    assert_eq!(table_name(0x40), None);
+
+    let name = metadata.column(TABLE_MODULE, 1, 1).unwrap();
+    assert_eq!(metadata.string(name).as_deref(), Some("Test.dll"));
+    let token = pe.clr_header().unwrap().entry_point_token();
+    assert_eq!(metadata.method_name(token).as_deref(), Some("Main"));
+}
+
+#[test]
+fn mixed_mode_requires_native_code_at_the_entry_point() {
+    assert!(!managed().is_mixed_mode());
+    // Clearing ILONLY alone does not make an assembly mixed-mode
+    assert!(!assembly(0x0000_0008, 0x0600_0001, &COR_MAIN_STUB).is_mixed_mode());
+    // A native entry point, flagged in the CLR header or reached from AddressOfEntryPoint
+    let flagged = assembly(0x0000_0018, 0x1240, &COR_MAIN_STUB);
+    assert!(flagged.clr_header().unwrap().native_entry_point());
+    assert!(flagged.is_mixed_mode());
+    assert!(assembly(0x0000_0008, 0x0600_0001, &NATIVE_CODE).is_mixed_mode());
+    // but not while the header claims IL only
+    assert!(!assembly(0x0000_0019, 0x1240, &NATIVE_CODE).is_mixed_mode());
+}
+
+#[test]
+fn embedded_manifest_resources_are_read_from_the_resources_directory() {
+    let mut resources_file = 0xBEEF_CACEu32.to_le_bytes().to_vec();
+    resources_file.extend_from_slice(&[1, 0, 0, 0]);
+    let pe = assembly_with_resources(
+        0x0000_0001,
+        0x0600_0001,
+        &COR_MAIN_STUB,
+        &[
+            ("Strings.resources", &resources_file),
+            ("logo.png", b"\x89PNG"),
+        ],
+    );
+    let metadata = pe.metadata().unwrap().unwrap();
+    assert_eq!(metadata.row_count(TABLE_MANIFEST_RESOURCE), 2);
+
+    let resources = pe.managed_resources().unwrap();
+    let names: Vec<&str> = resources.iter().map(|resource| resource.name()).collect();
+    assert_eq!(names, ["Strings.resources", "logo.png"]);
+    assert_eq!(resources[0].flags(), 1);
+    assert!(resources[0].is_resources_file());
+    assert!(!resources[1].is_resources_file());
+    match resources[1].data() {
+        ManagedResourceData::Embedded(data) => assert_eq!(*data, b"\x89PNG"),
+        data => panic!("{:?}", data),
+    }
+}
+
+/// File offset of `rva` in the `.text` section of [`assembly_data`]
+fn text_offset(rva: u32) -> usize {
+    0x200 + (rva - 0x1000) as usize
+}
+
+#[test]
+fn ijw_exports_are_traced_to_their_vtable_slots() {
+    let mut data = assembly_data(0, 0x0600_0001, &NATIVE_CODE, &[]);
+    // One 32-bit vtable fixup at RVA 0x1160 whose slot at 0x1170 holds the Main token
+    put32(&mut data, text_offset(0x1000) + 48, 0x1160);
+    put32(&mut data, text_offset(0x1000) + 52, 8);
+    let kind = COR_VTABLE_32BIT | COR_VTABLE_FROM_UNMANAGED;
+    put32(&mut data, text_offset(0x1160), 0x1170);
+    put32(&mut data, text_offset(0x1164), 1 | (kind as u32) << 16);
+    put32(&mut data, text_offset(0x1170), 0x0600_0001);
+    // Exported stub at 0x1260: jmp [slot]
+    data[text_offset(0x1260)..text_offset(0x1262)].copy_from_slice(&[0xFF, 0x25]);
+    put32(&mut data, text_offset(0x1262), 0x40_1170);
+    let specs = [ExportSpec::new(
+        Some("Exported".to_string()),
+        None,
+        ExportTarget::Rva(0x1260),
+    )];
+    let table = build_export_table("ijw.dll", &specs, 0x1280).unwrap();
+    data[text_offset(0x1280)..text_offset(0x1280) + table.len()].copy_from_slice(&table);
+    put32(&mut data, EXPORT_DIRECTORY, 0x1280);
+    put32(&mut data, EXPORT_DIRECTORY + 4, table.len() as u32);
+    let pe = PortExe::from_bytes(data).unwrap();
+    assert!(pe.is_mixed_mode());
+
+    let fixups = pe.vtable_fixups().unwrap();
+    assert_eq!(fixups.len(), 1);
+    assert_eq!((fixups[0].rva(), fixups[0].count()), (0x1170, 1));
+    assert!(fixups[0].from_unmanaged());
+    let slot = &fixups[0].slots()[0];
+    assert_eq!((slot.rva(), slot.token()), (0x1170, 0x0600_0001));
+    assert_eq!(slot.method_name(), Some("Main"));
+
+    let exports = pe.unmanaged_exports().unwrap();
+    assert_eq!(exports.len(), 1);
+    assert_eq!(exports[0].export().name().unwrap().to_string(), "Exported");
+    assert_eq!(exports[0].slot().unwrap().method_name(), Some("Main"));
+    assert!(managed().unmanaged_exports().unwrap().is_empty());
+}
@@ -0,0 +1,78 @@
+use pexp::codegen::CodegenLanguage;
+use pexp::export::{build_export_table, ExportSpec, ExportTarget};
+use pexp::testing::PeFixture;
+
+#[test]
+fn rust_modules_describe_the_image() {
+    let specs = [
+        ExportSpec::new(Some("Open".to_string()), None, ExportTarget::Rva(0x1000)),
+        ExportSpec::new(None, Some(5), ExportTarget::Rva(0x1010)),
+        ExportSpec::new(
+            Some("Sleep".to_string()),
+            None,
+            ExportTarget::Forwarder("kernel32.Sleep".to_string()),
+        ),
+    ];
+    let table = build_export_table("widget.dll", &specs, 0x2000).unwrap();
+    let pe = PeFixture::pe32()
+        .dll()
+        .time_date_stamp(0x5F00_0000)
+        .image_base(0x1000_0000)
+        .section(".t\"é", 0x6000_0020, &[0xC3; 0x20])
+        .section(".edata", 0x4000_0040, &table)
+        .directory(0, 0x2000, table.len() as u32)
+        .import("KERNEL32.dll", "Sleep")
+        .import_ordinal("ws2_32.dll", 23)
+        .parse()
+        .unwrap();
+    let code = pe.codegen(CodegenLanguage::Rust).unwrap();
+    assert!(code.starts_with("// Generated by pexp; do not edit.\n"));
+    assert!(code.contains("pub struct Section {"));
+    for line in [
+        "pub const MACHINE: u16 = 0x014c;",
+        "pub const TIME_DATE_STAMP: u32 = 0x5f000000;",
+        "pub const IMAGE_BASE: u64 = 0x10000000;",
+        "pub const DLL_NAME: &str = \"widget.dll\";",
+        // Names that are not plain ASCII are escaped byte for byte
+        "    Section { name: b\".t\\\"\\xc3\\xa9\", virtual_address: 0x1000, virtual_size: 0x20, \
+         size_of_raw_data: 0x200, characteristics: 0x60000020 },",
+        "    Export { ordinal: 1, name: Some(b\"Open\" as &[u8]), rva: 0x1000, forwarder: None },",
+        "    Export { ordinal: 5, name: None, rva: 0x1010, forwarder: None },",
+    ] {
+        assert!(code.contains(&format!("{}\n", line)), "{}\n{}", line, code);
+    }
+
+    let forwarder = &pe.exports().unwrap()[1];
+    assert!(code.contains(&format!(
+        "    Export {{ ordinal: 2, name: Some(b\"Sleep\" as &[u8]), rva: {:#x}, \
+         forwarder: Some(\"kernel32.Sleep\") }},\n",
+        forwarder.rva()
+    )));
+    let imports = pe.imports().unwrap();
+    assert!(code.contains(&format!(
+        "    Import {{ dll: \"KERNEL32.dll\", name: Some(b\"Sleep\" as &[u8]), hint: 0, \
+         ordinal: None, iat_rva: {:#x} }},\n",
+        imports[0].imports()[0].iat_rva()
+    )));
+    assert!(code.contains(&format!(
+        "    Import {{ dll: \"ws2_32.dll\", name: None, hint: 0, ordinal: Some(23), \
+         iat_rva: {:#x} }},\n",
+        imports[1].imports()[0].iat_rva()
+    )));
+}
+
+#[test]
+fn images_without_exports_or_imports_get_empty_tables() {
+    let code = PeFixture::pe64()
+        .image_base(0x1_4000_0000)
+        .section(".text", 0x6000_0020, &[0xC3])
+        .parse()
+        .unwrap()
+        .codegen(CodegenLanguage::Rust)
+        .unwrap();
+    assert!(code.contains("pub const MACHINE: u16 = 0x8664;\n"));
+    assert!(code.contains("pub const IMAGE_BASE: u64 = 0x140000000;\n"));
+    assert!(!code.contains("DLL_NAME"));
+    assert!(code.contains("pub static EXPORTS: &[Export] = &[\n];\n"));
+    assert!(code.ends_with("pub static IMPORTS: &[Import] = &[\n];\n"));
+}
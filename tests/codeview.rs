@@ -0,0 +1,138 @@
+mod common;
+
+use common::coff::object;
+use pexp::codeview::{
+    CV_SIGNATURE_C13, DEBUG_S_FILECHKSMS, DEBUG_S_IGNORE, DEBUG_S_STRINGTABLE, DEBUG_S_SYMBOLS,
+    LF_TYPESERVER2, S_COMPILE2, S_COMPILE3, S_OBJNAME,
+};
+
+const DEBUG: u32 = 0x4210_0040;
+const LF_ARGLIST: u16 = 0x1201;
+
+/// Symbol or type record of `kind`
+fn record(kind: u16, data: &[u8]) -> Vec<u8> {
+    let mut record = ((data.len() + 2) as u16).to_le_bytes().to_vec();
+    record.extend_from_slice(&kind.to_le_bytes());
+    record.extend_from_slice(data);
+    record
+}
+
+/// `.debug$S` subsection of `kind`, padded to a multiple of 4 bytes
+fn subsection(kind: u32, data: &[u8]) -> Vec<u8> {
+    let mut subsection = kind.to_le_bytes().to_vec();
+    subsection.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    subsection.extend_from_slice(data);
+    subsection.resize((subsection.len() + 3) & !3, 0);
+    subsection
+}
+
+fn c13(parts: &[Vec<u8>]) -> Vec<u8> {
+    let mut data = CV_SIGNATURE_C13.to_le_bytes().to_vec();
+    data.extend(parts.concat());
+    data
+}
+
+/// `S_COMPILE3` of a C++ compiler for x64 with front end version 19.38.33130.0
+fn compile3() -> Vec<u8> {
+    let mut data = vec![1, 0, 0, 0];
+    for value in [0xD0u16, 19, 38, 33130, 0, 19, 38, 33130, 0] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(b"Microsoft (R) Optimizing Compiler\0");
+    data
+}
+
+fn debug_symbols() -> Vec<u8> {
+    let mut objname = vec![0; 4];
+    objname.extend_from_slice(b"C:\\build\\widget.obj\0");
+    let symbols = [record(S_OBJNAME, &objname), record(S_COMPILE3, &compile3())].concat();
+
+    // widget.cpp with an MD5 checksum, widget.h without one, then widget.cpp again
+    let strings = b"\0widget.cpp\0widget.h\0";
+    let mut checksums = vec![1, 0, 0, 0, 16, 1];
+    checksums.extend_from_slice(&[0xAA; 16]);
+    checksums.extend_from_slice(&[0, 0]);
+    checksums.extend_from_slice(&[12, 0, 0, 0, 0, 0, 0, 0]);
+    checksums.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+
+    c13(&[
+        subsection(DEBUG_S_SYMBOLS, &symbols),
+        subsection(DEBUG_S_STRINGTABLE, strings),
+        subsection(DEBUG_S_FILECHKSMS | DEBUG_S_IGNORE, &checksums),
+    ])
+}
+
+#[test]
+fn object_debug_info_identifies_the_compiler_and_sources() {
+    let mut type_server = vec![0x11; 16];
+    type_server.extend_from_slice(&2u32.to_le_bytes());
+    type_server.extend_from_slice(b"C:\\build\\vc140.pdb\0");
+    let types = c13(&[
+        record(LF_ARGLIST, &[0; 4]),
+        record(LF_TYPESERVER2, &type_server),
+    ]);
+    let pe = object(
+        &[
+            (".text", 0x6050_0020, &[0xC3], 0),
+            (".debug$S", DEBUG, &debug_symbols(), 0),
+            (".debug$T", DEBUG, &types, 0),
+        ],
+        &[],
+    );
+
+    let info = pe.object_debug_info().unwrap().unwrap();
+    assert_eq!(info.object_name(), Some("C:\\build\\widget.obj"));
+    assert_eq!(info.source_files(), ["widget.cpp", "widget.h"]);
+    assert_eq!(info.type_records(), 2);
+    assert_eq!(info.type_server(), Some("C:\\build\\vc140.pdb"));
+    assert!(!info.precompiled_types());
+
+    let compiler = &info.compilers()[0];
+    assert_eq!((compiler.language(), compiler.machine()), (1, 0xD0));
+    assert_eq!(compiler.frontend_version(), [19, 38, 33130, 0]);
+    assert_eq!(compiler.backend_version(), [19, 38, 33130, 0]);
+    assert_eq!(compiler.version(), "Microsoft (R) Optimizing Compiler");
+}
+
+#[test]
+fn older_compilers_and_precompiled_types_are_recognised() {
+    let mut compile2 = vec![0, 0, 0, 0, 3, 0];
+    for value in [13u16, 10, 6030, 13, 10, 6030] {
+        compile2.extend_from_slice(&value.to_le_bytes());
+    }
+    compile2.extend_from_slice(b"Microsoft (R) Optimizing Compiler\0");
+    let symbols = c13(&[subsection(DEBUG_S_SYMBOLS, &record(S_COMPILE2, &compile2))]);
+    let pe = object(
+        &[
+            (".debug$S", DEBUG, &symbols, 0),
+            (".debug$P", DEBUG, &[0; 4], 0),
+        ],
+        &[],
+    );
+
+    let info = pe.object_debug_info().unwrap().unwrap();
+    assert!(info.precompiled_types());
+    assert_eq!(info.type_records(), 0);
+    let compiler = &info.compilers()[0];
+    assert_eq!((compiler.language(), compiler.machine()), (0, 3));
+    assert_eq!(compiler.frontend_version(), [13, 10, 6030, 0]);
+    assert_eq!(compiler.version(), "Microsoft (R) Optimizing Compiler");
+}
+
+#[test]
+fn objects_without_c13_codeview_are_told_apart() {
+    let pe = object(&[(".text", 0x6050_0020, &[0xC3], 0)], &[]);
+    assert!(pe.object_debug_info().unwrap().is_none());
+
+    // CodeView 4 and earlier used other signatures
+    let pe = object(&[(".debug$S", DEBUG, &[1, 0, 0, 0], 0)], &[]);
+    assert!(pe.object_debug_info().is_err());
+
+    // A subsection running past the section ends the walk without an error
+    let mut symbols = debug_symbols();
+    symbols.extend_from_slice(&subsection(DEBUG_S_SYMBOLS, &[])[..4]);
+    symbols.extend_from_slice(&0x100u32.to_le_bytes());
+    let pe = object(&[(".debug$S", DEBUG, &symbols, 0)], &[]);
+    let info = pe.object_debug_info().unwrap().unwrap();
+    assert_eq!(info.source_files().len(), 2);
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use common::coff::{function, object, section_definition, CHECKSUM, COMDAT_TEXT, TEXT};
+use pexp::comdat::ComdatSelection;
+use pexp::symbol::IMAGE_SYM_CLASS_STATIC;
+
+const COMDAT_DATA: u32 = 0x4030_1040;
+
+#[test]
+fn comdats_report_their_selection_and_associated_symbol() {
+    let section =
+        |name, number, definition| (name, 0, number, 0, IMAGE_SYM_CLASS_STATIC, definition);
+    let pe = object(
+        &[
+            (".text", TEXT, &[0; 0x10], 0),
+            (".text$mn", COMDAT_TEXT, &[0; 8], 0),
+            (".xdata", COMDAT_DATA, &[0; 4], 0),
+            (".pdata", COMDAT_DATA, &[0; 12], 0),
+            (".rdata$a_long_comdat_name", COMDAT_DATA, &[0; 16], 0),
+            // COMDAT flag without a section definition to read the selection from
+            (".text$x", COMDAT_TEXT, &[0; 4], 0),
+        ],
+        &[
+            section(".text", 1, section_definition(0x10, 0, 0)),
+            section(".text$mn", 2, section_definition(8, 0, 2)),
+            function("inline_fn", 0, 2),
+            // Unwind data is kept with the function, and the function table with that
+            section(".xdata", 3, section_definition(4, 2, 5)),
+            section(".pdata", 4, section_definition(12, 3, 5)),
+            section(".rdata$a", 5, section_definition(16, 0, 3)),
+            (
+                "??_C@_05string",
+                0,
+                5,
+                0,
+                IMAGE_SYM_CLASS_STATIC,
+                Vec::new(),
+            ),
+            function("orphan", 0, 6),
+        ],
+    );
+
+    let comdats = pe.comdats().unwrap();
+    let summary: Vec<_> = comdats
+        .iter()
+        .map(|comdat| {
+            (
+                comdat.section_number(),
+                comdat.section_name().to_string(),
+                comdat.selection(),
+                comdat.symbol(),
+                comdat.associated_section(),
+                comdat.associated_symbol(),
+                comdat.length(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (
+                2,
+                ".text$mn".to_string(),
+                ComdatSelection::Any,
+                Some("inline_fn"),
+                None,
+                None,
+                8
+            ),
+            (
+                3,
+                ".xdata".to_string(),
+                ComdatSelection::Associative,
+                None,
+                Some(2),
+                Some("inline_fn"),
+                4
+            ),
+            (
+                4,
+                ".pdata".to_string(),
+                ComdatSelection::Associative,
+                None,
+                Some(3),
+                Some("inline_fn"),
+                12
+            ),
+            (
+                5,
+                ".rdata$a_long_comdat_name".to_string(),
+                ComdatSelection::SameSize,
+                Some("??_C@_05string"),
+                None,
+                None,
+                16
+            ),
+        ]
+    );
+    assert!(comdats.iter().all(|comdat| comdat.checksum() == CHECKSUM));
+    assert_eq!(ComdatSelection::from(9), ComdatSelection::Unknown(9));
+}
+
+#[test]
+fn associative_cycles_do_not_hang() {
+    let section =
+        |name, number, definition| (name, 0, number, 0, IMAGE_SYM_CLASS_STATIC, definition);
+    let pe = object(
+        &[
+            (".xdata", COMDAT_DATA, &[0; 4], 0),
+            (".pdata", COMDAT_DATA, &[0; 4], 0),
+        ],
+        &[
+            section(".xdata", 1, section_definition(4, 2, 5)),
+            section(".pdata", 2, section_definition(4, 1, 5)),
+            function("unwind", 4, 1),
+        ],
+    );
+    let comdats = pe.comdats().unwrap();
+    assert_eq!(comdats.len(), 2);
+    // With no root to reach, the associated section's own symbol is reported
+    assert_eq!(comdats[0].associated_symbol(), None);
+    assert_eq!(comdats[1].associated_symbol(), Some("unwind"));
+}
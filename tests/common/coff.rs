@@ -0,0 +1,122 @@
+//! Builder of small COFF object files for the symbol table tests
+
+use pexp::port_exe::PortExe;
+use pexp::section_header::{IMAGE_SCN_LNK_COMDAT, IMAGE_SCN_LNK_NRELOC_OVFL};
+use pexp::symbol::{IMAGE_SIZEOF_SYMBOL, IMAGE_SYM_CLASS_EXTERNAL};
+
+/// Characteristics of a code section aligned to 16 bytes
+pub const TEXT: u32 = 0x6050_0020;
+pub const COMDAT_TEXT: u32 = TEXT | IMAGE_SCN_LNK_COMDAT;
+
+/// `(name, characteristics, data, relocations)` of an object file section
+pub type Section<'a> = (&'a str, u32, &'a [u8], u32);
+/// `(name, value, section number, type, storage class, auxiliary records)`
+pub type Symbol<'a> = (&'a str, u32, i16, u16, u8, Vec<u8>);
+
+/// Checksum recorded by [`section_definition`]
+pub const CHECKSUM: u32 = 0x00C0_FFEE;
+
+/// Auxiliary section definition record of a section of `length` bytes, associated with
+/// section `number` for associative COMDATs
+pub fn section_definition(length: u32, number: u16, selection: u8) -> Vec<u8> {
+    let mut aux = vec![0; IMAGE_SIZEOF_SYMBOL];
+    aux[0..4].copy_from_slice(&length.to_le_bytes());
+    aux[8..12].copy_from_slice(&CHECKSUM.to_le_bytes());
+    aux[12..14].copy_from_slice(&number.to_le_bytes());
+    aux[14] = selection;
+    aux
+}
+
+/// Auxiliary record of a `.file` symbol
+pub fn file_name(name: &str) -> Vec<u8> {
+    let mut aux = name.as_bytes().to_vec();
+    aux.resize(IMAGE_SIZEOF_SYMBOL, 0);
+    aux
+}
+
+/// External function symbol
+pub fn function(name: &str, value: u32, section: i16) -> Symbol<'_> {
+    (
+        name,
+        value,
+        section,
+        0x20,
+        IMAGE_SYM_CLASS_EXTERNAL,
+        Vec::new(),
+    )
+}
+
+/// x64 object file holding `sections`, names longer than 8 bytes going to the
+/// string table. More than 0xFFFF relocations are recorded the `IMAGE_SCN_LNK_NRELOC_OVFL`
+/// way, with only the first relocation written.
+pub fn object(sections: &[Section], symbols: &[Symbol]) -> PortExe {
+    let mut strings = Vec::new();
+    let mut long_name = |name: &str| {
+        let offset = 4 + strings.len();
+        strings.extend_from_slice(name.as_bytes());
+        strings.push(0);
+        offset
+    };
+
+    let mut data = vec![0; 20 + sections.len() * 40];
+    data[0..2].copy_from_slice(&0x8664u16.to_le_bytes());
+    data[2..4].copy_from_slice(&(sections.len() as u16).to_le_bytes());
+    for (index, &(name, characteristics, contents, relocations)) in sections.iter().enumerate() {
+        let header = 20 + index * 40;
+        let name = match name.len() {
+            0..=8 => name.to_string(),
+            _ => format!("/{}", long_name(name)),
+        };
+        data[header..header + name.len()].copy_from_slice(name.as_bytes());
+        let raw = data.len() as u32;
+        data.extend_from_slice(contents);
+        let overflow = relocations > 0xFFFE;
+        let (count, characteristics) = match overflow {
+            false => (relocations as u16, characteristics),
+            true => (0xFFFF, characteristics | IMAGE_SCN_LNK_NRELOC_OVFL),
+        };
+        let pointer = data.len() as u32;
+        for record in 0..if overflow { 1 } else { relocations } {
+            // The first relocation of an overflowing section holds the count, itself included
+            let address = if overflow {
+                relocations + 1
+            } else {
+                record * 8
+            };
+            data.extend_from_slice(&address.to_le_bytes());
+            data.extend_from_slice(&[0; 6]);
+        }
+        for (offset, value) in [
+            (16, contents.len() as u32),
+            (20, raw),
+            (24, if relocations == 0 { 0 } else { pointer }),
+            (36, characteristics),
+        ] {
+            data[header + offset..header + offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        data[header + 32..header + 34].copy_from_slice(&count.to_le_bytes());
+    }
+
+    let table = data.len() as u32;
+    let mut count = 0u32;
+    for (name, value, section, symbol_type, class, aux) in symbols {
+        let mut record = [0u8; IMAGE_SIZEOF_SYMBOL];
+        match name.len() {
+            0..=8 => record[..name.len()].copy_from_slice(name.as_bytes()),
+            _ => record[4..8].copy_from_slice(&(long_name(name) as u32).to_le_bytes()),
+        }
+        record[8..12].copy_from_slice(&value.to_le_bytes());
+        record[12..14].copy_from_slice(&section.to_le_bytes());
+        record[14..16].copy_from_slice(&symbol_type.to_le_bytes());
+        record[16] = *class;
+        record[17] = (aux.len() / IMAGE_SIZEOF_SYMBOL) as u8;
+        data.extend_from_slice(&record);
+        data.extend_from_slice(aux);
+        count += 1 + record[17] as u32;
+    }
+    data[8..12].copy_from_slice(&table.to_le_bytes());
+    data[12..16].copy_from_slice(&count.to_le_bytes());
+    data.extend_from_slice(&(4 + strings.len() as u32).to_le_bytes());
+    data.extend_from_slice(&strings);
+    PortExe::from_bytes(data).unwrap()
+}
@@ -0,0 +1,248 @@
+//! Shared support for the integration tests: byte-array fixtures, COFF object, resource
+//! section and version resource builders, and a driver that runs every analysis over a
+//! parsed file.
+
+#![allow(dead_code)]
+
+pub mod coff;
+pub mod fixtures;
+
+use pexp::port_exe::PortExe;
+use pexp::validation::ValidationMode;
+use std::fs;
+use std::path::PathBuf;
+
+/// File offset of the data directories of a PE32 image laid out by
+/// [`PeFixture`](pexp::testing::PeFixture)
+pub const DATA_DIRECTORIES: usize = 0x80 + 4 + 20 + 96;
+
+/// Writes `value` little-endian at `offset` of `data`
+pub fn put32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Fresh scratch directory, named after `tag`, under the system temporary directory
+pub fn scratch(tag: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("pexp-{}-{}", tag, std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    root
+}
+
+/// Type or name of a resource placed by [`resource_section`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKey<'a> {
+    Id(u16),
+    Name(&'a str),
+}
+
+/// Type, name and data of a resource
+pub type Resource<'a> = (ResourceKey<'a>, ResourceKey<'a>, &'a [u8]);
+
+/// Resource section to be mapped at `rva`, holding each `(type, name, data)` in
+/// language 0x409. Directories come first, then the data entries, the names and the
+/// data.
+pub fn resource_section(rva: u32, resources: &[Resource]) -> Vec<u8> {
+    let mut types: Vec<(ResourceKey, Vec<_>)> = Vec::new();
+    for &(type_key, name_key, data) in resources {
+        match types.iter_mut().find(|(key, _)| *key == type_key) {
+            Some((_, names)) => names.push((name_key, data)),
+            None => types.push((type_key, vec![(name_key, data)])),
+        }
+    }
+    // Named entries precede ID entries in every directory
+    let is_id = |key: &ResourceKey| matches!(key, ResourceKey::Id(_));
+    types.sort_by_key(|(key, _)| is_id(key));
+    for (_, names) in &mut types {
+        names.sort_by_key(|(key, _)| is_id(key));
+    }
+
+    let directory = |entries: usize| 16 + 8 * entries;
+    let mut name_directories = directory(types.len());
+    let mut language_directories = name_directories
+        + types
+            .iter()
+            .map(|(_, names)| directory(names.len()))
+            .sum::<usize>();
+    let mut data_entries = language_directories + directory(1) * resources.len();
+    let strings = data_entries + 16 * resources.len();
+    let mut string_data = Vec::new();
+    let mut blobs = Vec::new();
+    let encode = |key: ResourceKey, string_data: &mut Vec<u8>| match key {
+        ResourceKey::Id(id) => id as u32,
+        ResourceKey::Name(name) => {
+            let offset = strings + string_data.len();
+            let units: Vec<u16> = name.encode_utf16().collect();
+            push16(string_data, units.len() as u16);
+            for unit in units {
+                push16(string_data, unit);
+            }
+            0x8000_0000 | offset as u32
+        }
+    };
+
+    let mut root = Vec::new();
+    let mut name_tables = Vec::new();
+    let mut language_tables = Vec::new();
+    let mut entries = Vec::new();
+    let directory_header = |out: &mut Vec<u8>, keys: &[ResourceKey]| {
+        let named = keys.iter().filter(|key| !is_id(key)).count();
+        out.extend_from_slice(&[0; 12]);
+        push16(out, named as u16);
+        push16(out, (keys.len() - named) as u16);
+    };
+    let type_keys: Vec<ResourceKey> = types.iter().map(|(key, _)| *key).collect();
+    directory_header(&mut root, &type_keys);
+    let mut pending = Vec::new();
+    for (type_key, names) in &types {
+        push32(&mut root, encode(*type_key, &mut string_data));
+        push32(&mut root, 0x8000_0000 | name_directories as u32);
+        let name_keys: Vec<ResourceKey> = names.iter().map(|(key, _)| *key).collect();
+        directory_header(&mut name_tables, &name_keys);
+        for (name_key, data) in names {
+            push32(&mut name_tables, encode(*name_key, &mut string_data));
+            push32(&mut name_tables, 0x8000_0000 | language_directories as u32);
+            directory_header(&mut language_tables, &[ResourceKey::Id(0x409)]);
+            push32(&mut language_tables, 0x409);
+            push32(&mut language_tables, data_entries as u32);
+            pending.push(*data);
+            language_directories += directory(1);
+            data_entries += 16;
+        }
+        name_directories += directory(names.len());
+    }
+    let data_start = align((strings + string_data.len()) as u32, 8);
+    for data in pending {
+        push32(&mut entries, rva + data_start + blobs.len() as u32);
+        push32(&mut entries, data.len() as u32);
+        push32(&mut entries, 0);
+        push32(&mut entries, 0);
+        blobs.extend_from_slice(data);
+        blobs.resize(align(blobs.len() as u32, 8) as usize, 0);
+    }
+
+    let mut section = root;
+    section.extend_from_slice(&name_tables);
+    section.extend_from_slice(&language_tables);
+    section.extend_from_slice(&entries);
+    section.extend_from_slice(&string_data);
+    section.resize(data_start as usize, 0);
+    section.extend_from_slice(&blobs);
+    section
+}
+
+/// `text` as NUL-terminated UTF-16LE
+pub fn utf16z(text: &str) -> Vec<u8> {
+    text.encode_utf16()
+        .chain([0])
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+/// `VS_VERSIONINFO` node: header, key, value and children, each aligned to 4 bytes
+pub fn version_node(key: &str, value: &[u8], text: bool, children: &[Vec<u8>]) -> Vec<u8> {
+    let mut node = vec![0; 6];
+    node.extend_from_slice(&utf16z(key));
+    node.resize((node.len() + 3) & !3, 0);
+    node.extend_from_slice(value);
+    for child in children {
+        node.resize((node.len() + 3) & !3, 0);
+        node.extend_from_slice(child);
+    }
+    let value_length = if text { value.len() / 2 } else { value.len() };
+    let length = node.len() as u16;
+    node[0..2].copy_from_slice(&length.to_le_bytes());
+    node[2..4].copy_from_slice(&(value_length as u16).to_le_bytes());
+    node[4..6].copy_from_slice(&(text as u16).to_le_bytes());
+    node
+}
+
+/// Runs every analysis of the crate over `data`, discarding the results.
+///
+/// Errors are expected for malformed input; only panics are test failures.
+pub fn exercise(data: &[u8]) {
+    let pe = match PortExe::from_bytes(data.to_vec()) {
+        Ok(pe) => pe,
+        Err(_) => return,
+    };
+    for section in pe.section_headers() {
+        let _ = pe.section_data(section);
+        let _ = pe.relocation_count(section);
+    }
+    let _ = pe.to_bytes();
+    let _ = pe.validate(ValidationMode::Strict);
+    let _ = pe.checksec();
+    let _ = pe.load_config();
+    if let Ok(entries) = pe.debug_directory() {
+        for entry in &entries {
+            let _ = pe.debug_data(entry);
+        }
+    }
+    let _ = pe.exports();
+    let _ = pe.artifacts().count();
+    let _ = pe.platform_compatibility();
+    let _ = pe.minimum_os(&pexp::min_os::ApiVersionMap::bundled());
+    let _ = pe.imports();
+    let _ = pe.import_table();
+    let _ = pe.store_app_report(Some(&pexp::appcontainer::ApiAllowlist::default()));
+    let _ = pe.mingw_sections();
+    let _ = pe.dwarf_summary();
+    let _ = pe.module_definition();
+    let _ = pe.codegen(pexp::codegen::CodegenLanguage::Rust);
+    let _ = pe.resources();
+    let _ = pe.resource_directory(&pexp::resource::ResourceLimits::default());
+    let _ = pe.string_resources();
+    let _ = pe.resource_waste();
+    let _ = pe.version_info();
+    let _ = pe.manifest();
+    let _ = pe.rich_header();
+    let _ = pe.certificates();
+    let _ = pe.icon_hash();
+    let _ = pe.provenance();
+    let _ = pe.sniff_overlay(&pexp::overlay::OverlaySniffers::default());
+    let _ = pe.signature_layout();
+    let _ = pe.signing_requirements();
+    let _ = pe.slack_regions();
+    let _ = pe.tls_template();
+    let _ = pe.iat_map();
+    let _ = pe.annotations();
+    #[cfg(feature = "disassembler")]
+    let _ = pe.entry_trace();
+    let _ = pe.release_profile("exercise");
+    let _ = pe.pdb_path();
+    let _ = pe.ordinal_coverage();
+    let _ = pexp::repro::compare_builds(&pe, &pe);
+    let _ = pe.file_region(data.len() / 2);
+    let _ = pe.fingerprint("exercise");
+    let _ = pe.timeline_entry("exercise");
+    for function in pe.runtime_functions().unwrap_or_default() {
+        if let pexp::exception::RuntimeFunction::X64(function) = function {
+            let _ = pe.unwind_info(&function);
+        }
+    }
+    let _ = pe.truncation();
+    let _ = pe.delay_import_table();
+    let _ = pe.base_relocations();
+    let _ = pe.guard_cf_functions();
+    let _ = pe.cross_check();
+    let _ = pe.metadata();
+    let _ = pe.managed_resources();
+    let _ = pe.vtable_fixups();
+    let _ = pe.unmanaged_exports();
+    let _ = pe.coff_symbols();
+    let _ = pe.comdats();
+    let _ = pe.object_debug_info();
+    let _ = pexp::analysis::section_compression(&pe);
+}
+
+fn align(value: u32, alignment: u32) -> u32 {
+    (value + alignment - 1) / alignment * alignment
+}
+
+fn push16(data: &mut Vec<u8>, value: u16) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push32(data: &mut Vec<u8>, value: u32) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
@@ -0,0 +1,128 @@
+use pexp::config::{ColorChoice, Config};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const PROJECT: &str = r#"
+# Settings shared by the release scripts
+format = "x64dbg"
+analyses = ["security", 'entropy']   # no anomalies
+search-paths = ["redist", "C:\\Tools\\bin"]
+policy = 'store-apis.txt'
+color = "never"
+"#;
+
+#[test]
+fn settings_are_parsed_relative_to_the_file() {
+    let config = Config::parse(PROJECT, Path::new("project")).unwrap();
+    assert_eq!(config.format(), Some("x64dbg"));
+    assert_eq!(
+        config.analyses().unwrap(),
+        ["security".to_string(), "entropy".to_string()]
+    );
+    assert_eq!(
+        config.search_paths(),
+        [
+            Path::new("project").join("redist"),
+            Path::new("project").join("C:\\Tools\\bin")
+        ]
+    );
+    assert_eq!(
+        config.policy(),
+        Some(Path::new("project").join("store-apis.txt").as_path())
+    );
+    assert_eq!(config.color(), ColorChoice::Never);
+    assert!(config.sources().is_empty());
+
+    let empty = Config::parse("# nothing set\n\n", Path::new(".")).unwrap();
+    assert_eq!(empty, Config::default());
+    assert_eq!(empty.color(), ColorChoice::Auto);
+    assert_eq!(empty.analyses(), None);
+}
+
+#[test]
+fn project_settings_override_user_settings() {
+    let user = Config::parse(
+        "format = \"ida\"\ncolor = \"always\"\nsearch-paths = [\"sdk\"]",
+        Path::new("home"),
+    )
+    .unwrap();
+    let project = Config::parse(
+        "format = \"ghidra\"\nsearch-paths = [\"redist\"]",
+        Path::new("project"),
+    )
+    .unwrap();
+    let config = user.merge(project);
+    assert_eq!(config.format(), Some("ghidra"));
+    assert_eq!(config.color(), ColorChoice::Always);
+    assert_eq!(
+        config.search_paths(),
+        [PathBuf::from("project/redist"), PathBuf::from("home/sdk")]
+    );
+}
+
+#[test]
+fn project_files_are_found_in_parent_directories() {
+    let root = std::env::temp_dir().join(format!("pexp-config-{}", std::process::id()));
+    let nested = root.join("src").join("bin");
+    fs::create_dir_all(&nested).unwrap();
+    let file = root.join("pexp.toml");
+    fs::write(&file, "analyses = \"all\"\n").unwrap();
+
+    assert_eq!(
+        pexp::config::project_config_path(&nested),
+        Some(file.clone())
+    );
+    let config = Config::load(&file).unwrap();
+    assert_eq!(config.analyses().unwrap(), ["all".to_string()]);
+    assert_eq!(config.sources().to_vec(), vec![file.clone()]);
+
+    fs::write(&file, "colour = \"never\"\n").unwrap();
+    let error = Config::load(&file).unwrap_err();
+    fs::remove_dir_all(&root).unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(error.to_string().ends_with("line 1: unknown key colour"));
+}
+
+#[test]
+fn malformed_values_are_reported_with_their_line() {
+    for (text, message) in [
+        ("[scan]", "line 1: tables are not supported"),
+        (
+            "\nformat = ida",
+            "line 2: expected a string, a boolean or an array",
+        ),
+        ("format = \"ida", "line 1: unterminated string"),
+        ("format = [\"ida\"]", "line 1: format must be a string"),
+        (
+            "analyses = [\"a\" \"b\"]",
+            "line 1: expected , between array items",
+        ),
+        (
+            "analyses = [\"a\",",
+            "line 1: arrays must end on the line they start",
+        ),
+        (
+            "color = \"sometimes\"",
+            "line 1: unknown color choice sometimes",
+        ),
+        (
+            "search-paths = true",
+            "line 1: search-paths must be an array of strings",
+        ),
+    ] {
+        let error = Config::parse(text, Path::new(".")).unwrap_err();
+        assert_eq!(error.to_string(), message, "{}", text);
+    }
+}
+
+#[test]
+fn auto_color_follows_the_terminal() {
+    assert!(ColorChoice::Always.enabled(false));
+    assert!(!ColorChoice::Never.enabled(true));
+    if env::var_os("NO_COLOR").is_none() && env::var_os("CLICOLOR_FORCE").is_none() {
+        assert!(ColorChoice::Auto.enabled(true));
+        assert!(!ColorChoice::Auto.enabled(false));
+    }
+}
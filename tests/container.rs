@@ -0,0 +1,325 @@
+#![cfg(feature = "containers")]
+
+mod common;
+
+use common::fixtures;
+use pexp::container::{contained_images, open_container, Cabinet, CompoundFile, DataSource};
+
+/// Zip archive of `files`, deflated when `deflate` is set, with offsets relative to the
+/// start of the archive as a zip tool appending to a stub would leave them.
+fn zip(files: &[(&str, &[u8])], deflate: bool) -> Vec<u8> {
+    let mut archive = Vec::new();
+    let mut central_directory = Vec::new();
+    for (name, data) in files {
+        let stored = if deflate {
+            miniz_oxide::deflate::compress_to_vec(data, 6)
+        } else {
+            data.to_vec()
+        };
+        let method: u16 = if deflate { 8 } else { 0 };
+        let mut header = Vec::new();
+        header.extend_from_slice(&method.to_le_bytes());
+        header.extend_from_slice(&[0; 8]);
+        header.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&[0; 2]);
+
+        central_directory.extend_from_slice(b"PK\x01\x02\x14\x00\x14\x00\x00\x00");
+        central_directory.extend_from_slice(&header);
+        central_directory.extend_from_slice(&[0; 10]);
+        central_directory.extend_from_slice(&(archive.len() as u32).to_le_bytes());
+        central_directory.extend_from_slice(name.as_bytes());
+
+        archive.extend_from_slice(b"PK\x03\x04\x14\x00\x00\x00");
+        archive.extend_from_slice(&header);
+        archive.extend_from_slice(name.as_bytes());
+        archive.extend_from_slice(&stored);
+    }
+    let offset = archive.len() as u32;
+    archive.extend_from_slice(&central_directory);
+    archive.extend_from_slice(b"PK\x05\x06\x00\x00\x00\x00");
+    archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&(central_directory.len() as u32).to_le_bytes());
+    archive.extend_from_slice(&offset.to_le_bytes());
+    archive.extend_from_slice(&[0; 2]);
+    archive
+}
+
+/// Cabinet with one folder holding `files`, split into data blocks of `block_size`
+/// bytes and compressed with MSZIP when `mszip` is set.
+fn cabinet(files: &[(&str, &[u8])], mszip: bool, block_size: usize) -> Vec<u8> {
+    let contents: Vec<u8> = files.iter().flat_map(|(_, data)| data.to_vec()).collect();
+    let mut entries = Vec::new();
+    let mut offset = 0u32;
+    for (name, data) in files {
+        entries.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        entries.extend_from_slice(&offset.to_le_bytes());
+        entries.extend_from_slice(&[0; 6]);
+        entries.extend_from_slice(&0x20u16.to_le_bytes());
+        entries.extend_from_slice(name.as_bytes());
+        entries.push(0);
+        offset += data.len() as u32;
+    }
+    let mut blocks = Vec::new();
+    let mut count = 0u16;
+    for chunk in contents.chunks(block_size) {
+        let stored = if mszip {
+            [&b"CK"[..], &miniz_oxide::deflate::compress_to_vec(chunk, 6)].concat()
+        } else {
+            chunk.to_vec()
+        };
+        blocks.extend_from_slice(&[0; 4]);
+        blocks.extend_from_slice(&(stored.len() as u16).to_le_bytes());
+        blocks.extend_from_slice(&(chunk.len() as u16).to_le_bytes());
+        blocks.extend_from_slice(&stored);
+        count += 1;
+    }
+    let files_offset = 36 + 8;
+    let data_offset = files_offset + entries.len();
+    let mut cabinet = b"MSCF\0\0\0\0".to_vec();
+    cabinet.extend_from_slice(&((data_offset + blocks.len()) as u32).to_le_bytes());
+    cabinet.extend_from_slice(&[0; 4]);
+    cabinet.extend_from_slice(&(files_offset as u32).to_le_bytes());
+    cabinet.extend_from_slice(&[0, 0, 0, 0, 3, 1, 1, 0]);
+    cabinet.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    cabinet.extend_from_slice(&[0; 6]);
+    cabinet.extend_from_slice(&(data_offset as u32).to_le_bytes());
+    cabinet.extend_from_slice(&count.to_le_bytes());
+    cabinet.extend_from_slice(&(mszip as u16).to_le_bytes());
+    cabinet.extend_from_slice(&entries);
+    cabinet.extend_from_slice(&blocks);
+    cabinet
+}
+
+/// MSI stream name `name`, packed two characters to a unit
+fn msi_name(name: &str) -> Vec<u16> {
+    const ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz._";
+    let index = |byte| ALPHABET.iter().position(|&c| c == byte).unwrap() as u16;
+    name.as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [first, second] => 0x3800 + index(*first) + (index(*second) << 6),
+            [single] => 0x4800 + index(*single),
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/// Version 3 compound file with `streams` under the root storage, the ones smaller
+/// than 4096 bytes in the mini stream.
+fn compound_file(streams: &[(Vec<u16>, &[u8])]) -> Vec<u8> {
+    const END_OF_CHAIN: u32 = 0xFFFF_FFFE;
+    const NO_STREAM: u32 = 0xFFFF_FFFF;
+    let mut sectors: Vec<u8> = Vec::new();
+    let mut fat: Vec<u32> = Vec::new();
+    let mut chain = |data: &[u8], sectors: &mut Vec<u8>| {
+        let start = fat.len() as u32;
+        let count = (data.len() + 511) / 512;
+        for index in 0..count {
+            let mut sector = data[index * 512..]
+                .iter()
+                .take(512)
+                .copied()
+                .collect::<Vec<_>>();
+            sector.resize(512, 0);
+            sectors.extend_from_slice(&sector);
+            let next = start + index as u32 + 1;
+            fat.push(if index + 1 < count {
+                next
+            } else {
+                END_OF_CHAIN
+            });
+        }
+        if count == 0 {
+            END_OF_CHAIN
+        } else {
+            start
+        }
+    };
+    let mut mini_stream = Vec::new();
+    let mut mini_fat = Vec::new();
+    let mut entries = Vec::new();
+    for (name, data) in streams {
+        let start = if data.len() < 4096 {
+            let start = (mini_stream.len() / 64) as u32;
+            let count = (data.len() + 63) / 64;
+            for index in 0..count as u32 {
+                let next = start + index + 1;
+                mini_fat.push(if index + 1 < count as u32 {
+                    next
+                } else {
+                    END_OF_CHAIN
+                });
+            }
+            mini_stream.extend_from_slice(data);
+            mini_stream.resize(mini_stream.len() + (64 - data.len() % 64) % 64, 0);
+            start
+        } else {
+            chain(data, &mut sectors)
+        };
+        entries.push((name.clone(), 2u8, start, data.len()));
+    }
+    let mini_stream_start = chain(&mini_stream, &mut sectors);
+    let mini_fat_bytes: Vec<u8> = mini_fat
+        .iter()
+        .flat_map(|entry: &u32| entry.to_le_bytes())
+        .collect();
+    let mini_fat_start = chain(&mini_fat_bytes, &mut sectors);
+
+    let entry = |name: &[u16], kind: u8, right: u32, child: u32, start: u32, size: usize| {
+        let mut entry = vec![0u8; 128];
+        for (index, unit) in name.iter().enumerate() {
+            entry[index * 2..index * 2 + 2].copy_from_slice(&unit.to_le_bytes());
+        }
+        entry[0x40..0x42].copy_from_slice(&((name.len() as u16 + 1) * 2).to_le_bytes());
+        entry[0x42] = kind;
+        entry[0x44..0x48].copy_from_slice(&NO_STREAM.to_le_bytes());
+        entry[0x48..0x4C].copy_from_slice(&right.to_le_bytes());
+        entry[0x4C..0x50].copy_from_slice(&child.to_le_bytes());
+        entry[0x74..0x78].copy_from_slice(&start.to_le_bytes());
+        entry[0x78..0x80].copy_from_slice(&(size as u64).to_le_bytes());
+        entry
+    };
+    let root_name: Vec<u16> = "Root Entry".encode_utf16().collect();
+    let mut directory = entry(
+        &root_name,
+        5,
+        NO_STREAM,
+        1,
+        mini_stream_start,
+        mini_stream.len(),
+    );
+    for (index, (name, kind, start, size)) in entries.iter().enumerate() {
+        let right = if index + 1 < entries.len() {
+            index as u32 + 2
+        } else {
+            NO_STREAM
+        };
+        directory.extend(entry(name, *kind, right, NO_STREAM, *start, *size));
+    }
+    let directory_start = chain(&directory, &mut sectors);
+
+    let fat_sector = fat.len() as u32;
+    fat.push(0xFFFF_FFFD);
+    fat.resize(128, NO_STREAM);
+    let mut file = vec![0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+    file.resize(0x18, 0);
+    for field in [0x3Eu16, 3, 0xFFFE, 9, 6] {
+        file.extend_from_slice(&field.to_le_bytes());
+    }
+    file.resize(0x2C, 0);
+    for field in [
+        1,
+        directory_start,
+        0,
+        4096,
+        mini_fat_start,
+        (mini_fat.len() as u32 + 127) / 128,
+        END_OF_CHAIN,
+        0,
+        fat_sector,
+    ] {
+        file.extend_from_slice(&field.to_le_bytes());
+    }
+    file.resize(512, 0xFF);
+    file.extend_from_slice(&sectors);
+    file.extend(fat.iter().flat_map(|entry| entry.to_le_bytes()));
+    file
+}
+
+/// Names and contents of every file of `source`
+fn extract(source: &mut dyn DataSource) -> Vec<(String, Vec<u8>)> {
+    source
+        .files()
+        .to_vec()
+        .iter()
+        .map(|file| (file.name().to_string(), source.read(file).unwrap()))
+        .collect()
+}
+
+#[test]
+fn zip_archives_expose_their_files() {
+    let files: [(&str, &[u8]); 2] = [
+        ("bin/app.exe", fixtures::PE32_EXE),
+        ("lib/math.dll", fixtures::PE32_DLL),
+    ];
+    for deflate in [false, true] {
+        let mut archive = open_container(&zip(&files, deflate)).unwrap().unwrap();
+        let extracted = extract(archive.as_mut());
+        assert_eq!(extracted.len(), 2);
+        for ((name, data), (expected_name, expected)) in extracted.iter().zip(&files) {
+            assert_eq!(name, expected_name);
+            assert_eq!(data, expected);
+        }
+    }
+}
+
+/// Self-extracting installers append the archive to an executable stub.
+#[test]
+fn archives_after_an_installer_stub_are_found() {
+    let files: [(&str, &[u8]); 1] = [("payload.dll", fixtures::PE32_DLL)];
+    for payload in [zip(&files, true), cabinet(&files, true, 0x8000)] {
+        let installer = [fixtures::PE32_EXE, &payload].concat();
+        let mut container = open_container(&installer).unwrap().unwrap();
+        assert_eq!(
+            extract(container.as_mut()),
+            [("payload.dll".to_string(), fixtures::PE32_DLL.to_vec())]
+        );
+    }
+    assert!(open_container(fixtures::PE32_EXE).unwrap().is_none());
+}
+
+#[test]
+fn cabinet_folders_span_data_blocks() {
+    let files: [(&str, &[u8]); 3] = [
+        ("drivers\\sys.sys", fixtures::PE32PLUS_DRIVER),
+        ("readme.txt", b"not an image"),
+        ("app.exe", fixtures::PE32PLUS_EXE),
+    ];
+    for mszip in [false, true] {
+        let mut cabinet = Cabinet::parse(cabinet(&files, mszip, 0x100)).unwrap();
+        let extracted = extract(&mut cabinet);
+        let names: Vec<&str> = extracted.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["drivers/sys.sys", "readme.txt", "app.exe"]);
+        for ((_, data), (_, expected)) in extracted.iter().zip(&files) {
+            assert_eq!(data, expected);
+        }
+    }
+}
+
+/// MSI packages keep custom action DLLs in `Binary` streams and media in cabinets.
+#[test]
+fn msi_packages_expose_embedded_images() {
+    let media = cabinet(
+        &[
+            ("app.exe", fixtures::PE32PLUS_EXE),
+            ("drivers\\sys.sys", fixtures::PE32PLUS_DRIVER),
+        ],
+        true,
+        0x8000,
+    );
+    let padded_media = [&media[..], &[0; 4096]].concat();
+    let package = compound_file(&[
+        (msi_name("Binary.CustomAction"), fixtures::PE32_DLL),
+        ([&[0x4840][..], &msi_name("File")].concat(), b"table rows"),
+        (msi_name("Data1.cab"), &padded_media),
+    ]);
+    let mut package = CompoundFile::parse(package).unwrap();
+    let names: Vec<&str> = package.files().iter().map(|file| file.name()).collect();
+    assert_eq!(names, ["Binary.CustomAction", "!File", "Data1.cab"]);
+
+    let images = contained_images(&mut package);
+    let paths: Vec<&str> = images.iter().map(|image| image.path()).collect();
+    assert_eq!(
+        paths,
+        [
+            "Binary.CustomAction",
+            "Data1.cab/app.exe",
+            "Data1.cab/drivers/sys.sys"
+        ]
+    );
+    assert_eq!(images[0].image().unwrap().data(), fixtures::PE32_DLL);
+    assert_eq!(images[2].image().unwrap().data(), fixtures::PE32PLUS_DRIVER);
+}
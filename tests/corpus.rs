@@ -0,0 +1,42 @@
+mod common;
+
+use common::{exercise, fixtures};
+use std::fs;
+use std::panic;
+use std::path::Path;
+
+/// Parses every file in `tests/corpus` and runs all analyses over it; any panic fails the test.
+#[test]
+fn corpus_does_not_panic() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("corpus");
+    let mut failures = Vec::new();
+    for entry in fs::read_dir(&corpus).unwrap() {
+        let path = entry.unwrap().path();
+        if !path.is_file() || path.extension().map_or(false, |ext| ext == "md") {
+            continue;
+        }
+        let data = fs::read(&path).unwrap();
+        if panic::catch_unwind(|| exercise(&data)).is_err() {
+            failures.push(path.display().to_string());
+        }
+    }
+    assert!(failures.is_empty(), "panicked on {:?}", failures);
+}
+
+/// Truncated fixtures exercise the bounds checks of every reader.
+#[test]
+fn truncated_fixtures_do_not_panic() {
+    for data in [
+        fixtures::PE32_EXE,
+        fixtures::PE32PLUS_EXE,
+        fixtures::PE32_DLL,
+        fixtures::PE32PLUS_DRIVER,
+        fixtures::AMD64_OBJECT,
+    ] {
+        for length in 0..data.len() {
+            exercise(&data[..length]);
+        }
+    }
+}
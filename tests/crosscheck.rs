@@ -0,0 +1,158 @@
+use pexp::crosscheck::CrossCheckFinding;
+use pexp::load_config::{
+    IMAGE_GUARD_CF_FUNCTION_TABLE_PRESENT, IMAGE_GUARD_CF_FUNCTION_TABLE_SIZE_SHIFT,
+    IMAGE_GUARD_CF_INSTRUMENTED,
+};
+use pexp::port_exe::PortExe;
+use pexp::reloc::{IMAGE_REL_BASED_DIR64, IMAGE_REL_BASED_HIGHLOW};
+use pexp::testing::PeFixture;
+
+const EXCEPTION: usize = 3;
+const BASERELOC: usize = 5;
+const LOAD_CONFIG: usize = 10;
+const IMAGE_BASE_64: u64 = 0x1_4000_0000;
+const CFG: u32 = IMAGE_GUARD_CF_INSTRUMENTED | IMAGE_GUARD_CF_FUNCTION_TABLE_PRESENT;
+
+fn put(data: &mut [u8], offset: usize, value: &[u8]) {
+    data[offset..offset + value.len()].copy_from_slice(value);
+}
+
+/// x64 image with 0x100 bytes of code at RVA 0x1000 and, in `.rdata` at RVA 0x2000, a
+/// load config setting `guard_flags`, five `.pdata` entries at 0x2200, three relocated
+/// pointers to two of the functions at 0x2300 and, at 0x2340, a CFG table of two entries
+/// with `extra` bytes of metadata each that leaves out the second function
+fn image(guard_flags: u32, extra: usize) -> PortExe {
+    let mut rdata = vec![0u8; 0x400];
+    put(&mut rdata, 0, &312u32.to_le_bytes());
+    put(&mut rdata, 128, &(IMAGE_BASE_64 + 0x2340).to_le_bytes());
+    put(&mut rdata, 136, &2u64.to_le_bytes());
+    put(&mut rdata, 144, &guard_flags.to_le_bytes());
+    let functions = [
+        (0x1000u32, 0x1020u32),
+        (0x1040, 0x1060),
+        (0x1050, 0x1070),
+        (0x10F0, 0x1200),
+        (0x2000, 0x2010),
+    ];
+    for (index, (begin, end)) in functions.iter().enumerate() {
+        put(&mut rdata, 0x200 + index * 12, &begin.to_le_bytes());
+        put(&mut rdata, 0x204 + index * 12, &end.to_le_bytes());
+    }
+    for (index, target) in [0x1000u64, 0x1040, 0x1040].iter().enumerate() {
+        put(
+            &mut rdata,
+            0x300 + index * 8,
+            &(IMAGE_BASE_64 + target).to_le_bytes(),
+        );
+    }
+    // The second entry lists a function no relocation points at
+    put(&mut rdata, 0x340, &0x1000u32.to_le_bytes());
+    put(&mut rdata, 0x344 + extra, &0x10F0u32.to_le_bytes());
+
+    let mut reloc = 0x2000u32.to_le_bytes().to_vec();
+    reloc.extend_from_slice(&16u32.to_le_bytes());
+    for offset in [0x300u16, 0x308, 0x310, 0] {
+        let kind = if offset == 0 {
+            0
+        } else {
+            IMAGE_REL_BASED_DIR64
+        };
+        reloc.extend_from_slice(&((kind as u16) << 12 | offset).to_le_bytes());
+    }
+    PeFixture::pe64()
+        .section(".text", 0x6000_0020, &[0xCC; 0x100])
+        .section(".rdata", 0x4000_0040, &rdata)
+        .section(".reloc", 0x4200_0040, &reloc)
+        .directory(EXCEPTION, 0x2200, 60)
+        .directory(BASERELOC, 0x3000, 16)
+        .directory(LOAD_CONFIG, 0x2000, 312)
+        .parse()
+        .unwrap()
+}
+
+#[test]
+fn pdata_relocations_and_cfg_table_are_correlated() {
+    let check = image(CFG, 0).cross_check().unwrap();
+    assert_eq!(check.functions(), 5);
+    assert_eq!(check.address_taken(), 2);
+    assert_eq!(check.cfg_targets(), Some(2));
+    assert_eq!(
+        check.findings(),
+        [
+            CrossCheckFinding::Overlap {
+                previous: 0x1040,
+                begin: 0x1050
+            },
+            CrossCheckFinding::InvalidRange {
+                begin: 0x10F0,
+                end: 0x1200
+            },
+            CrossCheckFinding::FunctionOutsideCode { begin: 0x2000 },
+            // Reported at the first slot pointing at it
+            CrossCheckFinding::MissingFromCfg {
+                function: 0x1040,
+                reference: 0x2308
+            },
+        ]
+    );
+
+    // Without enforced CFG there is no table to check against
+    let check = image(IMAGE_GUARD_CF_INSTRUMENTED, 0).cross_check().unwrap();
+    assert_eq!(check.cfg_targets(), None);
+    assert_eq!(check.findings().len(), 3);
+}
+
+#[test]
+fn cfg_table_entries_skip_their_metadata() {
+    assert_eq!(
+        image(CFG, 0).guard_cf_functions().unwrap(),
+        [0x1000, 0x10F0]
+    );
+    let flags = CFG | 1 << IMAGE_GUARD_CF_FUNCTION_TABLE_SIZE_SHIFT;
+    assert_eq!(
+        image(flags, 1).guard_cf_functions().unwrap(),
+        [0x1000, 0x10F0]
+    );
+}
+
+#[test]
+fn base_relocations_skip_padding() {
+    let relocations: Vec<_> = image(CFG, 0)
+        .base_relocations()
+        .unwrap()
+        .iter()
+        .map(|relocation| (relocation.rva(), relocation.kind(), relocation.size()))
+        .collect();
+    assert_eq!(
+        relocations,
+        [
+            (0x2300, IMAGE_REL_BASED_DIR64, Some(8)),
+            (0x2308, IMAGE_REL_BASED_DIR64, Some(8)),
+            (0x2310, IMAGE_REL_BASED_DIR64, Some(8)),
+        ]
+    );
+
+    let mut reloc = 0x1000u32.to_le_bytes().to_vec();
+    reloc.extend_from_slice(&12u32.to_le_bytes());
+    reloc.extend_from_slice(&((IMAGE_REL_BASED_HIGHLOW as u16) << 12 | 4).to_le_bytes());
+    reloc.extend_from_slice(&(1u16 << 12).to_le_bytes());
+    let pe = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0; 8])
+        .section(".reloc", 0x4200_0040, &reloc)
+        .directory(BASERELOC, 0x2000, 12)
+        .parse()
+        .unwrap();
+    let relocations = pe.base_relocations().unwrap();
+    assert_eq!(relocations.len(), 2);
+    assert_eq!(relocations[0].size(), Some(4));
+    assert_eq!(relocations[1].size(), None);
+
+    // A block claiming more than the directory holds
+    let pe = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0; 8])
+        .section(".reloc", 0x4200_0040, &reloc)
+        .directory(BASERELOC, 0x2000, 10)
+        .parse()
+        .unwrap();
+    assert!(pe.base_relocations().is_err());
+}
@@ -0,0 +1,118 @@
+use pexp::decompress::{decompress, decompress_lznt1, detect_compression, CompressionFormat};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+use std::io::ErrorKind;
+
+/// One compressed LZNT1 chunk spelling `abcdefgh` and then copying it with a
+/// back-reference
+const LZNT1: [u8; 14] = [
+    0x0B, 0xB0, 0x00, b'a', b'b', b'c', b'd', b'e', b'f', b'g', b'h', 0x01, 0x05, 0x70,
+];
+
+/// `hello` as a zlib stream holding one stored deflate block
+const ZLIB: [u8; 16] = [
+    0x78, 0x01, 0x01, 0x05, 0x00, 0xFA, 0xFF, b'h', b'e', b'l', b'l', b'o', 0x06, 0x2C, 0x02, 0x15,
+];
+
+fn push16(data: &mut Vec<u8>, value: u16) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push32(data: &mut Vec<u8>, value: u32) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Zip archive storing `contents` uncompressed as `name`, recording `size` as its
+/// uncompressed size
+fn zip(name: &str, contents: &[u8], size: u32) -> Vec<u8> {
+    let mut archive = Vec::new();
+    push32(&mut archive, 0x0403_4B50);
+    archive.extend_from_slice(&[0; 14]);
+    push32(&mut archive, contents.len() as u32);
+    push32(&mut archive, size);
+    push16(&mut archive, name.len() as u16);
+    push16(&mut archive, 0);
+    archive.extend_from_slice(name.as_bytes());
+    archive.extend_from_slice(contents);
+
+    let central_directory = archive.len();
+    push32(&mut archive, 0x0201_4B50);
+    archive.extend_from_slice(&[0; 16]);
+    push32(&mut archive, contents.len() as u32);
+    push32(&mut archive, size);
+    push16(&mut archive, name.len() as u16);
+    archive.extend_from_slice(&[0; 12]);
+    push32(&mut archive, 0);
+    archive.extend_from_slice(name.as_bytes());
+    let central_directory_size = archive.len() - central_directory;
+
+    push32(&mut archive, 0x0605_4B50);
+    archive.extend_from_slice(&[0, 0, 0, 0, 1, 0, 1, 0]);
+    push32(&mut archive, central_directory_size as u32);
+    push32(&mut archive, central_directory as u32);
+    push16(&mut archive, 0);
+    archive
+}
+
+#[test]
+fn lznt1_back_references_repeat_earlier_output() {
+    assert_eq!(detect_compression(&LZNT1), Some(CompressionFormat::Lznt1));
+    assert_eq!(decompress_lznt1(&LZNT1).unwrap(), b"abcdefghabcdefgh");
+
+    assert!(decompress_lznt1(&LZNT1[..LZNT1.len() - 1]).is_err());
+    assert_eq!(detect_compression(&LZNT1[..LZNT1.len() - 1]), None);
+    // A back-reference with nothing before it to copy
+    assert!(decompress_lznt1(&[0x02, 0xB0, 0x01, 0x00, 0x00]).is_err());
+}
+
+#[test]
+fn stored_zip_members_are_extracted_without_inflating() {
+    let archive = zip("a.txt", b"hello", 5);
+    assert_eq!(detect_compression(&archive), Some(CompressionFormat::Zip));
+    let payloads = decompress(&archive).unwrap();
+    assert_eq!(payloads.len(), 1);
+    assert_eq!(payloads[0].name(), Some("a.txt"));
+    assert_eq!(payloads[0].data(), b"hello");
+
+    assert!(decompress(&zip("a.txt", b"hello", 6)).is_err());
+    assert!(decompress(&archive[..archive.len() - 4]).is_err());
+}
+
+#[test]
+fn zlib_needs_the_compression_feature() {
+    assert_eq!(detect_compression(&ZLIB), Some(CompressionFormat::Zlib));
+    let result = decompress(&ZLIB);
+    if cfg!(feature = "compression") {
+        assert_eq!(result.unwrap()[0].data(), b"hello");
+    } else {
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::Unsupported);
+    }
+    assert!(decompress(b"plain text").is_err());
+}
+
+#[test]
+fn extracted_resources_carry_their_decompressed_payloads() {
+    let pe = PortExe::from_bytes(
+        PeFixture::pe32()
+            .section(".text", 0x6000_0020, &[0xC3])
+            .resource(10, 1, 0x409, &LZNT1)
+            .resource(10, 2, 0x409, b"plain text")
+            .build(),
+    )
+    .unwrap();
+    let resources = pe.resources().unwrap();
+    assert_eq!(resources.len(), 2);
+
+    let compressed = pe.extract_resource(&resources[0]).unwrap();
+    assert_eq!(compressed.raw(), LZNT1);
+    assert_eq!(compressed.format(), Some(CompressionFormat::Lznt1));
+    assert_eq!(
+        compressed.decompressed().unwrap()[0].data(),
+        b"abcdefghabcdefgh"
+    );
+
+    let plain = pe.extract_resource(&resources[1]).unwrap();
+    assert_eq!(plain.raw(), b"plain text");
+    assert_eq!(plain.format(), None);
+    assert!(plain.decompressed().is_none());
+}
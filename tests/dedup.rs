@@ -0,0 +1,71 @@
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+const RT_ICON: u16 = 3;
+const RT_RCDATA: u16 = 10;
+const SECURITY: usize = 4;
+
+/// Icon repeated for English and German, a unique blob and one that is mostly padding
+fn fixture() -> PeFixture {
+    let mut padded = vec![1; 0x10];
+    padded.resize(0x200, 0);
+    PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .resource(RT_ICON, 1, 0x409, &[7; 0x300])
+        .resource(RT_ICON, 1, 0x407, &[7; 0x300])
+        .resource(RT_RCDATA, 1, 0x409, &[9; 0x40])
+        .resource(RT_RCDATA, 2, 0x409, &padded)
+}
+
+#[test]
+fn duplicated_and_padded_resources_are_found() {
+    let pe = fixture().parse().unwrap();
+    let waste = pe.resource_waste().unwrap();
+    assert_eq!(waste.duplicates().len(), 1);
+    let icons = &waste.duplicates()[0];
+    assert_eq!((icons.size(), icons.copies()), (0x300, 2));
+    assert_eq!(icons.entries().len(), 2);
+    assert_eq!(icons.wasted(), 0x300);
+    assert_eq!(waste.duplicate_bytes(), 0x300);
+
+    assert_eq!(waste.padded().len(), 1);
+    assert_eq!(waste.padded()[0].entry().size(), 0x200);
+    assert_eq!(waste.padded()[0].padding(), 0x1F0);
+    assert_eq!(waste.padding_bytes(), 0x1F0);
+
+    let pe = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .resource(RT_RCDATA, 1, 0x409, &[0; 0x80])
+        .parse()
+        .unwrap();
+    // Short runs of zeros are ordinary alignment
+    assert!(pe.resource_waste().unwrap().is_empty());
+}
+
+#[test]
+fn duplicates_share_the_first_copy() {
+    let original = fixture().build();
+    let mut pe = PortExe::from_bytes(original.clone()).unwrap();
+    let before = pe.resources().unwrap();
+    assert_eq!(pe.dedup_resources().unwrap(), 0x300);
+
+    let after = pe.resources().unwrap();
+    assert_eq!(after.len(), before.len());
+    assert_eq!(after[0].data_rva(), after[1].data_rva());
+    for (old, new) in before.iter().zip(&after) {
+        let old_pe = PortExe::from_bytes(original.clone()).unwrap();
+        assert_eq!(old_pe.resource_data(old), pe.resource_data(new));
+    }
+    // `.rsrc` ends the file, so the packed section is cut short
+    assert!(pe.data().len() < original.len());
+    assert!(pe.resource_waste().unwrap().duplicates().is_empty());
+    assert_eq!(pe.dedup_resources().unwrap(), 0);
+}
+
+#[test]
+fn signed_files_keep_their_duplicates() {
+    let data = fixture().directory(SECURITY, 0x2000, 8).build();
+    let mut pe = PortExe::from_bytes(data.clone()).unwrap();
+    assert!(pe.dedup_resources().is_err());
+    assert_eq!(pe.to_bytes(), data);
+}
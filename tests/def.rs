@@ -0,0 +1,173 @@
+use pexp::def::ModuleDefinition;
+use pexp::export::{build_export_table, ExportSpec, ExportTarget};
+use pexp::testing::PeFixture;
+use std::io::ErrorKind;
+
+const DEF: &str = r#"; Widget library
+LIBRARY "widget lib.dll" BASE=0x10000000
+DESCRIPTION "Widgets"
+HEAPSIZE 0x1000
+EXPORTS
+    Run @1
+    Stop = _Stop@0 @2 PRIVATE
+    Table @3 DATA ; exported variable
+    Hidden @7 NONAME
+    Sleep=kernel32.Sleep
+STACKSIZE 0x100000
+    ignored outside EXPORTS
+"#;
+
+fn error(text: &str) -> String {
+    let error = ModuleDefinition::parse(text).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    error.to_string()
+}
+
+#[test]
+fn module_definitions_are_parsed() {
+    let definition = ModuleDefinition::parse(DEF).unwrap();
+    assert_eq!(definition.library(), Some("widget lib.dll"));
+    assert_eq!(definition.base(), Some(0x1000_0000));
+    let exports: Vec<_> = definition
+        .exports()
+        .iter()
+        .map(|export| {
+            (
+                export.name(),
+                export.internal_name(),
+                export.ordinal(),
+                export.noname(),
+                export.private(),
+                export.data(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        exports,
+        [
+            ("Run", None, Some(1), false, false, false),
+            ("Stop", Some("_Stop@0"), Some(2), false, true, false),
+            ("Table", None, Some(3), false, false, true),
+            ("Hidden", None, Some(7), true, false, false),
+            ("Sleep", Some("kernel32.Sleep"), None, false, false, false),
+        ]
+    );
+    assert_eq!(definition.exports()[1].forwarder(), None);
+    assert_eq!(definition.exports()[4].forwarder(), Some("kernel32.Sleep"));
+
+    // Written back out, the definition parses to itself
+    let written = definition.to_string();
+    assert!(written.starts_with("LIBRARY \"widget lib.dll\" BASE=0x10000000\nEXPORTS\n"));
+    assert!(
+        written.contains("    Stop=_Stop@0 @2 PRIVATE\n"),
+        "{}",
+        written
+    );
+    assert_eq!(ModuleDefinition::parse(&written).unwrap(), definition);
+}
+
+#[test]
+fn malformed_exports_are_reported_with_their_line() {
+    assert_eq!(
+        error("EXPORTS\n  Run @0"),
+        "line 2: export ordinals start at 1"
+    );
+    assert_eq!(error("EXPORTS\n\n  Run @x"), "line 3: bad export ordinal");
+    assert_eq!(
+        error("EXPORTS Hidden NONAME"),
+        "line 1: NONAME export without an ordinal"
+    );
+    assert_eq!(error("EXPORTS\n  =Run"), "line 2: bad export alias");
+    assert_eq!(
+        error("EXPORTS\n  Run @1 FAST"),
+        "line 2: unexpected token in export entry"
+    );
+    assert_eq!(error("NAME app.exe BASE=oops"), "line 1: bad BASE value");
+}
+
+#[test]
+fn export_tables_are_built_from_module_definitions() {
+    let definition = ModuleDefinition::parse(DEF).unwrap();
+    assert!(definition.export_specs(|_| None).is_err());
+    let specs = definition
+        .export_specs(|symbol| match symbol {
+            "Run" => Some(0x1000),
+            "_Stop@0" => Some(0x1010),
+            "Table" => Some(0x1020),
+            "Hidden" => Some(0x1030),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(specs[3].name(), None);
+    assert_eq!(specs[4].ordinal(), None);
+    assert_eq!(
+        specs[4].target(),
+        &ExportTarget::Forwarder("kernel32.Sleep".to_string())
+    );
+
+    let table = build_export_table("widget.dll", &specs, 0x2000).unwrap();
+    let pe = PeFixture::pe32()
+        .dll()
+        .section(".text", 0x6000_0020, &[0xC3; 0x40])
+        .section(".edata", 0x4000_0040, &table)
+        .directory(0, 0x2000, table.len() as u32)
+        .parse()
+        .unwrap();
+    let exports: Vec<_> = pe
+        .exports()
+        .unwrap()
+        .iter()
+        .map(|export| {
+            (
+                export.ordinal(),
+                export.name().map(ToString::to_string),
+                export.rva(),
+                export.forwarder().map(str::to_string),
+            )
+        })
+        .collect();
+    assert_eq!(
+        exports,
+        [
+            (1, Some("Run".to_string()), 0x1000, None),
+            (2, Some("Stop".to_string()), 0x1010, None),
+            (3, Some("Table".to_string()), 0x1020, None),
+            // The forwarder takes the first ordinal left free
+            (
+                4,
+                Some("Sleep".to_string()),
+                exports[3].2,
+                Some("kernel32.Sleep".to_string())
+            ),
+            (7, None, 0x1030, None),
+        ]
+    );
+
+    let written = pe.module_definition().unwrap();
+    assert_eq!(written.library(), Some("widget.dll"));
+    let written = written.to_string();
+    assert!(
+        written.contains("    Sleep=kernel32.Sleep @4\n"),
+        "{}",
+        written
+    );
+    assert!(written.contains("    Ordinal7 @7 NONAME\n"), "{}", written);
+}
+
+#[test]
+fn conflicting_export_specs_are_rejected() {
+    let rva = |name: &str, ordinal| {
+        ExportSpec::new(Some(name.to_string()), ordinal, ExportTarget::Rva(0x1000))
+    };
+    assert!(build_export_table("a.dll", &[rva("A", Some(2)), rva("B", Some(2))], 0x2000).is_err());
+    assert!(build_export_table("a.dll", &[rva("A", None), rva("A", None)], 0x2000).is_err());
+    assert!(build_export_table("a.dll", &[rva("A", Some(0))], 0x2000).is_err());
+    assert!(build_export_table(
+        "a.dll",
+        &[rva("A", Some(1)), rva("B", Some(0x10_0000))],
+        0x2000
+    )
+    .is_err());
+    assert!(build_export_table("a.dll", &[rva("A", None)], 0xFFFF_FFF0).is_err());
+    assert!(build_export_table("a.dll", &[rva("A", None)], 0x2000).is_ok());
+}
@@ -0,0 +1,113 @@
+use pexp::import::ImportWarning;
+use pexp::port_exe::PortExe;
+use pexp::search::{ImportQuery, MatchMode};
+use pexp::testing::PeFixture;
+
+/// Offset of the delay-load import directory in a PE32 image built by `PeFixture`
+const DELAY_IMPORT_DIRECTORY: usize = 0x80 + 4 + 20 + 96 + 13 * 8;
+/// ImageBase of a PE32 image built by `PeFixture`
+const IMAGE_BASE: u32 = 0x40_0000;
+
+/// PE32 image delay-loading `MessageBoxA` and ordinal 5 from USER32.dll through a
+/// descriptor at RVA 0x1000; with `rva_based` clear the descriptor and its import name
+/// table hold virtual addresses as Visual C++ 6 wrote them
+fn image(rva_based: bool) -> PortExe {
+    let base = if rva_based { 0 } else { IMAGE_BASE };
+    let mut data = vec![0u8; 0xA0];
+    for (offset, value) in [
+        (0x00, rva_based as u32),
+        (0x04, base + 0x1040),
+        (0x08, base + 0x1090),
+        (0x0C, base + 0x1080),
+        (0x10, base + 0x1050),
+        (0x1C, 0x1234_5678),
+        (0x50, base + 0x1060),
+        (0x54, 0x8000_0005),
+        (0x80, IMAGE_BASE + 0x2000),
+        (0x84, IMAGE_BASE + 0x2010),
+    ] {
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    data[0x40..0x4A].copy_from_slice(b"USER32.dll");
+    data[0x60..0x62].copy_from_slice(&7u16.to_le_bytes());
+    data[0x62..0x6D].copy_from_slice(b"MessageBoxA");
+
+    let mut image = PeFixture::pe32()
+        .dll_characteristics(0)
+        .section(".didat", 0xC000_0040, &data)
+        .build();
+    image[DELAY_IMPORT_DIRECTORY..DELAY_IMPORT_DIRECTORY + 8]
+        .copy_from_slice(&[0, 0x10, 0, 0, 0x40, 0, 0, 0]);
+    PortExe::from_bytes(image).unwrap()
+}
+
+#[test]
+fn delay_load_descriptors_are_parsed() {
+    for rva_based in [true, false] {
+        let pe = image(rva_based);
+        let table = pe.delay_import_table().unwrap();
+        assert!(table.warnings().is_empty());
+        let descriptors = table.descriptors();
+        assert_eq!(descriptors.len(), 1);
+
+        let descriptor = &descriptors[0];
+        assert!(descriptor.is_delay_loaded());
+        assert_eq!(descriptor.dll_name(), "USER32.dll");
+        assert_eq!(descriptor.name_rva(), 0x1040);
+        assert_eq!(descriptor.first_thunk(), 0x1080);
+        assert_eq!(descriptor.original_first_thunk(), 0x1050);
+        assert_eq!(descriptor.time_date_stamp(), 0x1234_5678);
+        let delay_load = descriptor.delay_load().unwrap();
+        assert_eq!(delay_load.is_rva_based(), rva_based);
+        assert_eq!(delay_load.module_handle_rva(), 0x1090);
+        assert_eq!(delay_load.bound_iat_rva(), 0);
+        assert_eq!(delay_load.unload_iat_rva(), 0);
+
+        let imports = descriptor.imports();
+        assert_eq!(imports.len(), 2);
+        assert_eq!(imports[0].name().unwrap(), "MessageBoxA");
+        assert_eq!(imports[0].hint(), 7);
+        assert_eq!(imports[0].hint_name_rva(), Some(0x1060));
+        assert_eq!(imports[0].iat_rva(), 0x1080);
+        assert_eq!(imports[1].ordinal(), Some(5));
+        assert_eq!(imports[1].iat_rva(), 0x1084);
+    }
+}
+
+#[test]
+fn delay_imports_follow_the_import_directory() {
+    let pe = image(true);
+    assert!(pe.imports().unwrap().is_empty());
+    let all = pe.all_imports().unwrap();
+    assert_eq!(all.len(), 1);
+    assert!(all[0].is_delay_loaded());
+    assert_eq!(all[0].imports(), pe.delay_imports().unwrap()[0].imports());
+}
+
+#[test]
+fn find_import_searches_delay_loaded_imports() {
+    let pe = image(true);
+    for query in ["user32!MessageBoxA", "USER32.dll!#5"] {
+        let query = ImportQuery::parse(query, MatchMode::Exact).unwrap();
+        let found = pe.find_imports(&query).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].dll(), "USER32.dll");
+    }
+    let query = ImportQuery::parse("!MessageBoxW", MatchMode::Exact).unwrap();
+    assert!(pe.find_imports(&query).unwrap().is_empty());
+}
+
+#[test]
+fn delay_load_names_outside_the_image_are_reported() {
+    let mut data = image(true).data().to_vec();
+    // DllNameRVA of the descriptor at file offset 0x200
+    data[0x204..0x208].copy_from_slice(&0x9000u32.to_le_bytes());
+    let pe = PortExe::from_bytes(data).unwrap();
+    let table = pe.delay_import_table().unwrap();
+    assert_eq!(
+        table.warnings(),
+        [ImportWarning::NameOutsideImage { rva: 0x9000 }]
+    );
+    assert_eq!(table.descriptors()[0].dll_name(), "");
+    assert_eq!(table.descriptors()[0].imports().len(), 2);
+}
@@ -0,0 +1,143 @@
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+/// Image with a section for each `(name, data)`, their names stored in the string table
+/// behind an empty symbol table the way GNU ld writes them
+fn image(sections: &[(&str, &[u8])]) -> PortExe {
+    let mut strings = Vec::new();
+    let mut fixture = PeFixture::pe32().section(".text", 0x6000_0020, &[0xC3]);
+    for (name, data) in sections {
+        fixture = fixture.section(&format!("/{}", 4 + strings.len()), 0x4200_0040, data);
+        strings.extend_from_slice(name.as_bytes());
+        strings.push(0);
+    }
+    let mut data = fixture.build();
+    let table = data.len() as u32;
+    data[0x8C..0x90].copy_from_slice(&table.to_le_bytes());
+    data.extend_from_slice(&(4 + strings.len() as u32).to_le_bytes());
+    data.extend_from_slice(&strings);
+    PortExe::from_bytes(data).unwrap()
+}
+
+/// `.debug_info` with a DWARF 4 unit, a DWARF 5 unit, a 64-bit DWARF 2 unit, each with
+/// nothing after its header, and alignment padding
+fn debug_info() -> Vec<u8> {
+    let mut info = Vec::new();
+    info.extend_from_slice(&7u32.to_le_bytes());
+    info.extend_from_slice(&4u16.to_le_bytes());
+    info.extend_from_slice(&0x10u32.to_le_bytes());
+    info.push(4);
+
+    info.extend_from_slice(&8u32.to_le_bytes());
+    info.extend_from_slice(&5u16.to_le_bytes());
+    info.extend_from_slice(&[0x01, 8]);
+    info.extend_from_slice(&0x20u32.to_le_bytes());
+
+    info.extend_from_slice(&u32::MAX.to_le_bytes());
+    info.extend_from_slice(&11u64.to_le_bytes());
+    info.extend_from_slice(&2u16.to_le_bytes());
+    info.extend_from_slice(&0x30u64.to_le_bytes());
+    info.push(8);
+    info.resize(0x40, 0);
+    info
+}
+
+/// zlib stream storing `data` in a single uncompressed deflate block
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01, 0x01];
+    stream.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    stream.extend_from_slice(&(!(data.len() as u16)).to_le_bytes());
+    stream.extend_from_slice(data);
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    stream.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    stream
+}
+
+#[test]
+fn unit_headers_are_read_in_every_format() {
+    let info = debug_info();
+    let pe = image(&[(".debug_abbrev", &[0; 4]), (".debug_info", &info)]);
+    let summary = pe.dwarf_summary().unwrap();
+    let names: Vec<_> = summary
+        .sections()
+        .iter()
+        .map(|section| (section.name().to_string(), section.size()))
+        .collect();
+    assert_eq!(
+        names,
+        [
+            (".debug_abbrev".to_string(), 4),
+            (".debug_info".to_string(), 0x40)
+        ]
+    );
+    assert!(!summary.is_truncated());
+
+    let units: Vec<_> = summary
+        .units()
+        .iter()
+        .map(|unit| {
+            (
+                unit.offset(),
+                unit.length(),
+                unit.is_dwarf64(),
+                unit.version(),
+                unit.unit_type(),
+                unit.address_size(),
+                unit.abbrev_offset(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        units,
+        [
+            (0, 7, false, 4, None, 4, 0x10),
+            (11, 8, false, 5, Some(1), 8, 0x20),
+            (23, 11, true, 2, None, 8, 0x30),
+        ]
+    );
+    assert_eq!(
+        summary.versions().into_iter().collect::<Vec<_>>(),
+        [2, 4, 5]
+    );
+}
+
+#[test]
+fn walks_stopping_early_are_flagged() {
+    let mut info = debug_info();
+    // An unknown version in the second unit
+    info[15] = 6;
+    let summary = image(&[(".debug_info", &info)]).dwarf_summary().unwrap();
+    assert_eq!(summary.units().len(), 1);
+    assert!(summary.is_truncated());
+
+    let summary = image(&[(".debug_info", &debug_info()[..20])])
+        .dwarf_summary()
+        .unwrap();
+    assert_eq!(summary.units().len(), 1);
+    assert!(summary.is_truncated());
+
+    let summary = image(&[(".debug_line", &[1; 8])]).dwarf_summary().unwrap();
+    assert!(summary.units().is_empty() && !summary.is_truncated());
+    assert!(image(&[(".rdata", &[1; 8])]).dwarf_summary().is_none());
+}
+
+#[test]
+fn compressed_debug_info_needs_the_compression_feature() {
+    let info = debug_info();
+    let mut zdebug = b"ZLIB".to_vec();
+    zdebug.extend_from_slice(&(info.len() as u64).to_be_bytes());
+    zdebug.extend_from_slice(&zlib_stored(&info));
+    let summary = image(&[(".zdebug_info", &zdebug)]).dwarf_summary().unwrap();
+    assert!(summary.sections()[0].is_compressed());
+    if cfg!(feature = "compression") {
+        assert_eq!(summary.units().len(), 3);
+        assert!(!summary.is_truncated());
+    } else {
+        assert!(summary.units().is_empty());
+        assert!(summary.is_truncated());
+    }
+}
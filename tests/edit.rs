@@ -0,0 +1,208 @@
+use pexp::edit::HardenPolicy;
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+/// Index of the certificate table in the data directories
+const SECURITY: usize = 4;
+
+/// Image with writable code, a writable `.rdata` and executable data
+fn sloppy() -> PeFixture {
+    PeFixture::pe32()
+        .section(".text", 0xE000_0020, &[0xC3; 0x10])
+        .section(".rdata", 0xC000_0040, &[1; 0x10])
+        .section(".data", 0xE000_0040, &[2; 0x10])
+}
+
+fn characteristics(pe: &PortExe) -> Vec<u32> {
+    pe.section_headers()
+        .iter()
+        .map(|section| u32::from_le_bytes(section.characteristics().raw_bytes()))
+        .collect()
+}
+
+#[test]
+fn hardening_clears_unneeded_write_and_execute_bits() {
+    let mut pe = sloppy().parse().unwrap();
+    let changes = pe.harden_sections(&HardenPolicy::default()).unwrap();
+    let changed: Vec<(usize, u32, u32)> = changes
+        .iter()
+        .map(|change| (change.index(), change.before(), change.after()))
+        .collect();
+    assert_eq!(
+        changed,
+        [
+            (0, 0xE000_0020, 0x6000_0020),
+            (1, 0xC000_0040, 0x4000_0040),
+            (2, 0xE000_0040, 0xC000_0040),
+        ]
+    );
+    let reparsed = PortExe::from_bytes(pe.to_bytes()).unwrap();
+    assert_eq!(
+        characteristics(&reparsed),
+        [0x6000_0020, 0x4000_0040, 0xC000_0040]
+    );
+    assert!(pe
+        .harden_sections(&HardenPolicy::default())
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn signed_images_are_not_hardened() {
+    let data = sloppy().directory(SECURITY, 0x1000, 0x100).build();
+    let mut pe = PortExe::from_bytes(data.clone()).unwrap();
+    assert!(pe.harden_sections(&HardenPolicy::default()).is_err());
+    assert_eq!(pe.to_bytes(), data);
+}
+
+/// File offsets of `CheckSum` and `DllCharacteristics`, the same for PE32 and PE32+
+const CHECKSUM: usize = 0x98 + 64;
+const DLL_CHARACTERISTICS: usize = 0x98 + 70;
+/// Index of the base relocation table in the data directories
+const BASERELOC: usize = 5;
+
+fn dll_characteristics(data: &[u8]) -> u16 {
+    u16::from_le_bytes([data[DLL_CHARACTERISTICS], data[DLL_CHARACTERISTICS + 1]])
+}
+
+/// Image with a base relocation directory and no mitigations
+fn relocatable(fixture: PeFixture) -> Vec<u8> {
+    fixture
+        .dll_characteristics(0)
+        .section(".text", 0x6000_0020, &[0xC3; 0x10])
+        .section(".reloc", 0x4200_0040, &[0, 0x10, 0, 0, 8, 0, 0, 0])
+        .directory(BASERELOC, 0x2000, 8)
+        .build()
+}
+
+#[test]
+fn aslr_is_enabled_on_relocatable_images() {
+    let mut data = relocatable(PeFixture::pe32());
+    data[CHECKSUM..CHECKSUM + 4].copy_from_slice(&1u32.to_le_bytes());
+    let mut pe = PortExe::from_bytes(data).unwrap();
+    assert!(pe.enable_aslr().unwrap());
+    let edited = pe.to_bytes();
+    assert_eq!(dll_characteristics(&edited), 0x0040);
+    // A checksum that was set is kept valid
+    let reparsed = PortExe::from_bytes(edited).unwrap();
+    let checksum = reparsed.optional_header().unwrap().checksum();
+    assert_eq!(Some(checksum), reparsed.compute_checksum());
+    assert!(!pe.enable_aslr().unwrap());
+
+    // PE32+ images get high entropy ASLR too, and a zero checksum stays zero
+    let mut pe = PortExe::from_bytes(relocatable(PeFixture::pe64())).unwrap();
+    assert!(pe.enable_aslr().unwrap());
+    let edited = pe.to_bytes();
+    assert_eq!(dll_characteristics(&edited), 0x0060);
+    assert_eq!(edited[CHECKSUM..CHECKSUM + 4], [0; 4]);
+}
+
+#[test]
+fn aslr_needs_relocations() {
+    let stripped = relocatable(PeFixture::pe32().characteristics(0x0103));
+    let mut pe = PortExe::from_bytes(stripped.clone()).unwrap();
+    let error = pe.enable_aslr().unwrap_err();
+    assert!(
+        error.to_string().contains("IMAGE_FILE_RELOCS_STRIPPED"),
+        "{}",
+        error
+    );
+    assert_eq!(pe.to_bytes(), stripped);
+
+    let data = PeFixture::pe32()
+        .dll_characteristics(0)
+        .section(".text", 0x6000_0020, &[0xC3])
+        .build();
+    let mut pe = PortExe::from_bytes(data.clone()).unwrap();
+    assert!(pe.enable_aslr().is_err());
+    assert_eq!(pe.to_bytes(), data);
+}
+
+/// Image with two sections and an overlay, laid out with the fixture's 0x200
+/// `FileAlignment`
+fn aligned() -> Vec<u8> {
+    let mut data = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3; 0x10])
+        .section(".data", 0xC000_0040, &[1; 0x10])
+        .build();
+    data.extend_from_slice(b"OVERLAY");
+    data
+}
+
+fn raw_layout(pe: &PortExe) -> Vec<(u32, u32)> {
+    pe.section_headers()
+        .iter()
+        .map(|section| {
+            (
+                section.pointer_to_raw_data().into_value(),
+                section.size_of_raw_data().into_value(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn sections_are_moved_to_a_new_file_alignment() {
+    let original = aligned();
+    let mut pe = PortExe::from_bytes(original.clone()).unwrap();
+    assert_eq!(raw_layout(&pe), [(0x200, 0x200), (0x400, 0x200)]);
+    assert!(pe.set_file_alignment(0x1000).unwrap());
+
+    let header = pe.optional_header().unwrap();
+    assert_eq!(header.file_alignment(), 0x1000);
+    assert_eq!(header.size_of_headers(), 0x1000);
+    assert_eq!(raw_layout(&pe), [(0x1000, 0x1000), (0x2000, 0x1000)]);
+    assert_eq!(
+        pe.section_data(&pe.section_headers()[0])[..0x10],
+        [0xC3; 0x10]
+    );
+    assert_eq!(pe.section_data(&pe.section_headers()[1])[..0x10], [1; 0x10]);
+    assert_eq!(pe.overlay_offset(), 0x3000);
+    assert_eq!(pe.overlay(), b"OVERLAY");
+    assert!(!pe.set_file_alignment(0x1000).unwrap());
+
+    // The padding added on the way up is dropped on the way back
+    assert!(pe.set_file_alignment(0x200).unwrap());
+    assert_eq!(pe.to_bytes(), original);
+}
+
+#[test]
+fn file_alignments_outside_the_format_are_refused() {
+    let mut pe = PortExe::from_bytes(aligned()).unwrap();
+    for alignment in [0x100, 0x300, 0x2000, 0x2_0000] {
+        assert!(
+            pe.set_file_alignment(alignment).is_err(),
+            "{:#x}",
+            alignment
+        );
+    }
+
+    let mut pe = PortExe::from_bytes(
+        PeFixture::pe32()
+            .section(".text", 0x6000_0020, &[0xC3; 0x10])
+            .directory(SECURITY, 0x400, 8)
+            .build(),
+    )
+    .unwrap();
+    assert!(pe.set_file_alignment(0x400).is_err());
+    assert!(pe.pad_to(0x1000).is_err());
+}
+
+#[test]
+fn files_are_padded_with_a_zero_overlay() {
+    let mut data = aligned();
+    data[CHECKSUM] = 1;
+    let mut pe = PortExe::from_bytes(data.clone()).unwrap();
+    assert!(pe.pad_to(data.len() - 1).is_err());
+    pe.pad_to(data.len()).unwrap();
+    assert_eq!(pe.data(), &data[..]);
+
+    pe.pad_to(0x1000).unwrap();
+    assert_eq!(pe.data().len(), 0x1000);
+    assert!(pe.overlay().starts_with(b"OVERLAY\0"));
+    assert!(pe.overlay()[7..].iter().all(|&byte| byte == 0));
+    // A nonzero checksum is kept up to date
+    let mut checksum = [0; 4];
+    checksum.copy_from_slice(&pe.data()[CHECKSUM..CHECKSUM + 4]);
+    assert_eq!(pe.compute_checksum(), Some(u32::from_le_bytes(checksum)));
+}
@@ -0,0 +1,231 @@
+mod common;
+
+use common::{put32, DATA_DIRECTORIES};
+use pexp::edit::WxPolicy;
+use pexp::editor::PeEditor;
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+use pexp::validation::ValidationMode;
+use pexp::WindowsSubsystem;
+
+/// File offset of the `Subsystem` field of a PE32 image laid out by [`PeFixture`]
+const SUBSYSTEM: usize = 0x98 + 68;
+/// File offset of the only section, at RVA 0x1000
+const SECTION: usize = 0x200;
+/// Offset of the import lookup table within the section
+const LOOKUP_TABLE: usize = 64;
+
+/// Executable importing `Sleep` from `kernel32.dll` by name
+fn importer() -> PortExe {
+    let rva = 0x1000;
+    let mut section = vec![0u8; 0x200];
+    put32(&mut section, 0, rva + LOOKUP_TABLE as u32);
+    put32(&mut section, 12, rva + 0x1F0);
+    put32(&mut section, 16, rva + 96);
+    put32(&mut section, LOOKUP_TABLE, rva + 128);
+    put32(&mut section, 96, rva + 128);
+    section[130..135].copy_from_slice(b"Sleep");
+    section[0x1F0..0x1FC].copy_from_slice(b"kernel32.dll");
+    let mut data = PeFixture::pe32()
+        .dll_characteristics(0)
+        .section(".idata", 0x4000_0040, &section)
+        .build();
+    put32(&mut data, DATA_DIRECTORIES + 8, rva);
+    put32(&mut data, DATA_DIRECTORIES + 12, 40);
+    PortExe::from_bytes(data).unwrap()
+}
+
+fn messages(editor: &PeEditor) -> Vec<String> {
+    editor
+        .findings()
+        .iter()
+        .map(|finding| finding.message().to_string())
+        .collect()
+}
+
+fn revalidated(editor: &PeEditor) -> Vec<String> {
+    PortExe::from_bytes(editor.pe().data().to_vec())
+        .unwrap()
+        .validate(ValidationMode::Strict)
+        .iter()
+        .map(|finding| finding.message().to_string())
+        .collect()
+}
+
+#[test]
+fn writes_refresh_only_the_structures_they_touch() {
+    let mut editor = PeEditor::new(importer(), ValidationMode::Strict);
+    assert!(messages(&editor).is_empty());
+
+    let refresh = editor.write(SECTION + 0x180, b"data").unwrap();
+    assert!(!refresh.reparsed_headers());
+    assert!(!refresh.revalidated_imports());
+
+    let ordinal = 0x8000_0010u32.to_le_bytes();
+    let refresh = editor.write(SECTION + LOOKUP_TABLE, &ordinal).unwrap();
+    assert!(!refresh.reparsed_headers());
+    assert!(refresh.revalidated_imports());
+    assert_eq!(
+        messages(&editor),
+        ["all 1 imports from system DLL kernel32.dll are by ordinal"]
+    );
+
+    let refresh = editor.write(SUBSYSTEM, &2u16.to_le_bytes()).unwrap();
+    assert!(refresh.reparsed_headers());
+    assert!(refresh.revalidated_imports());
+    assert_eq!(
+        editor.pe().optional_header().unwrap().subsystem(),
+        WindowsSubsystem::WindowsGraphicalUI
+    );
+    assert_eq!(messages(&editor), revalidated(&editor));
+
+    editor.write(SECTION + 0x184, b"more").unwrap();
+    assert_eq!(
+        editor.dirty_regions(),
+        [
+            SUBSYSTEM..SUBSYSTEM + 2,
+            SECTION + LOOKUP_TABLE..SECTION + LOOKUP_TABLE + 4,
+            SECTION + 0x180..SECTION + 0x188,
+        ]
+    );
+}
+
+#[test]
+fn writes_breaking_the_headers_are_undone() {
+    let mut editor = PeEditor::new(importer(), ValidationMode::Strict);
+    let before = editor.pe().data().to_vec();
+    assert!(editor.write(0x80, b"NE\0\0").is_err());
+    assert_eq!(editor.pe().data(), &before[..]);
+    assert!(editor.dirty_regions().is_empty());
+
+    let len = before.len();
+    assert!(editor.write(len - 2, b"abc").is_err());
+}
+
+#[test]
+fn operations_can_be_undone_and_redone() {
+    let original = importer();
+    let bytes = original.data().to_vec();
+    let mut editor = PeEditor::new(original, ValidationMode::Strict);
+    editor.write(SECTION + 0x180, b"data").unwrap();
+    let (_, refresh) = editor
+        .apply("pad", |pe| pe.pad_to(bytes.len() + 0x100))
+        .unwrap();
+    // Only the overlay is new, nothing else moves
+    assert_eq!(refresh.regions().len(), 1);
+    assert_eq!(refresh.regions()[0], bytes.len()..bytes.len());
+    let names: Vec<&str> = editor.journal().iter().map(|op| op.name()).collect();
+    assert_eq!(names, ["write", "pad"]);
+    let padded = editor.pe().data().to_vec();
+    assert_eq!(padded.len(), bytes.len() + 0x100);
+
+    editor.undo().unwrap().unwrap();
+    editor.undo().unwrap().unwrap();
+    assert_eq!(editor.pe().data(), &bytes[..]);
+    assert!(editor.undo().unwrap().is_none());
+    assert_eq!(editor.undone().len(), 2);
+
+    editor.redo().unwrap().unwrap();
+    editor.redo().unwrap().unwrap();
+    assert_eq!(editor.pe().data(), &padded[..]);
+    assert!(editor.redo().unwrap().is_none());
+
+    editor.undo().unwrap().unwrap();
+    editor.write(SUBSYSTEM, &2u16.to_le_bytes()).unwrap();
+    assert!(editor.undone().is_empty());
+}
+
+#[test]
+fn dry_runs_report_ranges_without_editing() {
+    let original = importer();
+    let bytes = original.data().to_vec();
+    let mut editor = PeEditor::new(original, ValidationMode::Strict);
+    editor.set_dry_run(true);
+
+    let refresh = editor.write(SUBSYSTEM, &2u16.to_le_bytes()).unwrap();
+    assert!(!refresh.applied());
+    assert!(refresh.reparsed_headers());
+    assert_eq!(refresh.regions().len(), 1);
+    assert_eq!(refresh.regions()[0], SUBSYSTEM..SUBSYSTEM + 2);
+
+    let (changed, refresh) = editor
+        .apply("characteristics", |pe| {
+            pe.set_section_characteristics(0, 0xC000_0040, WxPolicy::Refuse)
+        })
+        .unwrap();
+    assert_eq!(changed.after(), 0xC000_0040);
+    // Only the high byte of the characteristics differs
+    assert_eq!(refresh.regions().len(), 1);
+    assert_eq!(refresh.regions()[0], 0x178 + 39..0x178 + 40);
+
+    assert_eq!(editor.pe().data(), &bytes[..]);
+    assert!(editor.journal().is_empty());
+    assert!(editor.dirty_regions().is_empty());
+}
+
+#[test]
+fn transactions_commit_as_one_operation() {
+    let original = importer();
+    let bytes = original.data().to_vec();
+    let mut editor = PeEditor::new(original, ValidationMode::Strict);
+    let checksum = 0x98 + 64;
+    editor
+        .transaction(|t| {
+            t.write(SECTION + 0x180, b"data")?;
+            t.write(checksum, &1u32.to_le_bytes())?;
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(editor.journal().len(), 1);
+    assert_eq!(editor.journal()[0].name(), "transaction");
+    // The checksum was recomputed before the gate
+    let header = editor.pe().optional_header().unwrap();
+    assert_ne!(header.checksum(), 1);
+    assert_eq!(Some(header.checksum()), editor.pe().compute_checksum());
+
+    editor.undo().unwrap().unwrap();
+    assert_eq!(editor.pe().data(), &bytes[..]);
+}
+
+#[test]
+fn transactions_failing_the_gate_are_rolled_back() {
+    let original = importer();
+    let bytes = original.data().to_vec();
+    let mut editor = PeEditor::new(original, ValidationMode::Strict);
+    editor.write(SECTION + 0x180, b"data").unwrap();
+    let dirty = editor.dirty_regions().to_vec();
+    let edited = editor.pe().data().to_vec();
+
+    let error = editor
+        .transaction(|t| {
+            t.write(SECTION + 0x184, b"more")?;
+            t.write(DATA_DIRECTORIES + 12, &0x2000u32.to_le_bytes())?;
+            assert_eq!(t.pe().data()[SECTION + 0x184], b'm');
+            Ok(())
+        })
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "transaction rolled back: data directory 1 at 0x1000+0x2000 is not within the \
+headers or a section"
+    );
+    assert_eq!(editor.pe().data(), &edited[..]);
+    assert_eq!(editor.dirty_regions(), &dirty[..]);
+    assert_eq!(editor.journal().len(), 1);
+
+    let error = editor
+        .transaction(|t| {
+            t.write(SECTION + 0x184, b"more")?;
+            t.write(bytes.len(), b"past the end")
+        })
+        .unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(editor.pe().data(), &edited[..]);
+
+    editor.set_dry_run(true);
+    editor
+        .transaction(|t| t.write(SECTION + 0x184, b"more").map(|_| ()))
+        .unwrap();
+    assert_eq!(editor.pe().data(), &edited[..]);
+    assert_eq!(editor.journal().len(), 1);
+}
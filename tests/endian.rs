@@ -0,0 +1,77 @@
+mod common;
+
+use common::fixtures;
+use pexp::port_exe::PortExe;
+use pexp::Machine;
+
+/// Little-endian value of `size` bytes of `data` at `offset`, decoded by hand so the
+/// expectation does not share code with the parser.
+fn le(data: &[u8], offset: usize, size: usize) -> u64 {
+    data[offset..offset + size]
+        .iter()
+        .rev()
+        .fold(0, |value, &byte| value << 8 | byte as u64)
+}
+
+/// Header fields decode to the values the fixture bytes spell out, on any host.
+#[test]
+fn header_fields_decode_from_fixture_bytes() {
+    let data = fixtures::PE32_EXE;
+    let pe = PortExe::from_bytes(data.to_vec()).unwrap();
+    let header = pe.file_header();
+    assert_eq!(header.machine().into_value(), Machine::Intel386);
+    assert_eq!(header.machine().raw_bytes(), [0x4C, 0x01]);
+    assert_eq!(header.time_date_stamp().into_value(), 0x5F00_0000);
+    assert_eq!(
+        header.time_date_stamp().into_value() as u64,
+        le(data, header.time_date_stamp().offset() as usize, 4)
+    );
+
+    let optional = pe.optional_header().unwrap();
+    assert_eq!(optional.image_base(), 0x40_0000);
+    assert_eq!(optional.address_of_entry_point(), 0x1000);
+    assert_eq!(optional.size_of_image(), 0x2000);
+
+    let section = &pe.section_headers()[0];
+    assert_eq!(section.name().into_value(), ".text");
+    assert_eq!(section.virtual_address().into_value(), 0x1000);
+    assert_eq!(section.size_of_raw_data().into_value(), 0x200);
+    assert_eq!(
+        section.characteristics().raw_bytes(),
+        [0x20, 0x00, 0x00, 0x60]
+    );
+}
+
+/// 64-bit fields combine both halves in the right order.
+#[test]
+fn wide_fields_decode_from_fixture_bytes() {
+    let pe = PortExe::from_bytes(fixtures::PE32PLUS_EXE.to_vec()).unwrap();
+    let optional = pe.optional_header().unwrap();
+    assert_eq!(optional.image_base(), 0x1_4000_0000);
+}
+
+/// Raw bytes of every field are the bytes stored at its offset, not a re-encoding of
+/// the value in host byte order.
+#[test]
+fn raw_bytes_are_the_stored_bytes() {
+    for data in [
+        fixtures::PE32_EXE,
+        fixtures::PE32PLUS_EXE,
+        fixtures::PE32_DLL,
+        fixtures::PE32PLUS_DRIVER,
+        fixtures::AMD64_OBJECT,
+    ] {
+        let pe = PortExe::from_bytes(data.to_vec()).unwrap();
+        let header = pe.file_header();
+        let number_of_sections = header.number_of_sections();
+        let offset = number_of_sections.offset() as usize;
+        assert_eq!(number_of_sections.raw_bytes(), data[offset..offset + 2]);
+        assert_eq!(number_of_sections.into_value() as u64, le(data, offset, 2));
+        for section in pe.section_headers() {
+            let field = section.pointer_to_raw_data();
+            let offset = field.offset() as usize;
+            assert_eq!(field.raw_bytes(), data[offset..offset + 4]);
+            assert_eq!(field.into_value() as u64, le(data, offset, 4));
+        }
+    }
+}
@@ -0,0 +1,95 @@
+#![cfg(feature = "disassembler")]
+
+use pexp::entry::EntryHopKind;
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+use std::io;
+
+/// Image for `machine` whose `.text`, mapped at RVA 0x1000 where the entry point is,
+/// holds `code`, followed by a `.data` section at 0x2000.
+fn image(machine: u16, code: &[u8]) -> PortExe {
+    PortExe::from_bytes(
+        PeFixture::pe32()
+            .machine(machine)
+            .dll_characteristics(0)
+            .section(".text", 0x6000_0020, code)
+            .section(".data", 0xC000_0040, &[0xC3; 0x10])
+            .build(),
+    )
+    .unwrap()
+}
+
+/// Places the `opcode` of a relative jump or call at `rva` of `code`, which starts at
+/// RVA 0x1000, branching to `target`.
+fn branch(code: &mut Vec<u8>, rva: u32, opcode: u8, target: u32) {
+    let offset = (rva - 0x1000) as usize;
+    if code.len() < offset + 5 {
+        code.resize(offset + 5, 0xCC);
+    }
+    code[offset] = opcode;
+    let displacement = target.wrapping_sub(rva + 5);
+    code[offset + 1..offset + 5].copy_from_slice(&displacement.to_le_bytes());
+}
+
+fn place(code: &mut Vec<u8>, rva: u32, bytes: &[u8]) {
+    let offset = (rva - 0x1000) as usize;
+    if code.len() < offset + bytes.len() {
+        code.resize(offset + bytes.len(), 0xCC);
+    }
+    code[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+#[test]
+fn startup_wrappers_are_followed_to_the_real_start() {
+    let mut code = Vec::new();
+    // mainCRTStartup: jmp to a stub calling __security_init_cookie and jumping on
+    branch(&mut code, 0x1000, 0xE9, 0x1010);
+    branch(&mut code, 0x1010, 0xE8, 0x1020);
+    branch(&mut code, 0x1015, 0xE9, 0x1030);
+    place(&mut code, 0x1020, &[0xC3]);
+    // A call wrapper, then a function that does more than one call
+    branch(&mut code, 0x1030, 0xE8, 0x1040);
+    place(&mut code, 0x1035, &[0xC3]);
+    place(&mut code, 0x1040, &[0x55, 0x8B, 0xEC]);
+    branch(&mut code, 0x1043, 0xE8, 0x1020);
+    branch(&mut code, 0x1048, 0xE8, 0x1020);
+    place(&mut code, 0x104D, &[0x5D, 0xC3]);
+
+    let trace = image(0x14C, &code).entry_trace().unwrap();
+    let hops: Vec<(u32, u32, EntryHopKind)> = trace
+        .hops()
+        .iter()
+        .map(|hop| (hop.from(), hop.to(), hop.kind()))
+        .collect();
+    assert_eq!(
+        hops,
+        [
+            (0x1000, 0x1010, EntryHopKind::Jump),
+            (0x1010, 0x1030, EntryHopKind::InitThenJump { init: 0x1020 }),
+            (0x1030, 0x1040, EntryHopKind::CallWrapper),
+        ]
+    );
+    assert_eq!(trace.start(), 0x1040);
+}
+
+#[test]
+fn unwrapping_stops_outside_code_and_on_loops() {
+    let mut code = Vec::new();
+    branch(&mut code, 0x1000, 0xE9, 0x2000);
+    let trace = image(0x14C, &code).entry_trace().unwrap();
+    assert!(trace.hops().is_empty());
+    assert_eq!(trace.start(), 0x1000);
+
+    let mut code = Vec::new();
+    branch(&mut code, 0x1000, 0xE9, 0x1010);
+    branch(&mut code, 0x1010, 0xE9, 0x1000);
+    let trace = image(0x14C, &code).entry_trace().unwrap();
+    assert_eq!(trace.hops().len(), 1);
+    assert_eq!(trace.start(), 0x1010);
+}
+
+#[test]
+fn other_machines_are_unsupported() {
+    let error = image(0x1C4, &[0x70, 0x47]).entry_trace().unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+}
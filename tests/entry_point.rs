@@ -0,0 +1,96 @@
+use pexp::analysis::EntryPointAnomaly;
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+use pexp::PeString;
+
+/// PE32 image with a 0x10-byte `.text` at RVA 0x1000 and a `.data` section at RVA
+/// 0x2000 holding 0x10 bytes and `bss` more of zero fill, entering at `entry_point`
+fn image(characteristics: u16, bss: u32, entry_point: u32) -> PortExe {
+    PeFixture::pe32()
+        .characteristics(characteristics)
+        .subsystem(2)
+        .dll_characteristics(0)
+        .entry_point(entry_point)
+        .section(".text", 0x6000_0020, &[0xC3; 0x10])
+        .section_with_bss(".data", 0xC000_0040, &[1; 0x10], bss)
+        .parse()
+        .unwrap()
+}
+
+#[test]
+fn entry_points_in_code_are_not_reported() {
+    let pe = image(0x0102, 0, 0x1004);
+    assert!(pe.entry_point_anomalies().is_empty());
+    // A DLL may have no entry point at all
+    let dll = image(0x2102, 0, 0);
+    assert!(dll.entry_point_anomalies().is_empty());
+}
+
+#[test]
+fn entry_points_outside_sections_are_reported() {
+    let pe = image(0x0102, 0, 0);
+    assert_eq!(pe.entry_point_anomalies(), [EntryPointAnomaly::Zero]);
+
+    let pe = image(0x0102, 0, 0x40);
+    assert_eq!(
+        pe.entry_point_anomalies(),
+        [EntryPointAnomaly::InHeaders {
+            rva: 0x40,
+            size_of_headers: 0x200
+        }]
+    );
+    assert_eq!(
+        pe.entry_point_anomalies()[0].to_string(),
+        "entry point 0x40 is in the headers, which span 0x200 bytes"
+    );
+
+    let pe = image(0x0102, 0, 0x1800);
+    assert_eq!(
+        pe.entry_point_anomalies(),
+        [EntryPointAnomaly::OutsideSections { rva: 0x1800 }]
+    );
+
+    let pe = image(0x0102, 0, 0x8000);
+    assert_eq!(
+        pe.entry_point_anomalies(),
+        [EntryPointAnomaly::BeyondImage {
+            rva: 0x8000,
+            size_of_image: 0x3000
+        }]
+    );
+}
+
+#[test]
+fn entry_points_in_data_and_zero_fill_are_reported() {
+    let data = PeString::from_bytes(b".data");
+    let pe = image(0x0102, 0, 0x2004);
+    assert_eq!(
+        pe.entry_point_anomalies(),
+        [EntryPointAnomaly::NonExecutable {
+            rva: 0x2004,
+            section: data.clone()
+        }]
+    );
+
+    // Room reserved to unpack code into, entered past the bytes in the file
+    let pe = image(0x0102, 0x8000, 0x5000);
+    assert_eq!(
+        pe.entry_point_anomalies(),
+        [
+            EntryPointAnomaly::NonExecutable {
+                rva: 0x5000,
+                section: data.clone()
+            },
+            EntryPointAnomaly::InflatedSection {
+                section: data.clone(),
+                virtual_size: 0x8010,
+                raw_size: 0x200
+            },
+            EntryPointAnomaly::InZeroFill {
+                rva: 0x5000,
+                section: data,
+                raw_size: 0x200
+            },
+        ]
+    );
+}
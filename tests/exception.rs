@@ -0,0 +1,238 @@
+mod common;
+
+use common::put32;
+use pexp::exception::{
+    Arm64Unwind, ArmUnwind, RuntimeFunction, UnwindOperation, UNW_FLAG_CHAININFO, UNW_FLAG_EHANDLER,
+};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+/// Offset of the exception directory entry in a PE32+ image built by `PeFixture`
+const EXCEPTION_DIRECTORY: usize = 0x80 + 4 + 20 + 112 + 3 * 8;
+
+/// PE32+ image for `machine` with code at RVA 0x1000 and `rdata` at RVA 0x2000, the
+/// exception directory taking its first `pdata_size` bytes
+fn image(machine: u16, rdata: Vec<u8>, pdata_size: u32) -> PortExe {
+    let mut data = PeFixture::pe64()
+        .machine(machine)
+        .dll_characteristics(0)
+        .section(".text", 0x6000_0020, &[0xCC; 0x100])
+        .section(".rdata", 0x4000_0040, &rdata)
+        .build();
+    put32(&mut data, EXCEPTION_DIRECTORY, 0x2000);
+    put32(&mut data, EXCEPTION_DIRECTORY + 4, pdata_size);
+    PortExe::from_bytes(data).unwrap()
+}
+
+fn x64_image() -> PortExe {
+    let mut rdata = vec![0u8; 0x80];
+    for (offset, value) in [
+        (0x00, 0x1000),
+        (0x04, 0x1040),
+        (0x08, 0x2040),
+        (0x0C, 0x1040),
+        (0x10, 0x1080),
+        (0x14, 0x2060),
+    ] {
+        put32(&mut rdata, offset, value);
+    }
+    // Version 1 with an exception handler: push rbp, push rbx, sub rsp, 0x200,
+    // lea rbp, [rsp+0x20]
+    rdata[0x40..0x44].copy_from_slice(&[0x09, 0x0E, 5, 0x25]);
+    for (index, slot) in [0x030Eu16, 0x010A, 0x0040, 0x3002, 0x5001]
+        .iter()
+        .enumerate()
+    {
+        let offset = 0x44 + index * 2;
+        rdata[offset..offset + 2].copy_from_slice(&slot.to_le_bytes());
+    }
+    put32(&mut rdata, 0x50, 0x1234);
+    // Chained to the first function after allocating 0x28 bytes
+    rdata[0x60..0x66].copy_from_slice(&[0x21, 0x04, 1, 0, 0x04, 0x42]);
+    for (offset, value) in [(0x68, 0x1000), (0x6C, 0x1040), (0x70, 0x2040)] {
+        put32(&mut rdata, offset, value);
+    }
+    image(0x8664, rdata, 24)
+}
+
+#[test]
+fn x64_unwind_codes_are_decoded() {
+    let pe = x64_image();
+    let functions = pe.runtime_functions().unwrap();
+    assert_eq!(functions.len(), 2);
+    let function = match &functions[0] {
+        RuntimeFunction::X64(function) => function,
+        other => panic!("unexpected entry {:?}", other),
+    };
+    assert_eq!(functions[0].end_address(), Some(0x1040));
+
+    let info = pe.unwind_info(function).unwrap();
+    assert_eq!(info.version(), 1);
+    assert_eq!(info.flags(), UNW_FLAG_EHANDLER);
+    assert_eq!(info.size_of_prolog(), 0x0E);
+    assert_eq!(info.frame_register(), Some("rbp"));
+    assert_eq!(info.frame_offset(), 0x20);
+    assert_eq!(info.handler_rva(), Some(0x1234));
+    let codes: Vec<(u8, UnwindOperation)> = info
+        .codes()
+        .iter()
+        .map(|code| (code.prolog_offset(), code.operation()))
+        .collect();
+    assert_eq!(
+        codes,
+        [
+            (0x0E, UnwindOperation::SetFramePointer),
+            (0x0A, UnwindOperation::Alloc { size: 0x200 }),
+            (0x02, UnwindOperation::PushNonVolatile { register: 3 }),
+            (0x01, UnwindOperation::PushNonVolatile { register: 5 }),
+        ]
+    );
+    assert_eq!(codes[2].1.to_string(), "push rbx");
+    assert_eq!(codes[1].1.to_string(), "alloc 0x200");
+}
+
+#[test]
+fn chained_unwind_info_names_the_parent_function() {
+    let pe = x64_image();
+    let functions = pe.runtime_functions().unwrap();
+    let function = match &functions[1] {
+        RuntimeFunction::X64(function) => function,
+        other => panic!("unexpected entry {:?}", other),
+    };
+    let info = pe.unwind_info(function).unwrap();
+    assert_eq!(info.flags(), UNW_FLAG_CHAININFO);
+    assert_eq!(info.frame_register(), None);
+    assert_eq!(info.handler_rva(), None);
+    assert_eq!(
+        info.codes()[0].operation(),
+        UnwindOperation::Alloc { size: 0x28 }
+    );
+    let parent = info.chained().unwrap();
+    assert_eq!(
+        (parent.begin_address(), parent.unwind_info_rva()),
+        (0x1000, 0x2040)
+    );
+}
+
+#[test]
+fn arm64_entries_are_packed_or_point_to_xdata() {
+    let mut rdata = vec![0u8; 0x80];
+    let packed = 1 | 0x10 << 2 | 2 << 16 | 3 << 21 | 2 << 23;
+    for (offset, value) in [
+        (0x00, 0x1000),
+        (0x04, packed),
+        (0x08, 0x1040),
+        (0x0C, 0x2040),
+    ] {
+        put32(&mut rdata, offset, value);
+    }
+    // 0x80 bytes, one epilogue described in the header, one code word, a handler
+    put32(
+        &mut rdata,
+        0x40,
+        0x20 | 1 << 20 | 1 << 21 | 1 << 22 | 1 << 27,
+    );
+    put32(&mut rdata, 0x48, 0x1500);
+    let pe = image(0xAA64, rdata, 16);
+    let functions = pe.runtime_functions().unwrap();
+    let ends: Vec<Option<u32>> = functions.iter().map(|f| f.end_address()).collect();
+    assert_eq!(ends, [Some(0x1040), Some(0x10C0)]);
+
+    let unwind = |index: usize| match &functions[index] {
+        RuntimeFunction::Arm64(function) => function.unwind().clone(),
+        other => panic!("unexpected entry {:?}", other),
+    };
+    match unwind(0) {
+        Arm64Unwind::Packed(packed) => {
+            assert!(!packed.fragment());
+            assert_eq!(packed.reg_i(), 2);
+            assert_eq!(packed.cr(), 3);
+            assert_eq!(packed.frame_size(), 0x20);
+        }
+        other => panic!("unexpected unwind data {:?}", other),
+    }
+    match unwind(1) {
+        Arm64Unwind::Unpacked {
+            unwind_data_rva,
+            unwind_data: Some(data),
+        } => {
+            assert_eq!(unwind_data_rva, 0x2040);
+            assert!(data.epilogue_in_header());
+            assert_eq!(data.epilogue_count(), 1);
+            assert_eq!(data.code_words(), 1);
+            assert_eq!(data.exception_handler_rva(), Some(0x1500));
+        }
+        other => panic!("unexpected unwind data {:?}", other),
+    }
+}
+
+#[test]
+fn armnt_entries_are_packed_or_point_to_xdata() {
+    let mut rdata = vec![0u8; 0x80];
+    // 0x40 bytes homing r0-r3, saving r4-r7 and LR, chaining r11, allocating 16 bytes
+    let packed = 1 | 0x20 << 2 | 1 << 15 | 3 << 16 | 1 << 20 | 1 << 21 | 4 << 22;
+    for (offset, value) in [
+        (0x00, 0x1001),
+        (0x04, packed),
+        (0x08, 0x1041),
+        (0x0C, 0x2040),
+        (0x10, 0x10C1),
+        (0x14, 0x9000),
+    ] {
+        put32(&mut rdata, offset, value);
+    }
+    // 0x80 byte fragment, one epilogue described in the header, one code word, a handler
+    put32(
+        &mut rdata,
+        0x40,
+        0x40 | 1 << 20 | 1 << 21 | 1 << 22 | 1 << 23 | 1 << 28,
+    );
+    put32(&mut rdata, 0x48, 0x1500);
+    // The last, incomplete entry is not read
+    let pe = image(0x01C4, rdata, 28);
+    let functions = pe.runtime_functions().unwrap();
+    let starts: Vec<u32> = functions.iter().map(|f| f.begin_address()).collect();
+    assert_eq!(starts, [0x1000, 0x1040, 0x10C0]);
+    let ends: Vec<Option<u32>> = functions.iter().map(|f| f.end_address()).collect();
+    assert_eq!(ends, [Some(0x1040), Some(0x10C0), None]);
+
+    let unwind = |index: usize| match &functions[index] {
+        RuntimeFunction::Arm(function) => function.unwind().clone(),
+        other => panic!("unexpected entry {:?}", other),
+    };
+    match unwind(0) {
+        ArmUnwind::Packed(packed) => {
+            assert!(!packed.fragment());
+            assert_eq!(packed.ret(), 0);
+            assert!(packed.homes_parameters());
+            assert_eq!(packed.reg(), 3);
+            assert!(!packed.r());
+            assert!(packed.saves_link_register());
+            assert!(packed.chains_frame());
+            assert_eq!(packed.stack_adjust(), 4);
+        }
+        other => panic!("unexpected unwind data {:?}", other),
+    }
+    match unwind(1) {
+        ArmUnwind::Unpacked {
+            unwind_data_rva,
+            unwind_data: Some(data),
+        } => {
+            assert_eq!(unwind_data_rva, 0x2040);
+            assert!(data.fragment());
+            assert!(data.epilogue_in_header());
+            assert_eq!(data.epilogue_count(), 1);
+            assert_eq!(data.code_words(), 1);
+            assert_eq!(data.exception_handler_rva(), Some(0x1500));
+        }
+        other => panic!("unexpected unwind data {:?}", other),
+    }
+    // .xdata outside the image
+    assert!(matches!(
+        unwind(2),
+        ArmUnwind::Unpacked {
+            unwind_data_rva: 0x9000,
+            unwind_data: None
+        }
+    ));
+}
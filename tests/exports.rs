@@ -0,0 +1,177 @@
+use pexp::bindings::BindingLanguage;
+use pexp::demangle::{demangle, Decoration};
+use pexp::export::{build_export_table, ExportKind, ExportSpec, ExportTarget};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+const EXPORT_DIRECTORY: usize = 0x80 + 4 + 20 + 96;
+
+/// DLL with code at RVA 0x1000, data at 0x2000 and its export directory at 0x3000,
+/// exporting `Run` from the code, `Table` from the data, `Sleep` as a forwarder and
+/// ordinal 9 from past the end of the image.
+fn dll() -> PortExe {
+    let specs = [
+        ExportSpec::new(Some("Run".to_string()), None, ExportTarget::Rva(0x1000)),
+        ExportSpec::new(Some("Table".to_string()), None, ExportTarget::Rva(0x2010)),
+        ExportSpec::new(
+            Some("Sleep".to_string()),
+            None,
+            ExportTarget::Forwarder("kernel32.Sleep".to_string()),
+        ),
+        ExportSpec::new(None, Some(9), ExportTarget::Rva(0x9000)),
+    ];
+    let table = build_export_table("mixed.dll", &specs, 0x3000).unwrap();
+    let mut data = PeFixture::pe32()
+        .characteristics(0x2102)
+        .subsystem(2)
+        .dll_characteristics(0)
+        .section(".text", 0x6000_0020, &[0xC3; 0x20])
+        .section(".data", 0xC000_0040, &[0; 0x20])
+        .section(".edata", 0x4000_0040, &table)
+        .build();
+    data[EXPORT_DIRECTORY..EXPORT_DIRECTORY + 4].copy_from_slice(&0x3000u32.to_le_bytes());
+    data[EXPORT_DIRECTORY + 4..EXPORT_DIRECTORY + 8]
+        .copy_from_slice(&(table.len() as u32).to_le_bytes());
+    PortExe::from_bytes(data).unwrap()
+}
+
+#[test]
+fn exports_are_classified_by_section() {
+    let kinds: Vec<(u32, ExportKind)> = dll()
+        .exports()
+        .unwrap()
+        .iter()
+        .map(|export| (export.ordinal(), export.kind()))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            (1, ExportKind::Code),
+            (2, ExportKind::Data),
+            (3, ExportKind::Forwarder),
+            (9, ExportKind::Unmapped)
+        ]
+    );
+}
+
+#[test]
+fn data_exports_are_flagged_in_definitions_and_bindings() {
+    let pe = dll();
+    let definition = pe.module_definition().unwrap().to_string();
+    assert!(definition.contains("Table @2 DATA"), "{}", definition);
+    assert!(!definition.contains("Run @1 DATA"), "{}", definition);
+
+    let header = pe.export_bindings(BindingLanguage::C).unwrap();
+    assert!(header.contains("/* Table (ordinal 2) is data, not a function */"));
+    assert!(header.contains("void Run(); /* ordinal 1 */"));
+}
+
+/// DLL for `machine` exporting a function named `names[i]` with ordinal `i + 1`
+fn exporting(machine: u16, names: &[&str]) -> PortExe {
+    let specs: Vec<ExportSpec> = names
+        .iter()
+        .map(|name| ExportSpec::new(Some(name.to_string()), None, ExportTarget::Rva(0x1000)))
+        .collect();
+    let table = build_export_table("widget.dll", &specs, 0x2000).unwrap();
+    PeFixture::pe32()
+        .machine(machine)
+        .dll()
+        .section(".text", 0x6000_0020, &[0xC3; 0x20])
+        .section(".edata", 0x4000_0040, &table)
+        .directory(0, 0x2000, table.len() as u32)
+        .parse()
+        .unwrap()
+}
+
+#[test]
+fn decorations_give_x86_bindings_their_calling_convention() {
+    let names = [
+        "_Sleep@4",
+        "@Fast@8",
+        "Vector@@16",
+        "_Nop@0",
+        "?Run@Widget@@QAEXXZ",
+        "_ZN6Widget4StopEv",
+        "move",
+    ];
+    let pe = exporting(0x014C, &names);
+    let header = pe.export_bindings(BindingLanguage::C).unwrap();
+    for line in [
+        "#ifndef PEXP_WIDGET_DLL_H",
+        "void __stdcall Sleep(uint32_t arg0); /* ordinal 1 */",
+        "void __fastcall Fast(uint32_t arg0, uint32_t arg1); /* ordinal 2 */",
+        "void __vectorcall Vector(); /* ordinal 3 */",
+        "void __stdcall Nop(void); /* ordinal 4 */",
+        "/* ?Run@Widget@@QAEXXZ (ordinal 5) is the C++ symbol Widget::Run */",
+        "/* _ZN6Widget4StopEv (ordinal 6) is the C++ symbol Widget::Stop */",
+        "void move(); /* ordinal 7 */",
+    ] {
+        assert!(header.contains(line), "{}\n{}", line, header);
+    }
+
+    let rust = pe.export_bindings(BindingLanguage::Rust).unwrap();
+    for line in [
+        "// Vector@@16 (ordinal 3): vectorcall is not stable in Rust",
+        "// ?Run@Widget@@QAEXXZ (ordinal 5) is the C++ symbol Widget::Run",
+        "#[link(name = \"widget\")]\nextern \"stdcall\" {",
+        "    pub fn Sleep(arg0: u32);",
+        "    pub fn Nop();",
+        "extern \"fastcall\" {\n    /// Ordinal 2\n    pub fn Fast(arg0: u32, arg1: u32);",
+        "    #[link_name = \"move\"]\n    pub fn move_();",
+    ] {
+        assert!(rust.contains(line), "{}\n{}", line, rust);
+    }
+
+    // Only x86 decorates names with the calling convention it uses
+    let header = exporting(0x8664, &names)
+        .export_bindings(BindingLanguage::C)
+        .unwrap();
+    assert!(
+        header.contains("void Sleep(); /* ordinal 1 */"),
+        "{}",
+        header
+    );
+    assert!(
+        header.contains("void Fast(); /* ordinal 2 */"),
+        "{}",
+        header
+    );
+}
+
+#[test]
+fn cpp_symbols_are_reduced_to_their_qualified_names() {
+    for (symbol, name, decoration) in [
+        ("?Run@Widget@@QAEXXZ", "Widget::Run", Decoration::Msvc),
+        (
+            "??0Widget@ui@@QAE@XZ",
+            "ui::Widget::Widget",
+            Decoration::Msvc,
+        ),
+        ("??1Widget@@UAE@XZ", "Widget::~Widget", Decoration::Msvc),
+        (
+            "??4Widget@@QAEAAV0@ABV0@@Z",
+            "Widget::operator=",
+            Decoration::Msvc,
+        ),
+        (
+            "_ZN2ui6Widget4StopEv",
+            "ui::Widget::Stop",
+            Decoration::Itanium,
+        ),
+        ("_ZN6WidgetD2Ev", "Widget::~Widget", Decoration::Itanium),
+        ("__Z4mainv", "main", Decoration::Itanium),
+        // Templates are beyond the decoder and come back untouched
+        ("?f@?$Box@H@@QAEXXZ", "?f@?$Box@H@@QAEXXZ", Decoration::None),
+        ("Plain", "Plain", Decoration::None),
+        ("_cdecl_name", "_cdecl_name", Decoration::None),
+    ] {
+        let demangled = demangle(symbol);
+        assert_eq!(
+            (demangled.name(), demangled.decoration()),
+            (name, decoration),
+            "{}",
+            symbol
+        );
+        assert_eq!(demangled.is_cpp(), decoration != Decoration::None);
+    }
+}
@@ -0,0 +1,79 @@
+use pexp::address::{RelativeVirtualAddress, VirtualAddress};
+use pexp::expression::{AddressExpression, AddressValue};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+const IMAGE_BASE_64: u64 = 0x1_4000_0000;
+
+/// `.text` with 0x300 bytes at RVA 0x1000, file offset 0x200, and `.data` with 0x80 bytes
+/// at RVA 0x2000, file offset 0x600, with the entry point at RVA 0x1010
+fn image() -> PortExe {
+    PeFixture::pe64()
+        .dll_characteristics(0)
+        .entry_point(0x1010)
+        .section(".text", 0x6000_0020, &[0xCC; 0x300])
+        .section(".data", 0xC000_0040, &[0; 0x80])
+        .parse()
+        .unwrap()
+}
+
+fn rva(rva: u32) -> AddressValue {
+    AddressValue::Rva(RelativeVirtualAddress::new(rva))
+}
+
+#[test]
+fn terms_evaluate_over_the_image() {
+    let pe = image();
+    let resolver = pe.address_resolver();
+    let evaluate = |text: &str| pe.evaluate_address(text).unwrap();
+
+    assert_eq!(evaluate("entry+0x20"), rva(0x1030));
+    assert_eq!(evaluate("section(.text).start+10"), rva(0x100A));
+    assert_eq!(evaluate("section(.data)"), rva(0x2000));
+    assert_eq!(evaluate("section(.text).end - 0x10"), rva(0x12F0));
+    assert_eq!(evaluate("section(.data).size"), AddressValue::Number(0x80));
+    assert_eq!(evaluate("section(.data).raw"), AddressValue::Offset(0x600));
+    assert_eq!(evaluate("rva:0x1234"), rva(0x1234));
+    assert_eq!(
+        evaluate("base+0x1000"),
+        AddressValue::Va(VirtualAddress::new(IMAGE_BASE_64 + 0x1000))
+    );
+    assert_eq!(
+        evaluate("section(.text).end-section(.text).start"),
+        AddressValue::Number(0x300)
+    );
+    assert_eq!(evaluate("0x40"), AddressValue::Number(0x40));
+
+    assert_eq!(evaluate("entry+0x20").offset(&resolver), Some(0x230));
+    assert_eq!(evaluate("va:0x140002010").offset(&resolver), Some(0x610));
+    assert_eq!(
+        evaluate("off:0x610").rva(&resolver),
+        Some(RelativeVirtualAddress::new(0x2010))
+    );
+    assert_eq!(evaluate("rva:0x1234").to_string(), "rva:0x1234");
+}
+
+#[test]
+fn malformed_expressions_are_rejected() {
+    let pe = image();
+    for text in [
+        "",
+        "entry+",
+        "entry*2",
+        "section(.text",
+        "section(.text).middle",
+        "heap:0x10",
+        "0xZZ",
+    ] {
+        assert!(AddressExpression::parse(text).is_err(), "{}", text);
+    }
+    for text in [
+        "entry+section(.text)",
+        "entry-base",
+        "-entry",
+        "section(.rsrc)",
+        "export(Missing)",
+    ] {
+        assert!(pe.evaluate_address(text).is_err(), "{}", text);
+    }
+}
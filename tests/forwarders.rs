@@ -0,0 +1,133 @@
+use pexp::export::{build_export_table, ExportSpec, ExportTarget};
+use pexp::loader::{ForwarderOutcome, SymbolRef, VirtualLoader, MAX_FORWARDER_DEPTH};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+const EXPORT_DIRECTORY: usize = 0x80 + 4 + 20 + 96;
+
+/// DLL named `name` whose export directory, in its only section at RVA 0x1000, holds
+/// `exports` as (name, forwarder) pairs; exports without a forwarder point past the
+/// directory, at RVA 0x1800.
+fn dll(name: &str, exports: &[(&str, Option<&str>)]) -> PortExe {
+    let specs: Vec<ExportSpec> = exports
+        .iter()
+        .map(|(export, forwarder)| {
+            let target = match forwarder {
+                Some(forwarder) => ExportTarget::Forwarder(forwarder.to_string()),
+                None => ExportTarget::Rva(0x1800),
+            };
+            ExportSpec::new(Some(export.to_string()), None, target)
+        })
+        .collect();
+    let table = build_export_table(name, &specs, 0x1000).unwrap();
+    let mut data = PeFixture::pe32()
+        .characteristics(0x2102)
+        .subsystem(2)
+        .dll_characteristics(0)
+        .section(".edata", 0x4000_0040, &table)
+        .build();
+    data[EXPORT_DIRECTORY..EXPORT_DIRECTORY + 4].copy_from_slice(&0x1000u32.to_le_bytes());
+    data[EXPORT_DIRECTORY + 4..EXPORT_DIRECTORY + 8]
+        .copy_from_slice(&(table.len() as u32).to_le_bytes());
+    PortExe::from_bytes(data).unwrap()
+}
+
+fn loader(modules: Vec<(&str, PortExe)>) -> VirtualLoader {
+    let mut loader = VirtualLoader::new();
+    for (name, pe) in modules {
+        loader.load_at_preferred_base(name, pe).unwrap();
+    }
+    loader
+}
+
+#[test]
+fn chains_resolve_across_modules() {
+    let loader = loader(vec![
+        ("a.dll", dll("a.dll", &[("Open", Some("b.Open"))])),
+        ("b.dll", dll("b.dll", &[("Open", Some("c.OpenImpl"))])),
+        ("c.dll", dll("c.dll", &[("OpenImpl", None)])),
+    ]);
+    let chains = loader.forwarder_chains(MAX_FORWARDER_DEPTH);
+    assert_eq!(chains.len(), 2);
+
+    let first = &chains[0];
+    assert_eq!(first.module(), "a.dll");
+    assert_eq!(first.export(), &SymbolRef::Name("Open".into()));
+    assert_eq!(first.forwarders(), ["b.Open", "c.OpenImpl"]);
+    assert!(!first.is_broken());
+    let c_base = loader.module("c").unwrap().base();
+    match first.outcome() {
+        ForwarderOutcome::Resolved(resolution) => {
+            assert_eq!(resolution.module(), "c.dll");
+            assert_eq!(resolution.address(), c_base + 0x1800);
+        }
+        outcome => panic!("unexpected outcome {}", outcome),
+    }
+    assert_eq!(chains[1].module(), "b.dll");
+    assert_eq!(chains[1].forwarders(), ["c.OpenImpl"]);
+}
+
+#[test]
+fn loops_and_deep_chains_are_broken() {
+    let loader = loader(vec![
+        (
+            "a.dll",
+            dll(
+                "a.dll",
+                &[("Ping", Some("b.Pong")), ("Step", Some("b.Step"))],
+            ),
+        ),
+        (
+            "b.dll",
+            dll(
+                "b.dll",
+                &[("Pong", Some("a.Ping")), ("Step", Some("c.Step"))],
+            ),
+        ),
+        ("c.dll", dll("c.dll", &[("Step", None)])),
+    ]);
+    let chains = loader.forwarder_chains(2);
+    let ping = &chains[0];
+    assert_eq!(ping.forwarders(), ["b.Pong", "a.Ping"]);
+    assert_eq!(ping.outcome(), &ForwarderOutcome::Loop);
+    assert!(ping.is_broken());
+
+    assert!(!chains[1].is_broken());
+    let step = &loader.forwarder_chains(1)[1];
+    assert_eq!(step.forwarders(), ["b.Step"]);
+    assert_eq!(step.outcome(), &ForwarderOutcome::TooDeep(1));
+    assert!(step.is_broken());
+
+    let error = loader
+        .resolve("a.dll", &SymbolRef::Name("Ping".into()))
+        .unwrap_err();
+    assert_eq!(error.to_string(), "forwarder loop");
+}
+
+#[test]
+fn chains_leaving_the_module_set_are_not_broken() {
+    let loader = loader(vec![(
+        "a.dll",
+        dll(
+            "a.dll",
+            &[
+                ("Alloc", Some("kernel32.HeapAlloc")),
+                ("Free", Some("a.Release")),
+            ],
+        ),
+    )]);
+    let chains = loader.forwarder_chains(MAX_FORWARDER_DEPTH);
+    assert_eq!(
+        chains[0].outcome(),
+        &ForwarderOutcome::ModuleNotLoaded("kernel32".to_string())
+    );
+    assert!(!chains[0].is_broken());
+    assert_eq!(
+        chains[1].outcome(),
+        &ForwarderOutcome::MissingExport {
+            module: "a.dll".to_string(),
+            symbol: SymbolRef::Name("Release".into()),
+        }
+    );
+    assert!(chains[1].is_broken());
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use common::{put32, DATA_DIRECTORIES};
+use pexp::export::{build_export_table, ExportSpec, ExportTarget};
+use pexp::loader::VirtualLoader;
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+/// PE32 image whose only section, at RVA 0x1000, holds the data of directory `index`
+fn image(characteristics: u16, index: usize, section: Vec<u8>) -> PortExe {
+    let size = section.len() as u32;
+    let mut data = PeFixture::pe32()
+        .characteristics(characteristics)
+        .dll_characteristics(0)
+        .section(".rdata", 0x4000_0040, &section)
+        .build();
+    put32(&mut data, DATA_DIRECTORIES + index * 8, 0x1000);
+    put32(&mut data, DATA_DIRECTORIES + index * 8 + 4, size);
+    PortExe::from_bytes(data).unwrap()
+}
+
+/// `util.dll` exporting `names`, which end up sorted in its export name table
+fn exporter(names: &[&str]) -> PortExe {
+    let specs: Vec<ExportSpec> = names
+        .iter()
+        .map(|name| ExportSpec::new(Some(name.to_string()), None, ExportTarget::Rva(0x1800)))
+        .collect();
+    image(
+        0x2102,
+        0,
+        build_export_table("util.dll", &specs, 0x1000).unwrap(),
+    )
+}
+
+/// Executable importing `(function, hint)` pairs from `util.dll`
+fn importer(imports: &[(&str, u16)]) -> PortExe {
+    let rva = 0x1000;
+    let mut data = vec![0u8; 0x200];
+    put32(&mut data, 0, rva + 64);
+    put32(&mut data, 12, rva + 0x1F0);
+    put32(&mut data, 16, rva + 96);
+    for (index, (function, hint)) in imports.iter().enumerate() {
+        let entry = 128 + index * 16;
+        put32(&mut data, 64 + index * 4, rva + entry as u32);
+        put32(&mut data, 96 + index * 4, rva + entry as u32);
+        data[entry..entry + 2].copy_from_slice(&hint.to_le_bytes());
+        data[entry + 2..entry + 2 + function.len()].copy_from_slice(function.as_bytes());
+    }
+    data[0x1F0..0x1F8].copy_from_slice(b"util.dll");
+    image(0x0102, 1, data)
+}
+
+fn loader(importer: PortExe, exporter: PortExe) -> VirtualLoader {
+    let mut loader = VirtualLoader::new();
+    loader.load_at_preferred_base("app.exe", importer).unwrap();
+    loader.load_at_preferred_base("util.dll", exporter).unwrap();
+    loader
+}
+
+#[test]
+fn matching_hints_are_not_reported() {
+    let loader = loader(
+        importer(&[("Open", 1), ("Close", 0), ("Read", 2)]),
+        exporter(&["Read", "Open", "Close"]),
+    );
+    assert!(loader.stale_hints().unwrap().is_empty());
+}
+
+#[test]
+fn hints_of_a_rebuilt_dll_are_stale() {
+    // Flush was added since the import library was made, shifting Open and Read
+    let loader = loader(
+        importer(&[("Open", 1), ("Close", 0), ("Read", 2), ("Seek", 0)]),
+        exporter(&["Read", "Open", "Close", "Flush"]),
+    );
+    let stale = loader.stale_hints().unwrap();
+    assert_eq!(stale.len(), 2);
+    assert_eq!(stale[0].importer(), "app.exe");
+    assert_eq!(stale[0].dll(), "util.dll");
+    assert_eq!(stale[0].function().to_string(), "Open");
+    assert_eq!(stale[0].hint(), 1);
+    assert_eq!(stale[0].position(), 2);
+    assert_eq!(stale[1].function().to_string(), "Read");
+    assert_eq!(stale[1].position(), 3);
+    assert_eq!(
+        stale[1].to_string(),
+        "app.exe imports util.dll!Read with hint 2, but it is at 3"
+    );
+}
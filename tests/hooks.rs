@@ -0,0 +1,147 @@
+use pexp::export::{build_export_table, ExportSpec, ExportTarget};
+use pexp::hook::{HookKind, DEFAULT_PROLOGUE_LENGTH};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+/// `push ebp; mov ebp, esp` and padding, repeated for each 16-byte function
+const PROLOGUE: [u8; 16] = [
+    0x55, 0x8B, 0xEC, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0xC3,
+];
+
+/// DLL exporting six functions 16 bytes apart from RVA 0x1000, a variable and a
+/// forwarder
+fn dll(fixture: PeFixture) -> PortExe {
+    let mut specs: Vec<ExportSpec> = ["A", "B", "C", "D", "E", "F"]
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            let target = ExportTarget::Rva(0x1000 + index as u32 * 0x10);
+            ExportSpec::new(Some(name.to_string()), None, target)
+        })
+        .collect();
+    specs.push(ExportSpec::new(
+        Some("Table".to_string()),
+        None,
+        ExportTarget::Rva(0x2000),
+    ));
+    specs.push(ExportSpec::new(
+        Some("Sleep".to_string()),
+        None,
+        ExportTarget::Forwarder("kernel32.Sleep".to_string()),
+    ));
+    let table = build_export_table("hooked.dll", &specs, 0x3000).unwrap();
+    fixture
+        .dll()
+        .section(".text", 0x6000_0020, &PROLOGUE.repeat(6))
+        .section(".data", 0xC000_0040, &[0; 0x10])
+        .section(".edata", 0x4000_0040, &table)
+        .directory(0, 0x3000, table.len() as u32)
+        .parse()
+        .unwrap()
+}
+
+/// `file` as the loader maps it, with `patches` applied at their RVAs
+fn dump(file: &PortExe, patches: &[(u32, &[u8])]) -> PortExe {
+    let header = file.optional_header().unwrap();
+    let mut mapped = vec![0u8; header.size_of_image() as usize];
+    let headers = header.size_of_headers() as usize;
+    mapped[..headers].copy_from_slice(&file.data()[..headers]);
+    for section in file.section_headers() {
+        let start = section.virtual_address().into_value() as usize;
+        let data = file.section_data(section);
+        mapped[start..start + data.len()].copy_from_slice(data);
+    }
+    for &(rva, bytes) in patches {
+        mapped[rva as usize..rva as usize + bytes.len()].copy_from_slice(bytes);
+    }
+    PortExe::from_mapped_bytes(mapped).unwrap()
+}
+
+#[test]
+fn detours_at_export_entry_points_are_classified() {
+    let original = dll(PeFixture::pe32());
+    let hooked = dump(
+        &original,
+        &[
+            (0x1000, &[0xE9, 0x00, 0x01, 0x00, 0x00]),
+            (0x1010, &[0xE8, 0xF0, 0xFF, 0xFF, 0xFF]),
+            (0x1020, &[0xFF, 0x25, 0x00, 0x20, 0x40, 0x00]),
+            (0x1030, &[0x68, 0x78, 0x56, 0x34, 0x12, 0xC3]),
+            (0x1040, &[0xCC]),
+            // Variables change all the time and are not code
+            (0x2000, &[0xE9]),
+        ],
+    );
+    assert!(hooked.layout().is_mapped());
+
+    let hooks = hooked
+        .inline_hooks(&original, DEFAULT_PROLOGUE_LENGTH)
+        .unwrap();
+    let summary: Vec<_> = hooks
+        .iter()
+        .map(|hook| {
+            (
+                hook.name().unwrap().to_string(),
+                hook.rva(),
+                hook.kind(),
+                hook.target(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("A".to_string(), 0x1000, HookKind::Jump, Some(0x0040_1105)),
+            ("B".to_string(), 0x1010, HookKind::Call, Some(0x0040_1005)),
+            ("C".to_string(), 0x1020, HookKind::IndirectJump, None),
+            (
+                "D".to_string(),
+                0x1030,
+                HookKind::PushRet,
+                Some(0x1234_5678)
+            ),
+            ("E".to_string(), 0x1040, HookKind::Patched, None),
+        ]
+    );
+    assert_eq!(hooks[4].original(), PROLOGUE);
+    assert_eq!(hooks[4].current()[..2], [0xCC, 0x8B]);
+    assert_eq!(HookKind::PushRet.to_string(), "push/ret");
+
+    // Comparing only the first byte still finds every hook, reporting that byte alone
+    let hooks = hooked.inline_hooks(&original, 1).unwrap();
+    assert_eq!(hooks.len(), 5);
+    assert_eq!(hooks[0].current(), [0xE9]);
+    assert!(original.inline_hooks(&original, 16).unwrap().is_empty());
+}
+
+#[test]
+fn short_jumps_and_absolute_jumps_of_x64_modules_are_followed() {
+    let original = dll(PeFixture::pe64());
+    let mut mov_jump = vec![0x48, 0xB8];
+    mov_jump.extend_from_slice(&0x7FFE_0000_1000u64.to_le_bytes());
+    mov_jump.extend_from_slice(&[0xFF, 0xE0]);
+    let hooked = dump(&original, &[(0x1000, &[0xEB, 0xFE]), (0x1010, &mov_jump)]);
+
+    let hooks = hooked.inline_hooks(&original, 16).unwrap();
+    let image_base = original.optional_header().unwrap().image_base();
+    assert_eq!(hooks[0].kind(), HookKind::Jump);
+    // `jmp $` spins on itself
+    assert_eq!(hooks[0].target(), Some(image_base + 0x1000));
+    assert_eq!(hooks[1].kind(), HookKind::MovJump);
+    assert_eq!(hooks[1].target(), Some(0x7FFE_0000_1000));
+
+    // The same bytes are no mov/jmp pair in 32-bit code
+    let original = dll(PeFixture::pe32());
+    let hooked = dump(&original, &[(0x1010, &mov_jump)]);
+    let hooks = hooked.inline_hooks(&original, 16).unwrap();
+    assert_eq!(hooks[0].kind(), HookKind::Patched);
+}
+
+#[test]
+fn object_files_have_no_mapped_layout() {
+    // Bare x64 file header without sections or symbols
+    let mut object = vec![0; 20];
+    object[..2].copy_from_slice(&0x8664u16.to_le_bytes());
+    assert!(PortExe::from_bytes(object.clone()).is_ok());
+    assert!(PortExe::from_mapped_bytes(object).is_err());
+}
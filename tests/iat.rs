@@ -0,0 +1,214 @@
+use pexp::export::{build_export_table, ExportSpec, ExportTarget};
+use pexp::iat::build_import_table;
+use pexp::loader::{SymbolRef, VirtualLoader};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+const KERNELBASE: u64 = 0x7000_0000;
+const KERNEL32: u64 = 0x7100_0000;
+const USER32: u64 = 0x7200_0000;
+
+/// DLL exporting `(name, ordinal, target)` entries, where a target of `Err` is a
+/// forwarder and `Ok` an RVA in its code
+fn library(name: &str, exports: &[(Option<&str>, Result<u32, &str>)]) -> PortExe {
+    let specs: Vec<ExportSpec> = exports
+        .iter()
+        .map(|(export, target)| {
+            let target = match target {
+                Ok(rva) => ExportTarget::Rva(*rva),
+                Err(forwarder) => ExportTarget::Forwarder(forwarder.to_string()),
+            };
+            ExportSpec::new(export.map(str::to_string), None, target)
+        })
+        .collect();
+    let table = build_export_table(name, &specs, 0x2000).unwrap();
+    PeFixture::pe32()
+        .dll()
+        .section(".text", 0x6000_0020, &[0xC3; 0x40])
+        .section(".edata", 0x4000_0040, &table)
+        .directory(0, 0x2000, table.len() as u32)
+        .parse()
+        .unwrap()
+}
+
+/// `kernel32.dll` forwarding `HeapAlloc` to `kernelbase.dll`, and `user32.dll` with an
+/// export that has no name
+fn loader() -> VirtualLoader {
+    let mut loader = VirtualLoader::new();
+    let kernelbase = library(
+        "kernelbase.dll",
+        &[(Some("HeapAlloc"), Ok(0x1000)), (Some("Sleep"), Ok(0x1010))],
+    );
+    let kernel32 = library(
+        "kernel32.dll",
+        &[
+            (Some("CreateFileA"), Ok(0x1000)),
+            (Some("ExitProcess"), Ok(0x1010)),
+            (Some("HeapAlloc"), Err("kernelbase.HeapAlloc")),
+        ],
+    );
+    let user32 = library(
+        "user32.dll",
+        &[(Some("MessageBoxA"), Ok(0x1000)), (None, Ok(0x1010))],
+    );
+    loader
+        .load("kernelbase.dll", kernelbase, KERNELBASE)
+        .unwrap();
+    loader.load("kernel32.dll", kernel32, KERNEL32).unwrap();
+    loader.load("user32.dll", user32, USER32).unwrap();
+    loader
+}
+
+/// Dumped image whose import address table at RVA 0x1000 holds `values`
+fn dump(values: &[u64]) -> PortExe {
+    let iat: Vec<u8> = values
+        .iter()
+        .flat_map(|&value| (value as u32).to_le_bytes())
+        .collect();
+    PeFixture::pe32()
+        .section(".data", 0xC000_0040, &iat)
+        .directory(12, 0x1000, iat.len() as u32)
+        .parse()
+        .unwrap()
+}
+
+fn name(name: &str) -> SymbolRef {
+    SymbolRef::Name(name.into())
+}
+
+#[test]
+fn iat_slots_are_attributed_to_the_module_of_their_neighbours() {
+    let pe = dump(&[
+        KERNELBASE + 0x1000,
+        KERNEL32 + 0x1000,
+        KERNEL32 + 0x1010,
+        0,
+        USER32 + 0x1000,
+        USER32 + 0x1010,
+        0,
+        0xDEAD_BEEF,
+        KERNELBASE + 0x1000,
+        KERNELBASE + 0x1010,
+        0,
+    ]);
+    let (rva, size) = pe.iat_range().unwrap();
+    assert_eq!((rva, size), (0x1000, 44));
+
+    let loader = loader();
+    let slots = pe.scan_iat(&loader, rva, size).unwrap();
+    let targets: Vec<_> = slots
+        .iter()
+        .map(|slot| {
+            slot.target()
+                .map(|target| (target.module(), target.symbol().clone()))
+        })
+        .collect();
+    assert_eq!(
+        targets,
+        [
+            // The address of kernelbase!HeapAlloc is kernel32's through its forwarder
+            Some(("kernel32.dll", name("HeapAlloc"))),
+            Some(("kernel32.dll", name("CreateFileA"))),
+            Some(("kernel32.dll", name("ExitProcess"))),
+            None,
+            Some(("user32.dll", name("MessageBoxA"))),
+            Some(("user32.dll", SymbolRef::Ordinal(2))),
+            None,
+            None,
+            Some(("kernelbase.dll", name("HeapAlloc"))),
+            Some(("kernelbase.dll", name("Sleep"))),
+            None,
+        ]
+    );
+    assert_eq!(slots[7].value(), 0xDEAD_BEEF);
+    assert_eq!(slots[9].rva(), 0x1024);
+
+    let descriptors = pe.reconstruct_imports(&loader, rva, size).unwrap();
+    let summary: Vec<_> = descriptors
+        .iter()
+        .map(|descriptor| {
+            (
+                descriptor.dll_name(),
+                descriptor.first_thunk(),
+                descriptor.symbols().len(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("kernel32.dll", 0x1000, 3),
+            ("user32.dll", 0x1010, 2),
+            ("kernelbase.dll", 0x1020, 2),
+        ]
+    );
+
+    assert!(pe.scan_iat(&loader, 0x5000, 8).is_err());
+}
+
+#[test]
+fn rebuilt_import_tables_parse_back() {
+    let pe = dump(&[
+        KERNEL32 + 0x1000,
+        KERNEL32 + 0x1010,
+        0,
+        USER32 + 0x1010,
+        USER32 + 0x1000,
+        0,
+    ]);
+    let (rva, size) = pe.iat_range().unwrap();
+    let descriptors = pe.reconstruct_imports(&loader(), rva, size).unwrap();
+    let table = build_import_table(&descriptors, 0x2000, false).unwrap();
+
+    let iat = pe.section_data(&pe.section_headers()[0]).to_vec();
+    let rebuilt = PeFixture::pe32()
+        .section(".data", 0xC000_0040, &iat)
+        .section(".idata", 0x4000_0040, &table)
+        .directory(1, 0x2000, table.len() as u32)
+        .directory(12, 0x1000, iat.len() as u32)
+        .parse()
+        .unwrap();
+    let imports: Vec<_> = rebuilt
+        .imports()
+        .unwrap()
+        .iter()
+        .flat_map(|descriptor| {
+            descriptor.imports().iter().map(move |import| {
+                (
+                    descriptor.dll_name().to_string(),
+                    import.name().map(ToString::to_string),
+                    import.ordinal(),
+                    import.iat_rva(),
+                )
+            })
+        })
+        .collect();
+    assert_eq!(
+        imports,
+        [
+            (
+                "kernel32.dll".to_string(),
+                Some("CreateFileA".to_string()),
+                None,
+                0x1000
+            ),
+            (
+                "kernel32.dll".to_string(),
+                Some("ExitProcess".to_string()),
+                None,
+                0x1004
+            ),
+            ("user32.dll".to_string(), None, Some(2), 0x100C),
+            (
+                "user32.dll".to_string(),
+                Some("MessageBoxA".to_string()),
+                None,
+                0x1010
+            ),
+        ]
+    );
+
+    assert!(build_import_table(&descriptors, 0xFFFF_FFF0, false).is_err());
+    let pe64 = build_import_table(&descriptors, 0x2000, true).unwrap();
+    assert_eq!(pe64.len() - table.len(), 4 * 6);
+}
@@ -0,0 +1,253 @@
+mod common;
+
+use common::{fixtures, resource_section, Resource, ResourceKey, DATA_DIRECTORIES};
+use pexp::icon::{hamming_distance, IconFormat, IconHash, DEFAULT_ICON_DISTANCE};
+use pexp::port_exe::PortExe;
+use pexp::resource::ResourceId;
+use pexp::testing::PeFixture;
+
+const RESOURCE_RVA: u32 = 0x2000;
+const RT_ICON: u16 = 3;
+const RT_GROUP_ICON: u16 = 14;
+
+/// Left half white, right half black
+fn white_left(x: usize, size: usize) -> bool {
+    x < size / 2
+}
+
+/// Icon bitmap of `size` pixels square whose pixels are white where `white` holds.
+///
+/// With `mask` set, 32-bit pixels carry no alpha and the AND mask marks the pixels
+/// `white` selects as transparent over otherwise black pixels.
+fn bitmap(size: usize, bit_count: u16, white: fn(usize, usize) -> bool, mask: bool) -> Vec<u8> {
+    let mut data = Vec::new();
+    for value in [40, size as u32, 2 * size as u32] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&bit_count.to_le_bytes());
+    data.extend_from_slice(&[0; 24]);
+    if bit_count == 4 {
+        data.extend_from_slice(&[0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0]);
+        data.extend_from_slice(&[0; 14 * 4]);
+    }
+    let stride = (size * bit_count as usize + 31) / 32 * 4;
+    let mask_stride = (size + 31) / 32 * 4;
+    let mut pixels = vec![0u8; stride * size];
+    let mut and_mask = vec![0u8; mask_stride * size];
+    for row in 0..size {
+        for x in 0..size {
+            let lit = white(x, size);
+            match bit_count {
+                32 if mask => {
+                    if lit {
+                        and_mask[row * mask_stride + x / 8] |= 0x80 >> (x % 8);
+                    }
+                }
+                32 => {
+                    let value = if lit { 0xFF } else { 0 };
+                    pixels[row * stride + x * 4..][..4]
+                        .copy_from_slice(&[value, value, value, 0xFF]);
+                }
+                _ => {
+                    if lit {
+                        pixels[row * stride + x / 2] |= if x % 2 == 0 { 0x10 } else { 0x01 };
+                    }
+                }
+            }
+        }
+    }
+    data.extend_from_slice(&pixels);
+    data.extend_from_slice(&and_mask);
+    data
+}
+
+/// `GRPICONDIR` listing `(size, bit_count, id)` images
+fn group(images: &[(u8, u16, u16)]) -> Vec<u8> {
+    let mut data = vec![0, 0, 1, 0];
+    data.extend_from_slice(&(images.len() as u16).to_le_bytes());
+    for &(size, bit_count, id) in images {
+        data.extend_from_slice(&[size, size, 0, 0, 1, 0]);
+        data.extend_from_slice(&bit_count.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&id.to_le_bytes());
+    }
+    data
+}
+
+fn image(icons: &[Resource]) -> PortExe {
+    let rsrc = resource_section(RESOURCE_RVA, icons);
+    let mut data = PeFixture::pe32()
+        .subsystem(2)
+        .dll_characteristics(0)
+        .section(".text", 0x6000_0020, &[0xC3; 0x10])
+        .section(".rsrc", 0x4000_0040, &rsrc)
+        .build();
+    let directory = DATA_DIRECTORIES + 2 * 8;
+    data[directory..directory + 4].copy_from_slice(&RESOURCE_RVA.to_le_bytes());
+    data[directory + 4..directory + 8].copy_from_slice(&(rsrc.len() as u32).to_le_bytes());
+    common::exercise(&data);
+    PortExe::from_bytes(data).unwrap()
+}
+
+#[test]
+fn largest_deepest_image_of_the_first_group_is_hashed() {
+    let small = bitmap(16, 4, white_left, false);
+    let large = bitmap(32, 32, white_left, false);
+    let other = group(&[(48, 32, 7)]);
+    let groups = group(&[(16, 4, 1), (32, 32, 2)]);
+    let pe = image(&[
+        (ResourceKey::Id(RT_ICON), ResourceKey::Id(1), &small),
+        (ResourceKey::Id(RT_ICON), ResourceKey::Id(2), &large),
+        (ResourceKey::Id(RT_GROUP_ICON), ResourceKey::Id(1), &other),
+        (
+            ResourceKey::Id(RT_GROUP_ICON),
+            ResourceKey::Name("MAINICON"),
+            &groups,
+        ),
+    ]);
+
+    let icon = pe.primary_icon().unwrap().unwrap();
+    match icon.group() {
+        ResourceId::Name(name) => assert_eq!(name.to_string_lossy(), "MAINICON"),
+        ResourceId::Id(id) => panic!("numbered group {} chosen", id),
+    }
+    assert_eq!(icon.id(), 2);
+    assert_eq!(
+        (icon.width(), icon.height(), icon.bit_count()),
+        (32, 32, 32)
+    );
+    assert_eq!(icon.format(), IconFormat::Bitmap);
+    assert_eq!(icon.data(), &large[..]);
+
+    let hash = pe.icon_hash().unwrap().unwrap();
+    assert_eq!(hash.ahash(), 0xF0F0_F0F0_F0F0_F0F0);
+    assert_eq!(hash.dhash(), 0x1818_1818_1818_1818);
+}
+
+#[test]
+fn the_same_picture_at_another_size_and_depth_is_near() {
+    let large = bitmap(32, 32, white_left, false);
+    let small = bitmap(16, 4, white_left, false);
+    let inverted = bitmap(32, 32, |x, size| !white_left(x, size), false);
+    let hash = |data: &[u8]| {
+        let groups = group(&[(0, 32, 1)]);
+        image(&[
+            (ResourceKey::Id(RT_ICON), ResourceKey::Id(1), data),
+            (ResourceKey::Id(RT_GROUP_ICON), ResourceKey::Id(1), &groups),
+        ])
+        .icon_hash()
+        .unwrap()
+        .unwrap()
+    };
+
+    let (large, small, inverted) = (hash(&large), hash(&small), hash(&inverted));
+    assert_eq!(small.ahash(), large.ahash());
+    assert!(large.distance(&small) <= DEFAULT_ICON_DISTANCE);
+    assert_eq!(hamming_distance(large.ahash(), inverted.ahash()), 64);
+    assert!(large.distance(&inverted) > DEFAULT_ICON_DISTANCE);
+}
+
+#[test]
+fn transparent_pixels_are_composited_over_white() {
+    let masked = bitmap(32, 32, white_left, true);
+    let groups = group(&[(32, 32, 1)]);
+    let pe = image(&[
+        (ResourceKey::Id(RT_ICON), ResourceKey::Id(1), &masked),
+        (ResourceKey::Id(RT_GROUP_ICON), ResourceKey::Id(1), &groups),
+    ]);
+    let hash = pe.icon_hash().unwrap().unwrap();
+    assert_eq!(
+        hash,
+        IconHash::new(0xF0F0_F0F0_F0F0_F0F0, 0x1818_1818_1818_1818)
+    );
+}
+
+/// 32x32 RGBA PNG of [`white_left`], one row per filter type
+#[cfg(feature = "icon-png")]
+fn png() -> Vec<u8> {
+    let chunk = |png: &mut Vec<u8>, kind: &[u8], body: &[u8]| {
+        png.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        png.extend_from_slice(kind);
+        png.extend_from_slice(body);
+        png.extend_from_slice(&[0; 4]);
+    };
+    let mut raw = Vec::new();
+    let mut previous = vec![0u8; 32 * 4];
+    for y in 0..32 {
+        let row: Vec<u8> = (0..32)
+            .flat_map(|x| {
+                let value = if white_left(x, 32) { 0xFF } else { 0 };
+                [value, value, value, 0xFF]
+            })
+            .collect();
+        // Alternate between no filter and the up filter
+        if y % 2 == 0 {
+            raw.push(0);
+            raw.extend_from_slice(&row);
+        } else {
+            raw.push(2);
+            raw.extend(row.iter().zip(&previous).map(|(a, b)| a.wrapping_sub(*b)));
+        }
+        previous = row;
+    }
+    let mut header = Vec::new();
+    header.extend_from_slice(&32u32.to_be_bytes());
+    header.extend_from_slice(&32u32.to_be_bytes());
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+    let mut png = b"\x89PNG\r\n\x1A\n".to_vec();
+    chunk(&mut png, b"IHDR", &header);
+    chunk(
+        &mut png,
+        b"IDAT",
+        &miniz_oxide::deflate::compress_to_vec_zlib(&raw, 6),
+    );
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+#[cfg(feature = "icon-png")]
+#[test]
+fn png_icons_hash_like_bitmaps() {
+    let png = png();
+    let groups = group(&[(0, 32, 1)]);
+    let pe = image(&[
+        (ResourceKey::Id(RT_ICON), ResourceKey::Id(1), &png),
+        (ResourceKey::Id(RT_GROUP_ICON), ResourceKey::Id(1), &groups),
+    ]);
+    let icon = pe.primary_icon().unwrap().unwrap();
+    assert_eq!(icon.format(), IconFormat::Png);
+    assert_eq!(icon.width(), 256);
+    assert_eq!(
+        icon.hash().unwrap(),
+        IconHash::new(0xF0F0_F0F0_F0F0_F0F0, 0x1818_1818_1818_1818)
+    );
+}
+
+#[cfg(not(feature = "icon-png"))]
+#[test]
+fn png_icons_need_the_feature() {
+    let png = b"\x89PNG\r\n\x1A\n\0\0\0\0IEND".to_vec();
+    let groups = group(&[(0, 32, 1)]);
+    let pe = image(&[
+        (ResourceKey::Id(RT_ICON), ResourceKey::Id(1), &png),
+        (ResourceKey::Id(RT_GROUP_ICON), ResourceKey::Id(1), &groups),
+    ]);
+    let error = pe.icon_hash().unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+}
+
+#[test]
+fn hashes_round_trip_through_text() {
+    let hash = IconHash::new(0xF0F0_F0F0_F0F0_F0F0, 0x0018_1818_1818_1818);
+    assert_eq!(hash.to_string(), "f0f0f0f0f0f0f0f0:0018181818181818");
+    assert_eq!(IconHash::parse(&hash.to_string()).unwrap(), hash);
+    assert!(IconHash::parse("f0f0").is_err());
+}
+
+#[test]
+fn files_without_icons_have_no_hash() {
+    let pe = PortExe::from_bytes(fixtures::PE32_EXE.to_vec()).unwrap();
+    assert!(pe.primary_icon().unwrap().is_none());
+    assert!(pe.icon_hash().unwrap().is_none());
+}
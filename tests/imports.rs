@@ -0,0 +1,177 @@
+use pexp::import::{ImportWarning, MAX_IMPORTS, MAX_IMPORTS_PER_DLL, MAX_IMPORT_DESCRIPTORS};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+const IMPORT: usize = 1;
+/// RVA of the `.idata` section every image below places its import table in
+const IDATA: u32 = 0x2000;
+
+fn put(section: &mut [u8], offset: usize, value: &[u8]) {
+    section[offset..offset + value.len()].copy_from_slice(value);
+}
+
+/// Import descriptor of the DLL named at `name` whose lookup and address tables are
+/// both at `thunks`, as offsets into `.idata`
+fn descriptor(section: &mut [u8], offset: usize, name: u32, thunks: u32) {
+    put(section, offset, &(IDATA + thunks).to_le_bytes());
+    put(section, offset + 12, &(IDATA + name).to_le_bytes());
+    put(section, offset + 16, &(IDATA + thunks).to_le_bytes());
+}
+
+/// Image whose `.idata` section is `section`, the last one in the file, with the
+/// import directory at `directory` within it
+fn image(fixture: PeFixture, section: &[u8], directory: u32) -> PortExe {
+    fixture
+        .section(".text", 0x6000_0020, &[0xC3])
+        .section(".idata", 0xC000_0040, section)
+        .directory(IMPORT, IDATA + directory, 40)
+        .parse()
+        .unwrap()
+}
+
+#[test]
+fn bad_names_are_reported_and_the_rest_is_kept() {
+    let mut section = vec![0u8; 0x200];
+    descriptor(&mut section, 0, 0x180, 0x100);
+    descriptor(&mut section, 20, 0x190, 0x140);
+    for (index, thunk) in [IDATA + 0x1A0, 0x5000, 0x8000_0017].iter().enumerate() {
+        put(&mut section, 0x100 + index * 4, &thunk.to_le_bytes());
+    }
+    put(&mut section, 0x140, &(IDATA + 0x1F0).to_le_bytes());
+    put(&mut section, 0x180, b"KERNEL32.dll\0");
+    put(&mut section, 0x190, b"bad.dll\0");
+    put(&mut section, 0x1A2, b"Good\0");
+    // The last name runs into the end of the file
+    put(&mut section, 0x1F2, &[b'A'; 14]);
+
+    let pe = image(PeFixture::pe32(), &section, 0);
+    let table = pe.import_table().unwrap();
+    assert_eq!(
+        table.warnings(),
+        [
+            ImportWarning::NameOutsideImage { rva: 0x5000 },
+            ImportWarning::UnterminatedName { rva: IDATA + 0x1F2 },
+        ]
+    );
+    let descriptors = table.descriptors();
+    assert_eq!(descriptors.len(), 2);
+    assert_eq!(descriptors[0].dll_name(), "KERNEL32.dll");
+    let imports = descriptors[0].imports();
+    assert_eq!(imports.len(), 3);
+    assert_eq!(imports[0].name().unwrap().to_string(), "Good");
+    assert!(imports[1].name().is_none());
+    assert_eq!(imports[2].ordinal(), Some(23));
+    assert_eq!(
+        descriptors[1].imports()[0].name().unwrap().to_string(),
+        "A".repeat(14)
+    );
+    assert_eq!(pe.imports().unwrap().len(), 2);
+}
+
+#[test]
+fn tables_running_out_of_the_file_end_early() {
+    let mut section = vec![0u8; 0x200];
+    // The second descriptor would straddle the end of the section
+    descriptor(&mut section, 0x1E0, 0x180, 0x1F8);
+    put(&mut section, 0x180, b"KERNEL32.dll\0");
+    put(&mut section, 0x1A2, b"Good\0");
+    put(&mut section, 0x1F8, &(IDATA + 0x1A0).to_le_bytes());
+    put(&mut section, 0x1FC, &(IDATA + 0x1A0).to_le_bytes());
+
+    let table = image(PeFixture::pe32(), &section, 0x1E0)
+        .import_table()
+        .unwrap();
+    assert_eq!(
+        table.warnings(),
+        [
+            ImportWarning::ThunksOutsideImage {
+                dll: "KERNEL32.dll".to_string()
+            },
+            ImportWarning::DescriptorOutsideImage { index: 1 },
+        ]
+    );
+    assert_eq!(table.descriptors()[0].imports().len(), 2);
+}
+
+#[test]
+fn reserved_bits_of_pe32plus_name_thunks_are_ignored() {
+    let mut section = vec![0u8; 0x200];
+    descriptor(&mut section, 0, 0x180, 0x100);
+    let thunk = 0x0000_0001_0000_0000 | u64::from(IDATA + 0x1A0);
+    put(&mut section, 0x100, &thunk.to_le_bytes());
+    put(&mut section, 0x180, b"KERNEL32.dll\0");
+    put(&mut section, 0x1A2, b"Good\0");
+
+    let table = image(PeFixture::pe64(), &section, 0)
+        .import_table()
+        .unwrap();
+    assert_eq!(
+        table.warnings(),
+        [ImportWarning::InvalidThunk {
+            dll: "KERNEL32.dll".to_string(),
+            value: thunk
+        }]
+    );
+    let import = &table.descriptors()[0].imports()[0];
+    assert_eq!(import.name().unwrap().to_string(), "Good");
+}
+
+#[test]
+fn counts_are_bounded() {
+    // Five DLLs sharing one thunk array that is a single import too long
+    let thunks = 0x100;
+    let mut section = vec![0u8; thunks + (MAX_IMPORTS_PER_DLL as usize + 1) * 4];
+    for index in 0..5 {
+        descriptor(&mut section, index * 20, 0x80, thunks as u32);
+    }
+    put(&mut section, 0x80, b"ws2_32.dll\0");
+    for index in 0..=MAX_IMPORTS_PER_DLL as usize {
+        put(
+            &mut section,
+            thunks + index * 4,
+            &0x8000_0001u32.to_le_bytes(),
+        );
+    }
+    let table = image(PeFixture::pe32(), &section, 0)
+        .import_table()
+        .unwrap();
+    let too_many_thunks = ImportWarning::TooManyThunks {
+        dll: "ws2_32.dll".to_string(),
+    };
+    assert_eq!(
+        table.warnings(),
+        [
+            too_many_thunks.clone(),
+            too_many_thunks.clone(),
+            too_many_thunks.clone(),
+            too_many_thunks,
+            ImportWarning::TooManyImports,
+        ]
+    );
+    let imports: usize = table
+        .descriptors()
+        .iter()
+        .map(|descriptor| descriptor.imports().len())
+        .sum();
+    assert_eq!(imports, MAX_IMPORTS as usize);
+
+    // Descriptors of empty DLLs without a null descriptor after them
+    let descriptors = MAX_IMPORT_DESCRIPTORS as usize * 20;
+    let mut section = vec![0u8; descriptors + 0x20];
+    for index in 0..MAX_IMPORT_DESCRIPTORS as usize {
+        descriptor(
+            &mut section,
+            index * 20,
+            descriptors as u32 + 8,
+            descriptors as u32,
+        );
+    }
+    // The empty thunk array right after the descriptors is all the walk sees of the
+    // next one, with the name making it nonzero
+    put(&mut section, descriptors + 8, b"a.dll\0");
+    let table = image(PeFixture::pe32(), &section, 0)
+        .import_table()
+        .unwrap();
+    assert_eq!(table.warnings(), [ImportWarning::TooManyDescriptors]);
+    assert_eq!(table.descriptors().len(), MAX_IMPORT_DESCRIPTORS as usize);
+}
@@ -0,0 +1,173 @@
+mod common;
+
+use common::put32;
+use pexp::export::{build_export_table, ExportSpec, ExportTarget};
+use pexp::labels::{label_script, Label, LabelFormat, LabelKind};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+/// `.idata` to be mapped at RVA 0x1000, importing `Sleep` and ordinal 12 from
+/// `kernel32.dll` through an IAT at 0x1080, and `MessageBoxW` from `user32.dll`
+/// through an IAT at 0x1050, before it.
+fn idata() -> Vec<u8> {
+    let rva = 0x1000;
+    let mut idata = vec![0u8; 0x100];
+    for (descriptor, (lookup, name, iat)) in
+        [(0x60, 0xC0, 0x80), (0x70, 0xD0, 0x50)].iter().enumerate()
+    {
+        let offset = descriptor * 20;
+        put32(&mut idata, offset, rva + lookup);
+        put32(&mut idata, offset + 12, rva + name);
+        put32(&mut idata, offset + 16, rva + iat);
+    }
+    for table in [0x60, 0x80] {
+        put32(&mut idata, table, rva + 0xA0);
+        put32(&mut idata, table + 4, 0x8000_000C);
+    }
+    for table in [0x70, 0x50] {
+        put32(&mut idata, table, rva + 0xB0);
+    }
+    idata[0xA2..0xA7].copy_from_slice(b"Sleep");
+    idata[0xB2..0xBD].copy_from_slice(b"MessageBoxW");
+    idata[0xC0..0xCC].copy_from_slice(b"kernel32.dll");
+    idata[0xD0..0xDA].copy_from_slice(b"user32.dll");
+    idata
+}
+
+/// Image of `sections`, the first of them the [`idata`], with the data directories
+/// given as (index, rva, size) set.
+fn image_with(sections: &[(&str, u32, &[u8])], directories: &[(usize, u32, u32)]) -> PortExe {
+    let mut fixture = PeFixture::pe32().dll_characteristics(0);
+    for &(name, characteristics, data) in sections {
+        fixture = fixture.section(name, characteristics, data);
+    }
+    for &(index, rva, size) in [(1, 0x1000, 60)].iter().chain(directories) {
+        fixture = fixture.directory(index, rva, size);
+    }
+    fixture.parse().unwrap()
+}
+
+fn image() -> PortExe {
+    image_with(&[(".idata", 0xC000_0040, &idata())], &[])
+}
+
+/// Image with the imports of [`idata`], code at 0x2000, exports at 0x3000 and a TLS
+/// directory at 0x4000 whose callbacks are at 0x2010 and 0x2020.
+fn annotated_image() -> PortExe {
+    let specs = [
+        ExportSpec::new(Some("Run".to_string()), None, ExportTarget::Rva(0x2000)),
+        ExportSpec::new(Some("Value".to_string()), None, ExportTarget::Rva(0x10F0)),
+        ExportSpec::new(
+            Some("Fwd".to_string()),
+            None,
+            ExportTarget::Forwarder("kernel32.Sleep".to_string()),
+        ),
+        ExportSpec::new(None, Some(9), ExportTarget::Rva(0x2030)),
+    ];
+    let edata = build_export_table("app.exe", &specs, 0x3000).unwrap();
+    let edata_size = edata.len() as u32;
+    let mut tls = vec![0u8; 0x30];
+    for (index, value) in [(3, 0x40_4020), (8, 0x40_2010), (9, 0x40_2020)] {
+        put32(&mut tls, index * 4, value);
+    }
+    image_with(
+        &[
+            (".idata", 0xC000_0040, &idata()),
+            (".text", 0x6000_0020, &[0xC3; 0x40]),
+            (".edata", 0x4000_0040, &edata),
+            (".tls", 0xC000_0040, &tls),
+        ],
+        &[(0, 0x3000, edata_size), (9, 0x4000, 24)],
+    )
+}
+
+#[test]
+fn iat_slots_map_to_their_imports_by_rva() {
+    let labels: Vec<(u32, String)> = image()
+        .iat_map()
+        .unwrap()
+        .iter()
+        .map(|entry| (entry.rva(), entry.label().name().to_string()))
+        .collect();
+    assert_eq!(
+        labels,
+        [
+            (0x1050, "user32.dll!MessageBoxW".to_string()),
+            (0x1080, "kernel32.dll!Sleep".to_string()),
+            (0x1084, "kernel32.dll!#12".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn labels_render_as_debugger_scripts() {
+    let labels = [
+        Label::new(0x1050, "user32.dll!MessageBoxW"),
+        Label::new(0x1084, "kernel32.dll!#12"),
+    ];
+    assert_eq!(
+        label_script("app.exe", &labels, LabelFormat::X64dbg),
+        "// Labels for app.exe\n\
+         lblset app.exe:$1050, \"user32.dll!MessageBoxW\"\n\
+         lblset app.exe:$1084, \"kernel32.dll!#12\"\n"
+    );
+    assert_eq!(
+        label_script("app.exe", &labels, LabelFormat::Windbg),
+        "$$ Labels for app.exe\n\
+         aS /x user32_dll_MessageBoxW app+0x1050\n\
+         aS /x kernel32_dll__12 app+0x1084\n"
+    );
+}
+
+#[test]
+fn annotations_collect_sections_exports_iat_slots_and_tls_callbacks() {
+    let annotations = annotated_image().annotations().unwrap();
+    let sections: Vec<(&str, u32)> = annotations
+        .sections()
+        .iter()
+        .map(|section| (section.name(), section.rva()))
+        .collect();
+    assert_eq!(
+        sections,
+        [
+            (".idata", 0x1000),
+            (".text", 0x2000),
+            (".edata", 0x3000),
+            (".tls", 0x4000)
+        ]
+    );
+    let labels: Vec<(u32, &str, LabelKind)> = annotations
+        .labels()
+        .iter()
+        .map(|label| (label.rva(), label.name(), label.kind()))
+        .collect();
+    assert_eq!(
+        labels,
+        [
+            (0x2000, "Run", LabelKind::Function),
+            (0x10F0, "Value", LabelKind::Data),
+            (0x2030, "ordinal_9", LabelKind::Function),
+            (0x1050, "user32.dll!MessageBoxW", LabelKind::Data),
+            (0x1080, "kernel32.dll!Sleep", LabelKind::Data),
+            (0x1084, "kernel32.dll!#12", LabelKind::Data),
+            (0x2010, "tls_callback_0", LabelKind::Function),
+            (0x2020, "tls_callback_1", LabelKind::Function),
+        ]
+    );
+
+    let ghidra = annotations.script("app.exe", LabelFormat::Ghidra);
+    assert!(ghidra.starts_with("# Annotations for app.exe\n"));
+    assert!(ghidra.contains("sections = [\n    (0x1000, \".idata\"),\n"));
+    assert!(ghidra.contains("functions = [\n    (0x2000, \"Run\"),\n"));
+    assert!(ghidra.contains("createLabel(base.add(rva), name, True, SourceType.IMPORTED)"));
+    let ida = annotations.script("app.exe", LabelFormat::Ida);
+    assert!(ida.contains("labels = [\n    (0x10f0, \"Value\"),\n"));
+    assert!(ida.contains("ida_funcs.add_func(base + rva)"));
+}
+
+#[test]
+fn python_scripts_escape_names() {
+    let labels = [Label::new(0x10, "na\u{ef}ve \"name\"")];
+    let script = label_script("app.exe", &labels, LabelFormat::Ida);
+    assert!(script.contains("    (0x10, u\"na\\u00efve \\\"name\\\"\"),\n"));
+}
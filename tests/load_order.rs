@@ -0,0 +1,269 @@
+mod common;
+
+use common::scratch;
+use pexp::load_order::{BindingSource, LoadOrderWarning, SearchOrder};
+use pexp::loader::VirtualLoader;
+use pexp::testing::PeFixture;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// DLL importing `Init` from each of `imports`
+fn library(imports: &[&str]) -> Vec<u8> {
+    let mut fixture = PeFixture::pe32().dll().image_base(0x1000_0000).section(
+        ".text",
+        0x6000_0020,
+        &[0xC3; 0x10],
+    );
+    for dll in imports {
+        fixture = fixture.import(dll, "Init");
+    }
+    fixture.build()
+}
+
+/// Creates `directory` holding a DLL file for each of `names`
+fn directory(root: &Path, directory: &str, names: &[&str]) -> PathBuf {
+    let directory = root.join(directory);
+    fs::create_dir_all(&directory).unwrap();
+    for name in names {
+        fs::write(directory.join(name), library(&[])).unwrap();
+    }
+    directory
+}
+
+/// Loader holding `app.exe` importing `Init` from each of `imports`
+fn application(imports: &[&str]) -> VirtualLoader {
+    let mut fixture = PeFixture::pe32().section(".text", 0x6000_0020, &[0xC3]);
+    for dll in imports {
+        fixture = fixture.import(dll, "Init");
+    }
+    let mut loader = VirtualLoader::new();
+    loader
+        .load_at_preferred_base("app.exe", fixture.parse().unwrap())
+        .unwrap();
+    loader
+}
+
+#[test]
+fn dlls_bind_to_the_first_directory_of_the_search_order() {
+    let root = scratch("load-order-directories");
+    let mut search = SearchOrder::new(directory(&root, "app", &["Local.DLL"]));
+    search.system_dir = Some(directory(&root, "system32", &["user32.dll"]));
+    search.windows_dir = Some(directory(&root, "windows", &["shell.dll"]));
+    search.current_dir = Some(directory(&root, "cwd", &["cwd.dll"]));
+    search.path = vec![
+        root.join("absent"),
+        directory(&root, "bin", &["onpath.dll"]),
+    ];
+    let app = root.join("app");
+    let cwd = root.join("cwd");
+    let bin = root.join("bin");
+    assert_eq!(
+        search
+            .directories()
+            .iter()
+            .map(|(source, _)| *source)
+            .collect::<Vec<_>>(),
+        [
+            BindingSource::ApplicationDir,
+            BindingSource::SystemDir,
+            BindingSource::WindowsDir,
+            BindingSource::CurrentDir,
+            BindingSource::Path,
+            BindingSource::Path,
+        ]
+    );
+
+    let mut loader = application(&[
+        "LOCAL.dll",
+        "user32.dll",
+        "shell.dll",
+        "cwd",
+        "onpath.dll",
+        "gone.dll",
+        "api-ms-win-core-synch-l1-2-0.dll",
+    ]);
+    let order = loader.load_dependencies(&search).unwrap();
+    assert!(order.warnings().is_empty());
+    let bindings: Vec<_> = order
+        .bindings()
+        .iter()
+        .map(|binding| {
+            (
+                binding.name(),
+                binding.source(),
+                binding.planting_directories().to_vec(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        bindings,
+        [
+            (
+                "api-ms-win-core-synch-l1-2-0.dll",
+                Some(BindingSource::ApiSet),
+                vec![]
+            ),
+            ("cwd", Some(BindingSource::CurrentDir), vec![app.clone()]),
+            (
+                "gone.dll",
+                None,
+                vec![app.clone(), cwd.clone(), root.join("absent"), bin.clone()]
+            ),
+            ("local.dll", Some(BindingSource::ApplicationDir), vec![]),
+            (
+                "onpath.dll",
+                Some(BindingSource::Path),
+                vec![app.clone(), cwd, root.join("absent")]
+            ),
+            (
+                "shell.dll",
+                Some(BindingSource::WindowsDir),
+                vec![app.clone()]
+            ),
+            (
+                "user32.dll",
+                Some(BindingSource::SystemDir),
+                vec![app.clone()]
+            ),
+        ]
+    );
+    let bindings = order.bindings();
+    assert!(bindings
+        .iter()
+        .all(|binding| binding.importer() == "app.exe"));
+    assert_eq!(bindings[0].path(), None);
+    assert_eq!(bindings[1].path(), Some(root.join("cwd/cwd.dll").as_path()));
+    assert_eq!(bindings[3].path(), Some(app.join("Local.DLL").as_path()));
+    assert!(bindings[2].is_missing() && bindings[2].is_hijackable());
+    assert!(!bindings[3].is_missing() && !bindings[3].is_hijackable());
+    assert!(bindings[6].is_hijackable());
+
+    // Everything found was loaded, which leaves the missing DLL and the API set
+    assert!(loader.module("local").is_some() && loader.module("cwd").is_some());
+    assert_eq!(
+        loader.missing_dependencies(),
+        ["api-ms-win-core-synch-l1-2-0.dll", "gone.dll"]
+    );
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn safe_search_mode_moves_the_current_directory_after_the_system_directories() {
+    let root = scratch("load-order-safe-mode");
+    let mut search = SearchOrder::new(directory(&root, "app", &[]));
+    search.system_dir = Some(directory(&root, "system32", &["user32.dll"]));
+    search.current_dir = Some(directory(&root, "cwd", &["user32.dll"]));
+
+    let order = application(&["user32.dll"])
+        .load_dependencies(&search)
+        .unwrap();
+    let binding = &order.bindings()[0];
+    assert_eq!(binding.source(), Some(BindingSource::SystemDir));
+    assert_eq!(binding.planting_directories(), [root.join("app")]);
+
+    search.safe_dll_search_mode = false;
+    let order = application(&["user32.dll"])
+        .load_dependencies(&search)
+        .unwrap();
+    let binding = &order.bindings()[0];
+    assert_eq!(binding.source(), Some(BindingSource::CurrentDir));
+    assert_eq!(binding.path(), Some(root.join("cwd/user32.dll").as_path()));
+    assert_eq!(binding.planting_directories(), [root.join("app")]);
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn redirects_and_known_dlls_bypass_the_directory_search() {
+    let root = scratch("load-order-known");
+    let app = directory(&root, "app", &["kernel32.dll", "comctl32.dll"]);
+    let system = directory(&root, "system32", &["kernel32.dll"]);
+    let sxs = directory(&root, "sxs", &["comctl32.dll"]);
+    let mut search = SearchOrder::new(&app);
+    search.system_dir = Some(system.clone());
+    search.known_dlls = vec!["KERNEL32".to_string()];
+    search
+        .redirects
+        .insert("COMCTL32".to_string(), sxs.join("comctl32.dll"));
+
+    let imports = ["kernel32.dll", "comctl32.dll"];
+    let order = application(&imports).load_dependencies(&search).unwrap();
+    let bindings: Vec<_> = order
+        .bindings()
+        .iter()
+        .map(|binding| {
+            (
+                binding.name(),
+                binding.source(),
+                binding.path(),
+                binding.is_hijackable(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        bindings,
+        [
+            (
+                "comctl32.dll",
+                Some(BindingSource::Redirect),
+                Some(sxs.join("comctl32.dll").as_path()),
+                false
+            ),
+            (
+                "kernel32.dll",
+                Some(BindingSource::KnownDll),
+                Some(system.join("kernel32.dll").as_path()),
+                false
+            ),
+        ]
+    );
+
+    // Without a system directory a known DLL is still never searched for
+    search.system_dir = None;
+    let order = application(&imports).load_dependencies(&search).unwrap();
+    let kernel32 = &order.bindings()[1];
+    assert_eq!(kernel32.source(), Some(BindingSource::KnownDll));
+    assert_eq!(kernel32.path(), None);
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn dependencies_of_bound_dlls_are_followed() {
+    let root = scratch("load-order-transitive");
+    let app = directory(&root, "app", &["second.dll", "readme"]);
+    fs::write(app.join("first.dll"), library(&["second", "readme."])).unwrap();
+    fs::write(app.join("junk.dll"), b"not an image").unwrap();
+    let search = SearchOrder::new(&app);
+
+    let mut loader = application(&["first.dll", "junk.dll"]);
+    let order = loader.load_dependencies(&search).unwrap();
+    let bindings: Vec<_> = order
+        .bindings()
+        .iter()
+        .map(|binding| (binding.name(), binding.importer(), binding.path()))
+        .collect();
+    assert_eq!(
+        bindings,
+        [
+            (
+                "first.dll",
+                "app.exe",
+                Some(app.join("first.dll").as_path())
+            ),
+            ("junk.dll", "app.exe", Some(app.join("junk.dll").as_path())),
+            // A trailing dot asks for a file without an extension
+            ("readme.", "first.dll", Some(app.join("readme").as_path())),
+            (
+                "second",
+                "first.dll",
+                Some(app.join("second.dll").as_path())
+            ),
+        ]
+    );
+    match order.warnings() {
+        [LoadOrderWarning::Unloadable { path, .. }] => assert_eq!(path, &app.join("junk.dll")),
+        warnings => panic!("{:?}", warnings),
+    }
+    assert_eq!(loader.modules().len(), 4);
+    assert_eq!(loader.missing_dependencies(), ["junk.dll"]);
+    let _ = fs::remove_dir_all(&root);
+}
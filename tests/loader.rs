@@ -0,0 +1,129 @@
+use pexp::export::{build_export_table, ExportSpec, ExportTarget};
+use pexp::loader::{SymbolRef, VirtualLoader, ALLOCATION_GRANULARITY};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+use std::io::ErrorKind;
+
+const BASE: u64 = 0x1000_0000;
+
+/// DLL preferring [`BASE`] with code at RVA 0x1000, exporting `Open` at 0x1000, `Close`
+/// at 0x1010, ordinal 5 at 0x1020 without a name and `Broken` with a forwarder that
+/// names no export
+fn library(name: &str) -> PortExe {
+    let specs = [
+        ExportSpec::new(Some("Open".to_string()), None, ExportTarget::Rva(0x1000)),
+        ExportSpec::new(Some("Close".to_string()), None, ExportTarget::Rva(0x1010)),
+        ExportSpec::new(None, Some(5), ExportTarget::Rva(0x1020)),
+        ExportSpec::new(
+            Some("Broken".to_string()),
+            None,
+            ExportTarget::Forwarder("nodot".to_string()),
+        ),
+    ];
+    let table = build_export_table(name, &specs, 0x2000).unwrap();
+    PeFixture::pe32()
+        .dll()
+        .image_base(BASE)
+        .section(".text", 0x6000_0020, &[0xC3; 0x30])
+        .section(".edata", 0x4000_0040, &table)
+        .directory(0, 0x2000, table.len() as u32)
+        .parse()
+        .unwrap()
+}
+
+#[test]
+fn modules_are_laid_out_without_overlapping() {
+    let mut loader = VirtualLoader::new();
+    let size = loader
+        .load_at_preferred_base("user.dll", library("user.dll"))
+        .unwrap()
+        .size();
+    assert!(size > 0 && size < ALLOCATION_GRANULARITY);
+
+    // The preferred base is taken, so the next free aligned range is used
+    let gdi = loader
+        .load_at_preferred_base("gdi.dll", library("gdi.dll"))
+        .unwrap();
+    assert_eq!(gdi.base(), BASE + ALLOCATION_GRANULARITY);
+    assert_eq!(gdi.exports().len(), 4);
+
+    let error = loader
+        .load("shell.dll", library("shell.dll"), BASE + 0x1000)
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    let error = loader
+        .load("USER", library("user.dll"), 0x2000_0000)
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+    loader
+        .load("shell.dll", library("shell.dll"), BASE - size)
+        .unwrap();
+
+    let (module, rva) = loader
+        .module_at(BASE + ALLOCATION_GRANULARITY + 0x1010)
+        .unwrap();
+    assert_eq!((module.name(), rva), ("gdi.dll", 0x1010));
+    assert_eq!(loader.module_at(BASE - 1).unwrap().0.name(), "shell.dll");
+    assert!(loader.module_at(BASE + size).is_none());
+    assert_eq!(loader.modules().len(), 3);
+
+    let mut object = vec![0; 20];
+    object[..2].copy_from_slice(&0x8664u16.to_le_bytes());
+    let object = PortExe::from_bytes(object).unwrap();
+    assert!(loader.load("a.obj", object, 0x3000_0000).is_err());
+}
+
+#[test]
+fn imports_resolve_to_the_modules_laid_out() {
+    let app = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .import("USER.dll", "Open")
+        .import_ordinal("USER.dll", 5)
+        .import("user.dll", "Missing")
+        .import("kernel32.dll", "Sleep")
+        .import("GDI32.dll", "TextOutA")
+        .parse()
+        .unwrap();
+    let mut loader = VirtualLoader::new();
+    loader.load_at_preferred_base("app.exe", app).unwrap();
+    loader
+        .load_at_preferred_base("user.dll", library("user.dll"))
+        .unwrap();
+    assert_eq!(loader.missing_dependencies(), ["gdi32.dll", "kernel32.dll"]);
+
+    let app = loader.module("APP.EXE").unwrap();
+    assert_eq!(
+        app.dependencies(),
+        ["USER.dll", "user.dll", "kernel32.dll", "GDI32.dll"]
+    );
+    let descriptors = app.pe().imports().unwrap();
+    let user = &descriptors[0];
+    let user_base = loader.module("user").unwrap().base();
+    let open = loader
+        .resolve_import(user.dll_name(), &user.imports()[0])
+        .unwrap();
+    assert_eq!((open.module(), open.rva()), ("user.dll", 0x1000));
+    assert_eq!(open.address(), user_base + 0x1000);
+    assert!(open.forwarders().is_empty());
+    let by_ordinal = loader
+        .resolve_import(user.dll_name(), &user.imports()[1])
+        .unwrap();
+    assert_eq!(by_ordinal.rva(), 0x1020);
+
+    let missing = loader
+        .resolve_import(descriptors[1].dll_name(), &descriptors[1].imports()[0])
+        .unwrap_err();
+    assert_eq!(missing.kind(), ErrorKind::NotFound);
+    assert_eq!(missing.to_string(), "user.dll does not export Missing");
+    let error = loader
+        .resolve("kernel32.dll", &SymbolRef::Name("Sleep".into()))
+        .unwrap_err();
+    assert_eq!(error.to_string(), "kernel32.dll is not loaded");
+    let error = loader.resolve("user", &SymbolRef::Ordinal(9)).unwrap_err();
+    assert_eq!(error.to_string(), "user.dll does not export #9");
+    let error = loader
+        .resolve("user", &SymbolRef::Name("Broken".into()))
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    assert_eq!(error.to_string(), "malformed forwarder nodot");
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use common::{utf16z, version_node};
+use pexp::localization::LocalizedResources;
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+const RT_STRING: u16 = 6;
+const RT_VERSION: u16 = 16;
+const ENGLISH: u16 = 0x409;
+const GERMAN: u16 = 0x407;
+const FRENCH: u16 = 0x40C;
+
+/// `RT_STRING` table holding each `(index, text)`, the other strings empty
+fn string_table(strings: &[(usize, &str)]) -> Vec<u8> {
+    let mut table = Vec::new();
+    for index in 0..16 {
+        let text = strings
+            .iter()
+            .find(|(at, _)| *at == index)
+            .map_or("", |(_, text)| text);
+        let units: Vec<u16> = text.encode_utf16().collect();
+        table.extend_from_slice(&(units.len() as u16).to_le_bytes());
+        table.extend(units.iter().flat_map(|unit| unit.to_le_bytes()));
+    }
+    table
+}
+
+/// `VS_VERSIONINFO` with a string table in `language` holding `strings`
+fn version(language: u16, strings: &[(&str, &str)]) -> Vec<u8> {
+    let strings: Vec<Vec<u8>> = strings
+        .iter()
+        .map(|(key, value)| version_node(key, &utf16z(value), true, &[]))
+        .collect();
+    let table = version_node(&format!("{:04x}04b0", language), &[], true, &strings);
+    let string_info = version_node("StringFileInfo", &[], true, &[table]);
+    version_node("VS_VERSION_INFO", &[], false, &[string_info])
+}
+
+fn file(strings: &[(usize, &str)], language: u16, version_strings: &[(&str, &str)]) -> PortExe {
+    PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .resource(RT_STRING, 1, language, &string_table(strings))
+        .resource(RT_VERSION, 1, language, &version(language, version_strings))
+        .parse()
+        .unwrap()
+}
+
+/// English executable and its German and French MUI satellites
+fn resources() -> LocalizedResources {
+    let mut resources = LocalizedResources::new();
+    let files = [
+        file(
+            &[
+                (1, "Open"),
+                (2, "%d"),
+                (3, "Close"),
+                (5, "Help"),
+                (6, "Exit"),
+                (7, "%1!s!"),
+            ],
+            ENGLISH,
+            &[("CompanyName", "Contoso"), ("ProductName", "Widget")],
+        ),
+        file(
+            &[
+                (1, "Öffnen"),
+                (2, "%d"),
+                (3, "Close"),
+                (4, "Extra"),
+                (7, "%1!s!"),
+            ],
+            GERMAN,
+            &[("CompanyName", "Contoso")],
+        ),
+        file(&[(1, "Ouvrir")], FRENCH, &[]),
+    ];
+    for pe in &files {
+        resources.add(pe).unwrap();
+    }
+    resources
+}
+
+#[test]
+fn string_tables_are_numbered_by_block() {
+    let pe = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .resource(RT_STRING, 1, ENGLISH, &string_table(&[(1, "Open")]))
+        .resource(RT_STRING, 3, ENGLISH, &string_table(&[(0, "Thirty-two")]))
+        .resource(RT_STRING, 1, GERMAN, &string_table(&[(15, "Öffnen")]))
+        .parse()
+        .unwrap();
+    let strings: Vec<_> = pe
+        .string_resources()
+        .unwrap()
+        .iter()
+        .map(|string| (string.id(), string.language(), string.value().to_string()))
+        .collect();
+    assert!(strings.contains(&(1, ENGLISH, "Open".to_string())));
+    assert!(strings.contains(&(32, ENGLISH, "Thirty-two".to_string())));
+    assert!(strings.contains(&(15, GERMAN, "Öffnen".to_string())));
+    assert_eq!(strings.len(), 3);
+
+    let mut truncated = string_table(&[(1, "Open")]);
+    truncated.truncate(6);
+    let pe = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .resource(RT_STRING, 1, ENGLISH, &truncated)
+        .parse()
+        .unwrap();
+    assert!(pe.string_resources().is_err());
+}
+
+#[test]
+fn languages_are_audited_against_the_largest() {
+    let resources = resources();
+    assert_eq!(resources.languages(), [GERMAN, ENGLISH, FRENCH]);
+    assert_eq!(resources.string(GERMAN, 1), Some("Öffnen"));
+    assert_eq!(resources.string(FRENCH, 2), None);
+
+    let audit = resources.audit(None).unwrap();
+    assert_eq!(audit.reference(), ENGLISH);
+    let german = &audit.languages()[0];
+    assert_eq!(german.language(), GERMAN);
+    assert_eq!(german.strings(), 5);
+    assert_eq!(german.missing(), [5, 6]);
+    // Format strings without words are the same in every language
+    assert_eq!(german.untranslated(), [3]);
+    assert_eq!(german.extra(), [4]);
+    assert_eq!(german.missing_version_keys(), ["ProductName"]);
+    assert!(!german.is_complete());
+
+    let french = &audit.languages()[1];
+    assert_eq!(french.language(), FRENCH);
+    assert_eq!(french.missing(), [2, 3, 5, 6, 7]);
+    assert_eq!(
+        french.missing_version_keys(),
+        ["CompanyName", "ProductName"]
+    );
+}
+
+#[test]
+fn any_language_can_be_the_reference() {
+    let audit = resources().audit(Some(FRENCH)).unwrap();
+    assert_eq!(audit.reference(), FRENCH);
+    // Extra strings and version keys do not make a language incomplete
+    assert!(audit
+        .languages()
+        .iter()
+        .all(|language| language.is_complete()));
+    assert_eq!(audit.languages()[1].extra(), [2, 3, 5, 6, 7]);
+
+    assert!(LocalizedResources::new().audit(None).is_none());
+}
@@ -0,0 +1,235 @@
+mod common;
+
+use common::scratch;
+use pexp::manifest::{AssemblyLocation, Manifest, SideBySide};
+use pexp::resource::RT_MANIFEST;
+use pexp::testing::PeFixture;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+const MANIFEST: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<assembly xmlns="urn:schemas-microsoft-com:asm.v1" manifestVersion="1.0">
+  <assemblyIdentity type="win32" name="Contoso.App" version="1.2.0.0" processorArchitecture="x86"/>
+  <!-- <dependency><dependentAssembly><assemblyIdentity name="Commented.Out"/></dependentAssembly></dependency> -->
+  <asmv3:application xmlns:asmv3="urn:schemas-microsoft-com:asm.v3">
+    <asmv3:windowsSettings><dpiAware>true</dpiAware></asmv3:windowsSettings>
+  </asmv3:application>
+  <dependency>
+    <dependentAssembly>
+      <assemblyIdentity type="win32" name="Microsoft.Windows.Common-Controls" version="6.0.0.0"
+          processorArchitecture="x86" publicKeyToken="6595b64144ccf1df" language="*"/>
+    </dependentAssembly>
+  </dependency>
+  <dependency>
+    <dependentAssembly>
+      <assemblyIdentity type='win32' name='Microsoft.VC90.CRT' version='9.0.21022.8'
+          processorArchitecture='x86' publicKeyToken='1fc8b3b9a1e18e3b'/>
+    </dependentAssembly>
+  </dependency>
+  <dependency>
+    <dependentAssembly><assemblyIdentity name="Contoso.Widgets" version="2.0.0.0"/></dependentAssembly>
+  </dependency>
+  <dependency>
+    <dependentAssembly><assemblyIdentity name="Missing.Assembly" version="1.0.0.0"/></dependentAssembly>
+  </dependency>
+  <file name="app&amp;tools.dll"/>
+</assembly>
+"#;
+
+/// Newest build of the common controls in the store of
+/// [`dependent_assemblies_resolve_to_private_and_shared_directories`]
+const CONTROLS_POLICY: &str =
+    "x86_microsoft.windows.common-controls_6595b64144ccf1df_6.0.19041.1110_none_60b5254";
+
+/// Creates `directory` holding an empty file for each of `names`
+fn directory(parent: &Path, directory: &str, names: &[&str]) -> PathBuf {
+    let directory = parent.join(directory);
+    fs::create_dir_all(&directory).unwrap();
+    for name in names {
+        fs::write(directory.join(name), b"").unwrap();
+    }
+    directory
+}
+
+#[test]
+fn manifests_are_read_in_utf8_and_utf16() {
+    let manifest = Manifest::parse(MANIFEST.as_bytes()).unwrap();
+    let identity = manifest.identity().unwrap();
+    assert_eq!(
+        (identity.name(), identity.version(), identity.kind()),
+        ("Contoso.App", Some("1.2.0.0"), Some("win32"))
+    );
+    assert_eq!(identity.to_string(), "Contoso.App 1.2.0.0 x86");
+    let names: Vec<_> = manifest
+        .dependencies()
+        .iter()
+        .map(|dependency| dependency.name())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "Microsoft.Windows.Common-Controls",
+            "Microsoft.VC90.CRT",
+            "Contoso.Widgets",
+            "Missing.Assembly",
+        ]
+    );
+    let controls = &manifest.dependencies()[0];
+    assert_eq!(controls.public_key_token(), Some("6595b64144ccf1df"));
+    assert_eq!(controls.language(), Some("*"));
+    assert_eq!(
+        manifest.dependencies()[1].processor_architecture(),
+        Some("x86")
+    );
+    assert_eq!(manifest.dependencies()[2].public_key_token(), None);
+    assert_eq!(manifest.files(), ["app&tools.dll"]);
+
+    let mut utf16 = vec![0xFF, 0xFE];
+    utf16.extend(MANIFEST.encode_utf16().flat_map(u16::to_le_bytes));
+    assert_eq!(Manifest::parse(&utf16).unwrap(), manifest);
+    let mut utf8 = vec![0xEF, 0xBB, 0xBF];
+    utf8.extend_from_slice(MANIFEST.as_bytes());
+    assert_eq!(Manifest::parse(&utf8).unwrap(), manifest);
+
+    let error = Manifest::parse(b"<assembly><assemblyIdentity name=\"a>\"").unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    assert_eq!(error.to_string(), "unterminated manifest tag");
+}
+
+#[test]
+fn the_manifest_the_loader_reads_is_preferred() {
+    let other = b"<assembly><assemblyIdentity name=\"Other\"/></assembly>";
+    let pe = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .resource(RT_MANIFEST, 7, 0x409, other)
+        .resource(RT_MANIFEST, 1, 0x409, MANIFEST.as_bytes())
+        .parse()
+        .unwrap();
+    let manifest = pe.manifest().unwrap().unwrap();
+    assert_eq!(manifest.identity().unwrap().name(), "Contoso.App");
+
+    let pe = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .resource(RT_MANIFEST, 7, 0x409, other)
+        .parse()
+        .unwrap();
+    let manifest = pe.manifest().unwrap().unwrap();
+    assert_eq!(manifest.identity().unwrap().name(), "Other");
+
+    let pe = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .resource(16, 1, 0x409, &[0; 8])
+        .parse()
+        .unwrap();
+    assert_eq!(pe.manifest().unwrap(), None);
+}
+
+#[test]
+fn dependent_assemblies_resolve_to_private_and_shared_directories() {
+    let root = scratch("manifest-resolve");
+    let app = directory(&root, "app", &["app.exe"]);
+    let widgets = directory(&app, "Contoso.Widgets", &["Widgets.dll", "readme.txt"]);
+    let winsxs = root.join("winsxs");
+    for (name, files) in [
+        (
+            "x86_microsoft.windows.common-controls_6595b64144ccf1df_6.0.19041.1_none_a8625c18",
+            &["comctl32.dll"][..],
+        ),
+        (CONTROLS_POLICY, &["comctl32.dll"]),
+        // Another architecture, another language and another minor version
+        (
+            "amd64_microsoft.windows.common-controls_6595b64144ccf1df_6.0.0.0_none_de1d5a6c",
+            &["comctl32.dll"],
+        ),
+        (
+            "x86_microsoft.windows.common-controls_6595b64144ccf1df_6.0.0.0_en-us_1c3a0ae1",
+            &["comctl32.dll"],
+        ),
+        (
+            "x86_microsoft.windows.common-controls_6595b64144ccf1df_5.82.0.0_none_ec7c4e34",
+            &["comctl32.dll"],
+        ),
+        (
+            "x86_microsoft.vc90.crt_1fc8b3b9a1e18e3b_9.0.21022.8_none_bcb86ed6",
+            &["msvcr90.dll", "msvcp90.dll"],
+        ),
+        (
+            "x86_microsoft.vc90.crt_1fc8b3b9a1e18e3b_9.0.30729.9635_none_508ff82e",
+            &["msvcr90.dll", "msvcp90.dll"],
+        ),
+        ("not-a-component", &["missing.dll"]),
+    ] {
+        directory(&winsxs, name, files);
+    }
+
+    let manifest = Manifest::parse(MANIFEST.as_bytes()).unwrap();
+    let resolved = SideBySide::resolve(&manifest, &app, Some(&winsxs)).unwrap();
+    let assemblies: Vec<_> = resolved
+        .assemblies()
+        .iter()
+        .map(|assembly| {
+            (
+                assembly.identity().name(),
+                assembly.location(),
+                assembly
+                    .directory()
+                    .and_then(Path::file_name)
+                    .map(|name| name.to_string_lossy().into_owned()),
+            )
+        })
+        .collect();
+    assert_eq!(
+        assemblies,
+        [
+            (
+                "Microsoft.Windows.Common-Controls",
+                // No exact match, so the newest 6.0 build stands in
+                Some(AssemblyLocation::Policy),
+                Some(CONTROLS_POLICY.to_string()),
+            ),
+            (
+                "Microsoft.VC90.CRT",
+                Some(AssemblyLocation::Shared),
+                Some(
+                    "x86_microsoft.vc90.crt_1fc8b3b9a1e18e3b_9.0.21022.8_none_bcb86ed6".to_string()
+                ),
+            ),
+            (
+                "Contoso.Widgets",
+                Some(AssemblyLocation::Private),
+                Some("Contoso.Widgets".to_string()),
+            ),
+            ("Missing.Assembly", None, None),
+        ]
+    );
+    assert!(resolved.assemblies()[3].is_unresolved());
+    let unresolved: Vec<_> = resolved
+        .unresolved()
+        .map(|identity| identity.name())
+        .collect();
+    assert_eq!(unresolved, ["Missing.Assembly"]);
+
+    let crt = resolved.assemblies()[1].directory().unwrap();
+    let controls = resolved.assemblies()[0].directory().unwrap();
+    let redirects: Vec<_> = resolved
+        .redirects()
+        .iter()
+        .map(|(name, path)| (name.as_str(), path.clone()))
+        .collect();
+    assert_eq!(
+        redirects,
+        [
+            ("Widgets.dll", widgets.join("Widgets.dll")),
+            ("comctl32.dll", controls.join("comctl32.dll")),
+            ("msvcp90.dll", crt.join("msvcp90.dll")),
+            ("msvcr90.dll", crt.join("msvcr90.dll")),
+        ]
+    );
+
+    // Without a store only the private assembly is found
+    let resolved = SideBySide::resolve(&manifest, &app, None).unwrap();
+    assert_eq!(resolved.unresolved().count(), 3);
+    assert_eq!(resolved.redirects().len(), 1);
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,67 @@
+use pexp::messages::{Catalog, Message};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+use pexp::validation::{Finding, Severity, ValidationMode};
+
+const GERMAN: &str = "
+# Übersetzung der Befunde
+global-ptr-size = Das GlobalPtr-Verzeichnis hat die Größe {0} statt null
+mitigation-disabled = {0} ist nicht aktiviert
+";
+
+/// PE32 image whose GlobalPtr directory has a size
+fn image() -> PortExe {
+    let mut data = PeFixture::pe32()
+        .dll_characteristics(0)
+        .section(".data", 0xC000_0040, &[0; 0x20])
+        .build();
+    let entry = 0x80 + 4 + 20 + 96 + 8 * 8;
+    data[entry + 4..entry + 8].copy_from_slice(&8u32.to_le_bytes());
+    PortExe::from_bytes(data).unwrap()
+}
+
+#[test]
+fn findings_are_translated_through_the_catalog() {
+    let german = Catalog::parse("de", GERMAN).unwrap();
+    assert_eq!(german.locale(), "de");
+    let findings = image().validate(ValidationMode::Strict);
+    let finding = findings
+        .iter()
+        .find(|finding| finding.catalog_message().map(Message::key) == Some("global-ptr-size"))
+        .unwrap();
+    assert_eq!(
+        finding.message(),
+        "GlobalPtr directory has a Size of 0x8 instead of zero"
+    );
+    assert_eq!(
+        finding.localize(&german),
+        "Das GlobalPtr-Verzeichnis hat die Größe 0x8 statt null"
+    );
+    assert_eq!(finding.localize(&Catalog::english()), finding.message());
+
+    // Untranslated messages fall back to English, plain-text findings stay as they are
+    let message = Message::new("not-signed", Vec::new());
+    assert_eq!(german.format(&message), "file is not signed");
+    let plain = Finding::new(Severity::Info, "custom check");
+    assert_eq!(plain.localize(&german), "custom check");
+    assert!(german.missing().contains(&"not-signed"));
+    assert!(!german.missing().contains(&"mitigation-disabled"));
+    assert!(Catalog::english().missing().is_empty());
+}
+
+#[test]
+fn templates_fill_placeholders_in_any_order() {
+    let mut catalog = Catalog::new("x-test");
+    catalog.insert("pair", "{1} before {0}, {{literal}} and {2} left");
+    let message = Message::new("pair", vec!["a".to_string(), "b".to_string()]);
+    assert_eq!(
+        catalog.format(&message),
+        "b before a, {literal} and {2} left"
+    );
+    assert_eq!(
+        Catalog::english().format(&message),
+        "pair: a, b",
+        "unknown keys show their arguments"
+    );
+    assert!(Catalog::parse("de", "no separator").is_err());
+}
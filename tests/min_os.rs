@@ -0,0 +1,152 @@
+mod common;
+
+use common::DATA_DIRECTORIES;
+use pexp::min_os::{ApiVersionMap, WindowsVersion};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+/// Image declaring Windows Vista and importing `ExitProcess`,
+/// `GetSystemTimePreciseAsFileTime` and `CreatePseudoConsole` from `KERNEL32.dll`,
+/// `WaitOnAddress` from an API set and ordinal 5 from `USER32.dll`.
+fn image() -> PortExe {
+    let rva = 0x1000u32;
+    let mut idata = vec![0u8; 0x200];
+    let put = |idata: &mut Vec<u8>, offset: usize, value: u32| {
+        idata[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    };
+    let dlls: [(u32, u32, &[u32]); 3] = [
+        (0x60, 0x180, &[rva + 0x100, rva + 0x110, rva + 0x130]),
+        (0x80, 0x190, &[rva + 0x150]),
+        (0x90, 0x1C0, &[0x8000_0005]),
+    ];
+    for (index, (table, name, thunks)) in dlls.iter().enumerate() {
+        put(&mut idata, index * 20, rva + table);
+        put(&mut idata, index * 20 + 12, rva + name);
+        put(&mut idata, index * 20 + 16, rva + table);
+        for (slot, &thunk) in thunks.iter().enumerate() {
+            put(&mut idata, *table as usize + slot * 4, thunk);
+        }
+    }
+    let strings: [(usize, &[u8]); 7] = [
+        (0x102, b"ExitProcess"),
+        (0x112, b"GetSystemTimePreciseAsFileTime"),
+        (0x132, b"CreatePseudoConsole"),
+        (0x152, b"WaitOnAddress"),
+        (0x180, b"KERNEL32.dll"),
+        (0x190, b"api-ms-win-core-synch-l1-2-0.dll"),
+        (0x1C0, b"USER32.dll"),
+    ];
+    for (offset, string) in strings {
+        idata[offset..offset + string.len()].copy_from_slice(string);
+    }
+    let mut data = PeFixture::pe32()
+        .dll_characteristics(0)
+        .section(".idata", 0xC000_0040, &idata)
+        .build();
+    let entry = DATA_DIRECTORIES + 8;
+    data[entry..entry + 4].copy_from_slice(&rva.to_le_bytes());
+    data[entry + 4..entry + 8].copy_from_slice(&80u32.to_le_bytes());
+    PortExe::from_bytes(data).unwrap()
+}
+
+#[test]
+fn imports_newer_than_the_subsystem_version_block_it() {
+    let minimum = image()
+        .minimum_os(&ApiVersionMap::bundled())
+        .unwrap()
+        .unwrap();
+    assert_eq!(minimum.subsystem_version(), WindowsVersion::new(6, 0, 0));
+    assert_eq!(minimum.minimum(), WindowsVersion::new(10, 0, 17763));
+    assert_eq!(
+        minimum.minimum().to_string(),
+        "Windows 10 1809 (10.0.17763)"
+    );
+    assert!(minimum.is_understated());
+    let blocking: Vec<(String, &str)> = minimum
+        .blocking()
+        .iter()
+        .map(|import| (import.version().to_string(), import.function().unwrap()))
+        .collect();
+    assert_eq!(
+        blocking,
+        [
+            (
+                "Windows 10 1809 (10.0.17763)".to_string(),
+                "CreatePseudoConsole"
+            ),
+            (
+                "Windows 8 (6.2)".to_string(),
+                "GetSystemTimePreciseAsFileTime"
+            ),
+            ("Windows 7 (6.1)".to_string(), "WaitOnAddress"),
+        ]
+    );
+}
+
+#[test]
+fn api_version_lists_add_and_override_entries() {
+    let mut map = ApiVersionMap::bundled();
+    map.add_text("# local additions\nSYNCH.DLL * 6.2\nsynch WaitOnAddress 6.3\n")
+        .unwrap();
+    assert_eq!(
+        map.requirement("Synch.dll", Some("WaitOnAddress")),
+        Some(WindowsVersion::new(6, 3, 0))
+    );
+    // A function does not lower the version of its DLL
+    map.add_text("synch WakeByAddressAll 6.0").unwrap();
+    assert_eq!(
+        map.requirement("synch", Some("WakeByAddressAll")),
+        Some(WindowsVersion::new(6, 2, 0))
+    );
+    assert_eq!(map.requirement("kernel32", Some("ExitProcess")), None);
+    assert!(map.add_text("kernel32 Sleep").is_err());
+    assert!(map.add_text("kernel32 Sleep six").is_err());
+}
+
+#[test]
+fn delay_loaded_imports_do_not_raise_the_minimum() {
+    // Delay-load descriptor at RVA 0x1000 naming CreatePseudoConsole from KERNEL32.dll
+    let mut didat = vec![0u8; 0xA0];
+    for (offset, value) in [
+        (0x00, 1u32),
+        (0x04, 0x1040),
+        (0x0C, 0x1080),
+        (0x10, 0x1050),
+        (0x50, 0x1060),
+        (0x80, 0x40_2000),
+    ] {
+        didat[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    didat[0x40..0x4C].copy_from_slice(b"KERNEL32.dll");
+    didat[0x62..0x75].copy_from_slice(b"CreatePseudoConsole");
+    let mut data = PeFixture::pe32()
+        .dll_characteristics(0)
+        .section(".didat", 0xC000_0040, &didat)
+        .import("KERNEL32.dll", "ExitProcess")
+        .build();
+    let entry = DATA_DIRECTORIES + 13 * 8;
+    data[entry..entry + 4].copy_from_slice(&0x1000u32.to_le_bytes());
+    data[entry + 4..entry + 8].copy_from_slice(&0x40u32.to_le_bytes());
+
+    let minimum = PortExe::from_bytes(data)
+        .unwrap()
+        .minimum_os(&ApiVersionMap::bundled())
+        .unwrap()
+        .unwrap();
+    assert_eq!(minimum.minimum(), WindowsVersion::new(6, 0, 0));
+    assert!(minimum.blocking().is_empty());
+    assert!(!minimum.is_understated());
+    let delay_loaded: Vec<_> = minimum
+        .delay_loaded()
+        .iter()
+        .map(|import| (import.dll(), import.function(), import.version()))
+        .collect();
+    assert_eq!(
+        delay_loaded,
+        [(
+            "KERNEL32.dll",
+            Some("CreatePseudoConsole"),
+            WindowsVersion::new(10, 0, 17763)
+        )]
+    );
+}
@@ -0,0 +1,128 @@
+use pexp::mingw::{CrtList, IdataPiece, CODEVIEW_PDB70_SIGNATURE};
+use pexp::port_exe::PortExe;
+use pexp::symbol::IMAGE_SYM_CLASS_EXTERNAL;
+use pexp::testing::PeFixture;
+
+fn pointers(values: &[u32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+/// mingw-style image: constructors and C initializers in `.CRT` named by their start
+/// symbols, a build ID, a section with a long name and an import of `Sleep`. Without
+/// `symbols` the string table is kept behind an empty symbol table, as `strip` leaves it.
+fn image_with(symbols: bool) -> PortExe {
+    let crt = pointers(&[0, 0x0040_1000, 0x0040_1010, 0, 0, 0x0040_1020, 0]);
+    let mut build_id = CODEVIEW_PDB70_SIGNATURE.to_le_bytes().to_vec();
+    build_id.extend(1..=16u8);
+    build_id.extend_from_slice(&3u32.to_le_bytes());
+    build_id.push(0);
+    let mut data = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3; 0x30])
+        .section(".CRT", 0xC000_0040, &crt)
+        .section(".buildid", 0x4000_0040, &build_id)
+        .section("/4", 0x4200_0040, &[0; 0x10])
+        .import("KERNEL32.dll", "Sleep")
+        .build();
+
+    let table = data.len() as u32;
+    data[0x8C..0x90].copy_from_slice(&table.to_le_bytes());
+    let names: &[(&str, u32)] = if symbols {
+        &[("__xc_a", 0), ("___xi_a", 16)]
+    } else {
+        &[]
+    };
+    data[0x90..0x94].copy_from_slice(&(names.len() as u32).to_le_bytes());
+    for &(name, value) in names {
+        let mut symbol = [0u8; 18];
+        symbol[..name.len()].copy_from_slice(name.as_bytes());
+        symbol[8..12].copy_from_slice(&value.to_le_bytes());
+        symbol[12..14].copy_from_slice(&2i16.to_le_bytes());
+        symbol[16] = IMAGE_SYM_CLASS_EXTERNAL;
+        data.extend_from_slice(&symbol);
+    }
+    let strings = b".gnu_debuglink\0";
+    data.extend_from_slice(&(4 + strings.len() as u32).to_le_bytes());
+    data.extend_from_slice(strings);
+    PortExe::from_bytes(data).unwrap()
+}
+
+fn image() -> PortExe {
+    image_with(true)
+}
+
+#[test]
+fn crt_lists_are_split_and_named() {
+    let sections = image().mingw_sections().unwrap();
+    let lists: Vec<_> = sections
+        .crt()
+        .iter()
+        .map(|list| (list.kind(), list.rva(), list.entries().to_vec()))
+        .collect();
+    assert_eq!(
+        lists,
+        [
+            (
+                Some(CrtList::CxxInitializers),
+                0x2004,
+                vec![0x0040_1000, 0x0040_1010]
+            ),
+            (Some(CrtList::CInitializers), 0x2014, vec![0x0040_1020]),
+        ]
+    );
+    assert!(sections.tls_callbacks().is_empty());
+
+    // Without symbols the lists are still found, only unnamed
+    let stripped = image_with(false).mingw_sections().unwrap();
+    assert_eq!(stripped.crt().len(), 2);
+    assert!(stripped.crt().iter().all(|list| list.kind().is_none()));
+}
+
+#[test]
+fn build_ids_and_long_section_names_are_read() {
+    let pe = image();
+    let build_id = pe.mingw_sections().unwrap().build_id().cloned().unwrap();
+    let mut id = [0; 16];
+    for (byte, value) in id.iter_mut().zip(1..) {
+        *byte = value;
+    }
+    assert_eq!(build_id.id(), id);
+    assert_eq!(build_id.age(), 3);
+    assert!(build_id.pdb_path().is_empty());
+
+    let section = &pe.section_headers()[3];
+    assert_eq!(section.name().into_value(), "/4");
+    assert_eq!(pe.section_name(section), ".gnu_debuglink");
+    assert_eq!(pe.section_name(&pe.section_headers()[1]), ".CRT");
+
+    // Stripped images keep the string table behind an empty symbol table
+    let stripped = image_with(false);
+    assert_eq!(
+        stripped.section_name(&stripped.section_headers()[3]),
+        ".gnu_debuglink"
+    );
+}
+
+#[test]
+fn import_data_is_split_into_its_pieces() {
+    let pe = image();
+    let sections = pe.mingw_sections().unwrap();
+    let spans: Vec<_> = sections
+        .idata()
+        .iter()
+        .map(|span| (span.piece(), span.rva().len()))
+        .collect();
+    assert!(spans.contains(&(IdataPiece::Descriptors, 40)));
+    assert!(spans.contains(&(IdataPiece::AddressTables, 8)));
+    assert!(spans.contains(&(IdataPiece::HintNames, "Sleep".len() + 3)));
+    assert!(spans.contains(&(IdataPiece::DllNames, "KERNEL32.dll".len() + 1)));
+    assert_eq!(IdataPiece::HintNames.grouped_section(), ".idata$6");
+
+    let plain = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .parse()
+        .unwrap();
+    assert!(plain.mingw_sections().unwrap().is_empty());
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use common::fixtures;
+use pexp::minidump::{DumpModuleWarning, Minidump};
+use pexp::port_exe::PortExe;
+
+const DLL_BASE: u64 = 0x1000_0000;
+const EXE_BASE: u64 = 0x40_0000;
+
+/// Lays the sections of `data` out at their virtual addresses, as the loader maps them.
+fn map(data: &[u8]) -> Vec<u8> {
+    let pe = PortExe::from_bytes(data.to_vec()).unwrap();
+    let header = pe.optional_header().unwrap();
+    let mut image = vec![0u8; header.size_of_image() as usize];
+    let headers = header.size_of_headers() as usize;
+    image[..headers].copy_from_slice(&data[..headers]);
+    for section in pe.section_headers() {
+        let raw = section.pointer_to_raw_data().into_value() as usize;
+        let size = section.size_of_raw_data().into_value() as usize;
+        let address = section.virtual_address().into_value() as usize;
+        image[address..address + size].copy_from_slice(&data[raw..raw + size]);
+    }
+    image
+}
+
+/// Minidump listing `modules` as (name, base, size, timestamp) and capturing `ranges`
+/// in a memory list.
+fn minidump(modules: &[(&str, u64, u32, u32)], ranges: &[(u64, &[u8])]) -> Vec<u8> {
+    let mut data = vec![0u8; 32 + 2 * 12];
+    data[..4].copy_from_slice(b"MDMP");
+    data[4..8].copy_from_slice(&0xA793u32.to_le_bytes());
+    data[8..12].copy_from_slice(&2u32.to_le_bytes());
+    data[12..16].copy_from_slice(&32u32.to_le_bytes());
+
+    let mut names = Vec::new();
+    for (name, ..) in modules {
+        names.push(data.len() as u32);
+        let units: Vec<u8> = name.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        data.extend_from_slice(&(units.len() as u32).to_le_bytes());
+        data.extend_from_slice(&units);
+        data.extend_from_slice(&[0, 0]);
+    }
+    let mut module_list = (modules.len() as u32).to_le_bytes().to_vec();
+    for ((_, base, size, time_date_stamp), name) in modules.iter().zip(&names) {
+        let mut entry = vec![0u8; 108];
+        entry[..8].copy_from_slice(&base.to_le_bytes());
+        entry[8..12].copy_from_slice(&size.to_le_bytes());
+        entry[16..20].copy_from_slice(&time_date_stamp.to_le_bytes());
+        entry[20..24].copy_from_slice(&name.to_le_bytes());
+        module_list.extend_from_slice(&entry);
+    }
+    let mut memory = Vec::new();
+    let mut memory_list = (ranges.len() as u32).to_le_bytes().to_vec();
+    let memory_start = data.len() + module_list.len() + 4 + 16 * ranges.len();
+    for (address, bytes) in ranges {
+        memory_list.extend_from_slice(&address.to_le_bytes());
+        memory_list.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        memory_list.extend_from_slice(&((memory_start + memory.len()) as u32).to_le_bytes());
+        memory.extend_from_slice(bytes);
+    }
+
+    for (index, (stream_type, stream)) in
+        [(4u32, &module_list), (5, &memory_list)].iter().enumerate()
+    {
+        let entry = 32 + index * 12;
+        let offset = data.len() as u32;
+        data[entry..entry + 4].copy_from_slice(&stream_type.to_le_bytes());
+        data[entry + 4..entry + 8].copy_from_slice(&(stream.len() as u32).to_le_bytes());
+        data[entry + 8..entry + 12].copy_from_slice(&offset.to_le_bytes());
+        data.extend_from_slice(stream);
+    }
+    data.extend_from_slice(&memory);
+    data
+}
+
+#[test]
+fn modules_are_rebuilt_from_captured_memory() {
+    let dll = map(fixtures::PE32_DLL);
+    let exe = map(fixtures::PE32_EXE);
+    let dll_stamp = PortExe::from_bytes(fixtures::PE32_DLL.to_vec())
+        .unwrap()
+        .file_header()
+        .time_date_stamp()
+        .into_value();
+    let data = minidump(
+        &[
+            ("C:\\app\\math.dll", DLL_BASE, dll.len() as u32, dll_stamp),
+            ("C:\\app\\app.exe", EXE_BASE, exe.len() as u32, 0x1234_5678),
+        ],
+        // The DLL in two ranges; only the headers page of the executable
+        &[
+            (DLL_BASE + 0x1000, &dll[0x1000..]),
+            (DLL_BASE, &dll[..0x1000]),
+            (EXE_BASE, &exe[..0x1000]),
+        ],
+    );
+    let dump = Minidump::parse(data).unwrap();
+    let names: Vec<&str> = dump.modules().iter().map(|module| module.name()).collect();
+    assert_eq!(names, ["C:\\app\\math.dll", "C:\\app\\app.exe"]);
+
+    let reports = dump.module_reports();
+    assert_eq!(reports[0].captured(), dll.len() as u64);
+    assert!(reports[0].warnings().is_empty());
+    let pe = reports[0].image().unwrap();
+    assert!(pe.layout().is_mapped());
+    let exports: Vec<String> = pe
+        .exports()
+        .unwrap()
+        .iter()
+        .filter_map(|export| export.name().map(|name| name.to_string()))
+        .collect();
+    assert_eq!(exports, ["Add", "Sub"]);
+
+    assert_eq!(
+        reports[1].warnings(),
+        [
+            DumpModuleWarning::Incomplete {
+                captured: 0x1000,
+                size: exe.len() as u32
+            },
+            DumpModuleWarning::TimestampMismatch {
+                listed: 0x1234_5678,
+                header: 0x5F00_0000
+            },
+        ]
+    );
+}
+
+#[test]
+fn truncated_dumps_are_rejected() {
+    let data = minidump(&[("a.dll", DLL_BASE, 0x1000, 0)], &[]);
+    for length in [0, 16, data.len() - 1] {
+        assert!(Minidump::parse(data[..length].to_vec()).is_err());
+    }
+    assert!(Minidump::parse(fixtures::PE32_EXE.to_vec()).is_err());
+}
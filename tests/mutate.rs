@@ -0,0 +1,69 @@
+mod common;
+
+use common::{exercise, fixtures};
+use pexp::testing::{mutate, mutation_targets, Corruption, PeFixture};
+use std::panic;
+
+/// Runs every analysis over each mutation of `data`, returning those that panicked.
+fn panicking_mutations(data: &[u8]) -> Vec<String> {
+    mutate(data)
+        .unwrap()
+        .filter(|mutation| panic::catch_unwind(|| exercise(mutation.data())).is_err())
+        .map(|mutation| mutation.to_string())
+        .collect()
+}
+
+#[test]
+fn mutations_cover_every_header_field_once() {
+    let fixture = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .import("KERNEL32.dll", "ExitProcess")
+        .build();
+    let targets = mutation_targets(&fixture).unwrap();
+    // 3 DOS and signature fields, 7 file header, 30 optional header, 16 directories with
+    // two fields each and 10 fields in each of the .text and .idata section headers
+    assert_eq!(targets.len(), 3 + 7 + 30 + 32 + 20);
+    assert!(targets
+        .windows(2)
+        .all(|pair| pair[0].offset() + pair[0].size() <= pair[1].offset()));
+    assert!(targets
+        .iter()
+        .any(|target| target.field() == "Data directory 1: Size"));
+
+    let mutations: Vec<_> = mutate(&fixture).unwrap().collect();
+    for mutation in &mutations {
+        let target = mutation.target();
+        let changed: Vec<usize> = (0..fixture.len())
+            .filter(|&offset| mutation.data()[offset] != fixture[offset])
+            .collect();
+        assert!(!changed.is_empty(), "{}", mutation);
+        assert!(
+            changed.iter().all(
+                |&offset| offset >= target.offset() && offset < target.offset() + target.size()
+            ),
+            "{}",
+            mutation
+        );
+    }
+    // Zero leaves the fields that are already zero alone
+    assert!(mutations.len() < targets.len() * 5);
+    assert!(!mutations.iter().any(|mutation| {
+        mutation.target().field() == "File header: Pointer to symbol table"
+            && mutation.corruption() == Corruption::Zero
+    }));
+}
+
+#[test]
+fn mutated_fixtures_do_not_panic() {
+    let mut failures = Vec::new();
+    for data in [
+        fixtures::PE32_EXE,
+        fixtures::PE32PLUS_EXE,
+        fixtures::PE32_DLL,
+        fixtures::PE32PLUS_DRIVER,
+        fixtures::AMD64_OBJECT,
+    ] {
+        failures.extend(panicking_mutations(data));
+    }
+    assert!(failures.is_empty(), "panicked on {:#?}", failures);
+}
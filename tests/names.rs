@@ -0,0 +1,89 @@
+mod common;
+
+use common::{resource_section, ResourceKey};
+use pexp::pe_string::PeString;
+use pexp::port_exe::PortExe;
+use pexp::resource::ResourceId;
+use pexp::testing::PeFixture;
+
+/// File offset of the first section header of a PE32 image laid out by [`PeFixture`]
+const SECTION_TABLE: usize = 0x98 + 0xE0;
+
+fn find(data: &[u8], needle: &[u8]) -> usize {
+    data.windows(needle.len())
+        .position(|window| window == needle)
+        .unwrap()
+}
+
+#[test]
+fn byte_and_utf16_strings_compare_as_stored() {
+    let bytes = PeString::from_bytes(&b"caf\xE9"[..]);
+    assert_eq!(bytes.as_bytes(), b"caf\xE9");
+    assert!(!bytes.is_utf16());
+    assert_eq!(bytes.len(), 4);
+    assert_eq!(bytes.as_str(), None);
+    assert_eq!(bytes.to_string_lossy(), "caf\u{FFFD}");
+    // The Latin-1 byte is not the UTF-8 encoding of the same letter
+    assert!(bytes != "café");
+    assert_eq!(PeString::from("café"), "café");
+
+    let units = PeString::from_utf16(&[0x4B, 0xD800, 0x61]);
+    assert!(units.is_utf16());
+    assert_eq!(units.as_bytes(), [0x4B, 0, 0x00, 0xD8, 0x61, 0]);
+    assert_eq!(units.as_str(), None);
+    assert_eq!(units.to_string(), "K\u{FFFD}a");
+    assert!(units != "K\u{FFFD}a");
+    let text = PeString::from_utf16(&[0x4B, 0x69, 0x74]);
+    assert_eq!(text, "Kit");
+    assert!(text.eq_ignore_ascii_case("KIT"));
+    assert!(!text.eq_ignore_ascii_case("Kite"));
+    // The same text stored as bytes and as UTF-16 is not the same name
+    assert_ne!(text, PeString::from("Kit"));
+    assert!(PeString::default().is_empty());
+}
+
+#[test]
+fn names_that_are_not_utf8_are_kept_raw() {
+    let mut data = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .import("KERNEL32.dll", "Sleep")
+        .build();
+    data[SECTION_TABLE..SECTION_TABLE + 8].copy_from_slice(b".t\xE9xt\0\0\0");
+    let name = find(&data, b"Sleep\0");
+    data[name] = 0xA7;
+
+    let pe = PortExe::from_bytes(data).unwrap();
+    let section = pe.section_headers()[0].name().into_value();
+    assert_eq!(section.as_bytes(), b".t\xE9xt");
+    assert_eq!(section.to_string(), ".t\u{FFFD}xt");
+    let imports = pe.imports().unwrap();
+    let import = imports[0].imports()[0].name().unwrap();
+    assert_eq!(import.as_bytes(), b"\xA7leep");
+    assert!(import != "\u{FFFD}leep");
+    assert_eq!(imports[0].dll_name(), "KERNEL32.dll");
+}
+
+#[test]
+fn resource_names_keep_unpaired_surrogates() {
+    let rsrc = resource_section(
+        0x2000,
+        &[(ResourceKey::Id(10), ResourceKey::Name("AB"), b"data")],
+    );
+    let mut data = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .section(".rsrc", 0x4000_0040, &rsrc)
+        .directory(2, 0x2000, rsrc.len() as u32)
+        .build();
+    let name = find(&data, &[2, 0, b'A', 0, b'B', 0]);
+    data[name + 4..name + 6].copy_from_slice(&0xDC00u16.to_le_bytes());
+
+    let pe = PortExe::from_bytes(data).unwrap();
+    let resources = pe.resources().unwrap();
+    match resources[0].name_id() {
+        ResourceId::Name(name) => {
+            assert_eq!(*name, PeString::from_utf16(&[0x41, 0xDC00]));
+            assert_eq!(name.to_string(), "A\u{FFFD}");
+        }
+        other => panic!("{:?}", other),
+    }
+}
@@ -0,0 +1,198 @@
+mod common;
+
+use common::coff::{file_name, function, object, section_definition, CHECKSUM, COMDAT_TEXT, TEXT};
+use pexp::diff::diff_objects;
+use pexp::symbol::{IMAGE_SYM_CLASS_EXTERNAL, IMAGE_SYM_CLASS_FILE, IMAGE_SYM_CLASS_STATIC};
+
+#[test]
+fn symbol_records_and_long_names_are_decoded() {
+    let pe = object(
+        &[
+            (".text$mn", TEXT, &[0; 0x10], 2),
+            (".debug$Symbols", 0x4210_0040, &[0; 8], 0),
+        ],
+        &[
+            (
+                ".file",
+                0,
+                -2,
+                0,
+                IMAGE_SYM_CLASS_FILE,
+                file_name("widget.c"),
+            ),
+            (
+                ".text$mn",
+                0,
+                1,
+                0,
+                IMAGE_SYM_CLASS_STATIC,
+                section_definition(0x10, 0, 0),
+            ),
+            function("main", 0, 1),
+            function("a_rather_long_function_name", 8, 1),
+            ("__imp_Sleep", 0, 0, 0, IMAGE_SYM_CLASS_EXTERNAL, Vec::new()),
+        ],
+    );
+    assert_eq!(
+        pe.section_name(&pe.section_headers()[0]).to_string(),
+        ".text$mn"
+    );
+    assert_eq!(
+        pe.section_name(&pe.section_headers()[1]).to_string(),
+        ".debug$Symbols"
+    );
+    assert_eq!(pe.relocation_count(&pe.section_headers()[0]), 2);
+
+    let symbols = pe.coff_symbols().unwrap();
+    let summary: Vec<_> = symbols
+        .iter()
+        .map(|symbol| {
+            (
+                symbol.index(),
+                symbol.name(),
+                symbol.number_of_aux_symbols(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (0, ".file", 1),
+            (2, ".text$mn", 1),
+            (4, "main", 0),
+            (5, "a_rather_long_function_name", 0),
+            (6, "__imp_Sleep", 0),
+        ]
+    );
+    assert_eq!(&symbols[0].aux_data()[..8], b"widget.c");
+    let definition = symbols[1].section_definition().unwrap();
+    assert_eq!(
+        (definition.length(), definition.checksum()),
+        (0x10, CHECKSUM)
+    );
+    assert!(symbols[2].section_definition().is_none());
+    assert!(symbols[3].is_function() && symbols[3].is_external() && symbols[3].is_defined());
+    assert!(!symbols[4].is_defined() && !symbols[4].is_function());
+}
+
+#[test]
+fn relocation_counts_past_the_header_field_are_read_from_the_first_relocation() {
+    let pe = object(
+        &[
+            (".text", TEXT, &[0; 0x10], 70_000),
+            (".data", 0xC050_0040, &[0; 8], 0xFFFE),
+        ],
+        &[],
+    );
+    assert_eq!(pe.relocation_count(&pe.section_headers()[0]), 70_000);
+    assert_eq!(pe.relocation_count(&pe.section_headers()[1]), 0xFFFE);
+}
+
+#[test]
+fn objects_are_compared_by_section_and_by_symbol() {
+    let before = object(
+        &[
+            (".text", TEXT, &[0; 0x10], 1),
+            (".text$mn", COMDAT_TEXT, &[0; 4], 0),
+            (".text$mn", COMDAT_TEXT, &[0; 8], 0),
+        ],
+        &[
+            function("f", 0, 1),
+            function("g", 8, 1),
+            (
+                ".text$mn",
+                0,
+                2,
+                0,
+                IMAGE_SYM_CLASS_STATIC,
+                section_definition(4, 0, 2),
+            ),
+            function("inline_a", 0, 2),
+            (
+                ".text$mn",
+                0,
+                3,
+                0,
+                IMAGE_SYM_CLASS_STATIC,
+                section_definition(8, 0, 2),
+            ),
+            function("inline_b", 0, 3),
+        ],
+    );
+    // Same code, with `inline_a` dropped, `inline_b` grown and `inline_c` added
+    let after = object(
+        &[
+            (".text", TEXT, &[0; 0x18], 3),
+            (".text$mn", COMDAT_TEXT, &[0; 4], 0),
+            (".text$mn", COMDAT_TEXT, &[0; 12], 0),
+        ],
+        &[
+            function("f", 0, 1),
+            function("g", 8, 1),
+            function("h", 0x10, 1),
+            (
+                ".text$mn",
+                0,
+                2,
+                0,
+                IMAGE_SYM_CLASS_STATIC,
+                section_definition(4, 0, 2),
+            ),
+            function("inline_c", 0, 2),
+            (
+                ".text$mn",
+                0,
+                3,
+                0,
+                IMAGE_SYM_CLASS_STATIC,
+                section_definition(12, 0, 2),
+            ),
+            function("inline_b", 0, 3),
+        ],
+    );
+
+    let diff = diff_objects(&before, &after).unwrap();
+    let sections: Vec<_> = diff
+        .sections()
+        .iter()
+        .map(|section| {
+            (
+                section.key(),
+                section.before().map(|s| s.size()),
+                section.after().map(|s| s.size()),
+                section.relocation_delta(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        sections,
+        [
+            (".text", Some(0x10), Some(0x18), 2),
+            (".text$mn (inline_a)", Some(4), None, 0),
+            (".text$mn (inline_b)", Some(8), Some(12), 0),
+            (".text$mn (inline_c)", None, Some(4), 0),
+        ]
+    );
+
+    let symbols: Vec<_> = diff
+        .symbols()
+        .iter()
+        .map(|symbol| (symbol.name(), symbol.size_delta(), symbol.is_changed()))
+        .collect();
+    assert_eq!(
+        symbols,
+        [
+            ("f", 0, false),
+            // `g` ran to the end of the section before `h` was added after it
+            ("g", 0, false),
+            ("h", 8, true),
+            ("inline_a", -4, true),
+            ("inline_b", 4, true),
+            ("inline_c", 4, true),
+        ]
+    );
+    assert_eq!(
+        diff.symbols()[4].after().unwrap().section(),
+        ".text$mn (inline_b)"
+    );
+}
@@ -0,0 +1,210 @@
+mod common;
+
+use common::{fixtures, put32, DATA_DIRECTORIES};
+use pexp::port_exe::PortExe;
+use pexp::resource::ResourceId;
+use pexp::testing::{PeFixture, FIXTURE_SECTION_ALIGNMENT};
+use pexp::PeString;
+
+const IMPORT_DIRECTORY_SIZE: u32 = 60;
+
+enum Key {
+    Id(u16),
+    Name(&'static str),
+}
+
+/// Import directory at `rva` naming `zeta.dll` (`Zed`, then `Abc`) before `alpha.dll`
+/// (`Mid`), so neither the DLLs nor the functions are in alphabetical order.
+fn import_section(rva: u32) -> Vec<u8> {
+    let mut data = vec![0u8; 0x100];
+    for (descriptor, (lookup, iat, name)) in [(64, 96, 176), (80, 112, 192)].iter().enumerate() {
+        let offset = descriptor * 20;
+        put32(&mut data, offset, rva + lookup);
+        put32(&mut data, offset + 12, rva + name);
+        put32(&mut data, offset + 16, rva + iat);
+    }
+    for (table, names) in [(64, &[128, 140][..]), (80, &[152][..])] {
+        for (index, &name) in names.iter().enumerate() {
+            put32(&mut data, table + index * 4, rva + name);
+            put32(&mut data, table + 32 + index * 4, rva + name);
+        }
+    }
+    for (offset, name) in [(130, "Zed"), (142, "Abc"), (154, "Mid")] {
+        data[offset..offset + 3].copy_from_slice(name.as_bytes());
+    }
+    data[176..184].copy_from_slice(b"zeta.dll");
+    data[192..201].copy_from_slice(b"alpha.dll");
+    data
+}
+
+/// Resource directory at `rva` with one type per key, stored in the order given, each
+/// holding resource 1 in language 0x409.
+fn resource_section(rva: u32, types: &[Key]) -> Vec<u8> {
+    let count = types.len();
+    let root = 16 + 8 * count;
+    let data_entries = root + 48 * count;
+    let mut strings = data_entries + 16 * count;
+    let mut data = vec![0u8; strings];
+    let named = types
+        .iter()
+        .filter(|key| matches!(key, Key::Name(_)))
+        .count();
+    data[12..14].copy_from_slice(&(named as u16).to_le_bytes());
+    data[14..16].copy_from_slice(&((count - named) as u16).to_le_bytes());
+    for (index, key) in types.iter().enumerate() {
+        let name_directory = root + 48 * index;
+        let language_directory = name_directory + 24;
+        let data_entry = data_entries + 16 * index;
+        let name = match key {
+            Key::Id(id) => *id as u32,
+            Key::Name(name) => {
+                let offset = strings;
+                data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+                for unit in name.encode_utf16() {
+                    data.extend_from_slice(&unit.to_le_bytes());
+                }
+                strings = data.len();
+                0x8000_0000 | offset as u32
+            }
+        };
+        put32(&mut data, 16 + 8 * index, name);
+        put32(
+            &mut data,
+            20 + 8 * index,
+            0x8000_0000 | name_directory as u32,
+        );
+        data[name_directory + 14] = 1;
+        put32(&mut data, name_directory + 16, 1);
+        put32(
+            &mut data,
+            name_directory + 20,
+            0x8000_0000 | language_directory as u32,
+        );
+        data[language_directory + 14] = 1;
+        put32(&mut data, language_directory + 16, 0x409);
+        put32(&mut data, language_directory + 20, data_entry as u32);
+        put32(&mut data, data_entry, rva);
+        put32(&mut data, data_entry + 4, 4);
+    }
+    data
+}
+
+/// PE32 image whose sections, imports and resources are all stored out of sorted order
+fn unsorted_image() -> Vec<u8> {
+    let import_rva = FIXTURE_SECTION_ALIGNMENT;
+    let resource_rva = 2 * FIXTURE_SECTION_ALIGNMENT;
+    let mut image = PeFixture::pe32()
+        .dll_characteristics(0)
+        .section(".idata", 0x4000_0040, &import_section(import_rva))
+        .section(
+            ".rsrc",
+            0x4000_0040,
+            &resource_section(
+                resource_rva,
+                &[
+                    Key::Name("b"),
+                    Key::Id(5),
+                    Key::Name("a"),
+                    Key::Id(2),
+                    Key::Name("B"),
+                ],
+            ),
+        )
+        .section(".a", 0x4000_0040, &[0; 16])
+        .build();
+    put32(&mut image, DATA_DIRECTORIES + 8, import_rva);
+    put32(&mut image, DATA_DIRECTORIES + 12, IMPORT_DIRECTORY_SIZE);
+    put32(&mut image, DATA_DIRECTORIES + 16, resource_rva);
+    put32(&mut image, DATA_DIRECTORIES + 20, 0x200);
+    image
+}
+
+fn name(text: &str) -> ResourceId {
+    ResourceId::Name(PeString::from_utf16(
+        &text.encode_utf16().collect::<Vec<_>>(),
+    ))
+}
+
+#[test]
+fn sections_follow_table_order() {
+    let pe = PortExe::from_bytes(unsorted_image()).unwrap();
+    let names: Vec<String> = pe
+        .section_headers()
+        .iter()
+        .map(|section| section.name().into_value().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names, [".idata", ".rsrc", ".a"]);
+}
+
+#[test]
+fn imports_follow_descriptor_and_thunk_order() {
+    let pe = PortExe::from_bytes(unsorted_image()).unwrap();
+    let imports: Vec<(String, Vec<String>)> = pe
+        .imports()
+        .unwrap()
+        .iter()
+        .map(|descriptor| {
+            let functions = descriptor
+                .imports()
+                .iter()
+                .map(|import| import.name().unwrap().to_string_lossy().into_owned())
+                .collect();
+            (descriptor.dll_name().to_string(), functions)
+        })
+        .collect();
+    assert_eq!(
+        imports,
+        [
+            (
+                "zeta.dll".to_string(),
+                vec!["Zed".to_string(), "Abc".to_string()]
+            ),
+            ("alpha.dll".to_string(), vec!["Mid".to_string()]),
+        ]
+    );
+}
+
+/// Types come out numeric IDs first, then names by UTF-16 code unit, whatever order
+/// the directory stores them in.
+#[test]
+fn resources_are_sorted_by_id_then_name() {
+    let pe = PortExe::from_bytes(unsorted_image()).unwrap();
+    let resources = pe.resources().unwrap();
+    let types: Vec<&ResourceId> = resources.iter().map(|entry| entry.type_id()).collect();
+    assert_eq!(
+        types,
+        [
+            &ResourceId::Id(2),
+            &ResourceId::Id(5),
+            &name("B"),
+            &name("a"),
+            &name("b"),
+        ]
+    );
+    assert!(ResourceId::Id(u16::MAX) < name(""));
+}
+
+/// Parsing the same bytes twice lists everything in the same order.
+#[test]
+fn repeated_parses_agree() {
+    let unsorted = unsorted_image();
+    for data in [
+        fixtures::PE32_EXE,
+        fixtures::PE32PLUS_EXE,
+        fixtures::PE32_DLL,
+        fixtures::PE32PLUS_DRIVER,
+        &unsorted[..],
+    ] {
+        let listing = || {
+            let pe = PortExe::from_bytes(data.to_vec()).unwrap();
+            format!(
+                "{:?}\n{:?}\n{:?}\n{:?}",
+                pe.section_headers(),
+                pe.imports().unwrap(),
+                pe.exports().unwrap(),
+                pe.resources().unwrap()
+            )
+        };
+        assert_eq!(listing(), listing());
+    }
+}
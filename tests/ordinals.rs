@@ -0,0 +1,68 @@
+use pexp::export::{build_export_table, ExportSpec, ExportTarget};
+use pexp::ordinals::OrdinalMap;
+use pexp::testing::PeFixture;
+
+#[test]
+fn bundled_maps_name_socket_and_automation_ordinals() {
+    let map = OrdinalMap::bundled();
+    assert_eq!(map.name("ws2_32.dll", 23), Some("socket"));
+    assert_eq!(map.name("WSOCK32", 9), Some("htons"));
+    assert_eq!(map.name("OleAut32.DLL", 2), Some("SysAllocString"));
+    assert_eq!(map.name("ws2_32.dll", 999), None);
+    assert_eq!(map.name("kernel32.dll", 1), None);
+    assert_eq!(OrdinalMap::new().name("ws2_32.dll", 23), None);
+
+    let pe = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .import_ordinal("WS2_32.dll", 23)
+        .import_ordinal("WS2_32.dll", 999)
+        .import("WS2_32.dll", "WSAStartup")
+        .parse()
+        .unwrap();
+    let descriptor = &pe.imports().unwrap()[0];
+    let names: Vec<String> = descriptor
+        .imports()
+        .iter()
+        .map(|import| map.import_name(descriptor.dll_name(), import))
+        .collect();
+    assert_eq!(names, ["socket", "#999", "WSAStartup"]);
+}
+
+#[test]
+fn user_maps_come_from_definitions_and_dlls() {
+    let mut map = OrdinalMap::bundled();
+    map.add_definition_text(
+        "LIBRARY vendor\nEXPORTS\n    Connect @1\n    Send @2 NONAME\n    Recv",
+    )
+    .unwrap();
+    assert_eq!(map.name("vendor.dll", 1), Some("Connect"));
+    // The name of an ordinal-only export is still worth knowing
+    assert_eq!(map.name("vendor.dll", 2), Some("Send"));
+
+    // Later maps override the bundled ones
+    map.add_definition_text("LIBRARY ws2_32.dll\nEXPORTS\n    patched_socket @23")
+        .unwrap();
+    assert_eq!(map.name("ws2_32", 23), Some("patched_socket"));
+    assert_eq!(map.name("ws2_32", 2), Some("bind"));
+    assert!(map.add_definition_text("EXPORTS\n    Connect @1").is_err());
+
+    let specs = [
+        ExportSpec::new(
+            Some("Alpha".to_string()),
+            Some(7),
+            ExportTarget::Rva(0x1000),
+        ),
+        ExportSpec::new(None, Some(8), ExportTarget::Rva(0x1000)),
+    ];
+    let table = build_export_table("plugin.dll", &specs, 0x2000).unwrap();
+    let plugin = PeFixture::pe32()
+        .dll()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .section(".edata", 0x4000_0040, &table)
+        .directory(0, 0x2000, table.len() as u32)
+        .parse()
+        .unwrap();
+    map.add_exports("Plugin.dll", &plugin.exports().unwrap());
+    assert_eq!(map.name("plugin", 7), Some("Alpha"));
+    assert_eq!(map.name("plugin", 8), None);
+}
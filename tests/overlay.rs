@@ -0,0 +1,91 @@
+mod common;
+
+use common::fixtures;
+use pexp::overlay::{MagicSniffer, OverlaySniffer, OverlaySniffers};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+fn image() -> Vec<u8> {
+    PeFixture::pe32()
+        .subsystem(2)
+        .dll_characteristics(0)
+        .section(".text", 0x6000_0020, &[0xC3; 0x10])
+        .build()
+}
+
+fn formats(pe: &PortExe, sniffers: &OverlaySniffers) -> Vec<(String, usize)> {
+    pe.sniff_overlay(sniffers)
+        .iter()
+        .map(|found| (found.format().to_string(), found.offset()))
+        .collect()
+}
+
+#[test]
+fn builtin_sniffers_locate_appended_formats() {
+    let mut data = image();
+    let overlay = data.len();
+    data.extend_from_slice(&[0xEE; 0x10]);
+    data.extend_from_slice(b"\0\0\0\0\xEF\xBE\xAD\xDENullsoftInst");
+    data.extend_from_slice(b"7z\xBC\xAF\x27\x1C\0\x04");
+    data.extend_from_slice(b"Rar!\x1A\x07\x01\x00");
+    data.extend_from_slice(b"PK\x03\x04\x14\0");
+    let pe = PortExe::from_bytes(data.clone()).unwrap();
+
+    assert_eq!(pe.overlay_offset(), overlay);
+    assert_eq!(pe.overlay(), &data[overlay..]);
+    assert_eq!(
+        formats(&pe, &OverlaySniffers::default()),
+        [
+            ("nsis".to_string(), overlay + 0x10),
+            ("7z".to_string(), overlay + 0x24),
+            ("rar".to_string(), overlay + 0x2C),
+            ("zip".to_string(), overlay + 0x34),
+        ]
+    );
+    common::exercise(&data);
+}
+
+/// Sniffer for a format that only counts when its header checksum matches
+struct Checksummed;
+
+impl OverlaySniffer for Checksummed {
+    fn name(&self) -> &str {
+        "checksummed"
+    }
+
+    fn sniff(&self, overlay: &[u8]) -> Option<usize> {
+        overlay
+            .windows(4)
+            .position(|window| window[..3] == *b"XYZ" && window[3] == b'X' ^ b'Y' ^ b'Z')
+    }
+}
+
+#[test]
+fn custom_sniffers_can_be_registered() {
+    let mut data = image();
+    let overlay = data.len();
+    data.extend_from_slice(b"XYZ\0--XYZ");
+    data.push(b'X' ^ b'Y' ^ b'Z');
+    data.extend_from_slice(b"--\x01\x02HDR");
+    let pe = PortExe::from_bytes(data).unwrap();
+
+    let mut sniffers = OverlaySniffers::empty();
+    assert!(pe.sniff_overlay(&sniffers).is_empty());
+    sniffers.register(Checksummed);
+    sniffers.register(MagicSniffer::new("hdr", b"HDR").with_offset(2));
+    assert_eq!(sniffers.names(), ["checksummed", "hdr"]);
+    assert_eq!(
+        formats(&pe, &sniffers),
+        [
+            ("checksummed".to_string(), overlay + 6),
+            ("hdr".to_string(), overlay + 12),
+        ]
+    );
+}
+
+#[test]
+fn images_without_overlay_sniff_nothing() {
+    let pe = PortExe::from_bytes(fixtures::PE32_EXE.to_vec()).unwrap();
+    assert!(pe.overlay().is_empty());
+    assert!(pe.sniff_overlay(&OverlaySniffers::default()).is_empty());
+}
@@ -0,0 +1,81 @@
+use pexp::patch::{Patch, PatchSource};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+/// Bytes that do not compress or repeat, standing in for compiled code
+fn noise(seed: u32, length: usize) -> Vec<u8> {
+    let mut state = seed;
+    (0..length)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (state >> 16) as u8
+        })
+        .collect()
+}
+
+fn image(text: Vec<u8>, data: Vec<u8>, overlay: &[u8]) -> PortExe {
+    let mut bytes = PeFixture::pe32()
+        .time_date_stamp(0x5000_0000)
+        .dll_characteristics(0)
+        .section(".text", 0x6000_0020, &text)
+        .section(".data", 0xC000_0040, &data)
+        .build();
+    bytes.extend_from_slice(overlay);
+    PortExe::from_bytes(bytes).unwrap()
+}
+
+/// Two builds of the same program: a function grew, moving the code after it and the
+/// data section, and a few pointers into the moved code changed
+fn versions() -> (PortExe, PortExe) {
+    let code = noise(1, 0x3000);
+    let data = noise(2, 0x800);
+    let old = image(code.clone(), data.clone(), b"overlay");
+
+    let mut new_code = code[..0x400].to_vec();
+    new_code.extend_from_slice(&noise(3, 0x240));
+    new_code.extend_from_slice(&code[0x400..]);
+    for pointer in [0x1000, 0x1800, 0x2400] {
+        new_code[pointer..pointer + 4].copy_from_slice(&0x0040_1234u32.to_le_bytes());
+    }
+    let new = image(new_code, data, b"overlay");
+    (old, new)
+}
+
+#[test]
+fn patches_rebuild_the_new_version() {
+    let (old, new) = versions();
+    let patch = Patch::between(&old, &new);
+    assert_eq!(patch.apply(old.data()).unwrap(), new.data());
+
+    let sources: Vec<PatchSource> = patch.regions().iter().map(|r| r.source()).collect();
+    assert_eq!(
+        sources,
+        [
+            PatchSource::Headers,
+            PatchSource::Section(0),
+            PatchSource::Section(1),
+            PatchSource::Overlay,
+        ]
+    );
+    // The data section moved but is otherwise copied whole
+    assert_eq!(patch.regions()[2].ops().len(), 1);
+    assert_eq!(patch.regions()[2].inserted(), 0);
+
+    let encoded = patch.to_bytes();
+    assert_eq!(Patch::parse(&encoded).unwrap(), patch);
+    // The new function and the changed pointers, plus little else
+    assert!(encoded.len() < 0x240 + 0x100, "{} bytes", encoded.len());
+}
+
+#[test]
+fn patches_refuse_other_files() {
+    let (old, new) = versions();
+    let patch = Patch::between(&old, &new);
+    let error = patch.apply(new.data()).unwrap_err();
+    assert_eq!(error.to_string(), "patch was made from a different file");
+
+    let mut encoded = patch.to_bytes();
+    encoded.pop();
+    assert!(Patch::parse(&encoded).is_err());
+    assert!(Patch::parse(b"PEXPATCH\x02").is_err());
+}
@@ -0,0 +1,83 @@
+use pexp::plugin::{Analysis, AnalysisRegistry};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+use pexp::validation::{Finding, Severity};
+use std::io;
+
+/// Downstream check flagging images without sections
+struct EmptyImage;
+
+impl Analysis for EmptyImage {
+    fn name(&self) -> &str {
+        "empty"
+    }
+
+    fn run(&self, pe: &PortExe) -> io::Result<Vec<Finding>> {
+        Ok(pe
+            .section_headers()
+            .is_empty()
+            .then(|| Finding::new(Severity::Error, "image has no sections"))
+            .into_iter()
+            .collect())
+    }
+}
+
+fn image() -> PeFixture {
+    PeFixture::pe32().dll_characteristics(0)
+}
+
+fn messages(registry: &AnalysisRegistry, pe: &PortExe) -> Vec<(String, String)> {
+    registry
+        .run(pe)
+        .iter()
+        .flat_map(|report| {
+            let findings = report.findings().unwrap();
+            findings
+                .iter()
+                .map(|finding| (report.analysis().to_string(), finding.message().to_string()))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+#[test]
+fn plugins_run_alongside_the_builtin_analyses() {
+    let mut registry = AnalysisRegistry::builtin();
+    registry.register(Box::new(EmptyImage));
+    assert_eq!(
+        registry.names(),
+        ["entropy", "anomalies", "security", "entry-point", "empty"]
+    );
+
+    let empty = messages(&registry, &image().parse().unwrap());
+    assert!(empty.contains(&("empty".to_string(), "image has no sections".to_string())));
+    assert!(empty.contains(&("security".to_string(), "ASLR is not enabled".to_string())));
+
+    let registry = registry.select(&["empty", "entropy"]).unwrap();
+    assert_eq!(registry.names(), ["empty", "entropy"]);
+    let mut state = 1u32;
+    let noise: Vec<u8> = (0..0x1000)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (state >> 16) as u8
+        })
+        .collect();
+    let packed = image()
+        .section("UPX1", 0xE000_0040, &noise)
+        .parse()
+        .unwrap();
+    let findings = messages(&registry, &packed);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].0, "entropy");
+    assert!(findings[0].1.starts_with("section UPX1 has entropy 7.9"));
+}
+
+#[test]
+fn unknown_analyses_cannot_be_selected() {
+    let error = AnalysisRegistry::builtin()
+        .select(&["security", "telemetry"])
+        .err()
+        .unwrap();
+    assert_eq!(error, "telemetry");
+    assert!(AnalysisRegistry::builtin().get("anomalies").is_some());
+}
@@ -0,0 +1,440 @@
+mod common;
+
+use common::{fixtures, put32, resource_section, ResourceKey, DATA_DIRECTORIES};
+use pexp::port_exe::PortExe;
+use pexp::provenance::{DelphiMarker, NsisCompression};
+use pexp::testing::PeFixture;
+
+const IMAGE_BASE: u32 = 0x40_0000;
+const TEXT_RVA: u32 = 0x1000;
+const BORLAND_TIME_DATE_STAMP: u32 = 0x2A42_5E19;
+const RT_RCDATA: u16 = 10;
+
+fn put(data: &mut [u8], offset: usize, bytes: &[u8]) {
+    data[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+fn va(offset: usize) -> u32 {
+    IMAGE_BASE + TEXT_RVA + offset as u32
+}
+
+fn image(time_date_stamp: u32, sections: &[(&str, u32, &[u8])]) -> Vec<u8> {
+    let mut fixture = PeFixture::pe32()
+        .time_date_stamp(time_date_stamp)
+        .subsystem(2)
+        .dll_characteristics(0);
+    for &(name, characteristics, data) in sections {
+        fixture = fixture.section(name, characteristics, data);
+    }
+    fixture.build()
+}
+
+/// `.text` of a VB6 executable: the `push <header>; call ThunRTMain` entry point, the
+/// `VB5!` header, project data, an object table naming two objects and an import of
+/// `MSVBVM60.DLL`
+fn visual_basic_text() -> Vec<u8> {
+    let mut text = vec![0u8; 0x300];
+    text[0] = 0x68;
+    put32(&mut text, 1, va(0x10));
+    text[5] = 0xE8;
+
+    put(&mut text, 0x10, b"VB5!");
+    put32(&mut text, 0x10 + 0x30, va(0x100));
+    put32(&mut text, 0x10 + 0x44, 1);
+    put32(&mut text, 0x10 + 0x5C, 0x70);
+    put32(&mut text, 0x10 + 0x64, 0x78);
+    put(&mut text, 0x80, b"Demo\0");
+    put(&mut text, 0x88, b"Project1\0");
+
+    put32(&mut text, 0x100 + 0x04, va(0x140));
+    put32(&mut text, 0x100 + 0x20, va(0x2E0));
+
+    text[0x140 + 0x2A] = 2;
+    put32(&mut text, 0x140 + 0x30, va(0x180));
+    put32(&mut text, 0x180 + 0x18, va(0x200));
+    put32(&mut text, 0x1B0 + 0x18, va(0x210));
+    put(&mut text, 0x200, b"Form1\0");
+    put(&mut text, 0x210, b"Module1\0");
+
+    put32(&mut text, 0x240, TEXT_RVA + 0x280);
+    put32(&mut text, 0x240 + 12, TEXT_RVA + 0x2A0);
+    put32(&mut text, 0x240 + 16, TEXT_RVA + 0x290);
+    put32(&mut text, 0x280, TEXT_RVA + 0x2B0);
+    put32(&mut text, 0x290, TEXT_RVA + 0x2B0);
+    put(&mut text, 0x2A0, b"MSVBVM60.DLL\0");
+    put(&mut text, 0x2B2, b"ThunRTMain\0");
+    text
+}
+
+#[test]
+fn visual_basic_project_is_decoded() {
+    let mut data = image(0, &[(".text", 0x6000_0020, &visual_basic_text())]);
+    put32(&mut data, DATA_DIRECTORIES + 8, TEXT_RVA + 0x240);
+    put32(&mut data, DATA_DIRECTORIES + 12, 40);
+    let pe = PortExe::from_bytes(data.clone()).unwrap();
+
+    let provenance = pe.provenance();
+    assert!(provenance.delphi().is_none());
+    let project = provenance.visual_basic().unwrap();
+    assert_eq!(project.runtime(), "MSVBVM60.DLL");
+    assert_eq!(project.header_rva(), Some(TEXT_RVA + 0x10));
+    assert_eq!(project.project_name(), Some("Project1"));
+    assert_eq!(project.exe_name(), Some("Demo"));
+    assert_eq!(project.description(), None);
+    assert_eq!(project.form_count(), 1);
+    assert_eq!(project.native_code(), Some(true));
+    assert_eq!(project.objects(), ["Form1", "Module1"]);
+    common::exercise(&data);
+}
+
+#[test]
+fn visual_basic_runtime_is_reported_without_header() {
+    let mut text = visual_basic_text();
+    text[0] = 0x90;
+    let mut data = image(0, &[(".text", 0x6000_0020, &text)]);
+    put32(&mut data, DATA_DIRECTORIES + 8, TEXT_RVA + 0x240);
+    put32(&mut data, DATA_DIRECTORIES + 12, 40);
+    let pe = PortExe::from_bytes(data).unwrap();
+
+    let provenance = pe.provenance();
+    let project = provenance.visual_basic().unwrap();
+    assert_eq!(project.runtime(), "MSVBVM60.DLL");
+    assert_eq!(project.header_rva(), None);
+    assert_eq!(project.project_name(), None);
+    assert!(project.objects().is_empty());
+}
+
+/// `PACKAGEINFO` requiring `rtl` and containing `System` and `Unit1`
+fn package_info() -> Vec<u8> {
+    let mut data = vec![0, 0, 0, 0];
+    data.extend_from_slice(&1u32.to_le_bytes());
+    data.extend_from_slice(b"\x00rtl\0");
+    data.extend_from_slice(&2u32.to_le_bytes());
+    data.extend_from_slice(b"\x00\x00System\0");
+    data.extend_from_slice(b"\x00\x00Unit1\0");
+    data
+}
+
+#[test]
+fn delphi_units_packages_and_forms_are_listed() {
+    let resource_rva = 0x4000;
+    let package_info = package_info();
+    let form = b"TPF0\x06TForm1\x05Form1\x00";
+    let rsrc = resource_section(
+        resource_rva,
+        &[
+            (
+                ResourceKey::Id(RT_RCDATA),
+                ResourceKey::Name("DVCLAL"),
+                &[0x23; 16],
+            ),
+            (
+                ResourceKey::Id(RT_RCDATA),
+                ResourceKey::Name("PACKAGEINFO"),
+                &package_info,
+            ),
+            (
+                ResourceKey::Id(RT_RCDATA),
+                ResourceKey::Name("TFORM1"),
+                form,
+            ),
+        ],
+    );
+    let mut data = image(
+        BORLAND_TIME_DATE_STAMP,
+        &[
+            ("CODE", 0x6000_0020, &[0xC3; 0x10]),
+            ("DATA", 0xC000_0040, &[0; 0x10]),
+            ("BSS", 0xC000_0080, &[0; 0x10]),
+            (".rsrc", 0x4000_0040, &rsrc),
+        ],
+    );
+    put32(&mut data, DATA_DIRECTORIES + 2 * 8, resource_rva);
+    put32(&mut data, DATA_DIRECTORIES + 2 * 8 + 4, rsrc.len() as u32);
+    let pe = PortExe::from_bytes(data.clone()).unwrap();
+
+    let provenance = pe.provenance();
+    assert!(provenance.visual_basic().is_none());
+    let delphi = provenance.delphi().unwrap();
+    assert_eq!(
+        delphi.markers(),
+        [
+            DelphiMarker::Dvclal,
+            DelphiMarker::PackageInfo,
+            DelphiMarker::Forms,
+            DelphiMarker::BorlandSections,
+            DelphiMarker::BorlandTimestamp,
+        ]
+    );
+    assert_eq!(delphi.required_packages(), ["rtl"]);
+    assert_eq!(delphi.units(), ["System", "Unit1"]);
+    assert_eq!(delphi.forms().len(), 1);
+    assert_eq!(delphi.forms()[0].resource(), "TFORM1");
+    assert_eq!(delphi.forms()[0].class_name(), "TForm1");
+    assert_eq!(delphi.forms()[0].name(), "Form1");
+    common::exercise(&data);
+}
+
+#[test]
+fn itext_section_alone_marks_delphi() {
+    let data = image(
+        0,
+        &[
+            (".text", 0x6000_0020, &[0xC3; 0x10]),
+            (".itext", 0x6000_0020, &[0xC3; 0x10]),
+        ],
+    );
+    let pe = PortExe::from_bytes(data).unwrap();
+    let provenance = pe.provenance();
+    assert_eq!(
+        provenance.delphi().unwrap().markers(),
+        [DelphiMarker::ItextSection]
+    );
+    assert!(provenance.delphi().unwrap().units().is_empty());
+}
+
+#[test]
+fn compiler_built_images_have_no_provenance() {
+    for data in [
+        fixtures::PE32_EXE,
+        fixtures::PE32_DLL,
+        fixtures::PE32PLUS_EXE,
+    ] {
+        let pe = PortExe::from_bytes(data.to_vec()).unwrap();
+        assert!(pe.provenance().is_empty());
+    }
+}
+
+const AUTOIT_MAGIC: [u8; 16] = [
+    0xA3, 0x48, 0x4B, 0xBE, 0x98, 0x6C, 0x4A, 0xA9, 0x99, 0x4C, 0x53, 0x0A, 0x86, 0xD6, 0x48, 0x7D,
+];
+
+fn autoit_script(format: &[u8]) -> Vec<u8> {
+    let mut script = AUTOIT_MAGIC.to_vec();
+    script.extend_from_slice(b"AU3!");
+    script.extend_from_slice(format);
+    script.extend_from_slice(&[0x5A; 0x40]);
+    script
+}
+
+#[test]
+fn autoit_script_in_overlay_is_located() {
+    let mut data = image(0, &[(".text", 0x6000_0020, &[0xC3; 0x10])]);
+    let overlay = data.len();
+    data.extend_from_slice(&autoit_script(b"EA05"));
+    let pe = PortExe::from_bytes(data).unwrap();
+
+    let provenance = pe.provenance();
+    let script = provenance.autoit().unwrap();
+    assert_eq!(script.format(), "EA05");
+    assert_eq!(script.offset(), overlay);
+    assert_eq!(script.size(), 0x58);
+    assert!(!script.resource());
+    assert!(provenance.pyinstaller().is_none());
+}
+
+#[test]
+fn autoit_script_resource_is_located() {
+    let resource_rva = 0x2000;
+    let script = autoit_script(b"EA06");
+    let rsrc = resource_section(
+        resource_rva,
+        &[(
+            ResourceKey::Id(RT_RCDATA),
+            ResourceKey::Name("SCRIPT"),
+            &script,
+        )],
+    );
+    let mut data = image(
+        0,
+        &[
+            (".text", 0x6000_0020, &[0xC3; 0x10]),
+            (".rsrc", 0x4000_0040, &rsrc),
+        ],
+    );
+    put32(&mut data, DATA_DIRECTORIES + 2 * 8, resource_rva);
+    put32(&mut data, DATA_DIRECTORIES + 2 * 8 + 4, rsrc.len() as u32);
+    let pe = PortExe::from_bytes(data.clone()).unwrap();
+
+    let provenance = pe.provenance();
+    let found = provenance.autoit().unwrap();
+    assert_eq!(found.format(), "EA06");
+    assert!(found.resource());
+    assert_eq!(found.size(), script.len());
+    assert_eq!(
+        &data[found.offset()..found.offset() + script.len()],
+        &script[..]
+    );
+}
+
+/// PyInstaller CArchive holding `entries` as `(type, name, data, compressed)`, with
+/// the 88-byte cookie of PyInstaller 2.1 and later
+fn pyinstaller_archive(entries: &[(u8, &str, &[u8], bool)]) -> Vec<u8> {
+    let mut archive = Vec::new();
+    let mut toc = Vec::new();
+    for &(kind, name, data, compressed) in entries {
+        let length = (18 + name.len() + 1 + 15) / 16 * 16;
+        toc.extend_from_slice(&(length as u32).to_be_bytes());
+        toc.extend_from_slice(&(archive.len() as u32).to_be_bytes());
+        toc.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        toc.extend_from_slice(&(data.len() as u32 * 2).to_be_bytes());
+        toc.push(compressed as u8);
+        toc.push(kind);
+        toc.extend_from_slice(name.as_bytes());
+        toc.resize(toc.len() + length - 18 - name.len(), 0);
+        archive.extend_from_slice(data);
+    }
+    let toc_offset = archive.len() as u32;
+    archive.extend_from_slice(&toc);
+    let size = archive.len() as u32 + 88;
+    archive.extend_from_slice(b"MEI\x0C\x0B\x0A\x0B\x0E");
+    for value in [size, toc_offset, toc.len() as u32, 311] {
+        archive.extend_from_slice(&value.to_be_bytes());
+    }
+    let mut library = b"python311.dll".to_vec();
+    library.resize(64, 0);
+    archive.extend_from_slice(&library);
+    archive
+}
+
+#[test]
+fn pyinstaller_archive_is_listed() {
+    let mut data = image(0, &[(".text", 0x6000_0020, &[0xC3; 0x10])]);
+    let overlay = data.len();
+    data.extend_from_slice(&pyinstaller_archive(&[
+        (b'm', "pyiboot01_bootstrap", b"abc", true),
+        (b's', "hello", b"print", true),
+        (b'b', "python311.dll", b"MZ", false),
+    ]));
+    // A signature appended after the archive leaves the cookie short of the end
+    data.extend_from_slice(&[0; 0x20]);
+    let pe = PortExe::from_bytes(data.clone()).unwrap();
+
+    let provenance = pe.provenance();
+    let archive = provenance.pyinstaller().unwrap();
+    assert_eq!(archive.offset(), overlay);
+    assert_eq!(archive.size(), data.len() - 0x20 - overlay);
+    assert_eq!(archive.python_version(), (3, 11));
+    assert_eq!(archive.python_library(), Some("python311.dll"));
+    let entries: Vec<_> = archive
+        .entries()
+        .iter()
+        .map(|entry| (entry.kind(), entry.name(), entry.offset(), entry.size()))
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            ('m', "pyiboot01_bootstrap", overlay, 3),
+            ('s', "hello", overlay + 3, 5),
+            ('b', "python311.dll", overlay + 8, 2),
+        ]
+    );
+    assert!(archive.entries()[1].compressed());
+    assert!(!archive.entries()[2].compressed());
+    assert_eq!(archive.entries()[1].uncompressed_size(), 10);
+    assert_eq!(&data[overlay + 3..overlay + 8], b"print");
+    common::exercise(&data);
+}
+
+fn nsis_first_header(flags: u32, header_size: u32, stream: &[u8]) -> Vec<u8> {
+    let mut data = flags.to_le_bytes().to_vec();
+    data.extend_from_slice(b"\xEF\xBE\xAD\xDENullsoftInst");
+    data.extend_from_slice(&header_size.to_le_bytes());
+    data.extend_from_slice(&(28 + stream.len() as u32).to_le_bytes());
+    data.extend_from_slice(stream);
+    data
+}
+
+#[test]
+fn nsis_installer_data_is_located() {
+    let mut data = image(0, &[(".text", 0x6000_0020, &[0xC3; 0x10])]);
+    let stub_end = data.len();
+    // Data before the first header, as left by a stub that was signed or extended
+    data.extend_from_slice(&[0xCC; 0x200]);
+    data.extend_from_slice(&nsis_first_header(0, 0x4000, b"\x5D\x00\x00\x10\x00lzma"));
+    let pe = PortExe::from_bytes(data).unwrap();
+    let provenance = pe.provenance();
+    let nsis = provenance.nsis().unwrap();
+    assert_eq!(nsis.offset(), stub_end + 0x200);
+    assert_eq!(nsis.header_size(), 0x4000);
+    assert_eq!(nsis.data_size(), 28 + 9);
+    assert_eq!(nsis.compression(), NsisCompression::Lzma);
+    assert!(nsis.solid());
+    assert!(!nsis.uninstaller());
+
+    let mut data = image(0, &[(".text", 0x6000_0020, &[0xC3; 0x10])]);
+    data.extend_from_slice(&nsis_first_header(1, 0x4000, b"\x20\x01\x00\x801\x09bzip2"));
+    let pe = PortExe::from_bytes(data).unwrap();
+    let provenance = pe.provenance();
+    let nsis = provenance.nsis().unwrap();
+    assert_eq!(nsis.compression(), NsisCompression::Bzip2);
+    assert!(!nsis.solid());
+    assert!(nsis.uninstaller());
+}
+
+/// Setup data starting with the ID naming the compiler version
+fn inno_setup_data() -> Vec<u8> {
+    let mut setup = b"Inno Setup Setup Data (5.5.7) (u)".to_vec();
+    setup.resize(64, 0);
+    setup.extend_from_slice(&[0x11; 0x20]);
+    setup
+}
+
+#[test]
+fn inno_setup_offset_table_resource_is_decoded() {
+    let resource_rva = 0x2000;
+    let overlay = 0x600u32;
+    let mut table = b"rDlPtS\xCD\xE6\xD7\x7B\x0B\x2A".to_vec();
+    for value in [1, 0x700, 0, 0, 0, overlay, overlay + 0x60, 0] {
+        table.extend_from_slice(&u32::to_le_bytes(value));
+    }
+    let rsrc = resource_section(
+        resource_rva,
+        &[(ResourceKey::Id(RT_RCDATA), ResourceKey::Id(11111), &table)],
+    );
+    let mut data = image(
+        0,
+        &[
+            (".text", 0x6000_0020, &[0xC3; 0x10]),
+            (".rsrc", 0x4000_0040, &rsrc),
+        ],
+    );
+    put32(&mut data, DATA_DIRECTORIES + 2 * 8, resource_rva);
+    put32(&mut data, DATA_DIRECTORIES + 2 * 8 + 4, rsrc.len() as u32);
+    assert_eq!(data.len(), overlay as usize);
+    data.extend_from_slice(&inno_setup_data());
+    let pe = PortExe::from_bytes(data.clone()).unwrap();
+
+    let provenance = pe.provenance();
+    let inno = provenance.inno_setup().unwrap();
+    assert_eq!(inno.loader_version(), "5.1.5");
+    assert_eq!(inno.setup_offset(), overlay);
+    assert_eq!(inno.data_offset(), overlay + 0x60);
+    assert_eq!(inno.setup_id(), Some("Inno Setup Setup Data (5.5.7) (u)"));
+    assert!(provenance.nsis().is_none());
+    common::exercise(&data);
+}
+
+#[test]
+fn legacy_inno_setup_offset_table_is_found_from_the_dos_header() {
+    let mut data = image(0, &[(".text", 0x6000_0020, &[0xC3; 0x10])]);
+    let table_offset = data.len();
+    let setup_offset = table_offset as u32 + 0x40;
+    put(&mut data, 0x30, b"Inno");
+    put32(&mut data, 0x34, table_offset as u32);
+    data.extend_from_slice(b"rDlPtS07\x87\x65\x56\x78");
+    for value in [0x700, 0, 0, 0, setup_offset, 0, 0] {
+        data.extend_from_slice(&u32::to_le_bytes(value));
+    }
+    data.resize(setup_offset as usize, 0);
+    data.extend_from_slice(&inno_setup_data());
+    let pe = PortExe::from_bytes(data).unwrap();
+
+    let provenance = pe.provenance();
+    let inno = provenance.inno_setup().unwrap();
+    assert_eq!(inno.loader_version(), "4.1.6");
+    assert_eq!(inno.table_offset(), table_offset);
+    assert_eq!(inno.setup_offset(), setup_offset);
+    assert_eq!(inno.data_offset(), 0);
+    assert_eq!(inno.setup_id(), Some("Inno Setup Setup Data (5.5.7) (u)"));
+}
@@ -0,0 +1,118 @@
+use pexp::edit::LongNames;
+use pexp::editor::PeEditor;
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+use pexp::validation::ValidationMode;
+use std::io;
+
+fn packed() -> PortExe {
+    PortExe::from_bytes(
+        PeFixture::pe64()
+            .dll_characteristics(0)
+            .uninitialized_section("UPX0", 0xE000_0080, 0x2000)
+            .section("UPX1", 0xE000_0040, &[0x90; 0x40])
+            .section("UPX2", 0xC000_0040, &[1; 0x10])
+            .build(),
+    )
+    .unwrap()
+}
+
+fn names(pe: &PortExe) -> Vec<String> {
+    pe.section_headers()
+        .iter()
+        .map(|section| pe.section_name(section).to_string_lossy().into_owned())
+        .collect()
+}
+
+#[test]
+fn short_names_are_written_to_the_header() {
+    let mut pe = packed();
+    let changes = pe
+        .rename_sections(
+            &[(1, ".text".to_string()), (2, "UPX2".to_string())],
+            LongNames::Refuse,
+        )
+        .unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].index(), 1);
+    assert_eq!(changes[0].before().to_string_lossy(), "UPX1");
+    assert_eq!(changes[0].after(), ".text");
+    assert_eq!(names(&pe), ["UPX0", ".text", "UPX2"]);
+}
+
+#[test]
+fn invalid_names_are_rejected() {
+    let mut pe = packed();
+    for name in ["", "/4", "a\0b", ".verylongname"] {
+        let error = pe
+            .rename_sections(&[(0, name.to_string())], LongNames::Refuse)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput, "{:?}", name);
+    }
+    assert!(pe
+        .rename_sections(&[(3, ".text".to_string())], LongNames::Refuse)
+        .is_err());
+    assert_eq!(names(&pe), ["UPX0", "UPX1", "UPX2"]);
+}
+
+#[test]
+fn long_names_go_to_a_new_string_table() {
+    let mut pe = packed();
+    let size = pe.data().len();
+    pe.rename_sections(
+        &[(1, ".text$unpacked".to_string()), (2, ".data".to_string())],
+        LongNames::StringTable,
+    )
+    .unwrap();
+    assert!(pe.data().len() > size);
+    assert_eq!(names(&pe), ["UPX0", ".text$unpacked", ".data"]);
+    assert_eq!(
+        pe.section_headers()[1]
+            .name()
+            .into_value()
+            .to_string_lossy(),
+        "/4"
+    );
+
+    pe.rename_sections(&[(0, ".bss$unpacked".to_string())], LongNames::StringTable)
+        .unwrap();
+    assert_eq!(names(&pe), [".bss$unpacked", ".text$unpacked", ".data"]);
+}
+
+#[test]
+fn packer_names_are_normalized_from_the_characteristics() {
+    let mut pe = packed();
+    let normalized = pe.normalized_section_names();
+    assert_eq!(
+        normalized,
+        [
+            (0, ".bss".to_string()),
+            (1, ".text".to_string()),
+            (2, ".data".to_string())
+        ]
+    );
+
+    let mut editor = PeEditor::new(packed(), ValidationMode::Strict);
+    let (changes, _) = editor
+        .apply("rename-sections", |pe| {
+            pe.rename_sections(&normalized, LongNames::Refuse)
+        })
+        .unwrap();
+    assert_eq!(changes.len(), 3);
+    assert_eq!(names(editor.pe()), [".bss", ".text", ".data"]);
+
+    let random = pe.random_section_names(7);
+    assert_eq!(random, pe.random_section_names(7));
+    assert_ne!(random, pe.random_section_names(8));
+    assert!(random
+        .iter()
+        .all(|(_, name)| name.len() == 8 && name.starts_with('.')));
+    pe.rename_sections(&random, LongNames::Refuse).unwrap();
+    assert_eq!(
+        names(&pe),
+        random
+            .iter()
+            .map(|(_, name)| name.clone())
+            .collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,99 @@
+use pexp::optional_header::DataDirectoryType;
+use pexp::port_exe::PortExe;
+use pexp::repro::{compare_builds, FileRegion, VolatileField};
+use pexp::testing::PeFixture;
+
+/// File offset of the optional header of an image laid out by [`PeFixture`]
+const OPTIONAL_HEADER: usize = 0x80 + 4 + 20;
+
+/// Image with `.text` at file offset 0x200 and `.rdata` at 0x400, whose first 0x40
+/// bytes hold an import directory, built at `time_date_stamp`
+fn build(time_date_stamp: u32) -> Vec<u8> {
+    let mut data = PeFixture::pe32()
+        .time_date_stamp(time_date_stamp)
+        .dll_characteristics(0)
+        .section(".text", 0x6000_0020, &[0xCC; 0x100])
+        .section(".rdata", 0x4000_0040, &[0x11; 0x100])
+        .build();
+    let import = OPTIONAL_HEADER + 96 + 8;
+    data[import..import + 4].copy_from_slice(&0x2000u32.to_le_bytes());
+    data[import + 4..import + 8].copy_from_slice(&0x40u32.to_le_bytes());
+    data
+}
+
+fn pe(data: Vec<u8>) -> PortExe {
+    PortExe::from_bytes(data).unwrap()
+}
+
+#[test]
+fn builds_differing_only_in_volatile_fields_are_reproducible() {
+    let mut later = build(0x6000_0000);
+    later[OPTIONAL_HEADER + 64..OPTIONAL_HEADER + 68].copy_from_slice(&0x1234u32.to_le_bytes());
+    let comparison = compare_builds(&pe(build(0x5000_0000)), &pe(later)).unwrap();
+    assert!(comparison.is_reproducible());
+    assert_eq!(
+        comparison.ignored(),
+        [VolatileField::TimeDateStamp, VolatileField::CheckSum]
+    );
+}
+
+#[test]
+fn remaining_differences_are_mapped_to_structures() {
+    let before = build(0);
+    let mut after = before.clone();
+    after[0x210] = 0x90;
+    after[0x211] = 0x90;
+    after[0x420] = 0;
+    after.extend_from_slice(&[0; 0x20]);
+    let comparison = compare_builds(&pe(before), &pe(after)).unwrap();
+    let changes: Vec<(std::ops::Range<usize>, String, bool)> = comparison
+        .changes()
+        .iter()
+        .map(|change| {
+            (
+                change.range(),
+                change.region().to_string(),
+                change.is_zero_padding(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        changes,
+        [
+            (0x210..0x212, ".text".to_string(), false),
+            (
+                0x420..0x421,
+                "Import directory in .rdata".to_string(),
+                false
+            ),
+            (0x600..0x620, "overlay".to_string(), true),
+        ]
+    );
+    assert_eq!(comparison.after_size(), comparison.before_size() + 0x20);
+    assert!(!comparison.differs_only_in_padding());
+}
+
+#[test]
+fn offsets_map_to_header_structures() {
+    let pe = pe(build(0));
+    assert_eq!(pe.file_region(0x3C), FileRegion::DosHeader);
+    assert_eq!(pe.file_region(0x40), FileRegion::DosStub);
+    assert_eq!(pe.file_region(0x84), FileRegion::FileHeader);
+    assert_eq!(pe.file_region(OPTIONAL_HEADER), FileRegion::OptionalHeader);
+    assert_eq!(
+        pe.file_region(OPTIONAL_HEADER + 96),
+        FileRegion::DataDirectories
+    );
+    assert_eq!(
+        pe.file_region(OPTIONAL_HEADER + 224),
+        FileRegion::SectionTable
+    );
+    assert_eq!(pe.file_region(0x1FF), FileRegion::HeaderPadding);
+    match pe.file_region(0x400) {
+        FileRegion::Section { section, directory } => {
+            assert_eq!(section.to_string_lossy(), ".rdata");
+            assert_eq!(directory, Some(DataDirectoryType::Import));
+        }
+        region => panic!("unexpected region {}", region),
+    }
+}
@@ -0,0 +1,97 @@
+use pexp::port_exe::PortExe;
+use pexp::resource::{ResourceId, ResourceLimits, ResourceWarning};
+use pexp::testing::PeFixture;
+
+const RESOURCE: usize = 2;
+const SUBDIRECTORY: u32 = 0x8000_0000;
+/// Offset of the only data entry in every tree below
+const DATA_ENTRY: u32 = 0x100;
+
+/// Resource section at RVA 0x2000 with a directory table of `(id, offset)` entries at
+/// each offset, and a data entry for 4 bytes at [`DATA_ENTRY`]
+fn section(tables: &[(u32, &[(u32, u32)])]) -> PortExe {
+    let mut rsrc = vec![0u8; 0x200];
+    for &(offset, entries) in tables {
+        let offset = offset as usize;
+        rsrc[offset + 14..offset + 16].copy_from_slice(&(entries.len() as u16).to_le_bytes());
+        for (index, (id, target)) in entries.iter().enumerate() {
+            let entry = offset + 16 + index * 8;
+            rsrc[entry..entry + 4].copy_from_slice(&id.to_le_bytes());
+            rsrc[entry + 4..entry + 8].copy_from_slice(&target.to_le_bytes());
+        }
+    }
+    let data = DATA_ENTRY as usize;
+    rsrc[data..data + 4].copy_from_slice(&0x2180u32.to_le_bytes());
+    rsrc[data + 4..data + 8].copy_from_slice(&4u32.to_le_bytes());
+    PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .section(".rsrc", 0x4000_0040, &rsrc)
+        .directory(RESOURCE, 0x2000, 0x200)
+        .parse()
+        .unwrap()
+}
+
+#[test]
+fn cycles_and_shared_directories_are_skipped() {
+    let pe = section(&[
+        (
+            0x00,
+            &[
+                (1, SUBDIRECTORY | 0x40),
+                (2, SUBDIRECTORY | 0x40),
+                (3, DATA_ENTRY),
+            ],
+        ),
+        (0x40, &[(1, SUBDIRECTORY | 0x60)]),
+        (0x60, &[(0x409, DATA_ENTRY), (0x40C, SUBDIRECTORY)]),
+    ]);
+    let tree = pe.resource_directory(&ResourceLimits::default()).unwrap();
+    assert_eq!(
+        tree.warnings(),
+        [
+            ResourceWarning::SharedDirectory { offset: 0x40 },
+            ResourceWarning::MisplacedData { offset: DATA_ENTRY },
+            ResourceWarning::Cycle { offset: 0 },
+        ]
+    );
+    assert_eq!(tree.entries().len(), 1);
+    let entry = &tree.entries()[0];
+    assert_eq!(
+        (entry.type_id(), entry.name_id(), entry.language()),
+        (
+            &ResourceId::Id(1),
+            &ResourceId::Id(1),
+            &ResourceId::Id(0x409)
+        )
+    );
+    assert_eq!((entry.data_rva(), entry.size()), (0x2180, 4));
+    assert_eq!(pe.resources().unwrap().len(), 1);
+    assert_eq!(
+        tree.warnings()[2].to_string(),
+        "directory at 0x0 contains itself"
+    );
+}
+
+#[test]
+fn walks_are_bounded_in_depth_and_entries() {
+    let pe = section(&[
+        (0x00, &[(1, SUBDIRECTORY | 0x40), (2, SUBDIRECTORY | 0xC0)]),
+        (0x40, &[(1, SUBDIRECTORY | 0x60)]),
+        (0x60, &[(0x409, DATA_ENTRY), (0x40C, SUBDIRECTORY | 0x80)]),
+        (0x80, &[(1, DATA_ENTRY)]),
+        (0xC0, &[(1, SUBDIRECTORY | 0xE0)]),
+        (0xE0, &[(0x409, DATA_ENTRY)]),
+    ]);
+    let tree = pe.resource_directory(&ResourceLimits::default()).unwrap();
+    assert_eq!(tree.entries().len(), 2);
+    assert_eq!(tree.warnings(), [ResourceWarning::TooDeep { offset: 0x80 }]);
+
+    let limits = ResourceLimits {
+        max_entries: 4,
+        ..ResourceLimits::default()
+    };
+    let tree = pe.resource_directory(&limits).unwrap();
+    // Both type entries, the first name entry and its first language entry
+    assert_eq!(tree.entries().len(), 1);
+    assert_eq!(tree.warnings(), [ResourceWarning::EntryBudget]);
+}
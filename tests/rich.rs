@@ -0,0 +1,109 @@
+use pexp::port_exe::PortExe;
+use pexp::rich::RICH_PRODUCT_IMPORT;
+use pexp::testing::PeFixture;
+
+const KEY: u32 = 0x1234_5678;
+/// `"DanS"` and `"Rich"` as little-endian dwords
+const DANS: u32 = 0x536E_6144;
+const RICH: u32 = 0x6863_6952;
+
+/// Rich header encrypted with [`KEY`] listing `(product, build, count)` entries
+fn rich_header(entries: &[(u16, u16, u32)]) -> Vec<u8> {
+    let mut header = Vec::new();
+    for value in [DANS ^ KEY, KEY, KEY, KEY] {
+        header.extend_from_slice(&value.to_le_bytes());
+    }
+    for &(product, build, count) in entries {
+        let id = (product as u32) << 16 | build as u32;
+        header.extend_from_slice(&(id ^ KEY).to_le_bytes());
+        header.extend_from_slice(&(count ^ KEY).to_le_bytes());
+    }
+    header.extend_from_slice(&RICH.to_le_bytes());
+    header.extend_from_slice(&KEY.to_le_bytes());
+    header
+}
+
+/// Image with `stub` placed after the DOS header, before the PE header at 0x80
+fn image(stub: &[u8]) -> PortExe {
+    let mut data = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .build();
+    data[0x40..0x40 + stub.len()].copy_from_slice(stub);
+    PortExe::from_bytes(data).unwrap()
+}
+
+#[test]
+fn rich_header_entries_are_decrypted() {
+    let pe = image(&rich_header(&[
+        (0x0104, 30729, 10),
+        (RICH_PRODUCT_IMPORT, 0, 50),
+        (0x0102, 30729, 1),
+    ]));
+    let rich = pe.rich_header().unwrap();
+    assert_eq!(rich.key(), KEY);
+    assert_eq!(rich.offset(), 0x40);
+    let entries: Vec<_> = rich
+        .entries()
+        .iter()
+        .map(|entry| (entry.product_id(), entry.build(), entry.count()))
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            (0x0104, 30729, 10),
+            (RICH_PRODUCT_IMPORT, 0, 50),
+            (0x0102, 30729, 1)
+        ]
+    );
+    assert_eq!(rich.linker().unwrap().product_id(), 0x0102);
+    // The import count is not a tool, however many objects it claims
+    assert_eq!(rich.primary_tool().unwrap().product_id(), 0x0104);
+
+    let profile = pe.release_profile("a.dll").unwrap();
+    assert!(profile
+        .properties()
+        .contains(&("Linker build", "product 0x102 build 30729".to_string())));
+}
+
+#[test]
+fn truncated_rich_headers_are_ignored() {
+    assert!(image(&[]).rich_header().is_none());
+    let header = rich_header(&[(0x0102, 30729, 1)]);
+
+    // Without its start marker, or with the padding after it cut short
+    let mut stub = header.clone();
+    stub[..4].fill(0);
+    assert!(image(&stub).rich_header().is_none());
+    let mut stub = header[..4].to_vec();
+    stub.extend_from_slice(&header[header.len() - 8..]);
+    assert!(image(&stub).rich_header().is_none());
+
+    // The key of a `Rich` marker in the last dword of the stub would overlap the PE header
+    let mut stub = vec![0; 0x40];
+    stub[0x3C..].copy_from_slice(&RICH.to_le_bytes());
+    assert!(image(&stub).rich_header().is_none());
+
+    // No entries at all is still a header, just one without a linker
+    let rich = image(&rich_header(&[])).rich_header().unwrap();
+    assert!(rich.entries().is_empty());
+    assert!(rich.linker().is_none());
+    assert!(rich.primary_tool().is_none());
+}
+
+#[test]
+fn rich_headers_are_bounded_by_the_pe_header() {
+    // Five entries fill the stub up to the PE header exactly
+    let entries = [(0x0104, 30729, 1); 5];
+    let header = rich_header(&entries);
+    assert_eq!(header.len(), 0x40);
+    assert_eq!(image(&header).rich_header().unwrap().entries().len(), 5);
+
+    // A header after the PE header is not looked for
+    let pe = PortExe::from_bytes(
+        PeFixture::pe32()
+            .section(".text", 0x6000_0020, &header)
+            .build(),
+    )
+    .unwrap();
+    assert!(pe.rich_header().is_none());
+}
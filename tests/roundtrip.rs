@@ -0,0 +1,225 @@
+mod common;
+
+use common::fixtures;
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+use pexp::PEType;
+use proptest::prelude::*;
+use std::io::Cursor;
+
+const FIXTURES: [(&str, &[u8]); 5] = [
+    ("PE32_EXE", fixtures::PE32_EXE),
+    ("PE32PLUS_EXE", fixtures::PE32PLUS_EXE),
+    ("PE32_DLL", fixtures::PE32_DLL),
+    ("PE32PLUS_DRIVER", fixtures::PE32PLUS_DRIVER),
+    ("AMD64_OBJECT", fixtures::AMD64_OBJECT),
+];
+
+/// Parses `data`, serializes it and parses the result again, checking that nothing changed.
+fn assert_round_trip(data: &[u8]) -> Result<(), TestCaseError> {
+    let pe = PortExe::from_bytes(data.to_vec())
+        .map_err(|error| TestCaseError::fail(error.to_string()))?;
+    let bytes = pe.to_bytes();
+    prop_assert_eq!(&bytes[..], data);
+
+    let reparsed =
+        PortExe::from_bytes(bytes).map_err(|error| TestCaseError::fail(error.to_string()))?;
+    prop_assert_eq!(
+        format!("{:?}", reparsed.file_header()),
+        format!("{:?}", pe.file_header())
+    );
+    prop_assert_eq!(
+        format!("{:?}", reparsed.optional_header()),
+        format!("{:?}", pe.optional_header())
+    );
+    prop_assert_eq!(
+        format!("{:?}", reparsed.section_headers()),
+        format!("{:?}", pe.section_headers())
+    );
+    Ok(())
+}
+
+#[test]
+fn fixtures_round_trip() {
+    for (name, data) in FIXTURES {
+        if let Err(error) = assert_round_trip(data) {
+            panic!("{}: {}", name, error);
+        }
+    }
+}
+
+#[test]
+fn fixtures_parse_as_expected() {
+    let pe32 = PortExe::from_bytes(fixtures::PE32_EXE.to_vec()).unwrap();
+    assert_eq!(pe32.pe_type(), PEType::Image);
+    assert!(pe32.optional_header().unwrap().image_type().is_x32());
+    assert_eq!(pe32.section_headers().len(), 1);
+
+    let pe64 = PortExe::from_bytes(fixtures::PE32PLUS_EXE.to_vec()).unwrap();
+    assert!(pe64.optional_header().unwrap().image_type().is_x64());
+
+    let dll = PortExe::from_bytes(fixtures::PE32_DLL.to_vec()).unwrap();
+    let names: Vec<_> = dll
+        .exports()
+        .unwrap()
+        .iter()
+        .filter_map(|export| export.name().cloned())
+        .collect();
+    assert_eq!(names, ["Add", "Sub"]);
+
+    let driver = PortExe::from_bytes(fixtures::PE32PLUS_DRIVER.to_vec()).unwrap();
+    assert_eq!(
+        driver.optional_header().unwrap().subsystem(),
+        pexp::optional_header::WindowsSubsystem::Native
+    );
+
+    let object = PortExe::from_bytes(fixtures::AMD64_OBJECT.to_vec()).unwrap();
+    assert_eq!(object.pe_type(), PEType::Object);
+    let symbols = object.coff_symbols().unwrap();
+    assert!(symbols
+        .iter()
+        .any(|symbol| symbol.name() == "main" && symbol.is_function() && symbol.is_external()));
+}
+
+/// An image parsed at an offset into a container matches the image parsed on its own.
+#[test]
+fn embedded_image_parses_in_place() {
+    let mut container = vec![0xCC; 0x1234];
+    container.extend_from_slice(fixtures::PE32_DLL);
+    container.extend_from_slice(b"trailing container data");
+    let embedded = PortExe::parse_at(&mut Cursor::new(&container), 0x1234).unwrap();
+    let standalone = PortExe::from_bytes(fixtures::PE32_DLL.to_vec()).unwrap();
+    assert_eq!(embedded.base_offset(), 0x1234);
+    assert_eq!(standalone.base_offset(), 0);
+    assert_eq!(
+        format!("{:?}", embedded.section_headers()),
+        format!("{:?}", standalone.section_headers())
+    );
+    assert_eq!(
+        format!("{:?}", embedded.exports().unwrap()),
+        format!("{:?}", standalone.exports().unwrap())
+    );
+    assert!(embedded.data().starts_with(fixtures::PE32_DLL));
+}
+
+#[test]
+fn header_spans_cover_the_parsed_structures() {
+    let image = PeFixture::pe32()
+        .characteristics(0x0102)
+        .dll_characteristics(0)
+        .section(".text", 0x6000_0020, &[0xC3])
+        .build();
+    let pe = PortExe::from_bytes(image).unwrap();
+    let spans: Vec<String> = pe
+        .header_spans()
+        .iter()
+        .map(|span| {
+            format!(
+                "{} {:#x}..{:#x}",
+                span.structure(),
+                span.range().start,
+                span.range().end
+            )
+        })
+        .collect();
+    assert_eq!(
+        spans,
+        [
+            "DOS header 0x0..0x40",
+            "PE signature 0x80..0x84",
+            "file header 0x84..0x98",
+            "optional header 0x98..0x178",
+            "section header 0 0x178..0x1a0",
+        ]
+    );
+}
+
+#[test]
+fn edits_of_overlapping_headers_are_kept() {
+    let mut image = PeFixture::pe32()
+        .characteristics(0x0102)
+        .dll_characteristics(0)
+        .section(".rdata", 0x4000_0040, &[0; 0x10])
+        .build();
+    // A SizeOfOptionalHeader of 64 puts the section table over CheckSum, Subsystem and
+    // DllCharacteristics, so the section name and those fields share their bytes
+    image[0x94..0x96].copy_from_slice(&64u16.to_le_bytes());
+    image.copy_within(0x178..0x1A0, 0xD8);
+    // NumberOfRvaAndSizes shares its bytes with PointerToLinenumbers of the section
+    image[0xF4..0xF8].copy_from_slice(&16u32.to_le_bytes());
+    let relocations = 0x98 + 96 + 5 * 8;
+    image[relocations..relocations + 8].copy_from_slice(&[0, 0x10, 0, 0, 0x10, 0, 0, 0]);
+
+    let mut pe = PortExe::from_bytes(image.clone()).unwrap();
+    assert!(pe.enable_aslr().unwrap());
+    let bytes = pe.to_bytes();
+    let reparsed = PortExe::from_bytes(bytes.clone()).unwrap();
+    assert!(reparsed
+        .optional_header()
+        .unwrap()
+        .dll_characteristics()
+        .dynamic_base());
+    // Only CheckSum and DllCharacteristics changed; the untouched section header did
+    // not write its stale copy of them back
+    let changed: Vec<usize> = (0..bytes.len())
+        .filter(|&offset| bytes[offset] != image[offset])
+        .collect();
+    assert!(changed.iter().all(|offset| (0xD8..0xE0).contains(offset)));
+    assert!(changed.contains(&0xDE));
+}
+
+/// Name, characteristics, data and zero fill of a generated section
+type SectionSpec = (String, u32, Vec<u8>, u32);
+
+fn section_spec() -> impl Strategy<Value = SectionSpec> {
+    (
+        "[.A-Za-z]{1,8}",
+        any::<u32>(),
+        proptest::collection::vec(any::<u8>(), 0..0x600),
+        0u32..0x3000,
+    )
+}
+
+fn image_spec() -> impl Strategy<Value = (PeFixture, Vec<SectionSpec>)> {
+    (
+        any::<bool>(),
+        any::<u16>(),
+        any::<u16>(),
+        any::<u32>(),
+        0u16..17,
+        any::<u16>(),
+        proptest::collection::vec(section_spec(), 0..6),
+    )
+        .prop_map(
+            |(pe64, machine, characteristics, time_date_stamp, subsystem, dll, sections)| {
+                let mut fixture = if pe64 {
+                    PeFixture::pe64()
+                } else {
+                    PeFixture::pe32()
+                }
+                .machine(machine)
+                .characteristics(characteristics)
+                .time_date_stamp(time_date_stamp)
+                .subsystem(subsystem)
+                .dll_characteristics(dll);
+                for (name, characteristics, data, bss) in &sections {
+                    fixture = fixture.section_with_bss(name, *characteristics, data, *bss);
+                }
+                (fixture, sections)
+            },
+        )
+}
+
+proptest! {
+    #[test]
+    fn generated_images_round_trip((fixture, sections) in image_spec()) {
+        let image = fixture.build();
+        assert_round_trip(&image)?;
+
+        let pe = PortExe::from_bytes(image).unwrap();
+        prop_assert_eq!(pe.section_headers().len(), sections.len());
+        for (header, (_, _, data, _)) in pe.section_headers().iter().zip(&sections) {
+            prop_assert_eq!(&pe.section_data(header)[..data.len()], &data[..]);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use pexp::export::{build_export_table, ExportSpec, ExportTarget};
+use pexp::search::{ImportQuery, ImportSymbol, MatchMode, NameMatcher};
+use pexp::testing::PeFixture;
+use std::io::ErrorKind;
+
+fn matcher(mode: MatchMode, pattern: &str) -> NameMatcher {
+    NameMatcher::new(mode, pattern).unwrap()
+}
+
+#[test]
+fn names_are_matched_in_each_mode() {
+    let exact = matcher(MatchMode::Exact, "CreateFileW");
+    assert_eq!(exact.mode(), MatchMode::Exact);
+    assert!(exact.matches("CreateFileW"));
+    assert!(!exact.matches("createfilew"));
+
+    let ignore_case = matcher(MatchMode::IgnoreCase, "CreateFileW");
+    assert!(ignore_case.matches("CREATEFILEW"));
+    assert!(!ignore_case.matches("CreateFileWEx"));
+
+    let prefix = matcher(MatchMode::Prefix, "createfile");
+    assert!(prefix.matches("CreateFileA"));
+    assert!(prefix.matches("CreateFile"));
+    assert!(!prefix.matches("Create"));
+    // The prefix would end inside a multibyte character
+    assert!(!matcher(MatchMode::Prefix, "a").matches("é"));
+}
+
+#[test]
+fn regular_expressions_need_their_feature() {
+    let result = NameMatcher::new(MatchMode::Regex, "^Create.*W$");
+    if cfg!(feature = "regex-search") {
+        let regex = result.unwrap();
+        assert!(regex.matches("CreateFileW"));
+        assert!(!regex.matches("CreateFileA"));
+        let error = NameMatcher::new(MatchMode::Regex, "(").unwrap_err();
+        assert!(error.to_string().starts_with("invalid regular expression"));
+    } else {
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+}
+
+#[test]
+fn named_exports_are_searched() {
+    let specs: Vec<_> = [
+        Some("CreateFileA"),
+        Some("CreateFileW"),
+        None,
+        Some("ReadFile"),
+    ]
+    .iter()
+    .enumerate()
+    .map(|(index, name)| {
+        ExportSpec::new(
+            name.map(str::to_string),
+            Some(index as u32 + 1),
+            ExportTarget::Rva(0x1000 + index as u32 * 0x10),
+        )
+    })
+    .collect();
+    let table = build_export_table("kernel32.dll", &specs, 0x2000).unwrap();
+    let pe = PeFixture::pe32()
+        .dll()
+        .section(".text", 0x6000_0020, &[0xC3; 0x40])
+        .section(".edata", 0x4000_0040, &table)
+        .directory(0, 0x2000, table.len() as u32)
+        .parse()
+        .unwrap();
+
+    let found: Vec<_> = pe
+        .find_exports(&matcher(MatchMode::Prefix, "createfile"))
+        .unwrap()
+        .iter()
+        .map(|export| export.ordinal())
+        .collect();
+    assert_eq!(found, [1, 2]);
+    let found = pe
+        .find_exports(&matcher(MatchMode::Exact, "ReadFile"))
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].rva(), 0x1030);
+    assert!(pe
+        .find_exports(&matcher(MatchMode::Exact, "readfile"))
+        .unwrap()
+        .is_empty());
+}
+
+fn found(query: &str, mode: MatchMode) -> Vec<(String, Option<String>, Option<u16>)> {
+    let pe = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .import("KERNEL32.dll", "CreateFileW")
+        .import("KERNEL32.dll", "ReadFile")
+        .import_ordinal("WS2_32.dll", 23)
+        .import("user32.dll", "CreateWindowExW")
+        .parse()
+        .unwrap();
+    pe.find_imports(&ImportQuery::parse(query, mode).unwrap())
+        .unwrap()
+        .iter()
+        .map(|found| {
+            let import = found.import();
+            (
+                found.dll().to_string(),
+                import
+                    .name()
+                    .map(|name| name.to_string_lossy().into_owned()),
+                import.ordinal(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn imports_are_searched_by_dll_and_function() {
+    let name = |dll: &str, name: &str| (dll.to_string(), Some(name.to_string()), None);
+    assert_eq!(
+        found("kernel32", MatchMode::Exact),
+        [
+            name("KERNEL32.dll", "CreateFileW"),
+            name("KERNEL32.dll", "ReadFile")
+        ]
+    );
+    assert_eq!(
+        found("kernel32.dll!ReadFile", MatchMode::Exact),
+        [name("KERNEL32.dll", "ReadFile")]
+    );
+    // Without a DLL, every DLL is searched
+    assert_eq!(
+        found("!create", MatchMode::Prefix),
+        [
+            name("KERNEL32.dll", "CreateFileW"),
+            name("user32.dll", "CreateWindowExW")
+        ]
+    );
+    assert_eq!(
+        found("ws2_32!#23", MatchMode::Exact),
+        [("WS2_32.dll".to_string(), None, Some(23))]
+    );
+    assert!(found("user32!readfile", MatchMode::IgnoreCase).is_empty());
+}
+
+#[test]
+fn malformed_import_queries_are_rejected() {
+    for query in ["", "!", "kernel32!", "ws2_32!#65536", "ws2_32!#x"] {
+        let error = ImportQuery::parse(query, MatchMode::Exact).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput, "{}", query);
+    }
+    let query = ImportQuery::parse("kernel32", MatchMode::Exact).unwrap();
+    assert!(matches!(query.symbol(), ImportSymbol::Any));
+    let query = ImportQuery::parse("!Sleep", MatchMode::IgnoreCase).unwrap();
+    assert!(
+        matches!(query.symbol(), ImportSymbol::Name(matcher) if matcher.mode() == MatchMode::IgnoreCase)
+    );
+}
@@ -0,0 +1,136 @@
+use pexp::analysis::{
+    compression_ratio, entropy, section_compression, SectionColumn, DEFAULT_SECTION_COLUMNS,
+};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+#[test]
+fn section_columns_describe_header_and_data() {
+    let data = PeFixture::pe32()
+        .dll_characteristics(0)
+        .section(".text", 0x6000_0020, b"abc")
+        .section(".data", 0xC000_0040, &[0; 0x10])
+        .build();
+    let pe = PortExe::from_bytes(data).unwrap();
+    let row = |index: usize, columns: &[SectionColumn]| -> Vec<String> {
+        columns
+            .iter()
+            .map(|&column| pe.section_column(&pe.section_headers()[index], column))
+            .collect()
+    };
+
+    assert_eq!(
+        row(0, &DEFAULT_SECTION_COLUMNS),
+        [".text", "0x00001000", "0x3", "0x00000200", "0x200", "r-x"]
+    );
+    assert!(!DEFAULT_SECTION_COLUMNS
+        .iter()
+        .any(|column| column.reads_data()));
+    assert_eq!(
+        row(1, &[SectionColumn::Flags, SectionColumn::Characteristics]),
+        ["rw-", "0xc0000040"]
+    );
+    // The raw data of .text is "abc" padded with zeros to the file alignment
+    assert_eq!(
+        row(0, &[SectionColumn::Md5]),
+        ["22527a32ccfbb5253e81782b28949f39"]
+    );
+    assert_eq!(row(1, &[SectionColumn::Entropy]), ["0.00"]);
+}
+
+#[test]
+fn entropy_counts_bits_per_byte() {
+    assert_eq!(entropy(&[]), 0.0);
+    assert_eq!(entropy(&[0x41; 100]), 0.0);
+    assert!((entropy(&[0, 1, 0, 1]) - 1.0).abs() < 1e-9);
+    let every_byte: Vec<u8> = (0..=255).collect();
+    assert!((entropy(&every_byte) - 8.0).abs() < 1e-9);
+}
+
+#[test]
+fn section_compression_covers_the_raw_data_of_each_section() {
+    // xorshift32 output, which deflate finds nothing to shorten in
+    let mut state = 0x2545_F491u32;
+    let noise: Vec<u8> = (0..0x200)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+    let data = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &noise)
+        .section(".data", 0xC000_0040, &[0; 0x200])
+        .build();
+    let pe = PortExe::from_bytes(data).unwrap();
+    let sections = section_compression(&pe);
+    assert_eq!(sections.len(), 2);
+    assert_eq!(sections[0].name().to_string(), ".text");
+    assert_eq!(sections[0].raw_size(), 0x200);
+    assert!(sections[0].entropy() > 7.0);
+    assert_eq!(sections[1].entropy(), 0.0);
+
+    if cfg!(feature = "compression") {
+        assert!(sections[0].compression_ratio().unwrap() > 0.9);
+        assert!(sections[1].compression_ratio().unwrap() < 0.1);
+    } else {
+        assert!(sections.iter().all(|s| s.compression_ratio().is_none()));
+    }
+    assert_eq!(compression_ratio(&[]), None);
+}
+
+/// Appends a symbol of the first section with a short name
+fn push_symbol(data: &mut Vec<u8>, name: &[u8; 8], symbol_type: u16, class: u8, aux: u8) {
+    data.extend_from_slice(name);
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&symbol_type.to_le_bytes());
+    data.extend_from_slice(&[class, aux]);
+}
+
+#[test]
+fn long_object_section_names_are_read_from_the_string_table() {
+    let name = b".text$long_comdat_name\0";
+    let mut data = Vec::new();
+    // File header: one section, three symbols at 64
+    for field in [0x14Cu16, 1] {
+        data.extend_from_slice(&field.to_le_bytes());
+    }
+    for field in [0u32, 64, 3] {
+        data.extend_from_slice(&field.to_le_bytes());
+    }
+    data.extend_from_slice(&[0; 4]);
+    // Section header: four bytes of code at 60, a COMDAT
+    data.extend_from_slice(b"/4\0\0\0\0\0\0");
+    for field in [0u32, 0, 4, 60, 0, 0] {
+        data.extend_from_slice(&field.to_le_bytes());
+    }
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(&0x6000_1020u32.to_le_bytes());
+    data.extend_from_slice(&[0x31, 0xC0, 0xC3, 0x90]);
+    // Section symbol with its definition, then the COMDAT symbol
+    push_symbol(&mut data, b".text\0\0\0", 0, 3, 1);
+    let mut definition = [0u8; 18];
+    definition[0..4].copy_from_slice(&4u32.to_le_bytes());
+    definition[14] = 2;
+    data.extend_from_slice(&definition);
+    push_symbol(&mut data, b"func\0\0\0\0", 0x20, 2, 0);
+    data.extend_from_slice(&(4 + name.len() as u32).to_le_bytes());
+    data.extend_from_slice(name);
+
+    let pe = PortExe::from_bytes(data).unwrap();
+    let section = &pe.section_headers()[0];
+    assert_eq!(section.name().into_value().to_string(), "/4");
+    assert_eq!(
+        pe.section_name(section).to_string(),
+        ".text$long_comdat_name"
+    );
+    let comdats = pe.comdats().unwrap();
+    assert_eq!(comdats.len(), 1);
+    assert_eq!(
+        comdats[0].section_name().to_string(),
+        ".text$long_comdat_name"
+    );
+    assert_eq!(comdats[0].symbol(), Some("func"));
+}
@@ -0,0 +1,507 @@
+mod common;
+
+use common::{resource_section, utf16z, version_node, ResourceKey, DATA_DIRECTORIES};
+use pexp::certificate::{
+    SignatureLayoutIssue, WIN_CERT_REVISION_2_0, WIN_CERT_TYPE_PKCS_SIGNED_DATA,
+};
+use pexp::port_exe::PortExe;
+use pexp::signing::{
+    same_organization, SigningIssue, CALG_SHA_256, EKU_CODE_SIGNING, EKU_EARLY_LAUNCH_ANTIMALWARE,
+    EKU_PROTECTED_PROCESS_LIGHT,
+};
+use pexp::testing::PeFixture;
+
+/// Size of the image [`image`] builds, where its overlay starts
+const IMAGE_SIZE: usize = 0x400;
+
+fn image() -> Vec<u8> {
+    image_with(0, vec![0xC3])
+}
+
+/// Image whose only section holds `data` at RVA 0x1000
+fn image_with(dll_characteristics: u16, data: Vec<u8>) -> Vec<u8> {
+    let image = PeFixture::pe32()
+        .dll_characteristics(dll_characteristics)
+        .section(".text", 0x6000_0020, &data)
+        .build();
+    assert_eq!(image.len(), IMAGE_SIZE);
+    image
+}
+
+/// Authenticode `WIN_CERTIFICATE` holding `data`, padded to 8 bytes
+fn win_certificate(data: &[u8]) -> Vec<u8> {
+    let length = 8 + data.len();
+    let mut entry = (length as u32).to_le_bytes().to_vec();
+    entry.extend_from_slice(&WIN_CERT_REVISION_2_0.to_le_bytes());
+    entry.extend_from_slice(&WIN_CERT_TYPE_PKCS_SIGNED_DATA.to_le_bytes());
+    entry.extend_from_slice(data);
+    entry.resize((length + 7) & !7, 0);
+    entry
+}
+
+/// `WIN_CERTIFICATE` holding a stand-in PKCS#7 structure followed by `trailing`
+fn certificate(trailing: &[u8]) -> Vec<u8> {
+    let mut data = vec![0x30, 0x03, 0x06, 0x01, 0x2A];
+    data.extend_from_slice(trailing);
+    win_certificate(&data)
+}
+
+/// Places `table` at `offset` and points the security directory at it
+fn sign(mut data: Vec<u8>, offset: usize, table: &[u8]) -> Vec<u8> {
+    data.resize(offset, 0xEE);
+    data.extend_from_slice(table);
+    let directory = DATA_DIRECTORIES + 4 * 8;
+    data[directory..directory + 4].copy_from_slice(&(offset as u32).to_le_bytes());
+    data[directory + 4..directory + 8].copy_from_slice(&(table.len() as u32).to_le_bytes());
+    data
+}
+
+fn issues(data: Vec<u8>) -> Vec<SignatureLayoutIssue> {
+    PortExe::from_bytes(data)
+        .unwrap()
+        .signature_layout()
+        .unwrap()
+}
+
+#[test]
+fn overlay_starts_after_the_last_section() {
+    let mut data = image();
+    assert_eq!(
+        PortExe::from_bytes(data.clone()).unwrap().overlay_offset(),
+        IMAGE_SIZE
+    );
+    data.extend_from_slice(&[1; 0x30]);
+    let pe = PortExe::from_bytes(data).unwrap();
+    assert_eq!(pe.overlay_offset(), IMAGE_SIZE);
+    assert_eq!(pe.data().len(), IMAGE_SIZE + 0x30);
+}
+
+#[test]
+fn table_covering_the_overlay_is_clean() {
+    assert!(issues(image()).is_empty());
+    let signed = sign(image(), IMAGE_SIZE, &certificate(&[]));
+    assert_eq!(
+        PortExe::from_bytes(signed.clone())
+            .unwrap()
+            .certificates()
+            .unwrap()
+            .len(),
+        1
+    );
+    assert!(issues(signed).is_empty());
+}
+
+#[test]
+fn data_appended_after_the_signature_is_reported() {
+    let mut data = sign(image(), IMAGE_SIZE, &certificate(&[]));
+    data.extend_from_slice(b"patched");
+    assert_eq!(
+        issues(data),
+        [SignatureLayoutIssue::DataAfterTable {
+            offset: IMAGE_SIZE + 16,
+            size: 7
+        }]
+    );
+}
+
+#[test]
+fn data_inside_the_entry_is_reported() {
+    let data = sign(image(), IMAGE_SIZE, &certificate(b"extra payload"));
+    let issues = issues(data);
+    assert_eq!(
+        issues,
+        [SignatureLayoutIssue::DataAfterSignedData {
+            offset: IMAGE_SIZE + 8 + 5,
+            size: 13
+        }]
+    );
+    assert_eq!(
+        issues[0].to_string(),
+        format!(
+            "data appended inside the signature entry: 13 bytes at {:#x}",
+            IMAGE_SIZE + 13
+        )
+    );
+}
+
+#[test]
+fn misplaced_tables_are_reported() {
+    assert_eq!(
+        issues(sign(image(), IMAGE_SIZE + 0x20, &certificate(&[]))),
+        [SignatureLayoutIssue::DataBeforeTable {
+            offset: IMAGE_SIZE,
+            size: 0x20
+        }]
+    );
+    assert_eq!(
+        issues(sign(image(), IMAGE_SIZE + 4, &certificate(&[]))),
+        [
+            SignatureLayoutIssue::UnalignedTable {
+                offset: IMAGE_SIZE + 4
+            },
+            SignatureLayoutIssue::DataBeforeTable {
+                offset: IMAGE_SIZE,
+                size: 4
+            },
+        ]
+    );
+}
+
+const FORCE_INTEGRITY: u16 = 0x0080;
+const OID_SIGNED_DATA: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x07, 0x02];
+const OID_EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1D, 0x25];
+const OID_CODE_SIGNING: &[u8] = &[0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x03];
+const OID_ELAM: &[u8] = &[0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0x37, 0x3D, 0x04, 0x01];
+const OID_PROTECTED_PROCESS_LIGHT: &[u8] =
+    &[0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0x37, 0x0A, 0x03, 0x16];
+
+fn der(tag: u8, parts: &[&[u8]]) -> Vec<u8> {
+    let content = parts.concat();
+    let mut element = vec![tag];
+    if content.len() < 0x80 {
+        element.push(content.len() as u8);
+    } else {
+        element.extend_from_slice(&[0x82, (content.len() >> 8) as u8, content.len() as u8]);
+    }
+    element.extend_from_slice(&content);
+    element
+}
+
+/// Authenticode entry whose signer certificate carries the EKUs `usages`, marked
+/// critical if `critical` is set
+fn signature(usages: &[&[u8]], critical: bool) -> Vec<u8> {
+    signature_by("Test", usages, critical)
+}
+
+/// [`signature`] by a signer with the common name `signer`
+fn signature_by(signer: &str, usages: &[&[u8]], critical: bool) -> Vec<u8> {
+    let name = der(
+        0x30,
+        &[&der(
+            0x31,
+            &[&der(
+                0x30,
+                &[
+                    &der(0x06, &[&[0x55, 0x04, 0x03]]),
+                    &der(0x13, &[signer.as_bytes()]),
+                ],
+            )],
+        )],
+    );
+    let serial = der(0x02, &[&[0x01, 0x02]]);
+    let usages: Vec<Vec<u8>> = usages.iter().map(|oid| der(0x06, &[oid])).collect();
+    let usages: Vec<&[u8]> = usages.iter().map(|oid| &oid[..]).collect();
+    let flag = if critical {
+        der(0x01, &[&[0xFF]])
+    } else {
+        Vec::new()
+    };
+    let extension = der(
+        0x30,
+        &[
+            &der(0x06, &[OID_EXTENDED_KEY_USAGE]),
+            &flag,
+            &der(0x04, &[&der(0x30, &usages)]),
+        ],
+    );
+    let tbs = der(
+        0x30,
+        &[
+            &der(0xA0, &[&der(0x02, &[&[2]])]),
+            &serial,
+            &der(0x30, &[]),
+            &name,
+            &der(0x30, &[]),
+            &name,
+            &der(0x30, &[]),
+            &der(0xA3, &[&der(0x30, &[&extension])]),
+        ],
+    );
+    let certificate = der(0x30, &[&tbs, &der(0x30, &[]), &der(0x03, &[&[0]])]);
+    let signer_info = der(0x30, &[&der(0x02, &[&[1]]), &der(0x30, &[&name, &serial])]);
+    let signed_data = der(
+        0x30,
+        &[
+            &der(0x02, &[&[1]]),
+            &der(0x31, &[]),
+            &der(0x30, &[]),
+            &der(0xA0, &[&certificate]),
+            &der(0x31, &[&signer_info]),
+        ],
+    );
+    let content_info = der(
+        0x30,
+        &[&der(0x06, &[OID_SIGNED_DATA]), &der(0xA0, &[&signed_data])],
+    );
+    win_certificate(&content_info)
+}
+
+/// Resource section at RVA 0x1000 holding a `MSElamCertInfoID` resource that allows one
+/// certificate
+fn elam_resource() -> Vec<u8> {
+    let mut elam = 1u16.to_le_bytes().to_vec();
+    elam.extend_from_slice(&utf16z("0123456789ABCDEF"));
+    elam.extend_from_slice(&CALG_SHA_256.to_le_bytes());
+    elam.extend_from_slice(&utf16z(";1.3.6.1.4.1.311.61.4.1;"));
+    resource_section(
+        0x1000,
+        &[(
+            ResourceKey::Name("MSELAMCERTINFOID"),
+            ResourceKey::Name("MICROSOFTELAMCERTIFICATEINFO"),
+            &elam,
+        )],
+    )
+}
+
+/// Image with the resource section from [`elam_resource`], its directory set
+fn elam_driver(dll_characteristics: u16) -> Vec<u8> {
+    let resources = elam_resource();
+    let size = resources.len() as u32;
+    let mut data = image_with(dll_characteristics, resources);
+    let directory = DATA_DIRECTORIES + 2 * 8;
+    data[directory..directory + 4].copy_from_slice(&0x1000u32.to_le_bytes());
+    data[directory + 4..directory + 8].copy_from_slice(&size.to_le_bytes());
+    data
+}
+
+#[test]
+fn signer_extended_key_usages_are_decoded() {
+    for critical in [false, true] {
+        let data = sign(
+            image(),
+            IMAGE_SIZE,
+            &signature(&[OID_CODE_SIGNING, OID_ELAM], critical),
+        );
+        let pe = PortExe::from_bytes(data).unwrap();
+        let signer = pe.signer().unwrap().unwrap();
+        assert_eq!(signer.common_name(), Some("Test"));
+        assert_eq!(
+            signer.extended_key_usages(),
+            [EKU_CODE_SIGNING, EKU_EARLY_LAUNCH_ANTIMALWARE]
+        );
+    }
+}
+
+#[test]
+fn signer_certificate_is_found_in_the_signed_data() {
+    let data = sign(image(), IMAGE_SIZE, &signature(&[OID_CODE_SIGNING], false));
+    let certificates = PortExe::from_bytes(data).unwrap().certificates().unwrap();
+    assert_eq!(certificates.len(), 1);
+    let certificate = &certificates[0];
+    assert_eq!(certificate.offset(), IMAGE_SIZE);
+    assert_eq!(certificate.revision(), WIN_CERT_REVISION_2_0);
+    assert_eq!(
+        certificate.certificate_type(),
+        WIN_CERT_TYPE_PKCS_SIGNED_DATA
+    );
+    let signer = certificate.signer().unwrap();
+    assert_eq!(signer.subject(), "CN=Test");
+    assert_eq!(signer.issuer(), "CN=Test");
+    assert_eq!(signer.serial_number(), [0x01, 0x02]);
+    assert_eq!(signer.serial_number_hex(), "01:02");
+}
+
+#[test]
+fn truncated_signed_data_has_no_signer() {
+    let entry = signature(&[OID_CODE_SIGNING], false);
+    let length = u32::from_le_bytes(entry[..4].try_into().unwrap()) as usize;
+    for cut in [9, length / 2, length - 1] {
+        let data = sign(image(), IMAGE_SIZE, &win_certificate(&entry[8..cut]));
+        let pe = PortExe::from_bytes(data).unwrap();
+        assert_eq!(pe.certificates().unwrap().len(), 1);
+        assert_eq!(pe.signer().unwrap(), None, "cut at {}", cut);
+    }
+
+    // A table running past the end of the file, or an entry past the end of the table
+    let mut data = sign(image(), IMAGE_SIZE, &entry);
+    data.truncate(data.len() - 8);
+    assert!(PortExe::from_bytes(data).unwrap().certificates().is_err());
+    let mut table = entry.clone();
+    table[..4].copy_from_slice(&(entry.len() as u32 + 8).to_le_bytes());
+    let data = sign(image(), IMAGE_SIZE, &table);
+    assert!(PortExe::from_bytes(data).unwrap().certificates().is_err());
+}
+
+#[test]
+fn oversized_der_lengths_are_rejected() {
+    let entry = signature(&[OID_CODE_SIGNING], false);
+    // The ContentInfo SEQUENCE uses a two-byte long-form length
+    assert_eq!(entry[8..10], [0x30, 0x82]);
+    for length in [
+        &[0x82, 0xFF, 0xFF][..],
+        &[0x84, 0x7F, 0xFF, 0xFF, 0xFF],
+        &[0x85, 0, 0, 0, 0, 1],
+    ] {
+        let mut data = vec![0x30];
+        data.extend_from_slice(length);
+        data.extend_from_slice(&entry[12..]);
+        let data = sign(image(), IMAGE_SIZE, &win_certificate(&data));
+        let pe = PortExe::from_bytes(data).unwrap();
+        assert_eq!(pe.signer().unwrap(), None, "length {:02x?}", length);
+        assert!(pe.signature_layout().unwrap().is_empty());
+    }
+}
+
+#[test]
+fn elam_drivers_need_integrity_check_and_elam_signer() {
+    let data = sign(
+        elam_driver(FORCE_INTEGRITY),
+        IMAGE_SIZE,
+        &signature(&[OID_ELAM], false),
+    );
+    let requirements = PortExe::from_bytes(data)
+        .unwrap()
+        .signing_requirements()
+        .unwrap();
+    assert!(requirements.issues().is_empty());
+    let certificates = requirements.elam_certificates().unwrap();
+    assert_eq!(certificates.len(), 1);
+    assert_eq!(certificates[0].hash(), "0123456789ABCDEF");
+    assert_eq!(certificates[0].algorithm(), CALG_SHA_256);
+    assert_eq!(
+        certificates[0].extended_key_usages(),
+        [EKU_EARLY_LAUNCH_ANTIMALWARE]
+    );
+
+    let data = sign(
+        elam_driver(0),
+        IMAGE_SIZE,
+        &signature(&[OID_CODE_SIGNING], false),
+    );
+    let requirements = PortExe::from_bytes(data)
+        .unwrap()
+        .signing_requirements()
+        .unwrap();
+    assert_eq!(
+        requirements.issues(),
+        [
+            SigningIssue::ElamWithoutIntegrityCheck,
+            SigningIssue::ElamSignerWithoutElamEku
+        ]
+    );
+}
+
+#[test]
+fn integrity_check_needs_a_signature() {
+    let pe = PortExe::from_bytes(image_with(FORCE_INTEGRITY, vec![0xC3])).unwrap();
+    let requirements = pe.signing_requirements().unwrap();
+    assert!(requirements.elam_certificates().is_none());
+    assert_eq!(
+        requirements.issues(),
+        [SigningIssue::IntegrityCheckWithoutSignature]
+    );
+
+    let data = sign(
+        image(),
+        IMAGE_SIZE,
+        &signature(&[OID_PROTECTED_PROCESS_LIGHT], false),
+    );
+    let requirements = PortExe::from_bytes(data)
+        .unwrap()
+        .signing_requirements()
+        .unwrap();
+    assert_eq!(
+        requirements.issues(),
+        [SigningIssue::ProtectedSignerWithoutIntegrityCheck {
+            eku: EKU_PROTECTED_PROCESS_LIGHT
+        }]
+    );
+}
+
+/// Image with a version resource holding `strings`, signed by `signer`
+fn signed_with_version(strings: &[(&str, &str)], signer: &str) -> PortExe {
+    let strings: Vec<Vec<u8>> = strings
+        .iter()
+        .map(|(key, value)| version_node(key, &utf16z(value), true, &[]))
+        .collect();
+    let table = version_node("040904b0", &[], true, &strings);
+    let file_info = version_node("StringFileInfo", &[], true, &[table]);
+    let version = version_node("VS_VERSION_INFO", &[], false, &[file_info]);
+    let resources = resource_section(
+        0x1000,
+        &[(ResourceKey::Id(16), ResourceKey::Id(1), &version)],
+    );
+    let size = resources.len() as u32;
+    let mut data = image_with(0, resources);
+    let directory = DATA_DIRECTORIES + 2 * 8;
+    data[directory..directory + 4].copy_from_slice(&0x1000u32.to_le_bytes());
+    data[directory + 4..directory + 8].copy_from_slice(&size.to_le_bytes());
+    let data = sign(
+        data,
+        IMAGE_SIZE,
+        &signature_by(signer, &[OID_CODE_SIGNING], false),
+    );
+    common::exercise(&data);
+    PortExe::from_bytes(data).unwrap()
+}
+
+#[test]
+fn version_claiming_another_company_is_flagged() {
+    let pe = signed_with_version(
+        &[
+            ("CompanyName", "Microsoft Corporation"),
+            ("ProductName", "Microsoft Word"),
+        ],
+        "Evil Software Ltd",
+    );
+    let requirements = pe.signing_requirements().unwrap();
+    assert_eq!(
+        requirements.issues(),
+        [SigningIssue::SignerIdentityMismatch {
+            company: Some("Microsoft Corporation".to_string()),
+            product: Some("Microsoft Word".to_string()),
+            signer: "Evil Software Ltd".to_string(),
+        }]
+    );
+    assert_eq!(
+        requirements.issues()[0].to_string(),
+        "version resource names Microsoft Corporation / Microsoft Word but the image is \
+         signed by Evil Software Ltd"
+    );
+}
+
+#[test]
+fn signer_of_the_claimed_company_or_product_is_accepted() {
+    for (strings, signer) in [
+        (
+            &[("CompanyName", "Microsoft Corporation")][..],
+            "Microsoft Windows",
+        ),
+        (&[("CompanyName", "Contoso, Inc.")][..], "CONTOSO"),
+        (
+            &[("CompanyName", "Fabrikam Labs"), ("ProductName", "Widget")][..],
+            "Fabrikam Labs GmbH",
+        ),
+        (
+            &[("CompanyName", " "), ("ProductName", "Google Chrome")][..],
+            "Google LLC",
+        ),
+        (&[("FileDescription", "Tool")][..], "Anyone"),
+    ] {
+        let pe = signed_with_version(strings, signer);
+        assert!(
+            pe.signing_requirements().unwrap().issues().is_empty(),
+            "{:?} signed by {}",
+            strings,
+            signer
+        );
+    }
+}
+
+#[test]
+fn organization_names_compare_without_legal_forms() {
+    assert!(same_organization(
+        "Microsoft Corporation",
+        "Microsoft Corp."
+    ));
+    assert!(same_organization(
+        "The Document Foundation",
+        "Document Foundation"
+    ));
+    assert!(same_organization("Ltd.", "Anyone"));
+    assert!(!same_organization("Adobe Inc.", "Acrobat Tools Inc"));
+    assert!(!same_organization(
+        "Microsoft Corporation",
+        "Micro Soft Ltd"
+    ));
+}
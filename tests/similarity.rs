@@ -0,0 +1,150 @@
+use pexp::digest::{md5, to_hex};
+use pexp::port_exe::PortExe;
+use pexp::similarity::{cluster, distance, Fingerprint, DEFAULT_CLUSTER_THRESHOLD};
+use pexp::testing::PeFixture;
+
+/// Offset of the delay-load import directory entry in a PE32 image built by `PeFixture`
+const DELAY_IMPORT_DIRECTORY: usize = 0x80 + 4 + 20 + 96 + 13 * 8;
+
+fn fingerprint(name: &str, fixture: PeFixture) -> Fingerprint {
+    fixture.parse().unwrap().fingerprint(name).unwrap()
+}
+
+/// Executable with a `.text` section of `size` bytes importing `functions` from kernel32
+fn program(size: usize, functions: &[&str]) -> PeFixture {
+    functions.iter().fold(
+        PeFixture::pe32().section(".text", 0x6000_0020, &vec![0xC3; size]),
+        |fixture, function| fixture.import("KERNEL32.dll", function),
+    )
+}
+
+#[test]
+fn md5_matches_the_rfc_test_suite() {
+    let hex = |data: &[u8]| to_hex(&md5(data));
+    assert_eq!(hex(b""), "d41d8cd98f00b204e9800998ecf8427e");
+    assert_eq!(hex(b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+    assert_eq!(hex(b"message digest"), "f96b697d7cb7938d525a2f31aaf161d0");
+    // Longer than one block, with the length spilling into a second padding block
+    assert_eq!(
+        hex(b"12345678901234567890123456789012345678901234567890123456789012345678901234567890"),
+        "57edf4a22be3c955ac49da2e2107b67a"
+    );
+}
+
+/// `.didat` section at RVA 0x2000 delay-loading `MessageBoxA` and ordinal 5 from
+/// USER32.dll, and the delay-load import directory entry to point at it
+fn delay_loads() -> (Vec<u8>, [u8; 8]) {
+    let mut didat = vec![0u8; 0xA0];
+    for (offset, value) in [
+        (0x00, 1u32),
+        (0x04, 0x2040),
+        (0x0C, 0x2080),
+        (0x10, 0x2050),
+        (0x50, 0x2060),
+        (0x54, 0x8000_0005),
+        (0x80, 0x40_3000),
+        (0x84, 0x40_3010),
+    ] {
+        didat[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    didat[0x40..0x4A].copy_from_slice(b"USER32.dll");
+    didat[0x62..0x6D].copy_from_slice(b"MessageBoxA");
+    (didat, [0, 0x20, 0, 0, 0x40, 0, 0, 0])
+}
+
+#[test]
+fn imports_are_normalized_the_way_imphash_expects() {
+    let (didat, directory) = delay_loads();
+    let mut data = program(0x10, &["CreateFileW", "ReadFile"])
+        .section(".didat", 0xC000_0040, &didat)
+        .import_ordinal("WS2_32.dll", 23)
+        .import_ordinal("foo.exe", 9)
+        .build();
+    data[DELAY_IMPORT_DIRECTORY..DELAY_IMPORT_DIRECTORY + 8].copy_from_slice(&directory);
+    let fingerprint = PortExe::from_bytes(data)
+        .unwrap()
+        .fingerprint("a.exe")
+        .unwrap();
+    assert_eq!(fingerprint.name(), "a.exe");
+    assert_eq!(
+        fingerprint.imports(),
+        [
+            "kernel32.createfilew",
+            "kernel32.readfile",
+            // Ordinals are named from the bundled map where it knows them
+            "ws2_32.socket",
+            "foo.exe.ord9",
+        ]
+    );
+    assert_eq!(
+        fingerprint.delay_imports(),
+        ["user32.messageboxa", "user32.ord5"]
+    );
+    // pefile's get_imphash of the same imports; it ignores the delay-load directory
+    assert_eq!(
+        fingerprint.imphash().as_deref(),
+        Some("1077bfed5892eee3d8dc3fdbbe2e6442")
+    );
+    assert_eq!(
+        fingerprint.delay_imphash().as_deref(),
+        Some("b3436006f0015183842a700db8f6e153")
+    );
+    assert_eq!(fingerprint.sections()[0], (".text".into(), 0x10));
+    assert!(fingerprint.rich_hash().is_none());
+    let empty = self::fingerprint("b.exe", program(0x10, &[]));
+    assert_eq!((empty.imphash(), empty.delay_imphash()), (None, None));
+}
+
+#[test]
+fn rich_headers_are_hashed_decoded() {
+    let mut data = program(0x10, &[]).build();
+    let key = 0x1234_5678u32;
+    let rich: Vec<u8> = [0x536E_6144 ^ key, key, key, key, 0x0102_0001 ^ key, 1 ^ key]
+        .iter()
+        .chain(&[0x6863_6952, key])
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    data[0x40..0x40 + rich.len()].copy_from_slice(&rich);
+    let fingerprint = PortExe::from_bytes(data).unwrap().fingerprint("a").unwrap();
+    assert_eq!(
+        fingerprint.rich_hash(),
+        Some("9f6794d760d019f2d481a9f60f547e05")
+    );
+}
+
+#[test]
+fn distances_weight_the_features_both_files_have() {
+    let a = fingerprint("a", program(0x10, &["CreateFileW", "ReadFile"]));
+    assert_eq!(distance(&a, &a), 0.0);
+    // Only sections to compare, one twice the size of the other
+    let small = fingerprint("small", program(0x10, &[]));
+    let large = fingerprint("large", program(0x20, &[]));
+    assert!((distance(&small, &large) - 0.5).abs() < 1e-9);
+    // One of three imports shared, weighted equally with identical sections
+    let b = fingerprint("b", program(0x10, &["CreateFileW", "SetEvent"]));
+    assert_eq!(a.sections(), b.sections());
+    assert!((distance(&a, &b) - 1.0 / 3.0).abs() < 1e-9);
+    // Imports and the import section only one file has count in full
+    assert!((distance(&a, &small) - 0.75).abs() < 1e-9);
+}
+
+#[test]
+fn close_files_are_clustered_through_each_other() {
+    let fingerprints = [
+        fingerprint(
+            "a",
+            program(0x10, &["CreateFileW", "ReadFile", "WriteFile"]),
+        ),
+        fingerprint("far", program(0x100, &["Sleep"])),
+        fingerprint("b", program(0x10, &["CreateFileW", "ReadFile"])),
+        fingerprint("c", program(0x10, &["CreateFileW"])),
+    ];
+    let members: Vec<_> = cluster(&fingerprints, DEFAULT_CLUSTER_THRESHOLD)
+        .iter()
+        .map(|cluster| cluster.members().to_vec())
+        .collect();
+    // `a` and `c` are only linked through `b`
+    assert!(distance(&fingerprints[0], &fingerprints[3]) >= DEFAULT_CLUSTER_THRESHOLD);
+    assert_eq!(members, [vec!["a", "b", "c"], vec!["far"]]);
+    assert_eq!(cluster(&fingerprints, 0.0).len(), 4);
+}
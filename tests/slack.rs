@@ -0,0 +1,113 @@
+mod common;
+
+use common::{fixtures, put32, DATA_DIRECTORIES};
+use pexp::port_exe::PortExe;
+use pexp::slack::SlackKind;
+use pexp::testing::{PeFixture, FIXTURE_SECTION_ALIGNMENT};
+use pexp::PeString;
+
+/// File offset of the second entry of the section table
+const SECOND_SECTION: usize = 0x80 + 4 + 20 + 224 + 40;
+const RDATA_RVA: u32 = 2 * FIXTURE_SECTION_ALIGNMENT;
+
+/// PE32 image with `.text` at 0x200 and `.rdata` at 0x400, whose export and IAT
+/// directories are 8 bytes apart
+fn image() -> Vec<u8> {
+    let mut image = PeFixture::pe32()
+        .dll_characteristics(0)
+        .section(".text", 0x4000_0040, &[0; 0x10])
+        .section(".rdata", 0x4000_0040, &[0; 0x40])
+        .build();
+    put32(&mut image, DATA_DIRECTORIES, RDATA_RVA);
+    put32(&mut image, DATA_DIRECTORIES + 4, 0x10);
+    put32(&mut image, DATA_DIRECTORIES + 12 * 8, RDATA_RVA + 0x18);
+    put32(&mut image, DATA_DIRECTORIES + 12 * 8 + 4, 8);
+    image
+}
+
+fn kinds(pe: &PortExe) -> Vec<(usize, usize, SlackKind, bool)> {
+    pe.slack_regions()
+        .unwrap()
+        .iter()
+        .map(|region| {
+            let range = region.range();
+            (
+                range.start,
+                range.end,
+                region.kind().clone(),
+                region.is_zero(),
+            )
+        })
+        .collect()
+}
+
+fn section_tail(name: &str) -> SlackKind {
+    SlackKind::SectionTail {
+        section: PeString::from_bytes(name),
+    }
+}
+
+#[test]
+fn linker_padding_is_listed_as_zero() {
+    let pe = PortExe::from_bytes(image()).unwrap();
+    assert_eq!(
+        kinds(&pe),
+        [
+            (0x1C8, 0x200, SlackKind::HeaderPadding, true),
+            (0x210, 0x400, section_tail(".text"), true),
+            (
+                0x410,
+                0x418,
+                SlackKind::DirectoryGap {
+                    before: 0,
+                    after: 12
+                },
+                true
+            ),
+            (0x440, 0x600, section_tail(".rdata"), true),
+        ]
+    );
+}
+
+#[test]
+fn hidden_data_is_reported_and_extracted() {
+    let mut data = image();
+    data[0x1E0..0x1EC].copy_from_slice(b"watermark-01");
+    data[0x300..0x304].copy_from_slice(&[0x90, 0x00, 0xFF, 0x41]);
+    data[0x410..0x418].copy_from_slice(b"abcdefgh");
+    // Move .rdata up by a block and fill the gap it leaves
+    data.splice(0x400..0x400, (0..=255u8).cycle().take(0x200));
+    put32(&mut data, SECOND_SECTION + 20, 0x600);
+    let pe = PortExe::from_bytes(data).unwrap();
+
+    let regions = pe.slack_regions().unwrap();
+    let hidden: Vec<_> = regions.iter().filter(|region| !region.is_zero()).collect();
+    assert_eq!(hidden.len(), 4);
+
+    assert_eq!(hidden[0].kind(), &SlackKind::HeaderPadding);
+    assert_eq!(hidden[0].nonzero_bytes(), 12);
+    assert_eq!(hidden[0].strings(), [(0x1E0, "watermark-01".to_string())]);
+
+    assert_eq!(hidden[1].kind(), &section_tail(".text"));
+    assert_eq!(hidden[1].nonzero_bytes(), 3);
+    assert!(hidden[1].strings().is_empty());
+
+    assert_eq!(hidden[2].kind(), &SlackKind::FileGap);
+    assert_eq!(hidden[2].range(), 0x400..0x600);
+    assert!((hidden[2].entropy() - 8.0).abs() < 1e-9);
+    assert_eq!(
+        pe.slack_data(hidden[2]),
+        &(0..=255u8).cycle().take(0x200).collect::<Vec<_>>()[..]
+    );
+
+    assert_eq!(hidden[3].range(), 0x610..0x618);
+    assert_eq!(pe.slack_data(hidden[3]), b"abcdefgh");
+}
+
+#[test]
+fn mapped_images_and_objects_are_refused() {
+    let pe = PortExe::from_mapped_bytes(image()).unwrap();
+    assert!(pe.slack_regions().is_err());
+    let object = PortExe::from_bytes(fixtures::AMD64_OBJECT.to_vec()).unwrap();
+    assert!(object.slack_regions().is_err());
+}
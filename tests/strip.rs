@@ -0,0 +1,241 @@
+use pexp::debug::{
+    IMAGE_DEBUG_TYPE_CODEVIEW, IMAGE_DEBUG_TYPE_EX_DLLCHARACTERISTICS, IMAGE_DEBUG_TYPE_MISC,
+};
+use pexp::port_exe::PortExe;
+use pexp::split::{DebugContainer, DEBUG_CONTAINER_MAGIC, IMAGE_DEBUG_MISC_EXENAME};
+use pexp::strip::StripOptions;
+use pexp::testing::PeFixture;
+
+const DEBUG: usize = 6;
+const BASERELOC: usize = 5;
+const SECURITY: usize = 4;
+/// Size of the symbol table and the empty string table appended by [`image`]
+const SYMBOLS: usize = 2 * 18 + 4;
+
+/// `.rdata` holding a debug directory with a CodeView entry and an
+/// `EX_DLLCHARACTERISTICS` entry, at RVA 0x2000 and file offset 0x400
+fn rdata() -> Vec<u8> {
+    let mut rdata = vec![0u8; 0x70];
+    for (index, (debug_type, rva, size)) in [
+        (IMAGE_DEBUG_TYPE_CODEVIEW, 0x2040u32, 0x18u32),
+        (IMAGE_DEBUG_TYPE_EX_DLLCHARACTERISTICS, 0x2060, 4),
+    ]
+    .iter()
+    .enumerate()
+    {
+        let entry = index * 28;
+        for (offset, value) in [
+            (12, *debug_type),
+            (16, *size),
+            (20, *rva),
+            (24, rva - 0x1C00),
+        ] {
+            rdata[entry + offset..entry + offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+    }
+    rdata[0x40..0x44].copy_from_slice(b"RSDS");
+    rdata[0x60] = 1;
+    rdata
+}
+
+/// Executable with a Rich header, a debug directory, a trailing `.reloc` section and
+/// a COFF symbol table after it
+fn image(fixture: PeFixture) -> Vec<u8> {
+    let mut data = fixture
+        .section(".text", 0x6000_0020, &[0xC3; 0x10])
+        .section(".rdata", 0x4000_0040, &rdata())
+        .section(".reloc", 0x4200_0040, &[0, 0x10, 0, 0, 8, 0, 0, 0])
+        .directory(DEBUG, 0x2000, 56)
+        .directory(BASERELOC, 0x3000, 8)
+        .build();
+
+    let key = 0x1234_5678u32;
+    let rich: Vec<u8> = [0x536E_6144 ^ key, key, key, key, 0x0102_0001 ^ key, 1 ^ key]
+        .iter()
+        .chain(&[0x6863_6952, key])
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    data[0x40..0x40 + rich.len()].copy_from_slice(&rich);
+
+    let symbols = data.len() as u32;
+    data[0x8C..0x90].copy_from_slice(&symbols.to_le_bytes());
+    data[0x90..0x94].copy_from_slice(&2u32.to_le_bytes());
+    data.extend_from_slice(&[0x11; 2 * 18]);
+    data.extend_from_slice(&4u32.to_le_bytes());
+    data
+}
+
+#[test]
+fn build_metadata_is_stripped() {
+    let original = image(PeFixture::pe32());
+    let mut pe = PortExe::from_bytes(original.clone()).unwrap();
+    assert!(pe.rich_header().is_some());
+    let report = pe.strip(&StripOptions::default()).unwrap();
+    assert_eq!(report.debug_entries(), 1);
+    assert!(report.rich_header());
+    assert_eq!(report.symbols(), 2);
+    assert!(!report.relocations());
+    assert_eq!(report.bytes_removed(), SYMBOLS);
+    assert!(report.skipped().is_empty());
+
+    let data = pe.to_bytes();
+    assert_eq!(data.len(), original.len() - SYMBOLS);
+    assert!(pe.rich_header().is_none());
+    assert_eq!(pe.file_header().number_of_symbols().into_value(), 0);
+    let entries = pe.debug_directory().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        entries[0].debug_type(),
+        IMAGE_DEBUG_TYPE_EX_DLLCHARACTERISTICS
+    );
+    // The CodeView record is gone, the CET flags it sat next to are not
+    assert_eq!(data[0x440..0x444], [0; 4]);
+    assert_eq!(data[0x460], 1);
+    assert_eq!(pe.section_headers().len(), 3);
+
+    let report = pe.strip(&StripOptions::default()).unwrap();
+    assert!(report.is_empty());
+}
+
+#[test]
+fn relocations_of_executables_are_stripped_with_their_section() {
+    let mut pe = PortExe::from_bytes(image(PeFixture::pe32())).unwrap();
+    let options = StripOptions {
+        debug: false,
+        rich_header: false,
+        symbols: false,
+        relocations: true,
+    };
+    let report = pe.strip(&options).unwrap();
+    assert!(report.relocations());
+    assert_eq!(report.debug_entries(), 0);
+    assert!(pe.rich_header().is_some());
+    // The symbols after the relocations keep them from being cut off
+    assert_eq!(report.bytes_removed(), 0);
+
+    assert_eq!(pe.section_headers().len(), 2);
+    let header = pe.optional_header().unwrap();
+    assert_eq!(header.size_of_image(), 0x3000);
+    let characteristics = pe.file_header().characteristics().into_value();
+    assert!(characteristics.relocs_stripped());
+    assert!(!header.dll_characteristics().dynamic_base());
+
+    let mut pe = PortExe::from_bytes(image(PeFixture::pe32())).unwrap();
+    let report = pe
+        .strip(&StripOptions {
+            relocations: true,
+            ..StripOptions::default()
+        })
+        .unwrap();
+    assert_eq!(report.bytes_removed(), 0x200 + SYMBOLS);
+    assert_eq!(pe.data().len(), 0x600);
+}
+
+#[test]
+fn signed_files_and_relocations_of_dlls_are_kept() {
+    let signed = image(PeFixture::pe32().directory(SECURITY, 0x700, 8));
+    let mut pe = PortExe::from_bytes(signed.clone()).unwrap();
+    assert!(pe.strip(&StripOptions::default()).is_err());
+    assert_eq!(pe.to_bytes(), signed);
+
+    let mut pe = PortExe::from_bytes(image(PeFixture::pe32().dll())).unwrap();
+    let options = StripOptions {
+        relocations: true,
+        ..StripOptions::default()
+    };
+    let error = pe.strip(&options).unwrap_err();
+    assert!(error.to_string().starts_with("DLLs need their relocations"));
+}
+
+#[test]
+fn symbols_are_kept_while_section_names_refer_to_them() {
+    let mut data = image(PeFixture::pe32());
+    // First section header, after the 0xE0 bytes of the PE32 optional header
+    data[0x178..0x180].copy_from_slice(b"/4\0\0\0\0\0\0");
+    let mut pe = PortExe::from_bytes(data).unwrap();
+    let report = pe.strip(&StripOptions::default()).unwrap();
+    assert_eq!(report.symbols(), 0);
+    assert_eq!(
+        report.skipped(),
+        ["symbols: section names refer to the string table"]
+    );
+    assert_eq!(pe.file_header().number_of_symbols().into_value(), 2);
+}
+
+#[test]
+fn debug_information_is_split_into_a_sidecar() {
+    let original = image(PeFixture::pe32());
+    let mut pe = PortExe::from_bytes(original.clone()).unwrap();
+    let container = pe.split_debug("app.dbg", false).unwrap();
+    assert!(container.matches(&pe));
+    assert_eq!(container.payloads().len(), 1);
+    let codeview = &container.payloads()[0];
+    assert_eq!(codeview.entry().debug_type(), IMAGE_DEBUG_TYPE_CODEVIEW);
+    assert_eq!(codeview.data().len(), 0x18);
+    assert!(codeview.data().starts_with(b"RSDS"));
+    assert_eq!(container.number_of_symbols(), 2);
+    assert_eq!(container.symbols().len(), SYMBOLS);
+
+    assert_eq!(pe.data().len(), original.len() - SYMBOLS);
+    assert!(pe
+        .file_header()
+        .characteristics()
+        .into_value()
+        .debug_stripped());
+    assert_eq!(pe.file_header().number_of_symbols().into_value(), 0);
+    let entries = pe.debug_directory().unwrap();
+    let types: Vec<_> = entries.iter().map(|entry| entry.debug_type()).collect();
+    assert_eq!(
+        types,
+        [
+            IMAGE_DEBUG_TYPE_EX_DLLCHARACTERISTICS,
+            IMAGE_DEBUG_TYPE_MISC
+        ]
+    );
+    // The MISC record naming the sidecar took the place of the CodeView record
+    let misc = pe.debug_data(&entries[1]).unwrap();
+    assert_eq!(misc[..4], IMAGE_DEBUG_MISC_EXENAME.to_le_bytes());
+    assert_eq!(&misc[12..20], b"app.dbg\0");
+    assert_eq!(entries[1].address_of_raw_data(), 0x2040);
+
+    let bytes = container.to_bytes();
+    assert!(bytes.starts_with(&DEBUG_CONTAINER_MAGIC));
+    let parsed = DebugContainer::parse(&bytes).unwrap();
+    assert!(parsed.matches(&pe));
+    assert_eq!(parsed.payloads()[0].data(), codeview.data());
+    assert_eq!(parsed.symbols(), container.symbols());
+    assert_eq!(parsed.to_bytes(), bytes);
+
+    let mut bare = PeFixture::pe32()
+        .section(".text", 0x6000_0020, &[0xC3])
+        .parse()
+        .unwrap();
+    assert!(bare.split_debug("app.dbg", false).is_err());
+}
+
+#[test]
+fn codeview_entries_can_stay_in_the_image() {
+    let mut pe = PortExe::from_bytes(image(PeFixture::pe32())).unwrap();
+    let container = pe.split_debug("app.dbg", true).unwrap();
+    assert!(container.payloads().is_empty());
+    assert_eq!(container.number_of_symbols(), 2);
+    let entries = pe.debug_directory().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert!(pe.debug_data(&entries[0]).unwrap().starts_with(b"RSDS"));
+}
+
+#[test]
+fn malformed_debug_containers_are_rejected() {
+    let mut pe = PortExe::from_bytes(image(PeFixture::pe32())).unwrap();
+    let bytes = pe.split_debug("app.dbg", false).unwrap().to_bytes();
+    assert!(DebugContainer::parse(b"PEXPDBG").is_err());
+    assert!(DebugContainer::parse(&bytes[1..]).is_err());
+    assert!(DebugContainer::parse(&bytes[..bytes.len() - 1]).is_err());
+    let mut version = bytes.clone();
+    version[8] = 2;
+    assert!(DebugContainer::parse(&version).is_err());
+
+    let signed = image(PeFixture::pe32().directory(SECURITY, 0x700, 8));
+    let mut pe = PortExe::from_bytes(signed).unwrap();
+    assert!(pe.split_debug("app.dbg", false).is_err());
+}
@@ -0,0 +1,186 @@
+use pexp::certificate::{WIN_CERT_REVISION_2_0, WIN_CERT_TYPE_PKCS_SIGNED_DATA};
+use pexp::debug::{IMAGE_DEBUG_TYPE_CODEVIEW, IMAGE_DEBUG_TYPE_POGO, IMAGE_DEBUG_TYPE_REPRO};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+use pexp::timeline::{format_timestamp, timeline, TimelineEntry, TimelineFlag, TimestampSource};
+
+const DEBUG: usize = 6;
+const SECURITY: usize = 4;
+const DAY: u32 = 86_400;
+/// 2020-07-04 04:05:20 UTC
+const BUILT: u32 = 1_593_835_520;
+
+/// DER element with a short-form length
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut element = vec![tag, content.len() as u8];
+    element.extend_from_slice(content);
+    element
+}
+
+/// Signature holding a `signingTime` attribute of UTCTime `signed` and, if given, a
+/// timestamp token of GeneralizedTime `countersigned`
+fn signature(signed: &str, countersigned: Option<&str>) -> Vec<u8> {
+    let oid = der(
+        0x06,
+        &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x09, 0x05],
+    );
+    let attribute = der(
+        0x30,
+        &[oid, der(0x31, &der(0x17, signed.as_bytes()))].concat(),
+    );
+    let mut content = der(0xA0, &attribute);
+    if let Some(time) = countersigned {
+        let info = [
+            der(0x02, &[1]),
+            der(0x06, &[0x2A, 0x03]),
+            der(0x30, &[]),
+            der(0x02, &[5]),
+            der(0x18, time.as_bytes()),
+        ]
+        .concat();
+        content.extend_from_slice(&der(0x04, &der(0x30, &info)));
+    }
+    der(0x30, &content)
+}
+
+/// Image built at `header` with debug entries of each `(type, timestamp)`, signed with
+/// `signature` if given
+fn image(header: u32, debug: &[(u32, u32)], signature: Option<Vec<u8>>) -> PortExe {
+    let mut table = Vec::new();
+    for &(debug_type, time) in debug {
+        let mut entry = [0u8; 28];
+        entry[4..8].copy_from_slice(&time.to_le_bytes());
+        entry[12..16].copy_from_slice(&debug_type.to_le_bytes());
+        table.extend_from_slice(&entry);
+    }
+    let mut fixture = PeFixture::pe32()
+        .time_date_stamp(header)
+        .section(".text", 0x6000_0020, &[0xC3])
+        .section(".rdata", 0x4000_0040, &[0; 0x40])
+        .directory(DEBUG, 0x2000, table.len() as u32);
+    if let Some(signature) = &signature {
+        fixture = fixture.directory(SECURITY, 0x600, 8 + signature.len() as u32);
+    }
+    let mut data = fixture.build();
+    data[0x400..0x400 + table.len()].copy_from_slice(&table);
+    if let Some(signature) = signature {
+        data.resize(0x600, 0);
+        data.extend_from_slice(&(8 + signature.len() as u32).to_le_bytes());
+        data.extend_from_slice(&WIN_CERT_REVISION_2_0.to_le_bytes());
+        data.extend_from_slice(&WIN_CERT_TYPE_PKCS_SIGNED_DATA.to_le_bytes());
+        data.extend_from_slice(&signature);
+    }
+    PortExe::from_bytes(data).unwrap()
+}
+
+fn flags(pe: &PortExe) -> Vec<TimelineFlag> {
+    pe.timeline_entry("a.exe").unwrap().flags().to_vec()
+}
+
+#[test]
+fn timestamps_are_formatted_as_utc() {
+    assert_eq!(format_timestamp(0), "1970-01-01 00:00:00");
+    assert_eq!(format_timestamp(BUILT as i64), "2020-07-04 04:05:20");
+    assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00:00");
+    assert_eq!(format_timestamp(-1), "1969-12-31 23:59:59");
+}
+
+#[test]
+fn build_and_signing_times_are_collected() {
+    // The POGO entry's zero timestamp is left out
+    let pe = image(
+        BUILT,
+        &[
+            (IMAGE_DEBUG_TYPE_CODEVIEW, BUILT),
+            (IMAGE_DEBUG_TYPE_POGO, 0),
+        ],
+        Some(signature("200705000000Z", Some("20200706000000.5Z"))),
+    );
+    let timestamps: Vec<_> = pe
+        .timestamps()
+        .unwrap()
+        .iter()
+        .map(|timestamp| (timestamp.source(), timestamp.seconds()))
+        .collect();
+    assert_eq!(
+        timestamps,
+        [
+            (TimestampSource::FileHeader, BUILT as i64),
+            (
+                TimestampSource::Debug(IMAGE_DEBUG_TYPE_CODEVIEW),
+                BUILT as i64
+            ),
+            (TimestampSource::SigningTime, 1_593_907_200),
+            (TimestampSource::TimestampToken, 1_593_993_600),
+        ]
+    );
+
+    let entry = pe.timeline_entry("a.exe").unwrap();
+    assert_eq!(entry.name(), "a.exe");
+    assert_eq!(entry.build_time(), Some(BUILT as i64));
+    // The countersignature is trusted over the signer's clock
+    assert_eq!(entry.signing_time(), Some(1_593_993_600));
+    assert!(entry.flags().is_empty());
+}
+
+#[test]
+fn suspicious_timestamps_are_flagged() {
+    // The header of a reproducible build holds a hash
+    let reproducible = image(
+        0xDEAD_BEEF,
+        &[
+            (IMAGE_DEBUG_TYPE_CODEVIEW, 0xDEAD_BEEF),
+            (IMAGE_DEBUG_TYPE_REPRO, 0),
+        ],
+        None,
+    );
+    assert_eq!(flags(&reproducible), [TimelineFlag::Reproducible]);
+    assert_eq!(reproducible.timeline_entry("a").unwrap().build_time(), None);
+
+    let missing = image(0, &[(IMAGE_DEBUG_TYPE_CODEVIEW, BUILT)], None);
+    assert_eq!(flags(&missing), [TimelineFlag::NoTimestamp]);
+    assert_eq!(
+        missing.timeline_entry("a").unwrap().build_time(),
+        Some(BUILT as i64)
+    );
+
+    assert_eq!(flags(&image(100_000, &[], None)), [TimelineFlag::BeforePe]);
+    let skewed = image(BUILT, &[(IMAGE_DEBUG_TYPE_CODEVIEW, BUILT + 2 * DAY)], None);
+    assert_eq!(flags(&skewed), [TimelineFlag::Inconsistent]);
+    let early = image(BUILT, &[], Some(signature("200101000000Z", None)));
+    assert_eq!(flags(&early), [TimelineFlag::SignedBeforeBuild]);
+}
+
+#[test]
+fn corpora_are_ordered_and_outliers_flagged() {
+    let entry = |name: &str, time: u32| -> TimelineEntry {
+        image(time, &[], None).timeline_entry(name).unwrap()
+    };
+    let entries = vec![
+        entry("future", BUILT + 20 * DAY),
+        entry("c", BUILT + 2 * DAY),
+        entry("unknown", 0),
+        entry("a", BUILT),
+        entry("old", BUILT - 3650 * DAY),
+        entry("d", BUILT + 3 * DAY),
+        entry("b", BUILT + DAY),
+    ];
+    let now = (BUILT + 10 * DAY) as i64;
+    let summary: Vec<_> = timeline(entries, now)
+        .iter()
+        .map(|entry| (entry.name().to_string(), entry.flags().to_vec()))
+        .collect();
+    let entry = |name: &str, flags: &[TimelineFlag]| (name.to_string(), flags.to_vec());
+    assert_eq!(
+        summary,
+        [
+            entry("old", &[TimelineFlag::Outlier]),
+            entry("a", &[]),
+            entry("b", &[]),
+            entry("c", &[]),
+            entry("d", &[]),
+            entry("future", &[TimelineFlag::InFuture]),
+            entry("unknown", &[TimelineFlag::NoTimestamp]),
+        ]
+    );
+}
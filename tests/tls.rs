@@ -0,0 +1,145 @@
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+
+const PE32_BASE: u64 = 0x40_0000;
+const PE64_BASE: u64 = 0x1_4000_0000;
+/// File offset of the TLS entry of the data directories of a PE32 image
+const PE32_TLS_DIRECTORY: usize = 0x80 + 4 + 20 + 96 + 9 * 8;
+/// TLS directory offset within `.tls`, past the template
+const DIRECTORY: usize = 0x100;
+
+/// Image whose `.tls` section, at RVA 0x1000, starts with `template` and holds a TLS
+/// directory at 0x1100 describing `start..end` with `zero_fill` bytes of zero fill.
+fn image(pe64: bool, template: &[u8], start: u32, end: u32, zero_fill: u32, bss: u32) -> PortExe {
+    with_callbacks(pe64, template, start, end, zero_fill, bss, &[], 0)
+}
+
+/// Image like [`image`] whose directory also points at `callbacks` at RVA 0x1180 and
+/// sets `characteristics`
+#[allow(clippy::too_many_arguments)]
+fn with_callbacks(
+    pe64: bool,
+    template: &[u8],
+    start: u32,
+    end: u32,
+    zero_fill: u32,
+    bss: u32,
+    callbacks: &[u32],
+    characteristics: u32,
+) -> PortExe {
+    let base = if pe64 { PE64_BASE } else { PE32_BASE };
+    let mut data = template.to_vec();
+    data.resize(DIRECTORY, 0);
+    for address in [start, end, 0x1170, 0x1180] {
+        let address = base + address as u64;
+        if pe64 {
+            data.extend_from_slice(&address.to_le_bytes());
+        } else {
+            data.extend_from_slice(&(address as u32).to_le_bytes());
+        }
+    }
+    data.extend_from_slice(&zero_fill.to_le_bytes());
+    data.extend_from_slice(&characteristics.to_le_bytes());
+    data.resize(0x180, 0);
+    for &callback in callbacks.iter().chain(&[0]) {
+        let address = if callback == 0 {
+            0
+        } else {
+            base + callback as u64
+        };
+        if pe64 {
+            data.extend_from_slice(&address.to_le_bytes());
+        } else {
+            data.extend_from_slice(&(address as u32).to_le_bytes());
+        }
+    }
+    let mut image = if pe64 {
+        PeFixture::pe64()
+    } else {
+        PeFixture::pe32()
+    }
+    .characteristics(0x0102)
+    .dll_characteristics(0)
+    .section_with_bss(".tls", 0xC000_0040, &data, bss)
+    .build();
+    let entry = if pe64 {
+        PE32_TLS_DIRECTORY + 16
+    } else {
+        PE32_TLS_DIRECTORY
+    };
+    image[entry..entry + 4].copy_from_slice(&(0x1000 + DIRECTORY as u32).to_le_bytes());
+    image[entry + 4..entry + 8].copy_from_slice(&24u32.to_le_bytes());
+    PortExe::from_bytes(image).unwrap()
+}
+
+#[test]
+fn template_bytes_are_extracted() {
+    let template: Vec<u8> = (0..=255u8).collect();
+    for pe64 in [false, true] {
+        let pe = image(pe64, &template[..0x40], 0x1000, 0x1040, 0x10, 0);
+        let tls = pe.tls_template().unwrap().unwrap();
+        let base = if pe64 { PE64_BASE } else { PE32_BASE };
+        assert_eq!(tls.start_address(), base + 0x1000);
+        assert_eq!(tls.end_address(), base + 0x1040);
+        assert_eq!(tls.size(), 0x40);
+        assert_eq!(tls.size_of_zero_fill(), 0x10);
+        assert_eq!(tls.section().unwrap().to_string_lossy(), ".tls");
+        assert_eq!(tls.offset(), Some(0x200));
+        assert_eq!(tls.data(), &template[..0x40]);
+        assert!(!tls.is_truncated());
+        assert!((tls.entropy() - 6.0).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn template_past_the_raw_data_is_truncated() {
+    // The raw data of .tls ends at 0x1200; the rest of the template is zero-filled memory
+    let pe = image(false, &[], 0x11F0, 0x1300, 0, 0x400);
+    let tls = pe.tls_template().unwrap().unwrap();
+    assert_eq!(tls.size(), 0x110);
+    assert_eq!(tls.data().len(), 0x10);
+    assert!(tls.is_truncated());
+    assert_eq!(tls.entropy(), 0.0);
+}
+
+#[test]
+fn reversed_template_range_is_rejected() {
+    let pe = image(false, &[], 0x1040, 0x1000, 0, 0);
+    assert!(pe.tls_template().is_err());
+
+    let empty = image(false, &[], 0, 0, 0, 0);
+    let tls = empty.tls_template().unwrap().unwrap();
+    assert_eq!(tls.size(), 0);
+    assert!(tls.data().is_empty());
+}
+
+#[test]
+fn directory_fields_and_callbacks_are_read() {
+    for pe64 in [false, true] {
+        let base = if pe64 { PE64_BASE } else { PE32_BASE };
+        let pe = with_callbacks(
+            pe64,
+            &[1; 8],
+            0x1000,
+            0x1008,
+            0,
+            0,
+            &[0x1010, 0x1020],
+            0x0030_0000,
+        );
+        let directory = pe.tls_directory().unwrap().unwrap();
+        assert_eq!(directory.start_address_of_raw_data(), base + 0x1000);
+        assert_eq!(directory.end_address_of_raw_data(), base + 0x1008);
+        assert_eq!(directory.address_of_index(), base + 0x1170);
+        assert_eq!(directory.address_of_callbacks(), base + 0x1180);
+        assert_eq!(directory.alignment(), Some(4));
+        let callbacks: Vec<u64> = directory.callbacks(&pe).collect();
+        assert_eq!(callbacks, [base + 0x1010, base + 0x1020]);
+        assert_eq!(pe.tls_callbacks(), callbacks);
+    }
+
+    let pe = image(true, &[], 0, 0, 0, 0);
+    let directory = pe.tls_directory().unwrap().unwrap();
+    assert_eq!(directory.alignment(), None);
+    assert_eq!(directory.callbacks(&pe).count(), 0);
+}
@@ -0,0 +1,109 @@
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+use pexp::truncation::TruncatedPart;
+use pexp::validation::{Severity, ValidationMode};
+use pexp::PeString;
+
+/// PE32 image with a 0x200-byte `.text` at file offset 0x200 and a 0x200-byte `.data`
+/// at 0x400
+fn image() -> Vec<u8> {
+    PeFixture::pe32()
+        .dll_characteristics(0)
+        .section(".text", 0x6000_0020, &[0xC3; 0x200])
+        .section(".data", 0xC000_0040, &[1; 0x200])
+        .build()
+}
+
+#[test]
+fn complete_files_are_not_truncated() {
+    let pe = PortExe::from_bytes(image()).unwrap();
+    assert_eq!(pe.truncation(), None);
+}
+
+#[test]
+fn truncated_sections_are_reported() {
+    let mut data = image();
+    assert_eq!(data.len(), 0x600);
+    data.truncate(0x300);
+    let pe = PortExe::from_bytes(data).unwrap();
+    let truncation = pe.truncation().unwrap();
+    assert_eq!(truncation.file_size(), 0x300);
+    assert_eq!(truncation.expected_size(), 0x600);
+    assert_eq!(truncation.missing(), 0x300);
+    assert_eq!(truncation.inferred_size_of_headers(), 0x200);
+    assert_eq!(truncation.inferred_size_of_image(), 0x3000);
+
+    let structures = truncation.structures();
+    assert_eq!(structures.len(), 2);
+    assert_eq!(
+        structures[0].part(),
+        &TruncatedPart::Section(PeString::from(".text"))
+    );
+    assert_eq!(structures[0].range(), 0x200..0x400);
+    assert_eq!(structures[0].present(), 0x100);
+    assert_eq!(structures[0].missing(), 0x100);
+    assert_eq!(structures[1].part().to_string(), "section .data");
+    assert_eq!(structures[1].present(), 0);
+}
+
+#[test]
+fn truncated_directories_are_reported() {
+    let mut data = image();
+    // Import directory in the middle of .data
+    let imports = 0x80 + 4 + 20 + 96 + 8;
+    data[imports..imports + 8].copy_from_slice(&[0x80, 0x20, 0, 0, 0x40, 0, 0, 0]);
+    data.truncate(0x490);
+    let pe = PortExe::from_bytes(data).unwrap();
+    let truncation = pe.truncation().unwrap();
+    let directory = truncation
+        .structures()
+        .iter()
+        .find(|structure| structure.part().to_string() == "Import directory")
+        .unwrap();
+    assert_eq!(directory.range(), 0x480..0x4C0);
+    assert_eq!(directory.present(), 0x10);
+}
+
+#[test]
+fn validation_reports_the_missing_bytes() {
+    let mut data = image();
+    data.truncate(0x300);
+    let pe = PortExe::from_bytes(data).unwrap();
+
+    let strict = pe.validate(ValidationMode::Strict);
+    let truncated: Vec<_> = strict
+        .iter()
+        .filter(|finding| {
+            finding.catalog_message().map(|message| message.key()) == Some("file-truncated")
+        })
+        .collect();
+    assert_eq!(truncated.len(), 1);
+    assert_eq!(truncated[0].severity(), Severity::Error);
+    assert_eq!(
+        truncated[0].message(),
+        "file is 0x300 bytes, 0x300 short of the 0x600 its headers describe"
+    );
+
+    let permissive = pe.validate(ValidationMode::Permissive);
+    assert!(permissive.iter().any(|finding| finding.message()
+        == "section .text at 0x200..0x400 is cut off after 0x100 of its 0x200 bytes"));
+}
+
+#[test]
+fn truncated_headers_name_the_structure() {
+    let mut data = image();
+    data.truncate(0x190);
+    let error = PortExe::from_bytes(data).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "file ends at 0x190, inside the section table at 0x178..0x1c8"
+    );
+
+    let mut data = image();
+    data.truncate(0x90);
+    let error = PortExe::from_bytes(data).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "file ends at 0x90, inside the file header at 0x84..0x98"
+    );
+}
@@ -0,0 +1,361 @@
+mod common;
+
+use common::{put32, DATA_DIRECTORIES};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+use pexp::validation::{LegacyQuirk, Severity, ValidationMode};
+
+/// PE32 image for `machine` with the Architecture and GlobalPtr directories set to
+/// `architecture` and `global_ptr`.
+fn image(machine: u16, architecture: (u32, u32), global_ptr: (u32, u32)) -> PortExe {
+    let mut data = PeFixture::pe32()
+        .machine(machine)
+        .dll_characteristics(0)
+        .section(".data", 0xC000_0040, &[0; 0x20])
+        .build();
+    for (index, (rva, size)) in [(7, architecture), (8, global_ptr)] {
+        let entry = DATA_DIRECTORIES + index * 8;
+        data[entry..entry + 4].copy_from_slice(&rva.to_le_bytes());
+        data[entry + 4..entry + 8].copy_from_slice(&size.to_le_bytes());
+    }
+    PortExe::from_bytes(data).unwrap()
+}
+
+fn warnings(pe: &PortExe) -> Vec<String> {
+    pe.validate(ValidationMode::Strict)
+        .iter()
+        .filter(|finding| finding.severity() == Severity::Warning)
+        .map(|finding| finding.message().to_string())
+        .collect()
+}
+
+#[test]
+fn global_ptr_is_decoded_as_an_address() {
+    let pe = image(0x200, (0, 0), (0x1010, 0));
+    let header = pe.optional_header().unwrap();
+    assert_eq!(header.global_ptr(), Some(0x40_1010));
+    assert_eq!(header.architecture_directory(), None);
+    assert!(warnings(&pe).is_empty());
+
+    let sized = image(0x200, (0, 0), (0x1010, 8));
+    assert_eq!(
+        warnings(&sized),
+        ["GlobalPtr directory has a Size of 0x8 instead of zero"]
+    );
+    assert_eq!(
+        image(0x14C, (0, 0), (0, 0))
+            .optional_header()
+            .unwrap()
+            .global_ptr(),
+        None
+    );
+}
+
+#[test]
+fn architecture_directory_is_only_expected_on_alpha() {
+    let alpha = image(0x184, (0x1000, 0x10), (0, 0));
+    assert_eq!(
+        alpha.optional_header().unwrap().architecture_directory(),
+        Some((0x1000, 0x10))
+    );
+    assert!(warnings(&alpha).is_empty());
+
+    let x86 = image(0x14C, (0x1000, 0x10), (0, 0));
+    let warnings = warnings(&x86);
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].starts_with("reserved Architecture directory is set to 0x1000+0x10"));
+}
+
+/// Image importing ordinals 1 and 2 from `KERNEL32.dll`, ordinal 23 from `WS2_32.dll`
+/// and `MessageBoxW` and ordinal 5 from `USER32.dll`.
+fn ordinal_imports_image() -> PortExe {
+    let rva = 0x1000u32;
+    let mut idata = vec![0u8; 0x100];
+    let put = |idata: &mut Vec<u8>, offset: usize, value: u32| {
+        idata[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    };
+    let dlls: [(u32, u32, &[u32]); 3] = [
+        (0x60, 0xC0, &[0x8000_0001, 0x8000_0002]),
+        (0x70, 0xD0, &[0x8000_0017]),
+        (0x80, 0xE0, &[rva + 0xA0, 0x8000_0005]),
+    ];
+    for (index, (table, name, thunks)) in dlls.iter().enumerate() {
+        put(&mut idata, index * 20, rva + table);
+        put(&mut idata, index * 20 + 12, rva + name);
+        put(&mut idata, index * 20 + 16, rva + table);
+        for (slot, &thunk) in thunks.iter().enumerate() {
+            put(&mut idata, *table as usize + slot * 4, thunk);
+        }
+    }
+    idata[0xA2..0xAD].copy_from_slice(b"MessageBoxW");
+    idata[0xC0..0xCC].copy_from_slice(b"KERNEL32.dll");
+    idata[0xD0..0xDA].copy_from_slice(b"WS2_32.dll");
+    idata[0xE0..0xEA].copy_from_slice(b"USER32.dll");
+    let mut data = PeFixture::pe32()
+        .dll_characteristics(0)
+        .section(".idata", 0xC000_0040, &idata)
+        .build();
+    let entry = DATA_DIRECTORIES + 8;
+    data[entry..entry + 4].copy_from_slice(&rva.to_le_bytes());
+    data[entry + 4..entry + 8].copy_from_slice(&80u32.to_le_bytes());
+    PortExe::from_bytes(data).unwrap()
+}
+
+#[test]
+fn system_dlls_imported_only_by_ordinal_are_flagged() {
+    let pe = ordinal_imports_image();
+    let coverage: Vec<(String, usize, usize, bool)> = pe
+        .ordinal_coverage()
+        .unwrap()
+        .iter()
+        .map(|dll| {
+            (
+                dll.dll().to_string(),
+                dll.by_name(),
+                dll.by_ordinal(),
+                dll.is_suspicious(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        coverage,
+        [
+            ("KERNEL32.dll".to_string(), 0, 2, true),
+            ("WS2_32.dll".to_string(), 0, 1, false),
+            ("USER32.dll".to_string(), 1, 1, false),
+        ]
+    );
+    assert_eq!(
+        warnings(&pe),
+        ["all 2 imports from system DLL KERNEL32.dll are by ordinal"]
+    );
+}
+
+/// Image for `machine` with PE32+ magic if `pe64`, and the given file header
+/// characteristics and DLL characteristics
+fn bitness_image(
+    pe64: bool,
+    machine: u16,
+    characteristics: u16,
+    dll_characteristics: u16,
+) -> Vec<u8> {
+    if pe64 {
+        PeFixture::pe64()
+    } else {
+        PeFixture::pe32()
+    }
+    .machine(machine)
+    .characteristics(characteristics)
+    .dll_characteristics(dll_characteristics)
+    .section(".data", 0xC000_0040, &[0; 0x20])
+    .build()
+}
+
+fn findings(data: Vec<u8>) -> Vec<(Severity, String)> {
+    PortExe::from_bytes(data)
+        .unwrap()
+        .validate(ValidationMode::Strict)
+        .iter()
+        .map(|finding| (finding.severity(), finding.message().to_string()))
+        .collect()
+}
+
+#[test]
+fn optional_header_magic_must_match_the_machine() {
+    assert_eq!(
+        findings(bitness_image(false, 0x8664, 0x0122, 0)),
+        [(
+            Severity::Error,
+            "X64 image has PE32 magic, but the machine requires PE32+".to_string()
+        )]
+    );
+    assert_eq!(
+        findings(bitness_image(true, 0x14C, 0x0122, 0)),
+        [(
+            Severity::Error,
+            "Intel386 image has PE32+ magic, but the machine requires PE32".to_string()
+        )]
+    );
+    assert!(findings(bitness_image(true, 0xAA64, 0x0022, 0x0020)).is_empty());
+    assert_eq!(
+        findings(bitness_image(false, 0xA641, 0x0122, 0)),
+        [(
+            Severity::Error,
+            "Arm64Ec image has PE32 magic, but the machine requires PE32+".to_string()
+        )]
+    );
+    assert!(findings(bitness_image(true, 0xA64E, 0x0022, 0x0020)).is_empty());
+    assert!(findings(bitness_image(false, 0x14C, 0x0122, 0)).is_empty());
+}
+
+#[test]
+fn image_base_and_large_address_flags_follow_the_bitness() {
+    // The optional header starts at 0x98; ImageBase is at offset 24 in PE32+
+    let mut low_base = bitness_image(true, 0x8664, 0x0002, 0);
+    low_base[0x98 + 24..0x98 + 32].copy_from_slice(&0x40_0000u64.to_le_bytes());
+    assert_eq!(
+        findings(low_base),
+        [
+            (
+                Severity::Info,
+                "ImageBase 0x400000 of a PE32+ image is below 4 GiB, where 64-bit linkers do not place images by default".to_string()
+            ),
+            (
+                Severity::Warning,
+                "PE32+ image is not LARGE_ADDRESS_AWARE, which confines it to the low 2 GiB"
+                    .to_string()
+            ),
+        ]
+    );
+    assert_eq!(
+        findings(bitness_image(false, 0x14C, 0x0122, 0x0020)),
+        [(
+            Severity::Warning,
+            "HIGH_ENTROPY_VA is set on a PE32 image, which has no 64-bit address space".to_string()
+        )]
+    );
+}
+
+#[test]
+fn data_directories_past_the_sixteenth_are_kept_and_flagged() {
+    // Grow the PE32 optional header by two directory entries, moving the section table
+    // at 0x178 into the header padding after it
+    let mut data = bitness_image(false, 0x14C, 0x0122, 0);
+    data.copy_within(0x178..0x1A0, 0x188);
+    data[0x178..0x180].copy_from_slice(&[0x34, 0x12, 0, 0, 8, 0, 0, 0]);
+    data[0x180..0x188].fill(0);
+    data[0x94..0x96].copy_from_slice(&240u16.to_le_bytes());
+    data[DATA_DIRECTORIES - 4..DATA_DIRECTORIES].copy_from_slice(&18u32.to_le_bytes());
+
+    let pe = PortExe::from_bytes(data.clone()).unwrap();
+    let header = pe.optional_header().unwrap();
+    assert_eq!(header.number_of_rva_and_sizes(), 18);
+    let directories = header.data_directories();
+    assert_eq!(directories.len(), 18);
+    assert_eq!(directories[16].index(), 16);
+    assert_eq!(directories[16].virtual_address().into_value(), 0x1234);
+    assert_eq!(directories[16].size().offset(), 0x17C);
+    assert_eq!(pe.section_headers().len(), 1);
+
+    assert_eq!(
+        findings(data.clone()),
+        [
+            (
+                Severity::Warning,
+                "NumberOfRvaAndSizes is 18; the loader ignores entries past the first 16"
+                    .to_string()
+            ),
+            (
+                Severity::Warning,
+                "extra data directory 16 is set to 0x1234+0x8".to_string()
+            ),
+        ]
+    );
+
+    // Entries that SizeOfOptionalHeader has no room for are not read
+    data[0x94..0x96].copy_from_slice(&224u16.to_le_bytes());
+    let pe = PortExe::from_bytes(data).unwrap();
+    assert_eq!(pe.optional_header().unwrap().data_directories().len(), 16);
+}
+
+/// Offset of the optional header of a PE32 image
+const OPTIONAL_HEADER: usize = 0x80 + 4 + 20;
+
+fn put16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Severity and quirk of each finding of `data` in `mode`
+fn quirks(data: &[u8], mode: ValidationMode) -> Vec<(Severity, Option<LegacyQuirk>)> {
+    PortExe::from_bytes(data.to_vec())
+        .unwrap()
+        .validate(mode)
+        .iter()
+        .map(|finding| (finding.severity(), finding.quirk()))
+        .collect()
+}
+
+/// GUI image for `machine` and `subsystem` with subsystem version 2.11, as Windows CE
+/// 2.11 stamps
+fn ce_image(machine: u16, subsystem: u16) -> Vec<u8> {
+    let mut data = PeFixture::pe32()
+        .machine(machine)
+        .subsystem(subsystem)
+        .dll_characteristics(0)
+        .section(".data", 0xC000_0040, &[0; 0x20])
+        .build();
+    put16(&mut data, OPTIONAL_HEADER + 48, 2);
+    put16(&mut data, OPTIONAL_HEADER + 50, 11);
+    data
+}
+
+#[test]
+fn windows_ce_conventions_are_informational_when_permissive() {
+    let ce = ce_image(0x0169, 2);
+    let expected = |severity| {
+        vec![
+            (severity, Some(LegacyQuirk::CeMachineWithDesktopSubsystem)),
+            (severity, Some(LegacyQuirk::CeSubsystemVersion)),
+        ]
+    };
+    assert_eq!(
+        quirks(&ce, ValidationMode::Strict),
+        expected(Severity::Warning)
+    );
+    assert_eq!(
+        quirks(&ce, ValidationMode::Permissive),
+        expected(Severity::Info)
+    );
+
+    // The same version on a desktop machine is below what the loader runs
+    let desktop = ce_image(0x014C, 2);
+    for mode in [ValidationMode::Strict, ValidationMode::Permissive] {
+        assert_eq!(quirks(&desktop, mode), [(Severity::Error, None)]);
+    }
+    // A CE subsystem with a version no CE release used
+    let mut unknown = ce_image(0x0169, 9);
+    put16(&mut unknown, OPTIONAL_HEADER + 48, 12);
+    assert_eq!(
+        quirks(&unknown, ValidationMode::Permissive),
+        [(Severity::Warning, None)]
+    );
+}
+
+#[test]
+fn xbox_layouts_are_informational_when_permissive() {
+    let layout = |subsystem: u16| {
+        let mut data = PeFixture::pe32()
+            .subsystem(subsystem)
+            .dll_characteristics(0)
+            .section(".data", 0xC000_0040, &[0; 0x20])
+            .build();
+        // FileAlignment of 32 bytes, SizeOfImage not a multiple of SectionAlignment
+        put32(&mut data, OPTIONAL_HEADER + 36, 0x20);
+        put32(&mut data, OPTIONAL_HEADER + 56, 0x2010);
+        // PointerToRawData of .data off the file alignment
+        put32(&mut data, OPTIONAL_HEADER + 224 + 20, 0x1F0);
+        data
+    };
+    let xbox = layout(14);
+    let expected = |severity| {
+        vec![
+            (severity, Some(LegacyQuirk::XboxFileAlignment)),
+            (severity, Some(LegacyQuirk::XboxImageSize)),
+            (severity, Some(LegacyQuirk::XboxUnalignedRawData)),
+        ]
+    };
+    assert_eq!(
+        quirks(&xbox, ValidationMode::Strict),
+        expected(Severity::Warning)
+    );
+    assert_eq!(
+        quirks(&xbox, ValidationMode::Permissive),
+        expected(Severity::Info)
+    );
+
+    let desktop = layout(3);
+    assert_eq!(
+        quirks(&desktop, ValidationMode::Permissive),
+        [(Severity::Warning, None); 3]
+    );
+}
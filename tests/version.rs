@@ -0,0 +1,94 @@
+mod common;
+
+use common::{utf16z, version_node};
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+use pexp::version::{format_version, VersionInfo, VS_FFI_SIGNATURE};
+
+/// Offset of `VS_FIXEDFILEINFO` in the resource from [`version_resource`], after the
+/// root node's header and `VS_VERSION_INFO` key
+const FIXED_FILE_INFO: usize = 40;
+
+/// `VS_VERSIONINFO` of file version 1.2.3.4 and product version 5.6.0.0, with a US
+/// English string table and translation
+fn version_resource() -> Vec<u8> {
+    let mut fixed = vec![0u8; 52];
+    for (offset, value) in [
+        (0, VS_FFI_SIGNATURE),
+        (4, 0x0001_0000),
+        (8, 0x0001_0002),
+        (12, 0x0003_0004),
+        (16, 0x0005_0006),
+    ] {
+        fixed[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    let strings: Vec<Vec<u8>> = [("CompanyName", "Contoso"), ("ProductName", "Widget")]
+        .iter()
+        .map(|(key, value)| version_node(key, &utf16z(value), true, &[]))
+        .collect();
+    let table = version_node("040904b0", &[], true, &strings);
+    let string_info = version_node("StringFileInfo", &[], true, &[table]);
+    let translation = version_node("Translation", &[0x09, 0x04, 0xB0, 0x04], false, &[]);
+    let var_info = version_node("VarFileInfo", &[], true, &[translation]);
+    version_node("VS_VERSION_INFO", &fixed, false, &[string_info, var_info])
+}
+
+fn assert_complete(info: &VersionInfo) {
+    assert_eq!(info.file_version(), Some([1, 2, 3, 4]));
+    assert_eq!(info.product_version(), Some([5, 6, 0, 0]));
+    assert_eq!(info.strings().len(), 2);
+    assert_eq!(info.strings()[0].table(), "040904b0");
+    assert_eq!(info.string("CompanyName"), Some("Contoso"));
+    assert_eq!(info.string("ProductName"), Some("Widget"));
+    assert_eq!(info.translations(), [(0x0409, 0x04B0)]);
+}
+
+#[test]
+fn version_resource_is_parsed() {
+    let version = version_resource();
+    let info = VersionInfo::parse(&version).unwrap();
+    assert_complete(&info);
+    assert_eq!(format_version(info.file_version().unwrap()), "1.2.3.4");
+
+    let pe = PortExe::from_bytes(
+        PeFixture::pe32()
+            .section(".text", 0x6000_0020, &[0xC3])
+            .resource(16, 1, 0x409, &version)
+            .build(),
+    )
+    .unwrap();
+    assert_eq!(pe.version_info().unwrap(), Some(info));
+    assert!(VersionInfo::parse(&version_node("VS_VERSION", &[], false, &[])).is_err());
+}
+
+#[test]
+fn truncated_version_resources_lose_what_is_cut_off() {
+    let version = version_resource();
+    for length in 0..version.len() {
+        // Only the root node's key is required
+        let info = VersionInfo::parse(&version[..length]);
+        assert_eq!(info.is_ok(), length >= FIXED_FILE_INFO - 2, "{}", length);
+    }
+    let info = VersionInfo::parse(&version[..FIXED_FILE_INFO + 51]).unwrap();
+    assert_eq!(info, VersionInfo::default());
+    let info = VersionInfo::parse(&version[..FIXED_FILE_INFO + 52]).unwrap();
+    assert_eq!(info.file_version(), Some([1, 2, 3, 4]));
+    assert!(info.strings().is_empty());
+    let info = VersionInfo::parse(&version[..version.len() - 4]).unwrap();
+    assert_eq!(info.strings().len(), 2);
+    assert!(info.translations().is_empty());
+}
+
+#[test]
+fn oversized_version_lengths_stop_at_the_end_of_the_data() {
+    let mut version = version_resource();
+    version[0..2].copy_from_slice(&0xFFFFu16.to_le_bytes());
+    assert_complete(&VersionInfo::parse(&version).unwrap());
+
+    // A value claiming the rest of the resource leaves no room for children
+    version[2..4].copy_from_slice(&0xFFFFu16.to_le_bytes());
+    let info = VersionInfo::parse(&version).unwrap();
+    assert_eq!(info.file_version(), Some([1, 2, 3, 4]));
+    assert!(info.strings().is_empty());
+    assert!(info.translations().is_empty());
+}
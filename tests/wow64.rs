@@ -0,0 +1,152 @@
+use pexp::port_exe::PortExe;
+use pexp::testing::PeFixture;
+use pexp::wow64::{Hybrid, Platform, PlatformCompatibility, Support};
+
+/// File offset of the data directories of a PE32+ image
+const DATA_DIRECTORIES_64: usize = 0x80 + 4 + 20 + 112;
+/// Index of the load configuration directory
+const LOAD_CONFIG: usize = 10;
+
+/// Image for `machine` with the given characteristics and subsystem, whose load
+/// configuration sets the CHPE metadata pointer if `chpe`
+fn compatibility(
+    pe64: bool,
+    machine: u16,
+    characteristics: u16,
+    subsystem: u16,
+    chpe: bool,
+) -> PlatformCompatibility {
+    let mut config = vec![0u8; 0x100];
+    let size: u32 = if pe64 { 208 } else { 128 };
+    config[..4].copy_from_slice(&size.to_le_bytes());
+    let chpe_offset = if pe64 { 200 } else { 124 };
+    if chpe {
+        config[chpe_offset..chpe_offset + 4].copy_from_slice(&0x1080u32.to_le_bytes());
+    }
+    let mut data = if pe64 {
+        PeFixture::pe64()
+    } else {
+        PeFixture::pe32()
+    }
+    .machine(machine)
+    .characteristics(characteristics)
+    .subsystem(subsystem)
+    .dll_characteristics(0)
+    .section(".rdata", 0x4000_0040, &config)
+    .build();
+    let directories = if pe64 {
+        DATA_DIRECTORIES_64
+    } else {
+        DATA_DIRECTORIES_64 - 16
+    };
+    let entry = directories + LOAD_CONFIG * 8;
+    data[entry..entry + 4].copy_from_slice(&0x1000u32.to_le_bytes());
+    data[entry + 4..entry + 8].copy_from_slice(&size.to_le_bytes());
+    PortExe::from_bytes(data)
+        .unwrap()
+        .platform_compatibility()
+        .unwrap()
+        .unwrap()
+}
+
+fn support(compatibility: &PlatformCompatibility) -> Vec<(Platform, Support, Option<u64>)> {
+    compatibility
+        .platforms()
+        .iter()
+        .map(|platform| {
+            (
+                platform.platform(),
+                platform.support(),
+                platform.address_space(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn x86_images_run_everywhere_and_large_address_awareness_doubles_wow64_space() {
+    let plain = compatibility(false, 0x14C, 0x0102, 3, false);
+    assert_eq!(plain.hybrid(), None);
+    assert_eq!(
+        support(&plain),
+        [
+            (Platform::X86, Support::Native, Some(2 << 30)),
+            (Platform::X64, Support::Wow64, Some(2 << 30)),
+            (Platform::Arm64, Support::Emulated, Some(2 << 30)),
+        ]
+    );
+
+    let chpe = compatibility(false, 0x14C, 0x0122, 3, true);
+    assert_eq!(chpe.hybrid(), Some(Hybrid::Chpe));
+    assert_eq!(
+        support(&chpe),
+        [
+            (Platform::X86, Support::Native, Some(3 << 30)),
+            (Platform::X64, Support::Wow64, Some(4 << 30)),
+            (Platform::Arm64, Support::Wow64Chpe, Some(4 << 30)),
+        ]
+    );
+}
+
+#[test]
+fn x64_images_need_arm64_emulation_unless_arm64ec() {
+    let x64 = compatibility(true, 0x8664, 0x0022, 3, false);
+    assert_eq!(
+        support(&x64),
+        [
+            (Platform::X86, Support::Unsupported, None),
+            (Platform::X64, Support::Native, Some(128 << 40)),
+            (Platform::Arm64, Support::Emulated, Some(128 << 40)),
+        ]
+    );
+    assert_eq!(
+        x64.platforms()[2].note(),
+        Some("x64 emulation needs Windows 11")
+    );
+
+    let arm64ec = compatibility(true, 0x8664, 0x0022, 3, true);
+    assert_eq!(arm64ec.hybrid(), Some(Hybrid::Arm64Ec));
+    assert_eq!(
+        support(&arm64ec)
+            .iter()
+            .map(|&(_, support, _)| support)
+            .collect::<Vec<_>>(),
+        [Support::Unsupported, Support::Unsupported, Support::Arm64Ec]
+    );
+}
+
+#[test]
+fn arm64ec_and_arm64x_machines_name_the_hybrid() {
+    let arm64ec = compatibility(true, 0xA641, 0x0022, 3, false);
+    assert_eq!(arm64ec.hybrid(), Some(Hybrid::Arm64Ec));
+    assert_eq!(
+        support(&arm64ec),
+        [
+            (Platform::X86, Support::Unsupported, None),
+            (Platform::X64, Support::Unsupported, None),
+            (Platform::Arm64, Support::Arm64Ec, Some(128 << 40)),
+        ]
+    );
+
+    let arm64x = compatibility(true, 0xA64E, 0x0022, 3, false);
+    assert_eq!(arm64x.hybrid(), Some(Hybrid::Arm64X));
+    assert_eq!(
+        support(&arm64x)
+            .iter()
+            .map(|&(_, support, _)| support)
+            .collect::<Vec<_>>(),
+        [Support::Unsupported, Support::Unsupported, Support::Native]
+    );
+}
+
+#[test]
+fn drivers_only_run_natively() {
+    let driver = compatibility(false, 0x14C, 0x0102, 1, false);
+    assert_eq!(
+        support(&driver)
+            .iter()
+            .map(|&(_, support, _)| support)
+            .collect::<Vec<_>>(),
+        [Support::Native, Support::Unsupported, Support::Unsupported]
+    );
+}